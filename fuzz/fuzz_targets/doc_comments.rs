@@ -0,0 +1,50 @@
+//! Feeds arbitrary input through doc-comment extraction and the markdown
+//! helpers. Any panic is a bug: malformed comments must degrade to warnings.
+//!
+//! Run with `cargo fuzz run doc_comments` from the repository root.
+
+#![no_main]
+
+// Only some of the helpers of these modules are fuzzed.
+#[allow(dead_code)]
+#[path = "../../src/comment.rs"]
+mod comment;
+#[allow(dead_code)]
+#[path = "../../src/format.rs"]
+mod format;
+
+use libfuzzer_sys::fuzz_target;
+use rnix::SyntaxKind;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(src) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    // Use the first byte as the heading shift so absurd levels get covered.
+    let levels = data.first().map_or(0, |b| (*b as usize).wrapping_mul(1_000_000_007));
+
+    // The raw text as if it were the contents of a doc comment.
+    if let Some(doc) = format::handle_indentation(src) {
+        let _ = format::shift_headings_with_warnings(&doc, levels);
+    }
+    for line in src.lines() {
+        let _ = format::get_fence(line, true);
+        let _ = format::get_fence(line, false);
+    }
+
+    // And wrapped in real Nix so comment lookup sees it.
+    let wrapped = format!("{{\n  /**{}*/\n  foo = x: x;\n}}", src.replace("*/", "* /"));
+    let root = rnix::Root::parse(&wrapped).syntax();
+    for node in root.descendants() {
+        if node.kind() == SyntaxKind::NODE_ATTRPATH_VALUE
+            || node.kind() == SyntaxKind::NODE_LAMBDA
+        {
+            if let Some(doc) = comment::get_expr_docs(&node)
+                && let Some(doc) = format::handle_indentation(&doc)
+            {
+                let _ = format::shift_headings_with_warnings(&doc, 2);
+            }
+        }
+    }
+});
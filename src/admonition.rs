@@ -0,0 +1,179 @@
+//! Admonitions in the markdown dialect of the output.
+//!
+//! Doc comments and option descriptions write admonitions as the nixpkgs
+//! manual does, as `:::` blocks of the class `note`, `tip`, `important`,
+//! `warning` or `caution`:
+//!
+//! ```markdown
+//! ::: {.warning}
+//! The result is not escaped.
+//! :::
+//! ```
+//!
+//! The output formats other than markdown convert them to admonitions of
+//! their own (see [`crate::asciidoc`], [`crate::docbook`], [`crate::mdx`] and
+//! [`crate::html`]). Markdown output keeps them as they are, unless
+//! `--markdown-dialect` names a renderer that does not read `:::` blocks:
+//! with `github`, admonitions become GitHub alerts (`> [!WARNING]`), the
+//! fences of other blocks, such as usage examples, are replaced by an anchor
+//! with their id and the headings within them by bold text, and heading ids
+//! (`{#id}`) become anchors; with `html`, all blocks become `<div>` elements
+//! of their class and id, as in the HTML output.
+
+use crate::format::{DivFence, div_fence, get_fence, heading_level, trim_leading_whitespace};
+use crate::html::{div_tag, escape_html};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+/// Classes of the `:::` blocks that are admonitions.
+pub const KINDS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Markdown dialect of the output, for the renderer reading it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dialect {
+    /// `::: {.note}` blocks, as read by the nixpkgs manual
+    #[default]
+    Nixpkgs,
+    /// `> [!NOTE]` alerts, as read by GitHub
+    Github,
+    /// `<div class="note">` elements, for renderers keeping raw HTML
+    Html,
+}
+
+/// Write the `:::` blocks of `markdown`, outside code blocks, in `dialect`.
+pub fn to_dialect(markdown: &str, dialect: Dialect) -> Cow<'_, str> {
+    let converted = match dialect {
+        Dialect::Nixpkgs => false,
+        Dialect::Github => markdown.contains(":::") || markdown.contains("{#"),
+        Dialect::Html => markdown.contains(":::"),
+    };
+    if converted {
+        Cow::Owned(convert(markdown, dialect))
+    } else {
+        Cow::Borrowed(markdown)
+    }
+}
+
+/// Remove the last line of `out` if it is blank within its block quotes, so
+/// dropping a fence does not leave two blank lines.
+fn drop_blank_line(out: &mut String) {
+    let Some(text) = out.strip_suffix('\n') else {
+        return;
+    };
+    let start = text.rfind('\n').map_or(0, |i| i + 1);
+    if text[start..].trim_matches(['>', ' ']).is_empty() {
+        out.truncate(start);
+    }
+}
+
+/// A heading line for GitHub, which ignores `{#id}` attributes: its id as an
+/// anchor before it, and within a block other than an alert, such as a usage
+/// example, its text in bold rather than a heading of the document.
+fn github_heading(line: &str, level: usize, in_block: bool, prefix: &str, out: &mut String) {
+    let text = format!(" {}", line.trim()[level..].trim());
+    let (text, id) = match text.rsplit_once(" {#") {
+        Some((text, id)) if id.ends_with('}') && !id.contains(char::is_whitespace) => {
+            (text.trim(), id.strip_suffix('}'))
+        }
+        _ => (text.trim(), None),
+    };
+    if let Some(id) = id {
+        let _ = writeln!(out, "{}<a id=\"{}\"></a>", prefix, escape_html(id));
+    }
+    if in_block {
+        let _ = writeln!(out, "{}**{}**", prefix, text);
+    } else {
+        let heading = format!("{} {}", "#".repeat(level), text);
+        let _ = writeln!(out, "{}{}", prefix, heading.trim_end());
+    }
+}
+
+/// Write the `:::` blocks of `markdown` as GitHub alerts or HTML elements.
+fn convert(markdown: &str, dialect: Dialect) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut fence: Option<(usize, char)> = None;
+    // Whether each open block is an alert, written as a block quote.
+    let mut blocks: Vec<bool> = vec![];
+    // Blank lines at the start of a block are dropped, and a line right
+    // after one is separated from it, so it does not continue the quote or
+    // the HTML block of its end tag.
+    let mut block_start = false;
+    let mut block_end = false;
+    for line in markdown.split_inclusive('\n') {
+        let depth = blocks.iter().filter(|&&alert| alert).count();
+        let prefix = "> ".repeat(depth);
+        let fence_line = trim_leading_whitespace(line, 3);
+        let div = fence.is_none().then(|| div_fence(&fence_line)).flatten();
+        if line.trim().is_empty() && div.is_none() {
+            if !block_start {
+                out.push_str(prefix.trim_end());
+                out.push('\n');
+            }
+            block_end = false;
+            continue;
+        }
+        if block_end {
+            out.push_str(prefix.trim_end());
+            out.push('\n');
+            block_end = false;
+        }
+        block_start = false;
+        let (classes, id) = match div {
+            None => {
+                match fence {
+                    Some((count, ch)) => {
+                        if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count)
+                        {
+                            fence = None;
+                        }
+                    }
+                    None => {
+                        fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                        if let Some(level) = heading_level(line)
+                            && fence.is_none()
+                            && dialect == Dialect::Github
+                        {
+                            let in_block = blocks.last() == Some(&false);
+                            github_heading(line, level, in_block, &prefix, &mut out);
+                            continue;
+                        }
+                    }
+                }
+                out.push_str(&prefix);
+                out.push_str(line);
+                continue;
+            }
+            Some(DivFence::Close) => {
+                let Some(alert) = blocks.pop() else {
+                    continue;
+                };
+                drop_blank_line(&mut out);
+                if dialect == Dialect::Html {
+                    out.push_str("\n</div>\n");
+                }
+                block_end = alert || dialect == Dialect::Html;
+                continue;
+            }
+            Some(DivFence::Open { classes, id }) => (classes, id),
+        };
+        if dialect == Dialect::Html {
+            out.push_str(&div_tag(&classes, id));
+            out.push_str("\n\n");
+            blocks.push(false);
+            block_start = true;
+            continue;
+        }
+        if let Some(id) = id {
+            let _ = writeln!(out, "{}<a id=\"{}\"></a>", prefix, escape_html(id));
+        }
+        match KINDS.iter().find(|&&kind| classes.first() == Some(&kind)) {
+            Some(kind) => {
+                let _ = writeln!(out, "{}> [!{}]", prefix, kind.to_ascii_uppercase());
+                blocks.push(true);
+                block_start = true;
+            }
+            None => blocks.push(false),
+        }
+    }
+    out
+}
@@ -0,0 +1,128 @@
+//! Antora documentation component.
+//!
+//! Antora (<https://antora.org>) builds sites from components, each a
+//! directory with an `antora.yml` descriptor and AsciiDoc pages in modules.
+//! Everything is written to the `ROOT` module:
+//!
+//! ```text
+//! antora/
+//!   antora.yml
+//!   modules/ROOT/
+//!     nav.adoc
+//!     pages/
+//!       index.adoc
+//!       strings.adoc
+//!       lists.adoc
+//! ```
+//!
+//! Each page is a DevDocs page converted with
+//! [`crate::asciidoc::markdown_to_asciidoc`]. The
+//! navigation lists the pages and, below each, its entries, linking to their
+//! anchors.
+
+use crate::asciidoc::{escape, markdown_to_asciidoc};
+use crate::devdocs::Page;
+use crate::summary::document_title;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Title of `page`: its document title, or its type.
+fn page_title(page: &Page) -> String {
+    document_title(&page.markdown).unwrap_or_else(|| page.type_name.clone())
+}
+
+/// The `antora.yml` of the component. Without a version, the component is
+/// versionless (`version: ~`).
+pub fn descriptor(name: &str, title: &str, version: Option<&str>) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    format!(
+        "name: {}\ntitle: {}\nversion: {}\nnav:\n- modules/ROOT/nav.adoc\n",
+        quote(name),
+        quote(title),
+        version.map_or("~".to_string(), quote)
+    )
+}
+
+/// The `nav.adoc` of the `ROOT` module, listing `pages` and their entries.
+pub fn render_nav(pages: &[Page]) -> String {
+    let mut nav = String::new();
+    for page in pages {
+        let _ = writeln!(
+            nav,
+            "* xref:{}.adoc[{}]",
+            page.path,
+            escape(&page_title(page))
+        );
+        for (name, anchor) in &page.entries {
+            let _ = writeln!(
+                nav,
+                "** xref:{}.adoc#{}[{}]",
+                page.path,
+                anchor,
+                escape(name)
+            );
+        }
+    }
+    nav
+}
+
+/// The AsciiDoc of `page`, given a document title if it has none.
+pub fn render_page(page: &Page) -> String {
+    let mut markdown = page.markdown.clone();
+    if document_title(&markdown).is_none() {
+        markdown = format!("# {}\n\n{}", page.type_name, markdown);
+    }
+    let mut adoc = markdown_to_asciidoc(&markdown);
+    adoc.push('\n');
+    adoc
+}
+
+/// The `index.adoc` of the component, linking to every page.
+fn render_index(title: &str, pages: &[Page]) -> String {
+    let mut index = format!("= {}\n\n", escape(title));
+    for page in pages {
+        let _ = writeln!(
+            index,
+            "* xref:{}.adoc[{}]",
+            page.path,
+            escape(&page_title(page))
+        );
+    }
+    index
+}
+
+/// Write the component for `pages` into `dir`, returning the files written.
+pub fn write_component(
+    dir: &Path,
+    name: &str,
+    title: &str,
+    version: Option<&str>,
+    pages: &[Page],
+) -> Result<Vec<PathBuf>, String> {
+    let pages_dir = dir.join("modules").join("ROOT").join("pages");
+    fs::create_dir_all(&pages_dir)
+        .map_err(|e| format!("Error creating {}: {}", pages_dir.display(), e))?;
+    let mut written = vec![];
+    let mut write = |path: PathBuf, contents: String| {
+        fs::write(&path, contents)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        written.push(path);
+        Ok::<(), String>(())
+    };
+    write(dir.join("antora.yml"), descriptor(name, title, version))?;
+    write(
+        dir.join("modules").join("ROOT").join("nav.adoc"),
+        render_nav(pages),
+    )?;
+    if !pages.iter().any(|page| page.path == "index") {
+        write(pages_dir.join("index.adoc"), render_index(title, pages))?;
+    }
+    for page in pages {
+        write(
+            pages_dir.join(format!("{}.adoc", page.path)),
+            render_page(page),
+        )?;
+    }
+    Ok(written)
+}
@@ -0,0 +1,484 @@
+//! AsciiDoc output.
+//!
+//! The generated CommonMark is converted as a whole, for Antora and other
+//! Asciidoctor-based sites:
+//!
+//! - headings become section titles (`=` per level), preceded by an
+//!   `[[anchor]]` if they have an `{#id}`,
+//! - links to `#anchor` become `<<anchor,text>>` cross references, other
+//!   links `link:` macros,
+//! - code blocks become `[source]` listings, tables `|===` tables and HTML
+//!   blocks passthrough blocks,
+//! - blocks within list items and definitions are attached with `+`,
+//! - `note`, `tip`, `important`, `warning` and `caution` blocks become
+//!   admonitions and `example` blocks example blocks titled by their heading.
+//!
+//! Characters with a meaning in AsciiDoc are written as character references,
+//! paragraphs are written on one line each and inline HTML is dropped,
+//! keeping the text between the tags.
+
+use crate::format::{get_fence, trim_leading_whitespace};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::fmt::Write as _;
+
+/// `:::` blocks with an admonition of their own.
+const ADMONITIONS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Escape text so it is not read as AsciiDoc markup.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '*' | '_' | '`' | '#' | '^' | '~' | '+' | '{' | '[' | ']' | '|' => {
+                let _ = write!(escaped, "&#{};", c as u32);
+            }
+            '<' => escaped.push_str("&lt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Inline code as literal monospace.
+fn literal(code: &str) -> String {
+    if code.contains("+`") || code.ends_with('+') {
+        // The passthrough would end early; fall back to escaped text.
+        return format!("``{}``", escape(code));
+    }
+    format!("`+{}+`", code)
+}
+
+/// A list item or definition whose blocks are being written.
+struct Item {
+    /// Marker of a list item (`*`, `..`); `None` for a definition, whose
+    /// first paragraph follows its term.
+    marker: Option<String>,
+    /// Set once the first block has been written.
+    started: bool,
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Inline content of the current block.
+    buf: String,
+    /// Marker character of each open list: `*` if unordered, `.` if ordered
+    /// and `:` for definition lists.
+    lists: Vec<char>,
+    items: Vec<Item>,
+    /// Targets of the links being written, with where their text starts in
+    /// `buf`.
+    links: Vec<(String, usize)>,
+    /// Rows of the table being written, and whether the first is a header.
+    table: Option<(Vec<Vec<String>>, bool)>,
+    code: Option<(String, String)>,
+    /// Level and id of the heading being written.
+    heading: Option<(usize, Option<String>)>,
+    /// Delimiters closing the open `:::` blocks; empty for dropped fences.
+    blocks: Vec<&'static str>,
+    /// Set after opening an example block until its title is known.
+    example: bool,
+    /// Set after an opening delimiter, which needs no blank line after it.
+    opened: bool,
+}
+
+impl Writer {
+    fn blank(&mut self) {
+        if std::mem::take(&mut self.opened) {
+            return;
+        }
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Number of open lists with the marker `kind`, the nesting level of its
+    /// next item.
+    fn depth(&self, kind: char) -> usize {
+        self.lists.iter().filter(|&&k| k == kind).count()
+    }
+
+    /// Start a block: after a blank line, or attached to the open list item.
+    fn start_block(&mut self) {
+        if self.example {
+            self.example = false;
+            self.blank();
+            self.out.push_str("====\n");
+            self.opened = true;
+        }
+        match self.items.last_mut() {
+            Some(item) if item.started => self.out.push_str("+\n"),
+            Some(item) => {
+                item.started = true;
+                match &item.marker {
+                    Some(marker) => {
+                        let _ = writeln!(self.out, "{} {{empty}}", marker);
+                        self.out.push_str("+\n");
+                    }
+                    None => self.out.push_str("{empty}\n+\n"),
+                }
+            }
+            None => self.blank(),
+        }
+    }
+
+    /// Write the pending inline content as a paragraph.
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.buf);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        // Lines starting like these would be read as block markup.
+        let guard = if text.starts_with(['.', '-', '/', ':', '=', '\'', '>']) {
+            "{empty}"
+        } else {
+            ""
+        };
+        match self.items.last_mut() {
+            Some(item) if !item.started => {
+                item.started = true;
+                match &item.marker {
+                    Some(marker) => {
+                        let _ = writeln!(self.out, "{} {}{}", marker, guard, text);
+                    }
+                    None => {
+                        let _ = writeln!(self.out, "{}{}", guard, text);
+                    }
+                }
+            }
+            _ => {
+                self.start_block();
+                let _ = writeln!(self.out, "{}{}", guard, text);
+            }
+        }
+    }
+
+    fn heading(&mut self, level: usize, id: Option<&str>) {
+        let title = std::mem::take(&mut self.buf);
+        let title = title.trim();
+        if std::mem::take(&mut self.example) {
+            // The heading of an example is its title.
+            if !title.is_empty() {
+                let _ = writeln!(self.out, ".{}", title);
+            }
+            self.out.push_str("====\n");
+            self.opened = true;
+            return;
+        }
+        self.blank();
+        if let Some(id) = id {
+            let _ = writeln!(self.out, "[[{}]]", id);
+        }
+        if !title.is_empty() {
+            let _ = writeln!(self.out, "{} {}", "=".repeat(level.clamp(1, 6)), title);
+        }
+        self.blank();
+    }
+
+    fn table(&mut self, rows: Vec<Vec<String>>, has_header: bool) {
+        self.start_block();
+        self.out.push_str("|===\n");
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = row.iter().map(|cell| format!("|{}", cell.trim())).collect();
+            let _ = writeln!(self.out, "{}", cells.join(" "));
+            if i == 0 && has_header {
+                self.out.push('\n');
+            }
+        }
+        self.out.push_str("|===\n");
+    }
+
+    fn open_block(&mut self, attrs: &str) {
+        self.flush();
+        let mut class = None;
+        let mut id = None;
+        for attr in attrs.trim_matches(['{', '}']).split_whitespace() {
+            match attr.chars().next() {
+                Some('.') => class = class.or(Some(&attr[1..])),
+                Some('#') => id = Some(&attr[1..]),
+                _ if !attr.contains('=') => class = class.or(Some(attr)),
+                _ => {}
+            }
+        }
+        if let Some(admonition) = ADMONITIONS.iter().find(|&&a| Some(a) == class) {
+            self.start_block();
+            if let Some(id) = id {
+                let _ = writeln!(self.out, "[[{}]]", id);
+            }
+            let _ = write!(self.out, "[{}]\n====\n", admonition.to_ascii_uppercase());
+            self.opened = true;
+            self.blocks.push("====");
+        } else if class == Some("example") {
+            self.blank();
+            if let Some(id) = id {
+                let _ = writeln!(self.out, "[[{}]]", id);
+            }
+            self.example = true;
+            self.blocks.push("====");
+        } else {
+            self.blocks.push("");
+        }
+    }
+
+    fn close_block(&mut self) {
+        self.flush();
+        self.opened = false;
+        if let Some(delimiter) = self.blocks.pop()
+            && !delimiter.is_empty()
+        {
+            if std::mem::take(&mut self.example) {
+                self.out.push_str("====\n");
+            }
+            let _ = writeln!(self.out, "{}", delimiter);
+            self.blank();
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some((_, code)) = &mut self.code {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let (lang, code) = self.code.take().unwrap_or_default();
+                    self.start_block();
+                    if lang.is_empty() {
+                        self.out.push_str("[source]\n");
+                    } else {
+                        let _ = writeln!(self.out, "[source,{}]", lang);
+                    }
+                    let _ = write!(self.out, "----\n{}\n----\n", code.trim_end_matches('\n'));
+                }
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Paragraph) => self.flush(),
+            Event::End(TagEnd::Paragraph) => self.flush(),
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                self.flush();
+                self.heading = Some((level as usize, id.map(|id| id.to_string())));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, id)) = self.heading.take() {
+                    self.heading(level, id.as_deref());
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                self.flush();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((lang, String::new()));
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.start_block();
+                }
+                self.lists.push(if start.is_some() { '.' } else { '*' });
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                let kind = self.lists.last().copied().unwrap_or('*');
+                let marker = kind.to_string().repeat(self.depth(kind).max(1));
+                self.items.push(Item {
+                    marker: Some(marker),
+                    started: false,
+                });
+            }
+            Event::End(TagEnd::Item) | Event::End(TagEnd::DefinitionListDefinition) => {
+                self.flush();
+                if let Some(Item {
+                    marker: Some(marker),
+                    started: false,
+                }) = self.items.pop()
+                {
+                    let _ = writeln!(self.out, "{} {{empty}}", marker);
+                }
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush();
+                self.start_block();
+                self.out.push_str("____\n");
+                self.opened = true;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.opened = false;
+                self.flush();
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                while self.out.ends_with("\n\n") {
+                    self.out.pop();
+                }
+                self.out.push_str("____\n");
+            }
+            Event::Start(Tag::DefinitionList) => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.start_block();
+                }
+                self.lists.push(':');
+            }
+            Event::End(TagEnd::DefinitionList) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            Event::Start(Tag::DefinitionListTitle) => self.flush(),
+            Event::End(TagEnd::DefinitionListTitle) => {
+                let term = std::mem::take(&mut self.buf);
+                let delimiter = ":".repeat(self.depth(':') + 1);
+                let _ = writeln!(self.out, "{}{}", term.trim(), delimiter);
+            }
+            Event::Start(Tag::DefinitionListDefinition) => self.items.push(Item {
+                marker: None,
+                started: false,
+            }),
+            Event::Start(Tag::Table(_)) => {
+                self.flush();
+                self.table = Some((vec![], false));
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some((rows, has_header)) = self.table.take() {
+                    self.table(rows, has_header);
+                }
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some((rows, has_header)) = &mut self.table {
+                    *has_header = true;
+                    rows.push(vec![]);
+                }
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some((rows, _)) = &mut self.table {
+                    rows.push(vec![]);
+                }
+            }
+            Event::End(TagEnd::TableCell) => {
+                let cell = std::mem::take(&mut self.buf);
+                if let Some(row) = self.table.as_mut().and_then(|(rows, _)| rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            Event::Start(Tag::Emphasis) | Event::End(TagEnd::Emphasis) => self.buf.push_str("__"),
+            Event::Start(Tag::Strong) | Event::End(TagEnd::Strong) => self.buf.push_str("**"),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.links.push((dest_url.to_string(), self.buf.len()));
+            }
+            Event::End(TagEnd::Link) => {
+                let Some((url, start)) = self.links.pop() else {
+                    return;
+                };
+                let text = self.buf.split_off(start);
+                let text = text.trim();
+                let _ = match url.strip_prefix('#') {
+                    Some(anchor) if text.is_empty() => write!(self.buf, "<<{}>>", anchor),
+                    Some(anchor) => write!(self.buf, "<<{},{}>>", anchor, text),
+                    None => write!(self.buf, "link:{}[{}]", url.replace(' ', "%20"), text),
+                };
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.links.push((dest_url.to_string(), self.buf.len()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, start)) = self.links.pop() {
+                    let alt = self.buf.split_off(start);
+                    let _ = write!(
+                        self.buf,
+                        "image:{}[{}]",
+                        url.replace(' ', "%20"),
+                        alt.trim()
+                    );
+                }
+            }
+            Event::Code(code) => self.buf.push_str(&literal(&code)),
+            Event::Text(text) => self.buf.push_str(&escape(&text)),
+            Event::SoftBreak => self.buf.push(' '),
+            Event::HardBreak => self.buf.push_str(" +\n"),
+            Event::Html(html) => {
+                self.flush();
+                self.start_block();
+                let _ = write!(self.out, "++++\n{}\n++++\n", html.trim_end());
+            }
+            Event::Rule => {
+                self.flush();
+                self.start_block();
+                self.out.push_str("'''\n");
+            }
+            _ => {}
+        }
+    }
+
+    fn convert(&mut self, markdown: &str) {
+        let options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_DEFINITION_LIST;
+        for event in Parser::new_ext(markdown, options) {
+            self.event(event);
+        }
+        self.flush();
+    }
+}
+
+/// `text` as a comment block.
+pub fn comment(text: &str) -> String {
+    format!("////\n{}////\n", text.replace("////", "// //"))
+}
+
+/// Convert CommonMark, as generated by docgen, to AsciiDoc.
+pub fn markdown_to_asciidoc(markdown: &str) -> String {
+    let mut writer = Writer::default();
+    let mut chunk = String::new();
+    let mut fence: Option<(usize, char)> = None;
+    for line in markdown.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(attrs) = fence_line.strip_prefix(":::") {
+                    let attrs = attrs.trim_start_matches(':').trim();
+                    writer.convert(&std::mem::take(&mut chunk));
+                    if attrs.is_empty() {
+                        writer.close_block();
+                    } else {
+                        writer.open_block(attrs);
+                    }
+                    continue;
+                }
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+            }
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    writer.convert(&chunk);
+    while !writer.blocks.is_empty() {
+        writer.close_block();
+    }
+    let mut out = writer.out;
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
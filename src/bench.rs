@@ -0,0 +1,147 @@
+//! Timing of the phases of a documentation run.
+//!
+//! Each phase, such as parsing a Nix file, collecting its entries or
+//! rendering them, is run a number of times in a row and timed on its own,
+//! later phases working on the result of the last run of the earlier ones.
+//! The report gives the mean, fastest and slowest run of each phase and its
+//! throughput, in bytes of input and in items (entries or options) per
+//! second.
+
+use std::fmt::Write as _;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// The runs of one phase.
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub name: String,
+    /// Duration of each run.
+    pub samples: Vec<Duration>,
+    /// Entries or options produced or consumed by a run, if the phase
+    /// deals in them.
+    pub items: Option<usize>,
+}
+
+impl Phase {
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len().max(1) as u32
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().copied().max().unwrap_or_default()
+    }
+}
+
+/// Timings of the phases run on one input.
+#[derive(Debug, Clone)]
+pub struct Bench {
+    /// Name of the input in the report.
+    pub input: String,
+    /// Size of the input in bytes.
+    pub bytes: usize,
+    pub iterations: usize,
+    pub phases: Vec<Phase>,
+}
+
+impl Bench {
+    pub fn new(input: &str, bytes: usize, iterations: usize) -> Bench {
+        Bench {
+            input: input.to_string(),
+            bytes,
+            iterations: iterations.max(1),
+            phases: vec![],
+        }
+    }
+
+    /// Run `f` once per iteration, timing each run, and return the result of
+    /// the last one. `items` counts the items of a result.
+    pub fn phase<T>(
+        &mut self,
+        name: &str,
+        items: impl Fn(&T) -> Option<usize>,
+        mut f: impl FnMut() -> T,
+    ) -> T {
+        let mut samples = Vec::with_capacity(self.iterations);
+        let mut result = None;
+        for _ in 0..self.iterations {
+            // Drop the previous result outside of the timed run.
+            drop(result.take());
+            let start = Instant::now();
+            let value = black_box(f());
+            samples.push(start.elapsed());
+            result = Some(value);
+        }
+        let result = result.expect("at least one iteration");
+        self.phases.push(Phase {
+            name: name.to_string(),
+            samples,
+            items: items(&result),
+        });
+        result
+    }
+
+    /// The report as a plain text table.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}: {} bytes, {} iteration{}\n",
+            self.input,
+            self.bytes,
+            self.iterations,
+            if self.iterations == 1 { "" } else { "s" }
+        );
+        let _ = writeln!(
+            out,
+            "{:<10} {:>7} {:>12} {:>12} {:>12} {:>10} {:>12}",
+            "phase", "items", "mean", "min", "max", "MiB/s", "items/s"
+        );
+        for phase in &self.phases {
+            let seconds = phase.mean().as_secs_f64();
+            let per_second = |count: f64| {
+                if seconds > 0.0 {
+                    count / seconds
+                } else {
+                    f64::INFINITY
+                }
+            };
+            let (items, items_per_second) = match phase.items {
+                Some(items) => (
+                    items.to_string(),
+                    format!("{:.0}", per_second(items as f64)),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let _ = writeln!(
+                out,
+                "{:<10} {:>7} {:>12} {:>12} {:>12} {:>10.1} {:>12}",
+                phase.name,
+                items,
+                format_duration(phase.mean()),
+                format_duration(phase.min()),
+                format_duration(phase.max()),
+                per_second(self.bytes as f64 / (1024.0 * 1024.0)),
+                items_per_second,
+            );
+        }
+        out
+    }
+}
+
+/// A duration in the largest unit keeping it above one.
+pub fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    if nanos >= 1_000_000_000 {
+        format!("{:.2} s", duration.as_secs_f64())
+    } else if nanos >= 1_000_000 {
+        format!("{:.2} ms", nanos as f64 / 1e6)
+    } else if nanos >= 1_000 {
+        format!("{:.2} µs", nanos as f64 / 1e3)
+    } else {
+        format!("{} ns", nanos)
+    }
+}
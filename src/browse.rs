@@ -0,0 +1,570 @@
+//! Terminal browser for module options.
+//!
+//! `docgen options browse` shows the options of an options JSON file as a
+//! tree of their attribute paths next to the details of the selected option:
+//! its type, default, example, declarations and rendered description. Typing
+//! after `/` replaces the tree with the options whose names match the query
+//! fuzzily, best matches first, like an offline `search.nixos.org`.
+//!
+//! [`Browser`] holds the state and handles keys, so it can be driven without
+//! a terminal; [`run`] connects it to one.
+
+use docgen::options::{self, OptionDef};
+use pulldown_cmark::{Event as MdEvent, HeadingLevel, Parser, Tag, TagEnd};
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+/// Score of a name matching a query, if every character of the query
+/// appears in it in order, ignoring case. Matches at the start of a path
+/// segment or a camel case word and runs of consecutive characters score
+/// higher, and a name containing the query as is scores highest.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = name.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last: Option<usize> = None;
+    for (i, c) in chars.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if c.to_lowercase().eq(query[next].to_lowercase()) {
+            score += 1;
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+            if prev.is_none_or(|p| p == '.' || (p.is_lowercase() && c.is_uppercase())) {
+                score += 8;
+            }
+            match last {
+                Some(l) if l + 1 == i => score += 5,
+                Some(l) => score -= (i - l - 1).min(5) as i64,
+                None => {}
+            }
+            last = Some(i);
+            next += 1;
+        }
+    }
+    if next < query.len() {
+        return None;
+    }
+    let query: String = query.into_iter().collect();
+    if name.to_lowercase().contains(&query) {
+        score += 20;
+    }
+    // Between equal matches, shorter names are closer.
+    Some(score * 1000 - chars.len() as i64)
+}
+
+/// The attribute path of an option, from its `loc` or else its name.
+fn segments(name: &str, opt: &OptionDef) -> Vec<String> {
+    if opt.loc.is_empty() {
+        name.split('.').map(str::to_string).collect()
+    } else {
+        opt.loc.clone()
+    }
+}
+
+/// A level of the tree of attribute paths.
+#[derive(Debug, Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    /// The option at this path, if there is one.
+    option: Option<usize>,
+    /// Number of options at or below this path.
+    count: usize,
+}
+
+/// A line of the list: a level of the tree or a search result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub path: Vec<String>,
+    pub label: String,
+    pub depth: usize,
+    /// Index of the option shown on the row, if any.
+    pub option: Option<usize>,
+    /// Number of options below the row, for levels of the tree.
+    pub children: usize,
+    pub expanded: bool,
+}
+
+/// State of the browser.
+#[derive(Debug)]
+pub struct Browser {
+    options: Vec<(String, OptionDef)>,
+    tree: TreeNode,
+    expanded: HashSet<Vec<String>>,
+    query: String,
+    /// Whether keys edit the query.
+    searching: bool,
+    rows: Vec<Row>,
+    selected: usize,
+    /// Lines scrolled in the details.
+    scroll: u16,
+}
+
+impl Browser {
+    /// A browser of `options`, shown in name order.
+    pub fn new(mut options: Vec<(String, OptionDef)>) -> Browser {
+        options.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut tree = TreeNode::default();
+        for (i, (name, opt)) in options.iter().enumerate() {
+            let mut node = &mut tree;
+            node.count += 1;
+            for segment in segments(name, opt) {
+                node = node.children.entry(segment).or_default();
+                node.count += 1;
+            }
+            node.option = Some(i);
+        }
+        let mut browser = Browser {
+            options,
+            tree,
+            expanded: HashSet::new(),
+            query: String::new(),
+            searching: false,
+            rows: vec![],
+            selected: 0,
+            scroll: 0,
+        };
+        browser.update_rows();
+        browser
+    }
+
+    #[cfg(test)]
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    #[cfg(test)]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The row under the cursor.
+    pub fn selected_row(&self) -> Option<&Row> {
+        self.rows.get(self.selected)
+    }
+
+    /// The option under the cursor, if the row shows one.
+    pub fn selected_option(&self) -> Option<(&str, &OptionDef)> {
+        let (name, opt) = &self.options[self.selected_row()?.option?];
+        Some((name, opt))
+    }
+
+    /// Recompute the rows after the query or the expanded levels changed,
+    /// keeping the cursor on the same path if it is still listed.
+    fn update_rows(&mut self) {
+        let current = self.selected_row().map(|row| row.path.clone());
+        self.rows = if self.query.is_empty() {
+            let mut rows = vec![];
+            self.tree_rows(&self.tree, &mut vec![], &mut rows);
+            rows
+        } else {
+            let mut matches: Vec<(i64, usize)> = self
+                .options
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (name, _))| Some((fuzzy_score(&self.query, name)?, i)))
+                .collect();
+            matches.sort_by(|(a, i), (b, j)| b.cmp(a).then(i.cmp(j)));
+            matches
+                .into_iter()
+                .map(|(_, i)| {
+                    let (name, opt) = &self.options[i];
+                    Row {
+                        path: segments(name, opt),
+                        label: name.clone(),
+                        depth: 0,
+                        option: Some(i),
+                        children: 0,
+                        expanded: false,
+                    }
+                })
+                .collect()
+        };
+        self.selected = current
+            .and_then(|path| self.rows.iter().position(|row| row.path == path))
+            .unwrap_or(self.selected)
+            .min(self.rows.len().saturating_sub(1));
+    }
+
+    fn tree_rows(&self, node: &TreeNode, path: &mut Vec<String>, rows: &mut Vec<Row>) {
+        for (segment, child) in &node.children {
+            path.push(segment.clone());
+            let children = child.count - usize::from(child.option.is_some());
+            let expanded = children > 0 && self.expanded.contains(path);
+            rows.push(Row {
+                path: path.clone(),
+                label: segment.clone(),
+                depth: path.len() - 1,
+                option: child.option,
+                children,
+                expanded,
+            });
+            if expanded {
+                self.tree_rows(child, path, rows);
+            }
+            path.pop();
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        let index = index.min(self.rows.len().saturating_sub(1));
+        if index != self.selected {
+            self.selected = index;
+            self.scroll = 0;
+        }
+    }
+
+    /// Open the level under the cursor, or move into it if it is open.
+    fn expand(&mut self) {
+        let Some(row) = self.selected_row().cloned() else {
+            return;
+        };
+        if row.children == 0 || !self.query.is_empty() {
+            return;
+        }
+        if row.expanded {
+            self.select(self.selected + 1);
+        } else {
+            self.expanded.insert(row.path);
+            self.update_rows();
+        }
+    }
+
+    /// Close the level under the cursor, or move to its parent.
+    fn collapse(&mut self) {
+        let Some(row) = self.selected_row().cloned() else {
+            return;
+        };
+        if !self.query.is_empty() {
+            return;
+        }
+        if row.expanded {
+            self.expanded.remove(&row.path);
+            self.update_rows();
+        } else if let Some(parent) = self
+            .rows
+            .iter()
+            .position(|r| r.path.len() + 1 == row.path.len() && row.path.starts_with(&r.path))
+        {
+            self.select(parent);
+        }
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.rows.clear();
+        self.selected = 0;
+        self.scroll = 0;
+        self.update_rows();
+    }
+
+    /// Handle a key press, returning whether to quit.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('c') => return true,
+                KeyCode::Char('d') => self.scroll = self.scroll.saturating_add(10),
+                KeyCode::Char('u') => self.scroll = self.scroll.saturating_sub(10),
+                _ => {}
+            }
+            return false;
+        }
+        let page = 10;
+        match key.code {
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(page)),
+            KeyCode::PageDown => self.select(self.selected + page),
+            KeyCode::Enter | KeyCode::Esc if self.searching => self.searching = false,
+            KeyCode::Backspace if self.searching => {
+                let mut query = self.query.clone();
+                query.pop();
+                self.set_query(query);
+            }
+            KeyCode::Char(c) if self.searching => {
+                let query = format!("{}{}", self.query, c);
+                self.set_query(query);
+            }
+            KeyCode::Esc if !self.query.is_empty() => self.set_query(String::new()),
+            KeyCode::Esc | KeyCode::Char('q') => return true,
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(self.rows.len()),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.expand(),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Char('J') => self.scroll = self.scroll.saturating_add(1),
+            KeyCode::Char('K') => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+        false
+    }
+
+    /// Draw the search bar, the list, the details and the key help.
+    pub fn draw(&self, frame: &mut Frame) {
+        let [search, main, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, details] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let mut query = Line::from(self.query.as_str());
+        if self.searching {
+            query.push_span(Span::from("_").add_modifier(Modifier::SLOW_BLINK));
+        }
+        let title = if self.query.is_empty() {
+            format!(" Search ({} options) ", self.options.len())
+        } else {
+            format!(
+                " Search ({} of {} options) ",
+                self.rows.len(),
+                self.options.len()
+            )
+        };
+        frame.render_widget(
+            Paragraph::new(query).block(Block::bordered().title(title)),
+            search,
+        );
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let marker = match (row.children, row.expanded) {
+                    (0, _) => "  ",
+                    (_, true) => "▾ ",
+                    (_, false) => "▸ ",
+                };
+                let mut line = Line::from(vec![
+                    Span::raw("  ".repeat(row.depth)),
+                    Span::raw(marker),
+                    Span::raw(row.label.clone()),
+                ]);
+                if row.children > 0 {
+                    line.push_span(Span::from(format!(" ({})", row.children)).dim());
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(" Options "))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            list,
+            &mut state,
+        );
+
+        let text = match (self.selected_option(), self.selected_row()) {
+            (Some((name, opt)), _) => option_text(name, opt),
+            (None, Some(row)) => Text::from(vec![
+                Line::from(row.path.join(".")).bold(),
+                Line::default(),
+                Line::from(format!("{} options", row.children)),
+            ]),
+            (None, None) => Text::from("No matching options"),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .block(Block::bordered().title(" Details "))
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0)),
+            details,
+        );
+
+        let keys = if self.searching {
+            "type to search  ↑↓ move  enter/esc done"
+        } else {
+            "/ search  ↑↓/jk move  →←/lh open/close  J/K scroll details  esc clear  q quit"
+        };
+        frame.render_widget(Paragraph::new(keys).dim(), help);
+    }
+}
+
+/// The details of an option: name, type, default, example, declarations and
+/// description.
+pub fn option_text(name: &str, opt: &OptionDef) -> Text<'static> {
+    let mut lines = vec![Line::from(name.to_string()).bold()];
+    if opt.read_only {
+        lines.push(Line::from("read-only").italic());
+    }
+    lines.push(Line::default());
+    let field = |label: &str| Span::from(format!("{}: ", label)).bold();
+    if let Some(ty) = &opt.option_type {
+        lines.push(Line::from(vec![field("Type"), Span::raw(ty.clone())]));
+    }
+    for (label, value) in [("Default", &opt.default), ("Example", &opt.example)] {
+        let Some(value) = value else { continue };
+        let text = options::option_value_text(value);
+        if text.contains('\n') {
+            lines.push(Line::from(field(label)));
+            lines.extend(code_lines(&text));
+        } else {
+            lines.push(Line::from(vec![field(label), Span::from(text).cyan()]));
+        }
+    }
+    if !opt.declarations.is_empty() {
+        lines.push(Line::from(field("Declared in")));
+        for declaration in &opt.declarations {
+            lines.push(Line::from(format!("  {}", declaration.name())));
+        }
+    }
+    if let Some(description) = &opt.description {
+        lines.push(Line::default());
+        lines.extend(markdown_lines(description.as_str()));
+    }
+    Text::from(lines)
+}
+
+/// Lines of a code block, indented.
+fn code_lines(code: &str) -> Vec<Line<'static>> {
+    code.trim_end_matches('\n')
+        .lines()
+        .map(|line| Line::from(format!("  {}", line)).cyan())
+        .collect()
+}
+
+/// Markdown as styled lines: headings in bold, code in color, emphasis and
+/// list items marked. `{role}` prefixes of inline code are left out.
+pub fn markdown_lines(markdown: &str) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line<'static>> = vec![];
+    let mut line: Vec<Span<'static>> = vec![];
+    let mut style = Style::new();
+    let mut styles: Vec<Style> = vec![];
+    let mut lists: Vec<Option<u64>> = vec![];
+    let mut code_block = false;
+
+    fn flush(lines: &mut Vec<Line<'static>>, line: &mut Vec<Span<'static>>) {
+        if !line.is_empty() {
+            lines.push(Line::from(std::mem::take(line)));
+        }
+    }
+    fn blank(lines: &mut Vec<Line<'static>>) {
+        if lines.last().is_some_and(|l| l.width() > 0) {
+            lines.push(Line::default());
+        }
+    }
+
+    for event in Parser::new(markdown) {
+        match event {
+            MdEvent::Start(Tag::Heading { level, .. }) => {
+                blank(&mut lines);
+                styles.push(style);
+                style = style.add_modifier(Modifier::BOLD);
+                if level == HeadingLevel::H1 {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+            }
+            MdEvent::Start(Tag::Emphasis) => {
+                styles.push(style);
+                style = style.add_modifier(Modifier::ITALIC);
+            }
+            MdEvent::Start(Tag::Strong) => {
+                styles.push(style);
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            MdEvent::Start(Tag::Link { .. }) => {
+                styles.push(style);
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            MdEvent::End(TagEnd::Heading(_)) => {
+                style = styles.pop().unwrap_or_default();
+                flush(&mut lines, &mut line);
+                lines.push(Line::default());
+            }
+            MdEvent::End(TagEnd::Emphasis | TagEnd::Strong | TagEnd::Link) => {
+                style = styles.pop().unwrap_or_default();
+            }
+            MdEvent::Start(Tag::Paragraph) if lists.is_empty() => blank(&mut lines),
+            MdEvent::End(TagEnd::Paragraph) => flush(&mut lines, &mut line),
+            MdEvent::Start(Tag::CodeBlock(_)) => {
+                flush(&mut lines, &mut line);
+                blank(&mut lines);
+                code_block = true;
+            }
+            MdEvent::End(TagEnd::CodeBlock) => {
+                code_block = false;
+                lines.push(Line::default());
+            }
+            MdEvent::Start(Tag::List(start)) => {
+                flush(&mut lines, &mut line);
+                if lists.is_empty() {
+                    blank(&mut lines);
+                }
+                lists.push(start);
+            }
+            MdEvent::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    lines.push(Line::default());
+                }
+            }
+            MdEvent::Start(Tag::Item) => {
+                flush(&mut lines, &mut line);
+                let indent = "  ".repeat(lists.len().saturating_sub(1));
+                let marker = match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                line.push(Span::raw(format!("{}{}", indent, marker)));
+            }
+            MdEvent::End(TagEnd::Item) => flush(&mut lines, &mut line),
+            MdEvent::Text(text) if code_block => lines.extend(code_lines(&text)),
+            MdEvent::Text(text) => line.push(Span::styled(text.into_string(), style)),
+            MdEvent::Code(code) => {
+                // `{option}` before inline code is a role for the manual.
+                if let Some(last) = line.last_mut()
+                    && let Some(start) = last.content.rfind('{')
+                    && last.content.ends_with('}')
+                {
+                    let kept = last.content[..start].to_string();
+                    last.content = kept.into();
+                }
+                line.push(Span::styled(code.into_string(), style.cyan()));
+            }
+            MdEvent::SoftBreak => line.push(Span::raw(" ")),
+            MdEvent::HardBreak => flush(&mut lines, &mut line),
+            _ => {}
+        }
+    }
+    flush(&mut lines, &mut line);
+    while lines.last().is_some_and(|l| l.width() == 0) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Browse `options` in the terminal until the user quits.
+pub fn run(options: Vec<(String, OptionDef)>) -> io::Result<()> {
+    let mut browser = Browser::new(options);
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && browser.handle_key(key)
+        {
+            return Ok(());
+        }
+    })();
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,119 @@
+//! Cache of the entries loaded from Nix files, shared by the commands run
+//! with `--cache-dir`.
+//!
+//! Loading a file parses it, the files it re-exports from, its types and
+//! its included fragments, so rendering it and then checking its coverage
+//! would do that twice. Instead, each load is stored as a record named by a
+//! [`key`] of the docgen version, the settings that affect loading, the
+//! file's path and the SHA-256 of its content:
+//!
+//! ```json
+//! {
+//!   "dependencies": [
+//!     { "path": "lib/strings.nix", "sha256": "…" },
+//!     { "path": "lib/strings.types.json", "sha256": null }
+//!   ],
+//!   "warnings": [],
+//!   "description": "…",
+//!   "entries": [ … ],
+//!   "public_bindings": [ "concatStrings", … ]
+//! }
+//! ```
+//!
+//! The record lists every other file read loading it, and the places files
+//! were looked for at but not found (with a `null` hash), and is only used
+//! while all of them are as they were. Records are never pruned; the
+//! directory can be deleted at any time.
+
+use crate::commonmark::ManualEntry;
+use crate::manifest::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file loading an input read, or looked for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub path: PathBuf,
+    /// Hash of the file's content, or none if there was no file.
+    pub sha256: Option<String>,
+}
+
+impl Dependency {
+    /// The file at `path` as it is now.
+    pub fn of(path: &Path) -> Dependency {
+        Dependency {
+            path: path.to_path_buf(),
+            sha256: fs::read(path).ok().map(|bytes| sha256_hex(&bytes)),
+        }
+    }
+
+    /// Whether the file is still as it was.
+    pub fn is_current(&self) -> bool {
+        *self == Dependency::of(&self.path)
+    }
+}
+
+/// What loading an input found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// The files read, the input first.
+    pub dependencies: Vec<Dependency>,
+    /// Warnings about the input, each starting with the file it is about.
+    pub warnings: Vec<String>,
+    /// The description of the input's category.
+    pub description: String,
+    pub entries: Vec<ManualEntry>,
+    /// Names of the bindings the input could document that are not hidden,
+    /// documented or not.
+    pub public_bindings: Vec<String>,
+}
+
+impl Record {
+    /// The dependencies that changed since the record was made.
+    pub fn changed(&self) -> Vec<&Path> {
+        self.dependencies
+            .iter()
+            .filter(|dependency| !dependency.is_current())
+            .map(|dependency| dependency.path.as_path())
+            .collect()
+    }
+}
+
+/// The name of the record of loading `input` with `settings`, which must
+/// hold everything besides the files read that the result depends on.
+pub fn key(input: &Path, settings: &serde_json::Value) -> Result<String, String> {
+    let content =
+        fs::read(input).map_err(|e| format!("Error reading {}: {}", input.display(), e))?;
+    let key = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "settings": settings,
+        "path": input,
+        "sha256": sha256_hex(&content),
+    });
+    Ok(sha256_hex(key.to_string().as_bytes()))
+}
+
+/// The record stored in `dir` under `key`, if there is one and none of the
+/// files it was made from changed since.
+pub fn lookup(dir: &Path, key: &str) -> Option<Record> {
+    let json = fs::read_to_string(record_path(dir, key)).ok()?;
+    let record: Record = serde_json::from_str(&json).ok()?;
+    record.changed().is_empty().then_some(record)
+}
+
+/// Store `record` in `dir` under `key`, creating the directory if needed.
+pub fn store(dir: &Path, key: &str, record: &Record) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("could not write to cache {}: {}", dir.display(), e);
+    fs::create_dir_all(dir).map_err(error)?;
+    let json = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    // Written aside and renamed, so a concurrent run never reads half a
+    // record.
+    let partial = dir.join(format!("{}.json.{}", key, std::process::id()));
+    fs::write(&partial, json).map_err(error)?;
+    fs::rename(&partial, record_path(dir, key)).map_err(error)
+}
+
+fn record_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.json", key))
+}
@@ -0,0 +1,378 @@
+//! Changes between two revisions of the documentation.
+//!
+//! Both revisions are reduced to a map from entry name (a function title or
+//! an option name) to its rendered CommonMark section. An entry is added or
+//! removed if only one side has it, and changed if its section differs. The
+//! changes are written as a CommonMark changelog or as an Atom feed that
+//! users can subscribe to, or, for reviewing, as a line diff of each changed
+//! section or an HTML page showing the old and new rendering side by side.
+
+use crate::html::{self, escape_html};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One side of the comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Anchor of the entry in the rendered documentation.
+    pub anchor: String,
+    /// One-line description.
+    pub summary: String,
+    /// Rendered CommonMark.
+    pub markdown: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl ChangeKind {
+    fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "Added",
+            ChangeKind::Changed => "Changed",
+            ChangeKind::Removed => "Removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<'a> {
+    pub kind: ChangeKind,
+    pub name: &'a str,
+    /// The new section, or the old one for removed entries.
+    pub section: &'a Section,
+    /// The old section of a changed entry.
+    pub previous: Option<&'a Section>,
+}
+
+/// Output format of `docgen diff`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// CommonMark with a `diff` code block per entry
+    #[default]
+    Markdown,
+    /// A page with the old and new rendering of each entry side by side
+    Html,
+}
+
+/// Compare two revisions, ordered by kind and then by name.
+pub fn diff<'a>(
+    old: &'a BTreeMap<String, Section>,
+    new: &'a BTreeMap<String, Section>,
+) -> Vec<Change<'a>> {
+    let mut changes = vec![];
+    for (name, section) in new {
+        let (kind, previous) = match old.get(name) {
+            None => (ChangeKind::Added, None),
+            Some(prev) if prev.markdown != section.markdown => (ChangeKind::Changed, Some(prev)),
+            Some(_) => continue,
+        };
+        changes.push(Change {
+            kind,
+            name,
+            section,
+            previous,
+        });
+    }
+    for (name, section) in old {
+        if !new.contains_key(name) {
+            changes.push(Change {
+                kind: ChangeKind::Removed,
+                name,
+                section,
+                previous: None,
+            });
+        }
+    }
+    changes.sort_by_key(|c| (c.kind, c.name));
+    changes
+}
+
+/// Link to an entry of the rendered documentation, if its URL is known.
+fn entry_link(base_url: Option<&str>, change: &Change) -> Option<String> {
+    let base = base_url?;
+    (change.kind != ChangeKind::Removed).then(|| format!("{}#{}", base, change.section.anchor))
+}
+
+/// Write the changes as a CommonMark list per kind of change.
+pub fn render_markdown(title: &str, changes: &[Change], base_url: Option<&str>) -> String {
+    let mut output = format!("# {}\n\n", title);
+    if changes.is_empty() {
+        output.push_str("No changes.\n");
+        return output;
+    }
+
+    let mut kind = None;
+    for change in changes {
+        if kind != Some(change.kind) {
+            if kind.is_some() {
+                output.push('\n');
+            }
+            let _ = write!(output, "## {}\n\n", change.kind.label());
+            kind = Some(change.kind);
+        }
+        match entry_link(base_url, change) {
+            Some(url) => {
+                let _ = write!(output, "- [`{}`]({})", change.name, url);
+            }
+            None => {
+                let _ = write!(output, "- `{}`", change.name);
+            }
+        }
+        if !change.section.summary.is_empty() {
+            let _ = write!(output, ": {}", change.section.summary);
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// The lines of `old` and `new` marked as removed (`-`), added (`+`) or
+/// kept (` `), keeping their longest common subsequence.
+pub fn line_diff<'t>(old: &'t str, new: &'t str) -> Vec<(char, &'t str)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    // lengths[i][j] is the length of the common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(a.len().max(b.len()));
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            lines.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+    lines.extend(a[i..].iter().map(|line| ('-', *line)));
+    lines.extend(b[j..].iter().map(|line| ('+', *line)));
+    lines
+}
+
+/// The old and new CommonMark of a change; empty for the missing side.
+fn sides<'a>(change: &Change<'a>) -> (&'a str, &'a str) {
+    match change.kind {
+        ChangeKind::Added => ("", &change.section.markdown),
+        ChangeKind::Removed => (&change.section.markdown, ""),
+        ChangeKind::Changed => (
+            change.previous.map_or("", |p| p.markdown.as_str()),
+            &change.section.markdown,
+        ),
+    }
+}
+
+/// Write each change as a heading and a `diff` code block of its section.
+pub fn render_diff_markdown(title: &str, changes: &[Change]) -> String {
+    let mut output = format!("# {}\n\n", title);
+    if changes.is_empty() {
+        output.push_str("No changes.\n");
+        return output;
+    }
+    for change in changes {
+        let (old, new) = sides(change);
+        let lines = line_diff(old, new);
+        // A fence longer than any backtick run in the sections.
+        let longest = lines
+            .iter()
+            .flat_map(|(_, line)| line.split(|c| c != '`'))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        let _ = write!(
+            output,
+            "## {} `{}`\n\n{}diff\n",
+            change.kind.label(),
+            change.name,
+            fence
+        );
+        for (mark, line) in lines {
+            let _ = writeln!(output, "{}{}", mark, line);
+        }
+        let _ = write!(output, "{}\n\n", fence);
+    }
+    output
+}
+
+/// CommonMark without `{#id}` heading attributes, so both renderings of an
+/// entry can be on one page.
+fn without_heading_ids(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(|line| match line.trim_end().strip_suffix('}') {
+            Some(rest) if line.trim_start().starts_with('#') => match rest.rfind(" {#") {
+                Some(start) => &line[..start],
+                None => line,
+            },
+            _ => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Write an HTML page showing, for each change, the old rendering of the
+/// entry next to the new one.
+pub fn render_diff_html(title: &str, changes: &[Change]) -> String {
+    let mut content = format!("<h1>{}</h1>\n", escape_html(title));
+    if changes.is_empty() {
+        content.push_str("<p>No changes.</p>\n");
+    }
+    for change in changes {
+        let (old, new) = sides(change);
+        let side = |label: &str, markdown: &str| {
+            let rendered = if markdown.is_empty() {
+                "<p><em>(none)</em></p>\n".to_string()
+            } else {
+                html::markdown_to_html(&without_heading_ids(markdown), true)
+            };
+            format!(
+                "<div class=\"diff-{}\">\n<p class=\"diff-label\">{}</p>\n{}</div>\n",
+                label.to_lowercase(),
+                label,
+                rendered
+            )
+        };
+        let _ = write!(
+            content,
+            "<section class=\"change change-{}\">\n<h2>{} <code>{}</code></h2>\n<div class=\"diff\">\n{}{}</div>\n</section>\n",
+            change.kind.label().to_lowercase(),
+            change.kind.label(),
+            escape_html(change.name),
+            side("Old", old),
+            side("New", new)
+        );
+    }
+    html::document(title, "", "", &content)
+}
+
+/// Metadata of an Atom feed.
+#[derive(Debug, Clone)]
+pub struct Feed<'a> {
+    pub title: &'a str,
+    /// Permanent id of the feed; also used to derive entry ids.
+    pub id: &'a str,
+    /// URL of the rendered documentation.
+    pub base_url: Option<&'a str>,
+    /// Label of the new revision.
+    pub revision: &'a str,
+    /// RFC 3339 timestamp of the new revision.
+    pub updated: &'a str,
+}
+
+/// Write the changes as an Atom feed with one entry per change.
+pub fn render_atom(feed: &Feed, changes: &[Change]) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    output.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(output, "  <title>{}</title>", xml_escape(feed.title));
+    let _ = writeln!(output, "  <id>{}</id>", xml_escape(feed.id));
+    let _ = writeln!(output, "  <updated>{}</updated>", xml_escape(feed.updated));
+    if let Some(url) = feed.base_url {
+        let _ = writeln!(output, "  <link href=\"{}\"/>", xml_escape(url));
+    }
+    let _ = writeln!(output, "  <author><name>docgen</name></author>");
+
+    for change in changes {
+        let label = change.kind.label();
+        output.push_str("  <entry>\n");
+        let _ = writeln!(
+            output,
+            "    <title>{} {}</title>",
+            label,
+            xml_escape(change.name)
+        );
+        let _ = writeln!(
+            output,
+            "    <id>{}</id>",
+            xml_escape(&format!(
+                "{}/{}/{}/{}",
+                feed.id.trim_end_matches('/'),
+                feed.revision,
+                label.to_lowercase(),
+                change.name
+            ))
+        );
+        let _ = writeln!(
+            output,
+            "    <updated>{}</updated>",
+            xml_escape(feed.updated)
+        );
+        let _ = writeln!(output, "    <category term=\"{}\"/>", label.to_lowercase());
+        if let Some(url) = entry_link(feed.base_url, change) {
+            let _ = writeln!(output, "    <link href=\"{}\"/>", xml_escape(&url));
+        }
+        if !change.section.summary.is_empty() {
+            let _ = writeln!(
+                output,
+                "    <summary>{}</summary>",
+                xml_escape(&change.section.summary)
+            );
+        }
+        let _ = writeln!(
+            output,
+            "    <content type=\"text\">{}</content>",
+            xml_escape(&change.section.markdown)
+        );
+        output.push_str("  </entry>\n");
+    }
+    output.push_str("</feed>\n");
+    output
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The current time as an RFC 3339 timestamp in UTC.
+pub fn now_rfc3339() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    rfc3339(secs)
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 timestamp in UTC.
+pub fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
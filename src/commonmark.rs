@@ -16,19 +16,46 @@
 //! This module implements CommonMark output for a struct
 //! representing a single entry in the manual.
 
-use serde::Serialize;
+use crate::doctest::{ASSERT_LANG, render_tables};
+use crate::format::{CodeLangs, check_template, expand_template, route_raw_blocks};
+use crate::html::escape_html;
+use crate::manifest::sha256_hex;
+use crate::sanitize::{HtmlPolicy, sanitize_html};
+use crate::signature::{Signature, SignatureParam};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Raw block formats (```` ```{=format} ````) whose contents can be passed
+/// through into CommonMark output. Raw blocks for other formats are dropped.
+pub const RAW_FORMATS: &[&str] = &["markdown", "commonmark", "html"];
 
 /// Represent a single function argument name and its (optional)
 /// doc-string.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SingleArg {
     pub name: String,
     pub doc: Option<String>,
 }
 
+impl SingleArg {
+    /// Write the argument as a definition list entry, falling back to a generic
+    /// description when it is undocumented.
+    fn write_definition(&self, output: &mut String) {
+        let doc = self.doc.as_deref().unwrap_or("Function argument");
+        let doc = route_raw_blocks(doc, RAW_FORMATS);
+        let _ = write!(
+            output,
+            "`{}`\n\n: {}\n\n",
+            self.name,
+            handle_indentation(doc.trim())
+        );
+    }
+}
+
 /// Represent a function argument, which is either a flat identifier
 /// or a pattern set.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Argument {
     /// Flat function argument (e.g. `n: n * 2`).
     Flat(SingleArg),
@@ -41,19 +68,13 @@ impl Argument {
     /// Write CommonMark structure for a single function argument.
     /// We use the definition list extension, which prepends each argument with `: `.
     /// For pattern arguments, we create a nested definition list.
-    fn format_argument(self) -> String {
+    fn write_argument(&self, output: &mut String) {
         match self {
             // Write a flat argument entry, e.g. `id = x: x`
             //
             // `x`
             // : Function argument
-            Argument::Flat(arg) => {
-                format!(
-                    "`{}`\n\n: {}\n\n",
-                    arg.name,
-                    handle_indentation(arg.doc.unwrap_or("Function argument".into()).trim())
-                )
-            }
+            Argument::Flat(arg) => arg.write_definition(output),
 
             // Write a pattern argument entry and its individual
             // parameters as a nested structure, e.g.:
@@ -66,18 +87,16 @@ impl Argument {
             Argument::Pattern(pattern_args) => {
                 let mut inner = String::new();
                 for pattern_arg in pattern_args {
-                    inner += &Argument::Flat(pattern_arg).format_argument();
+                    pattern_arg.write_definition(&mut inner);
                 }
 
                 let indented = textwrap::indent(&inner, "  ");
 
-                format!(
-                    // The `:` creates another definition list of which `indented` is the term.
-                    "structured function argument\n\n: {}",
-                    // drop leading indentation on the first line, the `: ` serves this function
-                    // already.
-                    indented.trim_start()
-                )
+                // The `:` creates another definition list of which `indented` is the term.
+                output.push_str("structured function argument\n\n: ");
+                // drop leading indentation on the first line, the `: ` serves this function
+                // already.
+                output.push_str(indented.trim_start());
             }
         }
     }
@@ -88,40 +107,55 @@ impl Argument {
 ///
 /// : first line
 ///   every other line
-fn handle_indentation(raw: &str) -> String {
+fn handle_indentation(raw: &str) -> Cow<'_, str> {
     match raw.split_once('\n') {
-        Some((first, rest)) => {
-            format!("{}\n{}", first, textwrap::indent(rest, "  "))
-        }
-        None => raw.into(),
+        Some((first, rest)) => Cow::Owned(format!("{}\n{}", first, textwrap::indent(rest, "  "))),
+        None => Cow::Borrowed(raw),
     }
 }
 
 /// Generate the identifier for CommonMark.
 /// ident is used as URL Encoded link to the function and has thus stricter rules (i.e. "' " in "lib.map' "  is not allowed).
-pub(crate) fn get_identifier(prefix: &String, category: &String, name: &String) -> String {
+pub fn get_identifier(prefix: &str, category: &str, name: &str) -> String {
     let name_prime = name.replace('\'', "-prime");
-    vec![prefix, category, &name_prime]
+    [prefix, category, &name_prime]
         .into_iter()
         .filter(|x| !x.is_empty())
-        .cloned()
-        .collect::<Vec<String>>()
+        .collect::<Vec<&str>>()
         .join(".")
 }
 
 /// Generate the title for CommonMark.
 /// the title is the human-readable name of the function.
-pub(crate) fn get_title(prefix: &String, category: &String, name: &String) -> String {
-    vec![prefix, category, name]
+pub fn get_title(prefix: &str, category: &str, name: &str) -> String {
+    [prefix, category, name]
         .into_iter()
         .filter(|x| !x.is_empty())
-        .cloned()
-        .collect::<Vec<String>>()
+        .collect::<Vec<&str>>()
         .join(".")
 }
 
+/// Placeholders of function anchor templates.
+pub const ANCHOR_PLACEHOLDERS: [&str; 4] = ["prefix", "category", "name", "ident"];
+
+/// Parse an `--anchor-prefix`: a prefix put before each entry's identifier,
+/// or a template using [`ANCHOR_PLACEHOLDERS`] (e.g. `fn-{name}`).
+pub fn parse_anchor_template(s: &str) -> Result<String, String> {
+    check_template(s, &ANCHOR_PLACEHOLDERS)
+}
+
+/// How the example of an entry is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExampleStyle {
+    /// An `{.example}` block, as Nixpkgs' manual renders them.
+    #[default]
+    Block,
+    /// A `<details>` element, collapsed until the reader opens it.
+    Collapsed,
+}
+
 /// Represents a single manual section describing a library function.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ManualEntry {
     /// Prefix for the category (e.g. 'lib' or 'utils').
     pub prefix: String,
@@ -146,44 +180,768 @@ pub struct ManualEntry {
     /// Usage example for the entry.
     pub example: Option<String>,
 
+    /// Call of the function to paste, from `--usage-snippets`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<String>,
+
     /// Arguments of the function.
     pub args: Vec<Argument>,
+
+    /// Structured parameters and type, for functions and entries with a
+    /// `# Type` section.
+    pub signature: Option<Signature>,
+
+    /// Whether the entry is a function or a constant.
+    pub kind: EntryKind,
+
+    /// Source of the value, for constants.
+    pub value: Option<String>,
+
+    /// Maturity from the `@stability` tag of the doc comment.
+    pub stability: Option<Stability>,
+
+    /// Deprecation from the `@deprecated` tag of the doc comment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+
+    /// Release the entry is available since, from the `@since` tag of the
+    /// doc comment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+
+    /// Related entries from the `@see` tags of the doc comment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub see_also: Vec<SeeAlso>,
+
+    /// What the function returns, from the `@return` tag of the doc comment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+
+    /// Whether the doc comment marks the binding as internal (see
+    /// [`INTERNAL_MARKER`]). Internal entries are only kept in JSON output.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub internal: bool,
+
+    /// Function whose docs the entry inherits, from the `@inheritDoc` tag of
+    /// the doc comment, until they are copied (see [`crate::inherit`]).
+    #[serde(skip)]
+    pub inherit_doc: Option<String>,
+
+    /// Anchor pinned by the doc comment, with an `@anchor` tag or a `{#id}`
+    /// line, in place of the one derived from the entry's name.
+    #[serde(rename = "anchor", skip_serializing_if = "Option::is_none")]
+    pub custom_anchor: Option<String>,
+
+    /// Topics from the `@tag` lines of the doc comment, for `--group-by tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Metadata from a `<name>Meta` sibling binding or the binding's
+    /// attribute in `__docsMeta`, as rendered values in source order.
+    #[serde(
+        default,
+        serialize_with = "meta_map",
+        deserialize_with = "meta_pairs",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub meta: Vec<(String, String)>,
+}
+
+fn meta_map<S: serde::Serializer>(meta: &[(String, String)], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_map(meta.iter().map(|(key, value)| (key, value)))
+}
+
+/// The pairs of a map written by [`meta_map`], in the order written.
+fn meta_pairs<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<(String, String)>, D::Error> {
+    struct Pairs;
+    impl<'de> serde::de::Visitor<'de> for Pairs {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map of strings")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> Result<Self::Value, A::Error> {
+            let mut pairs = vec![];
+            while let Some(pair) = map.next_entry()? {
+                pairs.push(pair);
+            }
+            Ok(pairs)
+        }
+    }
+    d.deserialize_map(Pairs)
+}
+
+/// `since` as `Since`, for the labels of metadata.
+fn meta_label(key: &str) -> String {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// What kind of binding an entry documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// A function, or any other value that is not a plain literal.
+    #[default]
+    Function,
+    /// A literal, or a small list or attribute set of literals.
+    Constant,
+}
+
+/// How safe an entry is to depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stability {
+    Stable,
+    Experimental,
+    Deprecated,
+}
+
+impl Stability {
+    pub const ALL: [Stability; 3] = [
+        Stability::Stable,
+        Stability::Experimental,
+        Stability::Deprecated,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stability::Stable => "stable",
+            Stability::Experimental => "experimental",
+            Stability::Deprecated => "deprecated",
+        }
+    }
+
+    /// Inline HTML badge, styled inline so it is colored without a
+    /// stylesheet and classed so a stylesheet can restyle it. Like the raw
+    /// HTML of doc comments, it is sanitized by `html`, if given: stripped,
+    /// just the tier is left.
+    pub fn badge(self, html: Option<&HtmlPolicy>) -> String {
+        let color = match self {
+            Stability::Stable => "#2da44e",
+            Stability::Experimental => "#bf8700",
+            Stability::Deprecated => "#cf222e",
+        };
+        let badge = format!(
+            "<span class=\"stability stability-{0}\" style=\"background:{1};color:#fff;border-radius:4px;padding:0 6px\">{0}</span>",
+            self.as_str(),
+            color
+        );
+        match html {
+            Some(policy) => sanitize_html(&badge, policy).into_owned(),
+            None => badge,
+        }
+    }
+}
+
+impl std::str::FromStr for Stability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Stability::ALL
+            .into_iter()
+            .find(|stability| stability.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown stability '{}' (expected experimental, stable or deprecated)",
+                    s
+                )
+            })
+    }
+}
+
+/// Remove the first line starting with `tag` from a doc comment, returning
+/// the rest of that line.
+fn take_tag<'a>(doc: &'a str, tag: &str) -> (Cow<'a, str>, Option<&'a str>) {
+    let (doc, line) = take_line(doc, |line| {
+        line.strip_prefix(tag)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    });
+    (doc, line.map(|line| line[tag.len()..].trim()))
+}
+
+/// Remove the first line of a doc comment that `matches`, given the line
+/// without its indentation, returning it trimmed.
+fn take_line<'a>(doc: &'a str, matches: impl Fn(&str) -> bool) -> (Cow<'a, str>, Option<&'a str>) {
+    let lines: Vec<&str> = doc.lines().collect();
+    let Some(i) = lines.iter().position(|line| matches(line.trim_start())) else {
+        return (Cow::Borrowed(doc), None);
+    };
+    let rest = lines[i].trim();
+    (Cow::Owned(remove_lines(lines, i..i + 1)), Some(rest))
+}
+
+/// Remove the first line starting with `tag` from a doc comment, and the
+/// lines after it indented deeper, returning their text after the tag with
+/// the lines trimmed.
+fn take_tag_block<'a>(doc: &'a str, tag: &str) -> (Cow<'a, str>, Option<String>) {
+    let lines: Vec<&str> = doc.lines().collect();
+    let Some(i) = lines.iter().position(|line| {
+        line.trim_start()
+            .strip_prefix(tag)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    }) else {
+        return (Cow::Borrowed(doc), None);
+    };
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let end = i
+        + 1
+        + lines[i + 1..]
+            .iter()
+            .take_while(|line| !line.trim().is_empty() && indent(line) > indent(lines[i]))
+            .count();
+    let text: Vec<&str> = lines[i..end].iter().map(|line| line.trim()).collect();
+    let text = text.join("\n")[tag.len()..].trim().to_string();
+    (Cow::Owned(remove_lines(lines, i..end)), Some(text))
+}
+
+/// Join `lines` without those in `range`, keeping a single blank line where
+/// they were a paragraph of their own.
+fn remove_lines(mut lines: Vec<&str>, range: std::ops::Range<usize>) -> String {
+    let i = range.start;
+    lines.drain(range);
+    let blank = |j: usize| lines.get(j).is_some_and(|l: &&str| l.trim().is_empty());
+    if (i == 0 || blank(i - 1)) && blank(i) {
+        lines.remove(i);
+    } else if i > 0 && i == lines.len() && blank(i - 1) {
+        lines.remove(i - 1);
+    }
+    lines.join("\n")
+}
+
+/// Marker of the doc comment of an internal binding, unless configured
+/// otherwise.
+pub const INTERNAL_MARKER: &str = "@internal";
+
+/// Remove the `marker` line from a doc comment, returning whether it had
+/// one.
+pub fn take_internal<'a>(doc: &'a str, marker: &str) -> (Cow<'a, str>, bool) {
+    let (doc, rest) = take_tag(doc, marker);
+    (doc, rest.is_some())
+}
+
+/// Remove the `@stability <tier>` line from a doc comment and parse it.
+pub fn take_stability(doc: &str) -> (Cow<'_, str>, Option<Result<Stability, String>>) {
+    let (doc, tier) = take_tag(doc, "@stability");
+    (doc, tier.map(str::parse))
+}
+
+/// Remove the `@since <release>` line from a doc comment and return the
+/// release, unless the tag names none.
+pub fn take_since(doc: &str) -> (Cow<'_, str>, Option<String>) {
+    let (doc, release) = take_tag(doc, "@since");
+    let release = release
+        .map(|release| release.trim_end_matches('.'))
+        .filter(|release| !release.is_empty());
+    (doc, release.map(String::from))
+}
+
+/// Remove the anchor pinned by a doc comment, with an `@anchor <id>` line or
+/// a line of its own `{#id}`, and return it.
+pub fn take_anchor(doc: &str) -> (Cow<'_, str>, Option<Result<String, String>>) {
+    let (doc, id) = match take_tag(doc, "@anchor") {
+        (taken, Some(id)) => (taken, Some(id)),
+        (_, None) => {
+            let (taken, line) = take_line(doc, |line| {
+                let line = line.trim_end();
+                line.starts_with("{#") && line.ends_with('}')
+            });
+            (taken, line.map(|line| &line[2..line.len() - 1]))
+        }
+    };
+    let id = id.map(|id| {
+        if !id.is_empty() && !id.contains(|c: char| c.is_whitespace() || "#{}".contains(c)) {
+            Ok(id.to_string())
+        } else {
+            Err(format!("invalid anchor '{}'", id))
+        }
+    });
+    (doc, id)
+}
+
+/// Remove the `@inheritDoc <function>` line from a doc comment and return the
+/// function, unless the tag names none.
+pub fn take_inherit_doc(doc: &str) -> (Cow<'_, str>, Option<String>) {
+    let (doc, name) = take_tag(doc, crate::inherit::TAG);
+    let name = name
+        .map(|name| name.trim_matches('`'))
+        .filter(|name| !name.is_empty());
+    (doc, name.map(String::from))
+}
+
+/// Argument docs written in the doc comment of a function rather than on
+/// its parameters, with `@param name description` and `@return description`
+/// tags.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParamDocs {
+    /// Docs of the arguments by name, in the order written.
+    pub params: Vec<(String, String)>,
+    pub returns: Option<String>,
+}
+
+impl ParamDocs {
+    /// The doc of the argument `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, doc)| doc.as_str())
+    }
+}
+
+/// Remove the `@param` and `@return` (or `@returns`) lines from a doc
+/// comment, each with the lines after it indented deeper.
+pub fn take_param_docs(doc: &str) -> (Cow<'_, str>, ParamDocs) {
+    // The description may be set off from the name by `-` or `:`.
+    let description = |text: &str| text.trim_start_matches(['-', ':']).trim().to_string();
+    let mut doc = Cow::Borrowed(doc);
+    let mut docs = ParamDocs::default();
+    loop {
+        let (rest, param) = take_tag_block(&doc, "@param");
+        let Some(param) = param else {
+            break;
+        };
+        let (name, text) = param
+            .split_once(char::is_whitespace)
+            .unwrap_or((&param, ""));
+        let name = name.trim_end_matches(':').trim_matches('`');
+        if !name.is_empty() {
+            docs.params.push((name.to_string(), description(text)));
+        }
+        doc = Cow::Owned(rest.into_owned());
+    }
+    for tag in ["@returns", "@return"] {
+        let (rest, returns) = take_tag_block(&doc, tag);
+        if let Some(returns) = returns {
+            docs.returns = Some(description(&returns)).filter(|r| !r.is_empty());
+            doc = Cow::Owned(rest.into_owned());
+        }
+    }
+    (doc, docs)
+}
+
+/// A related function, from a `@see lib.lists.map` tag.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeeAlso {
+    /// The function as written after `@see`.
+    pub name: String,
+    /// Link to its documentation, once resolved (see [`crate::see`]).
+    pub url: Option<String>,
+}
+
+impl SeeAlso {
+    /// The reference as a markdown link, or as code if it is unresolved.
+    pub fn link(&self) -> String {
+        match &self.url {
+            Some(url) => format!("[`{}`]({})", self.name, url),
+            None => format!("`{}`", self.name),
+        }
+    }
+}
+
+/// Remove the `@see` lines from a doc comment, each naming one or more
+/// functions separated by commas.
+pub fn take_see_also(doc: &str) -> (Cow<'_, str>, Vec<SeeAlso>) {
+    let mut doc = Cow::Borrowed(doc);
+    let mut see_also = vec![];
+    loop {
+        let (rest, names) = take_tag(&doc, "@see");
+        let Some(names) = names else {
+            break;
+        };
+        see_also.extend(
+            names
+                .split(',')
+                .map(|name| name.trim().trim_matches('`'))
+                .filter(|name| !name.is_empty())
+                .map(|name| SeeAlso {
+                    name: name.to_string(),
+                    url: None,
+                }),
+        );
+        doc = Cow::Owned(rest.into_owned());
+    }
+    (doc, see_also)
+}
+
+/// Remove the `@tag` lines from a doc comment, each naming one or more tags
+/// separated by commas, and return the tags in the order written.
+pub fn take_tags(doc: &str) -> (Cow<'_, str>, Vec<String>) {
+    let mut doc = Cow::Borrowed(doc);
+    let mut tags: Vec<String> = vec![];
+    loop {
+        let (rest, names) = take_tag(&doc, "@tag");
+        let Some(names) = names else {
+            break;
+        };
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if !tags.iter().any(|tag| tag == name) {
+                tags.push(name.to_string());
+            }
+        }
+        doc = Cow::Owned(rest.into_owned());
+    }
+    (doc, tags)
+}
+
+/// Why and since when an entry is deprecated, from the
+/// `@deprecated since 24.05, use lib.foo instead` tag of its doc comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Release the entry is deprecated since.
+    pub since: Option<String>,
+    /// What to do instead, as markdown.
+    pub note: Option<String>,
+}
+
+impl Deprecation {
+    /// Parse the text after `@deprecated`: an optional `since <release>`,
+    /// then the note, separated by a comma or colon.
+    pub fn parse(text: &str) -> Deprecation {
+        let text = text.trim();
+        let (since, note) = match text.strip_prefix("since ") {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == ',' || c == ':')
+                    .unwrap_or(rest.len());
+                let since = rest[..end].trim_end_matches('.');
+                (Some(since.to_string()), &rest[end..])
+            }
+            None => (None, text),
+        };
+        let note = note.trim_start_matches([',', ':']).trim();
+        Deprecation {
+            since: since.filter(|s| !s.is_empty()),
+            note: Some(note.to_string()).filter(|n| !n.is_empty()),
+        }
+    }
+
+    /// The warning as one line of markdown, e.g. "**Deprecated** since
+    /// 24.05: use lib.foo instead".
+    pub fn message(&self) -> String {
+        let mut message = "**Deprecated**".to_string();
+        if let Some(since) = &self.since {
+            let _ = write!(message, " since {}", since);
+        }
+        if let Some(note) = &self.note {
+            let _ = write!(message, ": {}", note);
+        }
+        message
+    }
+}
+
+/// Remove the `@deprecated` line from a doc comment and parse it.
+pub fn take_deprecation(doc: &str) -> (Cow<'_, str>, Option<Deprecation>) {
+    let (doc, text) = take_tag(doc, "@deprecated");
+    (doc, text.map(Deprecation::parse))
+}
+
+/// Write a table listing the entries of each stability tier, if any entry
+/// has one, with their badges sanitized by `html` (see [`Stability::badge`]).
+pub fn write_stability_table(
+    entries: &[ManualEntry],
+    category: &str,
+    anchor_prefix: &str,
+    html: Option<&HtmlPolicy>,
+    output: &mut String,
+) {
+    if entries.iter().all(|e| e.stability.is_none()) {
+        return;
+    }
+    let _ = write!(
+        output,
+        "## Stability {{#sec-functions-library-{}-stability}}\n\n| Stability | Functions |\n| --- | --- |\n",
+        category
+    );
+    for stability in Stability::ALL {
+        let links: Vec<String> = entries
+            .iter()
+            .filter(|e| e.stability == Some(stability))
+            .map(|e| {
+                let (_, title) = e.get_ident_title();
+                format!("[`{}`](#{})", title, e.anchor(anchor_prefix))
+            })
+            .collect();
+        if !links.is_empty() {
+            let _ = writeln!(
+                output,
+                "| {} | {} |",
+                stability.badge(html),
+                links.join(", ")
+            );
+        }
+    }
+    output.push('\n');
 }
 
 impl ManualEntry {
-    pub(crate) fn get_ident_title(&self) -> (String, String) {
+    pub fn get_ident_title(&self) -> (String, String) {
         let ident = get_identifier(&self.prefix, &self.category, &self.name);
         let title = get_title(&self.prefix, &self.category, &self.name);
         (ident, title)
     }
 
+    /// Anchor of the entry's section: `anchor` followed by the identifier,
+    /// or, if `anchor` is a template, the template with its placeholders
+    /// filled in. `'` in `{name}` is replaced like in the identifier. An
+    /// anchor pinned by the doc comment is used as it is.
+    pub fn anchor(&self, anchor: &str) -> String {
+        if let Some(custom) = &self.custom_anchor {
+            return custom.clone();
+        }
+        let ident = get_identifier(&self.prefix, &self.category, &self.name);
+        if !anchor.contains('{') {
+            return format!("{}{}", anchor, ident);
+        }
+        expand_template(
+            anchor,
+            &[
+                ("prefix", &self.prefix),
+                ("category", &self.category),
+                ("name", &self.name.replace('\'', "-prime")),
+                ("ident", &ident),
+            ],
+        )
+    }
+
+    /// Anchor of the entry's usage example.
+    pub fn example_anchor(&self, anchor: &str) -> String {
+        if anchor.contains('{') || self.custom_anchor.is_some() {
+            format!("example-{}", self.anchor(anchor))
+        } else {
+            let (ident, _) = self.get_ident_title();
+            format!("{}example-{}", anchor, ident)
+        }
+    }
+
+    /// Hex-encoded SHA-256 digest of what the entry documents: its title,
+    /// doc comment, type, parameters, argument docs, example, value,
+    /// stability, deprecation, release, related functions, return value,
+    /// internal marker, tags and metadata. It only changes when one of them does, not with the
+    /// entry's location, anchor or the docgen version, so it can key caches
+    /// of rendered entries.
+    pub fn content_hash(&self) -> String {
+        let (_, title) = self.get_ident_title();
+        let fn_type = self
+            .signature
+            .as_ref()
+            .and_then(|signature| signature.fn_type.as_ref())
+            .map(|ty| ty.text.clone())
+            .or_else(|| self.fn_type.clone());
+        let params: Vec<String> = self
+            .signature
+            .iter()
+            .flat_map(|signature| &signature.params)
+            .map(|param| match param {
+                SignatureParam::Positional { name } => name.clone(),
+                SignatureParam::Pattern {
+                    attrs,
+                    ellipsis,
+                    bind,
+                } => {
+                    let mut attrs: Vec<String> = attrs
+                        .iter()
+                        .map(|attr| match &attr.default {
+                            Some(default) => format!("{} ? {}", attr.name, default),
+                            None => attr.name.clone(),
+                        })
+                        .collect();
+                    if *ellipsis {
+                        attrs.push("...".to_string());
+                    }
+                    let bind = bind.as_deref().map_or(String::new(), |b| format!("@{}", b));
+                    format!("{{ {} }}{}", attrs.join(", "), bind)
+                }
+            })
+            .collect();
+        let meta: Vec<String> = self
+            .meta
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        let description = self.description.join("\n\n");
+        let params = params.join("\n");
+        let meta = meta.join("\n");
+        let mut parts = vec![
+            title.as_str(),
+            &description,
+            fn_type.as_deref().unwrap_or_default(),
+            &params,
+            self.example.as_deref().unwrap_or_default(),
+            self.value.as_deref().unwrap_or_default(),
+            self.stability.map_or("", Stability::as_str),
+            &meta,
+        ];
+        // Only deprecated entries hash a deprecation, only documented
+        // arguments their docs, and only entries with `@since`, `@see`,
+        // `@return`, `@internal` or `@tag` the text of those tags, keeping the
+        // digests of the others as they were.
+        let arg_docs: Vec<String> = self
+            .args
+            .iter()
+            .flat_map(|arg| match arg {
+                Argument::Flat(arg) => std::slice::from_ref(arg),
+                Argument::Pattern(args) => args.as_slice(),
+            })
+            .filter_map(|arg| Some(format!("arg {} {}", arg.name, arg.doc.as_ref()?)))
+            .collect();
+        parts.extend(arg_docs.iter().map(String::as_str));
+        let deprecated = self.deprecated.as_ref().map(Deprecation::message);
+        parts.extend(deprecated.as_deref());
+        let since = self.since.as_ref().map(|since| format!("since {}", since));
+        parts.extend(since.as_deref());
+        let see_also: Vec<String> = self
+            .see_also
+            .iter()
+            .map(|see| format!("see {}", see.name))
+            .collect();
+        parts.extend(see_also.iter().map(String::as_str));
+        let returns = self
+            .returns
+            .as_ref()
+            .map(|returns| format!("returns {}", returns));
+        parts.extend(returns.as_deref());
+        if self.internal {
+            parts.push(INTERNAL_MARKER);
+        }
+        let tags: Vec<String> = self.tags.iter().map(|tag| format!("tag {}", tag)).collect();
+        parts.extend(tags.iter().map(String::as_str));
+        // Fields are separated by NUL, which cannot occur in them.
+        sha256_hex(parts.join("\0").as_bytes())
+    }
+
+    /// First sentence of the description, without code markup.
+    pub fn summary(&self) -> String {
+        let first = self
+            .description
+            .iter()
+            .map(|p| p.trim())
+            .find(|p| !p.is_empty() && !p.starts_with('#'))
+            .unwrap_or_default();
+        let first = first.lines().collect::<Vec<_>>().join(" ");
+        let sentence = match first.find(". ") {
+            Some(end) => &first[..=end],
+            None => first.as_str(),
+        };
+        sentence.replace('`', "")
+    }
+
     /// Write a single CommonMark entry for a documented Nix function.
     ///
     /// # Arguments
     ///
-    /// - `anchor_prefix`: The prefix to use for the anchor links.
+    /// - `anchor_prefix`: The prefix to use for the anchor links, or an
+    ///   anchor template (see [`ManualEntry::anchor`]).
     ///   In Nixpkgs this would be "function-library-".
-    /// - `output`: The output string to append the CommonMark onto.
-    pub fn write_section(self, anchor_prefix: &str, output: &mut String) -> String {
-        let (ident, title) = self.get_ident_title();
-        output.push_str(&format!(
-            "## `{}` {{#{}{}}}\n\n",
-            title, anchor_prefix, ident
-        ));
+    /// - `code_langs`: Language tags for the usage example's code fence.
+    ///
+    /// Everything is written straight into `output`, so rendering a whole library
+    /// reuses a single growing buffer instead of allocating per section.
+    pub fn write_section(&self, anchor_prefix: &str, code_langs: &CodeLangs, output: &mut String) {
+        self.write_section_with(anchor_prefix, code_langs, ExampleStyle::Block, None, output);
+    }
+
+    /// Write a single CommonMark entry like [`ManualEntry::write_section`],
+    /// with its example in the given style and its stability badge sanitized
+    /// by `html` (see [`Stability::badge`]).
+    pub fn write_section_with(
+        &self,
+        anchor_prefix: &str,
+        code_langs: &CodeLangs,
+        examples: ExampleStyle,
+        html: Option<&HtmlPolicy>,
+        output: &mut String,
+    ) {
+        let (_, title) = self.get_ident_title();
+        let _ = write!(
+            output,
+            "## `{}` {{#{}}}\n\n",
+            title,
+            self.anchor(anchor_prefix)
+        );
+
+        // Deprecated entries have the warning below instead.
+        if let Some(stability) = self.stability.filter(|_| self.deprecated.is_none()) {
+            let _ = write!(output, "**Stability**: {}\n\n", stability.badge(html));
+        }
+
+        if let Some(since) = &self.since {
+            let _ = write!(output, "**Since**: {}\n\n", since);
+        }
+
+        if let Some(deprecated) = &self.deprecated {
+            let _ = write!(
+                output,
+                "::: {{.warning}}\n{}\n:::\n\n",
+                deprecated.message()
+            );
+        }
+
+        for (key, value) in &self.meta {
+            let _ = write!(output, "**{}**: {}\n\n", meta_label(key), value);
+        }
 
         // <subtitle> (type signature)
         if let Some(t) = &self.fn_type {
-            if t.lines().count() > 1 {
-                output.push_str(&format!("**Type**:\n```\n{}\n```\n\n", t));
+            if t.lines().nth(1).is_some() {
+                let _ = write!(output, "**Type**:\n```\n{}\n```\n\n", t);
             } else {
-                output.push_str(&format!("**Type**: `{}`\n\n", t));
+                let _ = write!(output, "**Type**: `{}`\n\n", t);
             }
         }
 
+        if let Some(usage) = &self.usage {
+            let _ = write!(
+                output,
+                "**Usage**:\n{}\n{}\n```\n\n",
+                code_langs.fence(),
+                usage
+            );
+        }
+
         // Primary doc string
         // TODO: Split paragraphs?
-        for paragraph in &self.description {
-            output.push_str(&format!("{}\n\n", paragraph));
+        if self
+            .description
+            .iter()
+            .any(|p| p.contains("{=") || p.contains(ASSERT_LANG))
+        {
+            // Raw blocks and assertion blocks may span several paragraphs, so
+            // render them on the whole description.
+            let description = self.description.join("\n\n");
+            let description = render_tables(&description);
+            output.push_str(route_raw_blocks(&description, RAW_FORMATS).trim_end());
+            output.push_str("\n\n");
+        } else {
+            for paragraph in &self.description {
+                output.push_str(paragraph);
+                output.push_str("\n\n");
+            }
+        }
+
+        if let Some(value) = &self.value {
+            if value.contains('\n') || value.contains('`') {
+                let _ = write!(
+                    output,
+                    "**Value**:\n{}\n{}\n```\n\n",
+                    code_langs.fence(),
+                    value
+                );
+            } else {
+                let _ = write!(output, "**Value**: `{}`\n\n", value);
+            }
         }
 
         // Function argument names - only output if the description doesn't already
@@ -192,28 +950,52 @@ impl ManualEntry {
             p.contains("# Arguments") || p.contains("## Arguments") || p.contains("### Arguments")
         });
         if !self.args.is_empty() && !has_args_section {
-            for arg in self.args {
-                output.push_str(&format!("{}\n", arg.format_argument()));
+            for arg in &self.args {
+                arg.write_argument(output);
+                output.push('\n');
             }
         }
 
-        // Example program listing (if applicable)
-        //
-        // TODO: In grhmc's version there are multiple (named)
-        // examples, how can this be achieved automatically?
-        if let Some(example) = &self.example {
-            output.push_str(&format!(
-                "::: {{.example #{}example-{}}}\n",
-                anchor_prefix, ident
-            ));
-            output.push_str(&format!("# `{}` usage example\n\n", title));
-            output.push_str(&format!("```nix\n{}\n```\n:::\n\n", example.trim()));
+        if let Some(returns) = &self.returns {
+            let _ = write!(output, "**Returns**: {}\n\n", returns);
+        }
+
+        // Example program listing (if applicable), after the description
+        // whether it was written in a `# Examples` section or not.
+        match (&self.example, examples) {
+            (None, _) => {}
+            (Some(example), ExampleStyle::Block) => {
+                let _ = write!(
+                    output,
+                    "::: {{.example #{}}}\n# `{}` usage example\n\n{}\n{}\n```\n:::\n\n",
+                    self.example_anchor(anchor_prefix),
+                    title,
+                    code_langs.fence(),
+                    example.trim()
+                );
+            }
+            (Some(example), ExampleStyle::Collapsed) => {
+                let _ = write!(
+                    output,
+                    "<details id=\"{}\">\n<summary><code>{}</code> usage example</summary>\n\n{}\n{}\n```\n\n</details>\n\n",
+                    self.example_anchor(anchor_prefix),
+                    escape_html(&title),
+                    code_langs.fence(),
+                    example.trim()
+                );
+            }
         }
 
-        if let Some(loc) = self.location {
-            output.push_str(&String::from(format!("Located at {loc}.\n\n")));
+        if !self.see_also.is_empty() {
+            output.push_str("**See also**:\n\n");
+            for see in &self.see_also {
+                let _ = writeln!(output, "- {}", see.link());
+            }
+            output.push('\n');
         }
 
-        output.to_string()
+        if let Some(loc) = &self.location {
+            let _ = write!(output, "Located at {loc}.\n\n");
+        }
     }
 }
@@ -0,0 +1,333 @@
+//! Project configuration read from `docgen.toml`.
+//!
+//! The `visibility` section controls which bindings are documented:
+//!
+//! ```toml
+//! [visibility]
+//! # Applies to every input.
+//! hide = ["_*", "**.internal"]
+//!
+//! [visibility.inputs."lib/strings.nix"]
+//! hide = ["escape*"]
+//! include = ["escapeShellArg"]
+//! ```
+//!
+//! Patterns are matched against a binding's attribute path within its file
+//! and against its full title (`lib.strings.escapeShellArg`). `*` matches
+//! within one path component, `**` across components, `?` a single
+//! character and `[...]` one of the characters of a class (`[abc]`, `[a-z]`,
+//! or, negated, `[!a-z]`); `\` makes the character after it literal. A
+//! binding is hidden if it matches a `hide` pattern and no `include`
+//! pattern. `include` also adds matching `let` bindings to the ones selected
+//! with `--export`.
+//!
+//! Bindings whose doc comment has an `@internal` line are hidden as well,
+//! unless kept in JSON output with `--keep-internal`. `internal_marker`
+//! names another marker to use in its place, globally or for an input:
+//!
+//! ```toml
+//! [visibility]
+//! internal_marker = "@private"
+//! ```
+//!
+//! The `options` section sets the headings of the options document, for the
+//! `options` and `module` commands; flags given on the command line win:
+//!
+//! ```toml
+//! [options]
+//! title = "Module Options ({count})"
+//! group_heading = "{group}"
+//! group_depth = 2
+//! option_heading = "`{name}` ({type})"
+//! ```
+//!
+//! The `headings` section translates the headings docgen writes, such as
+//! those of `--group-by` and "Other functions", for each `--lang`. Keys are
+//! the English headings; tag headings are keyed as written:
+//!
+//! ```toml
+//! [headings.de]
+//! "Other functions" = "Weitere Funktionen"
+//! "String manipulation" = "Zeichenketten"
+//! ```
+//!
+//! The `navigation` section sets the reading order of the pages of the
+//! `manifest` and `html` commands, by category; see [`crate::nav`]:
+//!
+//! ```toml
+//! [navigation]
+//! order = ["trivial", "lists", "strings"]
+//! ```
+//!
+//! The `checks` section sets what the `precommit` command checks of the
+//! files it is given. Nix files matching `files` (all by default) fail on
+//! warnings about their doc comments, unless `allow_warnings` is set, and
+//! on documenting less than `min_coverage` of their bindings; the options
+//! of modules matching `modules`, read without evaluation, and of options
+//! JSON files matching `options` fail on lint warnings. Patterns match
+//! paths relative to the working directory; a file matching several lists
+//! is checked as an options file, then a module, before a Nix file:
+//!
+//! ```toml
+//! [checks]
+//! files = ["lib/**/*.nix"]
+//! modules = ["modules/**/*.nix"]
+//! options = ["options.json"]
+//! min_coverage = 0.8
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the configuration file picked up from the working directory.
+pub const CONFIG_FILE: &str = "docgen.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub options: OptionsSection,
+    #[serde(default)]
+    pub navigation: NavigationSection,
+    #[serde(default)]
+    pub checks: ChecksSection,
+    /// Translations of generated headings, by language and English heading.
+    #[serde(default)]
+    pub headings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Reading order of multi-page output.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NavigationSection {
+    /// Categories in reading order.
+    #[serde(default)]
+    pub order: Vec<String>,
+}
+
+/// Checks of the `precommit` command.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChecksSection {
+    /// Nix files whose doc comments are checked.
+    #[serde(default = "all_nix_files")]
+    pub files: Vec<String>,
+    /// Modules whose options are linted.
+    #[serde(default)]
+    pub modules: Vec<String>,
+    /// Options JSON files whose options are linted.
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Least share of the bindings of a file that must be documented, from
+    /// 0 to 1.
+    pub min_coverage: Option<f64>,
+    /// Whether warnings about doc comments pass.
+    #[serde(default)]
+    pub allow_warnings: bool,
+}
+
+fn all_nix_files() -> Vec<String> {
+    vec!["**/*.nix".to_string()]
+}
+
+impl Default for ChecksSection {
+    fn default() -> Self {
+        ChecksSection {
+            files: all_nix_files(),
+            modules: vec![],
+            options: vec![],
+            min_coverage: None,
+            allow_warnings: false,
+        }
+    }
+}
+
+/// How the `precommit` command checks a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// Doc comments and coverage of a Nix file.
+    File,
+    /// Lints of the options of a module.
+    Module,
+    /// Lints of the options of an options JSON file.
+    Options,
+}
+
+impl ChecksSection {
+    /// How the file at `path`, relative to the working directory, is
+    /// checked, if it is.
+    pub fn kind_of(&self, path: &str) -> Option<CheckKind> {
+        let path = path.trim_start_matches("./");
+        let matches = |patterns: &[String]| patterns.iter().any(|p| glob_match_file(p, path));
+        if matches(&self.options) {
+            Some(CheckKind::Options)
+        } else if matches(&self.modules) {
+            Some(CheckKind::Module)
+        } else if matches(&self.files) {
+            Some(CheckKind::File)
+        } else {
+            None
+        }
+    }
+}
+
+/// Headings of the options document.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptionsSection {
+    pub title: Option<String>,
+    pub group_heading: Option<String>,
+    pub group_depth: Option<usize>,
+    pub option_heading: Option<String>,
+}
+
+/// Visibility rules for all inputs, and for particular inputs.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Visibility {
+    #[serde(default)]
+    pub hide: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Marker of internal bindings in place of `@internal`.
+    pub internal_marker: Option<String>,
+    /// Rules for inputs whose path ends with the key.
+    #[serde(default)]
+    pub inputs: BTreeMap<String, Rules>,
+}
+
+/// Hide and include patterns.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    #[serde(default)]
+    pub hide: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Marker of internal bindings in place of `@internal`.
+    pub internal_marker: Option<String>,
+}
+
+impl Config {
+    /// Read and parse a configuration file.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+    }
+}
+
+impl Visibility {
+    /// The global rules combined with those for `file`.
+    pub fn rules_for(&self, file: &Path) -> Rules {
+        let mut rules = Rules {
+            hide: self.hide.clone(),
+            include: self.include.clone(),
+            internal_marker: self.internal_marker.clone(),
+        };
+        for (input, input_rules) in &self.inputs {
+            if file.ends_with(input.trim_start_matches("./")) {
+                rules.hide.extend(input_rules.hide.iter().cloned());
+                rules.include.extend(input_rules.include.iter().cloned());
+                if input_rules.internal_marker.is_some() {
+                    rules.internal_marker = input_rules.internal_marker.clone();
+                }
+            }
+        }
+        rules
+    }
+}
+
+impl Rules {
+    /// Whether a binding with this attribute path and title is explicitly
+    /// included.
+    pub fn includes(&self, name: &str, title: &str) -> bool {
+        matches_any(&self.include, name, title)
+    }
+
+    /// Whether a binding with this attribute path and title is documented.
+    pub fn is_visible(&self, name: &str, title: &str) -> bool {
+        !matches_any(&self.hide, name, title) || self.includes(name, title)
+    }
+}
+
+fn matches_any(patterns: &[String], name: &str, title: &str) -> bool {
+    patterns
+        .iter()
+        .any(|p| glob_match(p, name) || glob_match(p, title))
+}
+
+/// Match an attribute path against a glob pattern.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob_match_chars(&pattern, &path, '.')
+}
+
+/// Match a relative file path with `/` separators against a glob pattern.
+/// As in shells, `**/` also matches no directory at all.
+pub fn glob_match_file(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob_match_chars(&pattern, &path, '/')
+}
+
+fn glob_match_chars(pattern: &[char], path: &[char], sep: char) -> bool {
+    if let ['[', class @ ..] = pattern
+        && let Some((matches, rest)) = match_class(class, path.first(), sep)
+    {
+        return matches && glob_match_chars(rest, &path[1..], sep);
+    }
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] if sep == '/' => (0..=path.len())
+            .filter(|&i| i == 0 || path[i - 1] == '/')
+            .any(|i| glob_match_chars(rest, &path[i..], sep)),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| glob_match_chars(rest, &path[i..], sep)),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != sep)
+            .any(|i| glob_match_chars(rest, &path[i..], sep)),
+        ['?', rest @ ..] => {
+            !path.is_empty() && path[0] != sep && glob_match_chars(rest, &path[1..], sep)
+        }
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            path.first() == Some(c) && glob_match_chars(rest, &path[1..], sep)
+        }
+    }
+}
+
+/// Match `c` against the character class at the start of `class`, after its
+/// `[`. Returns whether it matches and the pattern after the class, or
+/// `None` if the class is not closed, so its `[` is literal. A class never
+/// matches the separator.
+fn match_class<'a>(class: &'a [char], c: Option<&char>, sep: char) -> Option<(bool, &'a [char])> {
+    let (negated, class) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    // A `]` first in the class is one of its characters.
+    let end = 1 + class.get(1..)?.iter().position(|&c| c == ']')?;
+    let (items, rest) = (&class[..end], &class[end + 1..]);
+    let Some(&c) = c.filter(|&&c| c != sep) else {
+        return Some((false, rest));
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < items.len() {
+        match items[i..] {
+            [from, '-', to, ..] => {
+                found |= (from..=to).contains(&c);
+                i += 3;
+            }
+            _ => {
+                found |= items[i] == c;
+                i += 1;
+            }
+        }
+    }
+    Some((found != negated, rest))
+}
@@ -0,0 +1,169 @@
+//! DevDocs documentation set output.
+//!
+//! DevDocs (<https://devdocs.io>) loads a documentation set from a directory
+//! containing:
+//!
+//! - `index.json`: the searchable entries and the types (groups) they belong to,
+//! - `db.json`: a map from page path to the page's HTML,
+//! - `meta.json`: the name and slug of the set.
+//!
+//! Every page is also written as a standalone `<path>.html` file so the bundle
+//! can be inspected or served without DevDocs. Standalone pages are complete
+//! documents with a skip link, a navigation landmark listing the page's
+//! entries and a `main` landmark holding the content.
+//!
+//! In both, headings never skip a level and each heading with an id ends
+//! with a self-link, which can be reached with the keyboard.
+
+use crate::html::{self, escape_html};
+use crate::lunr::IndexDocument;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// One page of the documentation set, typically a function category.
+#[derive(Debug)]
+pub struct Page {
+    /// Path of the page within the set, without extension (e.g. `strings`).
+    pub path: String,
+    /// Type (group) the page's entries are listed under in DevDocs.
+    pub type_name: String,
+    /// CommonMark source of the page.
+    pub markdown: String,
+    /// Searchable entries on this page as (name, anchor) pairs.
+    pub entries: Vec<(String, String)>,
+    /// The entries as documents of the search index of an HTML site, linked
+    /// to `<path>.html`.
+    pub documents: Vec<IndexDocument>,
+}
+
+#[derive(Serialize)]
+struct Index<'a> {
+    entries: Vec<IndexEntry<'a>>,
+    types: Vec<IndexType<'a>>,
+}
+
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    name: &'a str,
+    path: String,
+    #[serde(rename = "type")]
+    type_name: &'a str,
+}
+
+#[derive(Serialize)]
+struct IndexType<'a> {
+    name: &'a str,
+    count: usize,
+    slug: String,
+}
+
+/// Render CommonMark as HTML, keeping `{#id}` heading anchors as ids and
+/// leaving code blocks to DevDocs' own highlighting.
+pub fn markdown_to_html(markdown: &str) -> String {
+    html::markdown_to_html(markdown, false)
+}
+
+/// A complete HTML document for a page: a skip link to the content, the
+/// page's entries as navigation, and the content as the main landmark.
+pub fn standalone_page(title: &str, entries: &[(String, String)], content: &str) -> String {
+    let mut nav = String::new();
+    for (name, anchor) in entries {
+        let _ = writeln!(
+            nav,
+            "<li><a href=\"#{}\"><code>{}</code></a></li>",
+            escape_html(anchor),
+            escape_html(name)
+        );
+    }
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+</head>
+<body>
+<a class="skip-link" href="#main">Skip to content</a>
+<nav aria-label="Entries">
+<ul>
+{nav}</ul>
+</nav>
+<main id="main">
+{content}</main>
+</body>
+</html>
+"##,
+        title = escape_html(title),
+    )
+}
+
+/// Turn a name into a DevDocs slug (lowercase, `_` for anything else).
+pub fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Write the documentation set for `pages` into `dir`.
+pub fn write_bundle(dir: &Path, name: &str, pages: &[Page]) -> Result<(), String> {
+    let write = |file: &str, contents: String| {
+        let path = dir.join(file);
+        fs::write(&path, contents).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    };
+    fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+
+    let mut db = BTreeMap::new();
+    let mut entries = Vec::new();
+    let mut type_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for page in pages {
+        let page_html = markdown_to_html(&page.markdown);
+        let title = format!("{} - {}", page.type_name, name);
+        write(
+            &format!("{}.html", page.path),
+            standalone_page(&title, &page.entries, &page_html),
+        )?;
+        db.insert(page.path.as_str(), page_html);
+
+        *type_counts.entry(&page.type_name).or_default() += page.entries.len();
+        entries.extend(page.entries.iter().map(|(entry_name, anchor)| IndexEntry {
+            name: entry_name,
+            path: format!("{}#{}", page.path, anchor),
+            type_name: &page.type_name,
+        }));
+    }
+
+    let index = Index {
+        entries,
+        types: type_counts
+            .into_iter()
+            .map(|(name, count)| IndexType {
+                name,
+                count,
+                slug: slugify(name),
+            })
+            .collect(),
+    };
+    let meta = serde_json::json!({
+        "name": name,
+        "slug": slugify(name),
+        "type": "simple",
+    });
+
+    let json_err = |e: serde_json::Error| format!("Problem converting to JSON: {}", e);
+    write(
+        "index.json",
+        serde_json::to_string(&index).map_err(json_err)?,
+    )?;
+    write("db.json", serde_json::to_string(&db).map_err(json_err)?)?;
+    write("meta.json", serde_json::to_string(&meta).map_err(json_err)?)
+}
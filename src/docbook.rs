@@ -0,0 +1,355 @@
+//! DocBook 5 output.
+//!
+//! The generated CommonMark is converted as a whole, for projects still
+//! building their manual with the DocBook toolchain:
+//!
+//! - headings open nested `<section>` elements, with the `{#id}` of the
+//!   heading as `xml:id`,
+//! - `::: {.example #id}` blocks become `<example>` elements titled by their
+//!   heading, and `note`, `tip`, `important`, `warning` and `caution`
+//!   blocks the admonitions of the same name,
+//! - code blocks become `<programlisting>` elements, definition lists
+//!   `<variablelist>` elements and tables `<informaltable>` elements,
+//! - links to `#anchor` become `linkend` links, other links `xlink:href`
+//!   links.
+//!
+//! HTML, inline or as blocks, is dropped. A document that is a single
+//! section has that section as its root element; anything else is wrapped
+//! in an `<article>`.
+
+use crate::format::{get_fence, trim_leading_whitespace};
+use crate::html::escape_html as escape;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::fmt::Write as _;
+
+const NAMESPACES: &str = " xmlns=\"http://docbook.org/ns/docbook\" \
+xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"5.0\"";
+
+/// `:::` blocks with an element of their own.
+const BLOCKS: [&str; 6] = ["example", "note", "tip", "important", "warning", "caution"];
+
+/// ` xml:id="id"`, if there is an id.
+fn id_attr(id: Option<&str>) -> String {
+    id.map(|id| format!(" xml:id=\"{}\"", escape(id)))
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Heading levels of the open sections.
+    sections: Vec<usize>,
+    /// Elements of the open `:::` blocks.
+    blocks: Vec<&'static str>,
+    /// Number of top-level sections.
+    roots: usize,
+    /// Set if there is content outside all sections.
+    loose: bool,
+    /// Depth of the open elements holding inline content.
+    inline: usize,
+    /// Set while a `<para>` opened for bare inline content is open.
+    implicit_para: bool,
+    /// For each open definition list, whether it has an open entry.
+    entries: Vec<bool>,
+    code: Option<String>,
+}
+
+impl Writer {
+    /// Start a block element, closing a `<para>` opened for inline content.
+    fn block(&mut self, tag: &str) {
+        self.end_inline();
+        if self.sections.is_empty() && self.blocks.is_empty() {
+            self.loose = true;
+        }
+        self.out.push_str(tag);
+    }
+
+    fn end_inline(&mut self) {
+        if std::mem::take(&mut self.implicit_para) {
+            self.out.push_str("</para>\n");
+        }
+    }
+
+    /// Make sure inline content is written inside an element allowing it.
+    fn inline(&mut self) {
+        if self.inline == 0 && !self.implicit_para {
+            self.block("<para>");
+            self.implicit_para = true;
+        }
+    }
+
+    fn open_section(&mut self, level: usize, id: Option<&str>) {
+        self.end_inline();
+        while self.sections.last().is_some_and(|&open| open >= level) {
+            self.sections.pop();
+            self.out.push_str("</section>\n");
+        }
+        if self.sections.is_empty() {
+            self.roots += 1;
+        }
+        self.sections.push(level);
+        let _ = write!(self.out, "<section{}>\n<title>", id_attr(id));
+        self.inline += 1;
+    }
+
+    fn open_block(&mut self, attrs: &str) {
+        let mut class = None;
+        let mut id = None;
+        for attr in attrs.trim_matches(['{', '}']).split_whitespace() {
+            match attr.chars().next() {
+                Some('.') => class = class.or(Some(&attr[1..])),
+                Some('#') => id = Some(&attr[1..]),
+                _ if !attr.contains('=') => class = class.or(Some(attr)),
+                _ => {}
+            }
+        }
+        let element = BLOCKS
+            .iter()
+            .find(|&&block| Some(block) == class)
+            .copied()
+            .unwrap_or("blockquote");
+        let id = id.filter(|_| element != "blockquote");
+        self.block(&format!("<{}{}>\n", element, id_attr(id)));
+        self.blocks.push(element);
+    }
+
+    fn close_block(&mut self) {
+        if let Some(element) = self.blocks.pop() {
+            self.end_inline();
+            let _ = writeln!(self.out, "</{}>", element);
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some(code) = &mut self.code {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let code = self.code.take().unwrap_or_default();
+                    self.out.push_str(&escape(code.trim_end_matches('\n')));
+                    self.out.push_str("</programlisting>\n");
+                }
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                self.block("<para>");
+                self.inline += 1;
+            }
+            Event::End(TagEnd::Paragraph) => {
+                self.inline -= 1;
+                self.out.push_str("</para>\n");
+            }
+            // Headings of `:::` blocks are their titles.
+            Event::Start(Tag::Heading { .. }) if !self.blocks.is_empty() => {
+                self.block("<title>");
+                self.inline += 1;
+            }
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                self.open_section(level as usize, id.as_deref())
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                self.inline -= 1;
+                self.out.push_str("</title>\n");
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                if lang.is_empty() {
+                    self.block("<programlisting>");
+                } else {
+                    self.block(&format!("<programlisting language=\"{}\">", escape(&lang)));
+                }
+                self.code = Some(String::new());
+            }
+            Event::Start(Tag::List(None)) => self.block("<itemizedlist>\n"),
+            Event::Start(Tag::List(Some(1))) => self.block("<orderedlist>\n"),
+            Event::Start(Tag::List(Some(start))) => {
+                self.block(&format!("<orderedlist startingnumber=\"{}\">\n", start))
+            }
+            Event::End(TagEnd::List(ordered)) => {
+                self.end_inline();
+                let tag = if ordered {
+                    "orderedlist"
+                } else {
+                    "itemizedlist"
+                };
+                let _ = writeln!(self.out, "</{}>", tag);
+            }
+            Event::Start(Tag::Item) => self.block("<listitem>\n"),
+            Event::End(TagEnd::Item) => {
+                self.end_inline();
+                self.out.push_str("</listitem>\n");
+            }
+            Event::Start(Tag::BlockQuote(_)) => self.block("<blockquote>\n"),
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.end_inline();
+                self.out.push_str("</blockquote>\n");
+            }
+            Event::Start(Tag::DefinitionList) => {
+                self.block("<variablelist>\n");
+                self.entries.push(false);
+            }
+            Event::End(TagEnd::DefinitionList) => {
+                if self.entries.pop() == Some(true) {
+                    self.out.push_str("</varlistentry>\n");
+                }
+                self.out.push_str("</variablelist>\n");
+            }
+            Event::Start(Tag::DefinitionListTitle) => {
+                if let Some(entry) = self.entries.last_mut()
+                    && std::mem::replace(entry, true)
+                {
+                    self.out.push_str("</varlistentry>\n");
+                }
+                self.out.push_str("<varlistentry>\n<term>");
+                self.inline += 1;
+            }
+            Event::End(TagEnd::DefinitionListTitle) => {
+                self.inline -= 1;
+                self.out.push_str("</term>\n");
+            }
+            Event::Start(Tag::DefinitionListDefinition) => self.out.push_str("<listitem>\n"),
+            Event::End(TagEnd::DefinitionListDefinition) => {
+                self.end_inline();
+                self.out.push_str("</listitem>\n");
+            }
+            Event::Start(Tag::Table(alignments)) => self.block(&format!(
+                "<informaltable>\n<tgroup cols=\"{}\">\n",
+                alignments.len()
+            )),
+            Event::End(TagEnd::Table) => {
+                self.out.push_str("</tbody>\n</tgroup>\n</informaltable>\n");
+            }
+            Event::Start(Tag::TableHead) => self.out.push_str("<thead>\n<row>\n"),
+            Event::End(TagEnd::TableHead) => self.out.push_str("</row>\n</thead>\n<tbody>\n"),
+            Event::Start(Tag::TableRow) => self.out.push_str("<row>\n"),
+            Event::End(TagEnd::TableRow) => self.out.push_str("</row>\n"),
+            Event::Start(Tag::TableCell) => {
+                self.out.push_str("<entry>");
+                self.inline += 1;
+            }
+            Event::End(TagEnd::TableCell) => {
+                self.inline -= 1;
+                self.out.push_str("</entry>\n");
+            }
+            Event::Start(Tag::Emphasis) => {
+                self.inline();
+                self.out.push_str("<emphasis>");
+            }
+            Event::End(TagEnd::Emphasis) => self.out.push_str("</emphasis>"),
+            Event::Start(Tag::Strong) => {
+                self.inline();
+                self.out.push_str("<emphasis role=\"strong\">");
+            }
+            Event::End(TagEnd::Strong) => self.out.push_str("</emphasis>"),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.inline();
+                let _ = match dest_url.strip_prefix('#') {
+                    Some(anchor) => write!(self.out, "<link linkend=\"{}\">", escape(anchor)),
+                    None => write!(self.out, "<link xlink:href=\"{}\">", escape(&dest_url)),
+                };
+            }
+            Event::End(TagEnd::Link) => self.out.push_str("</link>"),
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.inline();
+                let _ = write!(
+                    self.out,
+                    "<inlinemediaobject><imageobject><imagedata fileref=\"{}\"/></imageobject><textobject><phrase>",
+                    escape(&dest_url)
+                );
+            }
+            Event::End(TagEnd::Image) => self
+                .out
+                .push_str("</phrase></textobject></inlinemediaobject>"),
+            Event::Code(code) => {
+                self.inline();
+                let _ = write!(self.out, "<literal>{}</literal>", escape(&code));
+            }
+            Event::Text(text) => {
+                self.inline();
+                self.out.push_str(&escape(&text));
+            }
+            Event::SoftBreak | Event::HardBreak => self.out.push('\n'),
+            _ => {}
+        }
+    }
+
+    fn convert(&mut self, markdown: &str) {
+        let options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_DEFINITION_LIST;
+        for event in Parser::new_ext(markdown, options) {
+            self.event(event);
+        }
+        self.end_inline();
+    }
+}
+
+/// `text` as a `<?docgen ?>` processing instruction, which unlike a comment
+/// may contain `--`.
+pub fn comment(text: &str) -> String {
+    format!("<?docgen\n{}?>\n", text.replace("?>", "? >"))
+}
+
+/// Convert CommonMark, as generated by docgen, to a DocBook 5 document.
+pub fn markdown_to_docbook(markdown: &str) -> String {
+    let mut writer = Writer::default();
+    let mut chunk = String::new();
+    let mut fence: Option<(usize, char)> = None;
+    for line in markdown.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(attrs) = fence_line.strip_prefix(":::") {
+                    let attrs = attrs.trim_start_matches(':').trim();
+                    writer.convert(&std::mem::take(&mut chunk));
+                    if attrs.is_empty() {
+                        writer.close_block();
+                    } else {
+                        writer.open_block(attrs);
+                    }
+                    continue;
+                }
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+            }
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    writer.convert(&chunk);
+    while !writer.blocks.is_empty() {
+        writer.close_block();
+    }
+    for _ in writer.sections.drain(..) {
+        writer.out.push_str("</section>\n");
+    }
+
+    let mut document = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    match writer.out.strip_prefix("<section") {
+        Some(rest) if writer.roots == 1 && !writer.loose => {
+            let _ = write!(document, "<section{}{}", NAMESPACES, rest);
+        }
+        _ => {
+            let _ = write!(
+                document,
+                "<article{}>\n{}</article>\n",
+                NAMESPACES, writer.out
+            );
+        }
+    }
+    document
+}
@@ -0,0 +1,266 @@
+//! Examples written as assertions.
+//!
+//! A `nix-assert` code block holds `expression => expected` pairs, one per
+//! line. An expression can span several lines; the pair ends on the line
+//! with `=>` outside strings and comments:
+//!
+//! ````markdown
+//! ```nix-assert
+//! concatStrings [ "foo" "bar" ] => "foobar"
+//! concatMapStrings (x: x + "-")
+//!   [ "a" "b" ] => "a-b-"
+//! ```
+//! ````
+//!
+//! The block is rendered as a table of expressions and results, and
+//! `docgen doctest` evaluates both sides of each pair with `nix eval` and
+//! checks that they are equal. Both sides are evaluated with the attributes
+//! of the documented file in scope; a file that is a function is called with
+//! `lib` from `<nixpkgs/lib>`, as its argument (`lib: { ... }`) or as an
+//! attribute of it if it takes one (`{ lib, ... }: { ... }`).
+
+use crate::format::{get_fence, trim_leading_whitespace};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::Command;
+
+/// Info string of assertion blocks.
+pub const ASSERT_LANG: &str = "nix-assert";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    pub expr: String,
+    pub expected: String,
+}
+
+/// A block of assertions, or a line of ordinary markdown.
+enum Part<'a> {
+    Line(&'a str),
+    Block(Vec<Assertion>),
+}
+
+/// The string a line of Nix ends in, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quote {
+    None,
+    /// `"..."`
+    Double,
+    /// `''...''`
+    Indented,
+}
+
+/// Position of the `=>` of `line` outside strings and comments, starting
+/// within `quote` and updating it to where the line ends.
+fn find_arrow(line: &str, quote: &mut Quote) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        match *quote {
+            Quote::None => match rest {
+                [b'=', b'>', ..] => return Some(i),
+                [b'#', ..] => return None,
+                [b'"', ..] => *quote = Quote::Double,
+                [b'\'', b'\'', ..] => {
+                    *quote = Quote::Indented;
+                    i += 1;
+                }
+                _ => {}
+            },
+            Quote::Double => match rest {
+                [b'\\', _, ..] => i += 1,
+                [b'"', ..] => *quote = Quote::None,
+                _ => {}
+            },
+            Quote::Indented => match rest {
+                // `'''`, `''$` and `''\` are escapes within the string.
+                [b'\'', b'\'', b'\'' | b'$' | b'\\', ..] => i += 2,
+                [b'\'', b'\'', ..] => {
+                    *quote = Quote::None;
+                    i += 1;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    None
+}
+
+fn parse_parts(text: &str) -> Vec<Part<'_>> {
+    let mut parts = vec![];
+    let mut lines = text.split('\n');
+    while let Some(line) = lines.next() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        let Some((count, ch)) = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3)
+        else {
+            parts.push(Part::Line(line));
+            continue;
+        };
+        if fence_line.trim_start_matches(ch).trim() != ASSERT_LANG {
+            parts.push(Part::Line(line));
+            // Skip the contents of other code blocks.
+            for inner in lines.by_ref() {
+                parts.push(Part::Line(inner));
+                let inner_fence = trim_leading_whitespace(inner, 3);
+                if get_fence(&inner_fence, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let mut assertions = vec![];
+        let mut expr: Vec<&str> = vec![];
+        let mut quote = Quote::None;
+        for inner in lines.by_ref() {
+            let inner_fence = trim_leading_whitespace(inner, 3);
+            if get_fence(&inner_fence, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                break;
+            }
+            match find_arrow(inner, &mut quote) {
+                Some(arrow) => {
+                    expr.push(&inner[..arrow]);
+                    // Lines are kept as they are, as indented strings
+                    // depend on their indentation.
+                    assertions.push(Assertion {
+                        expr: expr.join("\n").trim().to_string(),
+                        expected: inner[arrow + 2..].trim().to_string(),
+                    });
+                    expr.clear();
+                    quote = Quote::None;
+                }
+                None => expr.push(inner),
+            }
+        }
+        parts.push(Part::Block(assertions));
+    }
+    parts
+}
+
+/// All assertions in a markdown text.
+pub fn assertions(text: &str) -> Vec<Assertion> {
+    parse_parts(text)
+        .into_iter()
+        .flat_map(|part| match part {
+            Part::Block(assertions) => assertions,
+            Part::Line(_) => vec![],
+        })
+        .collect()
+}
+
+/// Inline code that survives backticks and table pipes in `text`.
+fn table_code(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.replace('|', "\\|");
+    if text.contains('`') {
+        format!("`` {} ``", text)
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+/// Replace assertion blocks by tables of expressions and results.
+pub fn render_tables(text: &str) -> Cow<'_, str> {
+    if !text.contains(ASSERT_LANG) {
+        return Cow::Borrowed(text);
+    }
+    let mut lines: Vec<String> = vec![];
+    for part in parse_parts(text) {
+        match part {
+            Part::Line(line) => lines.push(line.to_string()),
+            Part::Block(assertions) => {
+                let mut table = String::from("| Expression | Result |\n| --- | --- |");
+                for a in &assertions {
+                    let _ = write!(
+                        table,
+                        "\n| {} | {} |",
+                        table_code(&a.expr),
+                        table_code(&a.expected)
+                    );
+                }
+                lines.push(table);
+            }
+        }
+    }
+    Cow::Owned(lines.join("\n"))
+}
+
+/// Outcome of evaluating an assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The sides differ; holds the JSON of the actual value if it has one.
+    Failed(Option<String>),
+}
+
+/// Nix expression comparing both sides of an assertion in the scope of
+/// `file`, which must be an absolute path.
+pub fn assertion_expr(file: &Path, assertion: &Assertion) -> String {
+    let path = file
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${");
+    format!(
+        r#"let
+  file = import (/. + "{path}");
+  lib = import <nixpkgs/lib>;
+  scope =
+    if !builtins.isFunction file then
+      file
+    else if builtins.functionArgs file == {{ }} then
+      file lib
+    else
+      file (builtins.intersectAttrs (builtins.functionArgs file) {{ inherit lib; }});
+  actual = with scope; (
+{expr}
+  );
+  expected = with scope; (
+{expected}
+  );
+  json = builtins.tryEval (builtins.toJSON actual);
+in
+{{
+  equal = actual == expected;
+  actual = if json.success then json.value else null;
+}}
+"#,
+        expr = assertion.expr,
+        expected = assertion.expected,
+    )
+}
+
+/// Evaluate an assertion with `nix eval`.
+pub fn evaluate(file: &Path, assertion: &Assertion, nix: &str) -> Result<Outcome, String> {
+    let file = file
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    let result = Command::new(nix)
+        .args([
+            "--extra-experimental-features",
+            "nix-command",
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+        ])
+        .arg(assertion_expr(&file, assertion))
+        .output()
+        .map_err(|e| format!("Error running {}: {}", nix, e))?;
+    if !result.status.success() {
+        return Err(String::from_utf8_lossy(&result.stderr)
+            .trim_end()
+            .to_string());
+    }
+    let value: serde_json::Value = serde_json::from_slice(&result.stdout)
+        .map_err(|e| format!("unexpected nix eval output: {}", e))?;
+    if value["equal"] == serde_json::Value::Bool(true) {
+        Ok(Outcome::Passed)
+    } else {
+        Ok(Outcome::Failed(
+            value["actual"].as_str().map(str::to_string),
+        ))
+    }
+}
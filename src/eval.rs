@@ -0,0 +1,50 @@
+//! Binding names of a file evaluated with `nix eval`.
+//!
+//! This is the fallback for files whose returned attribute set cannot be
+//! found statically, such as one built with `lib.genAttrs` or merged from
+//! several imports. A file that is a function is called with the arguments
+//! it declares, `lib` being `<nixpkgs/lib>`; the other arguments need
+//! defaults.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Arguments to `nix` listing the attribute names of `file`.
+pub fn attr_names_args(file: &Path) -> Vec<String> {
+    [
+        "--extra-experimental-features",
+        "nix-command",
+        "eval",
+        "--json",
+        "--impure",
+        "--file",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .chain([
+        file.to_string_lossy().into_owned(),
+        "--arg".to_string(),
+        "lib".to_string(),
+        "import <nixpkgs/lib>".to_string(),
+        "--apply".to_string(),
+        "builtins.attrNames".to_string(),
+    ])
+    .collect()
+}
+
+/// Evaluate `file` with `nix eval` and list the names of the attributes it
+/// returns.
+pub fn attr_names(file: &Path, nix: &str) -> Result<Vec<String>, String> {
+    let result = Command::new(nix)
+        .args(attr_names_args(file))
+        .output()
+        .map_err(|e| format!("Error running {}: {}", nix, e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "nix eval failed for {}:\n{}",
+            file.display(),
+            String::from_utf8_lossy(&result.stderr).trim_end()
+        ));
+    }
+    serde_json::from_slice(&result.stdout).map_err(|e| format!("nix eval output: {}", e))
+}
@@ -0,0 +1,120 @@
+//! Options of NixOS modules exported by a flake.
+//!
+//! The module is evaluated with `nix eval` on its own, without the rest of
+//! NixOS: `lib` comes from the flake's `nixpkgs` input (or `<nixpkgs/lib>`),
+//! and `pkgs` is the input's package set if there is one, and otherwise a
+//! stub holding only `lib` and `system`. Option definitions are not checked,
+//! so a module can set options it does not declare. The result has the
+//! layout of the options JSON read by the `options` command, with
+//! declarations relative to the flake.
+
+use std::path::Path;
+use std::process::Command;
+
+/// A flake output such as `.#nixosModules.myservice`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlakeOutput {
+    /// Flake reference; local paths are made absolute.
+    pub flake: String,
+    /// Attribute path of the module within the flake's outputs.
+    pub attr: Vec<String>,
+}
+
+impl std::str::FromStr for FlakeOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (flake, attr) = s
+            .split_once('#')
+            .ok_or_else(|| format!("expected FLAKE#ATTR, got '{}'", s))?;
+        let attr: Vec<String> = attr.split('.').map(|a| a.to_string()).collect();
+        if attr.iter().any(|a| a.is_empty()) {
+            return Err(format!("invalid attribute path in '{}'", s));
+        }
+        let flake = if flake.is_empty() { "." } else { flake };
+        let flake = if flake.starts_with('.') || flake.starts_with('/') {
+            Path::new(flake)
+                .canonicalize()
+                .map_err(|e| format!("{}: {}", flake, e))?
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            flake.to_string()
+        };
+        Ok(FlakeOutput { flake, attr })
+    }
+}
+
+/// Quote a string as a Nix string literal.
+fn nix_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Nix expression evaluating to the options JSON of the module.
+pub fn options_expr(output: &FlakeOutput) -> String {
+    let attr: Vec<String> = output.attr.iter().map(|a| nix_string(a)).collect();
+    format!(
+        r#"let
+  flake = builtins.getFlake {flake};
+  lib = flake.inputs.nixpkgs.lib or (import <nixpkgs/lib>);
+  system = builtins.currentSystem;
+  pkgs = flake.inputs.nixpkgs.legacyPackages.${{system}} or {{ inherit lib system; }};
+  module = lib.getAttrFromPath [ {attr} ] flake;
+  eval = lib.evalModules {{
+    modules = [
+      module
+      {{
+        _module.check = false;
+        _module.args.pkgs = lib.mkDefault pkgs;
+      }}
+    ];
+  }};
+  root = "${{flake.outPath}}/";
+  relative = decl: lib.removePrefix root (toString decl);
+  docs = lib.filter (opt: (opt.visible or true) != false && !(opt.internal or false)) (
+    lib.optionAttrSetToDocList eval.options
+  );
+in
+builtins.listToAttrs (
+  map (opt: {{
+    inherit (opt) name;
+    value = removeAttrs opt [ "name" "visible" "internal" ] // {{
+      declarations = map relative opt.declarations;
+    }};
+  }}) docs
+)
+"#,
+        flake = nix_string(&output.flake),
+        attr = attr.join(" "),
+    )
+}
+
+/// Evaluate the options of a flake's module with `nix eval`.
+pub fn eval_options(output: &FlakeOutput, nix: &str) -> Result<String, String> {
+    let result = Command::new(nix)
+        .args([
+            "--extra-experimental-features",
+            "nix-command flakes",
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+        ])
+        .arg(options_expr(output))
+        .output()
+        .map_err(|e| format!("Error running {}: {}", nix, e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "nix eval failed for {}#{}:\n{}",
+            output.flake,
+            output.attr.join("."),
+            String::from_utf8_lossy(&result.stderr).trim_end()
+        ));
+    }
+    String::from_utf8(result.stdout).map_err(|e| format!("nix eval output: {}", e))
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use textwrap::dedent;
 
 /// Ensure all lines in a multi-line doc-comments have the same indentation.
@@ -16,7 +17,7 @@ use textwrap::dedent;
 ///
 /// The parser turns this into:
 ///
-/// ```
+/// ```text
 /// foo is
 ///   the value:
 ///     10
@@ -28,7 +29,7 @@ use textwrap::dedent;
 ///
 /// What we want instead is:
 ///
-/// ```
+/// ```text
 /// foo is
 /// the value:
 ///   10
@@ -63,10 +64,24 @@ pub fn handle_indentation(raw: &str) -> Option<String> {
 /// H6 -> H6
 ///
 pub fn shift_headings(raw: &str, levels: usize) -> String {
-    let mut result = String::new();
+    shift_headings_with_warnings(raw, levels).0
+}
+
+/// Like [shift_headings], but also reports input that could only be handled
+/// by degrading gracefully, as human-readable warnings.
+///
+/// - Lines starting with more than six `#` are not headings in CommonMark and
+///   are left as they are.
+/// - Headings that would end up below H6 are clamped to H6.
+/// - A code fence that is never closed extends to the end of the input, so
+///   nothing after it is shifted.
+pub fn shift_headings_with_warnings(raw: &str, levels: usize) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(raw.len());
+    let mut warnings = Vec::new();
 
     let mut curr_fence: Option<(usize, char)> = None;
-    for raw_line in raw.split_inclusive('\n') {
+    let mut fence_start = 0;
+    for (line_no, raw_line) in raw.split_inclusive('\n').enumerate() {
         // Code blocks can only start with backticks or tildes
         // code fences can be indented by 0-3 spaces see commonmark spec.
         let fence_line = &trim_leading_whitespace(raw_line, 3);
@@ -75,19 +90,19 @@ pub fn shift_headings(raw: &str, levels: usize) -> String {
             if curr_fence.is_none() {
                 // Start of code block
                 curr_fence = fence_info;
+                fence_start = line_no + 1;
             } else {
                 // Possible end of code block. Ending fences cannot have info strings
-                match (curr_fence, get_fence(fence_line, false)) {
-                    // End of code block must have the same fence type as the start (~~~ or ```)
-                    // Code blocks must be ended with at least the same number of backticks or tildes as the start fence
-                    (Some((start_count, start_char)), Some((end_count, end_char))) => {
-                        if start_count <= end_count && start_char == end_char {
-                            // End of code block (same fence as start)
-                            curr_fence = None;
-                        }
-                    }
-                    _ => {}
-                };
+                // End of code block must have the same fence type as the start (~~~ or ```)
+                // Code blocks must be ended with at least the same number of backticks or tildes as the start fence
+                if let (Some((start_count, start_char)), Some((end_count, end_char))) =
+                    (curr_fence, get_fence(fence_line, false))
+                    && start_count <= end_count
+                    && start_char == end_char
+                {
+                    // End of code block (same fence as start)
+                    curr_fence = None;
+                }
             }
         }
 
@@ -95,19 +110,78 @@ pub fn shift_headings(raw: &str, levels: usize) -> String {
         // If the line has 4 or more whitespaces it is not a heading according to commonmark spec.
         let heading_line = &trim_leading_whitespace(raw_line, 3);
         if curr_fence.is_none() && heading_line.starts_with('#') {
-            let heading = handle_heading(heading_line, levels);
-            result.push_str(&heading);
+            if heading_line.starts_with("#######") {
+                warnings.push(format!(
+                    "line {}: more than 6 '#' is not a heading, left unchanged",
+                    line_no + 1
+                ));
+                result.push_str(raw_line);
+            } else {
+                let level = heading_line.len() - heading_line.trim_start_matches('#').len();
+                let shifted = level.saturating_add(levels);
+                let is_heading = heading_line[level..].starts_with([' ', '\t', '\r', '\n'])
+                    || heading_line.len() == level;
+                if shifted > 6 && is_heading {
+                    warnings.push(format!(
+                        "line {}: H{} shifted by {} would be H{}, clamped to H6",
+                        line_no + 1,
+                        level,
+                        levels,
+                        shifted
+                    ));
+                }
+                result.push_str(&handle_heading(heading_line, levels));
+            }
         } else {
             result.push_str(raw_line);
         }
     }
-    result
+
+    if curr_fence.is_some() {
+        warnings.push(format!(
+            "line {}: code fence is never closed, headings after it were not shifted",
+            fence_start
+        ));
+    }
+
+    (result, warnings)
+}
+
+/// A `:::` line of a fenced div, such as `::: {.example #id}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivFence<'a> {
+    /// A line opening a block, with its classes and id.
+    Open {
+        classes: Vec<&'a str>,
+        id: Option<&'a str>,
+    },
+    /// A line closing the innermost block.
+    Close,
+}
+
+/// The fenced div line `line`, without its indentation, if it is one. Its
+/// attributes are written `{.class #id key=value}`, or as a bare class.
+pub fn div_fence(line: &str) -> Option<DivFence<'_>> {
+    let attrs = line.strip_prefix(":::")?.trim_start_matches(':').trim();
+    if attrs.is_empty() {
+        return Some(DivFence::Close);
+    }
+    let words: Vec<&str> = attrs.trim_matches(['{', '}']).split_whitespace().collect();
+    let classes = words
+        .iter()
+        .filter_map(|w| {
+            w.strip_prefix('.')
+                .or((!w.starts_with('#') && !w.contains('=')).then_some(*w))
+        })
+        .collect();
+    let id = words.iter().find_map(|w| w.strip_prefix('#'));
+    Some(DivFence::Open { classes, id })
 }
 
 /// Removes leading whitespaces from code fences if present
-/// However maximum of [max] whitespaces are removed.
+/// However maximum of `max` whitespaces are removed.
 /// This is useful for code fences may have leading whitespaces (0-3).
-fn trim_leading_whitespace(input: &str, max: usize) -> String {
+pub fn trim_leading_whitespace(input: &str, max: usize) -> String {
     let mut count = 0;
     input
         .trim_start_matches(|c: char| {
@@ -121,29 +195,23 @@ fn trim_leading_whitespace(input: &str, max: usize) -> String {
         .to_string()
 }
 /// A function that returns the count of a code fence line.
-/// Param [allow_info] allows to keep info strings in code fences.
-/// Ending fences cannot have info strings
+/// Param `allow_info` allows to keep info strings in code fences.
+/// Ending fences cannot have info strings, only trailing whitespace
+/// (including the `\r` of CRLF line endings).
 pub fn get_fence(line: &str, allow_info: bool) -> Option<(usize, char)> {
-    let mut chars = line.chars();
-    if let Some(first_char) = chars.next() {
-        if first_char == '`' || first_char == '~' {
-            let mut count = 1;
-            for ch in chars {
-                if ch == first_char {
-                    // count the number of repeated code fence characters
-                    count += 1;
-                } else {
-                    if !allow_info && ch != '\n' {
-                        // info string is not allowed this is not a code fence
-                        return None;
-                    }
-                    return Some((count, first_char));
-                }
-            }
-            return Some((count, first_char));
-        }
+    let first_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let rest = line.trim_start_matches(first_char);
+    let count = line.len() - rest.len();
+    if first_char == '`' && allow_info && rest.contains('`') {
+        // Info strings of backtick fences cannot contain backticks, this is
+        // inline code such as ```foo```
+        return None;
+    }
+    if !allow_info && !rest.trim().is_empty() {
+        // info string is not allowed this is not a code fence
+        return None;
     }
-    None
+    Some((count, first_char))
 }
 // Dumb heading parser.
 pub fn handle_heading(line: &str, levels: usize) -> String {
@@ -160,11 +228,672 @@ pub fn handle_heading(line: &str, levels: usize) -> String {
             _ => rest.push(char),
         }
     }
-    let new_hashes = match hashes.len() + levels {
-        // We reached the maximum heading size.
-        6.. => "#".repeat(6),
-        _ => "#".repeat(hashes.len() + levels),
-    };
+    // We reached the maximum heading size.
+    let new_hashes = "#".repeat(hashes.len().saturating_add(levels).min(6));
 
     format!("{new_hashes}{rest}")
 }
+
+/// Resolve raw blocks for a particular output backend.
+///
+/// A raw block is a fenced code block whose info string is `{=format}`:
+///
+/// ````markdown
+/// ```{=html}
+/// <details><summary>More</summary>…</details>
+/// ```
+/// ````
+///
+/// Being fenced, its contents are already left alone by heading shifting and
+/// HTML sanitization. When rendering, blocks whose format is in `formats` are
+/// replaced by their contents verbatim; all other raw blocks are removed, as
+/// they are meant for a different backend. Ordinary code blocks are kept.
+pub fn route_raw_blocks<'a>(raw: &'a str, formats: &[&str]) -> Cow<'a, str> {
+    if !raw.contains("{=") {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut result = String::with_capacity(raw.len());
+    // The open fence, and for raw blocks whether their contents are kept.
+    let mut curr_fence: Option<((usize, char), Option<bool>)> = None;
+    let mut skip_blank = false;
+    for raw_line in raw.split_inclusive('\n') {
+        let fence_line = trim_leading_whitespace(raw_line, 3);
+        match curr_fence {
+            None => {
+                if let Some(fence) = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3) {
+                    let info = fence_line[fence.0..].trim();
+                    let raw_format = info
+                        .strip_prefix("{=")
+                        .and_then(|f| f.strip_suffix('}'))
+                        .map(|f| {
+                            formats
+                                .iter()
+                                .any(|name| name.eq_ignore_ascii_case(f.trim()))
+                        });
+                    curr_fence = Some((fence, raw_format));
+                    if raw_format.is_some() {
+                        continue;
+                    }
+                } else if skip_blank && fence_line.trim().is_empty() {
+                    skip_blank = false;
+                    continue;
+                }
+                skip_blank = false;
+                result.push_str(raw_line);
+            }
+            Some(((start_count, start_char), raw_format)) => {
+                let closes = get_fence(&fence_line, false)
+                    .is_some_and(|(count, ch)| ch == start_char && count >= start_count);
+                if closes {
+                    curr_fence = None;
+                }
+                match raw_format {
+                    // Dropping a block should not leave two blank lines behind.
+                    Some(false) if closes => {
+                        skip_blank = result.is_empty() || result.ends_with("\n\n")
+                    }
+                    Some(false) => {}
+                    Some(true) if closes => {}
+                    _ => result.push_str(raw_line),
+                }
+            }
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// How links are emitted in generated CommonMark.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LinkStyle {
+    /// `[text](url)`
+    #[default]
+    Inline,
+    /// `[text][label]`, with `[label]: url` collected at the end of the section
+    Reference,
+}
+
+/// The level of an ATX heading line, if it is one.
+pub fn heading_level(line: &str) -> Option<usize> {
+    let line = trim_leading_whitespace(line, 3);
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(char::is_whitespace)))
+        .then_some(level)
+}
+
+/// Take the `Example` or `Examples` section out of a description, up to the
+/// next heading of the same or a higher level, and return its code.
+///
+/// The section is only taken when it is made of Nix code blocks, untagged or
+/// tagged `nix`, with nothing but `:::` container lines and headings around
+/// them; several blocks are joined with a blank line. Sections with prose or
+/// other blocks, such as `nix-assert` tests, stay in the description, and so
+/// do sections with more than one titled example, whose titles would be lost.
+pub fn take_example_section(description: &mut Vec<String>) -> Option<String> {
+    let text = description.join("\n\n");
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+
+    // Heading level of the section, then each line's fence state.
+    let mut section: Option<(usize, usize)> = None;
+    let mut end = lines.len();
+    let mut blocks: Vec<String> = vec![];
+    let mut fence: Option<(usize, char)> = None;
+    let mut titles = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        if let Some((count, ch)) = fence {
+            if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                fence = None;
+            } else if section.is_some() {
+                let block = blocks.last_mut().expect("a block is open");
+                block.push_str(line);
+            }
+            continue;
+        }
+        if let Some(opened) = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3) {
+            fence = Some(opened);
+            if section.is_some() {
+                let info = fence_line.trim_start_matches(opened.1).trim();
+                let lang = info.split_whitespace().next().unwrap_or("");
+                if !(lang.is_empty() || lang.eq_ignore_ascii_case("nix")) {
+                    return None;
+                }
+                blocks.push(String::new());
+            }
+            continue;
+        }
+        let level = heading_level(line);
+        match (section, level) {
+            (None, Some(level)) => {
+                let title = line.trim().trim_start_matches('#').trim();
+                if title.eq_ignore_ascii_case("example") || title.eq_ignore_ascii_case("examples") {
+                    section = Some((i, level));
+                }
+            }
+            (Some((_, top)), Some(level)) if level <= top => {
+                end = i;
+                break;
+            }
+            (Some(_), Some(_)) => titles += 1,
+            (Some(_), None) if !line.trim().is_empty() && !line.trim_start().starts_with(":::") => {
+                return None;
+            }
+            _ => {}
+        }
+    }
+    let (start, _) = section?;
+    if titles > 1 {
+        return None;
+    }
+    let code: Vec<&str> = blocks
+        .iter()
+        .map(|block| block.trim_matches('\n'))
+        .filter(|block| !block.trim().is_empty())
+        .collect();
+    if code.is_empty() {
+        return None;
+    }
+    let rest = lines[..start].concat() + &lines[end..].concat();
+    *description = rest
+        .trim()
+        .split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .map(String::from)
+        .collect();
+    Some(dedent(&code.join("\n\n")).trim_end().to_string())
+}
+
+/// Rewrite the inline links of a section as reference-style links.
+///
+/// Link definitions are appended to the end of the section. Since CommonMark
+/// link labels are global to the document, every label is `{label_prefix}-{n}`,
+/// so passing something unique per section (such as its anchor) keeps them
+/// from clashing. Autolinks, images and links that are already reference-style
+/// are left alone.
+pub fn reference_links<'a>(section: &'a str, label_prefix: &str) -> Cow<'a, str> {
+    use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+    // (source range, text range, destination, title) of every inline link
+    let mut links: Vec<(
+        std::ops::Range<usize>,
+        std::ops::Range<usize>,
+        String,
+        String,
+    )> = Vec::new();
+    let mut open: Option<(std::ops::Range<usize>, String, String)> = None;
+    for (event, range) in Parser::new_ext(section, Options::all()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                title,
+                ..
+            }) => open = Some((range, dest_url.to_string(), title.to_string())),
+            // Links cannot nest, so this closes the link opened last.
+            Event::End(TagEnd::Link) => {
+                if let Some((range, dest, title)) = open.take() {
+                    // The link text ends at the last `](` before the destination.
+                    let text_start = range.start + 1;
+                    let text_end = section[text_start..range.end]
+                        .rfind("](")
+                        .map_or(text_start, |i| text_start + i);
+                    links.push((range, text_start..text_end, dest, title));
+                }
+            }
+            _ => {}
+        }
+    }
+    if links.is_empty() {
+        return Cow::Borrowed(section);
+    }
+
+    let mut output = String::with_capacity(section.len() + links.len() * 32);
+    let mut definitions = String::new();
+    let mut last = 0;
+    for (n, (range, text, dest, title)) in links.into_iter().enumerate() {
+        let label = format!("{}-{}", label_prefix, n + 1);
+        output.push_str(&section[last..range.start]);
+        output.push('[');
+        output.push_str(&section[text]);
+        output.push_str("][");
+        output.push_str(&label);
+        output.push(']');
+        last = range.end;
+
+        definitions.push('[');
+        definitions.push_str(&label);
+        definitions.push_str("]: <");
+        definitions.push_str(&dest);
+        definitions.push('>');
+        if !title.is_empty() {
+            definitions.push_str(" \"");
+            definitions.push_str(&title.replace('"', "\\\""));
+            definitions.push('"');
+        }
+        definitions.push('\n');
+    }
+    output.push_str(&section[last..]);
+
+    // Keep the section's trailing blank line after the definitions.
+    let trimmed = output.trim_end_matches('\n').len();
+    let trailing = output.len() - trimmed;
+    output.truncate(trimmed);
+    output.push_str("\n\n");
+    output.push_str(&definitions);
+    if trailing > 1 {
+        output.push('\n');
+    }
+    Cow::Owned(output)
+}
+
+/// Maximum line width for generated prose, or `none` to leave it unwrapped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Wrap {
+    #[default]
+    None,
+    Width(usize),
+}
+
+impl std::str::FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Wrap::None),
+            _ => match s.parse::<usize>() {
+                Ok(0) | Err(_) => Err(format!("expected a width or 'none', got '{}'", s)),
+                Ok(width) => Ok(Wrap::Width(width)),
+            },
+        }
+    }
+}
+
+/// Reflow the prose in a CommonMark document to at most `width` columns.
+///
+/// Only paragraphs, list items and definition list items are reflowed; code
+/// blocks, tables, headings, block quotes, `:::` fence lines, HTML blocks
+/// (up to the blank line or closing tag ending them) and paragraphs with
+/// hard line breaks are left untouched. Words that would change the meaning
+/// of the text at the start of a line (`#`, `-`, `1.`, `>`, …) are kept on
+/// the previous line, inline code spans are kept whole, with the spaces in
+/// them, and single words longer than `width` are never split.
+pub fn wrap_markdown(text: &str, width: usize) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut curr_fence: Option<(usize, char)> = None;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if let Some((start_count, start_char)) = curr_fence {
+            if get_fence(trimmed, false)
+                .is_some_and(|(count, ch)| ch == start_char && count >= start_count)
+            {
+                curr_fence = None;
+            }
+            output.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if let Some(fence) = get_fence(trimmed, true).filter(|(count, _)| *count >= 3) {
+            curr_fence = Some(fence);
+            output.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if trimmed.starts_with('<') && line.len() - trimmed.len() < 4 {
+            let terminator = html_block_terminator(trimmed);
+            while let Some(line) = lines.get(i) {
+                if terminator.is_none() && line.trim().is_empty() {
+                    break;
+                }
+                output.push(line.to_string());
+                i += 1;
+                if terminator.is_some_and(|end| line.to_ascii_lowercase().contains(end)) {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let Some((first_prefix, rest_prefix)) = wrap_prefixes(line) else {
+            output.push(line.to_string());
+            i += 1;
+            continue;
+        };
+
+        // Gather the block: its first line and plain continuation lines.
+        let mut end = i + 1;
+        while end < lines.len() && is_continuation(lines[end]) {
+            end += 1;
+        }
+        let block = &lines[i..end];
+        if block.iter().any(|l| l.ends_with("  ") || l.ends_with('\\')) {
+            output.extend(block.iter().map(|l| l.to_string()));
+        } else {
+            let content: Vec<&str> = block
+                .iter()
+                .enumerate()
+                .map(|(n, l)| if n == 0 { &l[first_prefix.len()..] } else { l }.trim())
+                .collect();
+            let words = split_words(&content.join("\n"));
+            let words = words.iter().map(String::as_str);
+            output.extend(fill_words(words, &first_prefix, &rest_prefix, width));
+        }
+        i = end;
+    }
+
+    output.join("\n")
+}
+
+/// Text ending the raw HTML block that starts with `content`, for the blocks
+/// that a blank line does not end: `<pre>`, `<script>`, `<style>` and
+/// `<textarea>` elements, comments, processing instructions, declarations
+/// and CDATA sections.
+fn html_block_terminator(content: &str) -> Option<&'static str> {
+    let lower = content.to_ascii_lowercase();
+    for (tag, end) in [
+        ("<pre", "</pre>"),
+        ("<script", "</script>"),
+        ("<style", "</style>"),
+        ("<textarea", "</textarea>"),
+    ] {
+        if let Some(rest) = lower.strip_prefix(tag)
+            && (rest.is_empty() || rest.starts_with([' ', '\t', '>']))
+        {
+            return Some(end);
+        }
+    }
+    if lower.starts_with("<!--") {
+        Some("-->")
+    } else if lower.starts_with("<?") {
+        Some("?>")
+    } else if lower.starts_with("<![cdata[") {
+        Some("]]>")
+    } else if lower.starts_with("<!") {
+        Some(">")
+    } else {
+        None
+    }
+}
+
+/// Prefixes for the first and following lines of a wrappable block starting
+/// at `line`, or `None` if the line must be left alone.
+fn wrap_prefixes(line: &str) -> Option<(String, String)> {
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+    if content.is_empty() || indent.contains('\t') {
+        return None;
+    }
+
+    let marker_len = list_marker_len(content).or_else(|| {
+        // Definition list item, as emitted for function arguments
+        content.starts_with(": ").then_some(2)
+    });
+    match marker_len {
+        Some(len) => {
+            let first = format!("{}{}", indent, &content[..len]);
+            let rest = " ".repeat(first.len());
+            Some((first, rest))
+        }
+        None if indent.len() < 4 && !starts_block(content) => {
+            Some((indent.to_string(), indent.to_string()))
+        }
+        None => None,
+    }
+}
+
+/// Length of a list marker and its following space (`- `, `1. `), if any.
+fn list_marker_len(content: &str) -> Option<usize> {
+    if content.starts_with("- ") || content.starts_with("* ") || content.starts_with("+ ") {
+        return Some(2);
+    }
+    let digits = content.chars().take_while(|c| c.is_ascii_digit()).count();
+    let rest = &content[digits..];
+    (digits > 0 && digits <= 9 && (rest.starts_with(". ") || rest.starts_with(") ")))
+        .then_some(digits + 2)
+}
+
+/// Split the text of a paragraph into words at whitespace outside inline
+/// code spans. Line breaks within a code span, which read as spaces, become
+/// spaces.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let after = &rest[ticks..];
+            // The code span ends at the next run of as many backticks.
+            let mut search = 0;
+            let close = loop {
+                let Some(start) = after[search..].find('`').map(|i| search + i) else {
+                    break None;
+                };
+                let run = after[start..].len() - after[start..].trim_start_matches('`').len();
+                if run == ticks {
+                    break Some(start + run);
+                }
+                search = start + run;
+            };
+            let len = ticks + close.unwrap_or(0);
+            word.push_str(&rest[..len].replace('\n', " "));
+            rest = &rest[len..];
+            continue;
+        }
+        word.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Whether a (trimmed) line starts a block that is never reflowed.
+fn starts_block(content: &str) -> bool {
+    content.starts_with(['#', '>', '|', '<', '='])
+        || content.starts_with(":::")
+        || content.starts_with("---")
+        || content.starts_with("***")
+        || content.starts_with("___")
+        || (content.starts_with('[') && content.contains("]:"))
+        || content.contains(" | ")
+        || content.ends_with(" |")
+}
+
+/// Whether `line` continues the paragraph or item above it.
+fn is_continuation(line: &str) -> bool {
+    let content = line.trim_start();
+    !content.is_empty()
+        && list_marker_len(content).is_none()
+        && !content.starts_with(": ")
+        && !content.starts_with("```")
+        && !content.starts_with("~~~")
+        && !starts_block(content)
+}
+
+/// Whether a word would start a new block if it began a line.
+fn unsafe_at_line_start(word: &str) -> bool {
+    word.starts_with(['#', '>', '-', '+', '*', '=', '|', ':', '<'])
+        || word.starts_with("```")
+        || word.starts_with("~~~")
+        || (word.starts_with('[') && word.contains("]:"))
+        || list_marker_len(&format!("{} ", word)).is_some()
+}
+
+/// Greedily fill words into lines of at most `width` columns.
+fn fill_words<'a>(
+    words: impl Iterator<Item = &'a str>,
+    first_prefix: &str,
+    rest_prefix: &str,
+    width: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = first_prefix.to_string();
+    let mut line_width = line.chars().count();
+    let mut empty = true;
+    for word in words {
+        let word_width = word.chars().count();
+        if !empty && line_width + 1 + word_width > width && !unsafe_at_line_start(word) {
+            lines.push(std::mem::replace(&mut line, rest_prefix.to_string()));
+            line_width = rest_prefix.len();
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+        empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Language tags of the code fences in generated CommonMark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeLangs {
+    /// Tag for the fences docgen adds itself, around usage examples and
+    /// multi-line option values. `None` leaves them untagged.
+    pub default: Option<String>,
+    /// `(from, to)` pairs: fences tagged `from` are tagged `to` instead.
+    /// Tags are compared case-insensitively.
+    pub map: Vec<(String, String)>,
+}
+
+impl Default for CodeLangs {
+    fn default() -> Self {
+        Self {
+            default: Some("nix".to_string()),
+            map: Vec::new(),
+        }
+    }
+}
+
+impl CodeLangs {
+    fn mapped<'a>(&'a self, lang: &'a str) -> &'a str {
+        self.map
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(lang))
+            .map_or(lang, |(_, to)| to.as_str())
+    }
+
+    /// Opening fence for code docgen wraps in a code block itself. Its tag
+    /// is retagged with the rest of the document by [`CodeLangs::remap`],
+    /// so a tag is only mapped once.
+    pub fn fence(&self) -> String {
+        match &self.default {
+            Some(lang) => format!("```{}", lang),
+            None => "```".to_string(),
+        }
+    }
+
+    /// Retag the code fences of a CommonMark document according to `map`.
+    ///
+    /// Only the language (the first word of the info string) of opening
+    /// fences is replaced; any further attributes are kept.
+    pub fn remap<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.map.is_empty() {
+            return Cow::Borrowed(text);
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut changed = false;
+        let mut curr_fence: Option<(usize, char)> = None;
+        for line in text.split_inclusive('\n') {
+            let fence_line = trim_leading_whitespace(line, 3);
+            if let Some((start_count, start_char)) = curr_fence {
+                if get_fence(&fence_line, false)
+                    .is_some_and(|(count, ch)| ch == start_char && count >= start_count)
+                {
+                    curr_fence = None;
+                }
+                result.push_str(line);
+                continue;
+            }
+            let Some(fence) = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3) else {
+                result.push_str(line);
+                continue;
+            };
+            curr_fence = Some(fence);
+
+            let info_start = line.len() - fence_line.len() + fence.0;
+            let info = &line[info_start..];
+            let lang_len = info
+                .trim_start()
+                .find(char::is_whitespace)
+                .unwrap_or(info.trim_start().len());
+            let lang_start = info_start + info.len() - info.trim_start().len();
+            let lang = &line[lang_start..lang_start + lang_len];
+            let mapped = self.mapped(lang);
+            if lang.is_empty() || mapped == lang {
+                result.push_str(line);
+            } else {
+                result.push_str(&line[..lang_start]);
+                result.push_str(mapped);
+                result.push_str(&line[lang_start + lang_len..]);
+                changed = true;
+            }
+        }
+        if changed {
+            Cow::Owned(result)
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+/// Replace the `{key}` placeholders of a template by their values.
+pub fn expand_template(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (key, value)| {
+            text.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+/// Check that a template only uses the placeholders in `keys`.
+pub fn check_template(template: &str, keys: &[&str]) -> Result<String, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(format!("unclosed '{{' in '{}'", template));
+        };
+        let key = &rest[start + 1..start + len];
+        if !keys.contains(&key) {
+            return Err(format!(
+                "unknown placeholder '{{{}}}' (expected one of {})",
+                key,
+                keys.iter()
+                    .map(|k| format!("{{{}}}", k))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    Ok(template.to_string())
+}
+
+/// Parse a `--code-lang-map` entry of the form `from=to`.
+pub fn parse_lang_mapping(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => Err(format!("expected FROM=TO, got '{}'", s)),
+    }
+}
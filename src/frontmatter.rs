@@ -0,0 +1,66 @@
+//! YAML frontmatter for static site generators.
+//!
+//! Hugo, Jekyll and Astro read a page's metadata from a block at its start:
+//!
+//! ```yaml
+//! ---
+//! title: "String functions"
+//! slug: "strings"
+//! weight: 10
+//! ---
+//! ```
+//!
+//! `title` is the document's first heading and `slug` its category, unless
+//! fields of the same name are given. Values that are JSON (numbers, `true`,
+//! `false`, `null`, quoted strings, lists and objects) are written as they
+//! are, since JSON is also YAML; anything else is written as a quoted string.
+
+/// Parse a `--frontmatter` field of the form `key=value`.
+pub fn parse_field(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value))
+            if !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            Ok((key.to_string(), value.to_string()))
+        }
+        Some(_) => Err(format!("invalid frontmatter key in '{}'", s)),
+        None => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+/// A value as YAML: JSON as it is, anything else quoted.
+fn yaml_value(value: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(_) => value.trim().to_string(),
+        Err(_) => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// `markdown` with a frontmatter block holding the automatic `title` and
+/// `slug`, then `fields`. A field overrides the automatic value of the same
+/// name, and of several fields of the same name the last one is kept.
+pub fn with_frontmatter(markdown: &str, slug: &str, fields: &[(String, String)]) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    let mut block = String::from("---\n");
+    let title = crate::summary::document_title(markdown);
+    let automatic = [("title", title.as_deref()), ("slug", Some(slug))];
+    for (key, value) in automatic {
+        if let Some(value) = value
+            && !fields.iter().any(|(k, _)| k == key)
+        {
+            block.push_str(&format!("{}: {}\n", key, quote(value)));
+        }
+    }
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if fields[i + 1..].iter().any(|(k, _)| k == key) {
+            continue;
+        }
+        block.push_str(&format!("{}: {}\n", key, yaml_value(value)));
+    }
+    block.push_str("---\n\n");
+    block.push_str(markdown);
+    block
+}
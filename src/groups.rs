@@ -0,0 +1,315 @@
+//! Groups of entries declared in a file's doc comment.
+//!
+//! A level 2 section of the file doc whose list names documented bindings
+//! declares a group, rendered in place of the section:
+//!
+//! ```markdown
+//! ## String predicates
+//!
+//! Functions testing a string.
+//!
+//! - `hasPrefix`
+//! - `lib.strings.hasSuffix`: also see `hasInfix`
+//! ```
+//!
+//! Items start with the name of a binding as a code span, either as declared
+//! in the file or as the entry's full title. The list is dropped and the rest
+//! of the section kept as the group's introduction, followed by the entries
+//! of the group in the list's order with their headings one level lower.
+//! Sections whose list has an item not naming a binding, and lists of links
+//! such as the featured functions of a landing page, declare no group.
+//!
+//! With `--group-by kind`, entries are grouped by what their signature says
+//! they take instead: constants, functions of one argument, curried
+//! functions of several and functions taking an attribute set, in that
+//! order. Functions whose signature says neither follow under "Other
+//! functions".
+//!
+//! With `--group-by tag`, entries are grouped by the topics of their `@tag`
+//! lines, in the order the tags first appear. Each group starts with a list
+//! linking to all entries with its tag; an entry with several tags is
+//! written under the first of them and listed by the others.
+
+use crate::commonmark::{EntryKind, ManualEntry};
+use crate::format::{get_fence, trim_leading_whitespace};
+use crate::locale::Headings;
+use crate::signature::SignatureParam;
+
+/// A group declared by a section of the file doc.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// Heading line of the section, as written.
+    pub heading: String,
+    /// The section without its heading and list.
+    pub intro: String,
+    /// Names of the entries of the group, as declared in the file.
+    pub names: Vec<String>,
+}
+
+/// How entries are grouped, instead of by the groups of the file doc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// By [`Kind`], derived from the signature of each entry.
+    Kind,
+    /// By the `@tag` lines of the doc comments.
+    Tag,
+}
+
+/// What an entry documents, by its signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Constant,
+    /// A function of one argument.
+    Unary,
+    /// A function of several arguments, one at a time.
+    Curried,
+    /// A function whose first argument is an attribute set.
+    Attrset,
+}
+
+impl Kind {
+    /// The kinds in the order their groups are written.
+    pub const ALL: [Kind; 4] = [Kind::Constant, Kind::Unary, Kind::Curried, Kind::Attrset];
+
+    /// The kind of `entry`, unless it is a function of unknown arguments.
+    /// A pattern parameter or an attribute set as the first argument type
+    /// counts for more than the number of arguments.
+    pub fn of(entry: &ManualEntry) -> Option<Kind> {
+        if entry.kind == EntryKind::Constant {
+            return Some(Kind::Constant);
+        }
+        let signature = entry.signature.as_ref()?;
+        let types = signature
+            .fn_type
+            .as_ref()
+            .map_or(&[][..], |fn_type| fn_type.args.as_slice());
+        let takes_attrs = matches!(
+            signature.params.first(),
+            Some(SignatureParam::Pattern { .. })
+        ) || types.first().is_some_and(|ty| is_attrs_type(ty));
+        if takes_attrs {
+            return Some(Kind::Attrset);
+        }
+        match signature.params.len().max(types.len()) {
+            0 => None,
+            1 => Some(Kind::Unary),
+            _ => Some(Kind::Curried),
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Kind::Constant => "Constants",
+            Kind::Unary => "Functions of one argument",
+            Kind::Curried => "Curried functions",
+            Kind::Attrset => "Functions taking an attribute set",
+        }
+    }
+
+    fn slug(self) -> &'static str {
+        match self {
+            Kind::Constant => "constants",
+            Kind::Unary => "unary",
+            Kind::Curried => "curried",
+            Kind::Attrset => "attrset",
+        }
+    }
+}
+
+/// Whether `ty` is the type of an attribute set.
+fn is_attrs_type(ty: &str) -> bool {
+    let ty = ty.trim();
+    ty.starts_with('{')
+        || ["attrset", "attrs", "attrsof"]
+            .iter()
+            .any(|name| ty.to_ascii_lowercase().starts_with(name))
+}
+
+/// A group per kind of the `entries` of `category`, in the order of
+/// [`Kind::ALL`], leaving out kinds of no entry, with their headings
+/// translated by `headings`.
+pub fn kind_groups(entries: &[ManualEntry], category: &str, headings: &Headings) -> Vec<Group> {
+    Kind::ALL
+        .into_iter()
+        .map(|kind| Group {
+            heading: format!(
+                "{} {{#sec-functions-library-{}-{}}}",
+                headings.get(kind.heading()),
+                category,
+                kind.slug()
+            ),
+            intro: String::new(),
+            names: entries
+                .iter()
+                .filter(|entry| Kind::of(entry) == Some(kind))
+                .map(|entry| entry.name.clone())
+                .collect(),
+        })
+        .filter(|group| !group.names.is_empty())
+        .collect()
+}
+
+/// `string-manipulation` as `String manipulation`, for the heading of a tag.
+fn tag_heading(tag: &str) -> String {
+    let words = tag.replace(['-', '_'], " ");
+    let mut chars = words.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// A group per tag of the `entries` of `category`, in the order the tags
+/// first appear, starting with links to the entries of the tag. Tags that
+/// differ only in case or punctuation get anchors numbered from `-2`.
+/// Headings are translated by `headings`.
+pub fn tag_groups(
+    entries: &[ManualEntry],
+    category: &str,
+    anchor_prefix: &str,
+    headings: &Headings,
+) -> Vec<Group> {
+    let mut tags: Vec<&str> = vec![];
+    for tag in entries.iter().flat_map(|entry| &entry.tags) {
+        if !tags.contains(&tag.as_str()) {
+            tags.push(tag);
+        }
+    }
+    let mut slugs: Vec<String> = vec![];
+    tags.into_iter()
+        .map(|tag| {
+            let tagged = entries
+                .iter()
+                .filter(|entry| entry.tags.iter().any(|t| t == tag));
+            let links: Vec<String> = tagged
+                .clone()
+                .map(|entry| {
+                    format!(
+                        "- [`{}`](#{})",
+                        entry.get_ident_title().1,
+                        entry.anchor(anchor_prefix)
+                    )
+                })
+                .collect();
+            let slug: String = tag
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '-'
+                    }
+                })
+                .collect();
+            let mut unique = slug.clone();
+            for n in 2.. {
+                if !slugs.contains(&unique) {
+                    break;
+                }
+                unique = format!("{}-{}", slug, n);
+            }
+            slugs.push(unique.clone());
+            Group {
+                heading: format!(
+                    "{} {{#sec-functions-library-{}-tag-{}}}",
+                    headings.get(&tag_heading(tag)),
+                    category,
+                    unique
+                ),
+                intro: links.join("\n"),
+                names: tagged
+                    .filter(|entry| entry.tags.first().is_some_and(|first| first == tag))
+                    .map(|entry| entry.name.clone())
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// The binding named at the start of a list item.
+fn item_name(item: &str) -> Option<&str> {
+    let rest = item.strip_prefix('`')?;
+    let (name, _) = rest.split_once('`')?;
+    Some(name.trim())
+}
+
+/// A list item marker at the start of `line`, returning the item's text.
+fn list_item(line: &str) -> Option<&str> {
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+}
+
+/// Split the group sections out of a category description. `resolve` maps
+/// a name from a list to the entry it names, if any.
+pub fn split_groups(
+    description: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<Group>) {
+    // Split into the text before the first section and the sections.
+    let mut sections: Vec<(String, Vec<&str>)> = vec![];
+    let mut before = String::new();
+    let mut fence: Option<(usize, char)> = None;
+    for line in description.split_inclusive('\n') {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                if fence.is_none() && line.starts_with("## ") {
+                    sections.push((line.trim_end().to_string(), vec![]));
+                    continue;
+                }
+                if fence.is_none() && line.starts_with("# ") {
+                    // A new top-level heading ends the sections.
+                    sections.push((String::new(), vec![]));
+                }
+            }
+        }
+        match sections.last_mut() {
+            Some((_, lines)) => lines.push(line),
+            None => before.push_str(line),
+        }
+    }
+
+    let mut text = before;
+    let mut groups = vec![];
+    for (heading, lines) in sections {
+        let mut names = vec![];
+        let mut intro = String::new();
+        let mut in_list = false;
+        let mut is_group = !heading.is_empty();
+        for line in &lines {
+            if let Some(item) = list_item(line) {
+                in_list = true;
+                match item_name(item).and_then(&resolve) {
+                    Some(name) => names.push(name),
+                    None => is_group = false,
+                }
+            } else if in_list && (line.starts_with([' ', '\t']) || line.trim().is_empty()) {
+                // Continuation of an item.
+            } else {
+                in_list = false;
+                intro.push_str(line);
+            }
+        }
+        if is_group && !names.is_empty() {
+            groups.push(Group {
+                heading: heading.trim_start_matches("## ").to_string(),
+                intro: intro.trim().to_string(),
+                names,
+            });
+        } else {
+            if !heading.is_empty() {
+                text.push_str(&heading);
+                text.push('\n');
+            }
+            text.extend(lines);
+        }
+    }
+    (text, groups)
+}
@@ -0,0 +1,545 @@
+//! Standalone HTML site output.
+//!
+//! Every page is written as `<path>.html`, a complete document with an
+//! embedded stylesheet, a skip link, a sidebar listing all pages and the
+//! entries of the current one, and the content as the `main` landmark.
+//! Nix code blocks are highlighted with `<span>` elements whose classes
+//! (`tok-keyword`, `tok-string`, `tok-comment`, `tok-number`, `tok-path`)
+//! are styled by the stylesheet. `index.html` lists the pages.
+//!
+//! Given the URL the site is served from, the site can also be searched:
+//! `search-index.json` lists every entry with its link, as the index written
+//! by the `index` command (see [`crate::lunr`]), `search.html` looks up its
+//! `q` parameter in it, jumping to an entry whose title matches exactly, and `opensearch.xml` describes the search page so browsers can
+//! add the site as a search engine. Every page links to the description and
+//! has a search box in its sidebar.
+//!
+//! Pages end with links to the previous and next page, in the order they are
+//! given in, which is also the order of `docgen-nav.json`.
+//!
+//! `::: {.class #id}` blocks become `<div>` elements of that class and id,
+//! so `warning` blocks such as deprecation notices and `example` blocks are
+//! set apart by the stylesheet. Headings within them, such as the titles of
+//! examples, are captions (`<p class="title">`) rather than headings of the
+//! page.
+
+use crate::devdocs::Page;
+use crate::format::{DivFence, div_fence, get_fence, trim_leading_whitespace};
+use crate::lunr::SearchIndex;
+use crate::nav::{NAV_FILE, Navigation};
+use pulldown_cmark::{
+    CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd, html,
+};
+use rnix::SyntaxKind::*;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const STYLE: &str = "\
+body { margin: 0; font-family: system-ui, sans-serif; line-height: 1.5; display: flex; }
+.skip-link { position: absolute; left: -10000px; }
+.skip-link:focus { left: 1em; top: 1em; background: #fff; padding: 0.5em; z-index: 1; }
+.sidebar { flex: 0 0 18em; height: 100vh; position: sticky; top: 0; overflow-y: auto; border-right: 1px solid #ddd; padding: 1em; box-sizing: border-box; font-size: 0.9em; }
+.sidebar ul { list-style: none; padding-left: 1em; margin: 0; }
+.sidebar > ul { padding-left: 0; }
+.sidebar a { text-decoration: none; }
+.sidebar a[aria-current] { font-weight: bold; }
+main { flex: 1; min-width: 0; padding: 1em 2em; max-width: 60em; }
+pre { background: #f6f8fa; padding: 0.75em; overflow-x: auto; }
+.self-link { visibility: hidden; text-decoration: none; }
+:hover > .self-link, .self-link:focus { visibility: visible; }
+.tok-keyword { color: #a626a4; }
+.tok-string { color: #50a14f; }
+.tok-comment { color: #a0a1a7; font-style: italic; }
+.tok-number { color: #986801; }
+.tok-path { color: #4078f2; }
+main:has(.diff) { max-width: none; }
+.diff { display: grid; grid-template-columns: 1fr 1fr; gap: 1em; }
+.diff > div { min-width: 0; padding: 0 1em; }
+.diff-label { font-weight: bold; }
+.diff-old { background: #fff5f5; }
+.diff-new { background: #f3fff5; }
+.search input { width: 100%; box-sizing: border-box; margin-bottom: 1em; }
+.pager { display: flex; gap: 1em; border-top: 1px solid #ddd; margin-top: 2em; padding-top: 1em; }
+.pager [rel=next] { margin-left: auto; }
+.warning, .note, .tip, .important, .caution, .example { border-left: 4px solid #ddd; padding: 0 1em; margin: 1em 0; }
+.warning, .caution { border-color: #cf222e; background: #fff5f5; }
+p.title { font-weight: bold; }
+.note, .tip, .important { border-color: #0969da; background: #f3f8ff; }
+";
+
+/// Script of `search.html`, listing the documents of `search-index.json`
+/// whose title contains the query.
+const SEARCH_SCRIPT: &str = r##"
+const query = new URLSearchParams(location.search).get("q") || "";
+const input = document.querySelector("#search-query");
+const results = document.querySelector("#search-results");
+input.value = query;
+fetch("search-index.json")
+  .then((response) => response.json())
+  .then((index) => {
+    const entries = index.documents;
+    const needle = query.trim().toLowerCase();
+    if (!needle) return;
+    const exact = entries.find((e) =>
+      e.title.toLowerCase() === needle || e.title.toLowerCase().endsWith("." + needle));
+    if (exact) {
+      location.replace(exact.url);
+      return;
+    }
+    const found = entries.filter((e) => e.title.toLowerCase().includes(needle));
+    results.replaceChildren(...found.map((e) => {
+      const item = document.createElement("li");
+      const link = document.createElement("a");
+      const code = document.createElement("code");
+      link.href = e.url;
+      code.textContent = e.title;
+      link.append(code);
+      item.append(link);
+      return item;
+    }));
+    document.querySelector("#search-status").textContent =
+      found.length === 1 ? "1 result" : found.length + " results";
+  });
+"##;
+
+/// Replace the `:::` lines of `markdown`, outside code blocks, with the
+/// `<div>` tags of the blocks they open and close. Also returns where each
+/// tag starts, and whether it opens its block.
+fn fenced_divs(markdown: &str) -> (Cow<'_, str>, Vec<(usize, bool)>) {
+    let mut tags = vec![];
+    if !markdown.contains(":::") {
+        return (Cow::Borrowed(markdown), tags);
+    }
+    let mut out = String::with_capacity(markdown.len());
+    let mut fence: Option<(usize, char)> = None;
+    let mut open = 0;
+    for line in markdown.split_inclusive('\n') {
+        let fence_line = trim_leading_whitespace(line, 3);
+        if let Some((count, ch)) = fence {
+            if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                fence = None;
+            }
+            out.push_str(line);
+            continue;
+        }
+        match div_fence(&fence_line) {
+            Some(DivFence::Open { classes, id }) => {
+                out.push('\n');
+                tags.push((out.len(), true));
+                out.push_str(&div_tag(&classes, id));
+                out.push_str("\n\n");
+                open += 1;
+            }
+            Some(DivFence::Close) => {
+                if open > 0 {
+                    open -= 1;
+                    out.push('\n');
+                    tags.push((out.len(), false));
+                    out.push_str("</div>\n\n");
+                }
+            }
+            None => {
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                out.push_str(line);
+            }
+        }
+    }
+    (Cow::Owned(out), tags)
+}
+
+/// The `<div>` tag opening a `:::` block of `classes` and `id`.
+pub(crate) fn div_tag(classes: &[&str], id: Option<&str>) -> String {
+    let mut div = String::from("<div");
+    if !classes.is_empty() {
+        let _ = write!(div, " class=\"{}\"", escape_html(&classes.join(" ")));
+    }
+    if let Some(id) = id {
+        let _ = write!(div, " id=\"{}\"", escape_html(id));
+    }
+    div.push('>');
+    div
+}
+
+/// Render CommonMark as HTML, keeping `{#id}` heading anchors as ids, and
+/// highlighting Nix code blocks if `highlight` is set.
+///
+/// A heading more than one level below the previous one is raised, so
+/// shifted headings do not skip levels; the first heading sets where the
+/// outline starts. Headings with an id end with a self-link. Headings within
+/// `:::` blocks are not part of the outline, and become captions.
+pub fn markdown_to_html(markdown: &str, highlight: bool) -> String {
+    let options = Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_DEFINITION_LIST;
+    let mut previous: Option<usize> = None;
+    let mut open: Option<(HeadingLevel, Option<CowStr>)> = None;
+    let mut code: Option<String> = None;
+    let (markdown, tags) = fenced_divs(markdown);
+    // Number of `:::` blocks open, and whether a caption is being written.
+    let mut depth = 0;
+    let mut caption = false;
+    let parser = Parser::new_ext(&markdown, options).into_offset_iter();
+    let events = parser.flat_map(|(event, range)| match event {
+        Event::Html(html) => {
+            if let Some((_, opens)) = tags.iter().find(|(start, _)| *start == range.start) {
+                if *opens {
+                    depth += 1;
+                } else {
+                    depth -= 1;
+                }
+            }
+            vec![Event::Html(html)]
+        }
+        Event::Start(Tag::Heading { id, .. }) if depth > 0 => {
+            caption = true;
+            let id = id
+                .map(|id| format!(" id=\"{}\"", escape_html(&id)))
+                .unwrap_or_default();
+            vec![Event::Html(format!("<p class=\"title\"{}>", id).into())]
+        }
+        Event::End(TagEnd::Heading(_)) if std::mem::take(&mut caption) => {
+            vec![Event::Html("</p>\n".into())]
+        }
+        Event::Start(Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        }) => {
+            let depth = previous.map_or(level as usize, |p| (level as usize).min(p + 1));
+            let level = HeadingLevel::try_from(depth).unwrap_or(level);
+            previous = Some(depth);
+            open = Some((level, id.clone()));
+            vec![Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            })]
+        }
+        Event::End(TagEnd::Heading(level)) => {
+            let (level, id) = open.take().unwrap_or((level, None));
+            let mut events = vec![];
+            if let Some(id) = id {
+                events.push(Event::InlineHtml(
+                    format!(
+                        " <a class=\"self-link\" href=\"#{}\" aria-label=\"Link to this section\">#</a>",
+                        escape_html(&id)
+                    )
+                    .into(),
+                ));
+            }
+            events.push(Event::End(TagEnd::Heading(level)));
+            events
+        }
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info)))
+            if highlight && info.split_whitespace().next() == Some("nix") =>
+        {
+            code = Some(String::new());
+            vec![]
+        }
+        Event::Text(text) if code.is_some() => {
+            code.as_mut().expect("in a code block").push_str(&text);
+            vec![]
+        }
+        Event::End(TagEnd::CodeBlock) if code.is_some() => {
+            let code = code.take().expect("in a code block");
+            vec![Event::Html(
+                format!(
+                    "<pre><code class=\"language-nix\">{}</code></pre>\n",
+                    highlight_nix(&code)
+                )
+                .into(),
+            )]
+        }
+        event => vec![event],
+    });
+    let mut output = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut output, events);
+    output
+}
+
+/// Escaped Nix code with its tokens wrapped in `<span class="tok-...">`,
+/// one span per run of tokens of the same class. Code that cannot be
+/// tokenized losslessly is only escaped.
+pub fn highlight_nix(code: &str) -> String {
+    let tokens = rnix::tokenize(code);
+    if tokens.iter().map(|(_, text)| text.len()).sum::<usize>() != code.len() {
+        return escape_html(code);
+    }
+    let mut output = String::with_capacity(code.len() * 2);
+    let mut open: Option<&str> = None;
+    for (kind, text) in tokens {
+        let class = match kind {
+            TOKEN_ASSERT | TOKEN_ELSE | TOKEN_IF | TOKEN_IN | TOKEN_INHERIT | TOKEN_LET
+            | TOKEN_OR | TOKEN_REC | TOKEN_THEN | TOKEN_WITH => Some("tok-keyword"),
+            TOKEN_STRING_START | TOKEN_STRING_CONTENT | TOKEN_STRING_END => Some("tok-string"),
+            TOKEN_COMMENT => Some("tok-comment"),
+            TOKEN_INTEGER | TOKEN_FLOAT => Some("tok-number"),
+            TOKEN_PATH | TOKEN_URI => Some("tok-path"),
+            _ => None,
+        };
+        if class != open {
+            if open.is_some() {
+                output.push_str("</span>");
+            }
+            if let Some(class) = class {
+                let _ = write!(output, "<span class=\"{}\">", class);
+            }
+            open = class;
+        }
+        output.push_str(&escape_html(text));
+    }
+    if open.is_some() {
+        output.push_str("</span>");
+    }
+    output
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The sidebar: every page, with the entries of `current` below it, and
+/// a search box if the site can be searched.
+fn sidebar(pages: &[Page], current: Option<&Page>, search: bool) -> String {
+    let mut nav = String::from("<nav class=\"sidebar\" aria-label=\"Contents\">\n");
+    if search {
+        nav.push_str(
+            "<form class=\"search\" role=\"search\" action=\"search.html\">\n<input type=\"search\" name=\"q\" aria-label=\"Search functions\" placeholder=\"Search\">\n</form>\n",
+        );
+    }
+    nav.push_str("<ul>\n");
+    for page in pages {
+        let is_current = current.is_some_and(|c| c.path == page.path);
+        let _ = write!(
+            nav,
+            "<li><a href=\"{}.html\"{}>{}</a>",
+            escape_html(&page.path),
+            if is_current {
+                " aria-current=\"page\""
+            } else {
+                ""
+            },
+            escape_html(&page.type_name)
+        );
+        if is_current && !page.entries.is_empty() {
+            nav.push_str("\n<ul>\n");
+            for (name, anchor) in &page.entries {
+                let _ = writeln!(
+                    nav,
+                    "<li><a href=\"#{}\"><code>{}</code></a></li>",
+                    escape_html(anchor),
+                    escape_html(name)
+                );
+            }
+            nav.push_str("</ul>\n");
+        }
+        nav.push_str("</li>\n");
+    }
+    nav.push_str("</ul>\n</nav>\n");
+    nav
+}
+
+/// A complete HTML document with the site's stylesheet and `head` at the
+/// end of its `head` element.
+pub(crate) fn document(title: &str, head: &str, nav: &str, content: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+{STYLE}</style>
+{head}</head>
+<body>
+<a class="skip-link" href="#main">Skip to content</a>
+{nav}<main id="main">
+{content}</main>
+</body>
+</html>
+"##,
+        title = escape_html(title),
+    )
+}
+
+/// The link to the OpenSearch description, for the `head` of a page.
+fn search_link(name: &str, search: bool) -> String {
+    if !search {
+        return String::new();
+    }
+    format!(
+        "<link rel=\"search\" type=\"application/opensearchdescription+xml\" title=\"{}\" href=\"opensearch.xml\">\n",
+        escape_html(name)
+    )
+}
+
+/// The pages of the site in reading order.
+pub fn navigation(pages: &[Page]) -> Navigation {
+    Navigation::new(
+        pages
+            .iter()
+            .map(|page| {
+                (
+                    page.type_name.clone(),
+                    page.type_name.clone(),
+                    format!("{}.html", page.path),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Links to the page before and after page `i`.
+fn pager(navigation: &Navigation, i: usize) -> String {
+    let (prev, next) = navigation.neighbours(i);
+    if prev.is_none() && next.is_none() {
+        return String::new();
+    }
+    let mut pager = String::from("<nav class=\"pager\" aria-label=\"Pages\">\n");
+    if let Some(prev) = prev {
+        let _ = writeln!(
+            pager,
+            "<a rel=\"prev\" href=\"{}\">← {}</a>",
+            escape_html(&prev.path),
+            escape_html(&prev.title)
+        );
+    }
+    if let Some(next) = next {
+        let _ = writeln!(
+            pager,
+            "<a rel=\"next\" href=\"{}\">{} →</a>",
+            escape_html(&next.path),
+            escape_html(&next.title)
+        );
+    }
+    pager.push_str("</nav>\n");
+    pager
+}
+
+/// A complete HTML document for `page`, with the sidebar of the site, links
+/// to the pages before and after it and, with `search`, its search box.
+pub fn render_page(name: &str, pages: &[Page], page: &Page, search: bool) -> String {
+    let content = markdown_to_html(&page.markdown, true);
+    let content = match pages.iter().position(|p| p.path == page.path) {
+        Some(i) => content + &pager(&navigation(pages), i),
+        None => content,
+    };
+    document(
+        &format!("{} - {}", page.type_name, name),
+        &search_link(name, search),
+        &sidebar(pages, Some(page), search),
+        &content,
+    )
+}
+
+/// The `search-index.json` of the site: the documents of every page.
+pub fn render_search_index(pages: &[Page]) -> String {
+    let documents = pages
+        .iter()
+        .flat_map(|page| page.documents.iter().cloned())
+        .collect();
+    serde_json::to_string(&SearchIndex::new(documents)).unwrap_or_default()
+}
+
+/// The `search.html` page, looking up its `q` parameter in the index.
+pub fn render_search_page(name: &str, pages: &[Page]) -> String {
+    let content = format!(
+        "<h1>Search</h1>\n<form role=\"search\" action=\"search.html\">\n<input id=\"search-query\" type=\"search\" name=\"q\" aria-label=\"Search functions\">\n</form>\n<p id=\"search-status\" role=\"status\"></p>\n<ul id=\"search-results\"></ul>\n<script>{}</script>\n",
+        SEARCH_SCRIPT
+    );
+    document(
+        &format!("Search - {}", name),
+        &search_link(name, true),
+        &sidebar(pages, None, false),
+        &content,
+    )
+}
+
+/// The OpenSearch description of the site served from `base_url`, pointing
+/// browsers at its search page.
+pub fn render_opensearch(name: &str, base_url: &str) -> String {
+    let base = format!("{}/", base_url.trim_end_matches('/'));
+    // Browsers show at most 16 characters of the short name.
+    let short_name: String = name.chars().take(16).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+<ShortName>{short_name}</ShortName>
+<Description>Search {name}</Description>
+<InputEncoding>UTF-8</InputEncoding>
+<Url type="text/html" method="get" template="{base}search.html?q={{searchTerms}}"/>
+<Url type="application/opensearchdescription+xml" rel="self" template="{base}opensearch.xml"/>
+</OpenSearchDescription>
+"#,
+        short_name = escape_html(&short_name),
+        name = escape_html(name),
+        base = escape_html(&base),
+    )
+}
+
+/// The index of the site, linking every page.
+pub fn render_index(name: &str, pages: &[Page], search: bool) -> String {
+    let mut content = format!("<h1>{}</h1>\n<ul>\n", escape_html(name));
+    for page in pages {
+        let _ = writeln!(
+            content,
+            "<li><a href=\"{}.html\">{}</a></li>",
+            escape_html(&page.path),
+            escape_html(&page.type_name)
+        );
+    }
+    content.push_str("</ul>\n");
+    document(
+        name,
+        &search_link(name, search),
+        &sidebar(pages, None, search),
+        &content,
+    )
+}
+
+/// Write `index.html` and one document per page into `dir`. With
+/// `base_url`, the URL the site is served from, also write its search page,
+/// search index and OpenSearch description.
+pub fn write_site(
+    dir: &Path,
+    name: &str,
+    pages: &[Page],
+    base_url: Option<&str>,
+) -> Result<(), String> {
+    let write = |file: &str, contents: String| {
+        let path = dir.join(file);
+        fs::write(&path, contents).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    };
+    if let Some(page) = pages
+        .iter()
+        .find(|page| page.path == "index" || (base_url.is_some() && page.path == "search"))
+    {
+        return Err(format!(
+            "a page named '{}' would overwrite {}.html",
+            page.path, page.path
+        ));
+    }
+    let search = base_url.is_some();
+    fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+    for page in pages {
+        write(
+            &format!("{}.html", page.path),
+            render_page(name, pages, page, search),
+        )?;
+    }
+    if let Some(base_url) = base_url {
+        write("search.html", render_search_page(name, pages))?;
+        write("search-index.json", render_search_index(pages))?;
+        write("opensearch.xml", render_opensearch(name, base_url))?;
+    }
+    navigation(pages).write(&dir.join(NAV_FILE))?;
+    write("index.html", render_index(name, pages, search))
+}
@@ -0,0 +1,201 @@
+//! Hugo content tree.
+//!
+//! Each category becomes a section, `<dir>/<category>/_index.md`, holding the
+//! category's introduction, with a page per function next to it:
+//!
+//! ```text
+//! content/functions/
+//!   strings/
+//!     _index.md
+//!     concatStrings.md
+//!     splitString.md
+//! ```
+//!
+//! Every file starts with frontmatter giving its `title`, `slug` and a
+//! `weight` keeping the order of the inputs and of the functions within a
+//! file, followed by any fields given with `--frontmatter`. A function whose
+//! name Hugo reads as a section or bundle, `index` or `_index`, has its page
+//! written as `function-index.md`, keeping its slug.
+//!
+//! Links to the anchor of a function, which is on a page of its own, link
+//! to that page with `relref`, such as
+//! `[join]({{< relref "../lists/join.md" >}})`.
+
+use crate::frontmatter::with_frontmatter;
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A category and its functions.
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub category: String,
+    /// Title of the category, without its anchor.
+    pub title: String,
+    /// Introduction of the category, below its heading.
+    pub intro: String,
+    pub pages: Vec<Page>,
+}
+
+/// A function of a section.
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// Attribute path of the function within its file, naming the page.
+    pub name: String,
+    /// Full title of the function, such as `lib.strings.concatStrings`.
+    pub title: String,
+    /// Anchor of the function's section, which links to the page.
+    pub anchor: String,
+    /// CommonMark section of the function, starting with its heading.
+    pub markdown: String,
+}
+
+/// Name of the file of `page` within its section.
+fn page_file(page: &Page) -> String {
+    let reserved = ["index", "_index"]
+        .iter()
+        .any(|name| page.name.eq_ignore_ascii_case(name));
+    if reserved {
+        format!("function-{}.md", page.name)
+    } else {
+        format!("{}.md", page.name)
+    }
+}
+
+/// `markdown`, of a file in the section `category`, with its links to the
+/// anchors of `pages`, given as their section and file, linked to the pages.
+fn link_pages(markdown: &str, category: &str, pages: &HashMap<String, (&str, String)>) -> String {
+    let relref = |anchor: &str| {
+        let (section, file) = pages.get(anchor.strip_prefix('#')?)?;
+        Some(if *section == category {
+            format!("{{{{< relref \"{}\" >}}}}", file)
+        } else {
+            format!("{{{{< relref \"../{}/{}\" >}}}}", section, file)
+        })
+    };
+    // Destinations to replace, as (start, end, link).
+    let mut links: Vec<(usize, usize, String)> = vec![];
+    let mut parser = Parser::new_ext(markdown, Options::all()).into_offset_iter();
+    for (event, range) in parser.by_ref() {
+        if let Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url,
+            ..
+        }) = event
+            && let Some(link) = relref(&dest_url)
+            && let Some(i) = markdown[range.clone()].rfind("](")
+            && let Some(j) = markdown[range.start + i..range.end].find(&*dest_url)
+        {
+            let start = range.start + i + j;
+            links.push((start, start + dest_url.len(), link));
+        }
+    }
+    for (_, definition) in parser.reference_definitions().iter() {
+        if let Some(link) = relref(&definition.dest)
+            && let Some(i) = markdown[definition.span.clone()].rfind(&*definition.dest)
+        {
+            let start = definition.span.start + i;
+            links.push((start, start + definition.dest.len(), link));
+        }
+    }
+    links.sort_by_key(|(start, _, _)| *start);
+    let mut output = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for (start, end, link) in links {
+        output.push_str(&markdown[last..start]);
+        output.push_str(&link);
+        last = end;
+    }
+    output.push_str(&markdown[last..]);
+    output
+}
+
+/// The frontmatter fields of a file: its title and weight, then `fields`.
+fn fields_with(title: &str, weight: usize, fields: &[(String, String)]) -> Vec<(String, String)> {
+    let mut all = vec![
+        (
+            "title".to_string(),
+            serde_json::to_string(title).unwrap_or_default(),
+        ),
+        ("weight".to_string(), weight.to_string()),
+    ];
+    all.extend(fields.iter().cloned());
+    all
+}
+
+/// The `_index.md` of a section, `weight` being its position among the
+/// sections.
+pub fn render_section(section: &Section, weight: usize, fields: &[(String, String)]) -> String {
+    let mut markdown = section.intro.trim().to_string();
+    markdown.push('\n');
+    with_frontmatter(
+        &markdown,
+        &section.category,
+        &fields_with(&section.title, weight, fields),
+    )
+}
+
+/// The page of a function, `weight` being its position within its section.
+/// The heading of the function is left to the page title.
+pub fn render_page(page: &Page, weight: usize, fields: &[(String, String)]) -> String {
+    let body = page
+        .markdown
+        .trim_start()
+        .split_once('\n')
+        .map_or("", |(_, body)| body);
+    let mut markdown = body.trim().to_string();
+    markdown.push('\n');
+    with_frontmatter(
+        &markdown,
+        &page.name,
+        &fields_with(&page.title, weight, fields),
+    )
+}
+
+/// Write the sections and their pages into `dir`, returning the files
+/// written.
+pub fn write_content(
+    dir: &Path,
+    sections: &[Section],
+    fields: &[(String, String)],
+) -> Result<Vec<PathBuf>, String> {
+    let pages: HashMap<String, (&str, String)> = sections
+        .iter()
+        .flat_map(|section| {
+            section.pages.iter().map(|page| {
+                (
+                    page.anchor.clone(),
+                    (section.category.as_str(), page_file(page)),
+                )
+            })
+        })
+        .collect();
+    let mut written = vec![];
+    let mut write = |path: PathBuf, contents: String| {
+        fs::write(&path, contents)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        written.push(path);
+        Ok::<(), String>(())
+    };
+    for (i, section) in sections.iter().enumerate() {
+        let section_dir = dir.join(&section.category);
+        fs::create_dir_all(&section_dir)
+            .map_err(|e| format!("Error creating {}: {}", section_dir.display(), e))?;
+        write(
+            section_dir.join("_index.md"),
+            link_pages(
+                &render_section(section, i + 1, fields),
+                &section.category,
+                &pages,
+            ),
+        )?;
+        for (j, page) in section.pages.iter().enumerate() {
+            write(
+                section_dir.join(page_file(page)),
+                link_pages(&render_page(page, j + 1, fields), &section.category, &pages),
+            )?;
+        }
+    }
+    Ok(written)
+}
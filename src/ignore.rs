@@ -0,0 +1,229 @@
+//! Ignore files for the directory walk of `--dir`.
+//!
+//! While walking a directory, the `.gitignore` and `.docgenignore` files
+//! found in it and in each directory below are read, so vendored or
+//! generated Nix files are left out of the documentation. The patterns
+//! follow `.gitignore`:
+//!
+//! - blank lines and lines starting with `#` are skipped, a leading `\`
+//!   escapes `#` or `!`,
+//! - `!` re-includes what an earlier pattern excluded, the last matching
+//!   pattern winning, with patterns of deeper files after those above them,
+//! - a trailing `/` matches directories only,
+//! - a pattern with a `/` elsewhere is relative to the directory of its
+//!   file, otherwise it matches a name at any depth,
+//! - `*` and `?` match within a path segment and `**` across them,
+//! - `[...]` matches a character of a class, such as `[oa]`, `[a-z]` or,
+//!   negated, `[!0-9]`, and `\` makes the character after it literal.
+//!
+//! Ignored directories are not descended into, so a file below one cannot be
+//! re-included. Extra ignore files given on the command line apply from the
+//! root of the walk.
+
+use crate::config::glob_match_file;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Ignore file of the same name as git's.
+pub const GITIGNORE: &str = ".gitignore";
+
+/// Ignore file read only by docgen.
+pub const DOCGENIGNORE: &str = ".docgenignore";
+
+/// A line of an ignore file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub glob: String,
+    /// Re-includes matching paths (`!`).
+    pub negated: bool,
+    /// Matches directories only (trailing `/`).
+    pub dir_only: bool,
+    /// Matches the path relative to the ignore file rather than a name at
+    /// any depth.
+    pub anchored: bool,
+}
+
+impl Pattern {
+    /// Whether the pattern matches `path`, relative to the directory of its
+    /// ignore file with `/` separators.
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match_file(&self.glob, path)
+        } else {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            glob_match_file(&self.glob, name)
+        }
+    }
+}
+
+/// Parse the patterns of an ignore file.
+pub fn parse(text: &str) -> Vec<Pattern> {
+    text.lines()
+        .filter_map(|line| {
+            // Trailing spaces are dropped unless escaped.
+            let line = if line.ends_with("\\ ") {
+                line
+            } else {
+                line.trim_end()
+            };
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => match line.strip_prefix('\\') {
+                    Some(rest) if rest.starts_with(['#', '!']) => (false, rest),
+                    _ => (false, line),
+                },
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            if line.is_empty() {
+                return None;
+            }
+            let anchored = line.contains('/');
+            Some(Pattern {
+                glob: line.trim_start_matches('/').replace("\\ ", " "),
+                negated,
+                dir_only,
+                anchored,
+            })
+        })
+        .collect()
+}
+
+/// Which ignore files to read.
+#[derive(Debug, Clone)]
+pub struct IgnoreOptions {
+    /// Read `.gitignore` files.
+    pub gitignore: bool,
+    /// Read `.docgenignore` files.
+    pub docgenignore: bool,
+    /// Ignore files applying from the root of the walk.
+    pub files: Vec<PathBuf>,
+}
+
+impl Default for IgnoreOptions {
+    fn default() -> Self {
+        IgnoreOptions {
+            gitignore: true,
+            docgenignore: true,
+            files: vec![],
+        }
+    }
+}
+
+/// The patterns of an ignore file with the directory they are relative to,
+/// as a path from the root of the walk.
+struct Rules {
+    base: String,
+    patterns: Vec<Pattern>,
+}
+
+/// Whether the last pattern matching `path`, relative to the root of the
+/// walk, excludes it.
+fn is_ignored(rules: &[Rules], path: &str, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rules in rules {
+        let relative = if rules.base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(rules.base.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(relative) => relative,
+                None => continue,
+            }
+        };
+        for pattern in &rules.patterns {
+            if pattern.matches(relative, is_dir) {
+                ignored = !pattern.negated;
+            }
+        }
+    }
+    ignored
+}
+
+fn read_rules(file: &Path, base: &str) -> Result<Rules, String> {
+    let text =
+        fs::read_to_string(file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+    Ok(Rules {
+        base: base.to_string(),
+        patterns: parse(&text),
+    })
+}
+
+/// The files below `root` not excluded by the ignore files selected in
+/// `options`, sorted by path.
+pub fn walk(root: &Path, options: &IgnoreOptions) -> Result<Vec<PathBuf>, String> {
+    fn walk_dir(
+        dir: &Path,
+        relative: &str,
+        options: &IgnoreOptions,
+        rules: &mut Vec<Rules>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        let depth = rules.len();
+        for (name, read) in [
+            (GITIGNORE, options.gitignore),
+            (DOCGENIGNORE, options.docgenignore),
+        ] {
+            let file = dir.join(name);
+            if read && file.is_file() {
+                rules.push(read_rules(&file, relative)?);
+            }
+        }
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+            let path = entry.path();
+            let file_type = entry
+                .file_type()
+                .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+            // Links to directories are not followed, as they can form cycles.
+            if file_type.is_symlink() && path.is_dir() {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let path_relative = if relative.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", relative, name)
+            };
+            let is_dir = file_type.is_dir();
+            // Git never looks into its own directory.
+            if (is_dir && options.gitignore && name == ".git")
+                || is_ignored(rules, &path_relative, is_dir)
+            {
+                continue;
+            }
+            if is_dir {
+                walk_dir(&path, &path_relative, options, rules, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        rules.truncate(depth);
+        Ok(())
+    }
+
+    let mut rules = options
+        .files
+        .iter()
+        .map(|file| read_rules(file, ""))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut files = vec![];
+    walk_dir(root, "", options, &mut rules, &mut files)?;
+    files.sort();
+    Ok(files)
+}
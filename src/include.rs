@@ -0,0 +1,184 @@
+//! Shared fragments included in doc comments.
+//!
+//! A line of a doc comment, file doc comment or preamble
+//!
+//! ```markdown
+//! @include ./fragments/warning.md
+//! ```
+//!
+//! is replaced by the markdown file it names, so notes repeated across many
+//! functions are written once. The path is relative to the file the line is
+//! written in: the Nix file, the description file or, for a preamble given
+//! on the command line, the working directory. The fragment's headings are
+//! shifted below the last heading before the line, so a fragment starting
+//! with `#` fits wherever it is included. Fragments can include other
+//! fragments, relative to their own directory; a fragment including itself
+//! is an error. Fragments must be within the directory of the file the
+//! first `@include` line is written in, so a doc comment cannot pull in
+//! files from elsewhere on the machine with an absolute path or `..`.
+
+use crate::commonmark::ManualEntry;
+use crate::format::{get_fence, heading_level, shift_headings, trim_leading_whitespace};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directive starting a line.
+pub const DIRECTIVE: &str = "@include";
+
+/// The lines of `text` with whether each is outside code blocks.
+fn lines_outside_code(text: &str) -> Vec<(&str, bool)> {
+    let mut fence: Option<(usize, char)> = None;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let fence_line = trim_leading_whitespace(line, 3);
+            match fence {
+                Some((count, ch)) => {
+                    if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                        fence = None;
+                    }
+                    (line, false)
+                }
+                None => {
+                    fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                    (line, fence.is_none())
+                }
+            }
+        })
+        .collect()
+}
+
+/// Read the fragment at `path`, which must be within `root`, expanded and
+/// with its top headings at `level`.
+fn fragment(
+    path: &Path,
+    level: usize,
+    root: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, String> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    if !canonical.starts_with(root) {
+        return Err(format!(
+            "{} is outside {}, which included fragments must be within",
+            path.display(),
+            root.display()
+        ));
+    }
+    if stack.contains(&canonical) {
+        return Err(format!("{} includes itself", path.display()));
+    }
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    stack.push(canonical);
+    let text = expand(&text, dir, 0, root, stack)?;
+    stack.pop();
+    let top = lines_outside_code(&text)
+        .into_iter()
+        .filter(|(_, outside)| *outside)
+        .filter_map(|(line, _)| heading_level(line))
+        .min();
+    Ok(match top {
+        Some(top) if top < level => shift_headings(&text, level - top),
+        _ => text,
+    })
+}
+
+fn expand(
+    text: &str,
+    dir: &Path,
+    level: usize,
+    root: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut level = level;
+    for (line, outside) in lines_outside_code(text) {
+        if outside {
+            if let Some(path) = line.trim().strip_prefix(DIRECTIVE)
+                && path.starts_with(char::is_whitespace)
+            {
+                let path = Path::new(path.trim());
+                if path.is_absolute() {
+                    return Err(format!(
+                        "{} {}: included fragments must be relative paths",
+                        DIRECTIVE,
+                        path.display()
+                    ));
+                }
+                let included = fragment(&dir.join(path), level + 1, root, stack)?;
+                out.push_str(included.trim_end_matches('\n'));
+                if line.ends_with('\n') {
+                    out.push('\n');
+                }
+                continue;
+            }
+            if let Some(heading) = heading_level(line) {
+                level = heading;
+            }
+        }
+        out.push_str(line);
+    }
+    Ok(out)
+}
+
+/// Replace the `@include` lines of `text`, outside code blocks, with the
+/// fragments they name, relative to `dir` and within it. `level` is the
+/// level of the heading `text` is written under.
+pub fn expand_includes<'a>(
+    text: &'a str,
+    dir: &Path,
+    level: usize,
+) -> Result<Cow<'a, str>, String> {
+    if !text.contains(DIRECTIVE) {
+        return Ok(Cow::Borrowed(text));
+    }
+    let root = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Error reading {}: {}", root.display(), e))?;
+    expand(text, dir, level, &root, &mut vec![]).map(Cow::Owned)
+}
+
+/// The fragments `text` includes, relative to `dir`, and those they include
+/// in turn, whether or not they can be read.
+pub fn included_files(text: &str, dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![];
+    let mut pending = vec![(text.to_string(), dir.to_path_buf())];
+    while let Some((text, dir)) = pending.pop() {
+        for (line, outside) in lines_outside_code(&text) {
+            let Some(path) = line.trim().strip_prefix(DIRECTIVE) else {
+                continue;
+            };
+            if !outside || !path.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let path = dir.join(path.trim());
+            if files.contains(&path) {
+                continue;
+            }
+            if let Ok(included) = fs::read_to_string(&path) {
+                let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                pending.push((included, parent));
+            }
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Expand the `@include` lines of the description of `entry`, written under
+/// its `##` heading.
+pub fn expand_entry(entry: &mut ManualEntry, dir: &Path) -> Result<(), String> {
+    let description = entry.description.join("\n\n");
+    if let Cow::Owned(expanded) = expand_includes(&description, dir, 2)? {
+        entry.description = expanded.split("\n\n").map(String::from).collect();
+    }
+    Ok(())
+}
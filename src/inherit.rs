@@ -0,0 +1,122 @@
+//! Documentation copied from another binding.
+//!
+//! A thin wrapper or alias names the function whose docs it shares with an
+//! `@inheritDoc` line, rather than repeating its doc comment:
+//!
+//! ```nix
+//! /**
+//!   Alias of `concatStringsSep`, kept for compatibility.
+//!
+//!   @inheritDoc lib.strings.concatStringsSep
+//! */
+//! joinStrings = concatStringsSep;
+//! ```
+//!
+//! The entry gets the description of the named function after its own, and
+//! the type, example, argument docs, return value and `@see` references it
+//! does not give itself. Its stability, deprecation and `@since` release are
+//! its own. The function is named like with `@see` (see [`crate::see`]): by
+//! its title, or its name within the category of the entry. It is looked up
+//! in the same file first, including bindings left out of the output, and
+//! then among all files of the run. Functions inheriting their docs can be
+//! inherited from in turn.
+
+use crate::commonmark::{Argument, ManualEntry, get_title};
+
+/// The tag naming the function to inherit from.
+pub const TAG: &str = "@inheritDoc";
+
+/// Whether `entry` is the function `name`, referred to by `from`.
+fn is_named(entry: &ManualEntry, name: &str, from: &ManualEntry) -> bool {
+    let title = entry.get_ident_title().1;
+    title == name || title == get_title(&from.prefix, &from.category, name)
+}
+
+/// Add the docs of `source` that `entry` lacks to it.
+fn inherit(entry: &mut ManualEntry, source: &ManualEntry) {
+    entry
+        .description
+        .retain(|paragraph| !paragraph.trim().is_empty());
+    entry.description.extend(source.description.iter().cloned());
+    if entry.fn_type.is_none() {
+        entry.fn_type = source.fn_type.clone();
+    }
+    match (&mut entry.signature, &source.signature) {
+        (None, signature) => entry.signature = signature.clone(),
+        (Some(signature), Some(source)) if signature.fn_type.is_none() => {
+            signature.fn_type = source.fn_type.clone();
+        }
+        _ => {}
+    }
+    if entry.example.is_none() {
+        entry.example = source.example.clone();
+    }
+    if entry.returns.is_none() {
+        entry.returns = source.returns.clone();
+    }
+    if entry.see_also.is_empty() {
+        entry.see_also = source.see_also.clone();
+    }
+    if entry.args.is_empty() {
+        entry.args = source.args.clone();
+        return;
+    }
+    let source_args: Vec<_> = source
+        .args
+        .iter()
+        .flat_map(|arg| match arg {
+            Argument::Flat(arg) => std::slice::from_ref(arg),
+            Argument::Pattern(args) => args.as_slice(),
+        })
+        .collect();
+    for arg in &mut entry.args {
+        let args = match arg {
+            Argument::Flat(arg) => std::slice::from_mut(arg),
+            Argument::Pattern(args) => args.as_mut_slice(),
+        };
+        for arg in args.iter_mut().filter(|arg| arg.doc.is_none()) {
+            arg.doc = source_args
+                .iter()
+                .find(|source| source.name == arg.name)
+                .and_then(|source| source.doc.clone());
+        }
+    }
+}
+
+/// Copy the docs named by the `@inheritDoc` tags of `entries` into them,
+/// where the function named is among `entries`. Returns the tags that could
+/// not be resolved, as `(title of the entry, tag as written)`; their entries
+/// keep them, to be resolved against more entries.
+pub fn inherit_docs<'a>(
+    entries: impl IntoIterator<Item = &'a mut ManualEntry>,
+) -> Vec<(String, String)> {
+    let mut entries: Vec<&mut ManualEntry> = entries.into_iter().collect();
+    // Entries inheriting from entries that inherit wait for them, so chains
+    // resolve in any order and cycles are left unresolved.
+    loop {
+        let mut resolved = false;
+        for i in 0..entries.len() {
+            let Some(name) = entries[i].inherit_doc.clone() else {
+                continue;
+            };
+            let source = entries
+                .iter()
+                .find(|source| source.inherit_doc.is_none() && is_named(source, &name, entries[i]));
+            if let Some(source) = source.map(|source| (*source).clone()) {
+                inherit(entries[i], &source);
+                entries[i].inherit_doc = None;
+                resolved = true;
+            }
+        }
+        if !resolved {
+            break;
+        }
+    }
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let name = entry.inherit_doc.as_ref()?;
+            Some((entry.get_ident_title().1, format!("{} {}", TAG, name)))
+        })
+        .collect()
+}
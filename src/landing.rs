@@ -0,0 +1,160 @@
+//! Curated landing pages for categories.
+//!
+//! A landing page is a markdown file that replaces the file-level doc
+//! comment at the top of a category page. Its front matter can set the page
+//! title and list featured functions, which are linked at the top of the page
+//! and listed first, in the given order:
+//!
+//! ```markdown
+//! ---
+//! title: String manipulation
+//! featured:
+//!   - concatStrings
+//!   - splitString
+//! ---
+//! Functions for working with strings.
+//! ```
+//!
+//! Featured functions are named by their attribute path within the file or
+//! by their full title (`lib.strings.concatStrings`). Each has to be one of the
+//! documented entries.
+
+use crate::commonmark::ManualEntry;
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Landing {
+    /// Page title, replacing `--description`.
+    pub title: Option<String>,
+    pub featured: Vec<String>,
+    /// Markdown after the front matter.
+    pub body: String,
+}
+
+/// Split a landing page into its front matter and body.
+pub fn parse(text: &str) -> Result<Landing, String> {
+    let mut landing = Landing::default();
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        landing.body = text.trim().to_string();
+        return Ok(landing);
+    };
+    let mut lines = rest.lines();
+    let mut in_featured = false;
+    for (i, line) in lines.by_ref().enumerate() {
+        let line_no = i + 2;
+        if line.trim_end() == "---" {
+            landing.body = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+            return Ok(landing);
+        }
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(item) = line.trim_start().strip_prefix("- ")
+            && line.starts_with(char::is_whitespace)
+        {
+            if !in_featured {
+                return Err(format!("line {}: list item outside of 'featured'", line_no));
+            }
+            landing.featured.push(unquote(item).to_string());
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("line {}: expected 'key: value'", line_no))?;
+        let value = value.trim();
+        in_featured = false;
+        match key.trim() {
+            "title" => landing.title = Some(unquote(value).to_string()),
+            "featured" if value.is_empty() => in_featured = true,
+            "featured" => {
+                let items = value
+                    .strip_prefix('[')
+                    .and_then(|v| v.strip_suffix(']'))
+                    .ok_or_else(|| format!("line {}: expected a list of names", line_no))?;
+                landing.featured.extend(
+                    items
+                        .split(',')
+                        .map(|item| unquote(item.trim()).to_string())
+                        .filter(|item| !item.is_empty()),
+                );
+            }
+            other => return Err(format!("line {}: unknown key '{}'", line_no, other)),
+        }
+    }
+    Err("front matter is not closed with '---'".to_string())
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+/// Move the featured entries to the front, in the order they are listed.
+pub fn order_entries(
+    landing: &Landing,
+    entries: Vec<ManualEntry>,
+) -> Result<Vec<ManualEntry>, String> {
+    let mut rest: Vec<Option<ManualEntry>> = entries.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(rest.len());
+    for name in &landing.featured {
+        let position = rest.iter().position(|entry| {
+            entry
+                .as_ref()
+                .is_some_and(|e| e.name == *name || e.get_ident_title().1 == *name)
+        });
+        match position {
+            Some(i) => ordered.push(rest[i].take().expect("entry is present")),
+            None if ordered
+                .iter()
+                .any(|e: &ManualEntry| e.name == *name || e.get_ident_title().1 == *name) =>
+            {
+                return Err(format!("featured function '{}' is listed twice", name));
+            }
+            None => return Err(format!("featured function '{}' is not documented", name)),
+        }
+    }
+    ordered.extend(rest.into_iter().flatten());
+    Ok(ordered)
+}
+
+/// The heading and body of a category page with a landing page, followed
+/// by links to the featured entries (the first ones of `entries`).
+pub fn render_header(
+    landing: &Landing,
+    title: &str,
+    category: &str,
+    entries: &[ManualEntry],
+    anchor_prefix: &str,
+) -> String {
+    let title = landing.title.as_deref().unwrap_or(title);
+    let mut output = format!("# {} {{#sec-functions-library-{}}}\n", title, category);
+    if !landing.body.is_empty() {
+        output.push_str(&landing.body);
+        output.push('\n');
+    }
+    if landing.featured.is_empty() {
+        return output;
+    }
+
+    let _ = write!(
+        output,
+        "\n## Featured {{#sec-functions-library-{}-featured}}\n\n",
+        category
+    );
+    for entry in &entries[..landing.featured.len()] {
+        let (_, title) = entry.get_ident_title();
+        let _ = write!(output, "- [`{}`](#{})", title, entry.anchor(anchor_prefix));
+        let summary = entry.summary();
+        if !summary.is_empty() {
+            let _ = write!(output, ": {}", summary);
+        }
+        output.push('\n');
+    }
+    output
+}
@@ -0,0 +1,99 @@
+//! Doc comments in the format nixdoc read before RFC 145.
+//!
+//! Older library files document bindings in ordinary block comments, with
+//! the type and an example in sections of their own:
+//!
+//! ```nix
+//! /* Concatenate a list of strings.
+//!
+//!    Type: concatStrings :: [string] -> string
+//!
+//!    Example:
+//!      concatStrings ["foo" "bar"]
+//!      => "foobar"
+//! */
+//! concatStrings = builtins.concatStringsSep "";
+//! ```
+//!
+//! With `--legacy-comments`, a binding without a `/** */` doc comment is
+//! documented by a block comment directly before it (see
+//! [`crate::collect_legacy_docs`]); without it, ordinary comments are never
+//! read as docs. The lines before `Type:` are its description, the
+//! rest of the `Type:` line and the lines up to `Example:` its type and the
+//! lines after `Example:` its example.
+
+use crate::format::handle_indentation;
+use rnix::{NodeOrToken, SyntaxKind, SyntaxNode};
+use textwrap::dedent;
+
+/// A legacy doc comment split into its sections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LegacyDoc {
+    pub description: String,
+    pub fn_type: Option<String>,
+    pub example: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Description,
+    Type,
+    Example,
+}
+
+/// Split the text of a legacy doc comment, without its `/*` and `*/`, into
+/// its sections.
+pub fn parse(comment: &str) -> LegacyDoc {
+    let mut section = Section::Description;
+    let mut description = String::new();
+    let mut fn_type = String::new();
+    let mut example = String::new();
+    for line in comment.lines() {
+        let trimmed = line.trim_start();
+        let line = if let Some(rest) = trimmed.strip_prefix("Type:") {
+            section = Section::Type;
+            rest
+        } else if let Some(rest) = trimmed.strip_prefix("Example:") {
+            section = Section::Example;
+            rest
+        } else {
+            line
+        };
+        let text = match section {
+            Section::Description => &mut description,
+            Section::Type => &mut fn_type,
+            Section::Example => &mut example,
+        };
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    let section = |text: &str| Some(dedent(text).trim().to_string()).filter(|s| !s.is_empty());
+    LegacyDoc {
+        description: handle_indentation(&description).unwrap_or_default(),
+        fn_type: section(&fn_type),
+        example: section(&example),
+    }
+}
+
+/// The legacy doc comment directly before `node`, with only whitespace in
+/// between.
+pub fn retrieve(node: &SyntaxNode) -> Option<LegacyDoc> {
+    let mut prev = node.prev_sibling_or_token();
+    while let Some(NodeOrToken::Token(token)) = prev {
+        match token.kind() {
+            SyntaxKind::TOKEN_WHITESPACE => prev = token.prev_sibling_or_token(),
+            SyntaxKind::TOKEN_COMMENT => {
+                let text = token.text();
+                if text.starts_with("/**") {
+                    return None;
+                }
+                return text
+                    .strip_prefix("/*")
+                    .and_then(|text| text.strip_suffix("*/"))
+                    .map(parse);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
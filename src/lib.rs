@@ -0,0 +1,1422 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Documentation extraction for Nix files.
+//!
+//! This crate holds everything behind the `docgen` command line tool, for
+//! tools that want to generate documentation without running it:
+//!
+//! - [`parse_file`] and [`collect_entries`] turn a Nix file into
+//!   [`ManualEntry`] values, one per documented binding, also reading
+//!   comments written before RFC 145, and [`extract_file_doc`] returns the
+//!   file's own doc comment; problems with doc comments are returned to the
+//!   caller as warnings rather than printed,
+//! - [`ManualEntry::write_section`] renders an entry as CommonMark, and the
+//!   [`options`], [`antora`], [`asciidoc`], [`devdocs`], [`docbook`],
+//!   [`html`], [`hugo`], [`man`], [`mdx`], [`rst`], [`search`], [`tags`]
+//!   and [`typst`] modules render module options, Antora components,
+//!   AsciiDoc, DevDocs sets, DocBook, HTML pages, Hugo content trees, man
+//!   pages, MDX, reStructuredText, search documents, tags files and Typst
+//!   markup, [`lunr`] builds client-side search indexes, [`schema`] converts
+//!   configuration schemas into module options and [`static_options`] reads
+//!   them from module source without evaluation,
+//! - [`render`] is the interface of the `--format` backends, for adding
+//!   output formats of your own,
+//! - [`manifest`], [`summary`], [`nav`], [`changes`] and [`metrics`] describe
+//!   generated output, [`provenance`] traces it back to its inputs, [`see`]
+//!   links `@see` references, [`inherit`] resolves `@inheritDoc` tags,
+//!   [`cache`] keeps loaded entries between runs, [`structured`] gives entries the fields of
+//!   version 2 of the JSON output and [`output_schema`] is the JSON Schema of
+//!   the JSON output.
+//!
+//! ```no_run
+//! use std::collections::HashMap;
+//! use std::path::Path;
+//!
+//! let root = docgen::parse_file(Path::new("lib/strings.nix")).unwrap();
+//! let mut warnings = vec![];
+//! let entries =
+//!     docgen::collect_entries(root, "lib", "strings", &HashMap::new(), &None, &mut warnings);
+//! for warning in &warnings {
+//!     eprintln!("warning: {}", warning);
+//! }
+//! let mut markdown = String::new();
+//! for entry in &entries {
+//!     entry.write_section("function-library-", &Default::default(), &mut markdown);
+//! }
+//! ```
+
+pub mod admonition;
+pub mod antora;
+pub mod asciidoc;
+pub mod cache;
+pub mod changes;
+mod comment;
+pub mod commonmark;
+pub mod config;
+pub mod devdocs;
+pub mod docbook;
+pub mod doctest;
+pub mod eval;
+pub mod flake;
+pub mod format;
+pub mod frontmatter;
+pub mod groups;
+pub mod html;
+pub mod hugo;
+pub mod ignore;
+pub mod include;
+pub mod inherit;
+pub mod landing;
+mod legacy;
+pub mod lint;
+pub mod locale;
+pub mod lunr;
+pub mod man;
+pub mod manifest;
+pub mod mdx;
+pub mod metrics;
+pub mod nav;
+pub mod nixfmt;
+pub mod normalize;
+pub mod options;
+pub mod output_schema;
+pub mod plan;
+pub mod provenance;
+pub mod render;
+pub mod rst;
+pub mod sanitize;
+pub mod schema;
+pub mod search;
+pub mod see;
+pub mod signature;
+pub mod snapshot;
+pub mod static_options;
+pub mod structured;
+pub mod summary;
+pub mod tags;
+pub mod types;
+pub mod typst;
+pub mod validate;
+pub mod variants;
+mod xref;
+
+use crate::comment::get_expr_docs;
+use crate::commonmark::{
+    Deprecation, INTERNAL_MARKER, ParamDocs, Stability, get_identifier, take_anchor,
+    take_deprecation, take_inherit_doc, take_internal, take_param_docs, take_see_also, take_since,
+    take_stability, take_tags,
+};
+use crate::format::{handle_indentation, shift_headings_with_warnings};
+use crate::sanitize::{HtmlPolicy, sanitize_html};
+use rnix::{
+    SyntaxKind, SyntaxNode,
+    ast::{
+        Attr, AttrpathValue, Expr, HasEntry, Ident, Inherit, InterpolPart, Lambda, LetIn, Param,
+        UnaryOpKind,
+    },
+};
+use rowan::{WalkEvent, ast::AstNode};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use crate::commonmark::{Argument, EntryKind, ManualEntry, SingleArg};
+
+/// Read and parse a Nix file.
+pub fn parse_file(path: &Path) -> Result<rnix::Root, String> {
+    let src =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    rnix::Root::parse(&src)
+        .ok()
+        .map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+#[derive(Debug, Default)]
+struct DocComment {
+    /// Primary documentation string.
+    doc: String,
+    /// Type from the `Type:` section of a legacy comment.
+    fn_type: Option<String>,
+    /// Example from the `Example:` section of a legacy comment.
+    example: Option<String>,
+    /// Argument docs from the `@param` and `@return` tags of the comment.
+    param_docs: ParamDocs,
+}
+
+impl DocComment {
+    /// The doc comment `doc`, without its `@param` and `@return` lines.
+    fn new(doc: String) -> Self {
+        let (rest, param_docs) = take_param_docs(&doc);
+        DocComment {
+            doc: rest.into_owned(),
+            param_docs,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DocItem {
+    name: String,
+    comment: DocComment,
+    args: Vec<Argument>,
+    params: Vec<signature::SignatureParam>,
+    value: Option<String>,
+}
+
+/// Returns a RFC145 doc-comment if one is present, adding problems with its
+/// headings to `warnings`.
+fn retrieve_doc_comment(
+    node: &SyntaxNode,
+    shift_headings_by: Option<usize>,
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    let doc_comment = get_expr_docs(node);
+
+    doc_comment.map(|doc_comment| {
+        let (doc, shift_warnings) = shift_headings_with_warnings(
+            &handle_indentation(&doc_comment).unwrap_or_default(),
+            // H1 to H4 can be used in the doc-comment with the current rendering.
+            // They will be shifted to H3, H6
+            // H1 and H2 are currently used by the outer rendering. (category and function name)
+            shift_headings_by.unwrap_or(2),
+        );
+        if !shift_warnings.is_empty() {
+            let line = source_line(node);
+            for warning in shift_warnings {
+                warnings.push(format!("doc comment near line {}: {}", line, warning));
+            }
+        }
+        doc
+    })
+}
+
+/// 1-based line of the source file on which `node` starts.
+fn source_line(node: &SyntaxNode) -> usize {
+    let root = node.ancestors().last().unwrap_or_else(|| node.clone());
+    let mut line = 1;
+    root.text()
+        .slice(..node.text_range().start())
+        .for_each_chunk(|chunk| line += chunk.matches('\n').count());
+    line
+}
+
+/// Traverse directly chained nix lambdas and collect the identifiers of all lambda arguments.
+/// Arguments without a doc comment of their own are documented by their
+/// `@param` tag in `docs`, if any.
+fn collect_lambda_args(
+    mut lambda: Lambda,
+    docs: &ParamDocs,
+    warnings: &mut Vec<String>,
+) -> Vec<Argument> {
+    let mut args = vec![];
+    let mut single_arg = |name: String, node: &SyntaxNode| {
+        let doc = retrieve_doc_comment(node, Some(1), warnings).unwrap_or_default();
+        let doc = handle_indentation(&doc)
+            .or_else(|| docs.get(&name).map(String::from))
+            .filter(|doc| !doc.is_empty());
+        SingleArg { name, doc }
+    };
+
+    loop {
+        match lambda.param().unwrap() {
+            Param::IdentParam(id) => {
+                args.push(Argument::Flat(single_arg(id.to_string(), id.syntax())));
+            }
+            Param::Pattern(pat) => {
+                let pattern_vec: Vec<_> = pat
+                    .pat_entries()
+                    .map(|entry| single_arg(entry.ident().unwrap().to_string(), entry.syntax()))
+                    .collect();
+
+                args.push(Argument::Pattern(pattern_vec));
+            }
+        }
+
+        match lambda.body() {
+            Some(Expr::Lambda(inner)) => lambda = inner,
+            _ => break,
+        }
+    }
+
+    args
+}
+
+/// Largest list or attribute set that is documented as a constant.
+const MAX_CONSTANT_ITEMS: usize = 10;
+
+/// Whether an expression is a plain value: a number, a string without
+/// interpolation, `true`, `false`, `null`, or a small list or non-recursive
+/// attribute set of such values.
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Str(s) => s.parts().all(|p| matches!(p, InterpolPart::Literal(_))),
+        Expr::Ident(i) => matches!(i.to_string().as_str(), "true" | "false" | "null"),
+        Expr::UnaryOp(op) => {
+            op.operator() == Some(UnaryOpKind::Negate)
+                && matches!(op.expr(), Some(Expr::Literal(_)))
+        }
+        Expr::Paren(p) => p.expr().is_some_and(|e| is_constant(&e)),
+        Expr::List(list) => {
+            list.items().count() <= MAX_CONSTANT_ITEMS && list.items().all(|e| is_constant(&e))
+        }
+        Expr::AttrSet(set) => {
+            set.rec_token().is_none()
+                && set.inherits().next().is_none()
+                && set.attrpath_values().count() <= MAX_CONSTANT_ITEMS
+                && set
+                    .attrpath_values()
+                    .all(|apv| apv.value().is_some_and(|v| is_constant(&v)))
+        }
+        _ => false,
+    }
+}
+
+/// Transforms an AST node into a `DocItem` if it has a leading
+/// documentation comment, or with `legacy`, a [`legacy`] one instead.
+fn retrieve_doc_item(
+    node: &AttrpathValue,
+    legacy: bool,
+    warnings: &mut Vec<String>,
+) -> Option<DocItem> {
+    let ident = node.attrpath().unwrap();
+    let item_name = ident.to_string();
+
+    let comment = if legacy {
+        let legacy = legacy::retrieve(node.syntax())?;
+        DocComment {
+            fn_type: legacy.fn_type,
+            example: legacy.example,
+            ..DocComment::new(legacy.description)
+        }
+    } else {
+        DocComment::new(retrieve_doc_comment(node.syntax(), Some(2), warnings)?)
+    };
+
+    Some(DocItem {
+        name: item_name,
+        comment,
+        args: vec![],
+        params: vec![],
+        value: None,
+    })
+}
+
+impl DocItem {
+    fn into_entry(
+        self,
+        prefix: &str,
+        category: &str,
+        locs: &HashMap<String, String>,
+        warnings: &mut Vec<String>,
+    ) -> ManualEntry {
+        let ident = get_identifier(prefix, category, &self.name);
+        let (doc, stability) = take_stability(&self.comment.doc);
+        let (doc, deprecated) = take_deprecation(&doc);
+        let (doc, since) = take_since(&doc);
+        let (doc, see_also) = take_see_also(&doc);
+        let (doc, internal) = take_internal(&doc, INTERNAL_MARKER);
+        let (doc, inherit_doc) = take_inherit_doc(&doc);
+        let (doc, custom_anchor) = take_anchor(&doc);
+        let (doc, tags) = take_tags(&doc);
+        let mut warn = |e: String| warnings.push(format!("{}: {}", ident, e));
+        let stability = stability.and_then(|tier| tier.map_err(&mut warn).ok());
+        // `@deprecated` puts the entry in the deprecated tier, and
+        // `@stability deprecated` deprecates it without a note.
+        let stability = if deprecated.is_some() {
+            Some(Stability::Deprecated)
+        } else {
+            stability
+        };
+        let deprecated = deprecated
+            .or_else(|| (stability == Some(Stability::Deprecated)).then(Deprecation::default));
+        let custom_anchor = custom_anchor.and_then(|id| id.map_err(&mut warn).ok());
+        let mut description: Vec<String> = doc.split("\n\n").map(|s| s.to_string()).collect();
+        let type_text = signature::take_type_section(&mut description).or(self.comment.fn_type);
+        let fn_type = type_text.as_deref().map(signature::parse_type);
+        let example = self
+            .comment
+            .example
+            .or_else(|| format::take_example_section(&mut description));
+        let signature =
+            (!self.params.is_empty() || fn_type.is_some()).then_some(signature::Signature {
+                params: self.params,
+                fn_type,
+            });
+        let ParamDocs { params, returns } = self.comment.param_docs;
+        let mut args = self.args;
+        if args.is_empty() {
+            // A function that is not written as a lambda, such as a partial
+            // application, has the arguments of its `@param` tags.
+            args = params
+                .into_iter()
+                .map(|(name, doc)| {
+                    Argument::Flat(SingleArg {
+                        name,
+                        doc: Some(doc).filter(|doc| !doc.is_empty()),
+                    })
+                })
+                .collect();
+        } else {
+            for (name, _) in &params {
+                let known = args.iter().any(|arg| match arg {
+                    Argument::Flat(arg) => arg.name == *name,
+                    Argument::Pattern(attrs) => attrs.iter().any(|attr| attr.name == *name),
+                });
+                if !known {
+                    warn(format!("@param {} names no argument", name));
+                }
+            }
+        }
+
+        ManualEntry {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            location: locs.get(&ident).cloned(),
+            name: self.name,
+            description,
+            fn_type: type_text,
+            example,
+            usage: None,
+            args,
+            signature,
+            kind: if self.value.is_some() {
+                EntryKind::Constant
+            } else {
+                EntryKind::Function
+            },
+            value: self.value,
+            stability,
+            deprecated,
+            since,
+            see_also,
+            returns,
+            internal,
+            inherit_doc,
+            custom_anchor,
+            tags,
+            meta: vec![],
+        }
+    }
+}
+
+/// Suffix of a sibling binding holding the metadata of a binding.
+const META_SUFFIX: &str = "Meta";
+
+/// Attribute holding the metadata of the bindings of its set, by name.
+const DOCS_META: &str = "__docsMeta";
+
+/// Metadata value as markdown: strings as they are, lists as their
+/// rendered items separated by commas, anything else as inline code.
+fn meta_value(expr: Expr) -> String {
+    match unparen(expr) {
+        Some(Expr::List(list)) => list.items().map(meta_value).collect::<Vec<_>>().join(", "),
+        Some(expr) => string_literal(&expr).unwrap_or_else(|| {
+            let source = expr.syntax().to_string();
+            format!(
+                "`{}`",
+                source.split_whitespace().collect::<Vec<_>>().join(" ")
+            )
+        }),
+        None => String::new(),
+    }
+}
+
+fn meta_fields(expr: Expr) -> Option<Vec<(String, String)>> {
+    let Some(Expr::AttrSet(set)) = unparen(expr) else {
+        return None;
+    };
+    Some(
+        set.attrpath_values()
+            .filter_map(|apv| Some((apv.attrpath()?.to_string(), meta_value(apv.value()?))))
+            .collect(),
+    )
+}
+
+/// Metadata of the bindings among `nodes`, by binding name: the
+/// `fooMeta = { ... }` siblings of bindings `foo`, and the attributes of a
+/// `__docsMeta = { foo = { ... }; }` set (also written as
+/// `__docsMeta.foo = { ... }`). `__docsMeta` takes precedence.
+fn binding_meta(nodes: impl Iterator<Item = SyntaxNode>) -> HashMap<String, Vec<(String, String)>> {
+    let mut siblings = HashMap::new();
+    let mut docs_meta = HashMap::new();
+    for apv in nodes.filter_map(AttrpathValue::cast) {
+        let (Some(path), Some(value)) = (apv.attrpath(), apv.value()) else {
+            continue;
+        };
+        let name = path.to_string();
+        if name == DOCS_META {
+            let Some(Expr::AttrSet(set)) = unparen(value) else {
+                continue;
+            };
+            for inner in set.attrpath_values() {
+                if let (Some(path), Some(fields)) =
+                    (inner.attrpath(), inner.value().and_then(meta_fields))
+                {
+                    docs_meta.insert(path.to_string(), fields);
+                }
+            }
+        } else if let Some(base) = name
+            .strip_prefix(DOCS_META)
+            .and_then(|n| n.strip_prefix('.'))
+            && let Some(fields) = meta_fields(value.clone())
+        {
+            docs_meta.insert(base.to_string(), fields);
+        } else if let Some(base) = name.strip_suffix(META_SUFFIX)
+            && !base.is_empty()
+            && let Some(fields) = meta_fields(value)
+        {
+            siblings.insert(base.to_string(), fields);
+        }
+    }
+    siblings.extend(docs_meta);
+    siblings
+}
+
+/// Names of the bindings among `nodes` that only hold metadata of others in
+/// `names`: `__docsMeta`, and the `fooMeta` attribute sets of bindings `foo`.
+/// Other bindings whose name ends in `Meta` are kept.
+fn meta_binding_names(nodes: impl Iterator<Item = SyntaxNode>, names: &[String]) -> Vec<String> {
+    nodes
+        .filter_map(AttrpathValue::cast)
+        .filter_map(|apv| {
+            let name = apv.attrpath()?.to_string();
+            let is_meta = name == DOCS_META
+                || name.starts_with(&format!("{}.", DOCS_META))
+                || name
+                    .strip_suffix(META_SUFFIX)
+                    .is_some_and(|base| names.iter().any(|n| n == base))
+                    && matches!(apv.value().and_then(unparen), Some(Expr::AttrSet(_)));
+            is_meta.then_some(name)
+        })
+        .collect()
+}
+
+/// Attach the metadata found for each entry, keeping what it has otherwise.
+fn apply_meta<'a>(
+    entries: impl Iterator<Item = &'a mut ManualEntry>,
+    meta: &HashMap<String, Vec<(String, String)>>,
+) {
+    for entry in entries {
+        if let Some(fields) = meta.get(&entry.name) {
+            entry.meta = fields.clone();
+        }
+    }
+}
+
+/// Traverse the arena from a top-level SetEntry and collect, where
+/// possible:
+///
+/// 1. The identifier of the set entry itself.
+/// 2. The attached doc comment on the entry.
+/// 3. The argument names of any curried functions.
+/// 4. The value of constants.
+fn collect_entry_information(
+    entry: AttrpathValue,
+    legacy: bool,
+    warnings: &mut Vec<String>,
+) -> Option<DocItem> {
+    let mut doc_item = retrieve_doc_item(&entry, legacy, warnings)?;
+
+    match entry.value() {
+        Some(Expr::Lambda(l)) => {
+            doc_item.params = signature::lambda_params(l.clone());
+            doc_item.args = collect_lambda_args(l, &doc_item.comment.param_docs, warnings);
+        }
+        Some(value) if is_constant(&value) => {
+            let text = value.syntax().to_string();
+            doc_item.value = Some(nixfmt::reindent(&text).into_owned());
+        }
+        _ => {}
+    }
+
+    Some(doc_item)
+}
+
+/// The file read by `import ./file.nix`, also when the imported function is
+/// applied further (`import ./file.nix { inherit lib; }`).
+fn import_path(expr: &Expr, dir: &std::path::Path) -> Option<PathBuf> {
+    let Expr::Apply(apply) = expr else {
+        return None;
+    };
+    match apply.lambda()? {
+        Expr::Ident(ident) if ident.to_string() == "import" => {
+            let Expr::Path(path) = apply.argument()? else {
+                return None;
+            };
+            let text = path.syntax().text().to_string();
+            if !text.starts_with("./") && !text.starts_with("../") {
+                return None;
+            }
+            let path: PathBuf = dir
+                .join(text)
+                .components()
+                .filter(|c| !matches!(c, std::path::Component::CurDir))
+                .collect();
+            Some(if path.is_dir() {
+                path.join("default.nix")
+            } else {
+                path
+            })
+        }
+        inner => import_path(&inner, dir),
+    }
+}
+
+/// The files `root`, a file in `dir`, re-exports attributes of with
+/// `inherit (import ./file.nix) name;`, whose docs are read with its own.
+pub fn imported_files(root: &rnix::Root, dir: &std::path::Path) -> Vec<PathBuf> {
+    root.syntax()
+        .descendants()
+        .filter_map(Inherit::cast)
+        .filter_map(|inherit| inherit.from()?.expr())
+        .filter_map(|expr| import_path(&expr, dir))
+        .collect()
+}
+
+/// Documentation of the attributes re-exported with
+/// `inherit (import ./file.nix) name;`, taken from the imported file.
+///
+/// Entries without a location from the location data are located at their
+/// binding in the imported file. Re-exports in the imported file itself are
+/// not followed.
+fn imported_entries(
+    inherit: &Inherit,
+    dir: &std::path::Path,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> Vec<ManualEntry> {
+    let Some(path) = inherit
+        .from()
+        .and_then(|from| from.expr())
+        .and_then(|expr| import_path(&expr, dir))
+    else {
+        return vec![];
+    };
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) => {
+            warnings.push(format!("{}: {}", path.display(), e));
+            return vec![];
+        }
+    };
+    let Ok(root) = rnix::Root::parse(&src).ok() else {
+        warnings.push(format!("{}: failed to parse", path.display()));
+        return vec![];
+    };
+
+    let names: Vec<String> = inherit
+        .attrs()
+        .filter_map(|a| match a {
+            Attr::Ident(i) => Some(i.to_string()),
+            _ => None,
+        })
+        .collect();
+    let bindings: Vec<AttrpathValue> = root
+        .syntax()
+        .descendants()
+        .filter_map(AttrpathValue::cast)
+        .collect();
+    let mut imported = vec![];
+    let mut entries = collect_entries(root.clone(), prefix, category, locs, &None, &mut imported);
+    warnings.extend(
+        imported
+            .into_iter()
+            .map(|warning| format!("{}: {}", path.display(), warning)),
+    );
+    entries.retain(|entry| names.contains(&entry.name));
+    for entry in &mut entries {
+        if entry.location.is_some() {
+            continue;
+        }
+        let binding = bindings
+            .iter()
+            .find(|apv| apv.attrpath().is_some_and(|p| p.to_string() == entry.name));
+        entry.location = binding.map(|apv| {
+            format!(
+                "`{}:{}`",
+                path.to_string_lossy().replace('\\', "/"),
+                source_line(apv.syntax())
+            )
+        });
+    }
+    entries.sort_by_key(|entry| names.iter().position(|name| *name == entry.name));
+    entries
+}
+
+/// Functions generated from a static set of names.
+struct Generated {
+    /// Attribute names, with the source of their value for `mapAttrs`.
+    names: Vec<(String, Option<String>)>,
+    /// The lambda building each attribute from its name (and value).
+    lambda: Lambda,
+    /// Number of parameters of `lambda` taken by the generator.
+    generator_params: usize,
+}
+
+pub(crate) fn unparen(expr: Expr) -> Option<Expr> {
+    match expr {
+        Expr::Paren(p) => unparen(p.expr()?),
+        expr => Some(expr),
+    }
+}
+
+/// The name of a called function: `f` for `f`, `lib.attrsets.f` or
+/// `builtins.f`.
+pub(crate) fn callee_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Ident(ident) => Some(ident.to_string()),
+        Expr::Select(select) => match select.attrpath()?.attrs().last()? {
+            Attr::Ident(ident) => Some(ident.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn string_literal(expr: &Expr) -> Option<String> {
+    let Expr::Str(s) = expr else {
+        return None;
+    };
+    s.normalized_parts()
+        .into_iter()
+        .map(|part| match part {
+            InterpolPart::Literal(text) => Some(text),
+            InterpolPart::Interpolation(_) => None,
+        })
+        .collect()
+}
+
+/// Recognise `genAttrs [ "a" "b" ] (name: ...)` and
+/// `mapAttrs (name: value: ...) { a = ...; }` over literal names.
+fn generated_functions(value: Expr) -> Option<Generated> {
+    let Expr::Apply(outer) = unparen(value)? else {
+        return None;
+    };
+    let Expr::Apply(inner) = unparen(outer.lambda()?)? else {
+        return None;
+    };
+    match callee_name(&unparen(inner.lambda()?)?)?.as_str() {
+        "genAttrs" => {
+            let Expr::List(list) = unparen(inner.argument()?)? else {
+                return None;
+            };
+            let Expr::Lambda(lambda) = unparen(outer.argument()?)? else {
+                return None;
+            };
+            let names = list
+                .items()
+                .map(|item| string_literal(&item).map(|name| (name, None)))
+                .collect::<Option<_>>()?;
+            Some(Generated {
+                names,
+                lambda,
+                generator_params: 1,
+            })
+        }
+        "mapAttrs" => {
+            let Expr::Lambda(lambda) = unparen(inner.argument()?)? else {
+                return None;
+            };
+            let Expr::AttrSet(set) = unparen(outer.argument()?)? else {
+                return None;
+            };
+            if set.rec_token().is_some() || set.inherits().next().is_some() {
+                return None;
+            }
+            let names = set
+                .attrpath_values()
+                .map(|apv| {
+                    let mut attrs = apv.attrpath()?.attrs();
+                    let name = match (attrs.next()?, attrs.next()) {
+                        (Attr::Ident(ident), None) => ident.to_string(),
+                        (Attr::Str(s), None) => string_literal(&Expr::Str(s))?,
+                        _ => return None,
+                    };
+                    Some((name, apv.value().map(|v| v.syntax().to_string())))
+                })
+                .collect::<Option<_>>()?;
+            Some(Generated {
+                names,
+                lambda,
+                generator_params: 2,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Entries for each attribute of a binding generated with `genAttrs` or
+/// `mapAttrs` over literal names, sharing the doc comment of the lambda (or
+/// of the binding). `${name}` in the doc comment is replaced by each
+/// attribute's name, and for `mapAttrs` the value parameter by its source.
+fn generated_entry_information(
+    entry: &AttrpathValue,
+    warnings: &mut Vec<String>,
+) -> Option<Vec<DocItem>> {
+    let generated = generated_functions(entry.value()?)?;
+    let doc = retrieve_doc_comment(generated.lambda.syntax(), Some(2), warnings)
+        .or_else(|| retrieve_doc_comment(entry.syntax(), Some(2), warnings))?;
+    let base = entry.attrpath()?.to_string();
+
+    // Placeholders for the generator's parameters, in order.
+    let mut placeholders: Vec<Option<String>> = vec![];
+    let mut body = Some(Expr::Lambda(generated.lambda));
+    for _ in 0..generated.generator_params {
+        let Some(Expr::Lambda(lambda)) = body else {
+            return None;
+        };
+        placeholders.push(match lambda.param() {
+            Some(Param::IdentParam(id)) => Some(format!("${{{}}}", id)),
+            _ => None,
+        });
+        body = lambda.body();
+    }
+    let function = match body {
+        Some(Expr::Lambda(lambda)) => Some(lambda),
+        _ => None,
+    };
+
+    Some(
+        generated
+            .names
+            .into_iter()
+            .map(|(name, value)| {
+                let mut doc = doc.clone();
+                if let Some(Some(placeholder)) = placeholders.first() {
+                    doc = doc.replace(placeholder, &name);
+                }
+                if let (Some(Some(placeholder)), Some(value)) = (placeholders.get(1), value) {
+                    doc = doc.replace(placeholder, &value);
+                }
+                let comment = DocComment::new(doc);
+                DocItem {
+                    name: format!("{}.{}", base, name),
+                    args: function
+                        .clone()
+                        .map(|function| {
+                            collect_lambda_args(function, &comment.param_docs, warnings)
+                        })
+                        .unwrap_or_default(),
+                    comment,
+                    params: function
+                        .clone()
+                        .map(signature::lambda_params)
+                        .unwrap_or_default(),
+                    value: None,
+                }
+            })
+            .collect(),
+    )
+}
+
+fn collect_bindings(
+    node: &SyntaxNode,
+    dir: Option<&std::path::Path>,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    scope: HashMap<String, ManualEntry>,
+    warnings: &mut Vec<String>,
+) -> Vec<ManualEntry> {
+    for ev in node.preorder() {
+        match ev {
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
+                let mut entries = vec![];
+                for child in n.children() {
+                    if let Some(apv) = AttrpathValue::cast(child.clone()) {
+                        if let Some(generated) = generated_entry_information(&apv, warnings) {
+                            entries.extend(
+                                generated
+                                    .into_iter()
+                                    .map(|di| di.into_entry(prefix, category, locs, warnings)),
+                            );
+                            continue;
+                        }
+                        entries.extend(
+                            collect_entry_information(apv, false, warnings)
+                                .map(|di| di.into_entry(prefix, category, locs, warnings)),
+                        );
+                    } else if let Some(inh) = Inherit::cast(child) {
+                        if inh.from().is_some() {
+                            if let Some(dir) = dir {
+                                entries.extend(imported_entries(
+                                    &inh, dir, prefix, category, locs, warnings,
+                                ));
+                            }
+                            continue;
+                        }
+                        entries.extend(inh.attrs().filter_map(|a| match a {
+                            Attr::Ident(i) => scope.get(&i.syntax().text().to_string()).cloned(),
+                            _ => None,
+                        }));
+                    }
+                }
+                let names: Vec<String> = n
+                    .children()
+                    .filter_map(AttrpathValue::cast)
+                    .filter_map(|apv| apv.attrpath())
+                    .map(|path| path.to_string())
+                    .collect();
+                let meta = meta_binding_names(n.children(), &names);
+                entries.retain(|entry| !meta.contains(&entry.name));
+                apply_meta(entries.iter_mut(), &binding_meta(n.children()));
+                return entries;
+            }
+            _ => (),
+        }
+    }
+
+    vec![]
+}
+
+/// Given a let-in expression and an identifier name, find the corresponding
+/// AttrpathValue binding in the let block.
+fn find_let_binding(let_in: &LetIn, name: &str) -> Option<AttrpathValue> {
+    for entry in let_in.entries() {
+        if let Some(apv) = AttrpathValue::cast(entry.syntax().clone())
+            && let Some(path) = apv.attrpath()
+            && path.to_string() == name
+        {
+            return Some(apv);
+        }
+    }
+    None
+}
+
+/// Resolve an identifier in the context of a let-in expression.
+fn resolve_let_ident(let_in: &LetIn, ident: &Ident) -> Option<SyntaxNode> {
+    let name = ident.to_string();
+    let apv = find_let_binding(let_in, &name)?;
+    let value = apv.value()?;
+
+    if let Expr::Ident(ref inner_ident) = value {
+        resolve_let_ident(let_in, inner_ident)
+    } else {
+        Some(value.syntax().clone())
+    }
+}
+
+/// Collect the documented entries of a parsed file, titled
+/// `prefix.category.name`. With `export`, only those `let` bindings are
+/// collected, whatever the file returns. Re-exports of imported files are
+/// skipped; see [`collect_entries_in`]. Problems with the doc comments, such
+/// as unknown tags, are added to `warnings` for the caller to report.
+pub fn collect_entries(
+    root: rnix::Root,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    export: &Option<Vec<String>>,
+    warnings: &mut Vec<String>,
+) -> Vec<ManualEntry> {
+    collect_entries_in(root, None, prefix, category, locs, export, warnings)
+}
+
+/// Collect the entries of a file in `dir`, resolving re-exports of imported
+/// files relative to it. Without a directory, re-exports from imports are
+/// skipped.
+pub fn collect_entries_in(
+    root: rnix::Root,
+    dir: Option<&std::path::Path>,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    export: &Option<Vec<String>>,
+    warnings: &mut Vec<String>,
+) -> Vec<ManualEntry> {
+    let mut preorder = root.syntax().preorder();
+    while let Some(ev) = preorder.next() {
+        match ev {
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_PATTERN => {
+                preorder.skip_subtree();
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_LET_IN => {
+                let let_in = LetIn::cast(n.clone()).unwrap();
+                let mut scope: HashMap<String, ManualEntry> = HashMap::new();
+                for apv in n.children().filter_map(AttrpathValue::cast) {
+                    if let Some(di) = collect_entry_information(apv, false, warnings) {
+                        let entry = di.into_entry(prefix, category, locs, warnings);
+                        scope.insert(entry.name.clone(), entry);
+                    }
+                }
+                apply_meta(scope.values_mut(), &binding_meta(n.children()));
+
+                if let Some(exports) = export {
+                    return exports
+                        .iter()
+                        .filter_map(|name| scope.get(name).cloned())
+                        .collect();
+                }
+
+                let body = let_in.body().unwrap();
+
+                if let Expr::Ident(ref ident) = body
+                    && let Some(resolved) = resolve_let_ident(&let_in, ident)
+                {
+                    return collect_bindings(
+                        &resolved, dir, prefix, category, locs, scope, warnings,
+                    );
+                }
+
+                return collect_bindings(
+                    body.syntax(),
+                    dir,
+                    prefix,
+                    category,
+                    locs,
+                    scope,
+                    warnings,
+                );
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
+                return collect_bindings(
+                    &n,
+                    dir,
+                    prefix,
+                    category,
+                    locs,
+                    Default::default(),
+                    warnings,
+                );
+            }
+            _ => (),
+        }
+    }
+
+    vec![]
+}
+
+/// Names of the bindings in the `let` block that `--export` selects from.
+pub fn let_binding_names(root: &rnix::Root) -> Vec<String> {
+    let mut preorder = root.syntax().preorder();
+    while let Some(ev) = preorder.next() {
+        match ev {
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_PATTERN => {
+                preorder.skip_subtree();
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_LET_IN => {
+                return n
+                    .children()
+                    .filter_map(AttrpathValue::cast)
+                    .filter_map(|apv| apv.attrpath())
+                    .map(|path| path.to_string())
+                    .collect();
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => break,
+            _ => (),
+        }
+    }
+    vec![]
+}
+
+/// The attribute set returned by a file, as found by `collect_entries`.
+fn returned_set(root: &rnix::Root) -> Option<SyntaxNode> {
+    let mut preorder = root.syntax().preorder();
+    loop {
+        match preorder.next() {
+            Some(WalkEvent::Enter(n)) if n.kind() == SyntaxKind::NODE_PATTERN => {
+                preorder.skip_subtree();
+            }
+            Some(WalkEvent::Enter(n)) if n.kind() == SyntaxKind::NODE_LET_IN => {
+                let let_in = LetIn::cast(n).unwrap();
+                let body = let_in.body()?;
+                return match body {
+                    Expr::Ident(ref ident) => resolve_let_ident(&let_in, ident),
+                    _ => None,
+                }
+                .unwrap_or_else(|| body.syntax().clone())
+                .descendants()
+                .find(|n| n.kind() == SyntaxKind::NODE_ATTR_SET);
+            }
+            Some(WalkEvent::Enter(n)) if n.kind() == SyntaxKind::NODE_ATTR_SET => return Some(n),
+            Some(_) => (),
+            None => return None,
+        }
+    }
+}
+
+/// Names of the bindings of the attribute set that `collect_entries`
+/// documents, including ones without a doc comment but not the bindings
+/// holding metadata of others (`fooMeta` next to `foo`, `__docsMeta`).
+pub fn returned_binding_names(root: &rnix::Root) -> Vec<String> {
+    let Some(set) = returned_set(root) else {
+        return vec![];
+    };
+
+    let mut names = vec![];
+    for child in set.children() {
+        if let Some(apv) = AttrpathValue::cast(child.clone()) {
+            names.extend(apv.attrpath().map(|path| path.to_string()));
+        } else if let Some(inh) = Inherit::cast(child)
+            && inh.from().is_none()
+        {
+            names.extend(inh.attrs().filter_map(|a| match a {
+                Attr::Ident(i) => Some(i.syntax().text().to_string()),
+                _ => None,
+            }));
+        }
+    }
+    let meta = meta_binding_names(set.children(), &names);
+    names.retain(|name| !meta.contains(name));
+    names
+}
+
+/// The documented bindings anywhere in a file, by name; the first one of
+/// each name.
+fn documented_items(root: &rnix::Root, warnings: &mut Vec<String>) -> HashMap<String, DocItem> {
+    let mut documented: HashMap<String, DocItem> = HashMap::new();
+    for apv in root.syntax().descendants().filter_map(AttrpathValue::cast) {
+        if let Some(item) = collect_entry_information(apv, false, warnings) {
+            documented.entry(item.name.clone()).or_insert(item);
+        }
+    }
+    documented
+}
+
+/// Entries for the bindings `names`, as listed by evaluating the file (see
+/// [`eval`]), each documented by the doc comment of the first binding of
+/// that name anywhere in the file. Names without one get an empty entry.
+/// Problems with the doc comments are added to `warnings`.
+pub fn entries_for_names(
+    root: &rnix::Root,
+    names: &[String],
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> Vec<ManualEntry> {
+    let mut documented = documented_items(root, warnings);
+    names
+        .iter()
+        .map(|name| match documented.remove(name) {
+            Some(item) => item.into_entry(prefix, category, locs, warnings),
+            None => {
+                let mut entry = DocItem {
+                    name: name.clone(),
+                    comment: DocComment::default(),
+                    args: vec![],
+                    params: vec![],
+                    value: None,
+                }
+                .into_entry(prefix, category, locs, warnings);
+                entry.description.clear();
+                entry
+            }
+        })
+        .collect()
+}
+
+/// Identifiers a value may be wrapping, most likely first: the value itself,
+/// or the arguments it applies, innermost first, and then the functions
+/// applied to them, looking through lambdas. In `flip bar`, `bar` is the
+/// function wrapped, and `flip` only wraps it. Parameters of the lambdas are
+/// left out.
+fn wrapped_names(expr: &Expr, params: &mut Vec<String>, names: &mut Vec<String>) {
+    match expr {
+        Expr::Lambda(lambda) => {
+            match lambda.param() {
+                Some(Param::IdentParam(id)) => params.push(id.to_string()),
+                Some(Param::Pattern(pat)) => params.extend(
+                    pat.pat_entries()
+                        .filter_map(|entry| entry.ident())
+                        .map(|ident| ident.to_string()),
+                ),
+                None => {}
+            }
+            if let Some(body) = lambda.body() {
+                wrapped_names(&body, params, names);
+            }
+        }
+        Expr::Apply(apply) => {
+            for part in [apply.argument(), apply.lambda()].into_iter().flatten() {
+                wrapped_names(&part, params, names);
+            }
+        }
+        Expr::Paren(paren) => {
+            if let Some(inner) = paren.expr() {
+                wrapped_names(&inner, params, names);
+            }
+        }
+        Expr::Ident(ident) => {
+            let name = ident.to_string();
+            if !params.contains(&name) {
+                names.push(name);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Document the bindings of the returned attribute set that have no doc
+/// comment of their own but wrap a documented function (`foo = flip bar;`,
+/// `foo = a: bar a 1;`) with the docs of that function, noting where they
+/// come from. The type of that function is left out, as the wrapper's
+/// differs. The entries are inserted among `entries` in source order.
+/// Problems with the doc comments are not reported again: they are those
+/// of `entries`, which [`collect_entries`] reports.
+pub fn inherit_wrapped_docs(
+    root: &rnix::Root,
+    entries: &mut Vec<ManualEntry>,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+) {
+    let Some(set) = returned_set(root) else {
+        return;
+    };
+    let mut reported = vec![];
+    let documented = documented_items(root, &mut reported);
+    let names = returned_binding_names(root);
+    let mut position = 0;
+    for child in set.children() {
+        let Some(apv) = AttrpathValue::cast(child.clone()) else {
+            // Keep the position after entries inherited here.
+            for attr in Inherit::cast(child).iter().flat_map(|inh| inh.attrs()) {
+                let name = attr.syntax().text().to_string();
+                if let Some(i) = entries.iter().position(|entry| entry.name == name) {
+                    position = position.max(i + 1);
+                }
+            }
+            continue;
+        };
+        let (Some(name), Some(value)) = (apv.attrpath().map(|p| p.to_string()), apv.value()) else {
+            continue;
+        };
+        if let Some(i) = entries.iter().position(|entry| entry.name == name) {
+            position = i + 1;
+            continue;
+        }
+        if documented.contains_key(&name) || !names.contains(&name) {
+            continue;
+        }
+        let mut candidates = vec![];
+        wrapped_names(&value, &mut vec![], &mut candidates);
+        let Some(wrapped) = candidates
+            .iter()
+            .find(|candidate| **candidate != name && documented.contains_key(*candidate))
+        else {
+            continue;
+        };
+        let source = entries
+            .iter()
+            .find(|entry| entry.name == *wrapped)
+            .map_or_else(|| wrapped.clone(), |entry| entry.get_ident_title().1);
+        let param_docs = documented[wrapped].comment.param_docs.clone();
+        let (args, params) = match &value {
+            Expr::Lambda(lambda) => (
+                collect_lambda_args(lambda.clone(), &param_docs, &mut reported),
+                signature::lambda_params(lambda.clone()),
+            ),
+            _ => (vec![], vec![]),
+        };
+        let mut description: Vec<String> = documented[wrapped]
+            .comment
+            .doc
+            .split("\n\n")
+            .map(|s| s.to_string())
+            .collect();
+        signature::take_type_section(&mut description);
+        let item = DocItem {
+            name,
+            comment: DocComment {
+                doc: format!("Derived from `{}`.\n\n{}", source, description.join("\n\n")),
+                fn_type: None,
+                example: documented[wrapped].comment.example.clone(),
+                param_docs,
+            },
+            args,
+            params,
+            value: None,
+        };
+        entries.insert(
+            position,
+            item.into_entry(prefix, category, locs, &mut reported),
+        );
+        position += 1;
+    }
+}
+
+/// Document the bindings of the returned attribute set that have no doc
+/// comment of their own but an ordinary block comment before them, in the
+/// format nixdoc read before RFC 145, with its `Type:` and `Example:`
+/// sections. A binding inherited from a `let` block is documented by the
+/// comment before the first binding of its name. The entries are inserted
+/// among `entries` in source order, and problems with the comments are added
+/// to `warnings`.
+pub fn collect_legacy_docs(
+    root: &rnix::Root,
+    entries: &mut Vec<ManualEntry>,
+    prefix: &str,
+    category: &str,
+    locs: &HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) {
+    let Some(set) = returned_set(root) else {
+        return;
+    };
+    let bindings: Vec<AttrpathValue> = root
+        .syntax()
+        .descendants()
+        .filter_map(AttrpathValue::cast)
+        .collect();
+    let mut position = 0;
+    for child in set.children() {
+        let documented: Vec<AttrpathValue> = match Inherit::cast(child.clone()) {
+            Some(inh) if inh.from().is_none() => inh
+                .attrs()
+                .filter_map(|attr| {
+                    let name = attr.syntax().text().to_string();
+                    bindings
+                        .iter()
+                        .find(|apv| apv.attrpath().is_some_and(|p| p.to_string() == name))
+                        .cloned()
+                })
+                .collect(),
+            Some(_) => vec![],
+            None => AttrpathValue::cast(child).into_iter().collect(),
+        };
+        for apv in documented {
+            let Some(name) = apv.attrpath().map(|p| p.to_string()) else {
+                continue;
+            };
+            if let Some(i) = entries.iter().position(|entry| entry.name == name) {
+                position = position.max(i + 1);
+                continue;
+            }
+            if let Some(item) = collect_entry_information(apv, true, warnings) {
+                entries.insert(position, item.into_entry(prefix, category, locs, warnings));
+                position += 1;
+            }
+        }
+    }
+}
+
+/// Extract just the file-level documentation comment from a Nix file,
+/// adding problems with its headings to `warnings`.
+pub fn extract_file_doc(nix: &rnix::Root, warnings: &mut Vec<String>) -> Option<String> {
+    nix.syntax()
+        .first_child()
+        .and_then(|node| retrieve_doc_comment(&node, Some(0), warnings))
+        .and_then(|doc_item| handle_indentation(&doc_item))
+}
+
+/// The heading of a category document, followed by the file doc comment.
+/// Empty if neither a description nor a category is given.
+pub fn retrieve_description(
+    nix: &rnix::Root,
+    description: &str,
+    category: &str,
+    warnings: &mut Vec<String>,
+) -> String {
+    category_header(
+        description,
+        category,
+        &extract_file_doc(nix, warnings).unwrap_or_default(),
+    )
+}
+
+/// The heading of a category document, followed by `preamble`. Empty if
+/// neither a description nor a category is given.
+pub fn category_header(description: &str, category: &str, preamble: &str) -> String {
+    if description.is_empty() && category.is_empty() {
+        return String::new();
+    }
+    format!(
+        "# {} {{#sec-functions-library-{}}}\n{}\n",
+        description, category, preamble
+    )
+}
+
+/// Split a markdown category introduction into its title, if it starts
+/// with a level 1 heading, and the rest. The rest has its headings shifted
+/// so the highest is level 2, below the category heading.
+pub fn split_intro(text: &str) -> (Option<String>, String) {
+    let text = text.trim();
+    let (title, rest) = match text.strip_prefix("# ") {
+        Some(heading) => {
+            let (title, rest) = heading.split_once('\n').unwrap_or((heading, ""));
+            (Some(title.trim().to_string()), rest.trim())
+        }
+        None => (None, text),
+    };
+    let highest = pulldown_cmark::Parser::new(rest)
+        .filter_map(|event| match event {
+            pulldown_cmark::Event::Start(pulldown_cmark::Tag::Heading { level, .. }) => {
+                Some(level as usize)
+            }
+            _ => None,
+        })
+        .min();
+    let rest = match highest {
+        Some(level) if level < 2 => format::shift_headings(rest, 2 - level),
+        _ => rest.to_string(),
+    };
+    (title, rest)
+}
+
+/// Select the language of an entry's description and argument docs.
+pub fn localize_entry(entry: &mut ManualEntry, lang: Option<&str>) {
+    let description = entry.description.join("\n\n");
+    if let Cow::Owned(localized) = locale::select_language(&description, lang) {
+        entry.description = localized.split("\n\n").map(|s| s.to_string()).collect();
+    }
+    for arg in &mut entry.args {
+        let docs: Vec<&mut SingleArg> = match arg {
+            Argument::Flat(arg) => vec![arg],
+            Argument::Pattern(args) => args.iter_mut().collect(),
+        };
+        for doc in docs.into_iter().filter_map(|arg| arg.doc.as_mut()) {
+            if let Cow::Owned(localized) = locale::select_language(doc, lang) {
+                *doc = localized;
+            }
+        }
+    }
+}
+
+/// Mark an entry as internal if its description has a `marker` line, as
+/// configured in place of `@internal`, and remove the line.
+pub fn mark_internal(entry: &mut ManualEntry, marker: &str) {
+    let description = entry.description.join("\n\n");
+    if let (Cow::Owned(rest), true) = take_internal(&description, marker) {
+        entry.description = rest.split("\n\n").map(String::from).collect();
+        entry.internal = true;
+    }
+}
+
+/// Sanitize the raw HTML in every markdown field of an entry.
+pub fn sanitize_entry(entry: &mut ManualEntry, policy: &HtmlPolicy) {
+    let sanitize = |text: &mut String| {
+        if let Cow::Owned(clean) = sanitize_html(text, policy) {
+            *text = clean;
+        }
+    };
+    entry.description.iter_mut().for_each(sanitize);
+    entry.example.iter_mut().for_each(sanitize);
+    for arg in &mut entry.args {
+        let docs: Vec<&mut SingleArg> = match arg {
+            Argument::Flat(arg) => vec![arg],
+            Argument::Pattern(args) => args.iter_mut().collect(),
+        };
+        for arg in docs {
+            arg.doc.iter_mut().for_each(sanitize);
+        }
+    }
+}
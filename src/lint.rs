@@ -0,0 +1,61 @@
+//! Style checks for module option documentation.
+//!
+//! The checks only produce warnings; they never change what is rendered.
+
+use crate::options::OptionDef;
+
+/// The description that `types.string` renders as. The type was deprecated
+/// in favour of `types.str`, `types.lines` and `types.separatedString`.
+const DEPRECATED_STRING_TYPE: &str = "strings concatenated with \"\"";
+
+/// Check one option and describe each problem found.
+pub fn lint_option(opt: &OptionDef) -> Vec<String> {
+    let mut warnings = vec![];
+
+    let description = opt.description.as_ref().map_or("", |d| d.as_str().trim());
+    if description.is_empty() {
+        warnings.push("empty description".to_string());
+    } else if !ends_sentence(description) {
+        warnings.push("description does not end with a full stop".to_string());
+    }
+
+    if let Some(option_type) = &opt.option_type {
+        if option_type.contains(DEPRECATED_STRING_TYPE) {
+            warnings.push(
+                "type uses the deprecated `types.string`; use `types.str`, `types.lines` or `types.separatedString`"
+                    .to_string(),
+            );
+        }
+        if opt.example.is_none() && !opt.read_only && !is_trivial_type(option_type) {
+            warnings.push(format!("no example for type '{}'", option_type));
+        }
+    }
+
+    warnings
+}
+
+/// Whether a description ends like a sentence or with a block (a code
+/// block or a list) that needs no punctuation.
+fn ends_sentence(description: &str) -> bool {
+    let last_line = description.lines().last().unwrap_or_default().trim();
+    if last_line.starts_with("```")
+        || last_line.starts_with("~~~")
+        || last_line.starts_with(['-', '*', '+'])
+        || last_line.starts_with(':')
+    {
+        return true;
+    }
+    let text = description.trim_end_matches([')', '"', '\'', '`', '*', '_']);
+    text.ends_with(['.', '!', '?'])
+}
+
+/// Whether a value of this type is easy enough to write that an example
+/// would not help.
+fn is_trivial_type(option_type: &str) -> bool {
+    let mut ty = option_type.trim();
+    while let Some(rest) = ty.strip_prefix("null or ") {
+        ty = rest;
+    }
+    let structured = ["attribute set", "list of", "submodule", "function", " or "];
+    !structured.iter().any(|s| ty.contains(s))
+}
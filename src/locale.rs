@@ -0,0 +1,197 @@
+//! Language selection for doc comments with translated sections.
+//!
+//! A heading ending in a language tag marks a translation of the section
+//! with the same title:
+//!
+//! ```markdown
+//! Split a string at a separator.
+//!
+//! # Description (de)
+//!
+//! Teilt eine Zeichenkette an einem Trennzeichen.
+//!
+//! # Example
+//!
+//! …
+//!
+//! # Example (de)
+//!
+//! …
+//! ```
+//!
+//! Rendering for a language replaces each section by its translation, if
+//! there is one, and drops the translations into other languages. A
+//! `Description` translation replaces the text before the first heading.
+//!
+//! The headings docgen writes itself, such as those of `--group-by`, are
+//! translated by the `headings` section of the configuration file (see
+//! [`crate::config`]), with [`Headings`].
+
+use crate::format::{get_fence, trim_leading_whitespace};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Translations of the headings docgen writes, by their English text.
+#[derive(Debug, Clone, Default)]
+pub struct Headings(BTreeMap<String, String>);
+
+impl Headings {
+    /// The translations `headings` of the configuration file into `lang`.
+    pub fn for_lang(
+        headings: &BTreeMap<String, BTreeMap<String, String>>,
+        lang: Option<&str>,
+    ) -> Headings {
+        let translations = lang.and_then(|lang| {
+            headings
+                .iter()
+                .find(|(tag, _)| tag.eq_ignore_ascii_case(lang))
+                .map(|(_, translations)| translations.clone())
+        });
+        Headings(translations.unwrap_or_default())
+    }
+
+    /// `heading` in the selected language, or as it is without a translation.
+    pub fn get<'a>(&'a self, heading: &'a str) -> &'a str {
+        self.0.get(heading).map_or(heading, String::as_str)
+    }
+}
+
+/// A heading outside code blocks, and the lines its section spans.
+struct Section<'a> {
+    start: usize,
+    end: usize,
+    level: usize,
+    title: &'a str,
+    lang: Option<&'a str>,
+}
+
+/// Whether `tag` looks like a language tag (`de`, `pt-BR`, `zh-Hant`).
+fn is_lang_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags
+            .all(|s| (2..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Split a heading title into the title and its language tag, if any.
+fn split_lang(title: &str) -> (&str, Option<&str>) {
+    match title
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        Some((base, tag)) if is_lang_tag(tag) => (base.trim_end(), Some(tag)),
+        _ => (title, None),
+    }
+}
+
+/// Find the ATX headings of a document and the extent of their sections.
+fn sections<'a>(lines: &[&'a str]) -> Vec<Section<'a>> {
+    let mut sections: Vec<Section> = vec![];
+    let mut curr_fence: Option<(usize, char)> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        if let Some((start_count, start_char)) = curr_fence {
+            if get_fence(&fence_line, false)
+                .is_some_and(|(count, ch)| ch == start_char && count >= start_count)
+            {
+                curr_fence = None;
+            }
+            continue;
+        }
+        if let Some(fence) = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3) {
+            curr_fence = Some(fence);
+            continue;
+        }
+
+        let level = fence_line.len() - fence_line.trim_start_matches('#').len();
+        let title = line.trim_start()[level..].trim();
+        if !(1..=6).contains(&level) || !fence_line[level..].starts_with(' ') || title.is_empty() {
+            continue;
+        }
+        for section in sections.iter_mut().filter(|s| s.end == lines.len()) {
+            if section.level >= level {
+                section.end = i;
+            }
+        }
+        let (title, lang) = split_lang(title);
+        sections.push(Section {
+            start: i,
+            end: lines.len(),
+            level,
+            title,
+            lang,
+        });
+    }
+    sections
+}
+
+/// Render a doc comment for `lang`, or for the default language if `None`.
+pub fn select_language<'a>(doc: &'a str, lang: Option<&str>) -> Cow<'a, str> {
+    if !doc.contains(')') {
+        return Cow::Borrowed(doc);
+    }
+    let lines: Vec<&str> = doc.split('\n').collect();
+    let sections = sections(&lines);
+    if sections.iter().all(|s| s.lang.is_none()) {
+        return Cow::Borrowed(doc);
+    }
+
+    let selected = |s: &Section| {
+        s.lang
+            .zip(lang)
+            .is_some_and(|(tag, lang)| tag.eq_ignore_ascii_case(lang))
+    };
+    let mut dropped = vec![false; lines.len()];
+    let mut renamed: Vec<(usize, String)> = vec![];
+    let mut description = None;
+    for section in &sections {
+        let drop = match section.lang {
+            Some(_) if !selected(section) => true,
+            Some(_) => false,
+            // The default text of a section that has a translation.
+            None => sections
+                .iter()
+                .any(|t| selected(t) && t.level == section.level && t.title == section.title),
+        };
+        if drop {
+            dropped[section.start..section.end].fill(true);
+        } else if section.lang.is_some() {
+            let preamble = section.title.eq_ignore_ascii_case("description")
+                && !sections
+                    .iter()
+                    .any(|s| s.lang.is_none() && s.title.eq_ignore_ascii_case("description"));
+            if preamble {
+                // Replaces the text before the first heading.
+                let first = sections.first().map_or(0, |s| s.start);
+                dropped[..first].fill(true);
+                dropped[section.start..section.end].fill(true);
+                description = Some(&lines[section.start + 1..section.end]);
+            } else {
+                let hashes = "#".repeat(section.level);
+                renamed.push((section.start, format!("{} {}", hashes, section.title)));
+            }
+        }
+    }
+
+    let mut output: Vec<Cow<str>> = vec![];
+    if let Some(description) = description {
+        let text = description.join("\n");
+        let text = text.trim_matches('\n');
+        if !text.is_empty() {
+            output.push(Cow::Owned(format!("{}\n", text)));
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        if dropped[i] {
+            continue;
+        }
+        match renamed.iter().find(|(start, _)| *start == i) {
+            Some((_, heading)) => output.push(Cow::Owned(heading.clone())),
+            None => output.push(Cow::Borrowed(line)),
+        }
+    }
+
+    Cow::Owned(output.join("\n"))
+}
@@ -0,0 +1,116 @@
+//! Client-side search index for lunr.js and elasticlunr.
+//!
+//! The index is a JSON document naming the reference and the fields to index
+//! and listing one document per documented function:
+//!
+//! ```json
+//! {
+//!   "ref": "id",
+//!   "fields": ["name", "title", "category", "description"],
+//!   "documents": [
+//!     {"id": "lib.strings.concatStrings", "name": "concatStrings", ...}
+//!   ]
+//! }
+//! ```
+//!
+//! A search box builds its lunr or elasticlunr index from the documents when
+//! the page loads and links each result to its `url`. The `search-index.json`
+//! of an HTML site (see [`crate::html`]) is such an index too. Descriptions are plain
+//! text, without markdown syntax, headings or code blocks, so examples do not
+//! drown out the prose.
+
+use crate::commonmark::ManualEntry;
+use crate::format::expand_template;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+/// The field documents are referred to by.
+pub const REF: &str = "id";
+
+/// The fields to index, most specific first.
+pub const FIELDS: [&str; 4] = ["name", "title", "category", "description"];
+
+/// A searchable function.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexDocument {
+    /// Full title, unique across the index.
+    pub id: String,
+    /// Attribute path of the binding within its file.
+    pub name: String,
+    pub title: String,
+    pub category: String,
+    #[serde(rename = "type")]
+    pub fn_type: Option<String>,
+    pub description: String,
+    /// Link to the entry: its page and anchor.
+    pub url: String,
+}
+
+impl IndexDocument {
+    /// Build the document for a library function, documented on the page
+    /// `page` under its anchor.
+    pub fn from_entry(entry: &ManualEntry, anchor_prefix: &str, page: &str) -> Self {
+        let (_, title) = entry.get_ident_title();
+        let fn_type = entry
+            .signature
+            .as_ref()
+            .and_then(|signature| signature.fn_type.as_ref())
+            .map(|ty| ty.text.clone())
+            .or_else(|| entry.fn_type.clone());
+        IndexDocument {
+            id: title.clone(),
+            name: entry.name.clone(),
+            title,
+            category: entry.category.clone(),
+            fn_type,
+            description: plain_text(&entry.description.join("\n\n")),
+            url: format!("{}#{}", page, entry.anchor(anchor_prefix)),
+        }
+    }
+}
+
+/// The index of a documentation site.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchIndex {
+    #[serde(rename = "ref")]
+    pub reference: &'static str,
+    pub fields: Vec<&'static str>,
+    pub documents: Vec<IndexDocument>,
+}
+
+impl SearchIndex {
+    pub fn new(documents: Vec<IndexDocument>) -> SearchIndex {
+        SearchIndex {
+            reference: REF,
+            fields: FIELDS.to_vec(),
+            documents,
+        }
+    }
+}
+
+/// The page documenting `category`: `template` with its `{category}`
+/// placeholder filled in.
+pub fn page_url(template: &str, category: &str) -> String {
+    expand_template(template, &[("category", category)])
+}
+
+/// The text of `markdown` on one line, without headings, code blocks, HTML
+/// or markup. Headings are section names such as "Example", the same for
+/// every entry.
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut skipped = 0usize;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Heading { .. }) => skipped += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::Heading(_)) => {
+                skipped = skipped.saturating_sub(1)
+            }
+            Event::Text(t) | Event::Code(t) if skipped == 0 => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::Paragraph | TagEnd::Item) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -16,27 +16,32 @@
 //! This tool generates CommonMark from a Nix file defining library
 //! functions, such as the files in `lib/` in the nixpkgs repository.
 
-mod comment;
-mod commonmark;
-mod format;
-mod options;
+mod bench;
+#[cfg(feature = "browse")]
+mod browse;
 #[cfg(test)]
 mod test;
 
-use crate::format::handle_indentation;
-
-use self::comment::get_expr_docs;
-use self::commonmark::*;
-use format::shift_headings;
-use rnix::{
-    SyntaxKind, SyntaxNode,
-    ast::{Attr, AttrpathValue, Expr, HasEntry, Ident, Inherit, Lambda, LetIn, Param},
+use docgen::admonition::Dialect;
+use docgen::commonmark::*;
+use docgen::format::{CodeLangs, LinkStyle, Wrap, route_raw_blocks, shift_headings_with_warnings};
+use docgen::sanitize::{HtmlMode, HtmlPolicy, sanitize_html};
+use docgen::{
+    admonition, antora, cache, category_header, changes, collect_entries_in, collect_legacy_docs,
+    config, devdocs, doctest, entries_for_names, eval, extract_file_doc, flake, format,
+    frontmatter, groups, html, hugo, ignore, imported_files, include, inherit,
+    inherit_wrapped_docs, landing, let_binding_names, lint, locale, localize_entry, lunr, man,
+    manifest, mark_internal, metrics, nav, normalize, options, output_schema, parse_file, plan,
+    provenance, render, retrieve_description, returned_binding_names, sanitize_entry, schema,
+    search, see, signature, snapshot, split_intro, static_options, structured, summary, tags,
+    types, validate, variants,
 };
-use rowan::{WalkEvent, ast::AstNode};
 use std::fs;
+use std::io::{self, Write};
 
 use serde::Serialize;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
 use clap::Parser;
 use std::path::PathBuf;
@@ -52,13 +57,55 @@ struct Args {
     #[arg(short, long, default_value_t = String::from("lib"))]
     prefix: String,
 
-    #[arg(long, default_value_t = String::from("function-library-"))]
+    /// Prefix for anchor IDs, put before `prefix.category.name`, or an anchor
+    /// template with `{prefix}`, `{category}`, `{name}` and `{ident}` (the
+    /// dotted path) placeholders, e.g. 'fn-{name}'.
+    #[arg(
+        long,
+        default_value_t = String::from("function-library-"),
+        value_parser = parse_anchor_template
+    )]
     anchor_prefix: String,
 
     /// Whether to output JSON.
     #[arg(short, long, default_value_t = false)]
     json_output: bool,
 
+    /// With --json-output, write each entry as one JSON object per line
+    /// (NDJSON), instead of a single document.
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "json_output",
+        conflicts_with = "es_bulk"
+    )]
+    stream: bool,
+
+    /// Version of the JSON output: 1 serializes entries with their type,
+    /// inputs and examples in the description paragraphs, 2 gives them
+    /// fields of their own along with the source span of each binding.
+    #[arg(
+        long,
+        global = true,
+        default_value_t = output_schema::JSON_OUTPUT_VERSION,
+        value_parser = clap::value_parser!(u32).range(1..=output_schema::LATEST_JSON_OUTPUT_VERSION as i64)
+    )]
+    json_version: u32,
+
+    /// Give each entry of JSON output its description rendered as HTML, as
+    /// `description_html`, next to the markdown.
+    #[arg(long, global = true, default_value_t = false)]
+    json_html: bool,
+
+    /// Keep the entries of bindings marked `@internal` in JSON output, with
+    /// `internal: true`, rather than leaving them out.
+    #[arg(long, default_value_t = false)]
+    keep_internal: bool,
+
+    /// Format of the rendered document (see --json-output for JSON).
+    #[arg(long, default_value = "markdown", value_parser = format_parser(), conflicts_with = "json_output")]
+    format: String,
+
     /// Name of the function category (e.g. 'strings', 'attrsets').
     #[arg(short, long, default_value_t = String::new())]
     category: String,
@@ -67,63 +114,626 @@ struct Args {
     #[arg(short, long, default_value_t = String::new())]
     description: String,
 
-    /// Nix file to process.
-    #[arg(short, long)]
+    /// Markdown file introducing the category, used below its heading
+    /// instead of the file's doc comment. A leading level 1 heading is used
+    /// as the description, if --description is not given, and the other
+    /// headings are shifted below the category heading.
+    #[arg(long, value_name = "FILE")]
+    description_file: Option<PathBuf>,
+
+    /// Nix file to process. Repeat to render several files into one
+    /// document (or one JSON entry list), with each file's name as its
+    /// category.
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Process the Nix files below this directory that match --glob, in
+    /// path order, with one section per file. The category is the path
+    /// within the directory, with `/` as `.` and without `default.nix`.
+    #[arg(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Pattern for the files processed with --dir, relative to it.
+    #[arg(long, default_value = "**/*.nix", requires = "dir")]
+    glob: String,
+
+    /// Do not skip the files excluded by `.gitignore` files when walking
+    /// --dir.
+    #[arg(long, global = true, default_value_t = false)]
+    no_gitignore: bool,
+
+    /// Do not skip the files excluded by `.docgenignore` files, in the
+    /// syntax of `.gitignore`, when walking --dir.
+    #[arg(long, global = true, default_value_t = false)]
+    no_docgenignore: bool,
+
+    /// Ignore file, in the syntax of `.gitignore`, whose patterns apply from
+    /// the root of --dir. Can be given multiple times.
+    #[arg(long, global = true, value_name = "FILE")]
+    ignore_file: Vec<PathBuf>,
+
+    /// The file being rendered.
+    #[arg(skip)]
     file: Option<PathBuf>,
 
     /// Path to a file containing location data as JSON.
     #[arg(short, long)]
     locs: Option<PathBuf>,
 
+    /// JSON or Nix file mapping binding names to type signatures, used for
+    /// entries without a `# Type` section. Defaults to a `<name>.types.json`
+    /// or `<name>.types.nix` next to the file.
+    #[arg(long, global = true, value_name = "FILE")]
+    types: Option<PathBuf>,
+
     /// Comma-separated list of bindings to export (documents only these from let block).
     /// When specified, ignores what the file returns and documents only these bindings.
     #[arg(short, long, value_delimiter = ',')]
     export: Option<Vec<String>>,
+
+    /// If the attribute set a file returns cannot be found statically, list
+    /// its bindings by evaluating it with this Nix executable, documenting
+    /// each with a doc comment found on a binding of the same name.
+    #[arg(
+        long,
+        global = true,
+        num_args = 0..=1,
+        default_missing_value = "nix",
+        value_name = "NIX"
+    )]
+    eval_fallback: Option<String>,
+
+    /// Document bindings without a doc comment that have an ordinary block
+    /// comment before them in the format nixdoc read before RFC 145, with
+    /// `Type:` and `Example:` sections, from that comment.
+    #[arg(long, global = true, default_value_t = false)]
+    legacy_comments: bool,
+
+    /// Document bindings without a doc comment that wrap a documented
+    /// function (`foo = flip bar;`, `foo = a: bar a 1;`) with its docs, but
+    /// not its type, noted as derived from it.
+    #[arg(long, global = true, default_value_t = false)]
+    inherit_docs: bool,
+
+    /// Give each function a usage snippet to paste, a call with an argument
+    /// per parameter derived from its signature (`lib.strings.concatStrings
+    /// [ ... ]`), also included in JSON output as `usage`.
+    #[arg(long, global = true, default_value_t = false)]
+    usage_snippets: bool,
+
+    /// Group the entries of each category into subsections, instead of by
+    /// the groups declared in its doc comment. 'kind' separates constants,
+    /// functions of one argument, curried functions and functions taking
+    /// an attribute set, as their signatures say; 'tag' groups them by the
+    /// `@tag` lines of their doc comments.
+    #[arg(long, global = true, value_enum, value_name = "GROUPING")]
+    group_by: Option<groups::GroupBy>,
+
+    /// Re-parse the generated CommonMark and fail on duplicate anchors or
+    /// unclosed code fences.
+    #[arg(long, global = true, default_value_t = false)]
+    validate_output: bool,
+
+    /// Remove or escape raw HTML in doc comments and option descriptions,
+    /// and the HTML stability badges of the markdown output.
+    #[arg(long, global = true, value_enum)]
+    sanitize_html: Option<HtmlMode>,
+
+    /// Comma-separated HTML tags kept by --sanitize-html, without their
+    /// event handlers and URLs other than http, https, mailto and relative
+    /// ones.
+    #[arg(long, global = true, value_delimiter = ',')]
+    allowed_html_tags: Vec<String>,
+
+    /// Output Elasticsearch bulk actions for the given index instead of
+    /// CommonMark (functions, or options with the options subcommand).
+    #[arg(long, global = true, value_name = "INDEX")]
+    es_bulk: Option<String>,
+
+    /// Shape of the documents written by --es-bulk: docgen's own, or the
+    /// option documents of search.nixos.org for a self-hosted instance
+    /// (options only).
+    #[arg(long, global = true, value_enum, default_value_t = search::BulkShape::Docgen)]
+    es_shape: search::BulkShape,
+
+    /// Flake the options exported with '--es-shape nixos-search' come from,
+    /// e.g. 'github:owner/repo', filling in the flake fields of each
+    /// document.
+    #[arg(long, global = true, value_name = "FLAKEREF")]
+    es_flake: Option<search::Flake>,
+
+    /// How links are emitted in generated CommonMark.
+    #[arg(long, global = true, value_enum, default_value_t = LinkStyle::Inline)]
+    link_style: LinkStyle,
+
+    /// How function examples are rendered in generated CommonMark: as
+    /// example blocks, or collapsed in '<details>' elements.
+    #[arg(long, global = true, value_enum, default_value_t = ExampleStyle::Block)]
+    example_style: ExampleStyle,
+
+    /// Reflow generated prose to this many columns ('none' to keep lines as
+    /// they are). Code blocks, tables, HTML blocks and ':::' fence lines
+    /// are never reflowed, nor inline code broken across lines.
+    #[arg(long, global = true, default_value = "none", value_name = "WIDTH|none")]
+    wrap: Wrap,
+
+    /// Markdown dialect of admonitions and other ':::' blocks in generated
+    /// CommonMark, for the renderer reading it.
+    #[arg(long, global = true, value_enum, default_value_t = Dialect::Nixpkgs)]
+    markdown_dialect: Dialect,
+
+    /// Language tag for the code blocks around usage examples and multi-line
+    /// option values ('none' to leave them untagged).
+    #[arg(long, global = true, default_value_t = String::from("nix"), value_name = "LANG|none")]
+    code_lang: String,
+
+    /// Comma-separated FROM=TO pairs retagging code blocks in the output
+    /// (e.g. 'console=shell').
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        value_parser = format::parse_lang_mapping,
+        value_name = "FROM=TO"
+    )]
+    code_lang_map: Vec<(String, String)>,
+
+    /// Language to render doc comments in. Sections with a heading like
+    /// `# Example (de)` replace their untagged counterpart; translations into
+    /// other languages are dropped.
+    #[arg(long, global = true, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Start emitted markdown with YAML frontmatter holding the document's
+    /// `title` and `slug` (its category) and, for each KEY=VALUE given, that
+    /// field. Can be given multiple times.
+    #[arg(
+        long,
+        global = true,
+        num_args = 0..=1,
+        value_parser = frontmatter::parse_field,
+        value_name = "KEY=VALUE"
+    )]
+    frontmatter: Option<Vec<(String, String)>>,
+
+    /// Search index, as written by the index command, linking `@see` and
+    /// `{ref}` references to functions not documented in the same run.
+    #[arg(long, global = true, value_name = "FILE")]
+    see_index: Option<PathBuf>,
+
+    /// Configuration file with visibility rules (defaults to docgen.toml in
+    /// the working directory, if present).
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Directory to keep the entries loaded from each Nix file in, reused
+    /// by later runs of any command while the file, its settings and the
+    /// files it reads are unchanged. Files loaded with --eval-fallback are
+    /// not cached.
+    #[arg(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Leave out the comment ending each generated document with the docgen
+    /// version, the command line and the SHA-256 of each input.
+    #[arg(long, global = true)]
+    no_metadata: bool,
+
+    /// The command line docgen was run with, recorded in that comment.
+    #[arg(skip)]
+    command_line: Vec<String>,
+}
+
+impl Args {
+    /// Arguments for rendering `file` as CommonMark with the same settings,
+    /// as used by the subcommands that process several files. Without
+    /// `--category`, the file name is used as the category.
+    fn for_file(&self, file: &std::path::Path) -> Args {
+        let category = if self.category.is_empty() {
+            file.file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            self.category.clone()
+        };
+        Args {
+            command: None,
+            prefix: self.prefix.clone(),
+            anchor_prefix: self.anchor_prefix.clone(),
+            json_output: self.json_output,
+            stream: false,
+            json_version: self.json_version,
+            json_html: self.json_html,
+            keep_internal: self.keep_internal,
+            format: "markdown".to_string(),
+            category,
+            description: self.description.clone(),
+            description_file: self.description_file.clone(),
+            files: vec![],
+            dir: None,
+            glob: self.glob.clone(),
+            no_gitignore: self.no_gitignore,
+            no_docgenignore: self.no_docgenignore,
+            ignore_file: self.ignore_file.clone(),
+            file: Some(file.to_path_buf()),
+            locs: self.locs.clone(),
+            types: self.types.clone(),
+            export: self.export.clone(),
+            eval_fallback: self.eval_fallback.clone(),
+            legacy_comments: self.legacy_comments,
+            inherit_docs: self.inherit_docs,
+            usage_snippets: self.usage_snippets,
+            group_by: self.group_by,
+            validate_output: false,
+            sanitize_html: self.sanitize_html,
+            allowed_html_tags: self.allowed_html_tags.clone(),
+            es_bulk: None,
+            es_shape: self.es_shape,
+            es_flake: self.es_flake.clone(),
+            link_style: self.link_style,
+            example_style: self.example_style,
+            wrap: self.wrap,
+            markdown_dialect: self.markdown_dialect,
+            code_lang: self.code_lang.clone(),
+            code_lang_map: self.code_lang_map.clone(),
+            frontmatter: self.frontmatter.clone(),
+            config: self.config.clone(),
+            cache_dir: self.cache_dir.clone(),
+            see_index: self.see_index.clone(),
+            lang: self.lang.clone(),
+            no_metadata: self.no_metadata,
+            command_line: self.command_line.clone(),
+        }
+    }
+
+    /// The settings loading a file depends on, besides the files it reads,
+    /// for the key of its cache record.
+    fn load_settings(&self) -> serde_json::Value {
+        serde_json::json!({
+            "prefix": self.prefix,
+            "anchor_prefix": self.anchor_prefix,
+            "category": self.category,
+            "description": self.description,
+            "description_file": self.description_file,
+            "locs": self.locs,
+            "types": self.types,
+            "export": self.export,
+            "legacy_comments": self.legacy_comments,
+            "inherit_docs": self.inherit_docs,
+            "usage_snippets": self.usage_snippets,
+            "keep_internal": self.json_output && self.keep_internal,
+            "sanitize_html": self.sanitize_html.map(|mode| value_name(&mode)),
+            "allowed_html_tags": self.allowed_html_tags,
+            "lang": self.lang,
+            "config": self.config,
+        })
+    }
+
+    /// The HTML sanitization policy selected on the command line, if any.
+    fn html_policy(&self) -> Option<HtmlPolicy> {
+        self.sanitize_html.map(|mode| HtmlPolicy {
+            mode,
+            allowed_tags: self.allowed_html_tags.clone(),
+        })
+    }
+
+    /// The ignore files read when walking --dir.
+    fn ignore_options(&self) -> ignore::IgnoreOptions {
+        ignore::IgnoreOptions {
+            gitignore: !self.no_gitignore,
+            docgenignore: !self.no_docgenignore,
+            files: self.ignore_file.clone(),
+        }
+    }
+
+    /// The code block language tags selected on the command line.
+    fn code_langs(&self) -> CodeLangs {
+        CodeLangs {
+            default: Some(self.code_lang.clone()).filter(|lang| lang != "none"),
+            map: self.code_lang_map.clone(),
+        }
+    }
+}
+
+/// Flags of the `manifest` command that relate to earlier runs and to the
+/// published site.
+#[derive(Debug, Default, clap::Args)]
+struct SiteArgs {
+    /// Delete documents listed in the previous manifest that this run no
+    /// longer produces, e.g. pages of renamed categories
+    #[arg(long, default_value_t = false)]
+    prune: bool,
+
+    /// Directory of category landing pages: `<category>.md` replaces the
+    /// file doc comment at the top of the category page, and its front
+    /// matter can set the title and list featured functions
+    #[arg(long)]
+    landing_dir: Option<PathBuf>,
+
+    /// Keep docgen-redirects.json, mapping `path#anchor` links of entries
+    /// whose page or anchor changed since the previous manifest to their
+    /// new links
+    #[arg(long, default_value_t = false)]
+    redirects: bool,
+
+    /// Also write an HTML page in place of each removed document that
+    /// forwards its links to the entries' new pages (implies --redirects)
+    #[arg(long, default_value_t = false)]
+    redirect_stubs: bool,
+
+    /// Write an mdBook SUMMARY.md listing the documents as chapters, nested
+    /// by category. In an existing file, only the lines between
+    /// `<!-- docgen:start -->` and `<!-- docgen:end -->` are replaced.
+    #[arg(long, value_name = "FILE")]
+    summary: Option<PathBuf>,
+
+    /// Link each document to the previous and next one in reading order
+    /// (see `[navigation]` in docgen.toml) and write docgen-nav.json
+    /// listing them
+    #[arg(long, default_value_t = false)]
+    navigation: bool,
+}
+
+/// Flags for rendering module options, shared by the `options`, `module` and
+/// `bench` commands.
+#[derive(Debug, clap::Args)]
+struct OptionsRenderArgs {
+    /// Document title, which may use a `{count}` placeholder for the number
+    /// of options (not with --stream) [default: Module Options]
+    #[arg(short, long, value_parser = options::parse_title_template)]
+    title: Option<String>,
+
+    /// Preamble text to include after the title
+    #[arg(short, long)]
+    preamble: Option<String>,
+
+    /// Prefix for anchor IDs, or an anchor template with a `{name}`
+    /// placeholder, e.g. 'option-{name}'
+    #[arg(long, default_value = "opt-", value_parser = options::parse_anchor_template)]
+    anchor_prefix: String,
+
+    /// Include declaration source links
+    #[arg(long, default_value_t = true)]
+    include_declarations: bool,
+
+    /// Base URL for declaration links (e.g., https://github.com/owner/repo)
+    #[arg(long)]
+    declarations_base_url: Option<String>,
+
+    /// Git revision for declaration links
+    #[arg(long)]
+    revision: Option<String>,
+
+    /// Link declarations under PREFIX to another repository, optionally
+    /// at its own revision (e.g.
+    /// 'home-manager/=https://github.com/nix-community/home-manager@release-24.05'),
+    /// HEAD unless given. The longest prefix matching whole path segments
+    /// wins and is removed from the path.
+    /// Can be given multiple times.
+    #[arg(long, value_name = "PREFIX=URL[@REV]")]
+    declarations_profile: Vec<options::DeclarationProfile>,
+
+    /// Link options mentioned in descriptions: `{option}` roles always, and
+    /// names of other options, bare or as inline code, unless streaming
+    #[arg(long, default_value_t = false)]
+    cross_links: bool,
+
+    /// List the options mentioning each option under "Referenced by"
+    /// (not with --stream)
+    #[arg(long, default_value_t = false)]
+    referenced_by: bool,
+
+    /// Heading of each option, with `{name}` and `{type}` placeholders, e.g.
+    /// '`{name}` ({type})' [default: `{name}`]
+    #[arg(long, value_parser = options::parse_option_heading)]
+    option_heading: Option<String>,
+
+    /// Write a heading with a `{group}` placeholder before the options of
+    /// each group, e.g. 'Options of `{group}`'
+    #[arg(long, value_parser = options::parse_group_heading)]
+    group_heading: Option<String>,
+
+    /// Number of leading name components making up the group of an option
+    /// [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    group_depth: Option<u64>,
+}
+
+impl OptionsRenderArgs {
+    /// Fill the headings not given on the command line from the `[options]`
+    /// section of the configuration file, and include the fragments named
+    /// by the preamble.
+    fn with_config(mut self, args: &Args) -> Result<Self, String> {
+        if let Some(preamble) = &self.preamble {
+            self.preamble =
+                Some(include::expand_includes(preamble, std::path::Path::new(""), 1)?.into_owned());
+        }
+        let Some(path) = &args.config else {
+            return Ok(self);
+        };
+        let section = config::Config::load(path)?.options;
+        let check = |value: Option<String>, parse: fn(&str) -> Result<String, String>| {
+            value
+                .map(|value| parse(&value))
+                .transpose()
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        self.title = self
+            .title
+            .or(check(section.title, options::parse_title_template)?);
+        self.option_heading = self.option_heading.or(check(
+            section.option_heading,
+            options::parse_option_heading,
+        )?);
+        self.group_heading = self
+            .group_heading
+            .or(check(section.group_heading, options::parse_group_heading)?);
+        self.group_depth = self.group_depth.or(section.group_depth.map(|d| d as u64));
+        Ok(self)
+    }
+
+    fn title(&self) -> String {
+        self.title
+            .clone()
+            .unwrap_or_else(|| "Module Options".to_string())
+    }
+
+    fn render_options(self, args: &Args) -> options::RenderOptions {
+        options::RenderOptions {
+            anchor_prefix: self.anchor_prefix,
+            include_declarations: self.include_declarations,
+            declarations_base_url: self.declarations_base_url,
+            revision: self.revision,
+            declaration_profiles: self.declarations_profile,
+            html_policy: args.html_policy(),
+            link_style: args.link_style,
+            wrap: args.wrap,
+            dialect: args.markdown_dialect,
+            code_langs: args.code_langs(),
+            cross_links: self.cross_links,
+            referenced_by: self.referenced_by,
+            option_heading: self
+                .option_heading
+                .unwrap_or_else(|| options::DEFAULT_OPTION_HEADING.to_string()),
+            group_heading: self.group_heading,
+            group_depth: self.group_depth.map_or(2, |d| d as usize),
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
 enum Command {
     /// Render NixOS-style module options from JSON to CommonMark
+    #[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
     Options {
+        #[command(subcommand)]
+        action: Option<OptionsCommand>,
+
         /// Input JSON file containing options (from lib.optionAttrSetToDocList)
-        #[arg(short, long)]
-        file: PathBuf,
+        #[arg(short, long, required_unless_present_any = ["schema", "module"])]
+        file: Option<PathBuf>,
+
+        /// JSON Schema (JSON or TOML) of a configuration format, rendered as
+        /// options below PREFIX next to those of --file. Can be given
+        /// multiple times.
+        #[arg(long, value_name = "[PREFIX=]FILE", value_parser = schema::parse_schema_input, conflicts_with = "stream")]
+        schema: Vec<schema::SchemaInput>,
+
+        /// NixOS module whose mkOption and mkEnableOption declarations are
+        /// read without evaluating it, rendered next to the options of
+        /// --file. Can be given multiple times.
+        #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+        module: Vec<PathBuf>,
 
         /// Output file (defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Document title
-        #[arg(short, long, default_value = "Module Options")]
-        title: String,
+        #[command(flatten)]
+        render: OptionsRenderArgs,
+
+        /// Render options in input order while parsing, keeping memory bounded
+        /// for very large option sets (skips sorting)
+        #[arg(long, default_value_t = false)]
+        stream: bool,
+
+        /// Only render options whose JSON field (a dotted path) equals
+        /// ('='), differs from ('!='), contains ('=~') or does not contain
+        /// ('!~') the value, e.g. 'type=~submodule' or 'readOnly=false'.
+        /// Can be given multiple times; all must match.
+        #[arg(long, value_name = "FIELD=VALUE")]
+        filter: Vec<options::OptionFilter>,
+
+        /// Warn about empty or unfinished descriptions, missing examples for
+        /// structured types and deprecated types
+        #[arg(long, default_value_t = false)]
+        lint: bool,
+    },
 
-        /// Preamble text to include after the title
+    /// Evaluate a NixOS module exported by a flake and render its options
+    ///
+    /// The module is evaluated on its own with `nix eval`, using `lib` from
+    /// the flake's nixpkgs input and a minimal `pkgs`, and its options are
+    /// rendered like the options command.
+    Module {
+        /// Flake output holding the module (e.g. '.#nixosModules.myservice')
+        #[arg(long, value_name = "FLAKE#ATTR")]
+        flake: flake::FlakeOutput,
+
+        /// Output file (defaults to stdout)
         #[arg(short, long)]
-        preamble: Option<String>,
+        output: Option<PathBuf>,
 
-        /// Prefix for anchor IDs
-        #[arg(long, default_value = "opt-")]
-        anchor_prefix: String,
+        /// Nix executable used for the evaluation
+        #[arg(long, default_value = "nix")]
+        nix: String,
 
-        /// Include declaration source links
-        #[arg(long, default_value_t = true)]
-        include_declarations: bool,
+        /// Warn about empty or unfinished descriptions, missing examples for
+        /// structured types and deprecated types
+        #[arg(long, default_value_t = false)]
+        lint: bool,
 
-        /// Base URL for declaration links (e.g., https://github.com/owner/repo)
-        #[arg(long)]
-        declarations_base_url: Option<String>,
+        #[command(flatten)]
+        render: OptionsRenderArgs,
+    },
 
-        /// Git revision for declaration links
-        #[arg(long)]
-        revision: Option<String>,
+    /// Evaluate the `nix-assert` examples of documented functions
+    ///
+    /// Both sides of each `expression => expected` pair are evaluated with
+    /// `nix eval` in the scope of the documented file and must be equal.
+    Doctest {
+        /// Nix files to test
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Nix executable used for the evaluation
+        #[arg(long, default_value = "nix")]
+        nix: String,
     },
 
-    /// Extract just the file-level documentation comment from a Nix file
-    FileDoc {
-        /// Nix file to extract documentation from
+    /// Render a single entry of a Nix file, for embedding its docs elsewhere
+    /// (e.g. in editor hovers)
+    ///
+    /// The entry is rendered like the default command, using the top-level
+    /// flags; without `--category`, the file name is used as the category.
+    Entry {
+        /// Nix file containing the entry
         #[arg(short, long)]
         file: PathBuf,
 
+        /// Attribute path of the entry within the file (e.g. 'concatStrings')
+        /// or its full title (e.g. 'lib.strings.concatStrings')
+        #[arg(short, long)]
+        name: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = EntryFormat::Markdown)]
+        format: EntryFormat,
+    },
+
+    /// Extract just the file-level documentation comment from Nix files
+    ///
+    /// With several files or --dir, JSON output is an array of objects and
+    /// markdown output has a section per documented file, headed by its
+    /// path, however many files there are.
+    FileDoc {
+        /// Nix file to extract documentation from. Repeat for several files.
+        #[arg(
+            short = 'f',
+            long = "file",
+            value_name = "FILE",
+            required_unless_present = "dir"
+        )]
+        files: Vec<PathBuf>,
+
+        /// Also extract the documentation of the Nix files below this
+        /// directory that match --glob, in path order.
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+
+        /// Pattern for the files read with --dir, relative to it.
+        #[arg(long, default_value = "**/*.nix", requires = "dir")]
+        glob: String,
+
         /// Output format: markdown, json, or plain
         #[arg(long, default_value = "markdown")]
         format: String,
@@ -132,340 +742,2466 @@ enum Command {
         #[arg(long, default_value_t = 0)]
         shift_headings: usize,
     },
-}
 
-#[derive(Debug)]
-struct DocComment {
-    /// Primary documentation string.
-    doc: String,
-}
+    /// Rewrite an existing CommonMark document into docgen's canonical style
+    Normalize {
+        /// Markdown file to normalize
+        #[arg(short, long)]
+        file: PathBuf,
 
-#[derive(Debug)]
-struct DocItem {
-    name: String,
-    comment: DocComment,
-    args: Vec<Argument>,
-}
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
 
-#[derive(Debug, Serialize)]
-struct JsonFormat {
-    version: u32,
-    entries: Vec<ManualEntry>,
-}
+        /// Language tag for code fences that have none (e.g. 'nix')
+        #[arg(long)]
+        fence_lang: Option<String>,
+    },
 
-/// Returns a RFC145 doc-comment if one is present
-pub fn retrieve_doc_comment(node: &SyntaxNode, shift_headings_by: Option<usize>) -> Option<String> {
-    let doc_comment = get_expr_docs(node);
+    /// Write or check golden copies of the generated documentation
+    ///
+    /// Each input is rendered like the default command, using the top-level
+    /// `--prefix`, `--anchor-prefix`, `--category` and `--description` flags.
+    /// Without `--category`, the file name is used as the category.
+    Snapshot {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory holding the golden outputs
+        #[arg(long, default_value = snapshot::DEFAULT_SNAPSHOT_DIR)]
+        dir: PathBuf,
+
+        /// Overwrite the golden outputs with the current rendering
+        #[arg(long, conflicts_with = "verify", required_unless_present = "verify")]
+        update: bool,
+
+        /// Fail if any golden output differs from the current rendering
+        #[arg(long)]
+        verify: bool,
+    },
 
-    doc_comment.map(|doc_comment| {
-        shift_headings(
-            &handle_indentation(&doc_comment).unwrap_or(String::new()),
-            // H1 to H4 can be used in the doc-comment with the current rendering.
-            // They will be shifted to H3, H6
-            // H1 and H2 are currently used by the outer rendering. (category and function name)
-            shift_headings_by.unwrap_or(2),
-        )
-    })
-}
+    /// Write a DevDocs documentation set (index.json, db.json and HTML pages)
+    ///
+    /// Each input becomes one page, rendered with the top-level flags like
+    /// the snapshot command.
+    Devdocs {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the documentation set to
+        #[arg(short, long, default_value = "devdocs")]
+        output_dir: PathBuf,
+
+        /// Name of the documentation set as shown in DevDocs
+        #[arg(short, long, default_value = "Nix")]
+        name: String,
+    },
 
-/// Traverse directly chained nix lambdas and collect the identifiers of all lambda arguments.
-fn collect_lambda_args(mut lambda: Lambda) -> Vec<Argument> {
-    let mut args = vec![];
+    /// Write a standalone HTML page per file plus an index page
+    ///
+    /// Pages have a sidebar listing the pages and the entries of the current
+    /// one, anchors on every entry and highlighted Nix code blocks, and need
+    /// no further processing to be served.
+    Html {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the pages to
+        #[arg(short, long, default_value = "html")]
+        output_dir: PathBuf,
+
+        /// Site name shown in page titles and on the index page
+        #[arg(short, long, default_value = "Nix library")]
+        name: String,
+
+        /// URL the site is served from; also writes a search page, its
+        /// index and an OpenSearch description for browsers
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+    },
 
-    loop {
-        match lambda.param().unwrap() {
-            Param::IdentParam(id) => {
-                args.push(Argument::Flat(SingleArg {
-                    name: id.to_string(),
-                    doc: handle_indentation(
-                        &retrieve_doc_comment(id.syntax(), Some(1)).unwrap_or_default(),
-                    ),
-                }));
-            }
-            Param::Pattern(pat) => {
-                let pattern_vec: Vec<_> = pat
-                    .pat_entries()
-                    .map(|entry| SingleArg {
-                        name: entry.ident().unwrap().to_string(),
-                        doc: handle_indentation(
-                            &retrieve_doc_comment(entry.syntax(), Some(1)).unwrap_or_default(),
-                        ),
-                    })
-                    .collect();
+    /// Write an Antora component: an AsciiDoc page per file and a navigation
+    ///
+    /// Each input becomes one page of the `ROOT` module, rendered with the
+    /// top-level flags like the html command, and is listed in `nav.adoc`
+    /// with its entries. Add the output directory to the content sources of
+    /// an Antora playbook to publish it.
+    Antora {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the component to
+        #[arg(short, long, default_value = "antora")]
+        output_dir: PathBuf,
+
+        /// Name of the component, used in page ids and URLs
+        #[arg(short, long, default_value = "nix")]
+        name: String,
+
+        /// Title of the component shown in the site
+        #[arg(long, default_value = "Nix library")]
+        title: String,
 
-                args.push(Argument::Pattern(pattern_vec));
-            }
-        }
+        /// Version of the component (defaults to a versionless component)
+        #[arg(long)]
+        component_version: Option<String>,
+    },
 
-        match lambda.body() {
-            Some(Expr::Lambda(inner)) => lambda = inner,
-            _ => break,
-        }
-    }
+    /// Write a Hugo content tree: a section per file and a page per function
+    ///
+    /// Each input is rendered like the default command, using the top-level
+    /// flags, into `<output-dir>/<category>/_index.md` and one
+    /// `<output-dir>/<category>/<name>.md` per function (`function-index.md`
+    /// for a function named `index` or `_index`), with links between the
+    /// functions going to their pages. Files start with `title`, `slug` and
+    /// `weight` frontmatter, plus the fields given with `--frontmatter`;
+    /// without `--category`, the file name is the category.
+    Hugo {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the sections to
+        #[arg(short, long, default_value = "content/functions")]
+        output_dir: PathBuf,
+    },
 
-    args
-}
+    /// Write one section 3 man page per function plus an index page
+    ///
+    /// Pages are written to `<output-dir>/man3`, so adding the output
+    /// directory to MANPATH makes `man lib.strings.concatStrings` work.
+    /// With `--combined`, all functions are written to one `lib(3)` page.
+    Man {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the man pages to
+        #[arg(short, long, default_value = "man")]
+        output_dir: PathBuf,
+
+        /// Manual title shown in the page footer
+        #[arg(short, long, default_value = "Nix Library Functions")]
+        manual: String,
+
+        /// Write a single page named after the prefix documenting every
+        /// function, instead of a page per function and an index
+        #[arg(long, default_value_t = false)]
+        combined: bool,
+    },
+
+    /// Write a tags file locating each documented function
+    ///
+    /// Every entry is tagged by its full title and its name, at the line
+    /// its binding is defined on, so editors can jump from a use of
+    /// `lib.strings.concatStrings` or `concatStrings` to its definition.
+    Tags {
+        /// Nix files to index
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Tags file to write [default: tags, or TAGS with --etags]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
 
-/// Transforms an AST node into a `DocItem` if it has a leading
-/// documentation comment.
-fn retrieve_doc_item(node: &AttrpathValue) -> Option<DocItem> {
-    let ident = node.attrpath().unwrap();
-    let item_name = ident.to_string();
+        /// Write an Emacs etags file instead of a ctags file
+        #[arg(short, long, default_value_t = false)]
+        etags: bool,
+    },
 
-    let doc_comment = retrieve_doc_comment(node.syntax(), Some(2))?;
+    /// Write a client-side search index for lunr.js or elasticlunr
+    ///
+    /// Lists every documented function with its name, title, category,
+    /// type and description as plain text, and a link to its entry, along
+    /// with the reference and fields to build the index from in the browser.
+    Index {
+        /// Nix files to index
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Page documenting each file, linked to by its entries, with a
+        /// `{category}` placeholder
+        #[arg(long, default_value = "{category}.html", value_name = "TEMPLATE")]
+        page_url: String,
 
-    Some(DocItem {
-        name: item_name,
-        comment: DocComment { doc: doc_comment },
-        args: vec![],
-    })
-}
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-impl DocItem {
-    fn into_entry(
-        self,
-        prefix: &str,
-        category: &str,
-        locs: &HashMap<String, String>,
-    ) -> ManualEntry {
-        let ident = get_identifier(
-            &prefix.to_string(),
-            &category.to_string(),
-            &self.name.to_string(),
-        );
+    /// Print the JSON Schema of the JSON output
+    ///
+    /// Describes the document written by `--json-output`, or with `--entry`
+    /// a single entry as written by `--json-output --stream` and
+    /// `entry --format json`, in the version selected with `--json-version`,
+    /// for validating the output or generating typed bindings.
+    Schema {
+        /// Describe a single entry instead of the whole document
+        #[arg(long, default_value_t = false)]
+        entry: bool,
 
-        ManualEntry {
-            prefix: prefix.to_string(),
-            category: category.to_string(),
-            location: locs.get(&ident).cloned(),
-            name: self.name,
-            description: self
-                .comment
-                .doc
-                .split("\n\n")
-                .map(|s| s.to_string())
-                .collect(),
-            fn_type: None,
-            example: None,
-            args: self.args,
-        }
-    }
-}
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-/// Traverse the arena from a top-level SetEntry and collect, where
-/// possible:
-///
-/// 1. The identifier of the set entry itself.
-/// 2. The attached doc comment on the entry.
-/// 3. The argument names of any curried functions.
-fn collect_entry_information(entry: AttrpathValue) -> Option<DocItem> {
-    let mut doc_item = retrieve_doc_item(&entry)?;
+    /// Write one CommonMark document per category plus a JSON manifest
+    /// describing them
+    ///
+    /// Documents are written to `<output-dir>/<category>.md` and listed with
+    /// their entries, anchors, source files and hashes in
+    /// `<output-dir>/docgen-manifest.json`. Each input is rendered like the
+    /// default command, using the top-level flags; without `--category`, the
+    /// file name is used as the category.
+    Manifest {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the documents and the manifest to
+        #[arg(short, long, default_value = "docs")]
+        output_dir: PathBuf,
+
+        #[command(flatten)]
+        site: SiteArgs,
+    },
 
-    if let Some(Expr::Lambda(l)) = entry.value() {
-        doc_item.args = collect_lambda_args(l);
-    }
+    /// Write one CommonMark document per category and rewrite it whenever a
+    /// file it is made from changes
+    ///
+    /// Each input is rendered like the default command to
+    /// `<output-dir>/<category>.md`. Its document is made from the input,
+    /// the files it re-exports attributes from with `inherit (import
+    /// ./file.nix) ...`, its location data, types, description file and
+    /// included fragments, the configuration file and `--see-index`; these
+    /// are checked for changes every `--interval`, and only the documents
+    /// made from a changed file are rebuilt, with a line naming the files
+    /// that changed. Runs until interrupted.
+    Watch {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory to write the documents to
+        #[arg(short, long, default_value = "docs")]
+        output_dir: PathBuf,
+
+        /// Milliseconds between checks for changed files
+        #[arg(long, default_value_t = 500, value_name = "MS")]
+        interval: u64,
+    },
+
+    /// Run the checks of the `[checks]` section of docgen.toml on the given
+    /// files, for pre-commit hooks
+    ///
+    /// Nix files fail on warnings about their doc comments and on
+    /// documentation coverage below `min_coverage`; modules and options JSON
+    /// files fail on the lint warnings of their options. Files no pattern of
+    /// the section matches, and files that no longer exist, are skipped. Each
+    /// problem is printed, and docgen exits with status 1 if there are any.
+    Precommit {
+        /// Files to check, such as the staged ones (read from standard
+        /// input, separated by newlines or NUL characters, if none are
+        /// given)
+        files: Vec<PathBuf>,
+    },
+
+    /// Show what `manifest` would do with the same arguments, without
+    /// writing anything
+    ///
+    /// Lists the configuration file in effect, the rendering settings, and
+    /// for each input its category, output document and the bindings that
+    /// would be documented, lack a doc comment or are hidden by visibility
+    /// rules, followed by the documents `--prune` would remove. The redirect
+    /// map, mdBook summary and navigation file that would be written are
+    /// listed with the configuration.
+    Plan {
+        /// Nix files to render
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Directory the documents and the manifest would be written to
+        #[arg(short, long, default_value = "docs")]
+        output_dir: PathBuf,
+
+        #[command(flatten)]
+        site: SiteArgs,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = plan::PlanFormat::Table)]
+        format: plan::PlanFormat,
+    },
+
+    /// Show how the documentation of each function or option changed
+    /// between two revisions
+    ///
+    /// Inputs are read like the changelog command. Each added, changed or
+    /// removed entry is shown as a line diff of its CommonMark section, or,
+    /// with `--format html`, as its old and new rendering side by side.
+    Diff {
+        /// Files of the previous revision
+        #[arg(long, required = true, num_args = 1..)]
+        old: Vec<PathBuf>,
+
+        /// Files of the new revision
+        #[arg(long, required = true, num_args = 1..)]
+        new: Vec<PathBuf>,
+
+        /// Compare module options JSON files instead of Nix files
+        #[arg(long, default_value_t = false)]
+        options: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = changes::DiffFormat::Markdown)]
+        format: changes::DiffFormat,
+
+        /// Title of the diff
+        #[arg(short, long, default_value = "Documentation diff")]
+        title: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write documentation coverage metrics per category as JSON
+    ///
+    /// Each input is processed like the default command, using the top-level
+    /// flags; without `--category`, the file name is used as the category.
+    Metrics {
+        /// Nix files to measure
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
 
-    Some(doc_item)
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Append the record to the JSON array in the output file instead of
+        /// overwriting it
+        #[arg(long, default_value_t = false, requires = "output")]
+        append: bool,
+
+        /// Git revision to record (defaults to the checked out commit)
+        #[arg(long)]
+        revision: Option<String>,
+
+        /// Time to record as RFC 3339 (defaults to now)
+        #[arg(long)]
+        timestamp: Option<String>,
+
+        /// Also record the word count, code-to-prose ratio and readability
+        /// of every entry
+        #[arg(long, default_value_t = false)]
+        entries: bool,
+    },
+
+    /// Time parsing, collecting and rendering an input
+    ///
+    /// A Nix file is parsed, its entries collected and rendered like the
+    /// default command, using the top-level flags; an options JSON file
+    /// (`.json`) is parsed and rendered like the options command. Each phase
+    /// is run the given number of times and reported with its mean, fastest
+    /// and slowest run and its throughput.
+    Bench {
+        /// Nix file or options JSON file to process
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Runs of each phase
+        #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        iterations: u64,
+
+        #[command(flatten)]
+        render: OptionsRenderArgs,
+    },
+
+    /// List the functions or options added, changed or removed between two
+    /// revisions, as CommonMark or as an Atom feed
+    ///
+    /// Nix files are rendered like the default command, using the top-level
+    /// flags; without `--category`, the file name is used as the category.
+    Changelog {
+        /// Files of the previous revision
+        #[arg(long, required = true, num_args = 1..)]
+        old: Vec<PathBuf>,
+
+        /// Files of the new revision
+        #[arg(long, required = true, num_args = 1..)]
+        new: Vec<PathBuf>,
+
+        /// Compare module options JSON files instead of Nix files
+        #[arg(long, default_value_t = false)]
+        options: bool,
+
+        /// Write an Atom feed instead of CommonMark
+        #[arg(long, default_value_t = false)]
+        atom: bool,
+
+        /// Title of the changelog or feed
+        #[arg(short, long, default_value = "Documentation changes")]
+        title: String,
+
+        /// URL of the rendered documentation, for links to changed entries
+        #[arg(long)]
+        base_url: Option<String>,
+
+        /// Permanent id of the feed (defaults to the base URL)
+        #[arg(long)]
+        feed_id: Option<String>,
+
+        /// Name of the new revision, used in feed entry ids
+        #[arg(long, default_value = "HEAD")]
+        revision: String,
+
+        /// Time of the new revision as RFC 3339 (defaults to now)
+        #[arg(long)]
+        updated: Option<String>,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
-fn collect_bindings(
-    node: &SyntaxNode,
-    prefix: &str,
-    category: &str,
-    locs: &HashMap<String, String>,
-    scope: HashMap<String, ManualEntry>,
-) -> Vec<ManualEntry> {
-    for ev in node.preorder() {
-        match ev {
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
-                let mut entries = vec![];
-                for child in n.children() {
-                    if let Some(apv) = AttrpathValue::cast(child.clone()) {
-                        entries.extend(
-                            collect_entry_information(apv)
-                                .map(|di| di.into_entry(prefix, category, locs)),
-                        );
-                    } else if let Some(inh) = Inherit::cast(child) {
-                        if inh.from().is_some() {
-                            continue;
-                        }
-                        entries.extend(inh.attrs().filter_map(|a| match a {
-                            Attr::Ident(i) => scope.get(&i.syntax().text().to_string()).cloned(),
-                            _ => None,
-                        }));
-                    }
-                }
-                return entries;
-            }
-            _ => (),
-        }
-    }
+#[derive(Debug, Serialize)]
+struct JsonFormat<E> {
+    version: u32,
+    entries: Vec<E>,
+}
 
-    vec![]
+/// An entry of version 1 of the JSON output, with its content hash and,
+/// with `--json-html`, its description as HTML.
+#[derive(Debug, Serialize)]
+struct JsonEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a ManualEntry,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description_html: Option<String>,
 }
 
-/// Given a let-in expression and an identifier name, find the corresponding
-/// AttrpathValue binding in the let block.
-fn find_let_binding(let_in: &LetIn, name: &str) -> Option<AttrpathValue> {
-    for entry in let_in.entries() {
-        if let Some(apv) = AttrpathValue::cast(entry.syntax().clone()) {
-            if let Some(path) = apv.attrpath() {
-                if path.to_string() == name {
-                    return Some(apv);
-                }
-            }
+impl<'a> JsonEntry<'a> {
+    fn new(entry: &'a ManualEntry, args: &Args) -> Self {
+        JsonEntry {
+            entry,
+            sha256: entry.content_hash(),
+            description_html: args
+                .json_html
+                .then(|| html::markdown_to_html(&entry.description.join("\n\n"), true)),
         }
     }
-    None
 }
 
-/// Resolve an identifier in the context of a let-in expression.
-fn resolve_let_ident(let_in: &LetIn, ident: &Ident) -> Option<SyntaxNode> {
-    let name = ident.to_string();
-    let apv = find_let_binding(let_in, &name)?;
-    let value = apv.value()?;
-
-    if let Expr::Ident(ref inner_ident) = value {
-        resolve_let_ident(let_in, inner_ident)
-    } else {
-        Some(value.syntax().clone())
-    }
-}
-
-fn collect_entries(
-    root: rnix::Root,
-    prefix: &str,
-    category: &str,
-    locs: &HashMap<String, String>,
-    export: &Option<Vec<String>>,
-) -> Vec<ManualEntry> {
-    let mut preorder = root.syntax().preorder();
-    while let Some(ev) = preorder.next() {
-        match ev {
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_PATTERN => {
-                preorder.skip_subtree();
-            }
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_LET_IN => {
-                let let_in = LetIn::cast(n.clone()).unwrap();
-                let scope: HashMap<String, ManualEntry> = n
-                    .children()
-                    .filter_map(AttrpathValue::cast)
-                    .filter_map(collect_entry_information)
-                    .map(|di| (di.name.to_string(), di.into_entry(prefix, category, locs)))
-                    .collect();
+/// The formats of the document rendered by the default command. Backends
+/// of other formats are registered here.
+fn renderers() -> render::Registry {
+    render::Registry::new()
+}
 
-                if let Some(exports) = export {
-                    return exports
-                        .iter()
-                        .filter_map(|name| scope.get(name).cloned())
-                        .collect();
-                }
+/// The `--format` names of [`renderers`].
+fn format_parser() -> clap::builder::PossibleValuesParser {
+    renderers()
+        .iter()
+        .map(|renderer| clap::builder::PossibleValue::new(renderer.name()).help(renderer.about()))
+        .collect::<Vec<_>>()
+        .into()
+}
 
-                let body = let_in.body().unwrap();
+/// Subcommands of the `options` command.
+#[derive(Debug, Parser)]
+enum OptionsCommand {
+    /// Browse options in the terminal
+    ///
+    /// Shows the options as a tree of their attribute paths next to the
+    /// type, default, example, declarations and rendered description of the
+    /// selected one. Press `/` to search option names fuzzily.
+    #[cfg(feature = "browse")]
+    Browse {
+        /// Input JSON file containing options (from lib.optionAttrSetToDocList)
+        #[arg(short, long, required_unless_present_any = ["schema", "module"])]
+        file: Option<PathBuf>,
+
+        /// JSON Schema (JSON or TOML) of a configuration format, browsed as
+        /// options below PREFIX next to those of --file. Can be given
+        /// multiple times.
+        #[arg(long, value_name = "[PREFIX=]FILE", value_parser = schema::parse_schema_input)]
+        schema: Vec<schema::SchemaInput>,
+
+        /// NixOS module whose option declarations are read without
+        /// evaluating it, browsed next to the options of --file. Can be
+        /// given multiple times.
+        #[arg(long, value_name = "FILE")]
+        module: Vec<PathBuf>,
+
+        /// Only browse options whose JSON field (a dotted path) matches, as
+        /// with the options command. Can be given multiple times.
+        #[arg(long, value_name = "FIELD=VALUE")]
+        filter: Vec<options::OptionFilter>,
+    },
+}
 
-                if let Expr::Ident(ref ident) = body {
-                    if let Some(resolved) = resolve_let_ident(&let_in, ident) {
-                        return collect_bindings(&resolved, prefix, category, locs, scope);
-                    }
-                }
+/// Output format of the `entry` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EntryFormat {
+    /// The entry's section, as in the full document
+    Markdown,
+    /// The entry as in the `entries` of `--json-output`
+    Json,
+}
 
-                return collect_bindings(body.syntax(), prefix, category, locs, scope);
-            }
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
-                return collect_bindings(&n, prefix, category, locs, Default::default());
+/// The visibility rules for `args.file`, and the bindings selected with
+/// `--export` extended by the rules' `include` patterns.
+fn visibility(
+    args: &Args,
+    nix: &rnix::Root,
+) -> Result<(config::Rules, Option<Vec<String>>), String> {
+    let rules = match (&args.config, &args.file) {
+        (Some(path), Some(file)) => config::Config::load(path)?.visibility.rules_for(file),
+        _ => Default::default(),
+    };
+    let mut export = args.export.clone();
+    if let Some(exports) = &mut export {
+        for name in let_binding_names(nix) {
+            let title = get_title(&args.prefix, &args.category, &name);
+            if !exports.contains(&name) && rules.includes(&name, &title) {
+                exports.push(name);
             }
-            _ => (),
         }
     }
+    Ok((rules, export))
+}
+
+/// Names of the bindings of `args.file` that are exported, split into the
+/// ones that visibility rules keep and the ones they hide.
+fn partition_bindings(args: &Args) -> Result<(Vec<String>, Vec<String>), String> {
+    let file = args.file.as_ref().ok_or("--file is required")?;
+    let nix = parse_file(file)?;
+    let (rules, export) = visibility(args, &nix)?;
+    Ok(split_bindings(args, &nix, &rules, export))
+}
+
+/// The bindings of `nix` that could be documented, split by whether `rules`
+/// show them.
+fn split_bindings(
+    args: &Args,
+    nix: &rnix::Root,
+    rules: &config::Rules,
+    export: Option<Vec<String>>,
+) -> (Vec<String>, Vec<String>) {
+    let names: Vec<String> = match export {
+        Some(exports) => {
+            let bound = let_binding_names(nix);
+            exports.into_iter().filter(|n| bound.contains(n)).collect()
+        }
+        None => returned_binding_names(nix),
+    };
+    names
+        .into_iter()
+        .partition(|name| rules.is_visible(name, &get_title(&args.prefix, &args.category, name)))
+}
 
-    vec![]
+/// Parse the input file and collect the category description and its
+/// entries, in the selected language and with the HTML policy (if any)
+/// applied, exiting if it cannot be read.
+fn load_entries(args: &Args) -> (String, Vec<ManualEntry>) {
+    try_load_entries(args).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
 }
 
-/// Extract just the file-level documentation comment from a Nix file.
-fn extract_file_doc(nix: &rnix::Root) -> Option<String> {
-    nix.syntax()
-        .first_child()
-        .and_then(|node| retrieve_doc_comment(&node, Some(0)))
-        .and_then(|doc_item| handle_indentation(&doc_item))
+/// Like [`load_entries`], returning the problems reading the input.
+fn try_load_entries(args: &Args) -> Result<(String, Vec<ManualEntry>), String> {
+    let record = load_record(args)?;
+    for warning in &record.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    Ok((record.description, record.entries))
 }
 
-fn retrieve_description(nix: &rnix::Root, description: &str, category: &str) -> String {
-    if description.is_empty() && category.is_empty() {
-        return String::new();
+/// Load the input file, or take what it loaded to from the cache in
+/// --cache-dir if neither it nor the files it reads changed since.
+fn load_record(args: &Args) -> Result<cache::Record, String> {
+    let file = args.file.as_ref().ok_or("--file is required")?;
+    let cached = match &args.cache_dir {
+        Some(dir) if args.eval_fallback.is_none() => {
+            Some((dir, cache::key(file, &args.load_settings())?))
+        }
+        _ => None,
+    };
+    if let Some((dir, key)) = &cached
+        && let Some(record) = cache::lookup(dir, key)
+    {
+        return Ok(record);
     }
-    format!(
-        "# {} {{#sec-functions-library-{}}}\n{}\n",
-        description,
-        category,
-        extract_file_doc(nix).unwrap_or_default()
-    )
+    let record = read_record(args)?;
+    if let Some((dir, key)) = &cached
+        && let Err(e) = cache::store(dir, key, &record)
+    {
+        eprintln!("warning: {}", e);
+    }
+    Ok(record)
 }
 
-fn main_with_args(args: &Args) -> String {
-    let file = args.file.as_ref().expect("--file is required");
-    let src = fs::read_to_string(file).unwrap();
+/// Parse the input file and collect the category description and its
+/// entries, with the files read for them.
+fn read_record(args: &Args) -> Result<cache::Record, String> {
+    let file = args.file.as_ref().ok_or("--file is required")?;
     let locs = match &args.locs {
         None => Default::default(),
         Some(p) => fs::read_to_string(p)
             .map_err(|e| e.to_string())
             .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
-            .expect("could not read location information"),
+            .map_err(|e| {
+                format!(
+                    "could not read location information from {}: {}",
+                    p.display(),
+                    e
+                )
+            })?,
+    };
+    let nix = parse_file(file)?;
+    let mut warnings = vec![];
+    let mut description = match &args.description_file {
+        Some(path) => {
+            let intro = fs::read_to_string(path)
+                .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+            let (title, preamble) = split_intro(&intro);
+            let title = match title {
+                Some(title) if args.description.is_empty() => title,
+                _ => args.description.clone(),
+            };
+            category_header(&title, &args.category, &preamble)
+        }
+        None => retrieve_description(&nix, &args.description, &args.category, &mut warnings),
     };
-    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
-    let description = retrieve_description(&nix, &args.description, &args.category);
 
-    let entries = collect_entries(nix, &args.prefix, &args.category, &locs, &args.export);
+    let (rules, export) = visibility(args, &nix)?;
+    let (public_bindings, _) = split_bindings(args, &nix, &rules, export.clone());
+    let dir = file.parent().unwrap_or(std::path::Path::new(""));
+    let mut entries = match &args.eval_fallback {
+        Some(nix_bin) if export.is_none() && returned_binding_names(&nix).is_empty() => {
+            eprintln!(
+                "warning: {}: returned attribute set not found, evaluating it with {}",
+                file.display(),
+                nix_bin
+            );
+            let names = eval::attr_names(file, nix_bin)?;
+            entries_for_names(
+                &nix,
+                &names,
+                &args.prefix,
+                &args.category,
+                &locs,
+                &mut warnings,
+            )
+        }
+        _ => {
+            let mut entries = collect_entries_in(
+                nix.clone(),
+                Some(dir),
+                &args.prefix,
+                &args.category,
+                &locs,
+                &export,
+                &mut warnings,
+            );
+            if args.legacy_comments && export.is_none() {
+                collect_legacy_docs(
+                    &nix,
+                    &mut entries,
+                    &args.prefix,
+                    &args.category,
+                    &locs,
+                    &mut warnings,
+                );
+            }
+            if args.inherit_docs && export.is_none() {
+                inherit_wrapped_docs(&nix, &mut entries, &args.prefix, &args.category, &locs);
+            }
+            entries
+        }
+    };
+    let mut warnings: Vec<String> = warnings
+        .into_iter()
+        .map(|warning| format!("{}: {}", file.display(), warning))
+        .collect();
+    // A configured marker replaces `@internal`, whose lines are still taken
+    // out of the docs as a tag of docgen's.
+    if let Some(marker) = &rules.internal_marker {
+        for entry in &mut entries {
+            entry.internal = false;
+            mark_internal(entry, marker);
+        }
+    }
+    // Hidden and internal bindings can still be inherited from; tags naming
+    // functions of other files are resolved with the rest of the run.
+    inherit::inherit_docs(&mut entries);
+    entries.retain(|entry| rules.is_visible(&entry.name, &entry.get_ident_title().1));
+    entries.retain(|entry| !entry.internal || (args.json_output && args.keep_internal));
+
+    let mut read = vec![file.clone()];
+    read.extend(args.locs.clone());
+    read.extend(args.description_file.clone());
+    read.extend(args.config.clone());
+    read.extend(imported_files(&nix, dir));
+    match &args.types {
+        Some(path) => read.push(path.clone()),
+        // Companions created later change the entries too.
+        None => read.extend(types::companion_paths(file)),
+    }
 
-    if args.json_output {
-        serde_json::to_string(&JsonFormat {
-            version: 1,
-            entries,
-        })
-        .expect("Problem converting entries to JSON")
-    } else {
-        let mut output = description + "\n";
-        for entry in entries {
-            entry.write_section(args.anchor_prefix.as_str(), &mut output);
+    if let Some(path) = args.types.clone().or_else(|| types::companion(file)) {
+        let signatures = types::read_types(&path)?;
+        types::apply_types(&mut entries, &signatures);
+    }
+    if args.usage_snippets {
+        for entry in &mut entries {
+            let (_, title) = entry.get_ident_title();
+            entry.usage = entry
+                .signature
+                .as_ref()
+                .and_then(|signature| signature::usage_snippet(&title, signature));
         }
-        output
     }
-}
+    let description_dir = match &args.description_file {
+        Some(path) => path.parent().unwrap_or(std::path::Path::new("")),
+        None => dir,
+    };
+    read.extend(include::included_files(&description, description_dir));
+    description = include::expand_includes(&description, description_dir, 1)?.into_owned();
+    for entry in &mut entries {
+        read.extend(include::included_files(
+            &entry.description.join("\n\n"),
+            dir,
+        ));
+        include::expand_entry(entry, dir)?;
+    }
+    for warning in variants::expand_all(&mut description, &mut entries, &args.anchor_prefix) {
+        warnings.push(format!("{}: {}", file.display(), warning));
+    }
 
-fn main() {
-    let args = Args::parse();
+    let lang = args.lang.as_deref();
+    if let Cow::Owned(localized) = locale::select_language(&description, lang) {
+        description = localized;
+    }
+    for entry in &mut entries {
+        localize_entry(entry, lang);
+    }
 
-    match args.command {
-        Some(Command::Options {
-            file,
-            output,
-            title,
-            preamble,
-            anchor_prefix,
-            include_declarations,
-            declarations_base_url,
-            revision,
-        }) => {
-            let render_opts = options::RenderOptions {
-                anchor_prefix,
-                include_declarations,
-                declarations_base_url,
-                revision,
-            };
+    if let Some(policy) = args.html_policy() {
+        description = sanitize_html(&description, &policy).into_owned();
+        for entry in &mut entries {
+            sanitize_entry(entry, &policy);
+        }
+    }
 
-            let parsed = options::parse_options_file(&file).unwrap_or_else(|e| {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            });
+    let mut dependencies: Vec<cache::Dependency> = vec![];
+    for path in read {
+        if !dependencies
+            .iter()
+            .any(|dependency| dependency.path == path)
+        {
+            dependencies.push(cache::Dependency::of(&path));
+        }
+    }
+    Ok(cache::Record {
+        dependencies,
+        warnings,
+        description,
+        entries,
+        public_bindings,
+    })
+}
 
-            let result = options::render_options_document(
-                &parsed,
-                &title,
-                preamble.as_deref(),
-                &render_opts,
+/// Rendered function sections of the given Nix files, by title.
+fn function_sections(files: &[PathBuf], args: &Args) -> BTreeMap<String, changes::Section> {
+    let code_langs = args.code_langs();
+    let html = args.html_policy();
+    let mut sections = BTreeMap::new();
+    for file in files {
+        for entry in load_entries(&args.for_file(file)).1 {
+            let (_, title) = entry.get_ident_title();
+            let mut markdown = String::new();
+            entry.write_section_with(
+                &args.anchor_prefix,
+                &code_langs,
+                args.example_style,
+                html.as_ref(),
+                &mut markdown,
             );
-
-            if let Some(out_path) = output {
-                fs::write(&out_path, &result).unwrap_or_else(|e| {
+            let section = changes::Section {
+                anchor: entry.anchor(&args.anchor_prefix),
+                summary: entry.summary(),
+                markdown: code_langs.remap(&markdown).into_owned(),
+            };
+            sections.insert(title, section);
+        }
+    }
+    sections
+}
+
+/// Rendered option sections of the given options JSON files, by name.
+fn option_sections(files: &[PathBuf], args: &Args) -> BTreeMap<String, changes::Section> {
+    let render_opts = options::RenderOptions {
+        html_policy: args.html_policy(),
+        code_langs: args.code_langs(),
+        ..Default::default()
+    };
+    let mut sections = BTreeMap::new();
+    for file in files {
+        let parsed = options::parse_options_file(file).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        });
+        for (name, opt) in &parsed {
+            let summary = opt
+                .description
+                .as_ref()
+                .and_then(|d| d.as_str().lines().next())
+                .unwrap_or_default()
+                .to_string();
+            let section = changes::Section {
+                anchor: options::make_anchor_id(name, &render_opts.anchor_prefix),
+                summary,
+                markdown: options::render_option(name, opt, &render_opts),
+            };
+            sections.insert(name.clone(), section);
+        }
+    }
+    sections
+}
+
+/// Render a category description and its entries as a CommonMark document.
+/// `file` rendered as a page of a DevDocs set or HTML site, named after the
+/// file, with its entries and their anchors.
+fn page(args: &Args, file: &std::path::Path) -> devdocs::Page {
+    let file_args = args.for_file(file);
+    let (description, entries) = load_entries(&file_args);
+    let page_entries = entries
+        .iter()
+        .map(|entry| {
+            let (_, title) = entry.get_ident_title();
+            (title, entry.anchor(&args.anchor_prefix))
+        })
+        .collect();
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let path = devdocs::slugify(&stem);
+    let url = format!("{}.html", path);
+    devdocs::Page {
+        documents: entries
+            .iter()
+            .map(|entry| lunr::IndexDocument::from_entry(entry, &args.anchor_prefix, &url))
+            .collect(),
+        path,
+        type_name: file_args.category.clone(),
+        markdown: render_markdown(description, &entries, &file_args),
+        entries: page_entries,
+    }
+}
+
+/// The pages of `files`, failing if two of them would get the same name.
+fn pages(args: &Args, files: &[PathBuf]) -> Result<Vec<devdocs::Page>, String> {
+    let mut pages: Vec<devdocs::Page> = Vec::with_capacity(files.len());
+    for file in files {
+        let page = page(args, file);
+        if let Some(other) = pages.iter().position(|other| other.path == page.path) {
+            return Err(format!(
+                "{} and {} would both be written to the page '{}'; rename one of them",
+                files[other].display(),
+                file.display(),
+                page.path
+            ));
+        }
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+/// The tags of the entries of `files`, with file names relative to the
+/// tags file `output`.
+fn file_tags(
+    args: &Args,
+    files: &[PathBuf],
+    output: &std::path::Path,
+) -> Result<Vec<tags::Tag>, String> {
+    let dir = output.parent().unwrap_or(std::path::Path::new(""));
+    let mut all = vec![];
+    for file in files {
+        let src = fs::read_to_string(file)
+            .map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+        let (_, entries) = load_entries(&args.for_file(file));
+        all.extend(tags::file_tags(
+            &summary::relative_link(dir, file),
+            &src,
+            &entries,
+        ));
+    }
+    Ok(all)
+}
+
+/// The client-side search index of the functions of `files`, each linked to
+/// the page given by `page_url` for its category.
+fn search_index(
+    args: &Args,
+    files: &[PathBuf],
+    page_url: &str,
+) -> Result<lunr::SearchIndex, String> {
+    let mut documents = vec![];
+    for file in files {
+        if !file.is_file() {
+            return Err(format!("Error reading {}: not a file", file.display()));
+        }
+        let file_args = args.for_file(file);
+        let page = lunr::page_url(page_url, &file_args.category);
+        let (_, entries) = load_entries(&file_args);
+        documents.extend(
+            entries
+                .iter()
+                .map(|entry| lunr::IndexDocument::from_entry(entry, &args.anchor_prefix, &page)),
+        );
+    }
+    Ok(lunr::SearchIndex::new(documents))
+}
+
+/// `files` as Hugo sections, each function rendered on its own like in the
+/// default command, with the references between them linked.
+fn hugo_sections(args: &Args, files: &[PathBuf]) -> Result<Vec<hugo::Section>, String> {
+    let inputs: Vec<Args> = files.iter().map(|file| args.for_file(file)).collect();
+    for (i, file_args) in inputs.iter().enumerate() {
+        if inputs[..i].iter().any(|a| a.category == file_args.category) {
+            return Err(format!(
+                "two inputs have the category {}; use a category per input",
+                file_args.category
+            ));
+        }
+    }
+    let (loaded, targets) = load_inputs(args, &inputs);
+    let mut sections: Vec<hugo::Section> = Vec::with_capacity(files.len());
+    for (file_args, (description, mut entries)) in inputs.into_iter().zip(loaded) {
+        warn_unresolved(targets.link_mentions(&mut entries, ""));
+        let (_, intro) = split_intro(&description);
+        let code_langs = file_args.code_langs();
+        let pages = entries
+            .iter()
+            .map(|entry| {
+                let mut markdown = String::new();
+                write_entry_section(entry, &file_args, &code_langs, &mut markdown);
+                let markdown = code_langs.remap(&markdown).into_owned();
+                hugo::Page {
+                    name: entry.name.clone(),
+                    title: entry.get_ident_title().1,
+                    anchor: entry.anchor(&file_args.anchor_prefix),
+                    markdown: match file_args.wrap {
+                        Wrap::Width(width) => format::wrap_markdown(&markdown, width),
+                        Wrap::None => markdown,
+                    },
+                }
+            })
+            .collect();
+        sections.push(hugo::Section {
+            title: summary::document_title(&description)
+                .unwrap_or_else(|| file_args.category.clone()),
+            category: file_args.category,
+            intro,
+            pages,
+        });
+    }
+    Ok(sections)
+}
+
+/// Time the phases of processing `file`, an options JSON file or a Nix file.
+fn bench_file(
+    args: &Args,
+    file: &std::path::Path,
+    iterations: usize,
+    render: OptionsRenderArgs,
+) -> Result<bench::Bench, String> {
+    let src =
+        fs::read_to_string(file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+    let mut report = bench::Bench::new(&file.display().to_string(), src.len(), iterations);
+    if file.extension().is_some_and(|ext| ext == "json") {
+        let render = render.with_config(args)?;
+        let title = render.title();
+        let preamble = render.preamble.clone();
+        let render_opts = render.render_options(args);
+        let parsed = report.phase(
+            "parse",
+            |parsed: &Result<options::OptionsMap, String>| parsed.as_ref().ok().map(|o| o.len()),
+            || options::parse_options_json(&src),
+        )?;
+        report.phase(
+            "render",
+            |_| Some(parsed.len()),
+            || options::render_options_document(&parsed, &title, preamble.as_deref(), &render_opts),
+        );
+    } else {
+        let file_args = args.for_file(file);
+        let nix = report
+            .phase("parse", |_| None, || rnix::Root::parse(&src))
+            .ok()
+            .map_err(|e| format!("Error parsing {}: {:?}", file.display(), e))?;
+        let dir = file.parent().unwrap_or(std::path::Path::new(""));
+        let entries = report.phase(
+            "collect",
+            |entries: &Vec<ManualEntry>| Some(entries.len()),
+            || {
+                collect_entries_in(
+                    nix.clone(),
+                    Some(dir),
+                    &file_args.prefix,
+                    &file_args.category,
+                    &Default::default(),
+                    &None,
+                    &mut vec![],
+                )
+            },
+        );
+        let description = retrieve_description(
+            &nix,
+            &file_args.description,
+            &file_args.category,
+            &mut vec![],
+        );
+        report.phase(
+            "render",
+            |_| Some(entries.len()),
+            || render_markdown(description.clone(), &entries, &file_args),
+        );
+    }
+    Ok(report)
+}
+
+fn render_markdown(description: String, entries: &[ManualEntry], args: &Args) -> String {
+    let headings = headings(args);
+    let (description, groups) = match args.group_by {
+        Some(groups::GroupBy::Kind) => {
+            let groups = groups::kind_groups(entries, &args.category, &headings);
+            (description, groups)
+        }
+        Some(groups::GroupBy::Tag) => {
+            let groups =
+                groups::tag_groups(entries, &args.category, &args.anchor_prefix, &headings);
+            (description, groups)
+        }
+        None => groups::split_groups(&description, |name| {
+            entries
+                .iter()
+                .find(|entry| entry.name == name || entry.get_ident_title().1 == name)
+                .map(|entry| entry.name.clone())
+        }),
+    };
+    let reference_links = args.link_style == LinkStyle::Reference;
+    let mut output = if reference_links {
+        let label = format!("sec-functions-library-{}", args.category);
+        format::reference_links(&description, &label).into_owned() + "\n"
+    } else {
+        description + "\n"
+    };
+    let html = args.html_policy();
+    write_stability_table(
+        entries,
+        &args.category,
+        &args.anchor_prefix,
+        html.as_ref(),
+        &mut output,
+    );
+    let code_langs = args.code_langs();
+    if groups.is_empty() {
+        for entry in entries {
+            write_entry_section(entry, args, &code_langs, &mut output);
+        }
+    } else {
+        write_groups(&groups, entries, args, &headings, &code_langs, &mut output);
+    }
+    if let Cow::Owned(remapped) = code_langs.remap(&output) {
+        output = remapped;
+    }
+    let output = match args.wrap {
+        Wrap::Width(width) => format::wrap_markdown(&output, width),
+        Wrap::None => output,
+    };
+    match admonition::to_dialect(&output, args.markdown_dialect) {
+        Cow::Owned(converted) => converted,
+        Cow::Borrowed(_) => output,
+    }
+}
+
+/// The translations of generated headings into `--lang`, from the
+/// configuration file.
+fn headings(args: &Args) -> locale::Headings {
+    let translations = match &args.config {
+        Some(path) => {
+            config::Config::load(path)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                })
+                .headings
+        }
+        None => Default::default(),
+    };
+    locale::Headings::for_lang(&translations, args.lang.as_deref())
+}
+
+/// Write `entries` below the headings of their groups, one level lower than
+/// usual. Entries in no group follow under a heading of their own; entries
+/// in several groups are written in the first, with a warning.
+fn write_groups(
+    groups: &[groups::Group],
+    entries: &[ManualEntry],
+    args: &Args,
+    headings: &locale::Headings,
+    code_langs: &CodeLangs,
+    output: &mut String,
+) {
+    let write_entry = |entry: &ManualEntry, output: &mut String| {
+        let mut section = String::new();
+        write_entry_section(entry, args, code_langs, &mut section);
+        output.push_str(&format::shift_headings(&section, 1));
+    };
+    for group in groups {
+        output.push_str(&format!("## {}\n\n", group.heading));
+        if !group.intro.is_empty() {
+            output.push_str(&format!("{}\n\n", group.intro));
+        }
+        for name in &group.names {
+            if let Some(first) = groups
+                .iter()
+                .take_while(|other| !std::ptr::eq(*other, group))
+                .find(|other| other.names.contains(name))
+            {
+                eprintln!(
+                    "warning: {}: `{}` is in the groups '{}' and '{}'; written in the first",
+                    args.category, name, first.heading, group.heading
+                );
+                continue;
+            }
+            if let Some(entry) = entries.iter().find(|entry| &entry.name == name) {
+                write_entry(entry, output);
+            }
+        }
+    }
+    let ungrouped: Vec<&ManualEntry> = entries
+        .iter()
+        .filter(|entry| !groups.iter().any(|group| group.names.contains(&entry.name)))
+        .collect();
+    if !ungrouped.is_empty() {
+        output.push_str(&format!(
+            "## {} {{#sec-functions-library-{}-other}}\n\n",
+            headings.get("Other functions"),
+            args.category
+        ));
+        for entry in ungrouped {
+            write_entry(entry, output);
+        }
+    }
+}
+
+/// Write the section of an entry, with its links in the selected style.
+fn write_entry_section(
+    entry: &ManualEntry,
+    args: &Args,
+    code_langs: &CodeLangs,
+    output: &mut String,
+) {
+    let start = output.len();
+    entry.write_section_with(
+        &args.anchor_prefix,
+        code_langs,
+        args.example_style,
+        args.html_policy().as_ref(),
+        output,
+    );
+    if args.link_style == LinkStyle::Reference {
+        let label = entry.anchor(&args.anchor_prefix);
+        if let Cow::Owned(section) = format::reference_links(&output[start..], &label) {
+            output.truncate(start);
+            output.push_str(&section);
+        }
+    }
+}
+
+/// Render the entry of `args.file` named by its attribute path or title.
+fn render_entry(args: &Args, name: &str, format: EntryFormat) -> Result<String, String> {
+    let (_, entries) = load_entries(args);
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.name == name || entry.get_ident_title().1 == name)
+    else {
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        return Err(format!(
+            "no documented entry '{}' in {} (documented: {})",
+            name,
+            args.file.as_ref().expect("--file is required").display(),
+            names.join(", ")
+        ));
+    };
+
+    match format {
+        EntryFormat::Json if args.json_version >= 2 => {
+            serde_json::to_string(&structured_entries(args, std::slice::from_ref(entry))[0])
+                .map_err(|e| format!("Problem converting entry to JSON: {}", e))
+        }
+        EntryFormat::Json => serde_json::to_string(&JsonEntry::new(entry, args))
+            .map_err(|e| format!("Problem converting entry to JSON: {}", e)),
+        EntryFormat::Markdown => {
+            let code_langs = args.code_langs();
+            let mut output = String::new();
+            write_entry_section(entry, args, &code_langs, &mut output);
+            if let Cow::Owned(remapped) = code_langs.remap(&output) {
+                output = remapped;
+            }
+            Ok(match args.wrap {
+                Wrap::Width(width) => format::wrap_markdown(&output, width),
+                Wrap::None => output,
+            })
+        }
+    }
+}
+
+/// Id of the page rendered by the default command: the category, or the
+/// name of the file or directory rendered.
+fn page_id(args: &Args) -> String {
+    if !args.category.is_empty() {
+        return args.category.clone();
+    }
+    args.file
+        .as_deref()
+        .and_then(|file| file.file_stem())
+        .or_else(|| args.dir.as_deref().and_then(|dir| dir.file_name()))
+        .map_or_else(
+            || "index".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+}
+
+fn main_with_args(args: &Args) -> String {
+    render_inputs(args, std::slice::from_ref(args))
+}
+
+/// Render several inputs as one output: their sections one after the other,
+/// or all their entries in one JSON document or bulk request. `args` selects
+/// the output format and each of `inputs` names a file and its category.
+fn render_inputs(args: &Args, inputs: &[Args]) -> String {
+    let (loaded, targets) = load_inputs(args, inputs);
+    render_loaded(args, inputs, &loaded, &targets)
+}
+
+/// Load the descriptions and entries of `inputs`, rendered as one page, with
+/// their `@see` references linked, and the functions the mentions in their
+/// docs can link to.
+fn load_inputs(args: &Args, inputs: &[Args]) -> (Vec<(String, Vec<ManualEntry>)>, see::Targets) {
+    link_inputs(args, inputs.iter().map(load_entries).collect())
+}
+
+/// Link the `@see` references of the `loaded` inputs of [`load_inputs`].
+fn link_inputs(
+    args: &Args,
+    mut loaded: Vec<(String, Vec<ManualEntry>)>,
+) -> (Vec<(String, Vec<ManualEntry>)>, see::Targets) {
+    warn_unresolved(inherit::inherit_docs(
+        loaded.iter_mut().flat_map(|(_, entries)| entries),
+    ));
+    let mut targets = see_targets(args);
+    for (_, entries) in &loaded {
+        targets.add_entries(entries, &args.anchor_prefix, "");
+    }
+    for (_, entries) in &mut loaded {
+        warn_unresolved(targets.link_see_also(entries, ""));
+    }
+    (loaded, targets)
+}
+
+/// The functions references can link to besides those of the run: those of
+/// `--see-index`.
+fn see_targets(args: &Args) -> see::Targets {
+    let mut targets = see::Targets::default();
+    if let Some(path) = &args.see_index {
+        targets.read_index(path).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        });
+    }
+    targets
+}
+
+fn warn_unresolved(unresolved: Vec<(String, String)>) {
+    for (title, reference) in unresolved {
+        eprintln!(
+            "warning: {}: {} matches no documented function",
+            title, reference
+        );
+    }
+}
+
+/// Render the `loaded` descriptions and entries of `inputs` like
+/// [`render_inputs`]. Only markdown has the mentions of functions in the docs
+/// linked to `targets`; JSON keeps the docs as written.
+fn render_loaded(
+    args: &Args,
+    inputs: &[Args],
+    loaded: &[(String, Vec<ManualEntry>)],
+    targets: &see::Targets,
+) -> String {
+    if let Some(index) = &args.es_bulk {
+        let mut output = Vec::new();
+        for entry in loaded.iter().flat_map(|(_, entries)| entries) {
+            let doc = search::SearchDocument::from_entry(entry, &args.anchor_prefix);
+            search::write_bulk_document(&mut output, index, &doc)
+                .expect("Problem converting entries to JSON");
+        }
+        String::from_utf8(output).expect("bulk output is valid UTF-8")
+    } else if args.json_output && args.json_version >= 2 {
+        serde_json::to_string(&JsonFormat {
+            version: args.json_version,
+            entries: loaded
+                .iter()
+                .zip(inputs)
+                .flat_map(|((_, entries), input)| structured_entries(input, entries))
+                .collect(),
+        })
+        .expect("Problem converting entries to JSON")
+    } else if args.json_output {
+        serde_json::to_string(&JsonFormat {
+            version: args.json_version,
+            entries: loaded
+                .iter()
+                .zip(inputs)
+                .flat_map(|((_, entries), input)| entries.iter().map(|e| JsonEntry::new(e, input)))
+                .collect(),
+        })
+        .expect("Problem converting entries to JSON")
+    } else {
+        loaded
+            .iter()
+            .zip(inputs)
+            .map(|((description, entries), input)| {
+                let mut entries = entries.clone();
+                warn_unresolved(targets.link_mentions(&mut entries, ""));
+                render_markdown(description.clone(), &entries, input)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Write the entries of `inputs` as NDJSON, one entry per line, each file's
+/// entries at once. Their references to other files are resolved like in a
+/// single document, so all files are read first.
+fn stream_entries<W: Write>(args: &Args, inputs: &[Args], writer: &mut W) -> io::Result<()> {
+    let (loaded, _) = load_inputs(args, inputs);
+    for ((_, entries), input) in loaded.iter().zip(inputs) {
+        if input.json_version >= 2 {
+            for entry in structured_entries(input, entries) {
+                serde_json::to_writer(&mut *writer, &entry)?;
+                writer.write_all(b"\n")?;
+            }
+        } else {
+            for entry in entries {
+                serde_json::to_writer(&mut *writer, &JsonEntry::new(entry, input))?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Entries of `args.file` in version 2 of the JSON output, located in its
+/// source.
+fn structured_entries(args: &Args, entries: &[ManualEntry]) -> Vec<structured::Entry> {
+    let file = args.file.as_ref().expect("--file is required");
+    let src = fs::read_to_string(file).unwrap_or_default();
+    let mut structured = structured::entries(&file.to_string_lossy(), &src, entries);
+    if args.json_html {
+        for entry in &mut structured {
+            entry.description_html = Some(html::markdown_to_html(&entry.description, true));
+        }
+    }
+    structured
+}
+
+/// Stream the entries of `inputs` to stdout, exiting on errors.
+fn stream_or_exit(args: &Args, inputs: &[Args]) {
+    let stdout = io::stdout();
+    if let Err(e) = stream_entries(args, inputs, &mut stdout.lock()) {
+        eprintln!("Error writing output: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// The inputs selected with several `--file` flags or with `--dir`, each
+/// rendered like a subcommand input with its own category and titled by it.
+fn combined_inputs(args: &Args) -> Result<Vec<Args>, String> {
+    if !args.category.is_empty() || !args.description.is_empty() || args.description_file.is_some()
+    {
+        return Err(
+            "--category, --description and --description-file apply to a single file; with several inputs, each file's category is derived from its path"
+                .to_string(),
+        );
+    }
+    let mut files: Vec<(PathBuf, Option<String>)> =
+        args.files.iter().map(|file| (file.clone(), None)).collect();
+    if let Some(dir) = &args.dir {
+        files.extend(
+            dir_inputs(dir, &args.glob, &args.ignore_options())?
+                .into_iter()
+                .map(|(file, category)| (file, Some(category))),
+        );
+    }
+    Ok(files
+        .into_iter()
+        .map(|(file, category)| {
+            let mut input = args.for_file(&file);
+            if let Some(category) = category {
+                input.category = category;
+            }
+            input.description = input.category.clone();
+            input
+        })
+        .collect())
+}
+
+/// The files below `dir` matching `glob` and not excluded by ignore files,
+/// sorted by path, with the category derived from each path.
+fn dir_inputs(
+    dir: &std::path::Path,
+    glob: &str,
+    ignore: &ignore::IgnoreOptions,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    let files = ignore::walk(dir, ignore)?;
+    let mut inputs = vec![];
+    for file in files {
+        let relative = file.strip_prefix(dir).unwrap_or(&file);
+        let relative: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        // Companion types files are read with the file they describe.
+        if !config::glob_match_file(glob, &relative.join("/"))
+            || relative
+                .last()
+                .is_some_and(|name| name.ends_with(".types.nix"))
+        {
+            continue;
+        }
+        let mut parts = relative;
+        if let Some(last) = parts.last_mut() {
+            *last = last.strip_suffix(".nix").unwrap_or(last).to_string();
+        }
+        if parts.len() > 1 && parts.last().is_some_and(|p| p == "default") {
+            parts.pop();
+        }
+        inputs.push((file, parts.join(".")));
+    }
+    if inputs.is_empty() {
+        return Err(format!("no files in {} match '{}'", dir.display(), glob));
+    }
+    Ok(inputs)
+}
+
+/// The file-level doc comment of `file`, in the selected language, with
+/// HTML sanitized and headings shifted by `shift`.
+fn file_doc(args: &Args, file: &std::path::Path, shift: usize) -> Result<Option<String>, String> {
+    let src =
+        fs::read_to_string(file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+    let nix = rnix::Root::parse(&src)
+        .ok()
+        .map_err(|e| format!("failed to parse {}: {}", file.display(), e))?;
+    let mut warnings = vec![];
+    let doc = extract_file_doc(&nix, &mut warnings);
+    for warning in warnings {
+        eprintln!("warning: {}: {}", file.display(), warning);
+    }
+    Ok(doc.map(|d| {
+        let d = locale::select_language(&d, args.lang.as_deref()).into_owned();
+        let d = match args.html_policy() {
+            Some(policy) => sanitize_html(&d, &policy).into_owned(),
+            None => d,
+        };
+        if shift > 0 {
+            let (shifted, warnings) = shift_headings_with_warnings(&d, shift);
+            for warning in warnings {
+                eprintln!("warning: {}: {}", file.display(), warning);
+            }
+            shifted
+        } else {
+            d
+        }
+    }))
+}
+
+/// Render the file-level doc comments of `docs` for the `file-doc`
+/// command. A single file is rendered on its own: as a JSON object, or as
+/// its comment, if it has one. A `batch` of files, given as several files or
+/// as a directory, is rendered as a JSON array, or as a section per
+/// documented file headed by its path, even if it has one file or none.
+fn render_file_docs(
+    args: &Args,
+    docs: &[(PathBuf, Option<String>)],
+    batch: bool,
+    format: &str,
+) -> Option<String> {
+    if format == "json" {
+        let objects: Vec<serde_json::Value> = docs
+            .iter()
+            .map(|(file, doc)| {
+                serde_json::json!({
+                    "file": file.to_string_lossy(),
+                    "doc": doc
+                })
+            })
+            .collect();
+        let json = match objects.as_slice() {
+            [object] if !batch => serde_json::to_string_pretty(object),
+            _ => serde_json::to_string_pretty(&objects),
+        };
+        return Some(json.unwrap());
+    }
+    // "plain" and "markdown" currently print the same text
+    let d = match docs {
+        [(_, doc)] if !batch => doc.clone()?,
+        _ => {
+            let sections: Vec<String> = docs
+                .iter()
+                .filter_map(|(file, doc)| {
+                    let doc = doc.as_ref()?;
+                    Some(format!("# `{}`\n\n{}\n", file.display(), doc))
+                })
+                .collect();
+            if sections.is_empty() {
+                return None;
+            }
+            sections.join("\n")
+        }
+    };
+    let code_langs = args.code_langs();
+    let d = route_raw_blocks(&d, RAW_FORMATS);
+    let d = match code_langs.remap(&d) {
+        Cow::Owned(remapped) => Cow::Owned(remapped),
+        Cow::Borrowed(_) => d,
+    };
+    let d = match args.wrap {
+        Wrap::Width(width) => Cow::Owned(format::wrap_markdown(&d, width)),
+        Wrap::None => d,
+    };
+    Some(d.trim_end().to_string())
+}
+
+/// The reproducibility metadata of a document generated from `sources`,
+/// unless `--no-metadata` is given.
+fn metadata(args: &Args, sources: &[manifest::Source]) -> Option<String> {
+    (!args.no_metadata)
+        .then(|| provenance::Provenance::new(args.command_line.clone(), sources.to_vec()).text())
+}
+
+/// A document of `write_documents`, rendered but not yet written.
+struct RenderedDocument {
+    args: Args,
+    path: String,
+    markdown: String,
+    sources: Vec<manifest::Source>,
+    entries: Vec<ManualEntry>,
+}
+
+/// Load the inputs of `write_documents` in reading order, each with the
+/// description heading its document, ordered by its landing page if it has
+/// one. The problems of an input are handed to `fail`, which either stops
+/// the loading with an error or skips the input.
+fn load_documents(
+    args: &Args,
+    files: &[PathBuf],
+    site: &SiteArgs,
+    mut fail: impl FnMut(String) -> Result<(), String>,
+) -> Result<Vec<(RenderedDocument, String)>, String> {
+    let files = match reading_order(args, files) {
+        Ok(files) => files,
+        Err(e) => {
+            fail(e)?;
+            files.to_vec()
+        }
+    };
+    let mut loaded: Vec<(RenderedDocument, String)> = Vec::with_capacity(files.len());
+    for file in files {
+        let file_args = args.for_file(&file);
+        let path = format!("{}.md", file_args.category);
+        if let Some((other, _)) = loaded.iter().find(|(page, _)| page.path == path) {
+            fail(format!(
+                "{} and {} would both be written to {}; use a category per input",
+                other.sources[0].path,
+                file.display(),
+                path
+            ))?;
+            continue;
+        }
+        match load_document(file_args, &file, path, site) {
+            Ok(document) => loaded.push(document),
+            Err(e) => fail(e)?,
+        }
+    }
+    Ok(loaded)
+}
+
+/// Load the input `file` of `load_documents`, written to `path`.
+fn load_document(
+    file_args: Args,
+    file: &std::path::Path,
+    path: String,
+    site: &SiteArgs,
+) -> Result<(RenderedDocument, String), String> {
+    let mut sources = vec![manifest::Source::read(file)?];
+    let (mut description, mut entries) = try_load_entries(&file_args)?;
+    let landing_path = site.landing_dir.as_ref().map(|dir| dir.join(&path));
+    if let Some(landing_path) = landing_path.filter(|p| p.exists()) {
+        let text = fs::read_to_string(&landing_path)
+            .map_err(|e| format!("Error reading {}: {}", landing_path.display(), e))?;
+        let landing = landing::parse(&text)
+            .and_then(|landing| {
+                entries = landing::order_entries(&landing, std::mem::take(&mut entries))?;
+                Ok(landing)
+            })
+            .map_err(|e| format!("{}: {}", landing_path.display(), e))?;
+        description = landing::render_header(
+            &landing,
+            &file_args.description,
+            &file_args.category,
+            &entries,
+            &file_args.anchor_prefix,
+        );
+        sources.push(manifest::Source::read(&landing_path)?);
+    }
+    Ok((
+        RenderedDocument {
+            args: file_args,
+            path,
+            markdown: String::new(),
+            sources,
+            entries,
+        },
+        description,
+    ))
+}
+
+/// Render each input to `<output-dir>/<category>.md` and write the manifest
+/// describing them. With `prune`, documents of the previous manifest that
+/// were not written again are deleted and returned.
+fn write_documents(
+    args: &Args,
+    files: &[PathBuf],
+    output_dir: &std::path::Path,
+    site: &SiteArgs,
+) -> Result<(manifest::Manifest, Vec<PathBuf>), String> {
+    let redirects = site.redirects || site.redirect_stubs;
+    let manifest_path = output_dir.join(manifest::MANIFEST_FILE);
+    let previous = if (site.prune || redirects) && manifest_path.exists() {
+        Some(manifest::Manifest::load(&manifest_path)?)
+    } else {
+        None
+    };
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Error creating {}: {}", output_dir.display(), e))?;
+
+    // Pages are rendered first, so each can link to the next one, after all
+    // are loaded, so references to functions can link to any of them.
+    let mut loaded = load_documents(args, files, site, Err)?;
+    warn_unresolved(inherit::inherit_docs(
+        loaded.iter_mut().flat_map(|(page, _)| &mut page.entries),
+    ));
+    let mut targets = see_targets(args);
+    for (page, _) in &loaded {
+        targets.add_entries(&page.entries, &args.anchor_prefix, &page.path);
+    }
+    for (page, _) in &mut loaded {
+        warn_unresolved(targets.link_see_also(&mut page.entries, &page.path));
+    }
+    let pages: Vec<RenderedDocument> = loaded
+        .into_iter()
+        .map(|(mut page, description)| {
+            let mut entries = page.entries.clone();
+            warn_unresolved(targets.link_mentions(&mut entries, &page.path));
+            page.markdown = render_markdown(description, &entries, &page.args);
+            page
+        })
+        .collect();
+
+    let navigation = site.navigation.then(|| {
+        nav::Navigation::new(
+            pages
+                .iter()
+                .map(|page| {
+                    (
+                        page.args.category.clone(),
+                        summary::document_title(&page.markdown)
+                            .unwrap_or_else(|| page.args.category.clone()),
+                        page.path.clone(),
+                    )
+                })
+                .collect(),
+        )
+    });
+    let mut documents: Vec<manifest::Document> = Vec::with_capacity(pages.len());
+    let mut chapters: Vec<summary::Chapter> = Vec::with_capacity(pages.len());
+    for (i, page) in pages.into_iter().enumerate() {
+        let RenderedDocument {
+            args: file_args,
+            path,
+            mut markdown,
+            sources,
+            entries,
+        } = page;
+        if let Some(links) = navigation.as_ref().and_then(|nav| nav.markdown_links(i)) {
+            markdown.push_str(&links);
+        }
+        if let Some(fields) = &args.frontmatter {
+            markdown = frontmatter::with_frontmatter(&markdown, &file_args.category, fields);
+        }
+        if let Some(metadata) = metadata(args, &sources) {
+            markdown.push('\n');
+            markdown.push_str(&provenance::html_comment(&metadata));
+        }
+        let target = output_dir.join(&path);
+        fs::write(&target, &markdown)
+            .map_err(|e| format!("Error writing {}: {}", target.display(), e))?;
+        if let Some(summary_path) = &site.summary {
+            let summary_dir = summary_path.parent().unwrap_or(std::path::Path::new(""));
+            chapters.push(summary::Chapter {
+                title: summary::document_title(&markdown)
+                    .unwrap_or_else(|| file_args.category.clone()),
+                category: file_args.category.clone(),
+                link: summary::relative_link(summary_dir, &target),
+            });
+        }
+        documents.push(manifest::Document::new(
+            path,
+            file_args.category,
+            &markdown,
+            sources,
+            &entries,
+            &args.anchor_prefix,
+        ));
+    }
+
+    let manifest = manifest::Manifest::new(documents);
+    let removed = match &previous {
+        Some(previous) if site.prune => manifest::prune(output_dir, previous, &manifest)?,
+        _ => vec![],
+    };
+    if redirects {
+        let redirects_path = output_dir.join(manifest::REDIRECTS_FILE);
+        let mut redirects = manifest::load_redirects(&redirects_path)?;
+        manifest::update_redirects(&mut redirects, previous.as_ref(), &manifest);
+        manifest::write_redirects(&redirects_path, &redirects)?;
+        if site.redirect_stubs {
+            manifest::write_redirect_stubs(output_dir, &redirects, &manifest)?;
+        }
+    }
+    if let Some(summary_path) = &site.summary {
+        summary::write_summary(summary_path, &chapters)?;
+    }
+    if let Some(navigation) = &navigation {
+        navigation.write(&output_dir.join(nav::NAV_FILE))?;
+    }
+    manifest.write(&manifest_path)?;
+    Ok((manifest, removed))
+}
+
+/// A document written by the watch command, with the files it was made
+/// from when it was last built.
+struct WatchedDocument {
+    input: PathBuf,
+    target: PathBuf,
+    dependencies: Vec<cache::Dependency>,
+}
+
+/// Render `input` to `target` like the default command, returning the files
+/// the document is made from.
+fn build_document(
+    args: &Args,
+    input: &std::path::Path,
+    target: &std::path::Path,
+) -> Result<Vec<cache::Dependency>, String> {
+    let file_args = args.for_file(input);
+    let sources = vec![manifest::Source::read(input)?];
+    let record = load_record(&file_args)?;
+    for warning in &record.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let mut dependencies = record.dependencies;
+    dependencies.extend(args.see_index.as_deref().map(cache::Dependency::of));
+
+    let (loaded, targets) = link_inputs(args, vec![(record.description, record.entries)]);
+    let mut markdown = render_loaded(
+        &file_args,
+        std::slice::from_ref(&file_args),
+        &loaded,
+        &targets,
+    );
+    if let Some(fields) = &args.frontmatter {
+        markdown = frontmatter::with_frontmatter(&markdown, &file_args.category, fields);
+    }
+    if let Some(metadata) = metadata(args, &sources) {
+        markdown.push('\n');
+        markdown.push_str(&provenance::html_comment(&metadata));
+    }
+    fs::write(target, &markdown)
+        .map_err(|e| format!("Error writing {}: {}", target.display(), e))?;
+    Ok(dependencies)
+}
+
+/// Build the document of each input into `output_dir`, to be kept up to
+/// date with [`rebuild_changed`].
+fn watch_documents(
+    args: &Args,
+    files: &[PathBuf],
+    output_dir: &std::path::Path,
+) -> Result<Vec<WatchedDocument>, String> {
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Error creating {}: {}", output_dir.display(), e))?;
+    let mut documents: Vec<WatchedDocument> = Vec::with_capacity(files.len());
+    for file in files {
+        let target = output_dir.join(format!("{}.md", args.for_file(file).category));
+        if let Some(other) = documents.iter().find(|document| document.target == target) {
+            return Err(format!(
+                "{} and {} would both be written to {}; use a category per input",
+                other.input.display(),
+                file.display(),
+                target.display()
+            ));
+        }
+        let dependencies = build_document(args, file, &target)?;
+        documents.push(WatchedDocument {
+            input: file.clone(),
+            target,
+            dependencies,
+        });
+    }
+    Ok(documents)
+}
+
+/// Rebuild the documents made from files that changed since they were last
+/// built, returning a line for each saying which files changed.
+fn rebuild_changed(args: &Args, documents: &mut [WatchedDocument]) -> Vec<String> {
+    // Each file is read once, however many documents are made from it.
+    let mut current: BTreeMap<PathBuf, cache::Dependency> = BTreeMap::new();
+    for dependency in documents.iter().flat_map(|document| &document.dependencies) {
+        current
+            .entry(dependency.path.clone())
+            .or_insert_with(|| cache::Dependency::of(&dependency.path));
+    }
+    let mut report = vec![];
+    for document in documents {
+        let changes: Vec<String> = document
+            .dependencies
+            .iter()
+            .filter_map(|old| {
+                let new = &current[&old.path];
+                let change = match (&old.sha256, &new.sha256) {
+                    (None, Some(_)) => "added",
+                    (Some(_), None) => "removed",
+                    (old, new) if old != new => "changed",
+                    _ => return None,
+                };
+                Some(format!("{} {}", old.path.display(), change))
+            })
+            .collect();
+        if changes.is_empty() {
+            continue;
+        }
+        match build_document(args, &document.input, &document.target) {
+            Ok(dependencies) => {
+                document.dependencies = dependencies;
+                report.push(format!(
+                    "Rebuilt {} ({})",
+                    document.target.display(),
+                    changes.join(", ")
+                ));
+            }
+            Err(e) => {
+                // Tried again once the files change again.
+                for dependency in &mut document.dependencies {
+                    *dependency = current[&dependency.path].clone();
+                }
+                report.push(format!(
+                    "Error: {} ({}): {}",
+                    document.target.display(),
+                    changes.join(", "),
+                    e
+                ));
+            }
+        }
+    }
+    report
+}
+
+/// The problems the checks of the configuration find in `files`, each
+/// starting with the file it is about.
+fn precommit(args: &Args, files: &[PathBuf]) -> Result<Vec<String>, String> {
+    let checks = match &args.config {
+        Some(path) => config::Config::load(path)?.checks,
+        None => Default::default(),
+    };
+    if let Some(min) = checks.min_coverage
+        && !(0.0..=1.0).contains(&min)
+    {
+        return Err(format!("min_coverage must be between 0 and 1, not {}", min));
+    }
+    let mut problems = vec![];
+    for file in files {
+        let Some(kind) = checks.kind_of(&file.to_string_lossy()) else {
+            continue;
+        };
+        if !file.is_file() {
+            continue;
+        }
+        let options = match kind {
+            config::CheckKind::File => {
+                problems.extend(check_nix_file(args, &checks, file));
+                continue;
+            }
+            config::CheckKind::Module => static_options::options_json(std::slice::from_ref(file))
+                .and_then(|json| options::parse_options_json(&json)),
+            config::CheckKind::Options => options::parse_options_file(file),
+        };
+        let options = match options {
+            Ok(options) => options,
+            Err(e) => {
+                problems.push(format!("{}: {}", file.display(), e));
+                continue;
+            }
+        };
+        let mut names: Vec<&String> = options.keys().collect();
+        names.sort();
+        for name in names {
+            for warning in lint::lint_option(&options[name]) {
+                problems.push(format!("{}: {}: {}", file.display(), name, warning));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// The problems of the Nix file `file` for [`precommit`].
+fn check_nix_file(
+    args: &Args,
+    checks: &config::ChecksSection,
+    file: &std::path::Path,
+) -> Vec<String> {
+    let record = match load_record(&args.for_file(file)) {
+        Ok(record) => record,
+        Err(e) => return vec![format!("{}: {}", file.display(), e)],
+    };
+    let mut problems = vec![];
+    if checks.allow_warnings {
+        for warning in &record.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    } else {
+        problems.extend(record.warnings);
+    }
+    let counts = metrics::Counts::new(&record.entries, &record.public_bindings);
+    if let Some(min) = checks.min_coverage
+        && counts.coverage < min
+    {
+        problems.push(format!(
+            "{}: {} of {} bindings documented ({:.0}%), below the {:.0}% required",
+            file.display(),
+            counts.documented,
+            counts.bindings,
+            counts.coverage * 100.0,
+            min * 100.0
+        ));
+    }
+    problems
+}
+
+/// `files` in the reading order set by the `[navigation]` section of the
+/// configuration file, warning about categories it names that no input has.
+fn reading_order(args: &Args, files: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let order = match &args.config {
+        Some(path) => config::Config::load(path)?.navigation.order,
+        None => vec![],
+    };
+    let (files, unknown) =
+        nav::reading_order(files.to_vec(), |file| args.for_file(file).category, &order);
+    for name in unknown {
+        eprintln!(
+            "warning: navigation order names '{}', the category of no input",
+            name
+        );
+    }
+    Ok(files)
+}
+
+/// Name of a flag value as given on the command line.
+fn value_name<T: clap::ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Resolve what `write_documents` would do with the same arguments, loading
+/// the inputs like it does, but collecting the problems that would make it
+/// fail instead of stopping at the first.
+fn build_plan(
+    args: &Args,
+    files: &[PathBuf],
+    output_dir: &std::path::Path,
+    site: &SiteArgs,
+) -> plan::Plan {
+    let mut errors = vec![];
+    let manifest_path = output_dir.join(manifest::MANIFEST_FILE);
+    let previous = if manifest_path.exists() {
+        manifest::Manifest::load(&manifest_path)
+            .map_err(|e| errors.push(e))
+            .ok()
+    } else {
+        None
+    };
+
+    let loaded = load_documents(args, files, site, |e| {
+        errors.push(e);
+        Ok(())
+    })
+    .unwrap_or_default();
+    let mut inputs: Vec<plan::Input> = Vec::with_capacity(loaded.len());
+    for (document, _) in loaded {
+        let (public, hidden) = partition_bindings(&document.args).unwrap_or_else(|e| {
+            errors.push(e);
+            Default::default()
+        });
+        let entries = document.entries;
+        let undocumented = public
+            .into_iter()
+            .filter(|name| !entries.iter().any(|e| e.name == *name))
+            .collect();
+        let mut sources = document.sources.into_iter().map(|source| source.path);
+        inputs.push(plan::Input {
+            file: sources.next().unwrap_or_default(),
+            category: document.args.category,
+            output: document.path,
+            format: "markdown".to_string(),
+            landing_page: sources.next(),
+            entries: entries.iter().map(|e| e.get_ident_title().1).collect(),
+            undocumented,
+            hidden,
+        });
+    }
+
+    let prune = match &previous {
+        Some(previous) if site.prune => previous
+            .documents
+            .iter()
+            .filter(|d| !inputs.iter().any(|i| i.output == d.path))
+            .map(|d| d.path.clone())
+            .collect(),
+        _ => vec![],
+    };
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "(none)".to_string());
+    let settings = vec![
+        plan::Setting::new("prefix", args.prefix.clone()),
+        plan::Setting::new("anchor prefix", args.anchor_prefix.clone()),
+        plan::Setting::new(
+            "description",
+            optional(Some(args.description.clone()).filter(|d| !d.is_empty())),
+        ),
+        plan::Setting::new(
+            "description file",
+            optional(
+                args.description_file
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+            ),
+        ),
+        plan::Setting::new(
+            "locations",
+            optional(args.locs.as_ref().map(|p| p.display().to_string())),
+        ),
+        plan::Setting::new(
+            "types",
+            optional(args.types.as_ref().map(|p| p.display().to_string())),
+        ),
+        plan::Setting::new(
+            "see index",
+            optional(args.see_index.as_ref().map(|p| p.display().to_string())),
+        ),
+        plan::Setting::new(
+            "export",
+            optional(args.export.as_ref().map(|e| e.join(","))),
+        ),
+        plan::Setting::new("eval fallback", optional(args.eval_fallback.clone())),
+        plan::Setting::new("legacy comments", args.legacy_comments.to_string()),
+        plan::Setting::new("inherit docs", args.inherit_docs.to_string()),
+        plan::Setting::new("usage snippets", args.usage_snippets.to_string()),
+        plan::Setting::new("group by", optional(args.group_by.as_ref().map(value_name))),
+        plan::Setting::new("lang", optional(args.lang.clone())),
+        plan::Setting::new(
+            "frontmatter",
+            optional(args.frontmatter.as_ref().map(|fields| {
+                ["title", "slug"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(
+                        fields
+                            .iter()
+                            .map(|(key, value)| format!("{}={}", key, value)),
+                    )
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })),
+        ),
+        plan::Setting::new("link style", value_name(&args.link_style)),
+        plan::Setting::new("example style", value_name(&args.example_style)),
+        plan::Setting::new(
+            "wrap",
+            match args.wrap {
+                Wrap::None => "none".to_string(),
+                Wrap::Width(width) => width.to_string(),
+            },
+        ),
+        plan::Setting::new("markdown dialect", value_name(&args.markdown_dialect)),
+        plan::Setting::new("code lang", args.code_lang.clone()),
+        plan::Setting::new(
+            "code lang map",
+            optional(
+                Some(
+                    args.code_lang_map
+                        .iter()
+                        .map(|(from, to)| format!("{}={}", from, to))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                )
+                .filter(|map| !map.is_empty()),
+            ),
+        ),
+        plan::Setting::new(
+            "sanitize html",
+            optional(args.sanitize_html.as_ref().map(value_name)),
+        ),
+        plan::Setting::new("metadata", (!args.no_metadata).to_string()),
+    ];
+
+    plan::Plan {
+        config: args.config.as_ref().map(|p| p.display().to_string()),
+        output_dir: output_dir.display().to_string(),
+        manifest: manifest_path.display().to_string(),
+        previous_manifest: previous.is_some(),
+        settings,
+        inputs,
+        prune,
+        redirects: (site.redirects || site.redirect_stubs).then(|| {
+            output_dir
+                .join(manifest::REDIRECTS_FILE)
+                .display()
+                .to_string()
+        }),
+        summary: site.summary.as_ref().map(|p| p.display().to_string()),
+        navigation: site
+            .navigation
+            .then(|| output_dir.join(nav::NAV_FILE).display().to_string()),
+        errors,
+    }
+}
+
+/// Print the lint warnings of an option.
+fn report_option_lints(name: &str, opt: &options::OptionDef) {
+    for warning in lint::lint_option(opt) {
+        eprintln!("warning: {}: {}", name, warning);
+    }
+}
+
+/// Hand the options of an options JSON file and of converted schemas that
+/// pass `filter` to `f`, the file's first.
+fn visit_option_inputs(
+    file: Option<&std::path::Path>,
+    schemas: &[schema::SchemaInput],
+    modules: &[PathBuf],
+    filter: &[options::OptionFilter],
+    mut f: impl FnMut(String, options::OptionDef),
+) -> Result<(), String> {
+    if let Some(file) = file {
+        options::stream_options_where(options::open_options_file(file)?, filter, &mut f)?;
+    }
+    if !schemas.is_empty() {
+        let json = schema::options_json(schemas)?;
+        options::stream_options_where(json.as_bytes(), filter, &mut f)?;
+    }
+    if !modules.is_empty() {
+        let json = static_options::options_json(modules)?;
+        options::stream_options_where(json.as_bytes(), filter, &mut f)?;
+    }
+    Ok(())
+}
+
+/// Print the lint warnings of all options, in name order.
+fn report_options_lints(options: &options::OptionsMap) {
+    let mut names: Vec<&String> = options.keys().collect();
+    names.sort();
+    for name in names {
+        report_option_lints(name, &options[name]);
+    }
+}
+
+/// Buffered writer for an output file, or stdout when no file is given.
+fn create_output_writer(output: Option<&std::path::Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match output {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    })
+}
+
+/// Exit with an error listing the structural problems in a generated document.
+fn validate_or_exit(doc: &str) {
+    let problems = validate::validate_commonmark(doc);
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("Error: invalid output: {}", problem);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let mut args = Args::parse();
+    args.command_line = std::env::args().collect();
+    // The program by its name, not the path it happened to be run from.
+    if let Some(program) = args.command_line.first_mut()
+        && let Some(name) = std::path::Path::new(program.as_str()).file_stem()
+    {
+        *program = name.to_string_lossy().into_owned();
+    }
+    if args.config.is_none() && std::path::Path::new(config::CONFIG_FILE).exists() {
+        args.config = Some(PathBuf::from(config::CONFIG_FILE));
+    }
+
+    match args.command.take() {
+        #[cfg(feature = "browse")]
+        Some(Command::Options {
+            action:
+                Some(OptionsCommand::Browse {
+                    file,
+                    schema,
+                    module,
+                    filter,
+                }),
+            ..
+        }) => {
+            use std::io::IsTerminal;
+            if !io::stdout().is_terminal() {
+                eprintln!("Error: options browse needs a terminal");
+                std::process::exit(1);
+            }
+            let mut all = vec![];
+            if let Err(e) =
+                visit_option_inputs(file.as_deref(), &schema, &module, &filter, |name, opt| {
+                    all.push((name, opt))
+                })
+            {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            if let Err(e) = browse::run(all) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Options {
+            action: None,
+            file,
+            schema,
+            module,
+            output,
+            render,
+            stream,
+            filter,
+            lint,
+        }) => {
+            let render = render.with_config(&args).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let title = render.title();
+            let preamble = render.preamble.clone();
+            let render_opts = render.render_options(&args);
+
+            if let Some(index) = &args.es_bulk {
+                let mut writer = create_output_writer(output.as_deref()).unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+                let mut result = Ok(());
+                let streamed =
+                    visit_option_inputs(file.as_deref(), &schema, &module, &filter, |name, opt| {
+                        if result.is_err() {
+                            return;
+                        }
+                        result = match args.es_shape {
+                            search::BulkShape::Docgen => {
+                                let doc = search::SearchDocument::from_option(
+                                    &name,
+                                    &opt,
+                                    &render_opts.anchor_prefix,
+                                );
+                                search::write_bulk_document(&mut writer, index, &doc)
+                            }
+                            search::BulkShape::NixosSearch => {
+                                let doc = search::NixosSearchOption::from_option(
+                                    &name,
+                                    &opt,
+                                    args.es_flake.as_ref(),
+                                );
+                                let id = format!("option-{}", name);
+                                search::write_bulk_source(&mut writer, index, &id, &doc)
+                            }
+                        };
+                    });
+                if let Err(e) = streamed.and_then(|()| {
+                    result
+                        .and_then(|()| writer.flush())
+                        .map_err(|e| format!("Error writing output: {}", e))
+                }) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+
+            if stream && let Some(file) = &file {
+                if lint {
+                    let reader = options::open_options_file(file).unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    });
+                    if let Err(e) = options::stream_options_where(reader, &filter, |name, opt| {
+                        report_option_lints(&name, &opt)
+                    }) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                if args.validate_output {
+                    eprintln!(
+                        "Error: --validate-output needs the whole document and cannot be used with --stream"
+                    );
+                    std::process::exit(1);
+                }
+                let reader = options::open_options_file(file).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+                let result = match output {
+                    Some(out_path) => fs::File::create(&out_path)
+                        .map_err(|e| format!("Error writing output: {}", e))
+                        .and_then(|f| {
+                            let mut writer = io::BufWriter::new(f);
+                            options::stream_options_document(
+                                reader,
+                                &mut writer,
+                                &title,
+                                preamble.as_deref(),
+                                &filter,
+                                &render_opts,
+                            )?;
+                            writer
+                                .flush()
+                                .map_err(|e| format!("Error writing output: {}", e))
+                        }),
+                    None => {
+                        let mut writer = io::BufWriter::new(io::stdout().lock());
+                        options::stream_options_document(
+                            reader,
+                            &mut writer,
+                            &title,
+                            preamble.as_deref(),
+                            &filter,
+                            &render_opts,
+                        )
+                        .and_then(|()| {
+                            writer
+                                .flush()
+                                .map_err(|e| format!("Error writing output: {}", e))
+                        })
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+
+            let mut parsed = options::OptionsMap::new();
+            let read =
+                visit_option_inputs(file.as_deref(), &schema, &module, &filter, |name, opt| {
+                    parsed.insert(name, opt);
+                });
+            if let Err(e) = read {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            if lint {
+                report_options_lints(&parsed);
+            }
+
+            let result = options::render_options_document(
+                &parsed,
+                &title,
+                preamble.as_deref(),
+                &render_opts,
+            );
+            if args.validate_output {
+                validate_or_exit(&result);
+            }
+
+            if let Some(out_path) = output {
+                fs::write(&out_path, &result).unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+            } else {
+                println!("{}", result);
+            }
+        }
+        Some(Command::Module {
+            flake,
+            output,
+            nix,
+            render,
+            lint,
+        }) => {
+            let parsed = flake::eval_options(&flake, &nix)
+                .and_then(|json| options::parse_options_json(&json))
+                .unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+            if lint {
+                report_options_lints(&parsed);
+            }
+            let render = render.with_config(&args).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let title = render.title();
+            let preamble = render.preamble.clone();
+            let result = options::render_options_document(
+                &parsed,
+                &title,
+                preamble.as_deref(),
+                &render.render_options(&args),
+            );
+            if args.validate_output {
+                validate_or_exit(&result);
+            }
+
+            if let Some(out_path) = output {
+                fs::write(&out_path, &result).unwrap_or_else(|e| {
                     eprintln!("Error writing output: {}", e);
                     std::process::exit(1);
                 });
@@ -473,52 +3209,625 @@ fn main() {
                 println!("{}", result);
             }
         }
+        Some(Command::Doctest { files, nix }) => {
+            let mut passed = 0;
+            let mut failed = 0;
+            for file in &files {
+                let (_, entries) = load_entries(&args.for_file(file));
+                for entry in &entries {
+                    let title = entry.get_ident_title().1;
+                    let description = entry.description.join("\n\n");
+                    for assertion in doctest::assertions(&description) {
+                        let failure = match doctest::evaluate(file, &assertion, &nix) {
+                            Ok(doctest::Outcome::Passed) => {
+                                passed += 1;
+                                continue;
+                            }
+                            Ok(doctest::Outcome::Failed(Some(actual))) => {
+                                format!("got {}", actual)
+                            }
+                            Ok(doctest::Outcome::Failed(None)) => "values differ".to_string(),
+                            Err(e) => e,
+                        };
+                        failed += 1;
+                        eprintln!(
+                            "FAIL {}: {} => {}\n  {}",
+                            title,
+                            assertion.expr.replace('\n', " "),
+                            assertion.expected,
+                            failure
+                        );
+                    }
+                }
+            }
+            eprintln!("{} passed, {} failed", passed, failed);
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Entry { file, name, format }) => {
+            match render_entry(&args.for_file(&file), &name, format) {
+                Ok(output) => println!("{}", output.trim_end()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Some(Command::FileDoc {
-            file,
+            mut files,
+            dir,
+            glob,
             format,
             shift_headings: shift_amount,
+        }) => {
+            let batch = dir.is_some() || files.len() > 1;
+            if let Some(dir) = dir {
+                match dir_inputs(&dir, &glob, &args.ignore_options()) {
+                    Ok(inputs) => files.extend(inputs.into_iter().map(|(file, _)| file)),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let docs: Vec<(PathBuf, Option<String>)> = files
+                .into_iter()
+                .map(|file| {
+                    let doc = file_doc(&args, &file, shift_amount).unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    });
+                    (file, doc)
+                })
+                .collect();
+            if let Some(d) = render_file_docs(&args, &docs, batch, &format) {
+                if format != "json" && args.validate_output {
+                    validate_or_exit(&d);
+                }
+                println!("{}", d);
+            }
+        }
+        Some(Command::Normalize {
+            file,
+            output,
+            fence_lang,
         }) => {
             let src = fs::read_to_string(&file).unwrap_or_else(|e| {
                 eprintln!("Error reading file: {}", e);
                 std::process::exit(1);
             });
-            let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+            let result = normalize::normalize_document(
+                &src,
+                &normalize::NormalizeOptions {
+                    default_fence_lang: fence_lang,
+                },
+            );
+
+            if let Some(out_path) = output {
+                fs::write(&out_path, &result).unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+            } else {
+                print!("{}", result);
+            }
+        }
+        Some(Command::Devdocs {
+            files,
+            output_dir,
+            name,
+        }) => {
+            let pages = pages(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            devdocs::write_bundle(&output_dir, &name, &pages).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Html {
+            files,
+            output_dir,
+            name,
+            base_url,
+        }) => {
+            let files = reading_order(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let pages = pages(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            html::write_site(&output_dir, &name, &pages, base_url.as_deref()).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Antora {
+            files,
+            output_dir,
+            name,
+            title,
+            component_version,
+        }) => {
+            let pages = pages(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            antora::write_component(
+                &output_dir,
+                &name,
+                &title,
+                component_version.as_deref(),
+                &pages,
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Hugo { files, output_dir }) => {
+            let sections = hugo_sections(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let fields = args.frontmatter.clone().unwrap_or_default();
+            hugo::write_content(&output_dir, &sections, &fields).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Man {
+            files,
+            output_dir,
+            manual,
+            combined,
+        }) => {
+            let entries: Vec<ManualEntry> = files
+                .iter()
+                .flat_map(|file| load_entries(&args.for_file(file)).1)
+                .collect();
+
+            let result = if combined {
+                man::write_combined(&output_dir, &args.prefix, &entries, &manual).map(|_| ())
+            } else {
+                man::write_bundle(&output_dir, &args.prefix, &entries, &manual).map(|_| ())
+            };
+            result.unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Tags {
+            files,
+            output,
+            etags,
+        }) => {
+            let output =
+                output.unwrap_or_else(|| PathBuf::from(if etags { "TAGS" } else { "tags" }));
+            let tags = file_tags(&args, &files, &output).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let contents = if etags {
+                tags::render_etags(&tags)
+            } else {
+                tags::render_ctags(&tags)
+            };
+            fs::write(&output, contents).unwrap_or_else(|e| {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Index {
+            files,
+            page_url,
+            output,
+        }) => {
+            let index = search_index(&args, &files, &page_url).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let result = serde_json::to_string(&index).expect("Problem converting index to JSON");
+            if let Some(out_path) = output {
+                fs::write(&out_path, &result).unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+            } else {
+                println!("{}", result);
+            }
+        }
+        Some(Command::Schema { entry, output }) => {
+            let schema = if entry {
+                output_schema::entry_schema(args.json_version)
+            } else {
+                output_schema::document_schema(args.json_version)
+            };
+            let result =
+                serde_json::to_string_pretty(&schema).expect("Problem converting schema to JSON");
+            if let Some(out_path) = output {
+                fs::write(&out_path, &result).unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+            } else {
+                println!("{}", result);
+            }
+        }
+        Some(Command::Manifest {
+            files,
+            output_dir,
+            site,
+        }) => {
+            let (_, removed) =
+                write_documents(&args, &files, &output_dir, &site).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+            for path in removed {
+                eprintln!("Removed {}", path.display());
+            }
+        }
+        Some(Command::Watch {
+            files,
+            output_dir,
+            interval,
+        }) => {
+            let mut documents = watch_documents(&args, &files, &output_dir).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            for document in &documents {
+                eprintln!("Built {}", document.target.display());
+            }
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(interval));
+                for line in rebuild_changed(&args, &mut documents) {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+        Some(Command::Precommit { mut files }) => {
+            if files.is_empty() {
+                let input = io::read_to_string(io::stdin()).unwrap_or_else(|e| {
+                    eprintln!("Error: reading standard input: {}", e);
+                    std::process::exit(1);
+                });
+                files = input
+                    .split(['\n', '\0'])
+                    .map(str::trim_end)
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect();
+            }
+            let problems = precommit(&args, &files).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            for problem in &problems {
+                eprintln!("{}", problem);
+            }
+            if !problems.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Plan {
+            files,
+            output_dir,
+            site,
+            format,
+        }) => {
+            let plan = build_plan(&args, &files, &output_dir, &site);
+            match format {
+                plan::PlanFormat::Table => print!("{}", plan::render_table(&plan)),
+                plan::PlanFormat::Json => match serde_json::to_string_pretty(&plan) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => {
+                        eprintln!("Problem converting to JSON: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+            }
+            if !plan.errors.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Bench {
+            file,
+            iterations,
+            render,
+        }) => {
+            let report =
+                bench_file(&args, &file, iterations as usize, render).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+            print!("{}", report.render());
+        }
+        Some(Command::Metrics {
+            files,
+            output,
+            append,
+            revision,
+            timestamp,
+            entries: entry_metrics,
+        }) => {
+            let mut categories: BTreeMap<String, metrics::Counts> = BTreeMap::new();
+            let mut measured = vec![];
+            for file in &files {
+                let file_args = args.for_file(file);
+                let record = load_record(&file_args).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+                for warning in &record.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                let entries = record.entries;
+                let counts = metrics::Counts::new(&entries, &record.public_bindings);
+                categories
+                    .entry(file_args.category)
+                    .or_default()
+                    .add(&counts);
+                if entry_metrics {
+                    measured.extend(entries.iter().map(metrics::EntryMetrics::new));
+                }
+            }
+            let mut record = metrics::Record::new(
+                timestamp.unwrap_or_else(changes::now_rfc3339),
+                revision.or_else(metrics::git_revision),
+                categories,
+            );
+            record.entries = measured;
+
+            match output {
+                Some(path) if append => metrics::append_record(&path, &record),
+                _ => serde_json::to_string_pretty(&record)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| {
+                        create_output_writer(output.as_deref())
+                            .and_then(|mut writer| {
+                                writeln!(writer, "{}", json)?;
+                                writer.flush()
+                            })
+                            .map_err(|e| format!("Error writing output: {}", e))
+                    }),
+            }
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        }
+        Some(Command::Changelog {
+            old,
+            new,
+            options,
+            atom,
+            title,
+            base_url,
+            feed_id,
+            revision,
+            updated,
+            output,
+        }) => {
+            let sections = |files: &[PathBuf]| {
+                if options {
+                    option_sections(files, &args)
+                } else {
+                    function_sections(files, &args)
+                }
+            };
+            let (old, new) = (sections(&old), sections(&new));
+            let changes = changes::diff(&old, &new);
+
+            let result = if atom {
+                let Some(id) = feed_id.as_deref().or(base_url.as_deref()) else {
+                    eprintln!("Error: --atom requires --feed-id or --base-url");
+                    std::process::exit(1);
+                };
+                let updated = updated.unwrap_or_else(changes::now_rfc3339);
+                let feed = changes::Feed {
+                    title: &title,
+                    id,
+                    base_url: base_url.as_deref(),
+                    revision: &revision,
+                    updated: &updated,
+                };
+                changes::render_atom(&feed, &changes)
+            } else {
+                changes::render_markdown(&title, &changes, base_url.as_deref())
+            };
 
-            let doc = extract_file_doc(&nix).map(|d| {
-                if shift_amount > 0 {
-                    shift_headings(&d, shift_amount)
+            let mut writer = create_output_writer(output.as_deref()).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            writer
+                .write_all(result.as_bytes())
+                .and_then(|()| writer.flush())
+                .unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+        }
+        Some(Command::Diff {
+            old,
+            new,
+            options,
+            format,
+            title,
+            output,
+        }) => {
+            let sections = |files: &[PathBuf]| {
+                if options {
+                    option_sections(files, &args)
                 } else {
-                    d
+                    function_sections(files, &args)
                 }
+            };
+            let (old, new) = (sections(&old), sections(&new));
+            let changes = changes::diff(&old, &new);
+            let result = match format {
+                changes::DiffFormat::Markdown => changes::render_diff_markdown(&title, &changes),
+                changes::DiffFormat::Html => changes::render_diff_html(&title, &changes),
+            };
+
+            let mut writer = create_output_writer(output.as_deref()).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             });
+            writer
+                .write_all(result.as_bytes())
+                .and_then(|()| writer.flush())
+                .unwrap_or_else(|e| {
+                    eprintln!("Error writing output: {}", e);
+                    std::process::exit(1);
+                });
+        }
+        Some(Command::Snapshot {
+            files,
+            dir,
+            update,
+            verify: _,
+        }) => {
+            let render = |file: &std::path::Path| main_with_args(&args.for_file(file));
 
-            match format.as_str() {
-                "json" => {
-                    let json_obj = serde_json::json!({
-                        "file": file.to_string_lossy(),
-                        "doc": doc
+            if update {
+                let written =
+                    snapshot::update_snapshots(&dir, &files, render).unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
                     });
-                    println!("{}", serde_json::to_string_pretty(&json_obj).unwrap());
+                for path in written {
+                    eprintln!("Updated {}", path.display());
                 }
-                "plain" => {
-                    if let Some(d) = doc {
-                        println!("{}", d);
-                    }
-                }
-                "markdown" | _ => {
-                    if let Some(d) = doc {
-                        println!("{}", d);
-                    }
+                return;
+            }
+
+            let mismatches = snapshot::verify_snapshots(&dir, &files, render).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            for m in &mismatches {
+                match &m.first_difference {
+                    None => eprintln!(
+                        "{}: missing snapshot {}",
+                        m.input.display(),
+                        m.snapshot.display()
+                    ),
+                    Some((line, expected, actual)) => eprintln!(
+                        "{}: differs from {} at line {}\n  expected: {}\n  actual:   {}",
+                        m.input.display(),
+                        m.snapshot.display(),
+                        line,
+                        expected,
+                        actual
+                    ),
                 }
             }
+            if !mismatches.is_empty() {
+                eprintln!(
+                    "{} of {} snapshots out of date; run with --update to accept",
+                    mismatches.len(),
+                    files.len()
+                );
+                std::process::exit(1);
+            }
         }
         None => {
-            if args.file.is_none() {
-                eprintln!("Error: --file is required");
+            if args.es_bulk.is_some() && args.es_shape == search::BulkShape::NixosSearch {
+                eprintln!(
+                    "Error: search.nixos.org has no documents for library functions; use --es-shape nixos-search with the options command"
+                );
+                std::process::exit(1);
+            }
+            if args.frontmatter.is_some()
+                && (args.json_output || args.es_bulk.is_some() || args.format != "markdown")
+            {
+                eprintln!("Error: --frontmatter only applies to markdown output");
+                std::process::exit(1);
+            }
+            let combined;
+            let inputs = match args.files.as_slice() {
+                [] if args.dir.is_none() => {
+                    eprintln!("Error: --file is required");
+                    std::process::exit(1);
+                }
+                [file] if args.dir.is_none() => {
+                    args.file = Some(file.clone());
+                    std::slice::from_ref(&args)
+                }
+                _ => {
+                    combined = combined_inputs(&args).unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    });
+                    &combined
+                }
+            };
+            if args.stream {
+                stream_or_exit(&args, inputs);
+                return;
+            }
+            let (loaded, targets) = load_inputs(&args, inputs);
+            let output = render_loaded(&args, inputs, &loaded, &targets);
+            if args.es_bulk.is_some() {
+                // Bulk actions are already newline-terminated.
+                print!("{}", output);
+                return;
+            }
+            if args.json_output {
+                println!("{}", output);
+                return;
+            }
+            if args.validate_output {
+                validate_or_exit(&output);
+            }
+            let id = page_id(&args);
+            let output = match &args.frontmatter {
+                Some(fields) => frontmatter::with_frontmatter(&output, &id, fields),
+                None => output,
+            };
+            let document = render::Document {
+                id,
+                sections: loaded
+                    .into_iter()
+                    .map(|(description, entries)| render::Section {
+                        description,
+                        entries,
+                    })
+                    .collect(),
+                markdown: output,
+            };
+            let renderers = renderers();
+            let renderer = renderers
+                .get(&args.format)
+                .expect("--format only accepts registered renderers");
+            let mut rendered = renderer.render(&document);
+            if !rendered.ends_with('\n') {
+                rendered.push('\n');
+            }
+            let sources: Result<Vec<manifest::Source>, String> = inputs
+                .iter()
+                .filter_map(|input| input.file.as_deref())
+                .map(manifest::Source::read)
+                .collect();
+            let sources = sources.unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
                 std::process::exit(1);
+            });
+            if let Some(metadata) = metadata(&args, &sources) {
+                rendered.push('\n');
+                rendered.push_str(&renderer.comment(&metadata));
             }
-            let output = main_with_args(&args);
-            println!("{}", output)
+            print!("{}", rendered);
         }
     }
 }
@@ -0,0 +1,345 @@
+//! Man page output.
+//!
+//! Every documented function becomes a section 3 page named after its full
+//! attribute path (`man3/lib.strings.concatStrings.3`), so `man
+//! lib.strings.concatStrings` works once the output directory is on the
+//! `MANPATH`. An index page named after the prefix (`man3/lib.3`) lists all
+//! functions by category.
+//!
+//! Alternatively, all functions are written to one combined page under the
+//! prefix's name, with a section per category and a subsection per function,
+//! read with `man 3 lib`.
+
+use crate::commonmark::{Argument, ManualEntry};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Escape text for roff, including control characters at the start of a line.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Append text, escaping each line that starts a new output line.
+fn push_text(out: &mut String, text: &str) {
+    for line in text.split_inclusive('\n') {
+        if out.is_empty() || out.ends_with('\n') {
+            out.push_str(&escape(line));
+        } else {
+            out.push_str(&line.replace('\\', "\\e").replace('-', "\\-"));
+        }
+    }
+}
+
+/// Make sure the next request starts on its own line.
+fn start_line(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Convert a CommonMark fragment to roff using the man macros.
+pub fn markdown_to_roff(markdown: &str) -> String {
+    to_roff(markdown, true)
+}
+
+/// Convert CommonMark to roff, with headings as `.SH`/`.SS` if `headings`
+/// is set and as bold paragraphs otherwise.
+fn to_roff(markdown: &str, headings: bool) -> String {
+    let options = Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_DEFINITION_LIST;
+    let mut out = String::new();
+    // Set right after `.IP`/`.TP`, whose first paragraph must not start a new one.
+    let mut in_item = false;
+    let mut link_urls: Vec<String> = Vec::new();
+    let mut list_numbers: Vec<Option<u64>> = Vec::new();
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                start_line(&mut out);
+                if !std::mem::take(&mut in_item) {
+                    out.push_str(".PP\n");
+                }
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                start_line(&mut out);
+                out.push_str(if !headings {
+                    ".PP\n\\fB"
+                } else if level <= HeadingLevel::H2 {
+                    ".SH "
+                } else {
+                    ".SS "
+                });
+            }
+            Event::End(TagEnd::Heading(_)) if !headings => {
+                out.push_str("\\fR\n");
+            }
+            Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::Paragraph) => start_line(&mut out),
+            Event::Start(Tag::CodeBlock(_)) => {
+                start_line(&mut out);
+                out.push_str(".PP\n.RS 4\n.EX\n");
+                in_item = false;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                start_line(&mut out);
+                out.push_str(".EE\n.RE\n");
+            }
+            Event::Start(Tag::List(first)) => list_numbers.push(first),
+            Event::End(TagEnd::List(_)) => {
+                list_numbers.pop();
+            }
+            Event::Start(Tag::Item) => {
+                start_line(&mut out);
+                match list_numbers.last_mut() {
+                    Some(Some(n)) => {
+                        let _ = writeln!(out, ".IP {}. 4", n);
+                        *n += 1;
+                    }
+                    _ => out.push_str(".IP \\(bu 4\n"),
+                }
+                in_item = true;
+            }
+            Event::Start(Tag::DefinitionListTitle) => {
+                start_line(&mut out);
+                out.push_str(".TP\n");
+            }
+            Event::End(TagEnd::DefinitionListTitle) => {
+                start_line(&mut out);
+                in_item = true;
+            }
+            Event::Start(Tag::Emphasis) => out.push_str("\\fI"),
+            Event::Start(Tag::Strong) => out.push_str("\\fB"),
+            Event::End(TagEnd::Emphasis) | Event::End(TagEnd::Strong) => out.push_str("\\fR"),
+            Event::Start(Tag::Link { dest_url, .. }) => link_urls.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = link_urls.pop()
+                    && !url.starts_with('#')
+                {
+                    let _ = write!(out, " <{}>", escape(&url));
+                }
+            }
+            Event::Code(code) => {
+                let _ = write!(out, "\\fB{}\\fR", escape(&code));
+            }
+            Event::Text(text) => push_text(&mut out, &text),
+            Event::SoftBreak => out.push('\n'),
+            Event::HardBreak => {
+                start_line(&mut out);
+                out.push_str(".br\n");
+            }
+            Event::Rule => {
+                start_line(&mut out);
+                out.push_str(".PP\n\\(em\\(em\\(em\n");
+            }
+            _ => {}
+        }
+    }
+    start_line(&mut out);
+    out
+}
+
+/// Write an entry's synopsis, description, arguments, example and source.
+/// On the entry's own page (`own_page`), each starts a `.SH` section;
+/// within the combined page, they are introduced by bold paragraphs and
+/// headings in the description are too.
+fn write_entry_body(entry: &ManualEntry, own_page: bool, page: &mut String) {
+    let section = |page: &mut String, name: &str| {
+        if own_page {
+            let _ = writeln!(page, ".SH {}", name.to_uppercase());
+        } else {
+            let _ = writeln!(page, ".PP\n\\fB{}\\fR", name);
+        }
+    };
+
+    if let Some(fn_type) = &entry.fn_type {
+        section(page, "Synopsis");
+        page.push_str(".EX\n");
+        for line in fn_type.lines() {
+            page.push_str(&escape(line));
+            page.push('\n');
+        }
+        page.push_str(".EE\n");
+    }
+
+    if own_page {
+        page.push_str(".SH DESCRIPTION\n");
+    }
+    page.push_str(&to_roff(&entry.description.join("\n\n"), own_page));
+
+    let has_args_section = entry.description.iter().any(|p| p.contains("# Arguments"));
+    if !entry.args.is_empty() && !has_args_section {
+        section(page, "Arguments");
+        for arg in &entry.args {
+            let args = match arg {
+                Argument::Flat(arg) => std::slice::from_ref(arg),
+                Argument::Pattern(args) => args.as_slice(),
+            };
+            for arg in args {
+                let _ = writeln!(page, ".TP\n\\fB{}\\fR", escape(&arg.name));
+                let doc = arg.doc.as_deref().unwrap_or("Function argument");
+                let doc = to_roff(doc, own_page);
+                page.push_str(doc.strip_prefix(".PP\n").unwrap_or(&doc));
+            }
+        }
+    }
+
+    if let Some(example) = &entry.example {
+        section(page, "Example");
+        page.push_str(".PP\n.RS 4\n.EX\n");
+        for line in example.lines() {
+            page.push_str(&escape(line));
+            page.push('\n');
+        }
+        page.push_str(".EE\n.RE\n");
+    }
+
+    if let Some(location) = &entry.location {
+        section(page, "Source");
+        page.push_str(&markdown_to_roff(location));
+    }
+}
+
+/// Render the man page for a single function.
+pub fn render_entry_page(entry: &ManualEntry, manual: &str) -> String {
+    let (_, title) = entry.get_ident_title();
+    let mut page = String::new();
+    let _ = writeln!(
+        page,
+        ".TH \"{}\" \"3\" \"\" \"\" \"{}\"",
+        escape(&title),
+        escape(manual)
+    );
+    let _ = writeln!(
+        page,
+        ".SH NAME\n{} \\- {}",
+        escape(&title),
+        escape(&entry.summary())
+    );
+    write_entry_body(entry, true, &mut page);
+
+    let _ = writeln!(
+        page,
+        ".SH SEE ALSO\n\\fB{}\\fR(3)",
+        escape(index_name(&entry.prefix))
+    );
+    page
+}
+
+/// Name of the index page; the prefix, or `index` without one.
+fn index_name(prefix: &str) -> &str {
+    if prefix.is_empty() { "index" } else { prefix }
+}
+
+/// Render the index page listing every function by category.
+pub fn render_index_page(prefix: &str, entries: &[ManualEntry], manual: &str) -> String {
+    let prefix = index_name(prefix);
+    let mut page = String::new();
+    let _ = writeln!(
+        page,
+        ".TH \"{}\" \"3\" \"\" \"\" \"{}\"",
+        escape(prefix),
+        escape(manual)
+    );
+    let _ = writeln!(page, ".SH NAME\n{} \\- {}", escape(prefix), escape(manual));
+    page.push_str(".SH FUNCTIONS\n");
+
+    let mut category = None;
+    for entry in entries {
+        if category != Some(&entry.category) {
+            let _ = writeln!(page, ".SS {}", escape(&entry.category));
+            category = Some(&entry.category);
+        }
+        let (_, title) = entry.get_ident_title();
+        let _ = writeln!(
+            page,
+            ".TP\n\\fB{}\\fR(3)\n{}",
+            escape(&title),
+            escape(&entry.summary())
+        );
+    }
+    page
+}
+
+/// Render one page documenting every function, with a section per
+/// category and a subsection per function.
+pub fn render_combined_page(prefix: &str, entries: &[ManualEntry], manual: &str) -> String {
+    let name = index_name(prefix);
+    let mut page = String::new();
+    let _ = writeln!(
+        page,
+        ".TH \"{}\" \"3\" \"\" \"\" \"{}\"",
+        escape(name),
+        escape(manual)
+    );
+    let _ = writeln!(page, ".SH NAME\n{} \\- {}", escape(name), escape(manual));
+
+    let mut category = None;
+    for entry in entries {
+        if category != Some(&entry.category) {
+            let heading = [prefix, entry.category.as_str()]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(".");
+            let _ = writeln!(page, ".SH \"{}\"", escape(&heading.to_uppercase()));
+            category = Some(&entry.category);
+        }
+        let (_, title) = entry.get_ident_title();
+        let _ = writeln!(page, ".SS \"{}\"", escape(&title));
+        write_entry_body(entry, false, &mut page);
+    }
+    page
+}
+
+/// Write the combined page into `dir/man3`, named after the prefix.
+pub fn write_combined(
+    dir: &Path,
+    prefix: &str,
+    entries: &[ManualEntry],
+    manual: &str,
+) -> Result<PathBuf, String> {
+    let man3 = dir.join("man3");
+    fs::create_dir_all(&man3).map_err(|e| format!("Error creating {}: {}", man3.display(), e))?;
+    let path = man3.join(format!("{}.3", index_name(prefix)));
+    fs::write(&path, render_combined_page(prefix, entries, manual))
+        .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Write one page per entry and the index page into `dir/man3`.
+pub fn write_bundle(
+    dir: &Path,
+    prefix: &str,
+    entries: &[ManualEntry],
+    manual: &str,
+) -> Result<Vec<PathBuf>, String> {
+    let man3 = dir.join("man3");
+    fs::create_dir_all(&man3).map_err(|e| format!("Error creating {}: {}", man3.display(), e))?;
+
+    let mut written = Vec::with_capacity(entries.len() + 1);
+    let mut write = |name: &str, contents: String| {
+        let path = man3.join(format!("{}.3", name));
+        fs::write(&path, contents)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        written.push(path);
+        Ok::<(), String>(())
+    };
+
+    for entry in entries {
+        let (_, title) = entry.get_ident_title();
+        write(&title, render_entry_page(entry, manual))?;
+    }
+    write(
+        index_name(prefix),
+        render_index_page(prefix, entries, manual),
+    )?;
+    Ok(written)
+}
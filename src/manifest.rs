@@ -0,0 +1,400 @@
+//! Machine-readable description of generated documentation.
+//!
+//! The manifest lists every document written to an output directory with
+//! the entries it contains and the sources it was generated from, so
+//! documentation portals can ingest docgen output without knowing its
+//! layout. The schema is versioned; readers should reject manifests with a
+//! `schema` they do not know:
+//!
+//! ```json
+//! {
+//!   "schema": 1,
+//!   "generator": "docgen 1.0.0",
+//!   "documents": [
+//!     {
+//!       "path": "strings.md",
+//!       "format": "markdown",
+//!       "category": "strings",
+//!       "sha256": "…",
+//!       "sources": [{ "path": "lib/strings.nix", "sha256": "…" }],
+//!       "entries": [
+//!         {
+//!           "name": "lib.strings.concatStrings",
+//!           "attribute": "concatStrings",
+//!           "anchor": "function-library-lib.strings.concatStrings",
+//!           "kind": "function",
+//!           "location": "[lib/strings.nix:12](…)",
+//!           "sha256": "…"
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Hashes are hex-encoded SHA-256 digests of the file contents, and for
+//! entries of what they document
+//! ([`ManualEntry::content_hash`](crate::ManualEntry::content_hash)), so a
+//! changed entry hash under an unchanged source hash means only docs
+//! changed elsewhere, and the pages of unchanged entries need not be
+//! rebuilt.
+//!
+//! The manifest of the previous run is also how docgen notices entries
+//! whose anchor or page changed: the redirect map next to it sends the old
+//! `path#anchor` links to the new ones, so deep links into a published
+//! manual survive renamed categories and changes to anchor generation:
+//!
+//! ```json
+//! {
+//!   "text.md#function-library-lib.text.concatStrings": "strings.md#function-library-lib.strings.concatStrings"
+//! }
+//! ```
+
+use crate::commonmark::{EntryKind, ManualEntry};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Version of the manifest layout.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Name of the manifest file in the output directory.
+pub const MANIFEST_FILE: &str = "docgen-manifest.json";
+
+/// Name of the redirect map in the output directory.
+pub const REDIRECTS_FILE: &str = "docgen-redirects.json";
+
+/// Old links mapped to the current link of the same entry. Links are
+/// `path#anchor`, with paths relative to the output directory.
+pub type Redirects = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema: u32,
+    /// Name and version of the program that wrote the manifest.
+    pub generator: String,
+    pub documents: Vec<Document>,
+}
+
+/// A generated file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    /// Path relative to the output directory, with `/` separators.
+    pub path: String,
+    /// Format of the document; only `markdown` so far.
+    pub format: String,
+    pub category: String,
+    pub sha256: String,
+    pub sources: Vec<Source>,
+    pub entries: Vec<Entry>,
+}
+
+/// An input file a document was generated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// An entry of a document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Full title, e.g. `lib.strings.concatStrings`.
+    pub name: String,
+    /// Attribute path within the source file, e.g. `concatStrings`.
+    #[serde(default)]
+    pub attribute: String,
+    /// Id of the entry's heading in the document.
+    pub anchor: String,
+    pub kind: EntryKind,
+    pub location: Option<String>,
+    /// [`ManualEntry::content_hash`] of the entry, empty in manifests
+    /// written before it was recorded.
+    #[serde(default)]
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn new(documents: Vec<Document>) -> Self {
+        Manifest {
+            schema: SCHEMA_VERSION,
+            generator: crate::provenance::generator(),
+            documents,
+        }
+    }
+
+    /// Read a manifest, failing if it was written with another schema.
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        let value: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+        match value.get("schema").and_then(|s| s.as_u64()) {
+            Some(schema) if schema == u64::from(SCHEMA_VERSION) => {}
+            schema => {
+                return Err(format!(
+                    "{}: unsupported manifest schema {} (expected {})",
+                    path.display(),
+                    schema.map_or("(none)".to_string(), |s| s.to_string()),
+                    SCHEMA_VERSION
+                ));
+            }
+        }
+        serde_json::from_value(value)
+            .map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+    }
+
+    /// Write the manifest as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json + "\n").map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    /// All entries with their documents.
+    fn entries(&self) -> impl Iterator<Item = (&Document, &Entry)> {
+        self.documents
+            .iter()
+            .flat_map(|d| d.entries.iter().map(move |e| (d, e)))
+    }
+}
+
+fn link(document: &Document, entry: &Entry) -> String {
+    format!("{}#{}", document.path, entry.anchor)
+}
+
+impl Document {
+    /// Describe a markdown document rendered from `sources`.
+    pub fn new(
+        path: String,
+        category: String,
+        contents: &str,
+        sources: Vec<Source>,
+        entries: &[ManualEntry],
+        anchor_prefix: &str,
+    ) -> Self {
+        Document {
+            path,
+            format: "markdown".to_string(),
+            category,
+            sha256: sha256_hex(contents.as_bytes()),
+            sources,
+            entries: entries
+                .iter()
+                .map(|entry| {
+                    let (_, title) = entry.get_ident_title();
+                    Entry {
+                        name: title,
+                        attribute: entry.name.clone(),
+                        anchor: entry.anchor(anchor_prefix),
+                        kind: entry.kind,
+                        location: entry.location.clone(),
+                        sha256: entry.content_hash(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Source {
+    /// Hash an input file.
+    pub fn read(path: &Path) -> Result<Source, String> {
+        let contents =
+            fs::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        Ok(Source {
+            path: path.to_string_lossy().replace('\\', "/"),
+            sha256: sha256_hex(&contents),
+        })
+    }
+}
+
+/// Read a redirect map, or start an empty one if there is none.
+pub fn load_redirects(path: &Path) -> Result<Redirects, String> {
+    if !path.exists() {
+        return Ok(Redirects::new());
+    }
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+/// Write a redirect map as pretty-printed JSON.
+pub fn write_redirects(path: &Path, redirects: &Redirects) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(redirects).map_err(|e| e.to_string())?;
+    fs::write(path, json + "\n").map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
+
+/// Add a redirect for each entry of `previous` whose link is gone from
+/// `current`, and point earlier redirects at current links.
+///
+/// An entry is found again by its name or, failing that, by its attribute
+/// in the same source file, which follows renamed categories. Redirects
+/// from links that are in use again, or to entries that are gone, are
+/// dropped.
+pub fn update_redirects(
+    redirects: &mut Redirects,
+    previous: Option<&Manifest>,
+    current: &Manifest,
+) {
+    let live: HashSet<String> = current.entries().map(|(d, e)| link(d, e)).collect();
+    let source = |document: &Document| document.sources.first().map(|s| s.path.clone());
+    for (document, entry) in previous.into_iter().flat_map(Manifest::entries) {
+        let old = link(document, entry);
+        if live.contains(&old) {
+            continue;
+        }
+        let found = current
+            .entries()
+            .find(|(_, e)| e.name == entry.name)
+            .or_else(|| {
+                current.entries().find(|(d, e)| {
+                    !entry.attribute.is_empty()
+                        && e.attribute == entry.attribute
+                        && source(d) == source(document)
+                })
+            });
+        if let Some((d, e)) = found {
+            redirects.insert(old, link(d, e));
+        }
+    }
+
+    let earlier = redirects.clone();
+    redirects.retain(|old, new| {
+        if live.contains(old) {
+            return false;
+        }
+        // Follow chains left by several runs; the hop limit stops cycles.
+        for _ in 0..earlier.len() {
+            match earlier.get(new.as_str()) {
+                Some(next) if !live.contains(new.as_str()) => *new = next.clone(),
+                _ => break,
+            }
+        }
+        live.contains(new.as_str())
+    });
+}
+
+/// Write an HTML page in place of each document that redirects lead away
+/// from, sending visitors to the new page of the entry named by the URL
+/// fragment. Documents are assumed to be published as `.html` files.
+///
+/// Returns the written stubs.
+pub fn write_redirect_stubs(
+    dir: &Path,
+    redirects: &Redirects,
+    current: &Manifest,
+) -> Result<Vec<PathBuf>, String> {
+    let mut pages: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+    for (old, new) in redirects {
+        let (page, anchor) = old.split_once('#').unwrap_or((old, ""));
+        if !current.documents.iter().any(|d| d.path == page) {
+            pages.entry(page).or_default().push((anchor, new));
+        }
+    }
+
+    let mut written = vec![];
+    for (page, targets) in pages {
+        let stub = Path::new(page).with_extension("html");
+        let path = path_inside(dir, &stub, "write a redirect stub for")?;
+        let up = "../".repeat(stub.components().count().saturating_sub(1));
+        let href = |link: &str| {
+            let (page, anchor) = link.split_once('#').unwrap_or((link, ""));
+            let page = Path::new(page).with_extension("html");
+            format!("{}{}#{}", up, page.to_string_lossy(), anchor)
+        };
+        // Without a known fragment, go where most of the page's entries went.
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, new) in &targets {
+            *counts
+                .entry(new.split('#').next().unwrap_or(new))
+                .or_default() += 1;
+        }
+        let fallback = counts
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(page, _)| href(page))
+            .unwrap_or_default();
+        let fallback = fallback.trim_end_matches('#');
+        let anchors: BTreeMap<&str, String> = targets
+            .iter()
+            .map(|(anchor, new)| (*anchor, href(new)))
+            .collect();
+        let anchors = serde_json::to_string(&anchors)
+            .map_err(|e| e.to_string())?
+            .replace("</", "<\\/");
+        let attr = fallback
+            .replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;");
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Moved</title>
+<script>
+const targets = {anchors};
+location.replace(targets[decodeURIComponent(location.hash.slice(1))] || "{fallback}");
+</script>
+<meta http-equiv="refresh" content="0; url={attr}">
+</head>
+<body>
+<p>This page has moved to <a href="{attr}">{attr}</a>.</p>
+</body>
+</html>
+"#,
+            fallback = fallback.replace('\\', "\\\\").replace('"', "\\\""),
+        );
+        fs::write(&path, html).map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// `relative` below `dir`, refusing paths that would leave it.
+fn path_inside(dir: &Path, relative: &Path, action: &str) -> Result<PathBuf, String> {
+    if relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        Ok(dir.join(relative))
+    } else {
+        Err(format!(
+            "refusing to {} {}: not a path inside {}",
+            action,
+            relative.display(),
+            dir.display()
+        ))
+    }
+}
+
+/// Delete the documents of a previous run that the current run did not
+/// produce, returning the removed files.
+///
+/// Only paths below `dir` are touched, so a tampered manifest cannot delete
+/// files elsewhere. Documents that are already gone are skipped.
+pub fn prune(dir: &Path, previous: &Manifest, current: &Manifest) -> Result<Vec<PathBuf>, String> {
+    let mut removed = vec![];
+    for document in &previous.documents {
+        if current.documents.iter().any(|d| d.path == document.path) {
+            continue;
+        }
+        let path = path_inside(dir, Path::new(&document.path), "prune")?;
+        match fs::remove_file(&path) {
+            Ok(()) => removed.push(path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Error removing {}: {}", path.display(), e)),
+        }
+    }
+    Ok(removed)
+}
+
+/// Hex-encoded SHA-256 digest.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,272 @@
+//! MDX output for Docusaurus.
+//!
+//! The generated CommonMark is kept as it is, except where MDX reads it
+//! differently:
+//!
+//! - the document starts with frontmatter giving its `id` and `title`,
+//! - `{`, `}` and `<` outside code are escaped, so they are not read as
+//!   JavaScript expressions or JSX, except for heading ids (`{#id}`), which
+//!   Docusaurus supports as they are,
+//! - HTML tags are rewritten as JSX (`className`, self-closing void
+//!   elements, no inline `style`), HTML comments are dropped and autolinks
+//!   become links,
+//! - `note`, `tip`, `important`, `warning` and `caution` blocks become
+//!   Docusaurus admonitions, `example` blocks `note` admonitions titled by
+//!   their heading, after an anchor with their id, and the fences of other
+//!   `:::` blocks are replaced by an anchor with their id.
+
+use crate::format::{DivFence, div_fence, get_fence, heading_level, trim_leading_whitespace};
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use std::fmt::Write as _;
+
+/// Admonitions by `:::` block class.
+const ADMONITIONS: [(&str, &str); 5] = [
+    ("note", "note"),
+    ("tip", "tip"),
+    ("important", "info"),
+    ("warning", "warning"),
+    ("caution", "danger"),
+];
+
+const VOID_ELEMENTS: [&str; 8] = ["br", "hr", "img", "input", "wbr", "col", "source", "meta"];
+
+/// The tag starting `text`, as JSX, and its length; `None` if `text` does
+/// not start with an HTML tag.
+fn jsx_tag(text: &str) -> Option<(String, usize)> {
+    let end = text.find('>')?;
+    let tag = &text[1..end];
+    let name_start = usize::from(tag.starts_with('/'));
+    let name: String = tag[name_start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    if name.is_empty()
+        || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || tag.contains('<')
+        || !tag[name_start + name.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || c == '/')
+    {
+        return None;
+    }
+    let mut jsx = tag.to_string();
+    for space in [" ", "\n", "\t"] {
+        jsx = jsx
+            .replace(&format!("{}class=", space), &format!("{}className=", space))
+            .replace(&format!("{}for=", space), &format!("{}htmlFor=", space));
+        if let Some(start) = jsx.find(&format!("{}style=\"", space))
+            && let Some(len) = jsx[start + 8..].find('"')
+        {
+            jsx.replace_range(start..start + 8 + len + 1, "");
+        }
+    }
+    let jsx = jsx.trim_end();
+    let jsx = if VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) && !jsx.ends_with('/')
+    {
+        format!("<{} />", jsx)
+    } else {
+        format!("<{}>", jsx)
+    };
+    Some((jsx, end + 1))
+}
+
+/// `html` as JSX: tags rewritten by [`jsx_tag`], comments dropped, and the
+/// `{`, `}` and `<` of the text between tags escaped.
+fn html_to_jsx(html: &str, out: &mut String) {
+    let mut i = 0;
+    while i < html.len() {
+        let rest = &html[i..];
+        let c = rest.chars().next().expect("index is a char boundary");
+        match c {
+            '<' if rest.starts_with("<!--") => {
+                i += rest.find("-->").map_or(rest.len(), |n| n + 3);
+            }
+            '<' => match jsx_tag(rest) {
+                Some((jsx, len)) => {
+                    out.push_str(&jsx);
+                    i += len;
+                }
+                None => {
+                    out.push_str("\\<");
+                    i += 1;
+                }
+            },
+            c => {
+                escape_char(c, out);
+                i += c.len_utf8();
+            }
+        }
+    }
+}
+
+/// Push `c`, escaped if MDX would read it as the start of an expression or
+/// a tag.
+fn escape_char(c: char, out: &mut String) {
+    if matches!(c, '{' | '}' | '<') {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+/// Escape the prose of `markdown`, by the events of a CommonMark parser:
+/// code is left alone, raw HTML is rewritten as JSX and autolinks become
+/// links. Heading ids are not text, and stay as they are.
+fn escape(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    // End of the source copied to `out`, and of the element being skipped.
+    let mut copied = 0;
+    let mut skip_to = 0;
+    let parser = Parser::new_ext(markdown, Options::ENABLE_HEADING_ATTRIBUTES).into_offset_iter();
+    for (event, range) in parser {
+        if range.start < skip_to {
+            continue;
+        }
+        let replace_from = |out: &mut String, copied: &mut usize| {
+            out.push_str(&markdown[*copied..range.start]);
+            *copied = range.end;
+        };
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => skip_to = range.end,
+            Event::Start(Tag::HtmlBlock) | Event::Html(_) | Event::InlineHtml(_) => {
+                replace_from(&mut out, &mut copied);
+                html_to_jsx(&markdown[range.clone()], &mut out);
+                skip_to = range.end;
+            }
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink,
+                dest_url,
+                ..
+            }) => {
+                replace_from(&mut out, &mut copied);
+                let _ = write!(out, "[{}]({})", dest_url, dest_url);
+                skip_to = range.end;
+            }
+            Event::Text(_) => {
+                replace_from(&mut out, &mut copied);
+                let text = &markdown[range.clone()];
+                for (i, c) in text.char_indices() {
+                    // Characters escaped in the source are left as they are.
+                    if range.start + i > 0 && markdown.as_bytes()[range.start + i - 1] == b'\\' {
+                        out.push(c);
+                    } else {
+                        escape_char(c, &mut out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out.push_str(&markdown[copied..]);
+    out
+}
+
+/// `text` as a JSX comment, as MDX does not allow HTML comments.
+pub fn comment(text: &str) -> String {
+    format!("{{/*\n{}*/}}\n", text.replace("*/", "* /"))
+}
+
+/// Convert CommonMark, as generated by docgen, to an MDX page with
+/// frontmatter. The title is the first level 1 heading, or `id`.
+pub fn markdown_to_mdx(markdown: &str, id: &str) -> String {
+    let title = markdown
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|heading| match heading.rsplit_once(" {#") {
+            Some((title, _)) => title.trim(),
+            None => heading.trim(),
+        })
+        .filter(|title| !title.is_empty())
+        .unwrap_or(id);
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    let mut out = format!("---\nid: {}\ntitle: {}\n---\n\n", quote(id), quote(title));
+
+    let mut markdown_out = String::with_capacity(markdown.len());
+    let mut fence: Option<(usize, char)> = None;
+    // For each open `:::` block, whether its closing fence is kept.
+    let mut blocks: Vec<bool> = vec![];
+    // Set after opening an example block until its title is known.
+    let mut example = false;
+    for line in markdown.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        let out = &mut markdown_out;
+        if example && fence.is_none() && div_fence(&fence_line).is_none() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            example = false;
+            if let Some(level) = heading_level(line) {
+                // The heading of an example is its title.
+                let title = line.trim()[level..].trim();
+                let title = match title.rsplit_once(" {#") {
+                    Some((title, attrs)) if attrs.ends_with('}') => title.trim(),
+                    _ => title,
+                };
+                let _ = writeln!(out, ":::note[{}]", title);
+                continue;
+            }
+            out.push_str(":::note[Example]\n");
+        }
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+                out.push_str(line);
+            }
+            None => match div_fence(&fence_line) {
+                Some(DivFence::Close) => {
+                    if std::mem::take(&mut example) {
+                        out.push_str(":::note[Example]\n");
+                    }
+                    if blocks.pop() == Some(true) {
+                        out.push_str(":::");
+                    } else {
+                        // Do not leave the blank line before the fence.
+                        if out.ends_with("\n\n") {
+                            out.pop();
+                        }
+                        continue;
+                    }
+                }
+                Some(DivFence::Open { classes, id }) if classes.first() == Some(&"example") => {
+                    if let Some(id) = id {
+                        // A blank line ends the HTML block of the anchor.
+                        let _ = write!(out, "<a id=\"{}\"></a>\n\n", id);
+                    }
+                    example = true;
+                    blocks.push(true);
+                    continue;
+                }
+                Some(DivFence::Open { classes, id }) => {
+                    match ADMONITIONS
+                        .iter()
+                        .find(|(from, _)| classes.first() == Some(from))
+                    {
+                        Some((_, admonition)) => {
+                            out.push_str(":::");
+                            out.push_str(admonition);
+                            blocks.push(true);
+                        }
+                        None => {
+                            blocks.push(false);
+                            match id {
+                                Some(id) => {
+                                    let _ = write!(out, "<a id=\"{}\"></a>", id);
+                                }
+                                None => continue,
+                            }
+                        }
+                    }
+                }
+                None => {
+                    fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                    out.push_str(line);
+                }
+            },
+        }
+        markdown_out.push('\n');
+    }
+    out.push_str(&escape(&markdown_out));
+    out
+}
@@ -0,0 +1,265 @@
+//! Documentation coverage metrics.
+//!
+//! One record describes the state of the documentation at one revision. The
+//! schema is versioned and only ever extended, so records can be appended to
+//! a history file and graphed over time:
+//!
+//! ```json
+//! {
+//!   "schema": 1,
+//!   "timestamp": "2024-05-01T12:00:00Z",
+//!   "revision": "0123abc",
+//!   "total": { "bindings": 12, "documented": 9, "functions": 8, "constants": 1,
+//!              "typed": 5, "examples": 4, "coverage": 0.75 },
+//!   "categories": { "strings": { "bindings": 12, ... } }
+//! }
+//! ```
+//!
+//! Records can also list the prose metrics of every entry, under `entries`:
+//! its word count, its amount of code relative to prose and the Flesch
+//! reading ease of its prose.
+
+use crate::commonmark::{Argument, EntryKind, ManualEntry};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Version of the record layout.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Metrics of the whole documentation at one revision.
+#[derive(Debug, Clone, Serialize)]
+pub struct Record {
+    pub schema: u32,
+    /// RFC 3339 time the record was taken.
+    pub timestamp: String,
+    /// Git revision of the sources, if known.
+    pub revision: Option<String>,
+    pub total: Counts,
+    pub categories: BTreeMap<String, Counts>,
+    /// Metrics of each entry, if requested.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<EntryMetrics>,
+}
+
+/// Counts for one category, or for all of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Counts {
+    /// Bindings that could be documented.
+    pub bindings: usize,
+    /// Of these bindings, those with a doc comment.
+    pub documented: usize,
+    pub functions: usize,
+    pub constants: usize,
+    /// Entries with a `# Type` section.
+    pub typed: usize,
+    /// Entries with an example in their description.
+    pub examples: usize,
+    /// `documented / bindings`, or 1 without bindings.
+    pub coverage: f64,
+}
+
+impl Counts {
+    /// Count the documented entries of a category whose bindings that could
+    /// be documented are `bindings`. Only entries of these bindings count as
+    /// documented, so doc comments on other bindings (e.g. those left out by
+    /// `--export`) do not count towards coverage.
+    pub fn new(entries: &[ManualEntry], bindings: &[String]) -> Self {
+        let mut counts = Counts {
+            bindings: bindings.len(),
+            documented: bindings
+                .iter()
+                .filter(|name| entries.iter().any(|entry| entry.name == **name))
+                .count(),
+            ..Default::default()
+        };
+        for entry in entries {
+            match entry.kind {
+                EntryKind::Function => counts.functions += 1,
+                EntryKind::Constant => counts.constants += 1,
+            }
+            if entry
+                .signature
+                .as_ref()
+                .is_some_and(|s| s.fn_type.is_some())
+            {
+                counts.typed += 1;
+            }
+            if entry.example.is_some() || entry.description.iter().any(|p| is_example(p)) {
+                counts.examples += 1;
+            }
+        }
+        counts.update_coverage();
+        counts
+    }
+
+    /// Add the counts of another category.
+    pub fn add(&mut self, other: &Counts) {
+        self.bindings += other.bindings;
+        self.documented += other.documented;
+        self.functions += other.functions;
+        self.constants += other.constants;
+        self.typed += other.typed;
+        self.examples += other.examples;
+        self.update_coverage();
+    }
+
+    fn update_coverage(&mut self) {
+        self.coverage = if self.bindings == 0 {
+            1.0
+        } else {
+            self.documented as f64 / self.bindings as f64
+        };
+    }
+}
+
+/// Prose metrics of one entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryMetrics {
+    pub title: String,
+    pub category: String,
+    /// Words of the description and argument docs, outside code.
+    pub words: usize,
+    /// Characters of code (blocks, inline code and the example) per
+    /// character of prose, or `null` without prose.
+    pub code_ratio: Option<f64>,
+    /// Flesch reading ease of the prose, from about 0 (very difficult) to
+    /// 100 (very easy), or `null` without prose.
+    pub readability: Option<f64>,
+}
+
+impl EntryMetrics {
+    pub fn new(entry: &ManualEntry) -> Self {
+        let mut texts = vec![entry.description.join("\n\n")];
+        for arg in &entry.args {
+            let args = match arg {
+                Argument::Flat(arg) => std::slice::from_ref(arg),
+                Argument::Pattern(args) => args.as_slice(),
+            };
+            texts.extend(args.iter().filter_map(|arg| arg.doc.clone()));
+        }
+        let mut prose = String::new();
+        let mut code = entry.example.clone().unwrap_or_default();
+        for text in &texts {
+            let mut in_code = false;
+            for event in Parser::new(text) {
+                match event {
+                    Event::Start(Tag::CodeBlock(_)) => in_code = true,
+                    Event::End(TagEnd::CodeBlock) => in_code = false,
+                    Event::Text(text) if in_code => code.push_str(&text),
+                    Event::Code(text) => code.push_str(&text),
+                    Event::Text(text) => prose.push_str(&text),
+                    Event::SoftBreak | Event::HardBreak | Event::End(_) => prose.push(' '),
+                    _ => {}
+                }
+            }
+        }
+        let (title, category) = (entry.get_ident_title().1, entry.category.clone());
+        let words: Vec<&str> = prose.split_whitespace().collect();
+        let characters = |text: &str| text.chars().filter(|c| !c.is_whitespace()).count();
+        let round = |value: f64| (value * 100.0).round() / 100.0;
+        EntryMetrics {
+            title,
+            category,
+            words: words.len(),
+            code_ratio: (!words.is_empty())
+                .then(|| round(characters(&code) as f64 / characters(&prose) as f64)),
+            readability: (!words.is_empty()).then(|| round(reading_ease(&words))),
+        }
+    }
+}
+
+/// Flesch reading ease of a text of at least one word.
+fn reading_ease(words: &[&str]) -> f64 {
+    let sentences = words
+        .iter()
+        .filter(|word| word.ends_with(['.', '!', '?']))
+        .count()
+        .max(1);
+    let syllables: usize = words.iter().map(|word| syllables(word)).sum();
+    let words_count = words.len() as f64;
+    206.835 - 1.015 * (words_count / sentences as f64) - 84.6 * (syllables as f64 / words_count)
+}
+
+/// Estimated syllables of an English word: its groups of vowels, not
+/// counting a final silent `e`, and at least one.
+fn syllables(word: &str) -> usize {
+    let word: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let is_vowel = |c: &char| "aeiouy".contains(*c);
+    let mut groups = 0;
+    let mut previous = false;
+    for c in &word {
+        let vowel = is_vowel(c);
+        if vowel && !previous {
+            groups += 1;
+        }
+        previous = vowel;
+    }
+    if word.ends_with(&['e']) && !word.ends_with(&['l', 'e']) && groups > 1 {
+        groups -= 1;
+    }
+    groups.max(1)
+}
+
+/// Whether a description paragraph is an example heading or block.
+fn is_example(paragraph: &str) -> bool {
+    let p = paragraph.trim_start();
+    let title = p.trim_start_matches('#').trim();
+    (p.starts_with('#')
+        && (title.eq_ignore_ascii_case("example") || title.eq_ignore_ascii_case("examples")))
+        || p.starts_with(":::") && p.contains(".example")
+}
+
+impl Record {
+    /// Build a record from per-category counts.
+    pub fn new(
+        timestamp: String,
+        revision: Option<String>,
+        categories: BTreeMap<String, Counts>,
+    ) -> Self {
+        let mut total = Counts::default();
+        total.update_coverage();
+        for counts in categories.values() {
+            total.add(counts);
+        }
+        Record {
+            schema: SCHEMA_VERSION,
+            timestamp,
+            revision,
+            total,
+            categories,
+            entries: vec![],
+        }
+    }
+}
+
+/// Append a record to a history file holding a JSON array of records,
+/// creating the file if it does not exist.
+pub fn append_record(path: &Path, record: &Record) -> Result<(), String> {
+    let mut history: Vec<serde_json::Value> = match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+    };
+    history.push(serde_json::to_value(record).map_err(|e| e.to_string())?);
+    let json = serde_json::to_string_pretty(&history).map_err(|e| e.to_string())?;
+    fs::write(path, json + "\n").map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
+
+/// The commit checked out in the working directory, if it is a git
+/// repository.
+pub fn git_revision() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    let revision = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !revision.is_empty()).then_some(revision)
+}
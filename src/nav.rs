@@ -0,0 +1,126 @@
+//! Reading order of the pages of a multi-page output.
+//!
+//! Pages follow the `order` of the `[navigation]` section of `docgen.toml`,
+//! with the categories it does not list after those it does, in input order:
+//!
+//! ```toml
+//! [navigation]
+//! order = ["trivial", "lists", "strings"]
+//! ```
+//!
+//! Each page links to the page before and after it, so the reference reads
+//! like a book, and `docgen-nav.json` lists the pages in reading order with
+//! their neighbours for site generators that draw their own navigation.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Name of the navigation file written next to the pages.
+pub const NAV_FILE: &str = "docgen-nav.json";
+
+/// `items` in reading order: those whose category is named in `order` first,
+/// in its order, then the others in their order. Also returns the names in
+/// `order` that are the category of no item.
+pub fn reading_order<T>(
+    items: Vec<T>,
+    category: impl Fn(&T) -> String,
+    order: &[String],
+) -> (Vec<T>, Vec<String>) {
+    let mut keyed: Vec<(usize, String, T)> = items
+        .into_iter()
+        .map(|item| {
+            let category = category(&item);
+            let rank = order
+                .iter()
+                .position(|name| *name == category)
+                .unwrap_or(order.len());
+            (rank, category, item)
+        })
+        .collect();
+    let unknown = order
+        .iter()
+        .filter(|name| !keyed.iter().any(|(_, category, _)| category == *name))
+        .cloned()
+        .collect();
+    keyed.sort_by_key(|(rank, _, _)| *rank);
+    (
+        keyed.into_iter().map(|(_, _, item)| item).collect(),
+        unknown,
+    )
+}
+
+/// A page and its neighbours in reading order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NavPage {
+    pub category: String,
+    pub title: String,
+    /// Path of the page, relative to the output directory.
+    pub path: String,
+    /// Path of the previous page.
+    pub prev: Option<String>,
+    /// Path of the next page.
+    pub next: Option<String>,
+}
+
+/// The pages of an output in reading order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Navigation {
+    pub pages: Vec<NavPage>,
+}
+
+impl Navigation {
+    /// Link `(category, title, path)` pages, given in reading order.
+    pub fn new(pages: Vec<(String, String, String)>) -> Navigation {
+        let paths: Vec<String> = pages.iter().map(|(_, _, path)| path.clone()).collect();
+        Navigation {
+            pages: pages
+                .into_iter()
+                .enumerate()
+                .map(|(i, (category, title, path))| NavPage {
+                    category,
+                    title,
+                    path,
+                    prev: i.checked_sub(1).map(|p| paths[p].clone()),
+                    next: paths.get(i + 1).cloned(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The page before and after page `i`.
+    pub fn neighbours(&self, i: usize) -> (Option<&NavPage>, Option<&NavPage>) {
+        (
+            i.checked_sub(1).and_then(|p| self.pages.get(p)),
+            self.pages.get(i + 1),
+        )
+    }
+
+    /// CommonMark links to the page before and after page `i`, below a
+    /// thematic break, or nothing for a single page.
+    pub fn markdown_links(&self, i: usize) -> Option<String> {
+        let link = |page: &NavPage, text: String| {
+            format!(
+                "[{}]({})",
+                text.replace('[', "\\[").replace(']', "\\]"),
+                page.path
+            )
+        };
+        let links: Vec<String> = match self.neighbours(i) {
+            (None, None) => return None,
+            (prev, next) => prev
+                .map(|page| link(page, format!("← {}", page.title)))
+                .into_iter()
+                .chain(next.map(|page| link(page, format!("{} →", page.title))))
+                .collect(),
+        };
+        Some(format!("\n---\n\n{}\n", links.join(" · ")))
+    }
+
+    /// Write the navigation as JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Problem converting navigation to JSON: {}", e))?;
+        fs::write(path, json + "\n").map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,111 @@
+//! Re-indentation of Nix expressions shown in the documentation.
+//!
+//! Option defaults and examples are usually produced by evaluation or
+//! `lib.literalExpression`, and keep whatever whitespace that left behind.
+//! This is not a full formatter: line breaks are kept as they are, and only
+//! the indentation of each line is recomputed from its nesting, two spaces per
+//! level. The contents of `''` strings and block comments are shifted along
+//! with the line they start on, so their relative indentation is preserved.
+
+use rnix::SyntaxKind::*;
+use std::borrow::Cow;
+
+/// Re-indent a multi-line Nix expression.
+///
+/// Expressions that are a single line or do not parse are returned as they are.
+pub fn reindent(text: &str) -> Cow<'_, str> {
+    if !text.trim().contains('\n') || !rnix::Root::parse(text).errors().is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    // Indentation of the lines that opened each unclosed bracket, `let` or
+    // interpolation.
+    let mut openers: Vec<usize> = Vec::new();
+    // For each open string, the shift to apply to its lines, if it is an
+    // indented string.
+    let mut strings: Vec<Option<isize>> = Vec::new();
+    let mut line_indent = 0;
+    let mut line_shift = 0;
+    let mut old_indent = 0;
+    let mut at_line_start = true;
+    let mut continuation = false;
+    let mut last_kind = None;
+
+    for (kind, token) in rnix::tokenize(text) {
+        if kind == TOKEN_WHITESPACE {
+            match token.rfind('\n') {
+                Some(last_newline) => {
+                    out.truncate(out.trim_end_matches([' ', '\t']).len());
+                    out.extend(std::iter::repeat_n('\n', token.matches('\n').count()));
+                    old_indent = token[last_newline + 1..].len();
+                    at_line_start = true;
+                    continuation = last_kind == Some(TOKEN_ASSIGN);
+                }
+                None if at_line_start => old_indent = token.len(),
+                None => out.push_str(token),
+            }
+            continue;
+        }
+
+        if at_line_start {
+            let closes = matches!(
+                kind,
+                TOKEN_R_BRACE | TOKEN_R_BRACK | TOKEN_R_PAREN | TOKEN_IN | TOKEN_INTERPOL_END
+            );
+            line_indent = match openers.last() {
+                Some(&indent) if closes => indent,
+                Some(&indent) => indent + 1 + continuation as usize,
+                None => continuation as usize,
+            };
+            line_shift = (line_indent * 2) as isize - old_indent as isize;
+            out.extend(std::iter::repeat_n(' ', line_indent * 2));
+            at_line_start = false;
+        }
+
+        match kind {
+            TOKEN_L_BRACE | TOKEN_L_BRACK | TOKEN_L_PAREN | TOKEN_LET | TOKEN_INTERPOL_START => {
+                openers.push(line_indent)
+            }
+            TOKEN_R_BRACE | TOKEN_R_BRACK | TOKEN_R_PAREN | TOKEN_IN | TOKEN_INTERPOL_END => {
+                openers.pop();
+            }
+            TOKEN_STRING_START => strings.push((token == "''").then_some(line_shift)),
+            TOKEN_STRING_END => {
+                strings.pop();
+            }
+            _ => {}
+        }
+
+        match kind {
+            TOKEN_STRING_CONTENT => match strings.last() {
+                Some(Some(shift)) => push_shifted(&mut out, token, *shift),
+                _ => out.push_str(token),
+            },
+            TOKEN_COMMENT => push_shifted(&mut out, token, line_shift),
+            _ => out.push_str(token),
+        }
+        last_kind = Some(kind);
+    }
+
+    Cow::Owned(out)
+}
+
+/// Append `text`, moving every line after the first by `shift` columns.
+/// Lines that are only whitespace become empty, except the last one, which
+/// holds the indentation of whatever follows.
+fn push_shifted(out: &mut String, text: &str, shift: isize) {
+    let mut lines = text.split('\n');
+    out.push_str(lines.next().unwrap_or_default());
+    let mut lines = lines.peekable();
+    while let Some(line) = lines.next() {
+        out.push('\n');
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() && lines.peek().is_some() {
+            continue;
+        }
+        let indent = (line.len() - content.len()) as isize + shift;
+        out.extend(std::iter::repeat_n(' ', indent.max(0) as usize));
+        out.push_str(content);
+    }
+}
@@ -0,0 +1,282 @@
+//! Normalization of existing CommonMark documents.
+//!
+//! Documentation generated by other tools tends to differ from docgen's
+//! output in purely cosmetic ways: setext headings instead of ATX ones, HTML
+//! anchors instead of `{#id}` attributes, untagged or inconsistently cased code
+//! fences and stray whitespace. Normalizing both sides makes a diff between
+//! them show only content changes.
+
+use crate::format::{get_fence, trim_leading_whitespace};
+
+/// Settings for [`normalize_document`].
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeOptions {
+    /// Language tag given to code fences that have no info string.
+    pub default_fence_lang: Option<String>,
+}
+
+/// Rewrite a CommonMark document into docgen's canonical style.
+///
+/// Outside of code blocks this:
+///
+/// - turns single-line setext headings into ATX headings,
+/// - drops closing `#` sequences and extra spaces from ATX headings,
+/// - moves `<a name="…"></a>` / `<a id="…"></a>` lines that directly precede a
+///   heading into a trailing `{#…}` attribute, and tightens `{ #… }` to `{#…}`,
+/// - lowercases fence languages, tagging untagged fences with
+///   `default_fence_lang` if set,
+/// - strips trailing whitespace and collapses runs of blank lines.
+///
+/// Code block contents, fenced or indented, are left untouched, and so are
+/// the indentation of fences, which may place them in a list item, and the
+/// trailing spaces of hard line breaks. The result ends in exactly one
+/// newline.
+pub fn normalize_document(input: &str, opts: &NormalizeOptions) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut curr_fence: Option<(usize, char)> = None;
+    let mut pending_anchor: Option<String> = None;
+    // Set within an indented code block.
+    let mut indented_code = false;
+
+    let raw_lines: Vec<&str> = input.lines().collect();
+    for (i, &raw_line) in raw_lines.iter().enumerate() {
+        let fence_line = trim_leading_whitespace(raw_line, 3);
+        let indent = &raw_line[..raw_line.len() - fence_line.len()];
+
+        if let Some((start_count, start_char)) = curr_fence {
+            if let Some((end_count, end_char)) = get_fence(fence_line.trim_end(), false)
+                && end_char == start_char
+                && end_count >= start_count
+            {
+                curr_fence = None;
+                lines.push(raw_line.trim_end().to_string());
+            } else {
+                lines.push(raw_line.to_string());
+            }
+            continue;
+        }
+
+        let line = raw_line.trim_end();
+
+        // Blank lines within an indented code block are part of the code.
+        let code_indent = indent_width(raw_line) >= 4;
+        if indented_code && (line.is_empty() || code_indent) {
+            lines.push(raw_line.to_string());
+            continue;
+        }
+        if indented_code {
+            indented_code = false;
+            while lines.last().is_some_and(|l| l.trim().is_empty()) {
+                lines.pop();
+            }
+            lines.push(String::new());
+        }
+        if code_indent && !line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            flush_anchor(&mut lines, &mut pending_anchor);
+            indented_code = true;
+            lines.push(raw_line.to_string());
+            continue;
+        }
+
+        if let Some(fence) = get_fence(&fence_line, true)
+            && fence.0 >= 3
+        {
+            flush_anchor(&mut lines, &mut pending_anchor);
+            curr_fence = Some(fence);
+            lines.push(format!(
+                "{}{}",
+                indent,
+                normalize_fence_open(fence_line.trim_end(), fence, opts)
+            ));
+            continue;
+        }
+
+        if line.is_empty() {
+            // A blank line separates a pending anchor from whatever follows,
+            // so it no longer belongs to a heading.
+            flush_anchor(&mut lines, &mut pending_anchor);
+            if lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+
+        if let Some(id) = html_anchor_id(line) {
+            flush_anchor(&mut lines, &mut pending_anchor);
+            pending_anchor = Some(id);
+            continue;
+        }
+
+        if let Some(level) = setext_level(line)
+            && let Some(text) = setext_text(&lines)
+        {
+            let text = text.trim().to_string();
+            lines.pop();
+            lines.push(format_heading(level, &text, None));
+            continue;
+        }
+
+        if let Some((level, text)) = parse_atx_heading(line) {
+            let heading = format_heading(level, text, pending_anchor.take().as_deref());
+            lines.push(heading);
+            continue;
+        }
+
+        flush_anchor(&mut lines, &mut pending_anchor);
+        // Two trailing spaces before another line of the paragraph are a
+        // hard line break.
+        let hard_break = raw_line.ends_with("  ")
+            && raw_lines
+                .get(i + 1)
+                .is_some_and(|next| !next.trim().is_empty());
+        lines.push(if hard_break { raw_line } else { line }.to_string());
+    }
+    flush_anchor(&mut lines, &mut pending_anchor);
+
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
+}
+
+/// Width of the indentation of `line`, with tabs to the next multiple of 4.
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// Re-emit an anchor that did not end up attached to a heading.
+fn flush_anchor(lines: &mut Vec<String>, pending: &mut Option<String>) {
+    if let Some(id) = pending.take() {
+        lines.push(format!("<a id=\"{}\"></a>", id));
+    }
+}
+
+/// Rewrite the opening line of a code fence with a normalized info string.
+fn normalize_fence_open(line: &str, (count, ch): (usize, char), opts: &NormalizeOptions) -> String {
+    let info = line[count * ch.len_utf8()..].trim();
+    let fence = ch.to_string().repeat(count);
+    let mut words = info.splitn(2, char::is_whitespace);
+    match words.next().filter(|lang| !lang.is_empty()) {
+        Some(lang) => match words.next() {
+            Some(rest) => format!("{}{} {}", fence, lang.to_lowercase(), rest.trim()),
+            None => format!("{}{}", fence, lang.to_lowercase()),
+        },
+        None => match &opts.default_fence_lang {
+            Some(lang) => format!("{}{}", fence, lang),
+            None => fence,
+        },
+    }
+}
+
+/// Return the id of a line consisting only of an empty HTML anchor element.
+fn html_anchor_id(line: &str) -> Option<String> {
+    let rest = line.trim().strip_prefix("<a ")?.strip_suffix("</a>")?;
+    let rest = rest.trim_end().strip_suffix('>')?.trim_end();
+    let rest = rest.strip_suffix('/').unwrap_or(rest).trim_end();
+    let value = rest
+        .strip_prefix("name=")
+        .or_else(|| rest.strip_prefix("id="))?;
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let id = value[1..].strip_suffix(quote)?;
+    (!id.is_empty() && !id.contains(quote)).then(|| id.to_string())
+}
+
+/// Level of a setext underline (`===` is 1, `---` is 2).
+fn setext_level(line: &str) -> Option<usize> {
+    let underline = trim_leading_whitespace(line, 3);
+    let first = underline.chars().next()?;
+    if (first == '=' || first == '-') && underline.chars().all(|c| c == first) {
+        Some(if first == '=' { 1 } else { 2 })
+    } else {
+        None
+    }
+}
+
+/// The previous line, if it is a single-line paragraph that a setext
+/// underline would turn into a heading.
+fn setext_text(lines: &[String]) -> Option<&str> {
+    let (last, before) = lines.split_last()?;
+    let paragraph_start = before.last().is_none_or(|l| l.is_empty());
+    let plain = !last.is_empty()
+        && !last.starts_with("    ")
+        && parse_atx_heading(last).is_none()
+        && !is_block_start(&trim_leading_whitespace(last, 3));
+    (paragraph_start && plain).then_some(last.as_str())
+}
+
+/// Whether a line opens a block that cannot be the text of a setext heading.
+fn is_block_start(line: &str) -> bool {
+    line.starts_with("- ")
+        || line.starts_with("* ")
+        || line.starts_with("+ ")
+        || line.starts_with('>')
+        || line.starts_with('<')
+        || line.starts_with(':')
+        || line.starts_with('|')
+        || line
+            .split_once(". ")
+            .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Split an ATX heading into its level and text, without the optional
+/// closing sequence of `#`.
+fn parse_atx_heading(line: &str) -> Option<(usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let heading = &line[indent..];
+    let level = heading.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &heading[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let text = rest.trim();
+    // A closing sequence only counts when separated from the text by a space.
+    let stripped = text.trim_end_matches('#');
+    let text = if stripped.is_empty() || stripped.ends_with([' ', '\t']) {
+        stripped.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+/// Format an ATX heading, normalizing any `{#id}` attribute on it.
+fn format_heading(level: usize, text: &str, anchor: Option<&str>) -> String {
+    let (text, attr_id) = split_heading_id(text);
+    let hashes = "#".repeat(level);
+    match anchor.or(attr_id) {
+        Some(id) if text.is_empty() => format!("{} {{#{}}}", hashes, id),
+        Some(id) => format!("{} {} {{#{}}}", hashes, text, id),
+        None if text.is_empty() => hashes,
+        None => format!("{} {}", hashes, text),
+    }
+}
+
+/// Separate a trailing `{#id}` (with any inner spacing) from heading text.
+fn split_heading_id(text: &str) -> (&str, Option<&str>) {
+    if let Some(body) = text.strip_suffix('}')
+        && let Some(open) = body.rfind('{')
+        && let Some(id) = body[open + 1..].trim().strip_prefix('#')
+        && !id.is_empty()
+        && !id.contains(char::is_whitespace)
+    {
+        return (body[..open].trim_end(), Some(id));
+    }
+    (text, None)
+}
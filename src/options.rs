@@ -37,9 +37,24 @@
 //! }
 //! ```
 
+use crate::admonition::{Dialect, to_dialect};
+use crate::commonmark::RAW_FORMATS;
+use crate::format::{
+    CodeLangs, LinkStyle, Wrap, check_template, expand_template, reference_links, route_raw_blocks,
+    wrap_markdown,
+};
+use crate::nixfmt;
+use crate::sanitize::{HtmlPolicy, sanitize_html};
+use crate::xref::{CrossRefs, link_mentions};
+use rayon::prelude::*;
 use serde::Deserialize;
+use serde::de::{Deserializer, Error as _, MapAccess, Visitor};
+use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fs;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
 use std::path::Path;
 
 /// A value that can be either a literal expression, literal markdown, or a raw value.
@@ -156,36 +171,393 @@ impl DeclarationLoc {
     }
 }
 
+/// Where declarations under a path prefix are linked to, for option sets
+/// that combine modules from several repositories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeclarationProfile {
+    /// Declaration paths starting with this prefix use this profile. The
+    /// prefix is removed from the path in the link target.
+    pub prefix: String,
+    /// Repository URL (e.g. <https://github.com/nix-community/home-manager>)
+    pub base_url: String,
+    /// Git revision, `HEAD` unless given: `--revision` names a revision of
+    /// the main repository, not of this one
+    pub revision: String,
+}
+
+impl std::str::FromStr for DeclarationProfile {
+    type Err = String;
+
+    /// Parse `PREFIX=URL` or `PREFIX=URL@REV`. An `@` in the authority of the
+    /// URL (`https://user@host/...`) is userinfo, not a revision.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("expected PREFIX=URL[@REV], got '{}'", s);
+        let (prefix, target) = s.split_once('=').ok_or_else(err)?;
+        let path_start = match target.find("://") {
+            Some(i) => target[i + 3..]
+                .find('/')
+                .map_or(target.len(), |j| i + 3 + j),
+            None => 0,
+        };
+        let (base_url, revision) = match target[path_start..].rsplit_once('@') {
+            Some((_, rev)) if !rev.is_empty() => {
+                (&target[..target.len() - rev.len() - 1], rev.to_string())
+            }
+            Some(_) => return Err(err()),
+            None => (target, "HEAD".to_string()),
+        };
+        if base_url.is_empty() {
+            return Err(err());
+        }
+        Ok(DeclarationProfile {
+            prefix: prefix.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            revision,
+        })
+    }
+}
+
 /// Parsed options from JSON
 pub type OptionsMap = HashMap<String, OptionDef>;
 
 /// Parse options JSON from a file
+///
+/// The file is deserialized straight from a buffered reader, so the raw JSON text
+/// is never held in memory alongside the parsed options.
 pub fn parse_options_file(path: &Path) -> Result<OptionsMap, String> {
-    let content =
-        fs::read_to_string(path).map_err(|e| format!("Failed to read options file: {}", e))?;
-    parse_options_json(&content)
+    parse_options_reader(open_options_file(path)?)
 }
 
 /// Parse options JSON from a string
 pub fn parse_options_json(json: &str) -> Result<OptionsMap, String> {
-    serde_json::from_str(json).map_err(|e| format!("Failed to parse options JSON: {}", e))
+    let mut options = OptionsMap::new();
+    visit_options(serde_json::de::StrRead::new(json), &[], |name, opt| {
+        options.insert(name, opt);
+    })?;
+    Ok(options)
+}
+
+/// Parse options JSON from any reader
+pub fn parse_options_reader<R: Read>(reader: R) -> Result<OptionsMap, String> {
+    let mut options = OptionsMap::new();
+    stream_options(reader, |name, opt| {
+        options.insert(name, opt);
+    })?;
+    Ok(options)
+}
+
+/// Open an options file for buffered reading
+pub fn open_options_file(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Failed to read options file: {}", e))
+}
+
+/// Deserialize options one entry at a time, handing each to `f` as soon as it is parsed.
+///
+/// Only a single `OptionDef` is alive at any point, which keeps memory bounded for
+/// very large inputs such as the full nixpkgs `options.json`.
+pub fn stream_options<R, F>(reader: R, f: F) -> Result<(), String>
+where
+    R: Read,
+    F: FnMut(String, OptionDef),
+{
+    stream_options_where(reader, &[], f)
+}
+
+/// Like [`stream_options`], skipping options that do not match all of
+/// `filters`.
+pub fn stream_options_where<R, F>(reader: R, filters: &[OptionFilter], f: F) -> Result<(), String>
+where
+    R: Read,
+    F: FnMut(String, OptionDef),
+{
+    visit_options(serde_json::de::IoRead::new(reader), filters, f)
+}
+
+fn visit_options<'de, R, F>(read: R, filters: &[OptionFilter], f: F) -> Result<(), String>
+where
+    R: serde_json::de::Read<'de>,
+    F: FnMut(String, OptionDef),
+{
+    let mut de = serde_json::Deserializer::new(read);
+    (&mut de)
+        .deserialize_map(OptionEntryVisitor(filters, f))
+        .and_then(|()| de.end())
+        .map_err(|e| format!("Failed to parse options JSON: {}", e))
+}
+
+/// How an [`OptionFilter`] compares a field with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    /// `field=value`
+    Equals,
+    /// `field!=value`
+    NotEquals,
+    /// `field=~value`: the field contains the value
+    Contains,
+    /// `field!~value`
+    NotContains,
+}
+
+/// A predicate on the raw JSON of an option, such as `type=~submodule` or
+/// `readOnly=false`.
+///
+/// The field is a dotted path into the option object (e.g. `default.text`).
+/// Strings are compared with their contents and other values with their
+/// JSON text, so `readOnly=false` and `loc=~"services"` work alike. Options
+/// without the field only match the negated operators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionFilter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+impl std::str::FromStr for OptionFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, len, op) = [
+            ("!=", FilterOp::NotEquals),
+            ("=~", FilterOp::Contains),
+            ("!~", FilterOp::NotContains),
+            ("=", FilterOp::Equals),
+        ]
+        .into_iter()
+        .filter_map(|(token, op)| s.find(token).map(|at| (at, token.len(), op)))
+        .min_by_key(|(at, _, _)| *at)
+        .ok_or_else(|| {
+            format!(
+                "expected FIELD=VALUE, FIELD!=VALUE, FIELD=~VALUE or FIELD!~VALUE, got '{}'",
+                s
+            )
+        })?;
+        let field = s[..at].trim();
+        if field.is_empty() {
+            return Err(format!("missing field name in '{}'", s));
+        }
+        Ok(OptionFilter {
+            field: field.to_string(),
+            op,
+            value: s[at + len..].to_string(),
+        })
+    }
+}
+
+impl OptionFilter {
+    /// Whether the raw JSON of an option matches.
+    pub fn matches(&self, option: &Value) -> bool {
+        let field = self
+            .field
+            .split('.')
+            .try_fold(option, |value, key| value.get(key));
+        let Some(field) = field else {
+            return matches!(self.op, FilterOp::NotEquals | FilterOp::NotContains);
+        };
+        let text = match field {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        match self.op {
+            FilterOp::Equals => text == self.value,
+            FilterOp::NotEquals => text != self.value,
+            FilterOp::Contains => text.contains(&self.value),
+            FilterOp::NotContains => !text.contains(&self.value),
+        }
+    }
+}
+
+/// Visits the top-level options object entry by entry, skipping options
+/// that do not match the filters
+struct OptionEntryVisitor<'f, F>(&'f [OptionFilter], F);
+
+impl<'de, F> Visitor<'de> for OptionEntryVisitor<'_, F>
+where
+    F: FnMut(String, OptionDef),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of option names to option definitions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some((name, value)) = map.next_entry::<String, Value>()? {
+            check_option(&value)
+                .map_err(|e| A::Error::custom(format!("option '{}': {}", name, e)))?;
+            if !self.0.iter().all(|filter| filter.matches(&value)) {
+                continue;
+            }
+            let opt = OptionDef::deserialize(value)
+                .map_err(|e| A::Error::custom(format!("option '{}': {}", name, e)))?;
+            (self.1)(name, opt);
+        }
+        Ok(())
+    }
+}
+
+/// `_type`s of values that `lib.optionAttrSetToDocList` produces, including
+/// the DocBook of releases before markdown.
+const VALUE_TYPES: [&str; 3] = ["literalExpression", "literalMD", "literalDocBook"];
+
+/// `_type`s of descriptions, markdown or the DocBook of older releases.
+const DESCRIPTION_TYPES: [&str; 2] = ["mdDoc", "literalDocBook"];
+
+/// Describe a JSON value for error messages.
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Check an option definition against the layout produced by
+/// `lib.optionAttrSetToDocList`, naming the field that does not match.
+///
+/// Fields that docgen does not read (`visible`, `internal`, ...) are not checked.
+fn check_option(value: &Value) -> Result<(), String> {
+    let Value::Object(fields) = value else {
+        return Err(format!("expected an object, found {}", json_kind(value)));
+    };
+    let field_err = |field: &str, expected: &str, found: &Value| {
+        format!(
+            "field '{}': expected {}, found {}",
+            field,
+            expected,
+            json_kind(found)
+        )
+    };
+
+    match fields.get("description") {
+        None => return Err("missing field 'description'".to_string()),
+        Some(Value::Null | Value::String(_)) => {}
+        Some(Value::Object(doc)) => match doc.get("_type") {
+            Some(Value::String(t)) if DESCRIPTION_TYPES.contains(&t.as_str()) => {
+                match doc.get("text") {
+                    Some(Value::String(_)) => {}
+                    Some(text) => return Err(field_err("description.text", "a string", text)),
+                    None => return Err("field 'description': missing 'text'".to_string()),
+                }
+            }
+            Some(Value::String(t)) => {
+                return Err(format!(
+                    "field 'description': unexpected _type '{}' (expected one of {})",
+                    t,
+                    DESCRIPTION_TYPES.map(|t| format!("'{}'", t)).join(", ")
+                ));
+            }
+            Some(t) => return Err(field_err("description._type", "a string", t)),
+            None => return Err("field 'description': object without _type".to_string()),
+        },
+        Some(other) => return Err(field_err("description", "a string", other)),
+    }
+
+    if let Some(loc) = fields.get("loc") {
+        let Value::Array(segments) = loc else {
+            return Err(field_err("loc", "a list of strings", loc));
+        };
+        if let Some((i, s)) = segments.iter().enumerate().find(|(_, s)| !s.is_string()) {
+            return Err(field_err(&format!("loc[{}]", i), "a string", s));
+        }
+    }
+
+    match fields.get("type") {
+        None | Some(Value::Null | Value::String(_)) => {}
+        Some(other) => return Err(field_err("type", "a string", other)),
+    }
+
+    for field in ["default", "example"] {
+        let Some(Value::Object(value)) = fields.get(field) else {
+            continue;
+        };
+        match value.get("_type") {
+            None => {}
+            Some(Value::String(t)) if VALUE_TYPES.contains(&t.as_str()) => {
+                match value.get("text") {
+                    Some(Value::String(_)) => {}
+                    Some(text) => {
+                        return Err(field_err(&format!("{}.text", field), "a string", text));
+                    }
+                    None => return Err(format!("field '{}': missing 'text'", field)),
+                }
+            }
+            Some(Value::String(t)) => {
+                return Err(format!(
+                    "field '{}': unexpected _type '{}' (expected one of {})",
+                    field,
+                    t,
+                    VALUE_TYPES.map(|t| format!("'{}'", t)).join(", ")
+                ));
+            }
+            Some(t) => return Err(field_err(&format!("{}._type", field), "a string", t)),
+        }
+    }
+
+    if let Some(declarations) = fields.get("declarations") {
+        let Value::Array(declarations) = declarations else {
+            return Err(field_err("declarations", "a list", declarations));
+        };
+        for (i, decl) in declarations.iter().enumerate() {
+            let field = format!("declarations[{}]", i);
+            match decl {
+                Value::String(_) => {}
+                Value::Object(decl) => {
+                    match decl.get("name") {
+                        Some(Value::String(_)) => {}
+                        Some(name) => {
+                            return Err(field_err(&format!("{}.name", field), "a string", name));
+                        }
+                        None => return Err(format!("field '{}': missing 'name'", field)),
+                    }
+                    match decl.get("url") {
+                        None | Some(Value::Null | Value::String(_)) => {}
+                        Some(url) => {
+                            return Err(field_err(&format!("{}.url", field), "a string", url));
+                        }
+                    }
+                }
+                other => {
+                    return Err(field_err(&field, "a path or an object with a name", other));
+                }
+            }
+        }
+    }
+
+    match fields.get("readOnly") {
+        None | Some(Value::Bool(_)) => {}
+        Some(other) => return Err(field_err("readOnly", "a boolean", other)),
+    }
+    match fields.get("relatedPackages") {
+        None | Some(Value::Null | Value::String(_)) => {}
+        Some(other) => return Err(field_err("relatedPackages", "a string", other)),
+    }
+    Ok(())
 }
 
 /// Escape special CommonMark characters
-fn md_escape(text: &str) -> String {
+fn md_escape(text: &str) -> Cow<'_, str> {
     // Escape characters that have special meaning in CommonMark
-    text.replace('\\', "\\\\")
-        .replace('*', "\\*")
-        .replace('_', "\\_")
-        .replace('[', "\\[")
-        .replace(']', "\\]")
-        .replace('<', "\\<")
-        .replace('>', "\\>")
-        .replace('`', "\\`")
+    const SPECIAL: [char; 8] = ['\\', '*', '_', '[', ']', '<', '>', '`'];
+    if !text.contains(SPECIAL) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if SPECIAL.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
 }
 
 /// Format an option value for display
-fn format_option_value(value: &OptionValue) -> String {
+fn format_option_value<'a>(value: &'a OptionValue, code_langs: &CodeLangs) -> Cow<'a, str> {
     match value {
         OptionValue::Tagged(tagged) => {
             match tagged.value_type.as_str() {
@@ -193,56 +565,99 @@ fn format_option_value(value: &OptionValue) -> String {
                     if let Some(text) = &tagged.text {
                         // Multi-line expressions get code blocks
                         if text.contains('\n') {
-                            format!("```nix\n{}\n```", text)
+                            let text = nixfmt::reindent(text);
+                            Cow::Owned(format!("{}\n{}\n```", code_langs.fence(), text))
                         } else {
-                            format!("`{}`", text)
+                            Cow::Owned(format!("`{}`", text))
                         }
                     } else {
-                        "`...`".to_string()
+                        Cow::Borrowed("`...`")
                     }
                 }
                 "literalMD" => {
                     // Literal markdown is rendered as-is
-                    tagged.text.clone().unwrap_or_default()
+                    Cow::Borrowed(tagged.text.as_deref().unwrap_or_default())
                 }
                 _ => {
                     // Unknown tagged type
-                    format!(
+                    Cow::Owned(format!(
                         "`<{}>: {}`",
                         tagged.value_type,
                         tagged.text.as_deref().unwrap_or("...")
-                    )
+                    ))
                 }
             }
         }
-        OptionValue::String(s) => format!("`\"{}\"`", s),
-        OptionValue::Bool(b) => format!("`{}`", b),
-        OptionValue::Number(n) => format!("`{}`", n),
+        OptionValue::String(s) => Cow::Owned(format!("`\"{}\"`", s)),
+        OptionValue::Bool(b) => Cow::Borrowed(if *b { "`true`" } else { "`false`" }),
+        OptionValue::Number(n) => Cow::Owned(format!("`{}`", n)),
         OptionValue::Array(arr) => {
             // Simple array representation
-            let items: Vec<String> = arr
-                .iter()
-                .map(|v| serde_json::to_string(v).unwrap_or_else(|_| "...".to_string()))
-                .collect();
-            if items.is_empty() {
-                "`[ ]`".to_string()
-            } else {
-                format!("`[ {} ]`", items.join(" "))
+            if arr.is_empty() {
+                return Cow::Borrowed("`[ ]`");
+            }
+            let mut out = String::from("`[ ");
+            for v in arr {
+                match serde_json::to_string(v) {
+                    Ok(item) => out.push_str(&item),
+                    Err(_) => out.push_str("..."),
+                }
+                out.push(' ');
             }
+            out.push_str("]`");
+            Cow::Owned(out)
         }
-        OptionValue::Object(_) => "`{ ... }`".to_string(),
-        OptionValue::Null => "`null`".to_string(),
+        OptionValue::Object(_) => Cow::Borrowed("`{ ... }`"),
+        OptionValue::Null => Cow::Borrowed("`null`"),
+    }
+}
+
+/// Create a sanitized anchor ID from an option name, after the prefix or
+/// as the `{name}` of an anchor template (e.g. `option-{name}`)
+pub fn make_anchor_id(name: &str, prefix: &str) -> String {
+    let sanitized = name.replace('.', "-").replace(['<', '>', '*'], "_");
+    if prefix.contains('{') {
+        expand_template(prefix, &[("name", &sanitized)])
+    } else {
+        format!("{}{}", prefix, sanitized)
     }
 }
 
-/// Create a sanitized anchor ID from an option name
-fn make_anchor_id(name: &str, prefix: &str) -> String {
-    let sanitized = name
-        .replace('.', "-")
-        .replace('<', "_")
-        .replace('>', "_")
-        .replace('*', "_");
-    format!("{}{}", prefix, sanitized)
+/// Parse an options `--anchor-prefix`: a prefix, or a template using
+/// `{name}`.
+pub fn parse_anchor_template(s: &str) -> Result<String, String> {
+    check_template(s, &["name"])
+}
+
+/// Option heading used unless another is configured.
+pub const DEFAULT_OPTION_HEADING: &str = "`{name}`";
+
+/// Parse an options document title, which may use `{count}`.
+pub fn parse_title_template(s: &str) -> Result<String, String> {
+    check_template(s, &["count"])
+}
+
+/// Parse an `--option-heading` template, using `{name}` and `{type}`.
+pub fn parse_option_heading(s: &str) -> Result<String, String> {
+    check_template(s, &["name", "type"])
+}
+
+/// Parse a `--group-heading` template, using `{group}`.
+pub fn parse_group_heading(s: &str) -> Result<String, String> {
+    check_template(s, &["group"])
+}
+
+/// Format an option value as plain text, without markdown decoration
+pub fn option_value_text(value: &OptionValue) -> String {
+    match value {
+        OptionValue::Tagged(tagged) => tagged.text.clone().unwrap_or_default(),
+        OptionValue::String(s) => format!("\"{}\"", s),
+        OptionValue::Bool(b) => b.to_string(),
+        OptionValue::Number(n) => n.to_string(),
+        OptionValue::Array(arr) => serde_json::to_string(arr).unwrap_or_default(),
+        OptionValue::Object(obj) => serde_json::to_string(obj).unwrap_or_default(),
+        OptionValue::Null => "null".to_string(),
+    }
 }
 
 /// Options for rendering
@@ -256,6 +671,29 @@ pub struct RenderOptions {
     pub declarations_base_url: Option<String>,
     /// Revision for GitHub links
     pub revision: Option<String>,
+    /// Per-prefix declaration links, tried before `declarations_base_url`
+    pub declaration_profiles: Vec<DeclarationProfile>,
+    /// Sanitize raw HTML in descriptions and literal markdown values
+    pub html_policy: Option<HtmlPolicy>,
+    /// How links (including declaration links) are emitted
+    pub link_style: LinkStyle,
+    /// Maximum width for reflowed prose
+    pub wrap: Wrap,
+    /// How `:::` blocks are written
+    pub dialect: Dialect,
+    /// Language tags for code fences
+    pub code_langs: CodeLangs,
+    /// Link `{option}` roles and mentions of other options in descriptions
+    pub cross_links: bool,
+    /// List the options whose descriptions mention each option
+    pub referenced_by: bool,
+    /// Option heading, with `{name}` and `{type}` placeholders
+    pub option_heading: String,
+    /// Heading written before the options of each group, with a `{group}`
+    /// placeholder; options are then level 3 headings
+    pub group_heading: Option<String>,
+    /// Number of leading name components making up an option's group
+    pub group_depth: usize,
 }
 
 impl Default for RenderOptions {
@@ -265,60 +703,178 @@ impl Default for RenderOptions {
             include_declarations: true,
             declarations_base_url: None,
             revision: None,
+            declaration_profiles: Vec::new(),
+            html_policy: None,
+            link_style: LinkStyle::Inline,
+            wrap: Wrap::None,
+            dialect: Dialect::Nixpkgs,
+            code_langs: CodeLangs::default(),
+            cross_links: false,
+            referenced_by: false,
+            option_heading: DEFAULT_OPTION_HEADING.to_string(),
+            group_heading: None,
+            group_depth: 2,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The group of an option: the first `group_depth` components of its
+    /// name, leaving at least the last one out.
+    fn group_of(&self, name: &str) -> String {
+        let parts: Vec<&str> = name.split('.').collect();
+        let depth = self
+            .group_depth
+            .clamp(1, parts.len().saturating_sub(1).max(1));
+        parts[..depth].join(".")
+    }
+
+    /// The group heading to write before `name`, if it starts a new group.
+    fn group_start(&self, name: &str, previous: Option<&str>) -> Option<String> {
+        let template = self.group_heading.as_ref()?;
+        let group = self.group_of(name);
+        if previous.is_some_and(|previous| self.group_of(previous) == group) {
+            return None;
+        }
+        Some(format!(
+            "## {}\n\n",
+            expand_template(template, &[("group", &group)])
+        ))
+    }
+
+    /// Apply the HTML policy, if any, to a markdown fragment.
+    fn sanitize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.html_policy {
+            Some(policy) => sanitize_html(text, policy),
+            None => Cow::Borrowed(text),
         }
     }
 }
 
 /// Render a single option to CommonMark
-fn render_option(name: &str, opt: &OptionDef, opts: &RenderOptions) -> String {
+///
+/// Without the other options, only `{option}` roles are cross-linked.
+pub fn render_option(name: &str, opt: &OptionDef, opts: &RenderOptions) -> String {
     let mut output = String::new();
+    write_option(name, opt, opts, None, &mut output);
+    output
+}
+
+/// Write a single option as CommonMark onto `output`
+fn write_option(
+    name: &str,
+    opt: &OptionDef,
+    opts: &RenderOptions,
+    xrefs: Option<&CrossRefs>,
+    output: &mut String,
+) {
+    let start = output.len();
+    write_option_block(name, opt, opts, xrefs, output);
+    if opts.link_style == LinkStyle::Reference {
+        let label = make_anchor_id(name, &opts.anchor_prefix);
+        if let Cow::Owned(block) = reference_links(&output[start..], &label) {
+            output.truncate(start);
+            output.push_str(&block);
+        }
+    }
+    if let Cow::Owned(block) = opts.code_langs.remap(&output[start..]) {
+        output.truncate(start);
+        output.push_str(&block);
+    }
+    if let Wrap::Width(width) = opts.wrap {
+        let block = wrap_markdown(&output[start..], width);
+        output.truncate(start);
+        output.push_str(&block);
+    }
+    if let Cow::Owned(block) = to_dialect(&output[start..], opts.dialect) {
+        output.truncate(start);
+        output.push_str(&block);
+    }
+}
 
+/// Write the CommonMark for a single option, with inline links
+fn write_option_block(
+    name: &str,
+    opt: &OptionDef,
+    opts: &RenderOptions,
+    xrefs: Option<&CrossRefs>,
+    output: &mut String,
+) {
     // Header with anchor
     let anchor = make_anchor_id(name, &opts.anchor_prefix);
-    output.push_str(&format!("## `{}` {{#{}}}\n\n", name, anchor));
+    let level = if opts.group_heading.is_some() {
+        "###"
+    } else {
+        "##"
+    };
+    let heading = expand_template(
+        &opts.option_heading,
+        &[
+            ("name", name),
+            ("type", opt.option_type.as_deref().unwrap_or_default()),
+        ],
+    );
+    let _ = write!(output, "{} {} {{#{}}}\n\n", level, heading, anchor);
 
     // Type and read-only status
     if let Some(ref opt_type) = opt.option_type {
         let ro = if opt.read_only { " *(read only)*" } else { "" };
-        output.push_str(&format!("**Type:** `{}`{}\n\n", opt_type, ro));
+        let _ = write!(output, "**Type:** `{}`{}\n\n", opt_type, ro);
     }
 
     // Default value
     if let Some(ref default) = opt.default {
-        let formatted = format_option_value(default);
-        if formatted.contains('\n') {
-            output.push_str(&format!("**Default:**\n\n{}\n\n", formatted));
-        } else {
-            output.push_str(&format!("**Default:** {}\n\n", formatted));
-        }
+        let formatted = format_option_value(default, &opts.code_langs);
+        write_labeled_value(output, "Default", &opts.sanitize(&formatted));
     }
 
     // Description
     if let Some(ref desc) = opt.description {
         let desc_text = desc.as_str();
         if !desc_text.is_empty() {
-            output.push_str(desc_text);
+            let mut desc_text = route_raw_blocks(desc_text, RAW_FORMATS);
+            if opts.cross_links {
+                let known = xrefs.map(|x| &x.known);
+                if let (Cow::Owned(linked), _) =
+                    link_mentions(&desc_text, name, known, &opts.anchor_prefix)
+                {
+                    desc_text = Cow::Owned(linked);
+                }
+            }
+            output.push_str(&opts.sanitize(&desc_text));
             output.push_str("\n\n");
         }
     }
 
     // Example
     if let Some(ref example) = opt.example {
-        let formatted = format_option_value(example);
-        if formatted.contains('\n') {
-            output.push_str(&format!("**Example:**\n\n{}\n\n", formatted));
-        } else {
-            output.push_str(&format!("**Example:** {}\n\n", formatted));
-        }
+        let formatted = format_option_value(example, &opts.code_langs);
+        write_labeled_value(output, "Example", &opts.sanitize(&formatted));
     }
 
     // Related packages
-    if let Some(ref related) = opt.related_packages {
-        if !related.is_empty() {
-            output.push_str("**Related packages:**\n\n");
-            output.push_str(related);
-            output.push_str("\n\n");
+    if let Some(ref related) = opt.related_packages
+        && !related.is_empty()
+    {
+        output.push_str("**Related packages:**\n\n");
+        output.push_str(related);
+        output.push_str("\n\n");
+    }
+
+    // Options mentioning this one
+    if opts.referenced_by
+        && let Some(sources) = xrefs.and_then(|x| x.referenced_by.get(name))
+    {
+        output.push_str("**Referenced by:**\n\n");
+        for source in sources {
+            let _ = writeln!(
+                output,
+                "- [`{}`](#{})",
+                source,
+                make_anchor_id(source, &opts.anchor_prefix)
+            );
         }
+        output.push('\n');
     }
 
     // Declarations
@@ -327,23 +883,58 @@ fn render_option(name: &str, opt: &OptionDef, opts: &RenderOptions) -> String {
         for decl in &opt.declarations {
             let name = decl.name();
             if let Some(url) = decl.url() {
-                output.push_str(&format!("- [{}]({})\n", md_escape(name), url));
-            } else if let Some(ref base_url) = opts.declarations_base_url {
-                // Build URL from base + path
-                let url = if let Some(ref rev) = opts.revision {
-                    format!("{}/blob/{}/{}", base_url.trim_end_matches('/'), rev, name)
-                } else {
-                    format!("{}/blob/master/{}", base_url.trim_end_matches('/'), name)
-                };
-                output.push_str(&format!("- [{}]({})\n", md_escape(name), url));
+                let _ = writeln!(output, "- [{}]({})", md_escape(name), url);
+            } else if let Some(url) = declaration_url(name, opts) {
+                let _ = writeln!(output, "- [{}]({})", md_escape(name), url);
             } else {
-                output.push_str(&format!("- `{}`\n", name));
+                let _ = writeln!(output, "- `{}`", name);
             }
         }
         output.push('\n');
     }
+}
 
-    output
+/// Whether the declaration `path` is under `prefix`, which matches whole
+/// path segments: `private` matches `private/git.nix` but not `privateer.nix`.
+fn under_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => {
+            prefix.is_empty() || prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+        }
+        None => false,
+    }
+}
+
+/// Build the link for a declaration path from the profile with the longest
+/// matching prefix, or from the base URL if no profile matches.
+fn declaration_url(path: &str, opts: &RenderOptions) -> Option<String> {
+    let profile = opts
+        .declaration_profiles
+        .iter()
+        .filter(|p| under_prefix(path, &p.prefix))
+        .max_by_key(|p| p.prefix.len());
+    let (base_url, path, rev) = match profile {
+        Some(p) => (
+            p.base_url.as_str(),
+            path[p.prefix.len()..].trim_start_matches('/'),
+            p.revision.as_str(),
+        ),
+        None => (
+            opts.declarations_base_url.as_deref()?.trim_end_matches('/'),
+            path,
+            opts.revision.as_deref().unwrap_or("master"),
+        ),
+    };
+    Some(format!("{}/blob/{}/{}", base_url, rev, path))
+}
+
+/// Write a `**Label:**` line, moving multi-line values into their own block
+fn write_labeled_value(output: &mut String, label: &str, formatted: &str) {
+    if formatted.contains('\n') {
+        let _ = write!(output, "**{}:**\n\n{}\n\n", label, formatted);
+    } else {
+        let _ = write!(output, "**{}:** {}\n\n", label, formatted);
+    }
 }
 
 /// Get sort priority for an option name segment.
@@ -361,10 +952,10 @@ fn segment_priority(segment: &str) -> u8 {
 /// Compare two option names for sorting.
 /// Sorts with enable first, then package, then alphabetically within each segment.
 fn compare_option_names(a: &str, b: &str) -> std::cmp::Ordering {
-    let a_parts: Vec<&str> = a.split('.').collect();
-    let b_parts: Vec<&str> = b.split('.').collect();
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
 
-    for (a_seg, b_seg) in a_parts.iter().zip(b_parts.iter()) {
+    while let (Some(a_seg), Some(b_seg)) = (a_parts.next(), b_parts.next()) {
         // First compare by priority (enable < package < other)
         let priority_cmp = segment_priority(a_seg).cmp(&segment_priority(b_seg));
         if priority_cmp != std::cmp::Ordering::Equal {
@@ -377,24 +968,48 @@ fn compare_option_names(a: &str, b: &str) -> std::cmp::Ordering {
         }
     }
     // Shorter paths come first
-    a_parts.len().cmp(&b_parts.len())
+    a_parts.count().cmp(&b_parts.count())
 }
 
 /// Render all options to CommonMark
+///
+/// Options are rendered in parallel; the blocks are concatenated in sorted order,
+/// so the output is identical to a sequential render.
 pub fn render_options_to_commonmark(options: &OptionsMap, render_opts: &RenderOptions) -> String {
-    let mut output = String::new();
-
     // Sort options by name for consistent output
-    let mut names: Vec<&String> = options.keys().collect();
-    names.sort_by(|a, b| compare_option_names(a, b));
+    let mut entries: Vec<(&String, &OptionDef)> = options.iter().collect();
+    entries.sort_by(|(a, _), (b, _)| compare_option_names(a, b));
 
-    for name in names {
-        if let Some(opt) = options.get(name) {
-            output.push_str(&render_option(name, opt, render_opts));
-        }
-    }
+    let xrefs = (render_opts.cross_links || render_opts.referenced_by).then(|| {
+        let descriptions: Vec<(&str, Option<&str>)> = entries
+            .iter()
+            .map(|(name, opt)| (name.as_str(), opt.description.as_ref().map(|d| d.as_str())))
+            .collect();
+        CrossRefs::new(&descriptions, &render_opts.anchor_prefix)
+    });
 
-    output
+    // Each worker appends to its own buffer, so only one allocation per chunk
+    // is concatenated at the end rather than one per option.
+    // Group headings depend on the previous option, so they are found first.
+    let groups: Vec<Option<String>> = (0..entries.len())
+        .map(|i| {
+            let previous = i.checked_sub(1).map(|p| entries[p].0.as_str());
+            render_opts.group_start(entries[i].0, previous)
+        })
+        .collect();
+
+    entries
+        .par_iter()
+        .zip(&groups)
+        .fold(String::new, |mut output, ((name, opt), group)| {
+            if let Some(group) = group {
+                output.push_str(group);
+            }
+            write_option(name, opt, render_opts, xrefs.as_ref(), &mut output);
+            output
+        })
+        .collect::<Vec<String>>()
+        .concat()
 }
 
 /// Render options with a title and optional preamble
@@ -407,6 +1022,8 @@ pub fn render_options_document(
     let mut output = String::new();
 
     // Title
+    let count = options.len().to_string();
+    let title = expand_template(title, &[("count", &count)]);
     output.push_str(&format!("# {}\n\n", title));
 
     // Preamble
@@ -421,6 +1038,50 @@ pub fn render_options_document(
     output
 }
 
+/// Render options with a title and optional preamble while they are being parsed.
+///
+/// Options are written in input order as soon as each one is deserialized, instead of
+/// being collected and sorted first. This trades the `enable`/`package` ordering of
+/// [`render_options_document`] for bounded memory and immediate output. Group
+/// headings are written whenever the group changes, so options of a group that
+/// are not next to each other in the input repeat it, and the title cannot use
+/// the `{count}` placeholder.
+pub fn stream_options_document<R: Read, W: Write>(
+    reader: R,
+    writer: &mut W,
+    title: &str,
+    preamble: Option<&str>,
+    filters: &[OptionFilter],
+    render_opts: &RenderOptions,
+) -> Result<(), String> {
+    let write_err = |e: io::Error| format!("Error writing output: {}", e);
+
+    if title.contains("{count}") {
+        return Err(
+            "the title cannot use the {count} placeholder while streaming options".to_string(),
+        );
+    }
+    write!(writer, "# {}\n\n", title).map_err(write_err)?;
+    if let Some(pre) = preamble {
+        write!(writer, "{}\n\n", pre).map_err(write_err)?;
+    }
+
+    let mut result = Ok(());
+    let mut previous: Option<String> = None;
+    stream_options_where(reader, filters, |name, opt| {
+        if let Some(group) = render_opts.group_start(&name, previous.as_deref())
+            && result.is_ok()
+        {
+            result = writer.write_all(group.as_bytes());
+        }
+        if result.is_ok() {
+            result = writer.write_all(render_option(&name, &opt, render_opts).as_bytes());
+        }
+        previous = Some(name);
+    })?;
+    result.map_err(write_err)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -467,4 +1128,314 @@ mod tests {
         assert!(output.contains("Whether to enable test."));
         assert!(output.contains("**Example:** `true`"));
     }
+
+    #[test]
+    fn test_declaration_profiles() {
+        let json = r#"{
+            "programs.git.enable": {
+                "description": "Whether to enable git.",
+                "declarations": [
+                    "home-manager/modules/programs/git.nix",
+                    "nixos/modules/programs/git.nix",
+                    "private/modules/git.nix",
+                    "privateer/git.nix",
+                    "other/git.nix"
+                ]
+            }
+        }"#;
+        let options = parse_options_json(json).unwrap();
+        let opts = RenderOptions {
+            declarations_base_url: Some("https://github.com/NixOS/nixpkgs/".to_string()),
+            revision: Some("nixos-24.05".to_string()),
+            declaration_profiles: vec![
+                "home-manager/=https://github.com/nix-community/home-manager@release-24.05"
+                    .parse()
+                    .unwrap(),
+                "private=https://git.example.com/infra/".parse().unwrap(),
+            ],
+            ..RenderOptions::default()
+        };
+        let output = render_options_to_commonmark(&options, &opts);
+
+        assert!(output.contains(
+            "- [home-manager/modules/programs/git.nix](https://github.com/nix-community/home-manager/blob/release-24.05/modules/programs/git.nix)"
+        ));
+        assert!(output.contains(
+            "- [private/modules/git.nix](https://git.example.com/infra/blob/HEAD/modules/git.nix)"
+        ));
+        assert!(output.contains(
+            "- [nixos/modules/programs/git.nix](https://github.com/NixOS/nixpkgs/blob/nixos-24.05/nixos/modules/programs/git.nix)"
+        ));
+        assert!(output.contains(
+            "- [other/git.nix](https://github.com/NixOS/nixpkgs/blob/nixos-24.05/other/git.nix)"
+        ));
+        assert!(output.contains(
+            "- [privateer/git.nix](https://github.com/NixOS/nixpkgs/blob/nixos-24.05/privateer/git.nix)"
+        ));
+
+        let profile: DeclarationProfile =
+            "x=https://ci@git.example.com/infra@v1.2".parse().unwrap();
+        assert_eq!(profile.base_url, "https://ci@git.example.com/infra");
+        assert_eq!(profile.revision, "v1.2");
+        let profile: DeclarationProfile = "x=https://ci@git.example.com/infra".parse().unwrap();
+        assert_eq!(profile.base_url, "https://ci@git.example.com/infra");
+        assert_eq!(profile.revision, "HEAD");
+
+        assert!("no-separator".parse::<DeclarationProfile>().is_err());
+        assert!(
+            "x=https://example.com/repo@"
+                .parse::<DeclarationProfile>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stream_options_in_input_order() {
+        let json = r#"{
+            "test.zeta": { "description": "Last alphabetically." },
+            "test.enable": { "description": "Whether to enable test." }
+        }"#;
+
+        let mut names = vec![];
+        stream_options(json.as_bytes(), |name, _| names.push(name)).unwrap();
+        assert_eq!(names, vec!["test.zeta", "test.enable"]);
+
+        let mut output = Vec::new();
+        stream_options_document(
+            json.as_bytes(),
+            &mut output,
+            "Options",
+            None,
+            &[],
+            &RenderOptions::default(),
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("# Options\n\n## `test.zeta`"));
+        assert!(output.contains("## `test.enable`"));
+    }
+
+    #[test]
+    fn test_option_filters() {
+        let json = r#"{
+            "test.enable": {
+                "description": "Whether to enable test.",
+                "type": "boolean",
+                "default": { "_type": "literalExpression", "text": "false" }
+            },
+            "test.settings": {
+                "description": "Settings.",
+                "type": "submodule",
+                "readOnly": true
+            }
+        }"#;
+        let names = |filters: &[&str]| {
+            let filters: Vec<OptionFilter> = filters.iter().map(|f| f.parse().unwrap()).collect();
+            let mut names = vec![];
+            stream_options_where(json.as_bytes(), &filters, |name, _| names.push(name)).unwrap();
+            names
+        };
+        assert_eq!(names(&["type=~submodule"]), vec!["test.settings"]);
+        assert_eq!(names(&["readOnly=true"]), vec!["test.settings"]);
+        assert_eq!(names(&["readOnly!=true"]), vec!["test.enable"]);
+        assert_eq!(names(&["default.text=false"]), vec!["test.enable"]);
+        assert_eq!(
+            names(&["type!~sub", "description=~test"]),
+            vec!["test.enable"]
+        );
+        assert!(names(&["type=~bool", "readOnly=true"]).is_empty());
+
+        let filter: OptionFilter = "a.b=~x=y".parse().unwrap();
+        assert_eq!(
+            (filter.field.as_str(), filter.op, filter.value.as_str()),
+            ("a.b", FilterOp::Contains, "x=y")
+        );
+        assert!("=x".parse::<OptionFilter>().is_err());
+        assert!("type".parse::<OptionFilter>().is_err());
+    }
+
+    #[test]
+    fn test_heading_templates() {
+        let json = r#"{
+            "programs.git.enable": { "description": "", "type": "boolean" },
+            "programs.git.package": { "description": "", "type": "package" },
+            "programs.zsh.enable": { "description": "", "type": "boolean" },
+            "networking.hostName": { "description": "", "type": "string" }
+        }"#;
+        let options = parse_options_json(json).unwrap();
+        let render_opts = RenderOptions {
+            option_heading: "`{name}` ({type})".to_string(),
+            group_heading: Some("Options of `{group}`".to_string()),
+            ..Default::default()
+        };
+        let output = render_options_document(&options, "Options ({count})", None, &render_opts);
+        let headings: Vec<&str> = output.lines().filter(|l| l.starts_with('#')).collect();
+        assert_eq!(
+            headings,
+            [
+                "# Options (4)",
+                "## Options of `networking`",
+                "### `networking.hostName` (string) {#opt-networking-hostName}",
+                "## Options of `programs.git`",
+                "### `programs.git.enable` (boolean) {#opt-programs-git-enable}",
+                "### `programs.git.package` (package) {#opt-programs-git-package}",
+                "## Options of `programs.zsh`",
+                "### `programs.zsh.enable` (boolean) {#opt-programs-zsh-enable}",
+            ]
+        );
+
+        let mut streamed = Vec::new();
+        stream_options_document(
+            json.as_bytes(),
+            &mut streamed,
+            "Options",
+            None,
+            &[],
+            &render_opts,
+        )
+        .unwrap();
+        let streamed = String::from_utf8(streamed).unwrap();
+        assert_eq!(streamed.matches("## Options of `programs.git`").count(), 1);
+        assert!(
+            stream_options_document(
+                json.as_bytes(),
+                &mut Vec::new(),
+                "{count}",
+                None,
+                &[],
+                &render_opts
+            )
+            .is_err()
+        );
+
+        assert!(parse_option_heading("{name}: {default}").is_err());
+        assert!(parse_group_heading("{group}").is_ok());
+    }
+
+    #[test]
+    fn test_stream_options_reports_malformed_input() {
+        let err = parse_options_reader(r#"{ "test.enable": { "readOnly": "yes" } }"#.as_bytes())
+            .unwrap_err();
+        assert!(err.starts_with("Failed to parse options JSON"));
+    }
+
+    #[test]
+    fn test_schema_errors_name_option_and_field() {
+        let check = |option: &str| {
+            let json = format!(
+                r#"{{ "a.ok": {{ "description": "Fine." }}, "a.bad": {} }}"#,
+                option
+            );
+            parse_options_json(&json).unwrap_err()
+        };
+
+        let err = check(r#"{ "description": "Bad.", "readOnly": "yes" }"#);
+        assert!(
+            err.starts_with(
+                "Failed to parse options JSON: option 'a.bad': field 'readOnly': expected a boolean, found a string"
+            ),
+            "{}",
+            err
+        );
+        assert!(
+            check(r#"{ "type": "boolean" }"#)
+                .contains("option 'a.bad': missing field 'description'")
+        );
+        assert!(
+            check(r#"{ "description": "Bad.", "declarations": "a.nix" }"#)
+                .contains("field 'declarations': expected a list, found a string")
+        );
+        assert!(
+            check(r#"{ "description": "Bad.", "declarations": ["a.nix", { "url": "x" }] }"#)
+                .contains("field 'declarations[1]': missing 'name'")
+        );
+        assert!(
+            check(
+                r#"{ "description": "Bad.", "default": { "_type": "literalNix", "text": "1" } }"#
+            )
+            .contains("field 'default': unexpected _type 'literalNix'")
+        );
+        assert!(
+            check(r#"{ "description": { "_type": "docBook", "text": "Bad." } }"#)
+                .contains(
+                    "field 'description': unexpected _type 'docBook' (expected one of 'mdDoc', 'literalDocBook')"
+                )
+        );
+        // The DocBook of releases before markdown is accepted.
+        let options = parse_options_json(
+            r#"{ "a.docbook": { "description": { "_type": "literalDocBook", "text": "<para>Old.</para>" }, "default": { "_type": "literalDocBook", "text": "<literal>1</literal>" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options["a.docbook"]
+                .description
+                .as_ref()
+                .map(Description::as_str),
+            Some("<para>Old.</para>")
+        );
+
+        // Null descriptions, raw values and unknown fields are accepted.
+        let options = parse_options_json(
+            r#"{ "a.raw": { "description": null, "default": { "port": 80 }, "visible": true } }"#,
+        )
+        .unwrap();
+        assert!(options["a.raw"].description.is_none());
+    }
+
+    #[test]
+    fn test_cross_links() {
+        let json = r#"{
+            "services.foo.enable": { "description": "Whether to enable foo." },
+            "services.foo.port": {
+                "description": "Port, used if services.foo.enable is set. See {option}`services.bar.host` and `services.foo.enable`, not [services.foo.enable](https://example.com/services.foo.enable).\n\n```\nservices.foo.enable = true;\n```"
+            },
+            "services.foo.user": { "description": "Like `services.foo.port`, but for services.foo.user." }
+        }"#;
+        let options = parse_options_json(json).unwrap();
+        let opts = RenderOptions {
+            cross_links: true,
+            referenced_by: true,
+            ..RenderOptions::default()
+        };
+        let output = render_options_to_commonmark(&options, &opts);
+
+        assert!(output.contains(
+            "Port, used if [`services.foo.enable`](#opt-services-foo-enable) is set. See [`services.bar.host`](#opt-services-bar-host) and [`services.foo.enable`](#opt-services-foo-enable), not [services.foo.enable](https://example.com/services.foo.enable).\n\n```\nservices.foo.enable = true;\n```"
+        ));
+        // Options do not link to themselves.
+        assert!(output.contains(
+            "Like [`services.foo.port`](#opt-services-foo-port), but for services.foo.user."
+        ));
+        assert!(output.contains(
+            "Whether to enable foo.\n\n**Referenced by:**\n\n- [`services.foo.port`](#opt-services-foo-port)\n\n"
+        ));
+
+        // Streaming only knows the option being rendered, so only roles are linked.
+        let streamed = render_option("services.foo.port", &options["services.foo.port"], &opts);
+        assert!(
+            streamed
+                .contains("Port, used if services.foo.enable is set. See [`services.bar.host`]")
+        );
+        assert!(!streamed.contains("Referenced by"));
+    }
+
+    #[test]
+    fn test_parallel_render_is_sorted() {
+        let options: OptionsMap = (0..200)
+            .map(|i| {
+                let json = format!(r#"{{ "description": "Option {i}." }}"#);
+                (
+                    format!("test.opt{i:03}"),
+                    serde_json::from_str(&json).unwrap(),
+                )
+            })
+            .collect();
+
+        let output = render_options_to_commonmark(&options, &RenderOptions::default());
+        let headings: Vec<&str> = output.lines().filter(|l| l.starts_with("## ")).collect();
+        let mut sorted = headings.clone();
+        sorted.sort();
+        assert_eq!(headings.len(), 200);
+        assert_eq!(headings, sorted);
+    }
 }
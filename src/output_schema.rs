@@ -0,0 +1,354 @@
+//! JSON Schema of the JSON output.
+//!
+//! `--json-output` writes a document `{ "version": 1, "entries": [...] }`,
+//! `--json-output --stream` and `entry --format json` write single entries.
+//! The schemas here describe both, so consumers can validate the output or
+//! generate typed bindings from it. They are written by hand next to the
+//! serialized types ([`ManualEntry`](crate::ManualEntry) for version 1,
+//! [`structured::Entry`](crate::structured::Entry) for version 2) and have
+//! to be kept in step with them; the version is raised when a change is not
+//! backwards compatible.
+
+use serde_json::{Value, json};
+
+/// Version of the JSON output written by default.
+pub const JSON_OUTPUT_VERSION: u32 = 1;
+
+/// Latest version of the JSON output, selected with `--json-version`.
+pub const LATEST_JSON_OUTPUT_VERSION: u32 = 2;
+
+/// A type or `null`.
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+/// Definitions of the types making up an entry of version 2.
+fn definitions_v2() -> Value {
+    json!({
+        "entry": {
+            "description": "A documented binding.",
+            "type": "object",
+            "properties": {
+                "prefix": { "type": "string", "description": "Prefix of the category, e.g. 'lib'." },
+                "category": { "type": "string", "description": "Category, e.g. 'strings'." },
+                "name": { "type": "string", "description": "Attribute path of the binding within its file." },
+                "title": { "type": "string", "description": "Full title, e.g. 'lib.strings.concatStrings'." },
+                "kind": { "enum": ["function", "constant"] },
+                "source": nullable(json!({ "$ref": "#/$defs/span" })),
+                "location": nullable(json!({ "type": "string", "description": "Location given with --locs." })),
+                "description": { "type": "string", "description": "The doc comment without its type, inputs and example sections, as markdown." },
+                "type": nullable(json!({ "$ref": "#/$defs/typeSignature" })),
+                "arguments": { "type": "array", "items": { "$ref": "#/$defs/parameter" } },
+                "examples": { "type": "array", "items": { "$ref": "#/$defs/example" } },
+                "usage": nullable(json!({ "type": "string", "description": "Call of the function to paste, from --usage-snippets." })),
+                "value": nullable(json!({ "type": "string", "description": "Source of the value, for constants." })),
+                "stability": nullable(json!({ "enum": ["stable", "experimental", "deprecated"] })),
+                "deprecated": deprecation(),
+                "since": { "type": "string", "description": "Release the entry is available since, from the @since tag of the doc comment." },
+                "see_also": see_also(),
+                "returns": { "type": "string", "description": "What the function returns, from the @return tag of the doc comment, as markdown." },
+                "internal": { "const": true, "description": "Present on entries of bindings marked @internal, kept with --keep-internal." },
+                "anchor": { "type": "string", "description": "Anchor pinned by the @anchor tag or a {#id} line of the doc comment, in place of the derived one." },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Topics from the @tag lines of the doc comment."
+                },
+                "meta": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Metadata of the binding, as rendered values."
+                },
+                "sha256": sha256(),
+                "description_html": description_html()
+            },
+            "required": [
+                "prefix", "category", "name", "title", "kind", "source", "location", "description",
+                "type", "arguments", "examples", "usage", "value", "stability", "meta", "sha256"
+            ],
+            "additionalProperties": false
+        },
+        "span": {
+            "description": "1-based lines and columns, in characters; the end is exclusive.",
+            "type": "object",
+            "properties": {
+                "file": { "type": "string" },
+                "line": { "type": "integer" },
+                "column": { "type": "integer" },
+                "end_line": { "type": "integer" },
+                "end_column": { "type": "integer" }
+            },
+            "required": ["file", "line", "column", "end_line", "end_column"],
+            "additionalProperties": false
+        },
+        "parameter": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "positional" },
+                        "name": { "type": "string" },
+                        "doc": nullable(json!({ "type": "string" }))
+                    },
+                    "required": ["kind", "name", "doc"],
+                    "additionalProperties": false
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "pattern" },
+                        "attrs": { "type": "array", "items": { "$ref": "#/$defs/patternAttr" } },
+                        "ellipsis": { "type": "boolean" },
+                        "bind": nullable(json!({ "type": "string" }))
+                    },
+                    "required": ["kind", "attrs", "ellipsis", "bind"],
+                    "additionalProperties": false
+                }
+            ]
+        },
+        "patternAttr": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "optional": { "type": "boolean" },
+                "default": nullable(json!({ "type": "string" })),
+                "doc": nullable(json!({ "type": "string" }))
+            },
+            "required": ["name", "optional", "default", "doc"],
+            "additionalProperties": false
+        },
+        "example": {
+            "type": "object",
+            "properties": {
+                "title": nullable(json!({ "type": "string" })),
+                "description": nullable(json!({ "type": "string" })),
+                "lang": nullable(json!({ "type": "string" })),
+                "code": { "type": "string" }
+            },
+            "required": ["title", "description", "lang", "code"],
+            "additionalProperties": false
+        },
+        "typeSignature": type_signature()
+    })
+}
+
+/// Schema of the `deprecated` field, present on deprecated entries only.
+fn deprecation() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "since": nullable(json!({ "type": "string", "description": "Release the entry is deprecated since." })),
+            "note": nullable(json!({ "type": "string", "description": "What to use instead, as markdown." }))
+        },
+        "required": ["since", "note"],
+        "additionalProperties": false,
+        "description": "From the @deprecated tag of the doc comment."
+    })
+}
+
+/// Schema of the `see_also` field, present on entries with `@see` tags only.
+fn see_also() -> Value {
+    json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "The function as written after @see." },
+                "url": nullable(json!({ "type": "string", "description": "Link to its documentation, if it was resolved." }))
+            },
+            "required": ["name", "url"],
+            "additionalProperties": false
+        },
+        "description": "Related functions, from the @see tags of the doc comment."
+    })
+}
+
+/// Definition of the content hash of an entry, the same in every version.
+fn sha256() -> Value {
+    json!({
+        "type": "string",
+        "description": "SHA-256 of the entry's title, docs, type, parameters, example, value, stability, deprecation, release, related functions, return value, internal marker, tags and metadata, changing only with them."
+    })
+}
+
+/// Definition of the description rendered as HTML, the same in every
+/// version.
+fn description_html() -> Value {
+    json!({
+        "type": "string",
+        "description": "The description rendered as HTML, with --json-html."
+    })
+}
+
+/// Definition of a parsed `# Type` signature, the same in every version.
+fn type_signature() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "text": { "type": "string" },
+            "name": nullable(json!({ "type": "string" })),
+            "args": { "type": "array", "items": { "type": "string" } },
+            "returns": { "type": "string" }
+        },
+        "required": ["text", "name", "args", "returns"],
+        "additionalProperties": false
+    })
+}
+
+/// Definitions of the types making up an entry of version 1.
+fn definitions_v1() -> Value {
+    json!({
+        "entry": {
+            "description": "A documented binding.",
+            "type": "object",
+            "properties": {
+                "prefix": { "type": "string", "description": "Prefix of the category, e.g. 'lib'." },
+                "category": { "type": "string", "description": "Category, e.g. 'strings'." },
+                "location": nullable(json!({ "type": "string", "description": "Source location of the binding." })),
+                "name": { "type": "string", "description": "Attribute path of the binding within its file." },
+                "fn_type": nullable(json!({ "type": "string", "description": "Type signature, if not given by a `# Type` section." })),
+                "description": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Paragraphs of the doc comment, as markdown."
+                },
+                "example": nullable(json!({ "type": "string" })),
+                "usage": { "type": "string", "description": "Call of the function to paste, from --usage-snippets." },
+                "args": { "type": "array", "items": { "$ref": "#/$defs/argument" } },
+                "signature": nullable(json!({ "$ref": "#/$defs/signature" })),
+                "kind": { "enum": ["function", "constant"] },
+                "value": nullable(json!({ "type": "string", "description": "Source of the value, for constants." })),
+                "stability": nullable(json!({ "enum": ["stable", "experimental", "deprecated"] })),
+                "deprecated": deprecation(),
+                "since": { "type": "string", "description": "Release the entry is available since, from the @since tag of the doc comment." },
+                "see_also": see_also(),
+                "returns": { "type": "string", "description": "What the function returns, from the @return tag of the doc comment, as markdown." },
+                "internal": { "const": true, "description": "Present on entries of bindings marked @internal, kept with --keep-internal." },
+                "anchor": { "type": "string", "description": "Anchor pinned by the @anchor tag or a {#id} line of the doc comment, in place of the derived one." },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Topics from the @tag lines of the doc comment."
+                },
+                "meta": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Metadata of the binding, as rendered values."
+                },
+                "sha256": sha256(),
+                "description_html": description_html()
+            },
+            "required": [
+                "prefix", "category", "location", "name", "fn_type", "description", "example",
+                "args", "signature", "kind", "value", "stability", "sha256"
+            ],
+            "additionalProperties": false
+        },
+        "singleArg": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "doc": nullable(json!({ "type": "string" }))
+            },
+            "required": ["name", "doc"],
+            "additionalProperties": false
+        },
+        "argument": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": { "Flat": { "$ref": "#/$defs/singleArg" } },
+                    "required": ["Flat"],
+                    "additionalProperties": false
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "Pattern": { "type": "array", "items": { "$ref": "#/$defs/singleArg" } }
+                    },
+                    "required": ["Pattern"],
+                    "additionalProperties": false
+                }
+            ]
+        },
+        "signature": {
+            "type": "object",
+            "properties": {
+                "params": { "type": "array", "items": { "$ref": "#/$defs/param" } },
+                "type": nullable(json!({ "$ref": "#/$defs/typeSignature" }))
+            },
+            "required": ["params", "type"],
+            "additionalProperties": false
+        },
+        "param": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "positional" },
+                        "name": { "type": "string" }
+                    },
+                    "required": ["kind", "name"],
+                    "additionalProperties": false
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "pattern" },
+                        "attrs": { "type": "array", "items": { "$ref": "#/$defs/patternAttr" } },
+                        "ellipsis": { "type": "boolean" },
+                        "bind": nullable(json!({ "type": "string" }))
+                    },
+                    "required": ["kind", "attrs", "ellipsis", "bind"],
+                    "additionalProperties": false
+                }
+            ]
+        },
+        "patternAttr": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "optional": { "type": "boolean" },
+                "default": nullable(json!({ "type": "string" }))
+            },
+            "required": ["name", "optional", "default"],
+            "additionalProperties": false
+        },
+        "typeSignature": type_signature()
+    })
+}
+
+/// Definitions of the types making up an entry of `version`.
+fn definitions(version: u32) -> Value {
+    match version {
+        1 => definitions_v1(),
+        _ => definitions_v2(),
+    }
+}
+
+/// Schema of the document written by `--json-output` in `version`.
+pub fn document_schema(version: u32) -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "docgen JSON output",
+        "type": "object",
+        "properties": {
+            "version": { "const": version },
+            "entries": { "type": "array", "items": { "$ref": "#/$defs/entry" } }
+        },
+        "required": ["version", "entries"],
+        "additionalProperties": false,
+        "$defs": definitions(version)
+    })
+}
+
+/// Schema of a single entry in `version`, as written by `--json-output
+/// --stream` and `entry --format json`.
+pub fn entry_schema(version: u32) -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "docgen entry",
+        "$ref": "#/$defs/entry",
+        "$defs": definitions(version)
+    })
+}
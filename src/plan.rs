@@ -0,0 +1,196 @@
+//! What a `manifest` run would do, as printed by `docgen plan`.
+//!
+//! The plan resolves the configuration file, the command line flags and the
+//! previous manifest into the documents that would be written, the bindings
+//! of each input that would and would not be documented (and why), and the
+//! files that would be removed. Nothing is written.
+
+use serde::Serialize;
+use std::fmt::Write as _;
+
+/// How the plan is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PlanFormat {
+    /// Aligned text for reading in a terminal
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    /// Configuration file with visibility rules, if any.
+    pub config: Option<String>,
+    pub output_dir: String,
+    pub manifest: String,
+    /// Whether the manifest of a previous run was found.
+    pub previous_manifest: bool,
+    /// Written as an object, in this order.
+    #[serde(serialize_with = "settings_map")]
+    pub settings: Vec<Setting>,
+    pub inputs: Vec<Input>,
+    /// Documents of the previous run that `--prune` would delete.
+    pub prune: Vec<String>,
+    /// Redirect map that would be updated, with `--redirects`.
+    pub redirects: Option<String>,
+    /// mdBook summary that would be written, with `--summary`.
+    pub summary: Option<String>,
+    /// Navigation file that would be written, with `--navigation`.
+    pub navigation: Option<String>,
+    /// Problems that would make the run fail.
+    pub errors: Vec<String>,
+}
+
+/// A rendering option and its effective value.
+#[derive(Debug, Clone, Serialize)]
+pub struct Setting {
+    pub name: String,
+    pub value: String,
+}
+
+/// An input file and the document it would be rendered to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Input {
+    pub file: String,
+    pub category: String,
+    pub output: String,
+    pub format: String,
+    pub landing_page: Option<String>,
+    /// Titles of the entries that would be documented.
+    pub entries: Vec<String>,
+    /// Public bindings without a doc comment.
+    pub undocumented: Vec<String>,
+    /// Public bindings hidden by visibility rules.
+    pub hidden: Vec<String>,
+}
+
+impl Setting {
+    pub fn new(name: &str, value: impl Into<String>) -> Self {
+        Setting {
+            name: name.to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+fn settings_map<S: serde::Serializer>(settings: &[Setting], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_map(
+        settings
+            .iter()
+            .map(|setting| (&setting.name, &setting.value)),
+    )
+}
+
+/// Lines of cells padded to the widest cell of each column.
+fn table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut output = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            let _ = write!(line, "{:<width$}  ", cell, width = width);
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn names(list: &[String]) -> String {
+    if list.is_empty() {
+        "-".to_string()
+    } else {
+        list.join(", ")
+    }
+}
+
+/// Render the plan as aligned text.
+pub fn render_table(plan: &Plan) -> String {
+    let mut output = String::new();
+    let manifest = if plan.previous_manifest {
+        format!("{} (previous run found)", plan.manifest)
+    } else {
+        format!("{} (no previous run)", plan.manifest)
+    };
+    let mut summary = vec![
+        vec![
+            "config".to_string(),
+            plan.config.clone().unwrap_or_else(|| "(none)".to_string()),
+        ],
+        vec!["output dir".to_string(), plan.output_dir.clone()],
+        vec!["manifest".to_string(), manifest],
+    ];
+    if let Some(redirects) = &plan.redirects {
+        summary.push(vec!["redirects".to_string(), redirects.clone()]);
+    }
+    if let Some(path) = &plan.summary {
+        summary.push(vec!["summary".to_string(), path.clone()]);
+    }
+    if let Some(path) = &plan.navigation {
+        summary.push(vec!["navigation".to_string(), path.clone()]);
+    }
+    summary.extend(
+        plan.settings
+            .iter()
+            .map(|s| vec![s.name.clone(), s.value.clone()]),
+    );
+    output.push_str(&table(&summary));
+
+    let mut rows = vec![
+        [
+            "input",
+            "category",
+            "output",
+            "format",
+            "entries",
+            "undocumented",
+            "hidden",
+        ]
+        .map(String::from)
+        .to_vec(),
+    ];
+    rows.extend(plan.inputs.iter().map(|input| {
+        vec![
+            input.file.clone(),
+            input.category.clone(),
+            input.output.clone(),
+            input.format.clone(),
+            input.entries.len().to_string(),
+            input.undocumented.len().to_string(),
+            input.hidden.len().to_string(),
+        ]
+    }));
+    output.push('\n');
+    output.push_str(&table(&rows));
+
+    for input in &plan.inputs {
+        let _ = write!(output, "\n{}\n", input.file);
+        let mut details = vec![];
+        if let Some(landing) = &input.landing_page {
+            details.push(vec!["  landing page".to_string(), landing.clone()]);
+        }
+        details.push(vec![
+            "  undocumented".to_string(),
+            names(&input.undocumented),
+        ]);
+        details.push(vec!["  hidden".to_string(), names(&input.hidden)]);
+        output.push_str(&table(&details));
+    }
+
+    if !plan.prune.is_empty() {
+        let _ = write!(output, "\nwould remove: {}\n", plan.prune.join(", "));
+    }
+    for error in &plan.errors {
+        let _ = writeln!(output, "\nerror: {}", error);
+    }
+    output
+}
@@ -0,0 +1,77 @@
+//! Reproducibility metadata of generated documents.
+//!
+//! Unless `--no-metadata` is given, every document ends with a comment
+//! naming the docgen version, the command that generated it and the
+//! SHA-256 of each of its inputs, so a published page can be traced back to
+//! what it was generated from:
+//!
+//! ```markdown
+//! <!--
+//! generator: docgen 1.0.0
+//! command: docgen --file lib/strings.nix --category strings
+//! source: lib/strings.nix sha256:5f0c…
+//! -->
+//! ```
+//!
+//! The comment holds no timestamp, so it is the same for the same inputs and
+//! flags; `--no-metadata` leaves it out for builds that compare output
+//! across docgen versions or invocations.
+
+use crate::manifest::Source;
+
+/// Name and version of docgen, as recorded in generated files.
+pub fn generator() -> String {
+    format!("docgen {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Where a document comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub generator: String,
+    /// The command line, program name first.
+    pub command: Vec<String>,
+    pub sources: Vec<Source>,
+}
+
+/// `arg` quoted for a POSIX shell, if it needs to be.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./=:,@%+".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+impl Provenance {
+    pub fn new(command: Vec<String>, sources: Vec<Source>) -> Self {
+        Provenance {
+            generator: generator(),
+            command,
+            sources,
+        }
+    }
+
+    /// The metadata as lines of `key: value`, without a comment around
+    /// them.
+    pub fn text(&self) -> String {
+        let mut text = format!("generator: {}\n", self.generator);
+        if !self.command.is_empty() {
+            let command: Vec<String> = self.command.iter().map(|arg| shell_quote(arg)).collect();
+            text.push_str(&format!("command: {}\n", command.join(" ")));
+        }
+        for source in &self.sources {
+            text.push_str(&format!(
+                "source: {} sha256:{}\n",
+                source.path, source.sha256
+            ));
+        }
+        text
+    }
+}
+
+/// `text` as an HTML comment, with `--` sequences that would end it broken
+/// up.
+pub fn html_comment(text: &str) -> String {
+    format!("<!--\n{}-->\n", text.replace("-->", "-- >"))
+}
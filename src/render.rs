@@ -0,0 +1,249 @@
+//! Output backends of the default command.
+//!
+//! A [`Renderer`] turns a [`Document`], the entries of one or more Nix files
+//! together with their CommonMark, into a document of its format. The
+//! [`CommonMark`] renderer is the reference: it writes the markdown every
+//! other format is derived from, and the built-in formats are [`Converter`]s
+//! of that markdown. `--format` picks a renderer from a [`Registry`] by name,
+//! so a backend of its own is a type implementing the trait, registered
+//! next to the built-in ones:
+//!
+//! ```
+//! use docgen::render::{Document, Registry, Renderer};
+//!
+//! struct Names;
+//!
+//! impl Renderer for Names {
+//!     fn name(&self) -> &'static str {
+//!         "names"
+//!     }
+//!     fn about(&self) -> &'static str {
+//!         "The documented names, one per line"
+//!     }
+//!     fn render(&self, document: &Document) -> String {
+//!         document
+//!             .entries()
+//!             .map(|entry| format!("{}\n", entry.name))
+//!             .collect()
+//!     }
+//! }
+//!
+//! let mut registry = Registry::new();
+//! registry.register(Names);
+//! assert!(registry.get("names").is_some());
+//! ```
+
+use crate::commonmark::ManualEntry;
+use crate::format::CodeLangs;
+use crate::provenance::html_comment;
+use crate::{asciidoc, docbook, mdx, rst, typst};
+
+/// The documented entries of a Nix file, with the markdown introducing them.
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    /// The file's doc comment or description, as CommonMark.
+    pub description: String,
+    pub entries: Vec<ManualEntry>,
+}
+
+/// A document to render: its sections, and the whole of it as CommonMark.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    /// Identifier of the page, such as its category.
+    pub id: String,
+    pub sections: Vec<Section>,
+    /// The document as written by the [`CommonMark`] renderer.
+    pub markdown: String,
+}
+
+impl Document {
+    /// A document of `sections`, each its description followed by the
+    /// sections of its entries (see [`ManualEntry::write_section`]).
+    pub fn new(id: &str, sections: Vec<Section>, anchor_prefix: &str) -> Document {
+        let code_langs = CodeLangs::default();
+        let mut markdown = String::new();
+        for section in &sections {
+            if !section.description.is_empty() {
+                markdown.push_str(section.description.trim_end());
+                markdown.push_str("\n\n");
+            }
+            for entry in &section.entries {
+                entry.write_section(anchor_prefix, &code_langs, &mut markdown);
+            }
+        }
+        Document {
+            id: id.to_string(),
+            sections,
+            markdown,
+        }
+    }
+
+    /// The entries of all sections, in order.
+    pub fn entries(&self) -> impl Iterator<Item = &ManualEntry> {
+        self.sections.iter().flat_map(|section| &section.entries)
+    }
+}
+
+/// An output format.
+pub trait Renderer {
+    /// Name selecting the renderer, as in `--format NAME`.
+    fn name(&self) -> &'static str;
+
+    /// One line describing the format, for `--help`.
+    fn about(&self) -> &'static str;
+
+    /// Render `document` in this format.
+    fn render(&self, document: &Document) -> String;
+
+    /// `text` as a comment of this format, for the metadata ending each
+    /// document (see [`crate::provenance`]).
+    fn comment(&self, text: &str) -> String {
+        html_comment(text)
+    }
+}
+
+/// CommonMark, as used by the nixpkgs manual.
+pub struct CommonMark;
+
+impl Renderer for CommonMark {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn about(&self) -> &'static str {
+        "CommonMark, as used by the nixpkgs manual"
+    }
+
+    fn render(&self, document: &Document) -> String {
+        document.markdown.clone()
+    }
+}
+
+/// A format converted from the CommonMark of the document.
+pub struct Converter {
+    name: &'static str,
+    about: &'static str,
+    convert: fn(&Document) -> String,
+    comment: fn(&str) -> String,
+}
+
+impl Converter {
+    pub fn new(name: &'static str, about: &'static str, convert: fn(&Document) -> String) -> Self {
+        Converter {
+            name,
+            about,
+            convert,
+            comment: html_comment,
+        }
+    }
+
+    /// Write comments with `comment`, rather than as HTML comments.
+    pub fn with_comment(mut self, comment: fn(&str) -> String) -> Self {
+        self.comment = comment;
+        self
+    }
+}
+
+impl Renderer for Converter {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn about(&self) -> &'static str {
+        self.about
+    }
+
+    fn render(&self, document: &Document) -> String {
+        (self.convert)(document)
+    }
+
+    fn comment(&self, text: &str) -> String {
+        (self.comment)(text)
+    }
+}
+
+/// The renderers `--format` chooses from, by name.
+pub struct Registry {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    /// The built-in renderers, CommonMark first.
+    pub fn new() -> Self {
+        let mut registry = Registry { renderers: vec![] };
+        registry.register(CommonMark);
+        registry.register(
+            Converter::new(
+                "rst",
+                "reStructuredText for Sphinx, with `.. _anchor:` targets",
+                |document| rst::markdown_to_rst(&document.markdown),
+            )
+            .with_comment(rst::comment),
+        );
+        registry.register(
+            Converter::new(
+                "docbook",
+                "DocBook 5, with headings as sections and anchors as `xml:id`s",
+                |document| docbook::markdown_to_docbook(&document.markdown),
+            )
+            .with_comment(docbook::comment),
+        );
+        registry.register(
+            Converter::new(
+                "mdx",
+                "MDX for Docusaurus, with frontmatter and escaped JSX syntax",
+                |document| mdx::markdown_to_mdx(&document.markdown, &document.id),
+            )
+            .with_comment(mdx::comment),
+        );
+        registry.register(
+            Converter::new(
+                "asciidoc",
+                "AsciiDoc for Asciidoctor and Antora, with `[[anchor]]` ids",
+                |document| asciidoc::markdown_to_asciidoc(&document.markdown),
+            )
+            .with_comment(asciidoc::comment),
+        );
+        registry.register(
+            Converter::new(
+                "typst",
+                "Typst, with `<anchor>` labels, for PDF manuals",
+                |document| typst::markdown_to_typst(&document.markdown),
+            )
+            .with_comment(typst::comment),
+        );
+        registry
+    }
+
+    /// Add `renderer`, in place of a renderer of the same name.
+    pub fn register(&mut self, renderer: impl Renderer + 'static) {
+        let renderer: Box<dyn Renderer> = Box::new(renderer);
+        match self
+            .renderers
+            .iter_mut()
+            .find(|r| r.name() == renderer.name())
+        {
+            Some(existing) => *existing = renderer,
+            None => self.renderers.push(renderer),
+        }
+    }
+
+    /// The renderer named `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn Renderer> {
+        self.renderers
+            .iter()
+            .find(|r| r.name() == name)
+            .map(|r| r.as_ref())
+    }
+
+    /// The renderers in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Renderer> {
+        self.renderers.iter().map(|r| r.as_ref())
+    }
+}
@@ -0,0 +1,489 @@
+//! reStructuredText output.
+//!
+//! The generated CommonMark is converted as a whole, so the document can be
+//! included in a Sphinx manual:
+//!
+//! - headings become section titles underlined by level (`=`, `-`, `~`, `^`,
+//!   `"`, `'`), preceded by a `.. _anchor:` target if they have an `{#id}`,
+//! - links to `#anchor` become `:ref:` roles, other links anonymous
+//!   hyperlinks,
+//! - code blocks become `code-block` directives, tables `list-table`
+//!   directives and HTML blocks `raw` directives,
+//! - lists, block quotes and definition lists are indented as RST expects,
+//! - `note`, `tip`, `important`, `warning` and `caution` blocks become
+//!   admonition directives and `example` blocks `admonition` directives
+//!   titled by their heading, preceded by a `.. _anchor:` target if they
+//!   have an id; headings within other `:::` blocks become rubrics.
+//!
+//! Inline HTML is dropped, keeping the text between the tags, and the lines
+//! opening and closing other `:::` blocks are removed.
+
+use crate::format::{DivFence, div_fence, get_fence, trim_leading_whitespace};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::fmt::Write as _;
+
+/// Underline characters of section titles, by heading level.
+const UNDERLINES: [char; 6] = ['=', '-', '~', '^', '"', '\''];
+
+/// `:::` blocks with a directive of their own.
+const ADMONITIONS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Escape text so it is not read as inline markup.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | '*' | '`' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // A trailing underscore makes a word a reference.
+            '_' if !chars.peek().is_some_and(|n| n.is_alphanumeric()) => escaped.push_str("\\_"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Inline code as an inline literal, or as escaped text if it cannot be one.
+fn literal(code: &str) -> String {
+    if code.contains("``") || code.ends_with('`') || code.is_empty() {
+        // Literals cannot hold these; fall back to escaped text.
+        return escape(code);
+    }
+    format!("``{}``", code)
+}
+
+/// Inline markup ends and starts only next to whitespace or punctuation; an
+/// escaped space separates it from adjacent word characters.
+fn separate(buf: &mut String) {
+    if buf.chars().last().is_some_and(|c| c.is_alphanumeric()) {
+        buf.push_str("\\ ");
+    }
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    has_header: bool,
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Indentation of the enclosing blocks.
+    indent: Vec<String>,
+    /// Marker replacing the innermost indentation on the next line written.
+    marker: Option<String>,
+    /// Inline content of the current block.
+    buf: String,
+    /// Set after inline markup, which must not be followed by a word character.
+    after_markup: bool,
+    /// Targets of the links being written, with the plain text of each and
+    /// where it starts in `buf`.
+    links: Vec<(String, String, usize)>,
+    lists: Vec<Option<u64>>,
+    table: Option<Table>,
+    code: Option<String>,
+    /// Level and id of the heading being written.
+    heading: Option<(usize, Option<String>)>,
+    /// Whether each open `:::` block is written as an indented directive.
+    blocks: Vec<bool>,
+    /// Set after opening an example block until its title is known.
+    example: bool,
+}
+
+impl Writer {
+    fn prefix(&mut self) -> String {
+        match self.marker.take() {
+            Some(marker) => {
+                let outer = self.indent.len().saturating_sub(1);
+                format!("{}{}", self.indent[..outer].concat(), marker)
+            }
+            None => self.indent.concat(),
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        let prefix = self.prefix();
+        if line.is_empty() {
+            self.out.push_str(prefix.trim_end());
+        } else {
+            self.out.push_str(&prefix);
+            self.out.push_str(line);
+        }
+        self.out.push('\n');
+    }
+
+    fn blank(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Write the pending inline content as a paragraph.
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.buf);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        for line in text.lines() {
+            self.line(line.trim_end());
+        }
+        self.blank();
+        self.after_markup = false;
+    }
+
+    fn text(&mut self, text: &str) {
+        if std::mem::take(&mut self.after_markup)
+            && text.chars().next().is_some_and(|c| c.is_alphanumeric())
+        {
+            self.buf.push_str("\\ ");
+        }
+        if let Some((_, plain, _)) = self.links.last_mut() {
+            plain.push_str(text);
+        }
+        self.buf.push_str(&escape(text));
+    }
+
+    fn markup(&mut self, markup: &str, closing: bool) {
+        if closing {
+            self.buf.push_str(markup);
+            self.after_markup = true;
+        } else {
+            separate(&mut self.buf);
+            self.buf.push_str(markup);
+            self.after_markup = false;
+        }
+    }
+
+    fn heading(&mut self, level: usize, id: Option<&str>) {
+        let title = std::mem::take(&mut self.buf);
+        let title = title.trim().replace('\n', " ");
+        self.after_markup = false;
+        if std::mem::take(&mut self.example) {
+            // The heading of an example is its title.
+            self.open_example(&title);
+            return;
+        }
+        if !self.blocks.is_empty() {
+            // Directives cannot hold sections.
+            self.line(&format!(".. rubric:: {}", title));
+            self.blank();
+            return;
+        }
+        if let Some(id) = id {
+            self.line(&format!(".. _{}:", id));
+            self.blank();
+        }
+        let underline = UNDERLINES[level.clamp(1, 6) - 1]
+            .to_string()
+            .repeat(title.chars().count().max(1));
+        self.line(&title);
+        self.line(&underline);
+        self.blank();
+    }
+
+    fn open_example(&mut self, title: &str) {
+        self.line(&format!(".. admonition:: {}", title));
+        self.line("   :class: example");
+        self.blank();
+        self.indent.push("   ".to_string());
+    }
+
+    fn open_block(&mut self, classes: &[&str], id: Option<&str>) {
+        self.flush();
+        let class = classes.first().copied();
+        let directive = class == Some("example") || ADMONITIONS.contains(&class.unwrap_or(""));
+        if let Some(id) = id {
+            self.line(&format!(".. _{}:", id));
+            self.blank();
+        }
+        match class {
+            Some("example") => self.example = true,
+            Some(admonition) if directive => {
+                self.line(&format!(".. {}::", admonition));
+                self.blank();
+                self.indent.push("   ".to_string());
+            }
+            _ => {}
+        }
+        self.blocks.push(directive);
+    }
+
+    fn close_block(&mut self) {
+        self.flush();
+        if std::mem::take(&mut self.example) {
+            // An example without a heading is titled by its class.
+            self.open_example("Example");
+        }
+        if self.blocks.pop() == Some(true) {
+            self.indent.pop();
+            self.blank();
+        }
+    }
+
+    /// Convert a chunk of CommonMark between `:::` lines.
+    fn convert(&mut self, markdown: &str) {
+        let options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_DEFINITION_LIST;
+        for event in Parser::new_ext(markdown, options) {
+            if self.example
+                && self.heading.is_none()
+                && !matches!(event, Event::Start(Tag::Heading { .. }))
+            {
+                self.example = false;
+                self.open_example("Example");
+            }
+            match event {
+                Event::Start(Tag::Heading { level, ref id, .. }) => {
+                    self.heading = Some((level as usize, id.as_ref().map(|id| id.to_string())));
+                    self.event(event);
+                }
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, id)) = self.heading.take() {
+                        self.heading(level, id.as_deref());
+                    }
+                }
+                event => self.event(event),
+            }
+        }
+        self.flush();
+    }
+
+    fn table(&mut self, table: Table) {
+        self.line(".. list-table::");
+        if table.has_header {
+            self.line("   :header-rows: 1");
+        }
+        self.blank();
+        for row in table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let marker = if i == 0 { "   * - " } else { "     - " };
+                self.line(&format!("{}{}", marker, cell.trim()));
+            }
+        }
+        self.blank();
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some(code) = &mut self.code {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let code = self.code.take().unwrap_or_default();
+                    self.indent.push("   ".to_string());
+                    for line in code.trim_end_matches('\n').lines() {
+                        self.line(line);
+                    }
+                    self.indent.pop();
+                    self.blank();
+                }
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Paragraph) => self.flush(),
+            Event::End(TagEnd::Paragraph) => self.flush(),
+            Event::Start(Tag::Heading { .. }) => self.flush(),
+            Event::End(TagEnd::Heading(_)) => {}
+            Event::Start(Tag::CodeBlock(kind)) => {
+                self.flush();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                if lang.is_empty() {
+                    self.line("::");
+                } else {
+                    self.line(&format!(".. code-block:: {}", lang));
+                }
+                self.blank();
+                self.code = Some(String::new());
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+                self.blank();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        let marker = format!("{}. ", n);
+                        *n += 1;
+                        marker
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.indent.push(" ".repeat(marker.len()));
+                self.marker = Some(marker);
+            }
+            Event::End(TagEnd::Item) => {
+                self.flush();
+                self.indent.pop();
+                self.marker = None;
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush();
+                self.indent.push("   ".to_string());
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush();
+                self.indent.pop();
+            }
+            Event::Start(Tag::DefinitionList) | Event::End(TagEnd::DefinitionList) => self.flush(),
+            Event::Start(Tag::DefinitionListTitle) => self.flush(),
+            Event::End(TagEnd::DefinitionListTitle) => {
+                let term = std::mem::take(&mut self.buf);
+                self.after_markup = false;
+                self.line(term.trim());
+            }
+            Event::Start(Tag::DefinitionListDefinition) => {
+                self.indent.push("   ".to_string());
+            }
+            Event::End(TagEnd::DefinitionListDefinition) => {
+                self.flush();
+                self.indent.pop();
+                self.blank();
+            }
+            Event::Start(Tag::Table(_)) => {
+                self.flush();
+                self.table = Some(Table::default());
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(table) = self.table.take() {
+                    self.table(table);
+                }
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some(table) = &mut self.table {
+                    table.has_header = true;
+                    table.rows.push(vec![]);
+                }
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(vec![]);
+                }
+            }
+            Event::End(TagEnd::TableCell) => {
+                let cell = std::mem::take(&mut self.buf);
+                self.after_markup = false;
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            Event::Start(Tag::Emphasis) => self.markup("*", false),
+            Event::End(TagEnd::Emphasis) => self.markup("*", true),
+            Event::Start(Tag::Strong) => self.markup("**", false),
+            Event::End(TagEnd::Strong) => self.markup("**", true),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                separate(&mut self.buf);
+                self.links
+                    .push((dest_url.to_string(), String::new(), self.buf.len()));
+            }
+            Event::End(TagEnd::Link) => {
+                // The text is written again, as plain text, with the target.
+                let Some((url, plain, start)) = self.links.pop() else {
+                    return;
+                };
+                self.buf.truncate(start);
+                let plain = plain.trim();
+                let _ = match url.strip_prefix('#') {
+                    Some(anchor) => write!(self.buf, ":ref:`{} <{}>`", plain, anchor),
+                    None if plain.is_empty() || plain == url => write!(self.buf, "`<{}>`__", url),
+                    None => write!(self.buf, "`{} <{}>`__", plain, url),
+                };
+                self.after_markup = true;
+            }
+            Event::Code(code) => {
+                if let Some((_, plain, _)) = self.links.last_mut() {
+                    plain.push_str(&code);
+                }
+                separate(&mut self.buf);
+                self.buf.push_str(&literal(&code));
+                self.after_markup = true;
+            }
+            Event::Text(text) => self.text(&text),
+            Event::SoftBreak | Event::HardBreak => self.buf.push('\n'),
+            Event::Html(html) => {
+                self.flush();
+                self.line(".. raw:: html");
+                self.blank();
+                self.indent.push("   ".to_string());
+                for line in html.trim_end().lines() {
+                    self.line(line);
+                }
+                self.indent.pop();
+                self.blank();
+            }
+            Event::Rule => {
+                self.flush();
+                self.line("----");
+                self.blank();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `text` as a comment, one indented line after `..` per line of it.
+pub fn comment(text: &str) -> String {
+    let mut out = String::from("..\n");
+    for line in text.lines() {
+        let _ = writeln!(out, "   {}", line);
+    }
+    out
+}
+
+/// Convert CommonMark, as generated by docgen, to reStructuredText.
+pub fn markdown_to_rst(markdown: &str) -> String {
+    let mut writer = Writer::default();
+    let mut chunk = String::new();
+    let mut fence: Option<(usize, char)> = None;
+    for line in markdown.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(div) = div_fence(&fence_line) {
+                    writer.convert(&std::mem::take(&mut chunk));
+                    match div {
+                        DivFence::Open { classes, id } => writer.open_block(&classes, id),
+                        DivFence::Close => writer.close_block(),
+                    }
+                    continue;
+                }
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+            }
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    writer.convert(&chunk);
+    while !writer.blocks.is_empty() {
+        writer.close_block();
+    }
+    let mut out = writer.out;
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
@@ -0,0 +1,362 @@
+//! Sanitization of raw HTML embedded in doc comments and option descriptions.
+//!
+//! CommonMark passes raw HTML straight through to the rendered page. For
+//! manuals built from third-party sources that is an injection vector, so the
+//! HTML can be removed or escaped before rendering. Only spans the CommonMark
+//! parser classifies as raw HTML, and the contents of ```` ```{=html} ```` raw
+//! blocks, are touched; `<` inside code spans, other code blocks or plain text
+//! stays as it is. Tags on the allowlist lose their event handler attributes
+//! (`onclick`, ...) and URL attributes (`href`, `src`, ...) whose URL, with
+//! its character references decoded, has a scheme other than `http`,
+//! `https` or `mailto`, such as `javascript:` or `data:`. Relative URLs and
+//! fragments are kept.
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// What to do with a tag that is not on the allowlist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HtmlMode {
+    /// Remove the tag, keeping any text between tags. The contents of
+    /// `<script>` and `<style>` elements are removed as well.
+    Strip,
+    /// Escape the tag so it renders as literal text.
+    Escape,
+}
+
+/// How raw HTML is sanitized.
+#[derive(Debug, Clone)]
+pub struct HtmlPolicy {
+    pub mode: HtmlMode,
+    /// Names of tags that are kept, compared case-insensitively.
+    pub allowed_tags: Vec<String>,
+}
+
+impl HtmlPolicy {
+    fn allows(&self, tag: &str) -> bool {
+        !tag.is_empty()
+            && self
+                .allowed_tags
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Sanitize the raw HTML in a CommonMark fragment according to `policy`.
+pub fn sanitize_html<'a>(text: &'a str, policy: &HtmlPolicy) -> Cow<'a, str> {
+    let ranges = html_ranges(text);
+    if ranges.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    // A stripped `<script>` or `<style>` element not closed in its own chunk
+    // of HTML, such as inline HTML around text, whose text is dropped up to
+    // the closing tag.
+    let mut skipping = None;
+    for range in ranges {
+        if skipping.is_none() {
+            output.push_str(&text[last..range.start]);
+        }
+        rewrite_html(&text[range.clone()], policy, &mut output, &mut skipping);
+        last = range.end;
+    }
+    if skipping.is_none() {
+        output.push_str(&text[last..]);
+    }
+    Cow::Owned(output)
+}
+
+/// Byte ranges of raw HTML, with adjacent ranges (the lines of an HTML
+/// block) merged.
+fn html_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut in_raw_html = false;
+    for (event, range) in Parser::new_ext(text, Options::empty()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                in_raw_html = info.trim().eq_ignore_ascii_case("{=html}");
+                continue;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_raw_html = false;
+                continue;
+            }
+            Event::Html(_) | Event::InlineHtml(_) => {}
+            Event::Text(_) if in_raw_html => {}
+            _ => continue,
+        }
+        match ranges.last_mut() {
+            Some(prev) if prev.end == range.start => prev.end = range.end,
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+}
+
+/// Rewrite every tag in a raw HTML chunk, keeping the text in between.
+/// `skipping` is the name of a stripped element whose contents are dropped,
+/// carried over from and to the chunks around this one.
+fn rewrite_html(
+    chunk: &str,
+    policy: &HtmlPolicy,
+    output: &mut String,
+    skipping: &mut Option<String>,
+) {
+    let mut rest = chunk;
+    if let Some(name) = skipping.take() {
+        match skip_element_body(rest, &name) {
+            Some(after) => rest = after,
+            None => {
+                *skipping = Some(name);
+                return;
+            }
+        }
+    }
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        let tag_src = &rest[start..];
+        let len = tag_len(tag_src);
+        let tag = &tag_src[..len];
+        let (name, closing) = tag_name(tag);
+        rest = &tag_src[len..];
+
+        if policy.allows(name) {
+            push_safe_tag(tag, 1 + usize::from(closing) + name.len(), output);
+            continue;
+        }
+        match policy.mode {
+            HtmlMode::Escape => escape_into(tag, output),
+            HtmlMode::Strip => {
+                if !closing
+                    && (name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style"))
+                {
+                    match skip_element_body(rest, name) {
+                        Some(after) => rest = after,
+                        None => {
+                            *skipping = Some(name.to_string());
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    output.push_str(rest);
+}
+
+/// Length of the tag, comment or declaration at the start of `src`,
+/// honouring quoted attribute values. Unterminated tags extend to the end.
+fn tag_len(src: &str) -> usize {
+    if let Some(comment) = src.strip_prefix("<!--") {
+        return comment.find("-->").map_or(src.len(), |i| i + 7);
+    }
+    let mut quote = None;
+    for (i, c) in src.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    src.len()
+}
+
+/// Name of a tag and whether it is a closing tag. Comments, declarations and
+/// processing instructions have an empty name.
+fn tag_name(tag: &str) -> (&str, bool) {
+    let inner = &tag[1..];
+    let (inner, closing) = match inner.strip_prefix('/') {
+        Some(inner) => (inner, true),
+        None => (inner, false),
+    };
+    let end = inner
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(inner.len());
+    (&inner[..end], closing)
+}
+
+/// Skip past the closing tag of a `<script>` or `<style>` element, or
+/// `None` if it is not in `rest`.
+fn skip_element_body<'a>(rest: &'a str, name: &str) -> Option<&'a str> {
+    let closing = format!("</{}", name.to_ascii_lowercase());
+    let pos = rest.to_ascii_lowercase().find(&closing)?;
+    let after = &rest[pos..];
+    Some(&after[tag_len(after)..])
+}
+
+/// Write an allowed tag, whose name ends at `head`, without the attributes
+/// that run scripts.
+fn push_safe_tag(tag: &str, head: usize, output: &mut String) {
+    output.push_str(&tag[..head]);
+    let mut rest = &tag[head..];
+    loop {
+        // Browsers read a slash between attributes like a space.
+        let attr = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if attr.is_empty() || attr.starts_with('>') {
+            output.push_str(rest);
+            return;
+        }
+        let name_len = attr
+            .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(attr.len());
+        let name = &attr[..name_len];
+        let mut after = &attr[name_len..];
+        let mut value = "";
+        if let Some(v) = after.trim_start().strip_prefix('=') {
+            let v = v.trim_start();
+            let len = match v.chars().next() {
+                Some(q @ ('"' | '\'')) => v[1..].find(q).map_or(v.len(), |i| i + 2),
+                _ => v
+                    .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                    .unwrap_or(v.len()),
+            };
+            value = &v[..len];
+            after = &v[len..];
+        }
+        if !runs_script(name, value) {
+            output.push_str(&rest[..rest.len() - after.len()]);
+        }
+        rest = after;
+    }
+}
+
+/// Attributes whose value is a URL, or with `srcset`, a list of them.
+const URL_ATTRIBUTES: [&str; 11] = [
+    "href",
+    "src",
+    "srcset",
+    "action",
+    "formaction",
+    "poster",
+    "cite",
+    "background",
+    "data",
+    "longdesc",
+    "xlink:href",
+];
+
+/// URL schemes kept in URL attributes.
+const URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Named character references of the ASCII characters that can make up or
+/// end a URL scheme, and of the whitespace browsers ignore in URLs.
+const NAMED_REFERENCES: [(&str, char); 20] = [
+    ("Tab", '\t'),
+    ("NewLine", '\n'),
+    ("nbsp", '\u{a0}'),
+    ("colon", ':'),
+    ("sol", '/'),
+    ("bsol", '\\'),
+    ("quest", '?'),
+    ("num", '#'),
+    ("period", '.'),
+    ("plus", '+'),
+    ("minus", '-'),
+    ("amp", '&'),
+    ("AMP", '&'),
+    ("semi", ';'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("percnt", '%'),
+    ("equals", '='),
+];
+
+/// Whether the attribute `name` with the value `value`, as written, is an
+/// event handler or a URL attribute with a scheme that is not allowed.
+fn runs_script(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    if name.starts_with("on") {
+        return true;
+    }
+    if !URL_ATTRIBUTES.contains(&name.as_str()) {
+        return false;
+    }
+    let value = decode_references(value.trim_matches(['"', '\'']));
+    if name == "srcset" {
+        return value
+            .split(',')
+            .any(|candidate| !is_safe_url(candidate.split_whitespace().next().unwrap_or("")));
+    }
+    !is_safe_url(&value)
+}
+
+/// Whether a URL, with its character references decoded, is relative, a
+/// fragment, or has an allowed scheme.
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters within the scheme.
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let Some(colon) = url.find(':') else {
+        return true;
+    };
+    let scheme = &url[..colon];
+    if scheme.contains(['/', '?', '#']) {
+        return true;
+    }
+    // A reference left undecoded may stand for a character of the scheme.
+    !scheme.contains('&') && URL_SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+}
+
+/// Decode the numeric character references in `text`, and the named ones of
+/// [`NAMED_REFERENCES`]. As in browsers, the `;` ending a numeric reference
+/// may be left out.
+fn decode_references(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = match rest[1..].strip_prefix('#') {
+            Some(number) => {
+                let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => (hex, 16),
+                    None => (number, 10),
+                };
+                let len = digits
+                    .find(|c: char| !c.is_digit(radix))
+                    .unwrap_or(digits.len());
+                u32::from_str_radix(&digits[..len], radix).ok().map(|code| {
+                    let end = rest.len() - digits.len() + len;
+                    let end = end + usize::from(rest[end..].starts_with(';'));
+                    (char::from_u32(code).unwrap_or('\u{fffd}'), end)
+                })
+            }
+            None => NAMED_REFERENCES.iter().find_map(|(name, c)| {
+                rest[1..]
+                    .strip_prefix(name)
+                    .filter(|after| after.starts_with(';'))
+                    .map(|_| (*c, name.len() + 2))
+            }),
+        };
+        match reference {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn escape_into(tag: &str, output: &mut String) {
+    for c in tag.chars() {
+        match c {
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(c),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Options from JSON-Schema-like configuration schemas.
+//!
+//! Tools configured outside Nix often describe their configuration with a
+//! JSON Schema, written by hand or exported from CUE (`cue export --out
+//! jsonschema`) or TOML tooling. Such a schema is converted into the
+//! `options.json` format, so it can be rendered, filtered and linted like
+//! module options, alone or next to them:
+//!
+//! ```json
+//! {
+//!   "properties": {
+//!     "server": {
+//!       "type": "object",
+//!       "properties": {
+//!         "port": { "type": "integer", "default": 8080, "description": "Port to listen on." }
+//!       }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! becomes the option `server.port` of type `signed integer`, with the
+//! schema file as its declaration. Objects with `properties` are nested
+//! into, every other property becomes an option. Types are named like the
+//! corresponding Nix option types (`list of string`, `null or boolean`,
+//! `one of "a", "b"`), defaults and examples are written as Nix
+//! expressions, local `$ref`s are followed and `examples` or `example` give
+//! the example. Schemas can be JSON or TOML files.
+
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::Path;
+
+/// How deep `$ref`s are followed, guarding against cyclic definitions.
+const MAX_REF_DEPTH: usize = 32;
+
+/// A schema to import, with the option path it is placed below.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaInput {
+    pub prefix: Vec<String>,
+    pub path: std::path::PathBuf,
+}
+
+/// Parse a `--schema` argument, `[PREFIX=]FILE`.
+pub fn parse_schema_input(s: &str) -> Result<SchemaInput, String> {
+    let (prefix, path) = match s.split_once('=') {
+        Some((prefix, path)) if !prefix.contains(['/', '\\']) => (prefix, path),
+        _ => ("", s),
+    };
+    if path.is_empty() {
+        return Err(format!("expected [PREFIX=]FILE, got '{}'", s));
+    }
+    Ok(SchemaInput {
+        prefix: prefix
+            .split('.')
+            .filter(|part| !part.is_empty())
+            .map(String::from)
+            .collect(),
+        path: path.into(),
+    })
+}
+
+/// Read a schema file, as TOML if it has a `.toml` extension and JSON
+/// otherwise.
+pub fn read_schema(path: &Path) -> Result<Value, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+    } else {
+        serde_json::from_str(&text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+    }
+}
+
+/// Resolve a local `$ref` (`#/definitions/name`, `#/$defs/name`) against
+/// the schema's root.
+fn resolve<'a>(root: &'a Value, schema: &'a Value, depth: usize) -> Result<&'a Value, String> {
+    let Some(reference) = schema.get("$ref").and_then(Value::as_str) else {
+        return Ok(schema);
+    };
+    if depth >= MAX_REF_DEPTH {
+        return Err(format!("$ref {} is nested too deeply", reference));
+    }
+    let pointer = reference
+        .strip_prefix('#')
+        .ok_or_else(|| format!("only local $refs are supported, got {}", reference))?;
+    let target = root
+        .pointer(pointer)
+        .ok_or_else(|| format!("$ref {} not found", reference))?;
+    resolve(root, target, depth + 1)
+}
+
+/// The Nix option type name of a schema.
+fn type_name(root: &Value, schema: &Value, depth: usize) -> Result<String, String> {
+    let schema = resolve(root, schema, depth)?;
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        return Ok(format!("one of {}", values.join(", ")));
+    }
+    if let Some(value) = schema.get("const") {
+        return Ok(format!("value {} (singular enum)", value));
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(alternatives) = schema.get(key).and_then(Value::as_array) {
+            let names = alternatives
+                .iter()
+                .map(|alternative| type_name(root, alternative, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(or_null(names));
+        }
+    }
+    match schema.get("type") {
+        Some(Value::Array(types)) => {
+            let names = types
+                .iter()
+                .map(|ty| {
+                    let mut single = schema.clone();
+                    single["type"] = ty.clone();
+                    type_name(root, &single, depth + 1)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(or_null(names))
+        }
+        Some(Value::String(ty)) => Ok(match ty.as_str() {
+            "string" => "string".to_string(),
+            "integer" => "signed integer".to_string(),
+            "number" => "floating point number".to_string(),
+            "boolean" => "boolean".to_string(),
+            "null" => "null".to_string(),
+            "array" => match schema.get("items") {
+                Some(items) => format!("list of {}", type_name(root, items, depth + 1)?),
+                None => "list of anything".to_string(),
+            },
+            "object" => match schema.get("additionalProperties") {
+                Some(values @ Value::Object(_)) => {
+                    format!("attribute set of {}", type_name(root, values, depth + 1)?)
+                }
+                _ => "attribute set".to_string(),
+            },
+            other => other.to_string(),
+        }),
+        _ => Ok("anything".to_string()),
+    }
+}
+
+/// Alternatives joined with `or`, `null` first like `types.nullOr`.
+fn or_null(mut names: Vec<String>) -> String {
+    if let Some(null) = names.iter().position(|name| name == "null")
+        && names.len() > 1
+    {
+        names.remove(null);
+        return format!("null or {}", names.join(" or "));
+    }
+    names.join(" or ")
+}
+
+/// A JSON value as a Nix expression.
+pub fn nix_literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace("${", "\\${")
+                .replace('\n', "\\n")
+        ),
+        Value::Array(items) if items.is_empty() => "[ ]".to_string(),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(nix_literal).collect();
+            format!("[ {} ]", items.join(" "))
+        }
+        Value::Object(attrs) if attrs.is_empty() => "{ }".to_string(),
+        Value::Object(attrs) => {
+            let attrs: Vec<String> = attrs
+                .iter()
+                .map(|(name, value)| {
+                    let is_ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '\'' | '-'));
+                    let name = if is_ident {
+                        name.clone()
+                    } else {
+                        nix_literal(&Value::String(name.clone()))
+                    };
+                    format!("{} = {};", name, nix_literal(value))
+                })
+                .collect();
+            format!("{{ {} }}", attrs.join(" "))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// A value as the `literalExpression` used for defaults and examples in
+/// `options.json`.
+fn literal_expression(value: &Value) -> Value {
+    json!({ "_type": "literalExpression", "text": nix_literal(value) })
+}
+
+/// Whether a property is nested into rather than documented as an option.
+fn has_properties(schema: &Value) -> bool {
+    schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .is_some_and(|properties| !properties.is_empty())
+}
+
+fn collect(
+    root: &Value,
+    schema: &Value,
+    loc: &mut Vec<String>,
+    declaration: &str,
+    depth: usize,
+    options: &mut Map<String, Value>,
+) -> Result<(), String> {
+    let schema = resolve(root, schema, depth)?;
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Ok(());
+    };
+    for (name, property) in properties {
+        loc.push(name.clone());
+        let property = resolve(root, property, depth)?;
+        if has_properties(property) {
+            collect(root, property, loc, declaration, depth + 1, options)?;
+        } else {
+            let description = property
+                .get("description")
+                .or_else(|| property.get("title"))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let example = property
+                .get("examples")
+                .and_then(|examples| examples.get(0))
+                .or_else(|| property.get("example"));
+            let mut option = json!({
+                "loc": loc,
+                "description": description,
+                "type": type_name(root, property, depth)?,
+                "declarations": [declaration],
+                "readOnly": property.get("readOnly").and_then(Value::as_bool).unwrap_or(false),
+            });
+            if let Some(default) = property.get("default") {
+                option["default"] = literal_expression(default);
+            }
+            if let Some(example) = example {
+                option["example"] = literal_expression(example);
+            }
+            options.insert(loc.join("."), option);
+        }
+        loc.pop();
+    }
+    Ok(())
+}
+
+/// Convert a schema into an `options.json` object, its options placed
+/// below `prefix` and declared in `declaration`.
+pub fn schema_to_options(
+    schema: &Value,
+    prefix: &[String],
+    declaration: &str,
+) -> Result<Map<String, Value>, String> {
+    let mut options = Map::new();
+    let mut loc = prefix.to_vec();
+    collect(schema, schema, &mut loc, declaration, 0, &mut options)?;
+    Ok(options)
+}
+
+/// Read the schemas of `inputs` and convert them into one `options.json`
+/// document.
+pub fn options_json(inputs: &[SchemaInput]) -> Result<String, String> {
+    let mut options = Map::new();
+    for input in inputs {
+        let schema = read_schema(&input.path)?;
+        let declaration = input.path.to_string_lossy().replace('\\', "/");
+        let converted = schema_to_options(&schema, &input.prefix, &declaration)
+            .map_err(|e| format!("{}: {}", input.path.display(), e))?;
+        options.extend(converted);
+    }
+    serde_json::to_string(&options).map_err(|e| e.to_string())
+}
@@ -0,0 +1,264 @@
+//! Search index export in the Elasticsearch bulk format.
+//!
+//! Each documented function or option becomes one flat document, named after
+//! the fields search.nixos.org indexes, preceded by an `index` action line.
+//! The output can be posted as-is to the `_bulk` endpoint:
+//!
+//! ```text
+//! {"index":{"_index":"docs","_id":"option-services.nginx.enable"}}
+//! {"type":"option","option_name":"services.nginx.enable",...}
+//! ```
+//!
+//! With [`BulkShape::NixosSearch`], options are written instead in the shape
+//! of the documents search.nixos.org's importer (flake-info) writes, with
+//! the description as HTML, defaults and examples as Nix expressions and the
+//! fields of the flake they come from, so a self-hosted nixos-search instance
+//! can index them alongside its own. search.nixos.org has no documents for
+//! library functions, so they have no such shape.
+
+use crate::commonmark::{Argument, ManualEntry};
+use crate::html::markdown_to_html;
+use crate::options::{self, OptionDef, OptionValue};
+use crate::schema::nix_literal;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Shape of the documents of a bulk export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BulkShape {
+    /// docgen's documents, for functions and options
+    #[default]
+    Docgen,
+    /// The option documents of search.nixos.org's importer
+    NixosSearch,
+}
+
+/// Repository of a flake, as in the `flake_resolved` field of nixos-search
+/// documents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FlakeRepo {
+    Github { owner: String, repo: String },
+    Gitlab { owner: String, repo: String },
+    Sourcehut { owner: String, repo: String },
+    Git { url: String },
+}
+
+/// The flake exported options come from, given by a flake reference such as
+/// `github:owner/repo` or `git+https://example.com/repo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Flake {
+    pub flake_name: String,
+    pub flake_description: Option<String>,
+    pub flake_resolved: FlakeRepo,
+}
+
+impl std::str::FromStr for Flake {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            format!(
+                "expected a flake reference such as github:owner/repo or git+https://..., got '{}'",
+                s
+            )
+        };
+        // Revisions and query parameters do not name the repository.
+        let reference = s.split(['?', '#']).next().unwrap_or(s);
+        let resolved = match reference.split_once(':') {
+            Some((kind @ ("github" | "gitlab" | "sourcehut"), path)) => {
+                let mut parts = path.splitn(3, '/');
+                let (Some(owner), Some(repo)) = (parts.next(), parts.next()) else {
+                    return Err(err());
+                };
+                if owner.is_empty() || repo.is_empty() {
+                    return Err(err());
+                }
+                let (owner, repo) = (owner.to_string(), repo.to_string());
+                match kind {
+                    "github" => FlakeRepo::Github { owner, repo },
+                    "gitlab" => FlakeRepo::Gitlab { owner, repo },
+                    _ => FlakeRepo::Sourcehut { owner, repo },
+                }
+            }
+            Some(("git+https" | "git+http" | "git+ssh" | "git+file", _)) => FlakeRepo::Git {
+                url: reference.to_string(),
+            },
+            _ => return Err(err()),
+        };
+        let name = match &resolved {
+            FlakeRepo::Github { repo, .. }
+            | FlakeRepo::Gitlab { repo, .. }
+            | FlakeRepo::Sourcehut { repo, .. } => repo.clone(),
+            FlakeRepo::Git { url } => url
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(url)
+                .trim_end_matches(".git")
+                .to_string(),
+        };
+        Ok(Flake {
+            flake_name: name,
+            flake_description: None,
+            flake_resolved: resolved,
+        })
+    }
+}
+
+/// An option in the shape of search.nixos.org's documents.
+#[derive(Debug, Serialize)]
+pub struct NixosSearchOption {
+    #[serde(flatten)]
+    pub flake: Option<Flake>,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// First file declaring the option.
+    pub option_source: Option<String>,
+    pub option_name: String,
+    /// The description rendered as HTML.
+    pub option_description: Option<String>,
+    pub option_type: Option<String>,
+    pub option_default: Option<String>,
+    pub option_example: Option<String>,
+    /// Flake and module the option is declared by, as named in the flake's
+    /// outputs; unknown when reading options JSON.
+    pub option_flake: Option<(String, String)>,
+}
+
+impl NixosSearchOption {
+    pub fn from_option(name: &str, opt: &OptionDef, flake: Option<&Flake>) -> Self {
+        NixosSearchOption {
+            flake: flake.cloned(),
+            kind: "option",
+            option_source: opt.declarations.first().map(|d| d.name().to_string()),
+            option_name: name.to_string(),
+            option_description: opt
+                .description
+                .as_ref()
+                .map(|d| markdown_to_html(d.as_str(), false)),
+            option_type: opt.option_type.clone(),
+            option_default: opt.default.as_ref().map(nix_value),
+            option_example: opt.example.as_ref().map(nix_value),
+            option_flake: None,
+        }
+    }
+}
+
+/// An option value as nixos-search shows it: literal expressions as
+/// written, markdown rendered as HTML and other values as Nix.
+fn nix_value(value: &OptionValue) -> String {
+    match value {
+        OptionValue::Tagged(tagged) if tagged.value_type == "literalMD" => {
+            markdown_to_html(tagged.text.as_deref().unwrap_or_default(), false)
+        }
+        OptionValue::Tagged(tagged) => tagged.text.clone().unwrap_or_default(),
+        OptionValue::String(s) => nix_literal(&serde_json::Value::String(s.clone())),
+        OptionValue::Array(items) => nix_literal(&serde_json::Value::Array(items.clone())),
+        OptionValue::Object(attrs) => nix_literal(&serde_json::Value::Object(attrs.clone())),
+        other => options::option_value_text(other),
+    }
+}
+
+/// A single searchable document.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SearchDocument {
+    Function {
+        function_name: String,
+        function_category: String,
+        function_description: String,
+        function_type: Option<String>,
+        function_arguments: Vec<String>,
+        function_location: Option<String>,
+        function_anchor: String,
+    },
+    Option {
+        option_name: String,
+        option_description: String,
+        option_type: Option<String>,
+        option_default: Option<String>,
+        option_example: Option<String>,
+        option_read_only: bool,
+        option_declarations: Vec<String>,
+        option_anchor: String,
+    },
+}
+
+impl SearchDocument {
+    /// Build the document for a library function.
+    pub fn from_entry(entry: &ManualEntry, anchor_prefix: &str) -> Self {
+        let (_, title) = entry.get_ident_title();
+        let function_arguments = entry
+            .args
+            .iter()
+            .flat_map(|arg| match arg {
+                Argument::Flat(arg) => vec![arg.name.clone()],
+                Argument::Pattern(args) => args.iter().map(|a| a.name.clone()).collect(),
+            })
+            .collect();
+
+        SearchDocument::Function {
+            function_name: title,
+            function_category: entry.category.clone(),
+            function_description: entry.description.join("\n\n"),
+            function_type: entry.fn_type.clone(),
+            function_arguments,
+            function_location: entry.location.clone(),
+            function_anchor: entry.anchor(anchor_prefix),
+        }
+    }
+
+    /// Build the document for a module option.
+    pub fn from_option(name: &str, opt: &OptionDef, anchor_prefix: &str) -> Self {
+        SearchDocument::Option {
+            option_name: name.to_string(),
+            option_description: opt
+                .description
+                .as_ref()
+                .map(|d| d.as_str().to_string())
+                .unwrap_or_default(),
+            option_type: opt.option_type.clone(),
+            option_default: opt.default.as_ref().map(options::option_value_text),
+            option_example: opt.example.as_ref().map(options::option_value_text),
+            option_read_only: opt.read_only,
+            option_declarations: opt
+                .declarations
+                .iter()
+                .map(|d| d.name().to_string())
+                .collect(),
+            option_anchor: options::make_anchor_id(name, anchor_prefix),
+        }
+    }
+
+    /// Document id, unique across functions and options.
+    fn id(&self) -> String {
+        match self {
+            SearchDocument::Function { function_name, .. } => format!("function-{}", function_name),
+            SearchDocument::Option { option_name, .. } => format!("option-{}", option_name),
+        }
+    }
+}
+
+/// Write the bulk action and source lines for one document.
+pub fn write_bulk_document<W: Write>(
+    writer: &mut W,
+    index: &str,
+    doc: &SearchDocument,
+) -> io::Result<()> {
+    write_bulk_source(writer, index, &doc.id(), doc)
+}
+
+/// Write the bulk action and source lines for a document of any shape.
+pub fn write_bulk_source<W: Write, T: Serialize>(
+    writer: &mut W,
+    index: &str,
+    id: &str,
+    source: &T,
+) -> io::Result<()> {
+    let action = serde_json::json!({ "index": { "_index": index, "_id": id } });
+    serde_json::to_writer(&mut *writer, &action)?;
+    writer.write_all(b"\n")?;
+    serde_json::to_writer(&mut *writer, source)?;
+    writer.write_all(b"\n")
+}
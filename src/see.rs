@@ -0,0 +1,179 @@
+//! Links of the references between functions.
+//!
+//! A doc comment names related functions with `@see` lines:
+//!
+//! ```nix
+//! /**
+//!   Apply a function to each element of a list.
+//!
+//!   @see lib.lists.imap0, lib.attrsets.mapAttrs
+//! */
+//! map = f: list: builtins.map f list;
+//! ```
+//!
+//! A reference is the title of a function, such as `lib.lists.imap0`, or its
+//! name within the category of the referring entry (`imap0`). It links to
+//! the function's anchor when the function is documented in the same run,
+//! or else when it is in the search index given with `--see-index` (as
+//! written by `docgen index`). Unresolved references are rendered as code.
+//!
+//! Functions are referred to in the text of doc comments too, with the
+//! `` {ref}`lib.strings.concatStrings` `` role, which resolves like `@see`,
+//! or as inline code naming the title of a function, which is linked when
+//! the function is known:
+//!
+//! ```markdown
+//! Like {ref}`concatStrings`, but with `lib.strings.concatStringsSep`.
+//! ```
+
+use crate::commonmark::{Argument, ManualEntry, get_title};
+use crate::xref::{Mentions, link_references};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Role marking a reference to a function.
+pub const ROLE: &str = "{ref}";
+
+/// Functions that references can link to, by title.
+#[derive(Debug, Clone, Default)]
+pub struct Targets {
+    /// Page and anchor of each function.
+    targets: HashMap<String, (String, String)>,
+}
+
+impl Targets {
+    /// Add the functions of the search index at `path`.
+    pub fn read_index(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        let index: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        let Some(documents) = index["documents"].as_array() else {
+            return Err(format!("{}: not a search index", path.display()));
+        };
+        for document in documents {
+            if let (Some(id), Some(url)) = (document["id"].as_str(), document["url"].as_str()) {
+                let (page, anchor) = url.split_once('#').unwrap_or((url, ""));
+                self.targets
+                    .insert(id.to_string(), (page.to_string(), anchor.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `entries`, documented on `page` (empty for the page being
+    /// written). They take the place of functions of the same title.
+    pub fn add_entries(&mut self, entries: &[ManualEntry], anchor_prefix: &str, page: &str) {
+        for entry in entries {
+            self.targets.insert(
+                entry.get_ident_title().1,
+                (page.to_string(), entry.anchor(anchor_prefix)),
+            );
+        }
+    }
+
+    /// Link to the function `name`, referred to by `entry` on `page`.
+    pub fn url(&self, name: &str, entry: &ManualEntry, page: &str) -> Option<String> {
+        let relative = get_title(&entry.prefix, &entry.category, name);
+        let (target_page, anchor) = self
+            .targets
+            .get(name)
+            .or_else(|| self.targets.get(&relative))?;
+        let fragment = if anchor.is_empty() {
+            String::new()
+        } else {
+            format!("#{}", anchor)
+        };
+        Some(if target_page == page {
+            fragment
+        } else {
+            format!("{}{}", target_page, fragment)
+        })
+    }
+
+    /// Link the `@see` tags of `entries`, documented on `page`. Returns the
+    /// references that could not be resolved, as `(title of the entry,
+    /// reference as written)`.
+    pub fn link_see_also(&self, entries: &mut [ManualEntry], page: &str) -> Vec<(String, String)> {
+        let mut unresolved = vec![];
+        for entry in entries {
+            let mut see_also = std::mem::take(&mut entry.see_also);
+            for see in &mut see_also {
+                see.url = self.url(&see.name, entry, page);
+                if see.url.is_none() {
+                    let title = entry.get_ident_title().1;
+                    unresolved.push((title, format!("@see {}", see.name)));
+                }
+            }
+            entry.see_also = see_also;
+        }
+        unresolved
+    }
+
+    /// Link the mentions of functions in the descriptions, argument docs and
+    /// return values of `entries`, documented on `page`. Returns the
+    /// references that could not be resolved, like [`Targets::link_see_also`].
+    ///
+    /// This changes the docs of the entries, so it is done on the entries
+    /// rendered, not those hashed or written as JSON: their docs do not
+    /// depend on the other functions of the run.
+    pub fn link_mentions(&self, entries: &mut [ManualEntry], page: &str) -> Vec<(String, String)> {
+        let mut unresolved = vec![];
+        for entry in entries {
+            let title = entry.get_ident_title().1;
+            let url = |name: &str, role: bool| {
+                if role {
+                    self.url(name, entry, page)
+                } else {
+                    self.targets
+                        .contains_key(name)
+                        .then(|| self.url(name, entry, page))
+                        .flatten()
+                }
+            };
+            let rules = Mentions {
+                role: ROLE,
+                bare: false,
+                current: &title,
+                url: &url,
+            };
+            let mut link = |text: &mut String| {
+                let linked = link_references(text, &rules);
+                unresolved.extend(
+                    linked
+                        .unresolved
+                        .into_iter()
+                        .map(|name| (title.clone(), format!("{}`{}`", ROLE, name))),
+                );
+                if let Cow::Owned(linked) = linked.text {
+                    *text = linked;
+                }
+            };
+            // Paragraphs are linked together, as code blocks can span them.
+            let joined = entry.description.join("\n\n");
+            let mut description = joined.clone();
+            link(&mut description);
+            let mut returns = entry.returns.clone();
+            returns.iter_mut().for_each(&mut link);
+            let mut args = entry.args.clone();
+            for arg in &mut args {
+                let args = match arg {
+                    Argument::Flat(arg) => std::slice::from_mut(arg),
+                    Argument::Pattern(args) => args.as_mut_slice(),
+                };
+                for arg in args {
+                    arg.doc.iter_mut().for_each(&mut link);
+                }
+            }
+            if description != joined {
+                entry.description = description.split("\n\n").map(String::from).collect();
+            }
+            entry.returns = returns;
+            entry.args = args;
+        }
+        unresolved
+    }
+}
@@ -0,0 +1,271 @@
+//! Machine-readable function signatures for the JSON output.
+//!
+//! The signature combines what the Nix code says about a function's
+//! parameters with the `# Type` section of its doc comment:
+//!
+//! ```json
+//! {
+//!   "params": [
+//!     { "kind": "positional", "name": "sep" },
+//!     {
+//!       "kind": "pattern",
+//!       "attrs": [{ "name": "strict", "optional": true, "default": "false" }],
+//!       "ellipsis": true,
+//!       "bind": "args"
+//!     }
+//!   ],
+//!   "type": {
+//!     "text": "join :: string -> { strict :: bool, ... } -> string",
+//!     "name": "join",
+//!     "args": ["string", "{ strict :: bool, ... }"],
+//!     "returns": "string"
+//!   }
+//! }
+//! ```
+
+use crate::format::handle_indentation;
+use rnix::ast::{Expr, Lambda, Param};
+use rowan::ast::AstNode;
+use serde::{Deserialize, Serialize};
+
+/// Parameters and declared type of a function.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Signature {
+    /// Parameters in application order, one per curried lambda.
+    pub params: Vec<SignatureParam>,
+    /// The `# Type` section of the doc comment.
+    #[serde(rename = "type")]
+    pub fn_type: Option<TypeSignature>,
+}
+
+/// A single lambda parameter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SignatureParam {
+    /// `x: ...`
+    Positional { name: String },
+    /// `{ a, b ? 1, ... }@args: ...`
+    Pattern {
+        attrs: Vec<PatternAttr>,
+        /// Whether the pattern accepts further attributes (`...`).
+        ellipsis: bool,
+        /// Name the whole argument is bound to with `@`.
+        bind: Option<String>,
+    },
+}
+
+/// An attribute of a pattern parameter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PatternAttr {
+    pub name: String,
+    /// Whether the attribute has a default value and can be left out.
+    pub optional: bool,
+    /// Source text of the default value.
+    pub default: Option<String>,
+}
+
+/// A `# Type` signature split into its parts.
+///
+/// This only splits on top-level arrows; the types themselves are kept as
+/// written.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TypeSignature {
+    /// The signature as written, without the code fence.
+    pub text: String,
+    /// Name before `::`, if given.
+    pub name: Option<String>,
+    /// Argument types in order.
+    pub args: Vec<String>,
+    /// Type after the last top-level arrow.
+    pub returns: String,
+}
+
+/// Collect the parameters of directly chained lambdas.
+pub fn lambda_params(mut lambda: Lambda) -> Vec<SignatureParam> {
+    let mut params = vec![];
+
+    loop {
+        match lambda.param() {
+            Some(Param::IdentParam(id)) => params.push(SignatureParam::Positional {
+                name: id.to_string(),
+            }),
+            Some(Param::Pattern(pat)) => params.push(SignatureParam::Pattern {
+                attrs: pat
+                    .pat_entries()
+                    .filter_map(|entry| {
+                        let default = entry.default().map(|d| d.syntax().to_string());
+                        Some(PatternAttr {
+                            name: entry.ident()?.to_string(),
+                            optional: default.is_some(),
+                            default,
+                        })
+                    })
+                    .collect(),
+                ellipsis: pat.ellipsis_token().is_some(),
+                bind: pat
+                    .pat_bind()
+                    .and_then(|bind| bind.ident())
+                    .map(|ident| ident.to_string()),
+            }),
+            None => break,
+        }
+
+        match lambda.body() {
+            Some(Expr::Lambda(inner)) => lambda = inner,
+            _ => break,
+        }
+    }
+
+    params
+}
+
+/// Find the contents of the code block in the `Type` section of a
+/// description, whatever level its heading has been shifted to.
+pub fn type_section(description: &[String]) -> Option<String> {
+    type_section_at(description).map(|(_, text)| text)
+}
+
+/// The index of the heading of the `Type` section and the contents of its
+/// code block.
+fn type_section_at(description: &[String]) -> Option<(usize, String)> {
+    let heading = description.iter().position(|p| {
+        let title = p.trim().trim_start_matches('#');
+        p.trim().starts_with('#') && title.trim().eq_ignore_ascii_case("type")
+    })?;
+    let block = description.get(heading + 1)?.trim();
+    let body = block
+        .strip_prefix("```")
+        .or_else(|| block.strip_prefix("~~~"))?;
+    // Drop the info string and the closing fence.
+    let (_, body) = body.split_once('\n')?;
+    let body = body.trim_end().trim_end_matches(['`', '~']);
+    Some((heading, body.trim().to_string())).filter(|(_, t)| !t.is_empty())
+}
+
+/// Take the type signature out of a description: the code block of its
+/// `Type` section, removed with the heading, or else a paragraph starting
+/// with `Type:` followed by a signature, with `::` or `->`, so prose
+/// starting with the word is left in the description.
+pub fn take_type_section(description: &mut Vec<String>) -> Option<String> {
+    if let Some((heading, text)) = type_section_at(description) {
+        description.drain(heading..heading + 2);
+        return Some(text);
+    }
+    let paragraph = description.iter().position(|p| {
+        p.trim_start()
+            .strip_prefix("Type:")
+            .is_some_and(|text| text.contains("::") || text.contains("->"))
+    })?;
+    let text = description[paragraph].trim_start()["Type:".len()..].to_string();
+    let text = handle_indentation(&text)?;
+    description.remove(paragraph);
+    Some(text)
+}
+
+/// Split a type signature at its top-level arrows.
+pub fn parse_type(text: &str) -> TypeSignature {
+    let (name, ty) = match split_top_level(text, "::").as_slice() {
+        [name, ty] if !name.is_empty() && !name.contains(char::is_whitespace) => {
+            (Some(name.to_string()), ty.to_string())
+        }
+        _ => (None, text.trim().to_string()),
+    };
+    let mut args = split_top_level(&ty, "->");
+    let returns = args.pop().unwrap_or_default();
+    TypeSignature {
+        text: text.to_string(),
+        name,
+        args,
+        returns,
+    }
+}
+
+/// A value of type `ty` to paste, if the type says what it looks like.
+fn type_placeholder(ty: &str) -> Option<&'static str> {
+    let ty = ty.trim();
+    if ty.starts_with('[') || ty.starts_with("List") || ty.starts_with("list") {
+        return Some("[ ... ]");
+    }
+    if ty.starts_with('(') && ty.contains("->") {
+        return Some("(x: ...)");
+    }
+    if ty.starts_with('{') || ty.starts_with("AttrsOf") || ty.starts_with("attrsOf") {
+        return Some("{ ... }");
+    }
+    match ty.to_ascii_lowercase().as_str() {
+        "string" | "str" => Some("\"\""),
+        "bool" | "boolean" => Some("false"),
+        "int" | "integer" | "number" | "float" => Some("0"),
+        "path" => Some("./."),
+        "attrset" | "attrs" => Some("{ ... }"),
+        _ => None,
+    }
+}
+
+/// A call of the function `title` to paste and fill in, with an argument
+/// per parameter or argument type: a pattern parameter as a set of its
+/// required attributes, an argument of a known type as a value of that type
+/// (`[ ... ]`, `""`, `{ ... }`), and anything else as the parameter's name.
+/// `None` if nothing is known about the arguments.
+pub fn usage_snippet(title: &str, signature: &Signature) -> Option<String> {
+    let types = signature
+        .fn_type
+        .as_ref()
+        .map_or(&[][..], |t| t.args.as_slice());
+    let count = signature.params.len().max(types.len());
+    if count == 0 {
+        return None;
+    }
+    let args: Vec<String> = (0..count)
+        .map(|i| match signature.params.get(i) {
+            Some(SignatureParam::Pattern { attrs, .. }) => {
+                let required: Vec<String> = attrs
+                    .iter()
+                    .filter(|attr| !attr.optional)
+                    .map(|attr| format!("{} = ...;", attr.name))
+                    .collect();
+                if required.is_empty() {
+                    "{ }".to_string()
+                } else {
+                    format!("{{ {} }}", required.join(" "))
+                }
+            }
+            param => match (types.get(i).and_then(|ty| type_placeholder(ty)), param) {
+                (Some(placeholder), _) => placeholder.to_string(),
+                (None, Some(SignatureParam::Positional { name })) => name.clone(),
+                (None, _) => "...".to_string(),
+            },
+        })
+        .collect();
+    Some(format!("{} {}", title, args.join(" ")))
+}
+
+/// Split `text` on `sep` outside of brackets, trimming each part and
+/// collapsing internal whitespace.
+fn split_top_level(text: &str, sep: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if depth == 0 && rest.starts_with(sep) {
+            parts.push(&text[start..i]);
+            i += sep.len();
+            start = i;
+            continue;
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    parts.push(&text[start..]);
+    parts
+        .into_iter()
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
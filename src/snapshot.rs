@@ -0,0 +1,113 @@
+//! Golden-file snapshots of generated documentation.
+//!
+//! Projects that embed docgen can keep the rendered markdown for each input
+//! file under a snapshot directory and check in CI that it still matches,
+//! without writing their own test harness around the CLI.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Default directory golden outputs are written to.
+pub const DEFAULT_SNAPSHOT_DIR: &str = "docs/snapshots";
+
+/// A golden output that does not match what docgen renders now.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub input: PathBuf,
+    pub snapshot: PathBuf,
+    /// First differing line as (line number, expected, actual); `None` when
+    /// the snapshot file is missing.
+    pub first_difference: Option<(usize, String, String)>,
+}
+
+/// Location of the golden output for `input` below `dir`.
+///
+/// The input's relative path is mirrored with a `.md` extension, so inputs with
+/// the same file name in different directories do not collide. Absolute paths
+/// and `..` components are dropped.
+pub fn snapshot_path(dir: &Path, input: &Path) -> PathBuf {
+    let relative: PathBuf = input
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    dir.join(relative.with_extension("md"))
+}
+
+/// Render every input and overwrite its golden output.
+pub fn update_snapshots(
+    dir: &Path,
+    inputs: &[PathBuf],
+    render: impl Fn(&Path) -> String,
+) -> Result<Vec<PathBuf>, String> {
+    let mut written = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let path = snapshot_path(dir, input);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Error creating {}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, render(input))
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Render every input and compare it against its golden output.
+pub fn verify_snapshots(
+    dir: &Path,
+    inputs: &[PathBuf],
+    render: impl Fn(&Path) -> String,
+) -> Result<Vec<Mismatch>, String> {
+    let mut mismatches = Vec::new();
+    for input in inputs {
+        let snapshot = snapshot_path(dir, input);
+        let expected = match fs::read_to_string(&snapshot) {
+            Ok(expected) => expected,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                mismatches.push(Mismatch {
+                    input: input.clone(),
+                    snapshot,
+                    first_difference: None,
+                });
+                continue;
+            }
+            Err(e) => return Err(format!("Error reading {}: {}", snapshot.display(), e)),
+        };
+        let actual = render(input);
+        if let Some(difference) = first_difference(&expected, &actual) {
+            mismatches.push(Mismatch {
+                input: input.clone(),
+                snapshot,
+                first_difference: Some(difference),
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Find the first line (1-based) where two documents differ.
+fn first_difference(expected: &str, actual: &str) -> Option<(usize, String, String)> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (None, None) => {
+                // Only the trailing newline differs.
+                return Some((line, String::new(), String::new()));
+            }
+            (e, a) => {
+                return Some((
+                    line,
+                    e.unwrap_or_default().to_string(),
+                    a.unwrap_or_default().to_string(),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Options read from the source of a NixOS module, without evaluating it.
+//!
+//! Where `nix eval` is not available, a module's options can still be
+//! previewed from the `mkOption` and `mkEnableOption` calls declared below
+//! its `options` attribute:
+//!
+//! ```nix
+//! { lib, ... }:
+//! {
+//!   options.services.foo = {
+//!     enable = lib.mkEnableOption "foo";
+//!     port = lib.mkOption {
+//!       type = lib.types.port;
+//!       default = 8080;
+//!       description = "Port to listen on.";
+//!     };
+//!   };
+//! }
+//! ```
+//!
+//! becomes the options `services.foo.enable` and `services.foo.port` in the
+//! `options.json` format, with the module as their declaration. The type is
+//! the text of its expression (`lib.types.port`) rather than its
+//! description, defaults and examples are the text of theirs unless given
+//! with `literalExpression` or `literalMD`, and `defaultText` takes the place
+//! of `default`. Options of submodules, options bound in `let` and options
+//! marked `internal` or not `visible` are left out.
+
+use crate::nixfmt::reindent;
+use crate::{callee_name, string_literal, unparen};
+use rnix::SyntaxNode;
+use rnix::ast::{Apply, Attr, AttrpathValue, Expr, HasEntry, LetIn};
+use rowan::ast::AstNode;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+
+/// Functions declaring an option.
+const OPTION_FUNCTIONS: [&str; 2] = ["mkOption", "mkEnableOption"];
+
+/// The option function called by `apply`, if it is one.
+fn option_function(apply: &Apply) -> Option<String> {
+    callee_name(&unparen(apply.lambda()?)?).filter(|name| OPTION_FUNCTIONS.contains(&name.as_str()))
+}
+
+/// The text of `expr` on one line.
+fn source_text(expr: &Expr) -> String {
+    expr.syntax()
+        .to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The argument of `expr` if it calls `function`.
+fn argument_of(expr: &Expr, function: &str) -> Option<Expr> {
+    let Expr::Apply(apply) = expr else {
+        return None;
+    };
+    (callee_name(&unparen(apply.lambda()?)?)? == function)
+        .then(|| apply.argument())
+        .flatten()
+        .and_then(unparen)
+}
+
+/// A description: a string, also wrapped in `mdDoc`, or else the text of
+/// the expression.
+fn description(expr: Expr) -> String {
+    let expr = argument_of(&expr, "mdDoc").unwrap_or(expr);
+    string_literal(&expr)
+        .map(|text| text.trim().to_string())
+        .unwrap_or_else(|| source_text(&expr))
+}
+
+/// A default or example as the tagged value of `options.json`.
+fn option_value(expr: Expr) -> Value {
+    for (function, tag) in [
+        ("literalExpression", "literalExpression"),
+        ("literalExample", "literalExpression"),
+        ("literalMD", "literalMD"),
+    ] {
+        if let Some(text) = argument_of(&expr, function).and_then(|arg| string_literal(&arg)) {
+            return json!({ "_type": tag, "text": text.trim() });
+        }
+    }
+    let text = expr.syntax().to_string();
+    json!({ "_type": "literalExpression", "text": reindent(&text) })
+}
+
+/// The name of an attribute, unless it is computed.
+fn attr_name(attr: Attr) -> Option<String> {
+    match attr {
+        Attr::Ident(ident) => Some(ident.to_string()),
+        Attr::Str(s) => string_literal(&Expr::Str(s)),
+        Attr::Dynamic(_) => None,
+    }
+}
+
+/// The path of the option declared by `call`, below the `options` attribute
+/// it is declared in.
+fn option_loc(call: &SyntaxNode) -> Option<Vec<String>> {
+    let mut path = vec![];
+    for node in call.ancestors().skip(1) {
+        if let Some(apv) = AttrpathValue::cast(node.clone()) {
+            if node
+                .parent()
+                .is_some_and(|parent| LetIn::can_cast(parent.kind()))
+            {
+                return None;
+            }
+            let names: Option<Vec<String>> = apv.attrpath()?.attrs().map(attr_name).collect();
+            path.splice(0..0, names?);
+        } else if Apply::cast(node).is_some_and(|apply| option_function(&apply).is_some()) {
+            // An option of a submodule.
+            return None;
+        }
+    }
+    let start = path.iter().position(|name| name == "options")? + 1;
+    Some(path.split_off(start)).filter(|loc| !loc.is_empty())
+}
+
+/// The `options.json` entry of the option declared by `call` of `function`.
+fn option(call: &Apply, function: &str, loc: &[String], declaration: &str) -> Option<Value> {
+    let argument = unparen(call.argument()?)?;
+    if function == "mkEnableOption" {
+        let name = string_literal(&argument).unwrap_or_else(|| source_text(&argument));
+        return Some(json!({
+            "loc": loc,
+            "description": format!("Whether to enable {}.", name),
+            "type": "boolean",
+            "default": false,
+            "example": true,
+            "declarations": [declaration],
+            "readOnly": false,
+        }));
+    }
+    let mut option = json!({
+        "loc": loc,
+        "description": "",
+        "declarations": [declaration],
+        "readOnly": false,
+    });
+    let Expr::AttrSet(set) = argument else {
+        return Some(option);
+    };
+    let mut default_text = None;
+    for apv in set.attrpath_values() {
+        let (Some(path), Some(value)) = (apv.attrpath(), apv.value()) else {
+            continue;
+        };
+        let is_true = source_text(&value) == "true";
+        match path.to_string().as_str() {
+            "description" => option["description"] = description(value).into(),
+            "type" => option["type"] = source_text(&value).into(),
+            "default" => option["default"] = option_value(value),
+            "defaultText" => default_text = Some(option_value(value)),
+            "example" => option["example"] = option_value(value),
+            "readOnly" => option["readOnly"] = is_true.into(),
+            "internal" if is_true => return None,
+            "visible" if source_text(&value) == "false" => return None,
+            _ => {}
+        }
+    }
+    if let Some(default_text) = default_text {
+        option["default"] = default_text;
+    }
+    Some(option)
+}
+
+/// The options declared in the source of a module, as an `options.json`
+/// object, declared in `declaration`.
+pub fn module_options(root: &rnix::Root, declaration: &str) -> Map<String, Value> {
+    let mut options = Map::new();
+    for node in root.syntax().descendants() {
+        let Some(call) = Apply::cast(node) else {
+            continue;
+        };
+        let Some(function) = option_function(&call) else {
+            continue;
+        };
+        let Some(loc) = option_loc(call.syntax()) else {
+            continue;
+        };
+        if let Some(option) = option(&call, &function, &loc, declaration) {
+            options.insert(loc.join("."), option);
+        }
+    }
+    options
+}
+
+/// Read the options of the modules `files` into one `options.json`
+/// document.
+pub fn options_json(files: &[impl AsRef<Path>]) -> Result<String, String> {
+    let mut options = Map::new();
+    for file in files {
+        let file = file.as_ref();
+        let root = crate::parse_file(file)?;
+        let declaration = file.to_string_lossy().replace('\\', "/");
+        options.extend(module_options(&root, &declaration));
+    }
+    serde_json::to_string(&options).map_err(|e| e.to_string())
+}
@@ -0,0 +1,470 @@
+//! Entries in version 2 of the JSON output.
+//!
+//! Version 1 serializes [`ManualEntry`] as it is: the type, inputs and
+//! examples stay folded into the description paragraphs. Version 2 takes
+//! them out into fields of their own:
+//!
+//! - `source`: file, line and column of the start and end of the binding,
+//! - `type`: the parsed `# Type` signature, or the one from a types file,
+//! - `arguments`: the lambda parameters in order, pattern attributes with
+//!   their defaults, each with its documentation from an inline doc comment
+//!   or the `# Inputs` (or `# Arguments`) section,
+//! - `examples`: the code blocks of the `# Example(s)` sections, with the
+//!   heading and text introducing each.
+//!
+//! The description keeps everything else as one markdown string. An inputs
+//! section is only taken out when it documents parameters of the function
+//! and nothing else.
+
+use crate::commonmark::{Argument, Deprecation, EntryKind, ManualEntry, SeeAlso, Stability};
+use crate::format::{get_fence, trim_leading_whitespace};
+use crate::signature::{self, SignatureParam, TypeSignature};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// Where a binding is defined. Lines and columns are 1-based, columns
+/// count characters and the end is exclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// The span of the byte range `range` of `src`.
+    pub fn new(file: &str, src: &str, range: Range<usize>) -> Span {
+        let position = |offset: usize| {
+            let before = &src[..offset.min(src.len())];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        };
+        let (line, column) = position(range.start);
+        let (end_line, end_column) = position(range.end);
+        Span {
+            file: file.to_string(),
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+/// A documented parameter of a function.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Parameter {
+    /// `x: ...`
+    Positional { name: String, doc: Option<String> },
+    /// `{ a, b ? 1, ... }@args: ...`
+    Pattern {
+        attrs: Vec<PatternAttr>,
+        /// Whether the pattern accepts further attributes (`...`).
+        ellipsis: bool,
+        /// Name the whole argument is bound to with `@`.
+        bind: Option<String>,
+    },
+}
+
+/// A documented attribute of a pattern parameter.
+#[derive(Clone, Debug, Serialize)]
+pub struct PatternAttr {
+    pub name: String,
+    /// Whether the attribute has a default value and can be left out.
+    pub optional: bool,
+    /// Source text of the default value.
+    pub default: Option<String>,
+    pub doc: Option<String>,
+}
+
+/// A code block of an example section.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Example {
+    /// Heading of the example, such as "`lib.strings.concatStrings` usage
+    /// example".
+    pub title: Option<String>,
+    /// Text between the heading or the previous example and the code.
+    pub description: Option<String>,
+    /// Language of the code block.
+    pub lang: Option<String>,
+    pub code: String,
+}
+
+/// An entry as written by version 2 of the JSON output.
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub prefix: String,
+    pub category: String,
+    /// Attribute path of the binding within its file.
+    pub name: String,
+    /// Full title, such as `lib.strings.concatStrings`.
+    pub title: String,
+    pub kind: EntryKind,
+    pub source: Option<Span>,
+    /// Location given with `--locs`.
+    pub location: Option<String>,
+    /// The doc comment without the sections taken out, as markdown.
+    pub description: String,
+    #[serde(rename = "type")]
+    pub fn_type: Option<TypeSignature>,
+    pub arguments: Vec<Parameter>,
+    pub examples: Vec<Example>,
+    pub usage: Option<String>,
+    /// Source of the value, for constants.
+    pub value: Option<String>,
+    pub stability: Option<Stability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// Release the entry is available since.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Related functions from `@see` tags.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub see_also: Vec<SeeAlso>,
+    /// What the function returns, from `@return`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+    /// Whether the binding is marked internal, with `--keep-internal`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub internal: bool,
+    /// Anchor pinned by the doc comment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+    /// Topics from `@tag` lines.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub meta: BTreeMap<String, String>,
+    /// [`ManualEntry::content_hash`] of the entry.
+    pub sha256: String,
+    /// The description as HTML, with `--json-html`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_html: Option<String>,
+}
+
+/// Sections taken out of the description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Section {
+    Type,
+    Examples,
+    Inputs,
+}
+
+/// The section a heading line starts, with its level.
+fn section_heading(line: &str) -> Option<(usize, Option<Section>)> {
+    let level = line.len() - line.trim_start_matches('#').len();
+    let title = line[level..].strip_prefix(' ')?;
+    // Drop an explicit anchor, `# Examples {#ex-concat}`.
+    let title = match title.rfind(" {#") {
+        Some(i) if title.trim_end().ends_with('}') => &title[..i],
+        _ => title,
+    };
+    let section = match title.trim().to_lowercase().as_str() {
+        "type" => Some(Section::Type),
+        "example" | "examples" => Some(Section::Examples),
+        "inputs" | "arguments" => Some(Section::Inputs),
+        _ => None,
+    };
+    (level > 0).then_some((level, section))
+}
+
+/// A part of a doc comment: text kept in the description, or a section to
+/// take out with its heading line.
+struct Part<'a> {
+    section: Option<(Section, &'a str)>,
+    text: String,
+}
+
+/// Split a doc comment into its parts, in order.
+fn split_sections(doc: &str) -> Vec<Part<'_>> {
+    let mut parts = vec![Part {
+        section: None,
+        text: String::new(),
+    }];
+    let mut current: Option<(usize, Section)> = None;
+    let mut fence: Option<(usize, char)> = None;
+    for line in doc.split_inclusive('\n') {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                if fence.is_none()
+                    && let Some((level, section)) = section_heading(line.trim_end())
+                {
+                    if let Some(section) = section {
+                        current = Some((level, section));
+                        parts.push(Part {
+                            section: Some((section, line)),
+                            text: String::new(),
+                        });
+                        continue;
+                    }
+                    if current.is_some_and(|(open, _)| level <= open) {
+                        current = None;
+                        parts.push(Part {
+                            section: None,
+                            text: String::new(),
+                        });
+                    }
+                }
+            }
+        }
+        parts
+            .last_mut()
+            .expect("parts start non-empty")
+            .text
+            .push_str(line);
+    }
+    parts
+}
+
+/// The examples of an example section.
+fn examples(section: &str) -> Vec<Example> {
+    let mut examples = vec![];
+    let mut title = None;
+    let mut text = String::new();
+    let mut code: Option<(usize, char, Option<String>, String)> = None;
+    for line in section.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        if let Some((count, ch, lang, body)) = &mut code {
+            if get_fence(&fence_line, false).is_some_and(|(n, c)| c == *ch && n >= *count) {
+                let description = text.trim();
+                examples.push(Example {
+                    title: title.take(),
+                    description: (!description.is_empty()).then(|| description.to_string()),
+                    lang: lang.take(),
+                    code: std::mem::take(body),
+                });
+                text.clear();
+                code = None;
+            } else {
+                body.push_str(line);
+                body.push('\n');
+            }
+        } else if let Some((count, ch)) = get_fence(&fence_line, true).filter(|(n, _)| *n >= 3) {
+            let lang = fence_line[count..]
+                .split_whitespace()
+                .next()
+                .map(String::from);
+            code = Some((count, ch, lang, String::new()));
+        } else if line.starts_with('#') {
+            title = Some(line.trim_start_matches('#').trim().to_string());
+        } else if !line.trim_start().starts_with(":::") {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    examples
+}
+
+/// A name in a code span at the start of `text`, and the text after it.
+fn code_name(text: &str) -> Option<(String, &str)> {
+    let rest = text.strip_prefix('`')?;
+    let (name, rest) = rest.split_once('`')?;
+    Some((name.trim().to_string(), rest))
+}
+
+/// Docs of the names of a list (`- `name`: doc`) or definition list
+/// (`` `name` `` followed by `: doc`) of an inputs section, or `None` if it
+/// holds anything else.
+fn input_docs(section: &str) -> Option<Vec<(String, String)>> {
+    let mut docs: Vec<(String, String)> = vec![];
+    let mut term: Option<String> = None;
+    for line in section.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            let (name, rest) = code_name(item)?;
+            let doc = rest.trim_start().strip_prefix(':').unwrap_or(rest).trim();
+            docs.push((name, doc.to_string()));
+            term = None;
+        } else if let Some(doc) = trimmed.strip_prefix(": ") {
+            match term.take() {
+                Some(name) => docs.push((name, doc.trim().to_string())),
+                None => docs.last_mut()?.1.push_str(&format!("\n{}", doc.trim())),
+            }
+        } else if let Some((name, _)) =
+            code_name(trimmed).filter(|(_, rest)| rest.trim().is_empty())
+        {
+            term = Some(name);
+        } else if line.starts_with([' ', '\t']) && term.is_none() {
+            // Continuation of the previous doc.
+            let (_, doc) = docs.last_mut()?;
+            doc.push('\n');
+            doc.push_str(trimmed);
+        } else {
+            return None;
+        }
+    }
+    (!docs.is_empty() && term.is_none()).then_some(docs)
+}
+
+/// The parameters of an entry, documented from inline doc comments and
+/// `docs`.
+fn parameters(entry: &ManualEntry, docs: &HashMap<String, String>) -> Vec<Parameter> {
+    let inline: HashMap<&str, &str> = entry
+        .args
+        .iter()
+        .flat_map(|arg| match arg {
+            Argument::Flat(arg) => std::slice::from_ref(arg),
+            Argument::Pattern(args) => args.as_slice(),
+        })
+        .filter_map(|arg| Some((arg.name.as_str(), arg.doc.as_deref()?)))
+        .filter(|(_, doc)| !doc.trim().is_empty())
+        .collect();
+    let doc = |name: &str| {
+        inline
+            .get(name)
+            .map(|doc| doc.trim().to_string())
+            .or_else(|| docs.get(name).cloned())
+    };
+    let Some(signature) = &entry.signature else {
+        return vec![];
+    };
+    signature
+        .params
+        .iter()
+        .map(|param| match param {
+            SignatureParam::Positional { name } => Parameter::Positional {
+                name: name.clone(),
+                doc: doc(name),
+            },
+            SignatureParam::Pattern {
+                attrs,
+                ellipsis,
+                bind,
+            } => Parameter::Pattern {
+                attrs: attrs
+                    .iter()
+                    .map(|attr| PatternAttr {
+                        name: attr.name.clone(),
+                        optional: attr.optional,
+                        default: attr.default.clone(),
+                        doc: doc(&attr.name),
+                    })
+                    .collect(),
+                ellipsis: *ellipsis,
+                bind: bind.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Names of the parameters of an entry, pattern attributes included.
+fn parameter_names(entry: &ManualEntry) -> Vec<&str> {
+    let Some(signature) = &entry.signature else {
+        return vec![];
+    };
+    signature
+        .params
+        .iter()
+        .flat_map(|param| match param {
+            SignatureParam::Positional { name } => vec![name.as_str()],
+            SignatureParam::Pattern { attrs, bind, .. } => attrs
+                .iter()
+                .map(|attr| attr.name.as_str())
+                .chain(bind.as_deref())
+                .collect(),
+        })
+        .collect()
+}
+
+impl Entry {
+    /// The version 2 form of `entry`, defined at `source`.
+    pub fn new(entry: &ManualEntry, source: Option<Span>) -> Entry {
+        let doc = entry.description.join("\n\n");
+        let names = parameter_names(entry);
+        let mut description = String::new();
+        let mut examples_found = vec![];
+        let mut docs = HashMap::new();
+        for Part { section, text } in split_sections(&doc) {
+            match section {
+                None => description.push_str(&text),
+                Some((Section::Type, _)) => {}
+                Some((Section::Examples, _)) => examples_found.extend(examples(&text)),
+                Some((Section::Inputs, heading)) => match input_docs(&text) {
+                    Some(found) if found.iter().all(|(name, _)| names.contains(&name.as_str())) => {
+                        docs.extend(found)
+                    }
+                    _ => {
+                        description.push_str(heading);
+                        description.push_str(&text);
+                    }
+                },
+            }
+        }
+        if let Some(example) = &entry.example {
+            examples_found.push(Example {
+                title: None,
+                description: None,
+                lang: None,
+                code: format!("{}\n", example.trim()),
+            });
+        }
+        let (_, title) = entry.get_ident_title();
+        Entry {
+            prefix: entry.prefix.clone(),
+            category: entry.category.clone(),
+            name: entry.name.clone(),
+            title,
+            kind: entry.kind,
+            source,
+            location: entry.location.clone(),
+            description: description.trim().to_string(),
+            fn_type: entry
+                .signature
+                .as_ref()
+                .and_then(|signature| signature.fn_type.clone())
+                .or_else(|| entry.fn_type.as_deref().map(signature::parse_type)),
+            arguments: parameters(entry, &docs),
+            examples: examples_found,
+            usage: entry.usage.clone(),
+            value: entry.value.clone(),
+            stability: entry.stability,
+            deprecated: entry.deprecated.clone(),
+            since: entry.since.clone(),
+            see_also: entry.see_also.clone(),
+            returns: entry.returns.clone(),
+            internal: entry.internal,
+            anchor: entry.custom_anchor.clone(),
+            tags: entry.tags.clone(),
+            meta: entry.meta.iter().cloned().collect(),
+            sha256: entry.content_hash(),
+            description_html: None,
+        }
+    }
+}
+
+/// The version 2 forms of `entries`, collected from the source `src` of
+/// `file`. Entries whose binding is not found in the file, such as those of
+/// imported files, have no source.
+pub fn entries(file: &str, src: &str, entries: &[ManualEntry]) -> Vec<Entry> {
+    let ranges = rnix::Root::parse(src)
+        .ok()
+        .map(|root| crate::tags::binding_ranges(&root))
+        .unwrap_or_default();
+    entries
+        .iter()
+        .map(|entry| {
+            let source =
+                crate::tags::entry_range(&ranges, entry).map(|range| Span::new(file, src, range));
+            Entry::new(entry, source)
+        })
+        .collect()
+}
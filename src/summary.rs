@@ -0,0 +1,136 @@
+//! mdBook `SUMMARY.md` for the documents written by `manifest`.
+//!
+//! Each document is a chapter titled by its first heading and nested below
+//! the chapter of its parent category, if there is one (`systems.parse`
+//! below `systems`):
+//!
+//! ```markdown
+//! # Summary
+//!
+//! - [Strings](lib/strings.md)
+//! - [Systems](lib/systems.md)
+//!   - [Parsing](lib/systems.parse.md)
+//! ```
+//!
+//! The chapters are written between a line with [`START_MARKER`] and one
+//! with [`END_MARKER`]. If the summary already exists, only the lines between
+//! them are replaced, so the generated chapters can sit within a book
+//! written by hand; a summary without them gets them appended.
+
+use std::fs;
+use std::path::{Component, Path};
+
+/// Line opening the generated part of a summary.
+pub const START_MARKER: &str = "<!-- docgen:start -->";
+
+/// Line closing the generated part of a summary.
+pub const END_MARKER: &str = "<!-- docgen:end -->";
+
+/// A generated document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub category: String,
+    /// Link to the document, relative to the summary.
+    pub link: String,
+}
+
+/// The title of a generated document: its first level 1 heading, without
+/// its anchor.
+pub fn document_title(markdown: &str) -> Option<String> {
+    let heading = markdown.lines().find_map(|line| line.strip_prefix("# "))?;
+    let title = match heading.rsplit_once(" {#") {
+        Some((title, _)) => title,
+        None => heading,
+    };
+    Some(title.trim().to_string()).filter(|title| !title.is_empty())
+}
+
+/// The chapter list, sorted by category, each chapter indented below the
+/// closest chapter of a parent category.
+pub fn render_chapters(chapters: &[Chapter]) -> String {
+    let mut sorted: Vec<&Chapter> = chapters.iter().collect();
+    sorted.sort_by(|a, b| a.category.split('.').cmp(b.category.split('.')));
+    let mut list = String::new();
+    for chapter in &sorted {
+        let parts: Vec<&str> = chapter.category.split('.').collect();
+        let depth = (1..parts.len())
+            .filter(|&n| {
+                let parent = parts[..n].join(".");
+                sorted.iter().any(|c| c.category == parent)
+            })
+            .count();
+        let title = chapter.title.replace('[', "\\[").replace(']', "\\]");
+        list.push_str(&format!(
+            "{}- [{}]({})\n",
+            "  ".repeat(depth),
+            title,
+            chapter.link.replace(' ', "%20")
+        ));
+    }
+    list
+}
+
+/// The summary with `chapters`: `existing` with the part between the markers
+/// replaced or appended, or a new summary.
+pub fn update_summary(existing: Option<&str>, chapters: &[Chapter]) -> String {
+    let list = render_chapters(chapters);
+    let block = format!("{}\n{}{}\n", START_MARKER, list, END_MARKER);
+    if let Some(existing) = existing {
+        let lines: Vec<&str> = existing.lines().collect();
+        let start = lines.iter().position(|l| l.trim() == START_MARKER);
+        let end = lines.iter().rposition(|l| l.trim() == END_MARKER);
+        if let (Some(start), Some(end)) = (start, end)
+            && start < end
+        {
+            let mut summary = String::new();
+            for line in &lines[..=start] {
+                summary.push_str(line);
+                summary.push('\n');
+            }
+            summary.push_str(&list);
+            for line in &lines[end..] {
+                summary.push_str(line);
+                summary.push('\n');
+            }
+            return summary;
+        }
+        return format!("{}\n\n{}", existing.trim_end(), block);
+    }
+    format!("# Summary\n\n{}", block)
+}
+
+/// `to` as a relative link from the directory `from`, with `/` separators.
+pub fn relative_link(from: &Path, to: &Path) -> String {
+    fn parts(path: &Path) -> Vec<String> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut parts = vec![];
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => parts.push(name.to_string_lossy().into_owned()),
+                Component::ParentDir => {
+                    parts.pop();
+                }
+                _ => {}
+            }
+        }
+        parts
+    }
+    let (from, to) = (parts(from), parts(to));
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut link = vec![".."; from.len() - common];
+    link.extend(to[common..].iter().map(String::as_str));
+    link.join("/")
+}
+
+/// Write the summary at `path`, keeping the rest of an existing summary
+/// with markers.
+pub fn write_summary(path: &Path, chapters: &[Chapter]) -> Result<(), String> {
+    let existing = match fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+    };
+    fs::write(path, update_summary(existing.as_deref(), chapters))
+        .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
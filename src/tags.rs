@@ -0,0 +1,156 @@
+//! Tags files for editors.
+//!
+//! Each documented binding is tagged twice, by its full title
+//! (`lib.strings.concatStrings`) and by its name (`concatStrings`), at the
+//! line the binding is defined on, or inherited on for re-exports. Two
+//! formats are written:
+//!
+//! - ctags, as read by Vim and most editors: a sorted `name<TAB>file<TAB>line`
+//!   line per tag, with the `kind` of the entry (`f` for functions, `v` for
+//!   constants) as an extended field,
+//! - etags, as read by Emacs: a section per file listing the text of each
+//!   tagged line with the tag's name, line and byte offset.
+//!
+//! File names are relative to the directory of the tags file.
+
+use crate::commonmark::{EntryKind, ManualEntry};
+use rnix::ast::{Attr, AttrpathValue, Inherit};
+use rowan::ast::AstNode;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+/// A tagged binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// File of the binding, relative to the tags file.
+    pub file: String,
+    /// 1-based line of the binding.
+    pub line: usize,
+    /// Byte offset of the start of the line.
+    pub offset: usize,
+    /// Text of the line, without its line break.
+    pub text: String,
+    pub kind: EntryKind,
+}
+
+/// Byte ranges of the bindings and inherited names of a file, by name; the
+/// first definition of a name wins.
+pub fn binding_ranges(root: &rnix::Root) -> HashMap<String, Range<usize>> {
+    let mut ranges = HashMap::new();
+    for node in root.syntax().descendants() {
+        if let Some(apv) = AttrpathValue::cast(node.clone())
+            && let Some(path) = apv.attrpath()
+        {
+            let range = apv.syntax().text_range();
+            ranges
+                .entry(path.to_string())
+                .or_insert(range.start().into()..range.end().into());
+        } else if let Some(inherit) = Inherit::cast(node) {
+            for attr in inherit.attrs() {
+                if let Attr::Ident(ident) = attr {
+                    let range = ident.syntax().text_range();
+                    ranges
+                        .entry(ident.to_string())
+                        .or_insert(range.start().into()..range.end().into());
+                }
+            }
+        }
+    }
+    ranges
+}
+
+/// The range of the binding of `entry`, by its attribute path or else its
+/// last name component, as re-exports are bound by the latter.
+pub fn entry_range(
+    ranges: &HashMap<String, Range<usize>>,
+    entry: &ManualEntry,
+) -> Option<Range<usize>> {
+    let last = entry.name.rsplit('.').next().unwrap_or(&entry.name);
+    ranges
+        .get(&entry.name)
+        .or_else(|| ranges.get(last))
+        .cloned()
+}
+
+/// The tags of `entries`, collected from the source `src` of `file`.
+/// Entries whose binding cannot be found in the file are left out.
+pub fn file_tags(file: &str, src: &str, entries: &[ManualEntry]) -> Vec<Tag> {
+    let Ok(root) = rnix::Root::parse(src).ok() else {
+        return vec![];
+    };
+    let ranges = binding_ranges(&root);
+    let mut tags = vec![];
+    for entry in entries {
+        let last = entry.name.rsplit('.').next().unwrap_or(&entry.name);
+        let Some(Range { start, .. }) = entry_range(&ranges, entry) else {
+            continue;
+        };
+        let offset = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line = src[..offset].matches('\n').count() + 1;
+        let text = src[offset..].lines().next().unwrap_or_default().to_string();
+        let (_, title) = entry.get_ident_title();
+        let names = if title == last {
+            vec![title]
+        } else {
+            vec![title, last.to_string()]
+        };
+        for name in names {
+            tags.push(Tag {
+                name,
+                file: file.to_string(),
+                line,
+                offset,
+                text: text.clone(),
+                kind: entry.kind,
+            });
+        }
+    }
+    tags
+}
+
+/// A ctags file holding `tags`, sorted by name.
+pub fn render_ctags(tags: &[Tag]) -> String {
+    let mut sorted: Vec<&Tag> = tags.iter().collect();
+    sorted.sort_by(|a, b| (&a.name, &a.file, a.line).cmp(&(&b.name, &b.file, b.line)));
+    let mut out = String::from(
+        "!_TAG_FILE_FORMAT\t2\t/extended format/\n!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n!_TAG_PROGRAM_NAME\tdocgen\t//\n",
+    );
+    for tag in sorted {
+        let kind = match tag.kind {
+            EntryKind::Function => 'f',
+            EntryKind::Constant => 'v',
+        };
+        let _ = writeln!(
+            out,
+            "{}\t{}\t{};\"\tkind:{}",
+            tag.name, tag.file, tag.line, kind
+        );
+    }
+    out
+}
+
+/// An etags file holding `tags`, a section per file in the order of the
+/// tags.
+pub fn render_etags(tags: &[Tag]) -> String {
+    let mut files: Vec<&str> = vec![];
+    for tag in tags {
+        if !files.contains(&tag.file.as_str()) {
+            files.push(&tag.file);
+        }
+    }
+    let mut out = String::new();
+    for file in files {
+        let mut section = String::new();
+        for tag in tags.iter().filter(|tag| tag.file == file) {
+            let _ = writeln!(
+                section,
+                "{}\x7f{}\x01{},{}",
+                tag.text, tag.name, tag.line, tag.offset
+            );
+        }
+        let _ = write!(out, "\x0c\n{},{}\n{}", file, section.len(), section);
+    }
+    out
+}
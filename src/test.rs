@@ -1,10 +1,12 @@
-use rnix;
+use std::borrow::Cow;
 use std::fs;
 use std::path::PathBuf;
 
-use crate::{
-    Args, ManualEntry, collect_entries, extract_file_doc, format::shift_headings, main_with_args,
-    options, retrieve_description,
+use crate::{Args, main_with_args};
+use docgen::{
+    ManualEntry, collect_entries, devdocs, extract_file_doc, format::route_raw_blocks,
+    format::shift_headings, format::shift_headings_with_warnings, html, locale, man, normalize,
+    options, retrieve_description, sanitize, schema, search, snapshot, static_options, validate,
 };
 
 fn make_args(file: &str, category: &str, description: &str, locs: Option<&str>) -> Args {
@@ -13,11 +15,48 @@ fn make_args(file: &str, category: &str, description: &str, locs: Option<&str>)
         prefix: String::from("lib"),
         anchor_prefix: String::from("function-library-"),
         json_output: false,
+        stream: false,
+        json_version: 1,
+        json_html: false,
+        markdown_dialect: Default::default(),
+        keep_internal: false,
+        format: "markdown".to_string(),
         category: category.to_string(),
         description: description.to_string(),
+        description_file: None,
+        files: vec![],
+        dir: None,
+        glob: String::new(),
+        no_gitignore: false,
+        no_docgenignore: false,
+        ignore_file: vec![],
         file: Some(PathBuf::from(file)),
         locs: locs.map(PathBuf::from),
+        types: None,
         export: None,
+        eval_fallback: None,
+        legacy_comments: false,
+        inherit_docs: false,
+        usage_snippets: false,
+        group_by: None,
+        frontmatter: None,
+        validate_output: false,
+        sanitize_html: None,
+        allowed_html_tags: vec![],
+        es_bulk: None,
+        es_shape: docgen::search::BulkShape::Docgen,
+        es_flake: None,
+        link_style: Default::default(),
+        no_metadata: false,
+        see_index: None,
+        command_line: vec![],
+        example_style: Default::default(),
+        wrap: Default::default(),
+        code_lang: "nix".to_string(),
+        code_lang_map: Vec::new(),
+        config: None,
+        cache_dir: None,
+        lang: None,
     }
 }
 
@@ -42,11 +81,48 @@ fn test_main_minimal() {
         prefix: String::new(),
         anchor_prefix: String::new(),
         json_output: false,
+        stream: false,
+        json_version: 1,
+        json_html: false,
+        markdown_dialect: Default::default(),
+        keep_internal: false,
+        format: "markdown".to_string(),
         category: String::new(),
         description: String::new(),
+        description_file: None,
+        files: vec![],
+        dir: None,
+        glob: String::new(),
+        no_gitignore: false,
+        no_docgenignore: false,
+        ignore_file: vec![],
         file: Some(PathBuf::from("test/strings.nix")),
         locs: Some(PathBuf::from("test/strings.json")),
+        types: None,
         export: None,
+        eval_fallback: None,
+        legacy_comments: false,
+        inherit_docs: false,
+        usage_snippets: false,
+        group_by: None,
+        frontmatter: None,
+        validate_output: false,
+        sanitize_html: None,
+        allowed_html_tags: vec![],
+        es_bulk: None,
+        es_shape: docgen::search::BulkShape::Docgen,
+        es_flake: None,
+        link_style: Default::default(),
+        no_metadata: false,
+        see_index: None,
+        command_line: vec![],
+        example_style: Default::default(),
+        wrap: Default::default(),
+        code_lang: "nix".to_string(),
+        code_lang_map: Vec::new(),
+        config: None,
+        cache_dir: None,
+        lang: None,
     };
 
     let output = main_with_args(&args);
@@ -54,6 +130,235 @@ fn test_main_minimal() {
     insta::assert_snapshot!(output);
 }
 
+/// Check `value` against the parts of JSON Schema used by the output schemas.
+fn check_schema(
+    root: &serde_json::Value,
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    path: &str,
+) -> Result<(), String> {
+    use serde_json::Value;
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let target = root.pointer(&reference[1..]).expect("$ref resolves");
+        check_schema(root, target, value, path)?;
+    }
+    if let Some(alternatives) = schema.get("anyOf").and_then(Value::as_array)
+        && !alternatives
+            .iter()
+            .any(|alt| check_schema(root, alt, value, path).is_ok())
+    {
+        return Err(format!("{}: no alternative matches {}", path, value));
+    }
+    if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = alternatives
+            .iter()
+            .filter(|alt| check_schema(root, alt, value, path).is_ok())
+            .count();
+        if matching != 1 {
+            return Err(format!(
+                "{}: {} alternatives match {}",
+                path, matching, value
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        return Err(format!("{}: expected {}, got {}", path, expected, value));
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array)
+        && !values.contains(value)
+    {
+        return Err(format!("{}: {} is not one of {:?}", path, value, values));
+    }
+    if let Some(ty) = schema.get("type").and_then(Value::as_str) {
+        let matches = match ty {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "null" => value.is_null(),
+            _ => panic!("unexpected type {}", ty),
+        };
+        if !matches {
+            return Err(format!("{}: expected {}, got {}", path, ty, value));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if !object.contains_key(required.as_str().unwrap()) {
+                return Err(format!("{}: missing {}", path, required));
+            }
+        }
+        for (key, item) in object {
+            let path = format!("{}.{}", path, key);
+            match (
+                properties.and_then(|p| p.get(key)),
+                schema.get("additionalProperties"),
+            ) {
+                (Some(property), _) => check_schema(root, property, item, &path)?,
+                (None, Some(Value::Bool(false))) => return Err(format!("{}: not allowed", path)),
+                (None, Some(additional)) => check_schema(root, additional, item, &path)?,
+                (None, None) => {}
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            check_schema(root, items, item, &format!("{}[{}]", path, i))?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_output_schema() {
+    for version in [1, 2] {
+        let document = docgen::output_schema::document_schema(version);
+        let entry = docgen::output_schema::entry_schema(version);
+        for file in [
+            "test/strings.nix",
+            "test/doc-comment-arguments.nix",
+            "test/meta.nix",
+            "test/typed.nix",
+            "test/variants.nix",
+            "test/structured.nix",
+        ] {
+            let args = Args {
+                json_output: true,
+                json_version: version,
+                json_html: true,
+                usage_snippets: true,
+                ..make_args(file, "test", "Test", None)
+            };
+            let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+            check_schema(&document, &document, &output, "$").unwrap();
+            for value in output["entries"].as_array().unwrap() {
+                check_schema(&entry, &entry, value, "$").unwrap();
+            }
+        }
+    }
+
+    // Fields the schema does not know about are reported.
+    let document = docgen::output_schema::document_schema(1);
+    let mut unknown = serde_json::json!({ "version": 1, "entries": [] });
+    unknown["extra"] = true.into();
+    assert!(check_schema(&document, &document, &unknown, "$").is_err());
+}
+
+#[test]
+fn test_content_hash() {
+    let (_, entries) = crate::load_entries(&make_args("test/strings.nix", "strings", "", None));
+    let (_, located) = crate::load_entries(&make_args(
+        "test/strings.nix",
+        "strings",
+        "",
+        Some("test/strings.json"),
+    ));
+    let hashes: Vec<String> = entries.iter().map(ManualEntry::content_hash).collect();
+
+    // Locations and anchors do not change the hash, docs do.
+    assert_eq!(
+        hashes,
+        located
+            .iter()
+            .map(ManualEntry::content_hash)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(hashes.len(), 3);
+    assert!(hashes[0] != hashes[1] && hashes[1] != hashes[2]);
+    let mut changed = entries[0].clone();
+    changed.description[0].push_str(" Again.");
+    assert_ne!(changed.content_hash(), hashes[0]);
+    let mut renamed = entries[0].clone();
+    renamed.category = "text".to_string();
+    assert_ne!(renamed.content_hash(), hashes[0]);
+    let mut documented = entries[0].clone();
+    documented.args = vec![docgen::Argument::Flat(docgen::SingleArg {
+        name: "s".to_string(),
+        doc: None,
+    })];
+    let undocumented = documented.content_hash();
+    let docgen::Argument::Flat(arg) = &mut documented.args[0] else {
+        unreachable!()
+    };
+    arg.doc = Some("The string".to_string());
+    assert_ne!(documented.content_hash(), undocumented);
+
+    let mut args = make_args("test/strings.nix", "strings", "", None);
+    args.json_output = true;
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(output["entries"][0]["sha256"], hashes[0]);
+    args.json_version = 2;
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(output["entries"][0]["sha256"], hashes[0]);
+}
+
+#[test]
+fn test_json_html() {
+    let mut args = make_args("test/structured.nix", "structured", "", None);
+    args.json_output = true;
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert!(output["entries"][0].get("description_html").is_none());
+
+    args.json_html = true;
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    let html = output["entries"][0]["description_html"].as_str().unwrap();
+    assert!(
+        html.starts_with("<p>Pad a string on the left to the given width.</p>\n<h3>Inputs</h3>")
+    );
+    assert!(
+        html.contains(
+            "<pre><code class=\"language-nix\">padLeft <span class=\"tok-number\">5</span>"
+        )
+    );
+    // The markdown is still there.
+    assert_eq!(
+        output["entries"][0]["description"][0],
+        "Pad a string on the left to the given width."
+    );
+
+    args.json_version = 2;
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(
+        output["entries"][1]["description_html"],
+        "<p>Make a derivation writing a file.</p>\n<h3>Inputs</h3>\n<ul>\n<li><code>name</code>: Name of the derivation</li>\n<li><code>environment</code>: Not a parameter</li>\n</ul>\n"
+    );
+}
+
+#[test]
+fn test_json_output_v2() {
+    let args = Args {
+        json_output: true,
+        json_version: 2,
+        ..make_args("test/structured.nix", "structured", "Structured", None)
+    };
+    let output: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    insta::assert_snapshot!(serde_json::to_string_pretty(&output).unwrap());
+
+    // Streamed lines and single entries use the same form.
+    let mut streamed = Vec::new();
+    crate::stream_entries(&args, std::slice::from_ref(&args), &mut streamed).unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(streamed)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(&lines, output["entries"].as_array().unwrap());
+    let entry = crate::render_entry(&args, "padLeft", crate::EntryFormat::Json).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&entry).unwrap(),
+        output["entries"][0]
+    );
+}
+
 #[test]
 fn test_json_output() {
     let args = Args {
@@ -61,16 +366,64 @@ fn test_json_output() {
         prefix: String::from("lib"),
         anchor_prefix: String::from("function-library-"),
         json_output: true,
+        stream: false,
+        json_version: 1,
+        json_html: false,
+        markdown_dialect: Default::default(),
+        keep_internal: false,
+        format: "markdown".to_string(),
         category: String::from("strings"),
         description: String::from("string manipulation functions"),
+        description_file: None,
+        files: vec![],
+        dir: None,
+        glob: String::new(),
+        no_gitignore: false,
+        no_docgenignore: false,
+        ignore_file: vec![],
         file: Some(PathBuf::from("test/strings.nix")),
         locs: Some(PathBuf::from("test/strings.json")),
+        types: None,
         export: None,
+        eval_fallback: None,
+        legacy_comments: false,
+        inherit_docs: false,
+        usage_snippets: false,
+        group_by: None,
+        frontmatter: None,
+        validate_output: false,
+        sanitize_html: None,
+        allowed_html_tags: vec![],
+        es_bulk: None,
+        es_shape: docgen::search::BulkShape::Docgen,
+        es_flake: None,
+        link_style: Default::default(),
+        no_metadata: false,
+        see_index: None,
+        command_line: vec![],
+        example_style: Default::default(),
+        wrap: Default::default(),
+        code_lang: "nix".to_string(),
+        code_lang_map: Vec::new(),
+        config: None,
+        cache_dir: None,
+        lang: None,
     };
 
     let output = main_with_args(&args);
 
     insta::assert_snapshot!(output);
+
+    // Streamed, each entry is a line of its own.
+    let mut streamed = Vec::new();
+    crate::stream_entries(&args, std::slice::from_ref(&args), &mut streamed).unwrap();
+    let document: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(streamed)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(&lines, document["entries"].as_array().unwrap());
 }
 
 #[test]
@@ -81,8 +434,15 @@ fn test_arg_formatting() {
     let prefix = "lib";
     let category = "options";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -96,8 +456,15 @@ fn test_inherited_exports() {
     let prefix = "lib";
     let category = "let";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -111,13 +478,413 @@ fn test_doc_comment() {
     let prefix = "lib";
     let category = "debug";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
 }
 
+#[test]
+fn test_type_sections() {
+    let src = "{\n  /**\n    Join strings.\n\n    # Type\n\n    ```\n    join :: string -> [string] -> string\n    ```\n\n    # Example\n\n    ```nix\n    join \",\" [ ]\n    ```\n  */\n  join = sep: list: 1;\n\n  /**\n    Type: id :: a -> a\n\n    Return its argument.\n  */\n  id = x: x;\n\n  /**\n    Type: the kind of value to check.\n  */\n  check = t: t;\n}";
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+
+    assert_eq!(
+        entries[0].fn_type.as_deref(),
+        Some("join :: string -> [string] -> string")
+    );
+    assert_eq!(entries[0].description, ["Join strings."]);
+    assert_eq!(entries[0].example.as_deref(), Some("join \",\" [ ]"));
+    assert_eq!(
+        entries[0]
+            .signature
+            .as_ref()
+            .and_then(|s| s.fn_type.as_ref())
+            .map(|t| t.returns.as_str()),
+        Some("string")
+    );
+    assert_eq!(entries[1].fn_type.as_deref(), Some("id :: a -> a"));
+    assert_eq!(entries[1].description, ["Return its argument."]);
+    // Prose starting with "Type:" is not a signature.
+    assert_eq!(entries[2].fn_type, None);
+    assert_eq!(
+        entries[2].description,
+        ["Type: the kind of value to check."]
+    );
+
+    let json = serde_json::to_value(&entries[0]).unwrap();
+    assert_eq!(json["fn_type"], "join :: string -> [string] -> string");
+    let mut output = String::new();
+    entries[0].write_section("function-library-", &Default::default(), &mut output);
+    assert!(output.contains("**Type**: `join :: string -> [string] -> string`\n"));
+    assert!(!output.contains("### Type"));
+}
+
+#[test]
+fn test_example_sections() {
+    let src = "{\n  /**\n    Pad a string.\n\n    # Examples\n    :::{.example}\n    ## `pad` usage example\n\n    ```nix\n    pad 3 \"a\"\n\n    # spaces on the left\n    => \"  a\"\n    ```\n\n    ```\n    pad 1 \"ab\"\n    ```\n    :::\n\n    # Notes\n\n    Longer strings are kept.\n  */\n  pad = n: s: s;\n\n  /**\n    Trim a string.\n\n    # Example\n\n    Spaces are removed:\n\n    ```nix\n    trim \" a \"\n    ```\n  */\n  trim = s: s;\n\n  /**\n    Check a string.\n\n    # Example\n\n    ```nix-assert\n    check \"a\" == true\n    ```\n  */\n  check = s: true;\n\n  /**\n    Split a string.\n\n    # Examples\n    :::{.example}\n    ## Splitting on commas\n\n    ```nix\n    split \",\" \"a,b\"\n    ```\n    :::\n\n    :::{.example}\n    ## Splitting on nothing\n\n    ```nix\n    split \"\" \"ab\"\n    ```\n    :::\n  */\n  split = sep: s: s;\n}";
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+
+    assert_eq!(
+        entries[0].example.as_deref(),
+        Some("pad 3 \"a\"\n\n# spaces on the left\n=> \"  a\"\n\npad 1 \"ab\"")
+    );
+    assert_eq!(
+        entries[0].description,
+        ["Pad a string.", "### Notes", "Longer strings are kept."]
+    );
+    // Prose and assertions stay where they are written.
+    assert!(entries[1].example.is_none());
+    assert_eq!(entries[1].description[1], "### Example");
+    assert!(entries[2].example.is_none());
+    assert_eq!(entries[2].description[1], "### Example");
+    // Several titled examples keep their titles.
+    assert!(entries[3].example.is_none());
+    assert!(
+        entries[3]
+            .description
+            .iter()
+            .any(|p| p.contains("Splitting on nothing"))
+    );
+
+    let mut output = String::new();
+    entries[0].write_section_with(
+        "function-library-",
+        &Default::default(),
+        docgen::commonmark::ExampleStyle::Collapsed,
+        None,
+        &mut output,
+    );
+    assert!(output.ends_with(
+        ": Function argument\n\n\n<details id=\"function-library-example-lib.strings.pad\">\n<summary><code>lib.strings.pad</code> usage example</summary>\n\n```nix\npad 3 \"a\"\n\n# spaces on the left\n=> \"  a\"\n\npad 1 \"ab\"\n```\n\n</details>\n\n"
+    ));
+}
+
+#[test]
+fn test_include_fragments() {
+    let dir = std::env::temp_dir().join(format!("docgen-include-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("fragments")).unwrap();
+    fs::write(
+        dir.join("lib.nix"),
+        "/**\n  Helpers.\n\n  @include fragments/intro.md\n*/\n{\n  /**\n    Do a thing.\n\n    @include ./fragments/warning.md\n\n    ```\n    @include not-a-directive.md\n    ```\n  */\n  f = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("fragments/intro.md"), "# About\n\nShared intro.\n").unwrap();
+    fs::write(
+        dir.join("fragments/warning.md"),
+        "# Warning\n\nNot pure.\n\n@include nested.md\n",
+    )
+    .unwrap();
+    fs::write(dir.join("fragments/nested.md"), "## Details\n\nNested.\n").unwrap();
+
+    let file = dir.join("lib.nix");
+    let args = make_args(file.to_str().unwrap(), "helpers", "", None);
+    let (description, entries) = crate::load_entries(&args);
+    assert!(description.ends_with("Helpers.\n\n## About\n\nShared intro.\n"));
+    assert_eq!(
+        entries[0].description,
+        [
+            "Do a thing.",
+            "### Warning",
+            "Not pure.",
+            "#### Details",
+            "Nested.",
+            "```\n@include not-a-directive.md\n```"
+        ]
+    );
+
+    fs::write(
+        dir.join("fragments/loop.md"),
+        "Again:\n\n@include loop.md\n",
+    )
+    .unwrap();
+    let error =
+        docgen::include::expand_includes("@include fragments/loop.md", &dir, 1).unwrap_err();
+    assert!(error.ends_with("loop.md includes itself"));
+    // Fragments stay within the directory of the including file.
+    let error = docgen::include::expand_includes("@include /etc/passwd", &dir, 1).unwrap_err();
+    assert!(error.ends_with("included fragments must be relative paths"));
+    let error = docgen::include::expand_includes("@include ../lib.nix", &dir.join("fragments"), 1)
+        .unwrap_err();
+    assert!(error.contains("lib.nix is outside"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_cache() {
+    let dir = std::env::temp_dir().join(format!("docgen-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.nix"),
+        "{\n  /**\n    Do a thing.\n\n    @include ./note.md\n  */\n  f = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("note.md"), "Not pure.\n").unwrap();
+
+    let file = dir.join("lib.nix");
+    let mut args = make_args(file.to_str().unwrap(), "helpers", "", None);
+    args.cache_dir = Some(dir.join("cache"));
+    let record = crate::load_record(&args).unwrap();
+    let read: Vec<_> = record
+        .dependencies
+        .iter()
+        .map(|dependency| dependency.path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(
+        read,
+        ["lib.nix", "lib.types.json", "lib.types.nix", "note.md"]
+    );
+    assert_eq!(record.entries[0].description, ["Do a thing.", "Not pure."]);
+
+    // The stored record is read back as long as nothing changed.
+    let key = docgen::cache::key(&file, &args.load_settings()).unwrap();
+    let mut stored = record.clone();
+    stored.entries[0].description = vec!["From the cache.".to_string()];
+    docgen::cache::store(&dir.join("cache"), &key, &stored).unwrap();
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[0].description, ["From the cache."]);
+    // Other settings are loaded anew.
+    let mut other = make_args(file.to_str().unwrap(), "other", "", None);
+    other.cache_dir = args.cache_dir.clone();
+    let (_, entries) = crate::load_entries(&other);
+    assert_eq!(entries[0].description, ["Do a thing.", "Not pure."]);
+
+    // So are changed fragments, and companions that were not there before.
+    fs::write(dir.join("note.md"), "Pure after all.\n").unwrap();
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[0].description, ["Do a thing.", "Pure after all."]);
+    fs::write(dir.join("lib.types.json"), r#"{ "f": "a -> a" }"#).unwrap();
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[0].fn_type.as_deref(), Some("a -> a"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_precommit() {
+    let dir = std::env::temp_dir().join(format!("docgen-precommit-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("docgen.toml"),
+        "[checks]\noptions = [\"**/options.json\"]\nmin_coverage = 0.5\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("good.nix"),
+        "{\n  /** Do a thing. */\n  f = x: x;\n  g = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("bad.nix"),
+        "{\n  /** Do a thing. */\n  f = x: x;\n  g = x: x;\n  h = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("reexport.nix"),
+        "{\n  /** Do a thing. */\n  f = x: x;\n  inherit (import ./missing.nix) g;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("options.json"),
+        r#"{ "services.foo.enable": { "description": "", "type": "boolean", "default": false } }"#,
+    )
+    .unwrap();
+    fs::write(dir.join("notes.md"), "Not checked.\n").unwrap();
+
+    let mut args = make_args("", "", "", None);
+    args.config = Some(dir.join("docgen.toml"));
+    let files: Vec<PathBuf> = [
+        "good.nix",
+        "bad.nix",
+        "reexport.nix",
+        "options.json",
+        "notes.md",
+        "gone.nix",
+    ]
+    .iter()
+    .map(|name| dir.join(name))
+    .collect();
+    let problems = crate::precommit(&args, &files).unwrap();
+    let problems: Vec<String> = problems
+        .iter()
+        .map(|problem| problem.replace(&format!("{}/", dir.display()), ""))
+        .collect();
+    assert_eq!(
+        problems,
+        [
+            "bad.nix: 1 of 3 bindings documented (33%), below the 50% required",
+            "reexport.nix: missing.nix: No such file or directory (os error 2)",
+            "options.json: services.foo.enable: empty description",
+        ]
+    );
+    assert!(crate::precommit(&args, &files[..1]).unwrap().is_empty());
+
+    fs::write(
+        dir.join("docgen.toml"),
+        "[checks]\nallow_warnings = true\nmin_coverage = 2\n",
+    )
+    .unwrap();
+    let error = crate::precommit(&args, &files).unwrap_err();
+    assert_eq!(error, "min_coverage must be between 0 and 1, not 2");
+    fs::write(dir.join("docgen.toml"), "[checks]\nallow_warnings = true\n").unwrap();
+    assert!(crate::precommit(&args, &files).unwrap().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_watch_rebuilds() {
+    let dir = std::env::temp_dir().join(format!("docgen-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.nix"),
+        "{\n  /** Do a thing. */\n  f = x: x;\n  inherit (import ./other.nix) g;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("other.nix"),
+        "{\n  /** Do another. */\n  g = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("more.nix"),
+        "{\n  /** Do more. */\n  h = x: x;\n}\n",
+    )
+    .unwrap();
+
+    let mut args = make_args("", "", "", None);
+    args.no_metadata = true;
+    let files = [dir.join("lib.nix"), dir.join("more.nix")];
+    let out = dir.join("docs");
+    let mut documents = crate::watch_documents(&args, &files, &out).unwrap();
+    assert!(crate::rebuild_changed(&args, &mut documents).is_empty());
+
+    // Only the document re-exporting from the changed file is rebuilt.
+    fs::write(
+        dir.join("other.nix"),
+        "{\n  /** Do something else. */\n  g = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("lib.types.json"), r#"{ "f": "a -> a" }"#).unwrap();
+    let report = crate::rebuild_changed(&args, &mut documents);
+    assert_eq!(
+        report,
+        [format!(
+            "Rebuilt {} ({} changed, {} added)",
+            out.join("lib.md").display(),
+            dir.join("other.nix").display(),
+            dir.join("lib.types.json").display()
+        )]
+    );
+    let page = fs::read_to_string(out.join("lib.md")).unwrap();
+    assert!(page.contains("Do something else."));
+    assert!(page.contains("a -> a"));
+    assert!(crate::rebuild_changed(&args, &mut documents).is_empty());
+
+    // Documents failing to build are reported and tried again on the next
+    // change.
+    fs::write(
+        dir.join("more.nix"),
+        "{\n  /**\n    @include missing.md\n  */\n  h = x: x;\n}\n",
+    )
+    .unwrap();
+    let report = crate::rebuild_changed(&args, &mut documents);
+    assert_eq!(report.len(), 1);
+    assert!(report[0].starts_with(&format!("Error: {}", out.join("more.md").display())));
+    assert!(crate::rebuild_changed(&args, &mut documents).is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_legacy_comments() {
+    let mut output = String::from("");
+    let src = fs::read_to_string("test/legacy.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let mut entries = collect_entries(
+        nix.clone(),
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    // Ordinary comments are only read with --legacy-comments.
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["hasPrefix"]);
+    docgen::collect_legacy_docs(
+        &nix,
+        &mut entries,
+        "lib",
+        "strings",
+        &Default::default(),
+        &mut vec![],
+    );
+
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["concatStrings", "concatMapStrings", "hasPrefix", "identity"]
+    );
+    assert_eq!(
+        entries[0].fn_type.as_deref(),
+        Some("concatStrings :: [string] -> string")
+    );
+    assert_eq!(
+        entries[0].example.as_deref(),
+        Some("concatStrings [\"foo\" \"bar\"]\n=> \"foobar\"")
+    );
+    assert_eq!(
+        entries[1]
+            .signature
+            .as_ref()
+            .and_then(|s| s.fn_type.as_ref())
+            .map(|t| t.args.len()),
+        Some(2)
+    );
+    assert_eq!(entries[1].example, None);
+    assert_eq!(
+        entries[2].description,
+        ["Documented with an RFC 145 comment, which takes precedence."]
+    );
+    assert_eq!(entries[2].fn_type, None);
+
+    let mut args = make_args("test/legacy.nix", "strings", "", None);
+    assert!(!main_with_args(&args).contains("concatStrings"));
+    args.legacy_comments = true;
+    assert!(main_with_args(&args).contains("Concatenate a list of strings."));
+
+    for entry in entries {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+    insta::assert_snapshot!(output);
+}
+
 #[test]
 fn test_commonmark() {
     let src = fs::read_to_string("test/commonmark.md").unwrap();
@@ -142,11 +909,18 @@ fn test_doc_comment_section_description() {
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
     let prefix = "lib";
     let category = "debug";
-    let desc = retrieve_description(&nix, &"Debug", category);
-    let mut output = String::from(desc) + "\n";
+    let desc = retrieve_description(&nix, "Debug", category, &mut vec![]);
+    let mut output = desc + "\n";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -158,11 +932,18 @@ fn test_doc_comment_no_duplicate_arguments() {
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
     let prefix = "lib";
     let category = "debug";
-    let desc = retrieve_description(&nix, &"Debug", category);
-    let mut output = String::from(desc) + "\n";
+    let desc = retrieve_description(&nix, "Debug", category, &mut vec![]);
+    let mut output = desc + "\n";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -176,9 +957,23 @@ fn test_empty_prefix() {
         location: None,
         description: vec![],
         example: None,
+        usage: None,
         fn_type: None,
         name: "mapSimple'".to_string(),
         prefix: "".to_string(),
+        signature: None,
+        kind: Default::default(),
+        value: None,
+        stability: None,
+        deprecated: None,
+        since: None,
+        see_also: vec![],
+        returns: None,
+        internal: false,
+        inherit_doc: None,
+        custom_anchor: None,
+        tags: vec![],
+        meta: vec![],
     };
 
     let (ident, title) = test_entry.get_ident_title();
@@ -195,8 +990,15 @@ fn test_patterns() {
     let prefix = "lib";
     let category = "debug";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -210,8 +1012,15 @@ fn test_let_ident() {
     let prefix = "lib";
     let category = "math";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -225,8 +1034,15 @@ fn test_let_ident_chained() {
     let prefix = "lib";
     let category = "math";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &None) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
@@ -246,13 +1062,259 @@ fn test_export_flag() {
         "anotherExported".to_string(),
     ]);
 
-    for entry in collect_entries(nix, prefix, category, &Default::default(), &export_list) {
-        entry.write_section("function-library-", &mut output);
+    for entry in collect_entries(
+        nix,
+        prefix,
+        category,
+        &Default::default(),
+        &export_list,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
     }
 
     insta::assert_snapshot!(output);
 }
 
+#[test]
+fn test_schema_options() {
+    let input = schema::parse_schema_input("services.web=test/schema.json").unwrap();
+    assert_eq!(input.prefix, ["services", "web"]);
+    assert_eq!(
+        schema::parse_schema_input("test/schema.json")
+            .unwrap()
+            .prefix,
+        Vec::<String>::new()
+    );
+
+    let json = schema::options_json(&[input]).unwrap();
+    let parsed = options::parse_options_json(&json).unwrap();
+    let mut types: Vec<(&str, &str)> = parsed
+        .iter()
+        .map(|(name, opt)| {
+            (
+                name.as_str(),
+                opt.option_type.as_deref().unwrap_or_default(),
+            )
+        })
+        .collect();
+    types.sort();
+    assert_eq!(
+        types,
+        [
+            ("services.web.labels", "attribute set of string"),
+            (
+                "services.web.logLevel",
+                "one of \"debug\", \"info\", \"warn\""
+            ),
+            ("services.web.server.hosts", "list of string"),
+            ("services.web.server.port", "signed integer"),
+            ("services.web.tls.certificate", "null or string"),
+        ]
+    );
+
+    let render_opts = options::RenderOptions {
+        anchor_prefix: "opt-".to_string(),
+        include_declarations: true,
+        declarations_base_url: None,
+        revision: None,
+        declaration_profiles: vec![],
+        html_policy: None,
+        link_style: Default::default(),
+        wrap: Default::default(),
+        dialect: Default::default(),
+        code_langs: Default::default(),
+        cross_links: false,
+        referenced_by: false,
+        option_heading: options::DEFAULT_OPTION_HEADING.to_string(),
+        group_heading: None,
+        group_depth: 2,
+    };
+    let output =
+        options::render_options_document(&parsed, "Web server options", None, &render_opts);
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_static_module_options() {
+    use clap::Parser;
+
+    let json = static_options::options_json(&["test/module.nix"]).unwrap();
+    let parsed = options::parse_options_json(&json).unwrap();
+    let mut names: Vec<&str> = parsed.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "services.web.enable",
+            "services.web.hosts",
+            "services.web.port",
+            "services.web.stateDir",
+            "services.web.tls-certificate",
+            "services.web.vhosts",
+        ]
+    );
+
+    let option = |name: &str| &parsed[name];
+    let value = |value: &Option<options::OptionValue>| {
+        value
+            .as_ref()
+            .map(options::option_value_text)
+            .unwrap_or_default()
+    };
+    let enable = option("services.web.enable");
+    assert_eq!(enable.option_type.as_deref(), Some("boolean"));
+    assert_eq!(
+        enable.description.as_ref().map(|d| d.as_str()),
+        Some("Whether to enable the web server.")
+    );
+    assert_eq!(value(&enable.default), "false");
+
+    let hosts = option("services.web.hosts");
+    assert_eq!(
+        hosts.option_type.as_deref(),
+        Some("with lib.types; listOf str")
+    );
+    assert_eq!(value(&hosts.default), "[ \"localhost\" ]");
+    assert_eq!(value(&hosts.example), "[ \"example.org\" ]");
+    assert_eq!(
+        hosts.description.as_ref().map(|d| d.as_str()),
+        Some("Host names to answer to.")
+    );
+    assert_eq!(hosts.declarations[0].name(), "test/module.nix");
+
+    assert_eq!(
+        value(&option("services.web.stateDir").default),
+        "\"/var/lib/${config.services.web.user}\""
+    );
+    assert!(option("services.web.tls-certificate").read_only);
+    assert_eq!(
+        option("services.web.vhosts").option_type.as_deref(),
+        Some(
+            "lib.types.attrsOf (lib.types.submodule { options.root = lib.mkOption { type = lib.types.path; }; })"
+        )
+    );
+
+    let args = Args::parse_from(["docgen", "options", "--module", "test/module.nix"]);
+    assert!(matches!(
+        args.command,
+        Some(crate::Command::Options { file: None, ref module, .. }) if module.len() == 1
+    ));
+}
+
+#[cfg(feature = "browse")]
+#[test]
+fn test_options_browse() {
+    use crate::browse::{Browser, fuzzy_score, markdown_lines};
+    use clap::Parser;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+    assert!(fuzzy_score("nginxen", "services.nginx.enable").is_some());
+    assert!(fuzzy_score("xyz", "services.nginx.enable").is_none());
+    assert!(
+        fuzzy_score("enable", "services.test.enable").unwrap()
+            > fuzzy_score("enable", "services.nginx.virtualHosts.<name>.enableACME").unwrap()
+    );
+
+    let args = Args::parse_from(["docgen", "options", "browse", "--file", "test/options.json"]);
+    let Some(crate::Command::Options {
+        action: Some(crate::OptionsCommand::Browse { file, .. }),
+        ..
+    }) = args.command
+    else {
+        panic!("expected the options browse command");
+    };
+    let options = options::parse_options_file(&file.unwrap()).unwrap();
+    let mut browser = Browser::new(options.into_iter().collect());
+    let key = |browser: &mut Browser, code: KeyCode| browser.handle_key(KeyEvent::from(code));
+
+    let labels = |browser: &Browser| -> Vec<String> {
+        browser.rows().iter().map(|row| row.label.clone()).collect()
+    };
+    assert_eq!(
+        labels(&browser),
+        [
+            "documentation",
+            "networking",
+            "programs",
+            "services",
+            "system"
+        ]
+    );
+    // Open services, then services.test, and select its first option.
+    key(&mut browser, KeyCode::Char('G'));
+    key(&mut browser, KeyCode::Up);
+    key(&mut browser, KeyCode::Enter);
+    key(&mut browser, KeyCode::Enter);
+    key(&mut browser, KeyCode::Right);
+    assert_eq!(browser.selected_row().unwrap().path, ["services", "test"]);
+    assert_eq!(browser.selected_row().unwrap().children, 4);
+    key(&mut browser, KeyCode::Down);
+    assert_eq!(browser.selected_option().unwrap().0, "services.test.enable");
+    key(&mut browser, KeyCode::Left);
+    assert_eq!(browser.selected_row().unwrap().path, ["services", "test"]);
+    key(&mut browser, KeyCode::Left);
+    assert!(!browser.selected_row().unwrap().expanded);
+    assert_eq!(browser.rows().len(), 6);
+
+    // Search, and clear the search with escape.
+    key(&mut browser, KeyCode::Char('/'));
+    for c in "edname".chars() {
+        key(&mut browser, KeyCode::Char(c));
+    }
+    assert_eq!(browser.query(), "edname");
+    assert_eq!(browser.selected_option().unwrap().0, "programs.editor.name");
+    key(&mut browser, KeyCode::Enter);
+    assert!(!key(&mut browser, KeyCode::Esc));
+    assert_eq!(browser.query(), "");
+    assert!(key(&mut browser, KeyCode::Char('q')));
+
+    key(&mut browser, KeyCode::Char('/'));
+    for c in "extracfg".chars() {
+        key(&mut browser, KeyCode::Char(c));
+    }
+    let backend = ratatui::backend::TestBackend::new(100, 30);
+    let mut terminal = ratatui::Terminal::new(backend).unwrap();
+    terminal.draw(|frame| browser.draw(frame)).unwrap();
+    let screen: String = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect::<Vec<_>>()
+        .chunks(100)
+        .map(|line| line.concat())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(screen.contains("Search (1 of 8 options)"));
+    assert!(screen.contains("services.test.extraConfig"));
+    assert!(screen.contains("Type: strings concatenated with \"\\n\""));
+    assert!(screen.contains("Default: \"\""));
+    assert!(screen.contains("modules/services/test.nix"));
+    assert!(screen.contains("Extra configuration lines."));
+
+    let lines: Vec<String> = markdown_lines(
+        "# Usage\n\nSet {option}`services.test.enable` to *true*.\n\n- one\n- two\n\n```nix\n{ a = 1; }\n```",
+    )
+    .iter()
+    .map(|line| line.to_string())
+    .collect();
+    assert_eq!(
+        lines,
+        [
+            "Usage",
+            "",
+            "Set services.test.enable to true.",
+            "",
+            "• one",
+            "• two",
+            "",
+            "  { a = 1; }"
+        ]
+    );
+}
+
 #[test]
 fn test_options_rendering() {
     let json = fs::read_to_string("test/options.json").unwrap();
@@ -263,6 +1325,17 @@ fn test_options_rendering() {
         include_declarations: true,
         declarations_base_url: Some("https://github.com/example/repo".to_string()),
         revision: Some("main".to_string()),
+        declaration_profiles: vec![],
+        html_policy: None,
+        link_style: Default::default(),
+        wrap: Default::default(),
+        dialect: Default::default(),
+        code_langs: Default::default(),
+        cross_links: false,
+        referenced_by: false,
+        option_heading: options::DEFAULT_OPTION_HEADING.to_string(),
+        group_heading: None,
+        group_depth: 2,
     };
 
     let output = options::render_options_document(
@@ -281,7 +1354,7 @@ fn test_file_doc_extraction() {
     let src = fs::read_to_string("test/doc-comment-sec-heading.nix").unwrap();
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
 
-    let doc = extract_file_doc(&nix);
+    let doc = extract_file_doc(&nix, &mut vec![]);
     assert!(doc.is_some());
     insta::assert_snapshot!(doc.unwrap());
 }
@@ -290,8 +1363,3262 @@ fn test_file_doc_extraction() {
 fn test_file_doc_no_doc() {
     // A file without a file-level doc comment
     let src = "{ foo = 1; }";
-    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let nix = rnix::Root::parse(src).ok().expect("failed to parse input");
 
-    let doc = extract_file_doc(&nix);
+    let doc = extract_file_doc(&nix, &mut vec![]);
     assert!(doc.is_none());
 }
+
+#[test]
+fn test_normalize_headings_and_anchors() {
+    let src = "Title\n=====\n\n\n\n<a name=\"lib.foo\"></a>\n##   `foo` ##   \nSome text.  \n\n### Bar { #bar }\n";
+    let output = normalize::normalize_document(src, &Default::default());
+
+    assert_eq!(
+        output,
+        "# Title\n\n## `foo` {#lib.foo}\nSome text.\n\n### Bar {#bar}\n"
+    );
+}
+
+#[test]
+fn test_normalize_code_fences() {
+    let src = "```Nix\nx  \n\n\n# not a heading\n```\n\n~~~~\nuntagged\n~~~~\n";
+    let opts = normalize::NormalizeOptions {
+        default_fence_lang: Some("nix".to_string()),
+    };
+    let output = normalize::normalize_document(src, &opts);
+
+    assert_eq!(
+        output,
+        "```nix\nx  \n\n\n# not a heading\n```\n\n~~~~nix\nuntagged\n~~~~\n"
+    );
+
+    // Fences in list items keep their indentation.
+    let src = "- item\n\n  ```Nix\n  x\n  ```\n";
+    assert_eq!(
+        normalize::normalize_document(src, &Default::default()),
+        "- item\n\n  ```nix\n  x\n  ```\n"
+    );
+    // Indented code keeps its blank lines and trailing whitespace, and hard
+    // line breaks keep their spaces.
+    let src = "Text\n\n    a  \n\n\n    b\n\n\nLine one  \nline two  \n";
+    assert_eq!(
+        normalize::normalize_document(src, &Default::default()),
+        "Text\n\n    a  \n\n\n    b\n\nLine one  \nline two\n"
+    );
+}
+
+#[test]
+fn test_normalize_is_idempotent() {
+    let src = fs::read_to_string("src/snapshots/docgen__test__main.snap").unwrap();
+    let once = normalize::normalize_document(&src, &Default::default());
+    let twice = normalize::normalize_document(&once, &Default::default());
+
+    assert_eq!(once, twice);
+}
+
+#[test]
+fn test_snapshot_path_mirrors_input() {
+    let dir = PathBuf::from("docs/snapshots");
+
+    assert_eq!(
+        snapshot::snapshot_path(&dir, &PathBuf::from("lib/strings.nix")),
+        PathBuf::from("docs/snapshots/lib/strings.md")
+    );
+    assert_eq!(
+        snapshot::snapshot_path(&dir, &PathBuf::from("/abs/../lists.nix")),
+        PathBuf::from("docs/snapshots/abs/lists.md")
+    );
+}
+
+#[test]
+fn test_snapshot_update_then_verify() {
+    let dir = std::env::temp_dir().join(format!("docgen-snapshot-{}", std::process::id()));
+    let inputs = vec![PathBuf::from("test/strings.nix")];
+
+    snapshot::update_snapshots(&dir, &inputs, |_| "# strings\n".to_string()).unwrap();
+    let clean = snapshot::verify_snapshots(&dir, &inputs, |_| "# strings\n".to_string()).unwrap();
+    let stale = snapshot::verify_snapshots(&dir, &inputs, |_| "# lists\n".to_string()).unwrap();
+    let missing =
+        snapshot::verify_snapshots(&dir.join("none"), &inputs, |_| String::new()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(clean.is_empty());
+    assert_eq!(
+        stale[0].first_difference,
+        Some((1, "# strings".to_string(), "# lists".to_string()))
+    );
+    assert!(missing[0].first_difference.is_none());
+}
+
+#[test]
+fn test_shift_headings_degrades_gracefully() {
+    // CRLF line endings still close fences.
+    assert_eq!(
+        shift_headings("```nix\r\n# x\r\n```\r\n# y\r\n", 1),
+        "```nix\r\n# x\r\n```\r\n## y\r\n"
+    );
+
+    // Inline code with triple backticks does not open a fence.
+    assert_eq!(shift_headings("```a```\n# y\n", 1), "```a```\n## y\n");
+
+    // Absurd shifts saturate at H6 instead of overflowing.
+    assert_eq!(shift_headings("# y", usize::MAX), "###### y");
+
+    let (output, warnings) = shift_headings_with_warnings("####### x\n```\n# y\n", 1);
+    assert_eq!(output, "####### x\n```\n# y\n");
+    assert_eq!(
+        warnings,
+        vec![
+            "line 1: more than 6 '#' is not a heading, left unchanged",
+            "line 2: code fence is never closed, headings after it were not shifted",
+        ]
+    );
+
+    let (output, warnings) = shift_headings_with_warnings(
+        "# a
+### b
+#### c
+####d
+",
+        3,
+    );
+    assert_eq!(
+        output,
+        "#### a
+###### b
+###### c
+######d
+"
+    );
+    assert_eq!(
+        warnings,
+        vec!["line 3: H4 shifted by 3 would be H7, clamped to H6",]
+    );
+}
+
+#[test]
+fn test_doc_processing_never_panics() {
+    // A small deterministic stand-in for the fuzz target in fuzz/, run on
+    // every test invocation.
+    let pieces = [
+        "#", "# ", "#######", "```", "~~~", "````nix", "`", "\n", "\r\n", "    ", "\t", "/**",
+        "*/", "*", "a", "é", "{#x}", ":", "- ",
+    ];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    for _ in 0..2000 {
+        let mut input = String::new();
+        for _ in 0..(state % 40) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            input.push_str(pieces[(state % pieces.len() as u64) as usize]);
+        }
+
+        if let Some(doc) = docgen::format::handle_indentation(&input) {
+            shift_headings_with_warnings(&doc, (state % 8) as usize);
+        }
+        let wrapped = format!(
+            "{{\n  /**{}*/\n  foo = x: x;\n}}",
+            input.replace("*/", "* /")
+        );
+        let nix = rnix::Root::parse(&wrapped);
+        if let Ok(root) = nix.ok() {
+            collect_entries(root, "lib", "fuzz", &Default::default(), &None, &mut vec![]);
+        }
+    }
+}
+
+#[test]
+fn test_validate_generated_output() {
+    let args = make_args(
+        "test/strings.nix",
+        "strings",
+        "string manipulation functions",
+        Some("test/strings.json"),
+    );
+    assert_eq!(
+        validate::validate_commonmark(&main_with_args(&args)),
+        Vec::<String>::new()
+    );
+
+    let broken = "## `a` {#lib.a}\n\n## `b` {#lib.a}\n\n## c {#x\n\n````nix\nx\n```\n";
+    assert_eq!(
+        validate::validate_commonmark(broken),
+        vec![
+            "line 3: duplicate anchor 'lib.a' (first defined on line 1)",
+            "line 5: h2 heading has an unparsed anchor attribute: c {#x",
+            "line 7: code fence is never closed",
+        ]
+    );
+
+    let nested = "> ```nix\n> x\n> ```\n\n- item\n\n  ```\n  y\n  ```\n\n> ````\n> ```\n";
+    assert_eq!(
+        validate::validate_commonmark(nested),
+        vec!["line 11: code fence is never closed"]
+    );
+
+    let divs = "## `a` {#lib.a}\n\n::: {.example #lib.a}\nx\n:::\n";
+    assert_eq!(
+        validate::validate_commonmark(divs),
+        vec!["line 3: duplicate anchor 'lib.a' (first defined on line 1)"]
+    );
+}
+
+#[test]
+fn test_sanitize_html() {
+    let src = "Hi <b>there</b> <kbd>C-x</kbd>, `<i>code</i>`.\n\n<script>\nalert(1)\n</script>\n\n```\n<p>\n```\n";
+    let strip = sanitize::HtmlPolicy {
+        mode: sanitize::HtmlMode::Strip,
+        allowed_tags: vec!["kbd".to_string()],
+    };
+    let escape = sanitize::HtmlPolicy {
+        mode: sanitize::HtmlMode::Escape,
+        allowed_tags: vec![],
+    };
+
+    assert_eq!(
+        sanitize::sanitize_html(src, &strip),
+        "Hi there <kbd>C-x</kbd>, `<i>code</i>`.\n\n\n\n```\n<p>\n```\n"
+    );
+    assert_eq!(
+        sanitize::sanitize_html("a <b class=\"x>y\">b</b>", &escape),
+        "a &lt;b class=\"x&gt;y\"&gt;b&lt;/b&gt;"
+    );
+    assert!(matches!(
+        sanitize::sanitize_html("no html here", &strip),
+        std::borrow::Cow::Borrowed(_)
+    ));
+
+    // Inline script elements lose their text too.
+    assert_eq!(
+        sanitize::sanitize_html("a <script>alert(1)</script> b <style>p{}</style>", &strip),
+        "a  b "
+    );
+    // Allowed tags keep no attributes that run scripts.
+    let links = sanitize::HtmlPolicy {
+        mode: sanitize::HtmlMode::Strip,
+        allowed_tags: vec!["a".to_string(), "img".to_string()],
+    };
+    assert_eq!(
+        sanitize::sanitize_html(
+            "<a href=\" JavaScript:alert(1)\" title=\"x\" onClick='f()'>x</a> <img onerror=f() src=a.png/>",
+            &links
+        ),
+        "<a title=\"x\">x</a> <img src=a.png/>"
+    );
+    // Schemes are read with their character references decoded, and only
+    // http, https and mailto are kept.
+    assert_eq!(
+        sanitize::sanitize_html(
+            "<a href=\"jav&#x61;script:alert(1)\">a</a> <a href=\"javascript&colon;alert(2)\">b</a> <a href=\"&#106avascript:x\">c</a> <a href=\"java&Tab;script:x\">d</a> <img src=\"data:image/svg+xml,x\"> <a href=\"jav&unknown;script:x\">e</a>",
+            &links
+        ),
+        "<a>a</a> <a>b</a> <a>c</a> <a>d</a> <img> <a>e</a>"
+    );
+    assert_eq!(
+        sanitize::sanitize_html(
+            "<a href=\"https://nixos.org/?a=1&amp;b=2\">a</a> <a href=\"mailto:x@y.z\">b</a> <a href=\"#lib.strings\">c</a> <a href=\"../x.html?t=a:b\">d</a> <img srcset=\"a.png 1x, data:x 2x\">",
+            &links
+        ),
+        "<a href=\"https://nixos.org/?a=1&amp;b=2\">a</a> <a href=\"mailto:x@y.z\">b</a> <a href=\"#lib.strings\">c</a> <a href=\"../x.html?t=a:b\">d</a> <img>"
+    );
+}
+
+#[test]
+fn test_raw_block_routing() {
+    let src = "Intro\n\n```{=html}\n<kbd>\n\n# kept\n```\n\n~~~{=latex}\n\\newpage\n~~~\n\n```{=nix}\n```\n";
+
+    // Raw blocks are fenced, so heading shifting leaves them alone.
+    assert_eq!(shift_headings(src, 2), src);
+    assert_eq!(
+        route_raw_blocks(src, &["html"]),
+        "Intro\n\n<kbd>\n\n# kept\n\n"
+    );
+
+    let policy = sanitize::HtmlPolicy {
+        mode: sanitize::HtmlMode::Escape,
+        allowed_tags: vec![],
+    };
+    assert!(sanitize::sanitize_html(src, &policy).contains("&lt;kbd&gt;"));
+}
+
+#[test]
+fn test_es_bulk_export() {
+    let mut args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    args.es_bulk = Some("docs".to_string());
+    let output = main_with_args(&args);
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+
+    assert_eq!(lines.len() % 2, 0);
+    assert_eq!(lines[0]["index"]["_index"], "docs");
+    assert_eq!(
+        lines[0]["index"]["_id"],
+        format!("function-{}", lines[1]["function_name"].as_str().unwrap())
+    );
+    assert_eq!(lines[1]["type"], "function");
+    assert_eq!(lines[1]["function_category"], "strings");
+
+    let json = fs::read_to_string("test/options.json").unwrap();
+    let parsed = options::parse_options_json(&json).unwrap();
+    let (name, opt) = parsed.iter().next().unwrap();
+    let mut bulk = Vec::new();
+    let doc = search::SearchDocument::from_option(name, opt, "opt-");
+    search::write_bulk_document(&mut bulk, "opts", &doc).unwrap();
+    let bulk = String::from_utf8(bulk).unwrap();
+    let source: serde_json::Value = serde_json::from_str(bulk.lines().nth(1).unwrap()).unwrap();
+
+    assert_eq!(source["type"], "option");
+    assert_eq!(source["option_name"], name.as_str());
+    assert!(
+        source["option_anchor"]
+            .as_str()
+            .unwrap()
+            .starts_with("opt-")
+    );
+}
+
+#[test]
+fn test_nixos_search_export() {
+    use clap::Parser;
+    use search::{Flake, FlakeRepo, NixosSearchOption};
+
+    let args = Args::parse_from([
+        "docgen",
+        "options",
+        "--file",
+        "test/options.json",
+        "--es-bulk",
+        "latest-42-nixos-unstable",
+        "--es-shape",
+        "nixos-search",
+        "--es-flake",
+        "github:owner/repo?ref=main",
+    ]);
+    assert_eq!(args.es_shape, search::BulkShape::NixosSearch);
+    let flake = args.es_flake.unwrap();
+    assert_eq!(flake.flake_name, "repo");
+    assert_eq!(
+        flake.flake_resolved,
+        FlakeRepo::Github {
+            owner: "owner".to_string(),
+            repo: "repo".to_string()
+        }
+    );
+    let git: Flake = "git+https://example.com/nix/modules.git".parse().unwrap();
+    assert_eq!(git.flake_name, "modules");
+    assert!("owner/repo".parse::<Flake>().is_err());
+    assert!("github:owner".parse::<Flake>().is_err());
+
+    let parsed = options::parse_options_file(std::path::Path::new("test/options.json")).unwrap();
+    let doc = NixosSearchOption::from_option(
+        "programs.editor.name",
+        &parsed["programs.editor.name"],
+        Some(&flake),
+    );
+    let mut bulk = Vec::new();
+    search::write_bulk_source(&mut bulk, "options", "option-programs.editor.name", &doc).unwrap();
+    let bulk = String::from_utf8(bulk).unwrap();
+    let source: serde_json::Value = serde_json::from_str(bulk.lines().nth(1).unwrap()).unwrap();
+    assert_eq!(
+        source,
+        serde_json::json!({
+            "flake_name": "repo",
+            "flake_description": null,
+            "flake_resolved": { "type": "github", "owner": "owner", "repo": "repo" },
+            "type": "option",
+            "option_source": "modules/programs/editor.nix",
+            "option_name": "programs.editor.name",
+            "option_description": "<p>The name of the editor to use.</p>\n<p>Supports <code>vim</code>, <code>emacs</code>, or <code>nano</code>.</p>\n",
+            "option_type": "one of \"vim\", \"emacs\", \"nano\"",
+            "option_default": "\"vim\"",
+            "option_example": null,
+            "option_flake": null
+        })
+    );
+}
+
+#[test]
+fn test_devdocs_bundle() {
+    let dir = std::env::temp_dir().join(format!("docgen-devdocs-{}", std::process::id()));
+    let pages = vec![devdocs::Page {
+        path: "strings".to_string(),
+        type_name: "strings".to_string(),
+        markdown: "## `lib.strings.foo` {#function-library-lib.strings.foo}\n\nFoo.\n\n#### Example\n\n::: {.example #ex-foo}\n# `foo` usage example\n\n```nix\nfoo\n```\n:::\n\n## Bar\n".to_string(),
+        entries: vec![(
+            "lib.strings.foo".to_string(),
+            "function-library-lib.strings.foo".to_string(),
+        )],
+        documents: vec![],
+    }];
+
+    devdocs::write_bundle(&dir, "Nix lib", &pages).unwrap();
+    let read_json = |name: &str| -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+    };
+    let index = read_json("index.json");
+    let db = read_json("db.json");
+    let meta = read_json("meta.json");
+    let page = fs::read_to_string(dir.join("strings.html")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        index["entries"][0]["path"],
+        "strings#function-library-lib.strings.foo"
+    );
+    assert_eq!(index["types"][0]["count"], 1);
+    assert_eq!(meta["slug"], "nix_lib");
+    let content = db["strings"].as_str().unwrap();
+    assert!(page.contains(&format!("<main id=\"main\">\n{}</main>", content)));
+    assert!(page.contains("<title>strings - Nix lib</title>"));
+    assert!(page.contains("<a class=\"skip-link\" href=\"#main\">"));
+    assert!(page.contains(
+        "<nav aria-label=\"Entries\">\n<ul>\n<li><a href=\"#function-library-lib.strings.foo\"><code>lib.strings.foo</code></a></li>"
+    ));
+    assert!(page.contains("<h2 id=\"function-library-lib.strings.foo\">"));
+    assert!(content.contains(
+        "<code>lib.strings.foo</code> <a class=\"self-link\" href=\"#function-library-lib.strings.foo\" aria-label=\"Link to this section\">#</a></h2>"
+    ));
+    // Headings do not skip levels.
+    assert!(content.contains("<h3>Example</h3>"));
+    assert!(content.contains("<h2>Bar</h2>"));
+    // Headings within blocks are their captions.
+    assert!(content.contains(
+        "<div class=\"example\" id=\"ex-foo\">\n<p class=\"title\"><code>foo</code> usage example</p>\n<pre><code class=\"language-nix\">foo\n</code></pre>\n</div>"
+    ));
+    assert!(!content.contains("<h1"));
+}
+
+#[test]
+fn test_html_site() {
+    let dir = std::env::temp_dir().join(format!("docgen-html-{}", std::process::id()));
+    let args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    let pages = vec![crate::page(&args, &PathBuf::from("test/strings.nix"))];
+
+    html::write_site(&dir, "Nix lib", &pages, None).unwrap();
+    let page = fs::read_to_string(dir.join("strings.html")).unwrap();
+    let index = fs::read_to_string(dir.join("index.html")).unwrap();
+    assert!(!dir.join("opensearch.xml").exists());
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(page.contains("<title>strings - Nix lib</title>"));
+    assert!(page.contains(
+        "<li><a href=\"strings.html\" aria-current=\"page\">strings</a>\n<ul>\n<li><a href=\"#function-library-lib.strings.concatStrings\"><code>lib.strings.concatStrings</code></a></li>"
+    ));
+    assert!(page.contains("<h2 id=\"function-library-lib.strings.concatStrings\">"));
+    assert!(page.contains("<pre><code class=\"language-nix\">concatStrings ["));
+    assert!(index.contains("<li><a href=\"strings.html\">strings</a></li>"));
+
+    assert_eq!(
+        html::highlight_nix("let x = \"a<b\"; in x # 1\n"),
+        "<span class=\"tok-keyword\">let</span> x = <span class=\"tok-string\">&quot;a&lt;b&quot;</span>; <span class=\"tok-keyword\">in</span> x <span class=\"tok-comment\"># 1</span>\n"
+    );
+}
+
+#[test]
+fn test_html_site_search() {
+    let dir = std::env::temp_dir().join(format!("docgen-opensearch-{}", std::process::id()));
+    let args = make_args("test/strings.nix", "strings", "", None);
+    let pages = vec![crate::page(&args, &PathBuf::from("test/strings.nix"))];
+
+    html::write_site(&dir, "Nix lib", &pages, Some("https://example.com/lib")).unwrap();
+    let page = fs::read_to_string(dir.join("strings.html")).unwrap();
+    let search = fs::read_to_string(dir.join("search.html")).unwrap();
+    let opensearch = fs::read_to_string(dir.join("opensearch.xml")).unwrap();
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("search-index.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(page.contains(
+        "<link rel=\"search\" type=\"application/opensearchdescription+xml\" title=\"Nix lib\" href=\"opensearch.xml\">\n</head>"
+    ));
+    assert!(page.contains("<form class=\"search\" role=\"search\" action=\"search.html\">"));
+    assert!(search.contains("fetch(\"search-index.json\")"));
+    assert!(opensearch.contains(
+        "<Url type=\"text/html\" method=\"get\" template=\"https://example.com/lib/search.html?q={searchTerms}\"/>"
+    ));
+    // The index of the `index` command, linked to the pages of the site.
+    assert_eq!(index["ref"], "id");
+    assert_eq!(index["documents"][0]["title"], "lib.strings.concatStrings");
+    assert_eq!(
+        index["documents"][0]["description"],
+        "Concatenate a list of strings."
+    );
+    assert_eq!(
+        index["documents"][0]["url"],
+        "strings.html#function-library-lib.strings.concatStrings"
+    );
+
+    let search_page = devdocs::Page {
+        path: "search".to_string(),
+        type_name: "search".to_string(),
+        markdown: String::new(),
+        entries: vec![],
+        documents: vec![],
+    };
+    assert!(
+        html::write_site(&dir, "Nix lib", &[search_page], Some("https://example.com")).is_err()
+    );
+
+    // Files of the same name in different directories would share a page.
+    let files = [dir.join("a/default.nix"), dir.join("b/default.nix")];
+    for file in &files {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, "{ }\n").unwrap();
+    }
+    let mut args = make_args("", "", "", None);
+    args.no_metadata = true;
+    let error = crate::pages(&args, &files).err().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        error.ends_with(
+            "default.nix would both be written to the page 'default'; rename one of them"
+        )
+    );
+}
+
+#[test]
+fn test_tags() {
+    let args = make_args("test/strings.nix", "strings", "", None);
+    let (_, entries) = crate::load_entries(&args);
+    let src = fs::read_to_string("test/strings.nix").unwrap();
+    let tags = docgen::tags::file_tags("lib/strings.nix", &src, &entries);
+    assert_eq!(tags.len(), entries.len() * 2);
+
+    let ctags = docgen::tags::render_ctags(&tags);
+    assert!(ctags.starts_with("!_TAG_FILE_FORMAT\t2\t/extended format/\n"));
+    assert!(ctags.contains("\nconcatStrings\tlib/strings.nix\t19;\"\tkind:f\n"));
+    assert!(ctags.contains("\nlib.strings.concatStrings\tlib/strings.nix\t19;\"\tkind:f\n"));
+    let names: Vec<&str> = ctags
+        .lines()
+        .filter(|line| !line.starts_with("!_"))
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    assert!(names.is_sorted());
+
+    let etags = docgen::tags::render_etags(&tags);
+    let (header, section) = etags
+        .strip_prefix("\x0c\nlib/strings.nix,")
+        .unwrap()
+        .split_once('\n')
+        .unwrap();
+    assert_eq!(header.parse::<usize>().unwrap(), section.len());
+    let offset = src.find("  concatStrings =").unwrap();
+    assert!(section.starts_with(&format!(
+        "  concatStrings = builtins.concatStringsSep \"\";\x7flib.strings.concatStrings\x0119,{}\n",
+        offset
+    )));
+}
+
+#[test]
+fn test_lunr_index() {
+    use docgen::lunr::plain_text;
+    assert_eq!(
+        plain_text("Join `xs`\nwith *sep*.\n\n```nix\njoin \",\" xs\n```\n\n- one\n- two"),
+        "Join xs with sep. one two"
+    );
+
+    let args = make_args("", "strings", "", None);
+    let index = crate::search_index(
+        &args,
+        &[PathBuf::from("test/strings.nix")],
+        "lib/{category}.html",
+    )
+    .unwrap();
+    let json = serde_json::to_value(&index).unwrap();
+    assert_eq!(json["ref"], "id");
+    assert_eq!(
+        json["fields"],
+        serde_json::json!(["name", "title", "category", "description"])
+    );
+    let document = &json["documents"][1];
+    assert_eq!(document["id"], "lib.strings.concatMapStrings");
+    assert_eq!(document["name"], "concatMapStrings");
+    assert_eq!(document["category"], "strings");
+    assert_eq!(
+        document["url"],
+        "lib/strings.html#function-library-lib.strings.concatMapStrings"
+    );
+    let description = document["description"].as_str().unwrap();
+    assert!(description.starts_with("Map a function over a list and concatenate"));
+    assert!(!description.contains("```"));
+    assert!(!description.contains("afooabar"));
+    assert!(!description.contains("Example"));
+}
+
+#[test]
+fn test_man_pages() {
+    let args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    let (_, entries) = crate::load_entries(&args);
+
+    let page = man::render_entry_page(&entries[0], "Nix Library Functions");
+    insta::assert_snapshot!(page);
+
+    let index = man::render_index_page("lib", &entries, "Nix Library Functions");
+    assert!(index.starts_with(".TH \"lib\" \"3\""));
+    assert_eq!(index.matches(".TP\n").count(), entries.len());
+
+    let combined = man::render_combined_page("lib", &entries, "Nix Library Functions");
+    assert!(combined.starts_with(".TH \"lib\" \"3\""));
+    assert_eq!(combined.matches(".SH \"LIB.STRINGS\"\n").count(), 1);
+    assert!(combined.contains(".SS \"lib.strings.concatStrings\"\n.PP\n"));
+    assert!(!combined.contains(".SH DESCRIPTION"));
+    assert_eq!(combined.matches(".SS ").count(), entries.len());
+}
+
+#[test]
+fn test_markdown_to_roff_escaping() {
+    assert_eq!(
+        man::markdown_to_roff("Use `a-b`\n.hidden\n\n- one\n- two\n\n```\n.x\n```\n"),
+        ".PP\nUse \\fBa\\-b\\fR\n\\&.hidden\n.IP \\(bu 4\none\n.IP \\(bu 4\ntwo\n.PP\n.RS 4\n.EX\n\\&.x\n.EE\n.RE\n"
+    );
+}
+
+#[test]
+fn test_reference_link_style() {
+    let mut args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    args.link_style = docgen::format::LinkStyle::Reference;
+    let output = main_with_args(&args);
+
+    assert!(!output.contains("](https://"));
+    assert!(output.contains(
+        "[lib/strings.nix:49][function-library-lib.strings.concatStrings-1] in `<nixpkgs>`"
+    ));
+    assert!(output.contains(
+        "[function-library-lib.strings.concatStrings-1]: <https://github.com/NixOS/nixpkgs/blob/"
+    ));
+    assert_eq!(
+        docgen::validate::validate_commonmark(&output),
+        Vec::<String>::new()
+    );
+
+    assert_eq!(
+        docgen::format::reference_links(
+            "See [the *docs*](https://x.org \"Docs\") and <https://y.org>.\n\n",
+            "s"
+        ),
+        "See [the *docs*][s-1] and <https://y.org>.\n\n[s-1]: <https://x.org> \"Docs\"\n\n"
+    );
+}
+
+#[test]
+fn test_wrap_markdown() {
+    let src = "A paragraph that is long enough to need wrapping - with a dash and\n1. a list marker inside.\n\n- list item that also goes on for quite a while longer\n\n: definition text that is long enough to wrap as well here\n\n```\ncode that is long enough to wrap but must never be reflowed at all\n```\n\n| a | table row that is long enough to wrap but must stay |\n";
+
+    assert_eq!(
+        docgen::format::wrap_markdown(src, 30),
+        "A paragraph that is long\nenough to need wrapping - with\na dash and\n1. a list marker inside.\n\n- list item that also goes on\n  for quite a while longer\n\n: definition text that is long\n  enough to wrap as well here\n\n```\ncode that is long enough to wrap but must never be reflowed at all\n```\n\n| a | table row that is long enough to wrap but must stay |\n"
+    );
+    // HTML blocks are kept up to the blank line or closing tag ending them.
+    let html = "<div class=\"note\">\ntext within the element that is long enough to wrap\n</div>\n\n<pre>\nline one\n\npreformatted text that is long enough to wrap\n</pre>\n\n<!--\na comment that is long enough to wrap\n-->\nA paragraph after the comment long enough to wrap.\n";
+    assert_eq!(
+        docgen::format::wrap_markdown(html, 30),
+        html.replace(
+            "A paragraph after the comment long enough to wrap.",
+            "A paragraph after the comment\nlong enough to wrap."
+        )
+    );
+
+    // Inline code keeps its spaces and is never broken across lines.
+    assert_eq!(
+        docgen::format::wrap_markdown("Call `f  x` or ``a ` b`` and\n`g\ny` then `` unclosed", 12),
+        "Call `f  x`\nor ``a ` b``\nand `g y`\nthen ``\nunclosed"
+    );
+
+    // `:::` fences stay on their own lines.
+    let mut args = make_args("test/admonition.nix", "strings", "", None);
+    args.wrap = docgen::format::Wrap::Width(40);
+    let output = main_with_args(&args);
+    assert!(docgen::validate::validate_commonmark(&output).is_empty());
+    insta::assert_snapshot!(output);
+
+    let mut args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    let unwrapped = main_with_args(&args);
+    args.wrap = docgen::format::Wrap::Width(40);
+    let wrapped = main_with_args(&args);
+    assert_eq!(
+        docgen::validate::validate_commonmark(&wrapped),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        wrapped.split_whitespace().collect::<Vec<_>>(),
+        unwrapped.split_whitespace().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_code_langs() {
+    let langs = docgen::format::CodeLangs {
+        default: Some("console".to_string()),
+        map: vec![
+            ("console".to_string(), "shell".to_string()),
+            ("nix".to_string(), "text".to_string()),
+        ],
+    };
+    assert_eq!(langs.fence(), "```console");
+    assert_eq!(
+        langs.remap(
+            "```Console\n$ ls\n```\n\n  ~~~~ nix {.example}\n```console\n~~~~\n\n```\nplain\n```\n"
+        ),
+        "```shell\n$ ls\n```\n\n  ~~~~ text {.example}\n```console\n~~~~\n\n```\nplain\n```\n"
+    );
+
+    let entry = ManualEntry {
+        args: vec![],
+        category: "test".to_string(),
+        location: None,
+        description: vec!["```console\n$ nix build\n```".to_string()],
+        example: Some("f 1".to_string()),
+        usage: None,
+        fn_type: None,
+        name: "f".to_string(),
+        prefix: "lib".to_string(),
+        signature: None,
+        kind: Default::default(),
+        value: None,
+        stability: None,
+        deprecated: None,
+        since: None,
+        see_also: vec![],
+        returns: None,
+        internal: false,
+        inherit_doc: None,
+        custom_anchor: None,
+        tags: vec![],
+        meta: vec![],
+    };
+    let mut output = String::new();
+    entry.write_section("function-library-", &langs, &mut output);
+    // Fences are only retagged as part of a whole document, once.
+    assert!(output.contains("usage example\n\n```console\nf 1\n```"));
+    assert!(output.contains("```console\n$ nix build"));
+    assert_eq!(
+        langs.remap(&output).matches("```shell\n").count(),
+        output.matches("```console\n").count()
+    );
+
+    let mut output = String::new();
+    let untagged = docgen::format::CodeLangs {
+        default: None,
+        map: vec![],
+    };
+    entry.write_section("function-library-", &untagged, &mut output);
+    assert!(output.contains("usage example\n\n```\nf 1\n```"));
+
+    let mut args = make_args("test/strings.nix", "strings", "", Some("test/strings.json"));
+    let tagged = main_with_args(&args);
+    assert!(tagged.contains("```nix\n"));
+    args.code_lang_map = vec![
+        ("nix".to_string(), "text".to_string()),
+        ("text".to_string(), "shell".to_string()),
+    ];
+    assert_eq!(
+        main_with_args(&args),
+        tagged.replace("```nix\n", "```text\n")
+    );
+}
+
+#[test]
+fn test_nixfmt_reindent() {
+    let src = "{\n        enable = true;\n  settings = {\n         port = 8080;   \n      hosts = [\n   \"a\"\n           \"b\"\n  ];\n    };\n      script = ''\n          echo start\n\n            echo indented\n        '';\n  value =\n     let x = 1;\n in x;\n}";
+    assert_eq!(
+        docgen::nixfmt::reindent(src),
+        "{\n  enable = true;\n  settings = {\n    port = 8080;\n    hosts = [\n      \"a\"\n      \"b\"\n    ];\n  };\n  script = ''\n      echo start\n\n        echo indented\n    '';\n  value =\n    let x = 1;\n    in x;\n}"
+    );
+
+    // Single lines and unparseable input are left alone.
+    assert_eq!(docgen::nixfmt::reindent("  { a = 1; }"), "  { a = 1; }");
+    assert_eq!(docgen::nixfmt::reindent("{\n     a = \n"), "{\n     a = \n");
+}
+
+#[test]
+fn test_json_signature() {
+    let src = r#"{
+  /**
+    Join strings.
+
+    # Type
+
+    ```
+    join :: string -> { strict :: bool, ... } -> string
+    ```
+  */
+  join = sep: { strict ? false, parts, ... }@args: sep;
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    let signature = serde_json::to_value(&entries[0].signature).unwrap();
+    assert_eq!(
+        signature,
+        serde_json::json!({
+            "params": [
+                { "kind": "positional", "name": "sep" },
+                {
+                    "kind": "pattern",
+                    "attrs": [
+                        { "name": "strict", "optional": true, "default": "false" },
+                        { "name": "parts", "optional": false, "default": null }
+                    ],
+                    "ellipsis": true,
+                    "bind": "args"
+                }
+            ],
+            "type": {
+                "text": "join :: string -> { strict :: bool, ... } -> string",
+                "name": "join",
+                "args": ["string", "{ strict :: bool, ... }"],
+                "returns": "string"
+            }
+        })
+    );
+}
+
+#[test]
+fn test_constants() {
+    let src = r#"{
+  /** The version. */
+  version = "1.2.3";
+
+  /** Default settings. */
+  defaults = {
+        port = 8080;
+        hosts = [ "a" "b" ];
+      };
+
+  /** Not a constant. */
+  name = "v${version}";
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(nix, "lib", "meta", &Default::default(), &None, &mut vec![]);
+    let json = serde_json::to_value(&entries).unwrap();
+    assert_eq!(json[0]["kind"], "constant");
+    assert_eq!(json[0]["value"], "\"1.2.3\"");
+    assert_eq!(json[1]["kind"], "constant");
+    assert_eq!(json[2]["kind"], "function");
+    assert_eq!(json[2]["value"], serde_json::Value::Null);
+
+    let mut output = String::new();
+    for entry in &entries {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+    assert!(output.contains("**Value**: `\"1.2.3\"`"));
+    assert!(
+        output
+            .contains("**Value**:\n```nix\n{\n  port = 8080;\n  hosts = [ \"a\" \"b\" ];\n}\n```")
+    );
+}
+
+#[test]
+fn test_config_visibility() {
+    let config = std::env::temp_dir().join(format!("docgen-config-{}.toml", std::process::id()));
+    fs::write(
+        &config,
+        r#"
+[visibility]
+hide = ["**Exported", "concat*"]
+
+[visibility.inputs."test/export.nix"]
+include = ["lib.export.another*", "notExported"]
+"#,
+    )
+    .unwrap();
+
+    let names = |args: &Args| -> Vec<String> {
+        crate::load_entries(args)
+            .1
+            .into_iter()
+            .map(|e| e.name)
+            .collect()
+    };
+
+    let mut args = make_args("test/export.nix", "export", "", None);
+    args.config = Some(config.clone());
+    args.export = Some(vec!["exportedFunc".to_string()]);
+    let exported = names(&args);
+
+    args.file = Some(PathBuf::from("test/strings.nix"));
+    args.export = None;
+    let strings = names(&args);
+    fs::remove_file(&config).unwrap();
+
+    // `include` adds let bindings to --export and overrides `hide`.
+    assert_eq!(
+        exported,
+        vec!["exportedFunc", "anotherExported", "notExported"]
+    );
+    assert!(!strings.is_empty());
+    assert!(!strings.iter().any(|name| name.starts_with("concat")));
+
+    assert!(docgen::config::glob_match(
+        "lib.*.escape*",
+        "lib.strings.escapeShellArg"
+    ));
+    assert!(!docgen::config::glob_match("lib.*", "lib.strings.escape"));
+    assert!(docgen::config::glob_match("lib.**", "lib.strings.escape"));
+    assert!(docgen::config::glob_match("a?c", "abc"));
+}
+
+#[test]
+fn test_changelog() {
+    let dir = std::env::temp_dir().join(format!("docgen-changelog-{}", std::process::id()));
+    fs::create_dir_all(dir.join("old")).unwrap();
+    fs::create_dir_all(dir.join("new")).unwrap();
+    fs::write(
+        dir.join("old/strings.nix"),
+        "{\n  /** Kept as is. */\n  id = x: x;\n  /** Old text. */\n  edited = x: x;\n  /** Going away. */\n  gone = x: x;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("new/strings.nix"),
+        "{\n  /** Kept as is. */\n  id = x: x;\n  /** New text. */\n  edited = x: x;\n  /** Brand new & shiny. */\n  fresh = x: x;\n}\n",
+    )
+    .unwrap();
+
+    let args = make_args("", "", "", None);
+    let old = crate::function_sections(&[dir.join("old/strings.nix")], &args);
+    let new = crate::function_sections(&[dir.join("new/strings.nix")], &args);
+    fs::remove_dir_all(&dir).unwrap();
+    let changes = docgen::changes::diff(&old, &new);
+
+    assert_eq!(
+        docgen::changes::render_markdown("Changes", &changes, Some("https://example.com/lib")),
+        "# Changes\n\n## Added\n\n- [`lib.strings.fresh`](https://example.com/lib#function-library-lib.strings.fresh): Brand new & shiny.\n\n## Changed\n\n- [`lib.strings.edited`](https://example.com/lib#function-library-lib.strings.edited): New text.\n\n## Removed\n\n- `lib.strings.gone`: Going away.\n"
+    );
+
+    let feed = docgen::changes::Feed {
+        title: "lib changes",
+        id: "https://example.com/lib",
+        base_url: Some("https://example.com/lib"),
+        revision: "v2",
+        updated: &docgen::changes::rfc3339(1_700_000_000),
+    };
+    let atom = docgen::changes::render_atom(&feed, &changes);
+    assert_eq!(atom.matches("<entry>").count(), 3);
+    assert!(atom.contains("<updated>2023-11-14T22:13:20Z</updated>"));
+    assert!(atom.contains("<id>https://example.com/lib/v2/added/lib.strings.fresh</id>"));
+    assert!(atom.contains("<summary>Brand new &amp; shiny.</summary>"));
+    assert!(!atom.contains("lib.strings.id<"));
+
+    let diff = docgen::changes::render_diff_markdown("Diff", &changes);
+    assert!(diff.contains(
+        "## Changed `lib.strings.edited`\n\n```diff\n ## `lib.strings.edited` {#function-library-lib.strings.edited}\n \n-Old text.\n+New text.\n \n"
+    ));
+    assert!(diff.contains("## Removed `lib.strings.gone`\n\n```diff\n-## `lib.strings.gone`"));
+    let page = docgen::changes::render_diff_html("Diff", &changes);
+    assert!(page.contains(
+        "<div class=\"diff-old\">\n<p class=\"diff-label\">Old</p>\n<h2><code>lib.strings.edited</code></h2>\n<p>Old text.</p>\n"
+    ));
+    assert!(page.contains("<p class=\"diff-label\">Old</p>\n<p><em>(none)</em></p>"));
+    assert!(!page.contains(" id=\"function-library-"));
+    assert_eq!(
+        docgen::changes::rfc3339(951_782_400),
+        "2000-02-29T00:00:00Z"
+    );
+}
+
+#[test]
+fn test_metrics() {
+    let args = make_args("test/doc-comment.nix", "doc", "", None);
+    let record = crate::load_record(&args).unwrap();
+    let entries = record.entries;
+    let counts = docgen::metrics::Counts::new(&entries, &record.public_bindings);
+    assert_eq!(
+        counts,
+        docgen::metrics::Counts {
+            bindings: 5,
+            documented: 3,
+            functions: 0,
+            constants: 3,
+            typed: 0,
+            examples: 1,
+            coverage: 0.6,
+        }
+    );
+
+    let mut export_args = make_args("test/export.nix", "export", "", None);
+    export_args.export = Some(vec!["exportedFunc".to_string(), "missing".to_string()]);
+    assert_eq!(
+        crate::load_record(&export_args).unwrap().public_bindings,
+        vec!["exportedFunc"]
+    );
+    // Entries of bindings that are not counted do not count as documented.
+    let uncounted = docgen::metrics::Counts::new(&entries, &["undocumented".to_string()]);
+    assert_eq!(
+        (uncounted.bindings, uncounted.documented, uncounted.coverage),
+        (1, 0, 0.0)
+    );
+
+    let categories = [("doc".to_string(), counts.clone())].into_iter().collect();
+    let record = docgen::metrics::Record::new("2024-05-01T12:00:00Z".to_string(), None, categories);
+    assert_eq!(record.total, counts);
+
+    let path = std::env::temp_dir().join(format!("docgen-metrics-{}.json", std::process::id()));
+    docgen::metrics::append_record(&path, &record).unwrap();
+    docgen::metrics::append_record(&path, &record).unwrap();
+    let history: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    assert_eq!(history[1]["schema"], 1);
+    assert_eq!(history[1]["categories"]["doc"]["coverage"], 0.6);
+    assert!(history[1].get("entries").is_none());
+
+    let args = make_args("test/strings.nix", "strings", "", None);
+    let (_, entries) = crate::load_entries(&args);
+    let measured = docgen::metrics::EntryMetrics::new(&entries[0]);
+    assert_eq!(measured.title, "lib.strings.concatStrings");
+    // "Concatenate a list of strings.", its example counted as code.
+    assert_eq!(measured.words, 5);
+    assert!(measured.code_ratio.unwrap() > 1.0);
+    assert!((0.0..100.0).contains(&measured.readability.unwrap()));
+
+    let mut entry = entries[0].clone();
+    entry.description = vec!["```nix\nx\n```".to_string()];
+    entry.example = None;
+    let measured = docgen::metrics::EntryMetrics::new(&entry);
+    assert_eq!(
+        (measured.words, measured.code_ratio, measured.readability),
+        (0, None, None)
+    );
+}
+
+#[test]
+fn test_select_language() {
+    let doc = "Split a string.
+
+# Description (de)
+
+Teilt eine Zeichenkette.
+
+# Example
+
+```nix
+split \",\" \"a,b\"
+```
+
+# Example (de)
+
+```nix
+split \",\" \"x,y\"
+```
+
+# Laws (monoid)
+
+Associative.";
+
+    let default = locale::select_language(doc, None);
+    assert_eq!(
+        default,
+        "Split a string.\n\n# Example\n\n```nix\nsplit \",\" \"a,b\"\n```\n\n# Laws (monoid)\n\nAssociative."
+    );
+
+    let german = locale::select_language(doc, Some("DE"));
+    assert_eq!(
+        german,
+        "Teilt eine Zeichenkette.\n\n# Example\n\n```nix\nsplit \",\" \"x,y\"\n```\n\n# Laws (monoid)\n\nAssociative."
+    );
+
+    // Untagged docs are passed through as they are.
+    let plain = "Text\n\n# Laws (monoid)\n\nAssociative.";
+    assert!(matches!(
+        locale::select_language(plain, Some("de")),
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_manifest() {
+    let dir = std::env::temp_dir().join(format!("docgen-manifest-{}", std::process::id()));
+    let mut args = make_args("", "", "", None);
+    let files = [
+        PathBuf::from("test/doc-comment.nix"),
+        PathBuf::from("test/let-ident.nix"),
+    ];
+    let (manifest, _) = crate::write_documents(&args, &files, &dir, &Default::default()).unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("docgen-manifest.json")).unwrap())
+            .unwrap();
+    assert_eq!(written, serde_json::to_value(&manifest).unwrap());
+    assert_eq!(written["schema"], 1);
+
+    let doc = &manifest.documents[0];
+    assert_eq!(doc.path, "doc-comment.md");
+    assert_eq!(doc.category, "doc-comment");
+    assert_eq!(doc.sources[0].path, "test/doc-comment.nix");
+    assert_eq!(
+        doc.sources[0].sha256,
+        docgen::manifest::sha256_hex(&fs::read("test/doc-comment.nix").unwrap())
+    );
+    assert_eq!(
+        doc.sha256,
+        docgen::manifest::sha256_hex(&fs::read(dir.join("doc-comment.md")).unwrap())
+    );
+    assert_eq!(doc.entries[0].name, "lib.doc-comment.docComment");
+    assert_eq!(
+        doc.entries[0].anchor,
+        "function-library-lib.doc-comment.docComment"
+    );
+    assert_eq!(manifest.documents[1].path, "let-ident.md");
+    let (_, entries) = crate::load_entries(&args.for_file(&files[0]));
+    assert_eq!(doc.entries[0].sha256, entries[0].content_hash());
+
+    // Inputs sharing a category would overwrite each other.
+    args.category = "shared".to_string();
+    let err = crate::write_documents(&args, &files, &dir, &Default::default()).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        err.contains("would both be written to shared.md"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_manifest_prune() {
+    let dir = std::env::temp_dir().join(format!("docgen-prune-{}", std::process::id()));
+    let files = [PathBuf::from("test/doc-comment.nix")];
+    let mut args = make_args("", "old", "", None);
+    let prune = crate::SiteArgs {
+        prune: true,
+        ..Default::default()
+    };
+    crate::write_documents(&args, &files, &dir, &prune).unwrap();
+    fs::write(dir.join("notes.md"), "Written by hand.\n").unwrap();
+
+    // A renamed category leaves the old page behind unless pruned.
+    args.category = "new".to_string();
+    let (manifest, removed) = crate::write_documents(&args, &files, &dir, &prune).unwrap();
+    assert_eq!(removed, vec![dir.join("old.md")]);
+    assert!(!dir.join("old.md").exists());
+    assert!(dir.join("new.md").exists());
+    assert!(dir.join("notes.md").exists());
+
+    // Paths outside the output directory are never deleted.
+    let mut escaping = manifest.clone();
+    escaping.documents[0].path = "../escape.md".to_string();
+    let err = docgen::manifest::prune(&dir, &escaping, &docgen::manifest::Manifest::new(vec![]))
+        .unwrap_err();
+    assert!(err.contains("refusing to prune ../escape.md"), "{}", err);
+
+    // Manifests of another schema are rejected.
+    fs::write(dir.join("docgen-manifest.json"), "{\"schema\": 2}").unwrap();
+    let err = crate::write_documents(&args, &files, &dir, &prune).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(err.contains("unsupported manifest schema 2"), "{}", err);
+}
+
+#[test]
+fn test_navigation() {
+    let dir = std::env::temp_dir().join(format!("docgen-nav-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("docgen.toml");
+    fs::write(
+        &config,
+        "[navigation]\norder = [\"strings\", \"missing\", \"doc-comment\"]\n",
+    )
+    .unwrap();
+    let mut args = make_args("", "", "", None);
+    args.config = Some(config);
+    args.no_metadata = true;
+    let files = [
+        PathBuf::from("test/let-ident.nix"),
+        PathBuf::from("test/doc-comment.nix"),
+        PathBuf::from("test/strings.nix"),
+    ];
+    let site = crate::SiteArgs {
+        navigation: true,
+        ..Default::default()
+    };
+    let (manifest, _) = crate::write_documents(&args, &files, &dir, &site).unwrap();
+    let paths: Vec<&str> = manifest.documents.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(paths, ["strings.md", "doc-comment.md", "let-ident.md"]);
+
+    let strings = fs::read_to_string(dir.join("strings.md")).unwrap();
+    let doc_comment = fs::read_to_string(dir.join("doc-comment.md")).unwrap();
+    let let_ident = fs::read_to_string(dir.join("let-ident.md")).unwrap();
+    let navigation: docgen::nav::Navigation =
+        serde_json::from_str(&fs::read_to_string(dir.join("docgen-nav.json")).unwrap()).unwrap();
+    assert!(strings.ends_with("\n---\n\n[doc-comment →](doc-comment.md)\n"));
+    assert!(
+        doc_comment.ends_with("\n---\n\n[← strings](strings.md) · [let-ident →](let-ident.md)\n")
+    );
+    assert!(let_ident.ends_with("\n---\n\n[← doc-comment](doc-comment.md)\n"));
+    assert_eq!(navigation.pages.len(), 3);
+    assert_eq!(navigation.pages[1].category, "doc-comment");
+    assert_eq!(navigation.pages[1].prev.as_deref(), Some("strings.md"));
+    assert_eq!(navigation.pages[1].next.as_deref(), Some("let-ident.md"));
+
+    let pages: Vec<devdocs::Page> = crate::reading_order(&args, &files)
+        .unwrap()
+        .iter()
+        .map(|file| crate::page(&args, file))
+        .collect();
+    let page = html::render_page("Nix lib", &pages, &pages[0], false);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(page.contains(
+        "<nav class=\"pager\" aria-label=\"Pages\">\n<a rel=\"next\" href=\"doc_comment.html\">doc-comment →</a>\n</nav>\n</main>"
+    ));
+}
+
+#[test]
+fn test_manifest_redirects() {
+    let dir = std::env::temp_dir().join(format!("docgen-redirects-{}", std::process::id()));
+    let files = [PathBuf::from("test/doc-comment.nix")];
+    let site = crate::SiteArgs {
+        redirect_stubs: true,
+        ..Default::default()
+    };
+    let mut args = make_args("", "old", "", None);
+    crate::write_documents(&args, &files, &dir, &site).unwrap();
+    let read_redirects = || docgen::manifest::load_redirects(&dir.join("docgen-redirects.json"));
+    assert!(read_redirects().unwrap().is_empty());
+
+    // Entries of a renamed category are found again by attribute and source.
+    args.category = "new".to_string();
+    crate::write_documents(&args, &files, &dir, &site).unwrap();
+    let redirects = read_redirects().unwrap();
+    assert_eq!(
+        redirects["old.md#function-library-lib.old.docComment"],
+        "new.md#function-library-lib.new.docComment"
+    );
+    let stub = fs::read_to_string(dir.join("old.html")).unwrap();
+    assert!(stub.contains(
+        r#""function-library-lib.old.docComment":"new.html#function-library-lib.new.docComment""#
+    ));
+    assert!(stub.contains(r#"<meta http-equiv="refresh" content="0; url=new.html">"#));
+
+    // Earlier redirects follow a later change of anchors.
+    args.anchor_prefix = "fn-".to_string();
+    crate::write_documents(&args, &files, &dir, &site).unwrap();
+    let redirects = read_redirects().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        redirects["old.md#function-library-lib.old.docComment"],
+        "new.md#fn-lib.new.docComment"
+    );
+    assert_eq!(
+        redirects["new.md#function-library-lib.new.docComment"],
+        "new.md#fn-lib.new.docComment"
+    );
+    // Two old links for each of the three entries.
+    assert_eq!(redirects.len(), 6);
+}
+
+#[test]
+fn test_manifest_summary() {
+    let dir = std::env::temp_dir().join(format!("docgen-summary-{}", std::process::id()));
+    let summary_path = dir.join("SUMMARY.md");
+    let site = crate::SiteArgs {
+        summary: Some(summary_path.clone()),
+        ..Default::default()
+    };
+    let args = make_args("", "", "", None);
+    let files = [
+        PathBuf::from("test/let-ident.nix"),
+        PathBuf::from("test/doc-comment.nix"),
+    ];
+    crate::write_documents(&args, &files, &dir.join("lib"), &site).unwrap();
+    assert_eq!(
+        fs::read_to_string(&summary_path).unwrap(),
+        "# Summary\n\n<!-- docgen:start -->\n- [doc-comment](lib/doc-comment.md)\n- [let-ident](lib/let-ident.md)\n<!-- docgen:end -->\n"
+    );
+
+    // A summary without markers gets the chapters appended.
+    fs::write(&summary_path, "# Summary\n\n[Introduction](intro.md)\n").unwrap();
+    crate::write_documents(&args, &files[..1], &dir.join("lib"), &site).unwrap();
+    assert_eq!(
+        fs::read_to_string(&summary_path).unwrap(),
+        "# Summary\n\n[Introduction](intro.md)\n\n<!-- docgen:start -->\n- [let-ident](lib/let-ident.md)\n<!-- docgen:end -->\n"
+    );
+
+    // Only the generated part of a summary written by hand is replaced.
+    fs::write(
+        &summary_path,
+        "# Summary\n\n[Introduction](intro.md)\n\n<!-- docgen:start -->\n- [old](old.md)\n<!-- docgen:end -->\n\n- [Contributing](contributing.md)\n",
+    )
+    .unwrap();
+    crate::write_documents(&args, &files[..1], &dir.join("lib"), &site).unwrap();
+    let summary = fs::read_to_string(&summary_path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        summary,
+        "# Summary\n\n[Introduction](intro.md)\n\n<!-- docgen:start -->\n- [let-ident](lib/let-ident.md)\n<!-- docgen:end -->\n\n- [Contributing](contributing.md)\n"
+    );
+
+    use docgen::summary::{Chapter, relative_link, render_chapters};
+    let chapter = |title: &str, category: &str| Chapter {
+        title: title.to_string(),
+        category: category.to_string(),
+        link: format!("{}.md", category),
+    };
+    assert_eq!(
+        render_chapters(&[
+            chapter("Parsing", "systems.parse"),
+            chapter("Systems", "systems"),
+            chapter("Inspect [draft]", "systems.inspect.x"),
+            chapter("Strings", "strings"),
+        ]),
+        "- [Strings](strings.md)\n- [Systems](systems.md)\n  - [Inspect \\[draft\\]](systems.inspect.x.md)\n  - [Parsing](systems.parse.md)\n"
+    );
+    assert_eq!(
+        relative_link(
+            std::path::Path::new("book/src"),
+            std::path::Path::new("docs/lib/strings.md")
+        ),
+        "../../docs/lib/strings.md"
+    );
+}
+
+#[test]
+fn test_plan() {
+    let dir = std::env::temp_dir().join(format!("docgen-plan-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("docgen.toml");
+    fs::write(&config, "[visibility]\nhide = [\"foo\"]\n").unwrap();
+    let mut args = make_args("", "", "", None);
+    args.config = Some(config);
+    let files = [
+        PathBuf::from("test/doc-comment.nix"),
+        PathBuf::from("test/let-ident.nix"),
+    ];
+    let plan = crate::build_plan(&args, &files, &dir, &Default::default());
+    let table = docgen::plan::render_table(&plan);
+
+    let input = &plan.inputs[0];
+    assert_eq!(input.output, "doc-comment.md");
+    assert_eq!(
+        input.entries,
+        ["lib.doc-comment.docComment", "lib.doc-comment.rfc-style"]
+    );
+    assert_eq!(input.undocumented, ["hidden", "bar"]);
+    assert_eq!(input.hidden, ["foo"]);
+    assert!(!plan.previous_manifest);
+    assert!(plan.errors.is_empty());
+    assert!(table.contains(
+        "\ntest/doc-comment.nix  doc-comment  doc-comment.md  markdown  2        2             1\n"
+    ));
+    let json = serde_json::to_value(&plan).unwrap();
+    assert_eq!(json["settings"]["prefix"], "lib");
+
+    // Nothing is written, and the problems of the run are reported.
+    assert!(!dir.join("doc-comment.md").exists());
+    args.category = "shared".to_string();
+    let plan = crate::build_plan(&args, &files, &dir, &Default::default());
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(plan.errors.len(), 1);
+    assert!(plan.errors[0].contains("would both be written to shared.md"));
+
+    // Inputs that cannot be read are reported too, and the files written
+    // besides the documents are listed.
+    args.category = String::new();
+    args.config = None;
+    let site = crate::SiteArgs {
+        navigation: true,
+        ..Default::default()
+    };
+    let missing = [files[0].clone(), PathBuf::from("test/missing.nix")];
+    let plan = crate::build_plan(&args, &missing, &dir, &site);
+    assert_eq!(plan.inputs.len(), 1);
+    assert_eq!(plan.errors.len(), 1);
+    assert!(plan.errors[0].contains("test/missing.nix"));
+    assert_eq!(
+        plan.navigation,
+        Some(dir.join("docgen-nav.json").display().to_string())
+    );
+    assert!(docgen::plan::render_table(&plan).contains("\nnavigation "));
+}
+
+#[test]
+fn test_combined_inputs() {
+    use docgen::config::glob_match_file;
+    assert!(glob_match_file("**/*.nix", "strings.nix"));
+    assert!(glob_match_file("**/*.nix", "systems/parse.nix"));
+    assert!(!glob_match_file("*.nix", "systems/parse.nix"));
+    assert!(!glob_match_file("**/*.nix", "options.json"));
+
+    let mut args = make_args("", "", "", None);
+    args.dir = Some(PathBuf::from("test"));
+    args.glob = "re*/**/*.nix".to_string();
+    let dir_inputs: Vec<String> = crate::combined_inputs(&args)
+        .unwrap()
+        .into_iter()
+        .map(|input| input.category)
+        .collect();
+    assert_eq!(dir_inputs, ["reexport.versions"]);
+
+    args.dir = None;
+    args.files = vec![
+        PathBuf::from("test/let-ident.nix"),
+        PathBuf::from("test/doc-comment.nix"),
+    ];
+    let inputs = crate::combined_inputs(&args).unwrap();
+    let output = crate::render_inputs(&args, &inputs);
+    let headings: Vec<&str> = output.lines().filter(|l| l.starts_with("# ")).collect();
+    assert_eq!(
+        headings,
+        [
+            "# let-ident {#sec-functions-library-let-ident}",
+            "# `lib.let-ident.add` usage example",
+            "# doc-comment {#sec-functions-library-doc-comment}"
+        ]
+    );
+    assert!(validate::validate_commonmark(&output).is_empty());
+
+    args.json_output = true;
+    let json: serde_json::Value =
+        serde_json::from_str(&crate::render_inputs(&args, &inputs)).unwrap();
+    let categories: Vec<&str> = json["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["category"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        categories,
+        [
+            "let-ident",
+            "let-ident",
+            "doc-comment",
+            "doc-comment",
+            "doc-comment"
+        ]
+    );
+
+    args.category = "shared".to_string();
+    assert!(crate::combined_inputs(&args).is_err());
+}
+
+#[test]
+fn test_ignore_files() {
+    use docgen::ignore::parse;
+    let patterns = parse("# vendored\n\n/node-packages.nix\ngenerated/\n!keep.nix\n\\#odd.nix\n");
+    assert_eq!(patterns.len(), 4);
+    assert!(patterns[0].anchored && !patterns[0].negated);
+    assert!(patterns[1].dir_only && !patterns[1].anchored);
+    assert!(patterns[2].negated);
+    assert_eq!(patterns[3].glob, "#odd.nix");
+    assert!(patterns[1].matches("pkgs/generated", true));
+    assert!(!patterns[1].matches("pkgs/generated", false));
+    // Character classes, as in `.gitignore`.
+    let patterns = parse("*.[oa]\n[Bb]uild/\nv[!0-9]*.nix\n[]x]y\nz[a-c-]\n\\[q]\n[unclosed\n");
+    assert!(patterns[0].matches("lib/x.o", false) && patterns[0].matches("x.a", false));
+    assert!(!patterns[0].matches("x.c", false));
+    assert!(patterns[1].matches("pkgs/Build", true) && patterns[1].matches("build", true));
+    assert!(!patterns[1].matches("rebuild", true));
+    assert!(patterns[2].matches("vendor.nix", false) && !patterns[2].matches("v2.nix", false));
+    assert!(patterns[3].matches("]y", false) && patterns[3].matches("xy", false));
+    assert!(patterns[4].matches("zb", false) && patterns[4].matches("z-", false));
+    assert!(!patterns[4].matches("zd", false));
+    assert!(patterns[5].matches("[q]", false) && !patterns[5].matches("q", false));
+    assert!(patterns[6].matches("[unclosed", false));
+    assert!(!docgen::config::glob_match_file("a[/]b", "a/b"));
+
+    let dir = std::env::temp_dir().join(format!("docgen-ignore-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for file in [
+        "strings.nix",
+        "node-packages.nix",
+        "pkgs/node-packages.nix",
+        "pkgs/generated/a.nix",
+        "vendor/b.nix",
+        "vendor/keep.nix",
+        "extra.nix",
+    ] {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ }\n").unwrap();
+    }
+    fs::write(dir.join(".gitignore"), "/node-packages.nix\ngenerated/\n").unwrap();
+    fs::write(dir.join("vendor/.docgenignore"), "*.nix\n!keep.nix\n").unwrap();
+    let extra = dir.join("extra.ignore");
+    fs::write(&extra, "extra.nix\n").unwrap();
+    // A link back to the root is not followed.
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("pkgs/loop")).unwrap();
+
+    let mut args = make_args("", "", "", None);
+    args.dir = Some(dir.clone());
+    args.glob = "**/*.nix".to_string();
+    let categories = |args: &Args| -> Vec<String> {
+        crate::combined_inputs(args)
+            .unwrap()
+            .into_iter()
+            .map(|input| input.category)
+            .collect()
+    };
+    assert_eq!(
+        categories(&args),
+        ["extra", "pkgs.node-packages", "strings", "vendor.keep"]
+    );
+
+    args.ignore_file = vec![extra];
+    args.no_docgenignore = true;
+    assert_eq!(
+        categories(&args),
+        ["pkgs.node-packages", "strings", "vendor.b", "vendor.keep"]
+    );
+
+    args.ignore_file = vec![];
+    args.no_gitignore = true;
+    let all = categories(&args);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(all.len(), 7);
+}
+
+#[test]
+fn test_file_doc_batch() {
+    let args = make_args("", "", "", None);
+    let files = [
+        "test/strings.nix",
+        "test/doc-comment.nix",
+        "test/export.nix",
+    ];
+    let docs: Vec<(PathBuf, Option<String>)> = files
+        .iter()
+        .map(|file| {
+            let file = PathBuf::from(file);
+            let doc = crate::file_doc(&args, &file, 1).unwrap();
+            (file, doc)
+        })
+        .collect();
+
+    let output = crate::render_file_docs(&args, &docs, true, "markdown").unwrap();
+    let headings: Vec<&str> = output.lines().filter(|l| l.starts_with("# ")).collect();
+    assert_eq!(headings, ["# `test/strings.nix`", "# `test/export.nix`"]);
+    assert!(output.contains("String manipulation functions."));
+    assert!(validate::validate_commonmark(&output).is_empty());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&crate::render_file_docs(&args, &docs, true, "json").unwrap())
+            .unwrap();
+    let array = json.as_array().unwrap();
+    assert_eq!(array.len(), 3);
+    assert_eq!(array[1]["file"], "test/doc-comment.nix");
+    assert!(array[1]["doc"].is_null());
+
+    // A single file keeps the output of a single object.
+    let render = |docs, batch, format| crate::render_file_docs(&args, docs, batch, format);
+    let json: serde_json::Value =
+        serde_json::from_str(&render(&docs[..1], false, "json").unwrap()).unwrap();
+    assert_eq!(json["doc"], "String manipulation functions.");
+    assert!(render(&docs[1..2], false, "markdown").is_none());
+    // A directory with one file is still an array.
+    let json: serde_json::Value =
+        serde_json::from_str(&render(&docs[..1], true, "json").unwrap()).unwrap();
+    assert_eq!(json[0]["doc"], "String manipulation functions.");
+    assert!(
+        render(&docs[..1], true, "markdown")
+            .unwrap()
+            .starts_with("# `test/strings.nix`")
+    );
+    assert_eq!(render(&[], true, "json").unwrap(), "[]");
+}
+
+#[test]
+fn test_entry_fragment() {
+    use crate::{EntryFormat, render_entry};
+    let args = make_args("", "", "", None).for_file(std::path::Path::new("test/doc-comment.nix"));
+
+    let markdown = render_entry(&args, "docComment", EntryFormat::Markdown).unwrap();
+    assert!(markdown.starts_with(
+        "## `lib.doc-comment.docComment` {#function-library-lib.doc-comment.docComment}\n\ndoc comment in markdown format\n"
+    ));
+    assert!(!markdown.contains("rfc-style"));
+
+    let json = render_entry(&args, "lib.doc-comment.foo", EntryFormat::Json).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["name"], "foo");
+    assert_eq!(json["kind"], "constant");
+
+    let err = render_entry(&args, "missing", EntryFormat::Json).unwrap_err();
+    assert!(
+        err.ends_with("(documented: docComment, rfc-style, foo)"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_import_reexports() {
+    let args = make_args("test/reexport.nix", "versions", "", None);
+    let (_, entries) = crate::load_entries(&args);
+    let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["add", "majorMinor", "splitVersion"]);
+    assert_eq!(entries[0].location, None);
+    assert_eq!(
+        entries[1].location.as_deref(),
+        Some("`test/reexport/versions.nix:18`")
+    );
+
+    let mut output = String::new();
+    for entry in &entries {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_flake_module_options() {
+    use docgen::flake::{FlakeOutput, eval_options, options_expr};
+
+    let output: FlakeOutput = "github:owner/repo#nixosModules.my-service".parse().unwrap();
+    assert_eq!(output.flake, "github:owner/repo");
+    assert_eq!(output.attr, ["nixosModules", "my-service"]);
+    assert!(
+        options_expr(&output)
+            .contains("lib.getAttrFromPath [ \"nixosModules\" \"my-service\" ] flake")
+    );
+    // Attribute names are quoted, so they cannot splice in Nix code.
+    assert!(options_expr(&"a#${x}".parse().unwrap()).contains("[ \"\\${x}\" ]"));
+    assert!("github:owner/repo".parse::<FlakeOutput>().is_err());
+    assert!(".#nixosModules.".parse::<FlakeOutput>().is_err());
+
+    let local: FlakeOutput = ".#nixosModules.default".parse().unwrap();
+    assert!(std::path::Path::new(&local.flake).is_absolute());
+
+    // Stand in for nix with a script printing an options JSON file.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let script = std::env::temp_dir().join(format!("docgen-nix-{}", std::process::id()));
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\ncat {}\n",
+                fs::canonicalize("test/options.json").unwrap().display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let json = eval_options(&local, script.to_str().unwrap()).unwrap();
+        fs::remove_file(&script).unwrap();
+        let parsed = options::parse_options_json(&json).unwrap();
+        assert!(parsed.contains_key("services.test.enable"));
+
+        let err = eval_options(&local, "/bin/false").unwrap_err();
+        assert!(err.starts_with("nix eval failed for"), "{}", err);
+    }
+}
+
+#[test]
+fn test_option_lints() {
+    let parsed = options::parse_options_json(
+        r#"{
+  "a.good": { "description": "Ports to open.", "type": "list of signed integer", "example": [80] },
+  "a.empty": { "description": "", "type": "boolean" },
+  "a.unfinished": { "description": "Whether to enable it", "type": "null or boolean" },
+  "a.block": { "description": "Settings, for example:\n\n```nix\n{ }\n```", "type": "attribute set of string", "example": {} },
+  "a.noExample": { "description": "Extra `settings`.", "type": "attribute set of string" },
+  "a.readOnly": { "description": "Computed.", "type": "list of string", "readOnly": true },
+  "a.string": { "description": "Name.", "type": "strings concatenated with \"\"" }
+}"#,
+    )
+    .unwrap();
+    let lints = |name: &str| docgen::lint::lint_option(&parsed[name]);
+
+    assert!(lints("a.good").is_empty());
+    assert_eq!(lints("a.empty"), ["empty description"]);
+    assert_eq!(
+        lints("a.unfinished"),
+        ["description does not end with a full stop"]
+    );
+    assert!(lints("a.block").is_empty());
+    assert_eq!(
+        lints("a.noExample"),
+        ["no example for type 'attribute set of string'"]
+    );
+    assert!(lints("a.readOnly").is_empty());
+    assert_eq!(lints("a.string").len(), 1);
+    assert!(lints("a.string")[0].contains("deprecated `types.string`"));
+}
+
+#[test]
+fn test_landing_pages() {
+    let landing = docgen::landing::parse(
+        "---\ntitle: \"Doc comments\"\n# Shown first.\nfeatured:\n  - foo\n  - lib.doc-comment.docComment\n---\n\nHand-written introduction.\n",
+    )
+    .unwrap();
+    assert_eq!(landing.title.as_deref(), Some("Doc comments"));
+    assert_eq!(landing.featured, ["foo", "lib.doc-comment.docComment"]);
+    assert_eq!(landing.body, "Hand-written introduction.");
+    assert_eq!(
+        docgen::landing::parse("---\nfeatured: [a, 'b']\n---\n")
+            .unwrap()
+            .featured,
+        ["a", "b"]
+    );
+    assert!(docgen::landing::parse("---\nauthor: me\n---\n").is_err());
+    assert!(docgen::landing::parse("---\ntitle: open\n").is_err());
+
+    let dir = std::env::temp_dir().join(format!("docgen-landing-{}", std::process::id()));
+    let landing_dir = dir.join("landing");
+    fs::create_dir_all(&landing_dir).unwrap();
+    fs::write(
+        landing_dir.join("doc-comment.md"),
+        "---\ntitle: Doc comments\nfeatured:\n  - foo\n  - lib.doc-comment.docComment\n---\nHand-written introduction.\n",
+    )
+    .unwrap();
+    let args = make_args("", "", "", None);
+    let files = [
+        PathBuf::from("test/doc-comment.nix"),
+        PathBuf::from("test/let-ident.nix"),
+    ];
+    let site = crate::SiteArgs {
+        landing_dir: Some(landing_dir.clone()),
+        ..Default::default()
+    };
+    let (manifest, _) = crate::write_documents(&args, &files, &dir, &site).unwrap();
+    let page = fs::read_to_string(dir.join("doc-comment.md")).unwrap();
+
+    let names: Vec<&str> = manifest.documents[0]
+        .entries
+        .iter()
+        .map(|e| e.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "lib.doc-comment.foo",
+            "lib.doc-comment.docComment",
+            "lib.doc-comment.rfc-style"
+        ]
+    );
+    assert_eq!(manifest.documents[0].sources.len(), 2);
+    assert_eq!(manifest.documents[1].sources.len(), 1);
+    assert!(page.starts_with(
+        "# Doc comments {#sec-functions-library-doc-comment}\nHand-written introduction.\n\n## Featured {#sec-functions-library-doc-comment-featured}\n\n- [`lib.doc-comment.foo`](#function-library-lib.doc-comment.foo)"
+    ));
+    assert!(validate::validate_commonmark(&page).is_empty());
+
+    fs::write(
+        landing_dir.join("doc-comment.md"),
+        "---\nfeatured: [missing]\n---\n",
+    )
+    .unwrap();
+    let err = crate::write_documents(&args, &files, &dir, &site).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        err.ends_with("doc-comment.md: featured function 'missing' is not documented"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_deprecated() {
+    use docgen::commonmark::{Deprecation, Stability};
+
+    assert_eq!(
+        Deprecation::parse("since 24.05, use `lib.foo` instead"),
+        Deprecation {
+            since: Some("24.05".to_string()),
+            note: Some("use `lib.foo` instead".to_string()),
+        }
+    );
+    assert_eq!(
+        Deprecation::parse("since 23.11"),
+        Deprecation {
+            since: Some("23.11".to_string()),
+            note: None,
+        }
+    );
+    assert_eq!(
+        Deprecation::parse("Use lib.bar."),
+        Deprecation {
+            since: None,
+            note: Some("Use lib.bar.".to_string()),
+        }
+    );
+    assert_eq!(Deprecation::parse(""), Deprecation::default());
+
+    let src = r#"{
+  /**
+    Old join.
+
+    @deprecated since 24.05, use `lib.concatStringsSep` instead
+  */
+  join = sep: list: 1;
+
+  /** @deprecatedAlias is not the tag. */
+  alias = x: x;
+
+  /**
+    Older join.
+
+    @stability deprecated
+  */
+  older = x: x;
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    assert_eq!(entries[0].description, ["Old join."]);
+    assert!(entries[1].deprecated.is_none());
+    // Both tags deprecate the entry and put it in the deprecated tier.
+    assert_eq!(entries[0].stability, Some(Stability::Deprecated));
+    assert_eq!(entries[2].deprecated, Some(Deprecation::default()));
+    assert_eq!(entries[2].stability, Some(Stability::Deprecated));
+    let args = make_args("", "strings", "Strings", None);
+    let rendered = crate::render_markdown(String::new(), &entries, &args);
+    assert!(rendered.contains(
+        ">deprecated</span> | [`lib.strings.join`](#function-library-lib.strings.join), [`lib.strings.older`](#function-library-lib.strings.older) |\n"
+    ));
+    assert!(!rendered.contains("**Stability**"));
+
+    let mut output = String::new();
+    entries[0].write_section("function-library-", &Default::default(), &mut output);
+    let warning =
+        "::: {.warning}\n**Deprecated** since 24.05: use `lib.concatStringsSep` instead\n:::\n\n";
+    assert!(output.contains(&format!("}}\n\n{}Old join.", warning)));
+    let html = html::markdown_to_html(&output, false);
+    assert!(html.contains("<div class=\"warning\">\n<p><strong>Deprecated</strong> since 24.05: use <code>lib.concatStringsSep</code> instead</p>\n</div>"));
+
+    let json = serde_json::to_value(&entries).unwrap();
+    assert_eq!(json[0]["deprecated"]["since"], "24.05");
+    assert!(json[1].get("deprecated").is_none());
+    // The deprecation is part of the content hash.
+    let mut undeprecated = entries[0].clone();
+    undeprecated.deprecated = None;
+    assert_ne!(entries[0].content_hash(), undeprecated.content_hash());
+}
+
+#[test]
+fn test_since() {
+    let src = r#"{
+  /**
+    Split a string.
+
+    @since 23.11
+  */
+  split = sep: s: 1;
+
+  /**
+    Join strings.
+
+    @since
+  */
+  join = sep: list: 1;
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    assert_eq!(entries[0].since.as_deref(), Some("23.11"));
+    assert_eq!(entries[0].description, ["Split a string."]);
+    assert!(entries[1].since.is_none());
+    assert_eq!(entries[1].description, ["Join strings."]);
+
+    let mut output = String::new();
+    entries[0].write_section("function-library-", &Default::default(), &mut output);
+    assert!(output.contains("}\n\n**Since**: 23.11\n\nSplit a string."));
+
+    let json = serde_json::to_value(&entries).unwrap();
+    assert_eq!(json[0]["since"], "23.11");
+    assert!(json[1].get("since").is_none());
+    let entry = docgen::structured::Entry::new(&entries[0], None);
+    assert_eq!(serde_json::to_value(&entry).unwrap()["since"], "23.11");
+    let mut unreleased = entries[0].clone();
+    unreleased.since = None;
+    assert_ne!(entries[0].content_hash(), unreleased.content_hash());
+}
+
+#[test]
+fn test_stability() {
+    let src = r#"{
+  /**
+    Parse a version.
+
+    @stability experimental
+
+    # Example
+
+    ```nix
+    parse "1.0"
+    ```
+  */
+  parse = v: v;
+
+  /**
+    Compare versions.
+    @stability stable
+  */
+  compare = a: b: a;
+
+  /** Old name. @stability is only read on its own line. */
+  cmp = a: b: a;
+
+  /**
+    Unknown tiers are ignored.
+
+    @stability beta
+  */
+  beta = x: x;
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let mut warnings = vec![];
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "versions",
+        &Default::default(),
+        &None,
+        &mut warnings,
+    );
+    // Problems are returned to the caller rather than printed.
+    assert_eq!(
+        warnings,
+        [
+            "lib.versions.beta: unknown stability 'beta' (expected experimental, stable or deprecated)"
+        ]
+    );
+    let json = serde_json::to_value(&entries).unwrap();
+    assert_eq!(json[0]["stability"], "experimental");
+    assert_eq!(json[0]["example"], "parse \"1.0\"");
+    assert_eq!(json[1]["stability"], "stable");
+    assert_eq!(json[1]["description"][0], "Compare versions.");
+    assert_eq!(json[2]["stability"], serde_json::Value::Null);
+    assert_eq!(json[3]["stability"], serde_json::Value::Null);
+    assert_eq!(json[3]["description"][0], "Unknown tiers are ignored.");
+
+    let args = make_args("", "versions", "Versions", None);
+    let output = crate::render_markdown(String::new(), &entries, &args);
+    assert!(output.starts_with(
+        "\n## Stability {#sec-functions-library-versions-stability}\n\n| Stability | Functions |\n| --- | --- |\n| <span class=\"stability stability-stable\""
+    ));
+    assert!(output.contains(
+        ">experimental</span> | [`lib.versions.parse`](#function-library-lib.versions.parse) |\n"
+    ));
+    assert!(output.contains(
+        "{#function-library-lib.versions.parse}\n\n**Stability**: <span class=\"stability stability-experimental\""
+    ));
+    assert!(validate::validate_commonmark(&output).is_empty());
+
+    // The badges are raw HTML, sanitized like that of the doc comments.
+    let mut args = make_args("", "versions", "Versions", None);
+    args.sanitize_html = Some(sanitize::HtmlMode::Strip);
+    let output = crate::render_markdown(String::new(), &entries, &args);
+    assert!(!output.contains("<span"));
+    assert!(output.contains("| stable | [`lib.versions.compare`]"));
+    assert!(output.contains("\n\n**Stability**: experimental\n\n"));
+}
+
+#[test]
+fn test_doctest_assertions() {
+    use docgen::doctest::{
+        Assertion, Outcome, assertion_expr, assertions, evaluate, render_tables,
+    };
+
+    let doc = "Concatenate strings.
+
+```nix-assert
+concatStrings [ \"foo\" \"bar\" ] => \"foobar\"
+concatMapStrings (x: x + \"|\")
+  [ \"a\" ] => \"a|\"
+```
+
+```nix
+no => assertion
+```";
+    assert_eq!(
+        assertions(doc),
+        [
+            Assertion {
+                expr: "concatStrings [ \"foo\" \"bar\" ]".to_string(),
+                expected: "\"foobar\"".to_string(),
+            },
+            Assertion {
+                expr: "concatMapStrings (x: x + \"|\")\n  [ \"a\" ]".to_string(),
+                expected: "\"a|\"".to_string(),
+            },
+        ]
+    );
+    // Arrows within strings and comments are part of the expression, and
+    // lines keep their indentation, which indented strings depend on.
+    let strings = "```nix-assert
+\"a => b\" + ''
+    c => d
+  '' # e => f
+  => \"a => b  c => d\\n\"
+```";
+    assert_eq!(
+        assertions(strings),
+        [Assertion {
+            expr: "\"a => b\" + ''\n    c => d\n  '' # e => f".to_string(),
+            expected: "\"a => b  c => d\\n\"".to_string(),
+        }]
+    );
+    assert_eq!(
+        render_tables(doc),
+        "Concatenate strings.
+
+| Expression | Result |
+| --- | --- |
+| `concatStrings [ \"foo\" \"bar\" ]` | `\"foobar\"` |
+| `concatMapStrings (x: x + \"\\|\") [ \"a\" ]` | `\"a\\|\"` |
+
+```nix
+no => assertion
+```"
+    );
+
+    let nix = rnix::Root::parse(&format!(
+        "{{\n  /**\n    {}\n  */\n  f = x: x;\n}}",
+        doc.replace('\n', "\n    ")
+    ))
+    .ok()
+    .unwrap();
+    let mut output = String::new();
+    for entry in collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    ) {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+    assert!(output.contains("| Expression | Result |\n| --- | --- |\n| `concatStrings"));
+    assert!(validate::validate_commonmark(&output).is_empty());
+
+    let file = fs::canonicalize("test/strings.nix").unwrap();
+    let expr = assertion_expr(&file, &assertions(doc)[0]);
+    assert!(expr.contains(&format!("import (/. + \"{}\")", file.display())));
+    assert!(expr.contains("actual = with scope; (\nconcatStrings [ \"foo\" \"bar\" ]\n  );"));
+    assert!(expr.contains("else if builtins.functionArgs file == { } then\n      file lib\n"));
+
+    // Stand in for nix with a script printing a result. The script is
+    // written by a shell: written by this process, it could be held open
+    // by a process forked by another test while it runs, which fails.
+    #[cfg(unix)]
+    {
+        let base = std::env::temp_dir().join(format!("docgen-doctest-{}", std::process::id()));
+        let script = base.with_extension("sh");
+        let result = base.with_extension("json");
+        let created = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("printf '#!/bin/sh\\ncat \"%s\"\\n' \"$1\" > \"$0\" && chmod +x \"$0\"")
+            .arg(&script)
+            .arg(&result)
+            .status()
+            .unwrap();
+        assert!(created.success());
+        let run = |json: &str| {
+            fs::write(&result, json).unwrap();
+            evaluate(&file, &assertions(doc)[0], script.to_str().unwrap())
+        };
+        assert_eq!(
+            run(r#"{"equal": true, "actual": "\"foobar\""}"#),
+            Ok(Outcome::Passed)
+        );
+        assert_eq!(
+            run(r#"{"equal": false, "actual": "\"foo\""}"#),
+            Ok(Outcome::Failed(Some("\"foo\"".to_string())))
+        );
+        assert_eq!(
+            run(r#"{"equal": false, "actual": null}"#),
+            Ok(Outcome::Failed(None))
+        );
+        fs::remove_file(&script).unwrap();
+        fs::remove_file(&result).unwrap();
+    }
+}
+
+#[test]
+fn test_generated_functions() {
+    let mut output = String::from("");
+    let src = fs::read_to_string("test/generated.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "formats",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "toFormat.json",
+            "toFormat.yaml",
+            "isDefaultPort.http",
+            "isDefaultPort.https",
+            "computed"
+        ]
+    );
+
+    for entry in entries {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_binding_meta() {
+    let mut output = String::from("");
+    let src = fs::read_to_string("test/meta.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    assert_eq!(
+        docgen::returned_binding_names(&nix),
+        ["inner", "repeat", "join", "split", "splitMeta"]
+    );
+    let entries = collect_entries(nix, "lib", "meta", &Default::default(), &None, &mut vec![]);
+
+    let json = serde_json::to_value(&entries[1]).unwrap();
+    assert_eq!(json["meta"]["since"], "24.05");
+
+    for entry in entries {
+        entry.write_section("function-library-", &Default::default(), &mut output);
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_anchor_templates() {
+    let mut args = make_args("test/strings.nix", "strings", "", None);
+    args.anchor_prefix = "fn-{category}-{name}".to_string();
+    let output = main_with_args(&args);
+    assert!(output.contains("## `lib.strings.concatStrings` {#fn-strings-concatStrings}"));
+
+    args.anchor_prefix = "{ident}".to_string();
+    assert!(main_with_args(&args).contains("{#lib.strings.concatStrings}"));
+
+    assert_eq!(
+        options::make_anchor_id("services.foo.enable", "option-{name}"),
+        "option-services-foo-enable"
+    );
+    assert!(docgen::commonmark::parse_anchor_template("fn-{name}").is_ok());
+    assert_eq!(
+        options::parse_anchor_template("opt-{ident}").unwrap_err(),
+        "unknown placeholder '{ident}' (expected one of {name})"
+    );
+}
+
+#[test]
+fn test_rst_output() {
+    let args = make_args("test/arg-formatting.nix", "args", "Args", None);
+    insta::assert_snapshot!(docgen::rst::markdown_to_rst(&main_with_args(&args)));
+    // Admonitions and examples become directives, with their ids as targets.
+    let args = make_args("test/admonition.nix", "strings", "Strings", None);
+    insta::assert_snapshot!(
+        "rst_output_blocks",
+        docgen::rst::markdown_to_rst(&main_with_args(&args))
+    );
+    assert_eq!(
+        docgen::rst::markdown_to_rst(
+            "::: {.example}\nText.\n:::\n\n::: {.other}\n# Heading\n:::\n"
+        ),
+        ".. admonition:: Example\n   :class: example\n\n   Text.\n\n.. rubric:: Heading\n"
+    );
+
+    assert_eq!(
+        docgen::rst::markdown_to_rst(
+            "| a | b |\n| --- | --- |\n| `x` | [y](#z) |\n\n> quote *em*phasis_\n\n1. one\n2. two\n   - nested\n\n[docs](https://example.com) and <https://nixos.org>"
+        ),
+        ".. list-table::\n   :header-rows: 1\n\n   * - a\n     - b\n   * - ``x``\n     - :ref:`y <z>`\n\n   quote *em*\\ phasis\\_\n\n1. one\n\n2. two\n\n   - nested\n\n`docs <https://example.com>`__ and `<https://nixos.org>`__\n"
+    );
+}
+
+#[test]
+fn test_docbook_output() {
+    let args = make_args("test/arg-formatting.nix", "args", "Args", None);
+    insta::assert_snapshot!(docgen::docbook::markdown_to_docbook(&main_with_args(&args)));
+
+    let src = fs::read_to_string("test/commonmark.md").unwrap();
+    let output = docgen::docbook::markdown_to_docbook(&src);
+    assert!(output.contains("\n<section xmlns=\"http://docbook.org/ns/docbook\""));
+    assert!(output.contains(
+        "<example xml:id=\"ex-makeScope\">\n<title>Create an interdependent package set on top of <literal>pkgs</literal></title>"
+    ));
+    assert!(output.contains("<important>\n<variablelist>"));
+    assert!(output.contains("<section xml:id=\"some-id\">\n<title>Simple</title>"));
+    assert_eq!(
+        output.matches("<section").count(),
+        output.matches("</section>").count()
+    );
+
+    assert_eq!(
+        docgen::docbook::markdown_to_docbook("- a & [b](#c)\n- `<d>`"),
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<article xmlns=\"http://docbook.org/ns/docbook\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"5.0\">\n<itemizedlist>\n<listitem>\n<para>a &amp; <link linkend=\"c\">b</link></para>\n</listitem>\n<listitem>\n<para><literal>&lt;d&gt;</literal></para>\n</listitem>\n</itemizedlist>\n</article>\n"
+    );
+}
+
+#[test]
+fn test_types_file() {
+    let args = make_args("test/typed.nix", "typed", "Typed", None);
+    let output = main_with_args(&args);
+    assert!(output.contains(
+        "## `lib.typed.hasPrefix` {#function-library-lib.typed.hasPrefix}\n\n**Type**: `hasPrefix :: string -> string -> bool`\n"
+    ));
+    // The full title works as a key too.
+    assert!(output.contains("**Type**: `[string] -> string`"));
+    // A `# Type` section takes precedence.
+    assert!(!output.contains("not used"));
+
+    let mut args = make_args("test/typed.nix", "typed", "Typed", None);
+    args.json_output = true;
+    let json: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    let entry = &json["entries"][0];
+    assert_eq!(entry["fn_type"], "hasPrefix :: string -> string -> bool");
+    assert_eq!(entry["signature"]["type"]["returns"], "bool");
+
+    let dir = std::env::temp_dir().join(format!("docgen-types-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let json = dir.join("types.json");
+    fs::write(&json, r#"{ "hasPrefix": "string -> bool" }"#).unwrap();
+    let invalid = dir.join("invalid.nix");
+    fs::write(&invalid, "{ a = \"${b}\"; }").unwrap();
+    let types = docgen::types::read_types(&json);
+    let error = docgen::types::read_types(&invalid);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(types.unwrap()["hasPrefix"], "string -> bool");
+    assert!(error.unwrap_err().contains("not a string literal"));
+}
+
+#[test]
+fn test_usage_snippets() {
+    let mut args = make_args("test/typed.nix", "typed", "Typed", None);
+    args.usage_snippets = true;
+    let output = main_with_args(&args);
+    assert!(output.contains(
+        "**Type**: `[string] -> string`\n\n**Usage**:\n```nix\nlib.typed.concatStrings [ ... ]\n```\n"
+    ));
+    assert!(output.contains("lib.typed.hasPrefix \"\" \"\"\n"));
+
+    // Without a type, parameters are named and patterns list their
+    // required attributes.
+    let mut args = make_args("test/arg-formatting.nix", "args", "Args", None);
+    args.usage_snippets = true;
+    args.json_output = true;
+    let json: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(json["entries"][0]["usage"], "lib.args.fn a { }");
+    let root = rnix::Root::parse("{ a, b ? 1 }: c: d: e").tree();
+    let Some(rnix::ast::Expr::Lambda(lambda)) = root.expr() else {
+        panic!("not a lambda");
+    };
+    let signature = docgen::signature::Signature {
+        params: docgen::signature::lambda_params(lambda),
+        fn_type: Some(docgen::signature::parse_type(
+            "AttrSet -> (a -> b) -> bool -> string",
+        )),
+    };
+    assert_eq!(
+        docgen::signature::usage_snippet("f", &signature).unwrap(),
+        "f { a = ...; } (x: ...) false"
+    );
+
+    args.usage_snippets = false;
+    assert!(!main_with_args(&args).contains("\"usage\""));
+}
+
+#[test]
+fn test_group_by_kind() {
+    let mut args = make_args("test/kinds.nix", "kinds", "Kinds", None);
+    args.group_by = Some(docgen::groups::GroupBy::Kind);
+    let output = main_with_args(&args);
+    let headings: Vec<&str> = output.lines().filter(|l| l.starts_with('#')).collect();
+    assert_eq!(
+        headings,
+        [
+            "# Kinds {#sec-functions-library-kinds}",
+            "## Constants {#sec-functions-library-kinds-constants}",
+            "### `lib.kinds.separator` {#function-library-lib.kinds.separator}",
+            "## Functions of one argument {#sec-functions-library-kinds-unary}",
+            "### `lib.kinds.concatStrings` {#function-library-lib.kinds.concatStrings}",
+            "### `lib.kinds.id` {#function-library-lib.kinds.id}",
+            "## Curried functions {#sec-functions-library-kinds-curried}",
+            "### `lib.kinds.join` {#function-library-lib.kinds.join}",
+            "## Functions taking an attribute set {#sec-functions-library-kinds-attrset}",
+            "### `lib.kinds.writeFile` {#function-library-lib.kinds.writeFile}",
+            "### `lib.kinds.makeScope` {#function-library-lib.kinds.makeScope}",
+            "## Other functions {#sec-functions-library-kinds-other}",
+            "### `lib.kinds.generated` {#function-library-lib.kinds.generated}",
+        ]
+    );
+
+    // Groups declared in the file doc are left as written.
+    let mut args = make_args("test/groups.nix", "groups", "Groups", None);
+    args.group_by = Some(docgen::groups::GroupBy::Kind);
+    let output = main_with_args(&args);
+    assert!(output.contains("## String predicates {#sec-functions-library-groups-predicates}\n\nFunctions testing a string.\n\n- `hasPrefix`"));
+    assert!(output.contains("## Curried functions {#sec-functions-library-groups-curried}"));
+
+    // Generated headings are translated by the configuration file.
+    let dir = std::env::temp_dir().join(format!("docgen-headings-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("docgen.toml");
+    fs::write(
+        &config,
+        "[headings.de]\n\"Other functions\" = \"Weitere Funktionen\"\n\"Constants\" = \"Konstanten\"\n",
+    )
+    .unwrap();
+    let mut args = make_args("test/kinds.nix", "kinds", "Kinds", None);
+    args.group_by = Some(docgen::groups::GroupBy::Kind);
+    args.config = Some(config);
+    args.lang = Some("de".to_string());
+    let output = main_with_args(&args);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.contains("## Konstanten {#sec-functions-library-kinds-constants}"));
+    assert!(output.contains("## Weitere Funktionen {#sec-functions-library-kinds-other}"));
+    assert!(output.contains("## Curried functions {#sec-functions-library-kinds-curried}"));
+}
+
+#[test]
+fn test_entry_groups() {
+    let args = make_args("test/groups.nix", "groups", "Groups", None);
+    let output = main_with_args(&args);
+    let position = |needle: &str| output.find(needle).unwrap();
+
+    // The list declaring the group is replaced by its entries, in its order.
+    assert!(!output.contains("- `hasPrefix`"));
+    assert!(output.contains(
+        "## String predicates {#sec-functions-library-groups-predicates}\n\nFunctions testing a string.\n\n### `lib.groups.hasPrefix`"
+    ));
+    assert!(position("### `lib.groups.hasPrefix`") < position("### `lib.groups.hasSuffix`"));
+    assert!(output.contains("## `lib.groups.hasSuffix` usage example"));
+    assert!(
+        position("## Other functions {#sec-functions-library-groups-other}")
+            < position("### `lib.groups.concatStrings`")
+    );
+    // A list naming something else is left as it is.
+    assert!(output.contains("## Notes\n\n- `notABinding`\n"));
+
+    let (text, groups) = docgen::groups::split_groups(
+        "# T\n\n## A\n\n```\n## not a heading\n```\n- `a`\n",
+        |name| (name == "a").then(|| name.to_string()),
+    );
+    assert_eq!(text, "# T\n\n");
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].intro, "```\n## not a heading\n```");
+
+    // An entry listed by two groups is written in the first.
+    let (_, entries) = crate::load_entries(&args);
+    let output = crate::render_markdown(
+        "# Groups\n\n## A\n\n- `hasPrefix`\n\n## B\n\n- `hasPrefix`\n- `hasSuffix`\n".to_string(),
+        &entries,
+        &args,
+    );
+    let position = |needle: &str| output.find(needle).unwrap();
+    assert_eq!(output.matches("### `lib.groups.hasPrefix`").count(), 1);
+    assert!(position("## A") < position("### `lib.groups.hasPrefix`"));
+    assert!(position("## B") < position("### `lib.groups.hasSuffix`"));
+}
+
+#[test]
+fn test_variants_table() {
+    let args = make_args("test/variants.nix", "variants", "Variants", None);
+    let output = main_with_args(&args);
+
+    assert!(!output.contains("@variants"));
+    assert!(output.contains(
+        "Folds over lists.\n\n| Function | Type | Strictness |\n| --- | --- | --- |\n\
+         | [`lib.variants.foldl`](#function-library-lib.variants.foldl) | `(b -> a -> b) -> b -> [a] -> b` | The accumulator is built lazily, which can exhaust the stack on long lists. |\n\
+         | [`lib.variants.foldl'`](#function-library-lib.variants.foldl-prime) | `(b -> a -> b) -> b -> [a] -> b` | Strict in the accumulator, which is forced at each step. |\n\
+         | [`lib.variants.foldr`](#function-library-lib.variants.foldr) |  | Lazy in the rest of the list. |\n"
+    ));
+
+    // Names are validated against the entries; unknown ones are left out.
+    let (_, entries) = crate::load_entries(&args);
+    let mut warnings = vec![];
+    let table = docgen::variants::expand_variants(
+        "```\n@variants foldl\n```\n@variants foldr unknownFold\n",
+        &entries,
+        "function-library-",
+        &mut warnings,
+    );
+    assert!(table.starts_with("```\n@variants foldl\n```\n| Function |"));
+    assert_eq!(table.matches("lib.variants.foldr").count(), 2);
+    assert_eq!(
+        warnings,
+        ["@variants names no documented entry 'unknownFold'"]
+    );
+}
+
+#[test]
+fn test_bench() {
+    use clap::Parser;
+
+    for (file, phases) in [
+        (
+            "test/strings.nix",
+            vec![("parse", None), ("collect", Some(3)), ("render", Some(3))],
+        ),
+        (
+            "test/options.json",
+            vec![("parse", Some(8)), ("render", Some(8))],
+        ),
+    ] {
+        let mut args = Args::parse_from(["docgen", "bench", "--file", file, "-n", "3"]);
+        let Some(crate::Command::Bench {
+            file,
+            iterations,
+            render,
+        }) = args.command.take()
+        else {
+            panic!("expected the bench command");
+        };
+        let report = crate::bench_file(&args, &file, iterations as usize, render).unwrap();
+        let found: Vec<(&str, Option<usize>)> = report
+            .phases
+            .iter()
+            .map(|phase| (phase.name.as_str(), phase.items))
+            .collect();
+        assert_eq!(found, phases);
+        assert!(report.phases.iter().all(|phase| phase.samples.len() == 3));
+
+        let rendered = report.render();
+        assert!(rendered.starts_with(&format!(
+            "{}: {} bytes, 3 iterations\n",
+            file.display(),
+            report.bytes
+        )));
+        assert_eq!(rendered.lines().count(), 3 + phases.len());
+    }
+
+    assert_eq!(
+        crate::bench::format_duration(std::time::Duration::from_micros(1500)),
+        "1.50 ms"
+    );
+}
+
+#[test]
+fn test_asciidoc_output() {
+    let args = make_args("test/arg-formatting.nix", "args", "Args", None);
+    insta::assert_snapshot!(docgen::asciidoc::markdown_to_asciidoc(&main_with_args(
+        &args
+    )));
+
+    assert_eq!(
+        docgen::asciidoc::markdown_to_asciidoc(
+            "| a | b |\n| --- | --- |\n| `x` | [y](#z) |\n\n1. one\n2. two\n\n   more\n   - nested\n\n::: {.note}\n*a*_b\n:::\n\n::: {.example #ex-a}\n# Sum\n\n```nix\n1 + 1\n```\n:::"
+        ),
+        "|===\n|a |b\n\n|`+x+` |<<z,y>>\n|===\n\n. one\n. two\n+\nmore\n* nested\n\n[NOTE]\n====\n__a__&#95;b\n====\n\n[[ex-a]]\n.Sum\n====\n[source,nix]\n----\n1 + 1\n----\n====\n"
+    );
+}
+
+#[test]
+fn test_typst_output() {
+    let args = make_args("test/arg-formatting.nix", "args", "Args", None);
+    insta::assert_snapshot!(docgen::typst::markdown_to_typst(&main_with_args(&args)));
+    // Examples become figures captioned by their heading, with their label.
+    let args = make_args("test/admonition.nix", "strings", "Strings", None);
+    insta::assert_snapshot!(
+        "typst_output_blocks",
+        docgen::typst::markdown_to_typst(&main_with_args(&args))
+    );
+
+    assert_eq!(
+        docgen::typst::markdown_to_typst(
+            "## *a* #b {#x'y}\n\n| a | b |\n| --- | --- |\n| `x` | [y](#z) |\n\n1. one\n2. two\n\n   - nested\n\nSee [here](https://example.org)... and *this*.foo or `` a`b `` `-- 50` $5\n\n::: {.example}\n```nix\n1 + 1\n```\n:::"
+        ),
+        "== #emph[a] \\#b #label(\"x'y\")\n\n#table(\n  columns: 2,\n  table.header([a], [b]),\n  [`x`], [#link(<z>)[y]],\n)\n\n1. one\n\n2. two\n\n   - nested\n\nSee #link(\"https://example.org\")[here]... and #emph[this];.foo or #raw(\"a`b\") `-- 50` \\$5\n\n#figure(kind: \"example\", supplement: [Example])[\n  ```nix\n  1 + 1\n  ```\n]\n"
+    );
+}
+
+#[test]
+fn test_renderers() {
+    use docgen::render::{Document, Registry, Renderer, Section};
+
+    let args = make_args("test/strings.nix", "strings", "Strings", None);
+    let (description, entries) = crate::load_entries(&args);
+    let document = Document::new(
+        "strings",
+        vec![Section {
+            description,
+            entries,
+        }],
+        "function-library-",
+    );
+    assert!(document.markdown.starts_with("# Strings"));
+    assert!(
+        document.markdown.contains(
+            "## `lib.strings.concatStrings` {#function-library-lib.strings.concatStrings}"
+        )
+    );
+
+    let mut registry = Registry::new();
+    let names: Vec<&str> = registry.iter().map(|r| r.name()).collect();
+    assert_eq!(
+        names,
+        ["markdown", "rst", "docbook", "mdx", "asciidoc", "typst"]
+    );
+    assert_eq!(
+        registry.get("markdown").unwrap().render(&document),
+        document.markdown
+    );
+    assert_eq!(
+        registry.get("rst").unwrap().render(&document),
+        docgen::rst::markdown_to_rst(&document.markdown)
+    );
+    assert!(registry.get("names").is_none());
+
+    struct Names;
+    impl Renderer for Names {
+        fn name(&self) -> &'static str {
+            "names"
+        }
+        fn about(&self) -> &'static str {
+            "Documented names"
+        }
+        fn render(&self, document: &Document) -> String {
+            let names: Vec<&str> = document.entries().map(|e| e.name.as_str()).collect();
+            names.join("\n")
+        }
+    }
+    registry.register(Names);
+    assert_eq!(
+        registry.get("names").unwrap().render(&document),
+        "concatStrings\nconcatMapStrings\nhasPrefix"
+    );
+    // A renderer replaces the one of the same name, in its place.
+    registry.register(docgen::render::Converter::new("rst", "Plain", |d| {
+        d.id.clone()
+    }));
+    assert_eq!(registry.get("rst").unwrap().render(&document), "strings");
+    assert_eq!(registry.iter().nth(1).unwrap().about(), "Plain");
+}
+
+#[test]
+fn test_description_file() {
+    let mut args = make_args("test/strings.nix", "strings", "", None);
+    args.description_file = Some(PathBuf::from("test/strings-intro.md"));
+    let output = main_with_args(&args);
+    assert!(output.starts_with(
+        "# String functions {#sec-functions-library-strings}\nFunctions for manipulating strings.\n\n## Examples\n\n```nix\n# not a heading\n"
+    ));
+    assert!(output.contains("\n### Details\n"));
+    assert!(!output.contains("String manipulation functions."));
+
+    // --description takes precedence over the heading of the file.
+    args.description = "Strings".to_string();
+    assert!(main_with_args(&args).starts_with("# Strings {#sec-functions-library-strings}\n"));
+
+    assert_eq!(
+        docgen::split_intro("Intro.\n\n### Usage\n"),
+        (None, "Intro.\n\n### Usage".to_string())
+    );
+}
+
+#[test]
+fn test_mdx_output() {
+    let markdown = "# Sets {#sec-sets}\n\nSee <https://nixos.org> and `{ a = 1; }` or { a = 1; } <!-- hidden -->\n\n## `f` {#f}\n\n<span class=\"stability\" style=\"color:#fff\">stable</span> a < b<br>\n\n::: {.example #ex-f}\n# `f` usage example\n\n```nix\n{ x }: <nixpkgs>\n```\n:::\n\n::: {.warning}\nCareful\n:::\n";
+    assert_eq!(
+        docgen::mdx::markdown_to_mdx(markdown, "sets"),
+        "---\nid: \"sets\"\ntitle: \"Sets\"\n---\n\n# Sets {#sec-sets}\n\nSee [https://nixos.org](https://nixos.org) and `{ a = 1; }` or \\{ a = 1; \\} \n\n## `f` {#f}\n\n<span className=\"stability\">stable</span> a \\< b<br />\n\n<a id=\"ex-f\"></a>\n\n:::note[`f` usage example]\n\n```nix\n{ x }: <nixpkgs>\n```\n:::\n\n:::warning\nCareful\n:::\n"
+    );
+
+    // Multi-line HTML and indented code are read as such.
+    let markdown = "<!--\n{ hidden }\n-->\n\n<div\n  class=\"note\">\n\n{x}\n\n</div>\n\n    { indented = code; }\n";
+    assert_eq!(
+        docgen::mdx::markdown_to_mdx(markdown, "html"),
+        "---\nid: \"html\"\ntitle: \"html\"\n---\n\n\n\n<div\n  className=\"note\">\n\n\\{x\\}\n\n</div>\n\n    { indented = code; }\n"
+    );
+
+    // Examples without a heading, or with no content, are titled by their
+    // class.
+    assert_eq!(
+        docgen::mdx::markdown_to_mdx("::: {.example}\n\nText\n:::\n\n::: {.example}\n:::\n", "x"),
+        "---\nid: \"x\"\ntitle: \"x\"\n---\n\n:::note[Example]\nText\n:::\n\n:::note[Example]\n:::\n"
+    );
+
+    let args = make_args("test/strings.nix", "strings", "Strings", None);
+    let mdx = docgen::mdx::markdown_to_mdx(&main_with_args(&args), "strings");
+    assert!(mdx.starts_with("---\nid: \"strings\"\ntitle: \"Strings\"\n---\n\n# Strings {#sec-functions-library-strings}\n"));
+}
+
+#[test]
+fn test_eval_fallback() {
+    let args = docgen::eval::attr_names_args(std::path::Path::new("test/eval-fallback.nix"));
+    assert!(args.ends_with(&["--apply".to_string(), "builtins.attrNames".to_string()]));
+
+    let mut args = make_args("test/eval-fallback.nix", "arith", "Arithmetic", None);
+    // Static analysis does not see through `lib.genAttrs`.
+    assert!(!main_with_args(&args).contains("inc"));
+
+    // Stand in for nix with a script printing the attribute names.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let script = std::env::temp_dir().join(format!("docgen-eval-{}", std::process::id()));
+        fs::write(&script, "#!/bin/sh\necho '[\"dec\",\"inc\",\"twice\"]'\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        args.eval_fallback = Some(script.to_string_lossy().into_owned());
+        let output = main_with_args(&args);
+        fs::remove_file(&script).unwrap();
+        assert!(output.contains("## `lib.arith.dec`"));
+        assert!(output.contains("Subtract one from a number."));
+        assert!(output.contains("Add one to a number."));
+        assert!(output.contains("## `lib.arith.twice`"));
+
+        let err =
+            docgen::eval::attr_names(std::path::Path::new("test/eval-fallback.nix"), "/bin/false")
+                .unwrap_err();
+        assert!(err.starts_with("nix eval failed for"), "{}", err);
+    }
+}
+
+#[test]
+fn test_frontmatter() {
+    use docgen::frontmatter::{parse_field, with_frontmatter};
+
+    let args = make_args("test/strings.nix", "strings", "String functions", None);
+    let markdown = main_with_args(&args);
+    let fields: Vec<(String, String)> = ["weight=10", "tags=[\"nix\"]", "draft=no", "weight=20"]
+        .iter()
+        .map(|field| parse_field(field).unwrap())
+        .collect();
+    let page = with_frontmatter(&markdown, "strings", &fields);
+    assert!(page.starts_with(
+        "---\ntitle: \"String functions\"\nslug: \"strings\"\ntags: [\"nix\"]\ndraft: \"no\"\nweight: 20\n---\n\n# String functions {#sec-functions-library-strings}\n"
+    ));
+
+    let title = [parse_field("title=Strings: \"core\"").unwrap()];
+    assert!(
+        with_frontmatter("# A\n", "a", &title)
+            .starts_with("---\nslug: \"a\"\ntitle: \"Strings: \\\"core\\\"\"\n---\n")
+    );
+    assert!(parse_field("weight").is_err());
+    assert!(parse_field("a b=c").is_err());
+}
+
+#[test]
+fn test_inherit_wrapped_docs() {
+    let mut args = make_args("test/wrapped.nix", "text", "Text", None);
+    assert!(!main_with_args(&args).contains("joinLines"));
+
+    args.inherit_docs = true;
+    let output = main_with_args(&args);
+    let headings: Vec<&str> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("## "))
+        .collect();
+    assert_eq!(
+        headings,
+        [
+            "`lib.text.join` {#function-library-lib.text.join}",
+            "`lib.text.joinLines` {#function-library-lib.text.joinLines}",
+            "`lib.text.splitAt` {#function-library-lib.text.splitAt}",
+            "`lib.text.splitOn` {#function-library-lib.text.splitOn}",
+            "`lib.text.commas` {#function-library-lib.text.commas}",
+        ]
+    );
+    assert!(
+        output.contains(
+            "Derived from [`lib.text.join`](#function-library-lib.text.join).\n\nJoin a list of strings with a separator.\n"
+        )
+    );
+    // Let bindings are found too, and the wrapper keeps its own parameters.
+    assert!(
+        output.contains("Derived from [`lib.text.splitAt`](#function-library-lib.text.splitAt).")
+    );
+    // The function wrapped is the argument of `flip`, and its type is not
+    // the wrapper's.
+    let split_on = &output[output.find("`lib.text.splitOn`").unwrap()..];
+    assert!(!split_on.contains("Flip the order"));
+    assert!(!split_on.contains("splitAt ::"));
+    let commas = &output[output.find("`lib.text.commas`").unwrap()..];
+    assert!(commas.contains("`list`") && !commas.contains("`sep`"));
+    // Bindings wrapping nothing documented are still left out.
+    assert!(!output.contains("unrelated"));
+}
+
+#[test]
+fn test_hugo_content() {
+    let dir = std::env::temp_dir().join(format!("docgen-hugo-{}", std::process::id()));
+    let args = make_args("test/strings.nix", "", "String functions", None);
+    let files = [
+        PathBuf::from("test/strings.nix"),
+        PathBuf::from("test/wrapped.nix"),
+        PathBuf::from("test/see.nix"),
+    ];
+    let mut sections = crate::hugo_sections(&args, &files).unwrap();
+    sections[1].pages[0].name = "index".to_string();
+    let fields = vec![("draft".to_string(), "false".to_string())];
+    let written = docgen::hugo::write_content(&dir, &sections, &fields).unwrap();
+    let index = fs::read_to_string(dir.join("strings/_index.md")).unwrap();
+    let page = fs::read_to_string(dir.join("strings/concatStrings.md")).unwrap();
+    let second = fs::read_to_string(dir.join("wrapped/_index.md")).unwrap();
+    let renamed = fs::read_to_string(dir.join("wrapped/function-index.md")).unwrap();
+    let join = fs::read_to_string(dir.join("see/join.md")).unwrap();
+    let split = fs::read_to_string(dir.join("see/split.md")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        written.len(),
+        sections.iter().map(|s| s.pages.len() + 1).sum::<usize>()
+    );
+    assert!(index.starts_with(
+        "---\nslug: \"strings\"\ntitle: \"String functions\"\nweight: 1\ndraft: false\n---\n\nString manipulation functions.\n"
+    ));
+    assert!(second.contains("weight: 2\n"));
+    assert!(page.starts_with(
+        "---\nslug: \"concatStrings\"\ntitle: \"lib.strings.concatStrings\"\nweight: 1\ndraft: false\n---\n\n"
+    ));
+    // The function heading is the page title.
+    assert!(!page.contains("## `lib.strings.concatStrings`"));
+    // A function named like a section index does not replace it.
+    assert!(!second.contains("slug: \"index\""));
+    assert!(renamed.contains("slug: \"index\"\n"));
+    // References go to the pages of the functions.
+    assert!(join.contains(
+        "- [`lib.strings.concatStrings`]({{< relref \"../strings/concatStrings.md\" >}})\n"
+    ));
+    assert!(split.contains("[`join`]({{< relref \"join.md\" >}})"));
+
+    assert!(crate::hugo_sections(&args, &[files[0].clone(), files[0].clone()]).is_err());
+}
+
+#[test]
+fn test_antora_component() {
+    let dir = std::env::temp_dir().join(format!("docgen-antora-{}", std::process::id()));
+    let args = make_args("test/strings.nix", "", "String functions", None);
+    let pages = vec![
+        crate::page(&args, &PathBuf::from("test/strings.nix")),
+        crate::page(&args, &PathBuf::from("test/wrapped.nix")),
+    ];
+    let written =
+        docgen::antora::write_component(&dir, "nix", "Nix library", Some("24.05"), &pages).unwrap();
+    let descriptor = fs::read_to_string(dir.join("antora.yml")).unwrap();
+    let nav = fs::read_to_string(dir.join("modules/ROOT/nav.adoc")).unwrap();
+    let index = fs::read_to_string(dir.join("modules/ROOT/pages/index.adoc")).unwrap();
+    let strings = fs::read_to_string(dir.join("modules/ROOT/pages/strings.adoc")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(written.len(), 5);
+    assert_eq!(
+        descriptor,
+        "name: \"nix\"\ntitle: \"Nix library\"\nversion: \"24.05\"\nnav:\n- modules/ROOT/nav.adoc\n"
+    );
+    assert!(nav.starts_with(
+        "* xref:strings.adoc[String functions]\n** xref:strings.adoc#function-library-lib.strings.concatStrings[lib.strings.concatStrings]\n"
+    ));
+    assert!(index.starts_with("= Nix library\n\n* xref:strings.adoc[String functions]\n"));
+    assert!(strings.contains(
+        "[[function-library-lib.strings.concatStrings]]\n== `+lib.strings.concatStrings+`\n"
+    ));
+    assert!(docgen::antora::descriptor("nix", "Nix", None).contains("version: ~\n"));
+}
+
+#[test]
+fn test_provenance() {
+    let dir = std::env::temp_dir().join(format!("docgen-provenance-{}", std::process::id()));
+    let mut args = make_args("", "", "", None);
+    args.command_line = ["docgen", "manifest", "--prefix", "my lib"]
+        .map(String::from)
+        .to_vec();
+    let files = [PathBuf::from("test/strings.nix")];
+    crate::write_documents(&args, &files, &dir, &Default::default()).unwrap();
+    let strings = fs::read_to_string(dir.join("strings.md")).unwrap();
+    let hash = docgen::manifest::sha256_hex(&fs::read("test/strings.nix").unwrap());
+    assert!(strings.ends_with(&format!(
+        "\n\n<!--\ngenerator: docgen {}\ncommand: docgen manifest --prefix 'my lib'\nsource: test/strings.nix sha256:{}\n-->\n",
+        env!("CARGO_PKG_VERSION"),
+        hash
+    )));
+
+    args.no_metadata = true;
+    crate::write_documents(&args, &files, &dir, &Default::default()).unwrap();
+    let strings = fs::read_to_string(dir.join("strings.md")).unwrap();
+    assert!(!strings.contains("<!--"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    let text = "generator: docgen\ncommand: docgen --file a.nix\n";
+    let renderers = crate::renderers();
+    let comment = |name: &str| renderers.get(name).unwrap().comment(text);
+    assert_eq!(
+        comment("markdown"),
+        "<!--\ngenerator: docgen\ncommand: docgen --file a.nix\n-->\n"
+    );
+    assert_eq!(
+        comment("rst"),
+        "..\n   generator: docgen\n   command: docgen --file a.nix\n"
+    );
+    assert!(comment("docbook").starts_with("<?docgen\n"));
+    assert!(comment("mdx").starts_with("{/*\n") && comment("mdx").ends_with("*/}\n"));
+    assert!(comment("asciidoc").starts_with("////\n"));
+    assert!(comment("typst").starts_with("/*\n"));
+    assert_eq!(
+        docgen::provenance::html_comment("a --> b\n"),
+        "<!--\na -- > b\n-->\n"
+    );
+}
+
+#[test]
+fn test_see_also() {
+    let dir = std::env::temp_dir().join(format!("docgen-see-{}", std::process::id()));
+    let mut args = make_args("", "", "", None);
+    args.no_metadata = true;
+    let files = [
+        PathBuf::from("test/see.nix"),
+        PathBuf::from("test/strings.nix"),
+    ];
+    crate::write_documents(&args, &files, &dir, &Default::default()).unwrap();
+    let see = fs::read_to_string(dir.join("see.md")).unwrap();
+    assert!(see.contains(
+        "**See also**:\n\n\
+         - [`lib.strings.concatStrings`](strings.md#function-library-lib.strings.concatStrings)\n\
+         - [`lib.strings.hasPrefix`](strings.md#function-library-lib.strings.hasPrefix)\n\
+         - [`split`](#function-library-lib.see.split)\n\
+         - `lib.missing`\n\n"
+    ));
+
+    // Functions documented elsewhere are linked through a search index.
+    let index = crate::search_index(
+        &args,
+        &[PathBuf::from("test/strings.nix")],
+        "https://example.org/{category}.html",
+    )
+    .unwrap();
+    let index_path = dir.join("index.json");
+    fs::write(&index_path, serde_json::to_string(&index).unwrap()).unwrap();
+    let mut args = make_args("test/see.nix", "see", "", None);
+    args.see_index = Some(index_path);
+    let output = main_with_args(&args);
+    assert!(output.contains(
+        "- [`lib.strings.concatStrings`](https://example.org/strings.html#function-library-lib.strings.concatStrings)\n"
+    ));
+    assert!(output.contains("- [`split`](#function-library-lib.see.split)\n"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[0].description, ["Join strings with a separator."]);
+    let json = serde_json::to_value(&entries[0]).unwrap();
+    assert_eq!(json["see_also"][2]["name"], "split");
+    assert!(json["see_also"][2]["url"].is_null());
+    assert!(
+        serde_json::to_value(&entries[1])
+            .unwrap()
+            .get("see_also")
+            .is_none()
+    );
+}
+
+#[test]
+fn test_param_tags() {
+    let src = r#"{
+  /**
+    Join strings with a separator.
+
+    @param sep - The separator,
+      put between the strings
+    @param list The strings to join
+    @param missing Not an argument
+    @return The joined string
+  */
+  join = sep: list: 1;
+
+  /**
+    Replace a pattern.
+
+    @param from: What to replace
+    @param to: Overridden
+  */
+  replace =
+    {
+      from,
+      /** Inline docs take precedence. */
+      to,
+    }:
+    s: s;
+
+  /**
+    Concatenate strings.
+
+    @param list The strings
+  */
+  concat = join "";
+}"#;
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    assert_eq!(entries[0].description, ["Join strings with a separator."]);
+    assert_eq!(entries[0].returns.as_deref(), Some("The joined string"));
+    let docs = |entry: &ManualEntry| -> Vec<(String, Option<String>)> {
+        entry
+            .args
+            .iter()
+            .flat_map(|arg| match arg {
+                docgen::Argument::Flat(arg) => std::slice::from_ref(arg),
+                docgen::Argument::Pattern(args) => args.as_slice(),
+            })
+            .map(|arg| (arg.name.clone(), arg.doc.clone()))
+            .collect()
+    };
+    let doc = |name: &str, doc: Option<&str>| (name.to_string(), doc.map(String::from));
+    assert_eq!(
+        docs(&entries[0]),
+        [
+            doc("sep", Some("The separator,\nput between the strings")),
+            doc("list", Some("The strings to join")),
+        ]
+    );
+    assert_eq!(
+        docs(&entries[1]),
+        [
+            doc("from", Some("What to replace")),
+            doc("to", Some("Inline docs take precedence.")),
+            doc("s", None)
+        ]
+    );
+    // Without a lambda, the tags give the arguments.
+    assert_eq!(docs(&entries[2]), [doc("list", Some("The strings"))]);
+
+    let mut output = String::new();
+    entries[0].write_section("function-library-", &Default::default(), &mut output);
+    assert!(
+        output.contains("`list`\n\n: The strings to join\n\n\n**Returns**: The joined string\n\n")
+    );
+    let json = serde_json::to_value(&entries[0]).unwrap();
+    assert_eq!(json["returns"], "The joined string");
+}
+
+#[test]
+fn test_inline_references() {
+    let dir = std::env::temp_dir().join(format!("docgen-refs-{}", std::process::id()));
+    let mut args = make_args("", "", "", None);
+    args.no_metadata = true;
+    let files = [
+        PathBuf::from("test/see.nix"),
+        PathBuf::from("test/strings.nix"),
+    ];
+    crate::write_documents(&args, &files, &dir, &Default::default()).unwrap();
+    let see = fs::read_to_string(dir.join("see.md")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    // The role links names within the category, inline code only titles;
+    // the entry itself and code blocks are not linked.
+    assert!(see.contains(
+        "Split a string at a separator, the inverse of [`join`](#function-library-lib.see.join), unlike\n\
+         [`lib.strings.concatStrings`](strings.md#function-library-lib.strings.concatStrings), `lib.nope` or `split`.\n\n\
+         ```nix\nlib.strings.hasPrefix\n```\n"
+    ));
+
+    let src = "{\n  /** Like {ref}`lib.strings.foo`. */\n  foo = x: x;\n}";
+    let nix = rnix::Root::parse(src).ok().unwrap();
+    let mut entries = collect_entries(
+        nix,
+        "lib",
+        "strings",
+        &Default::default(),
+        &None,
+        &mut vec![],
+    );
+    let mut targets = docgen::see::Targets::default();
+    targets.add_entries(&entries, "function-library-", "");
+    assert!(targets.link_mentions(&mut entries, "").is_empty());
+    // JSON keeps the docs, and their hashes, as written.
+    let mut args = make_args("", "", "", None);
+    args.json_output = true;
+    args.json_version = 2;
+    let mut json = |files: &[&str]| -> serde_json::Value {
+        args.files = files.iter().map(PathBuf::from).collect();
+        let inputs = crate::combined_inputs(&args).unwrap();
+        serde_json::from_str(&crate::render_inputs(&args, &inputs)).unwrap()
+    };
+    let alone = json(&["test/see.nix"]);
+    let together = json(&["test/see.nix", "test/strings.nix"]);
+    assert_eq!(alone["entries"][1], together["entries"][1]);
+    assert!(
+        together["entries"][1]["description"]
+            .as_str()
+            .unwrap()
+            .contains("{ref}`join`")
+    );
+    // Written with the role, the entry links to itself.
+    assert_eq!(
+        entries[0].description,
+        ["Like [`lib.strings.foo`](#function-library-lib.strings.foo)."]
+    );
+}
+
+#[test]
+fn test_internal() {
+    let names = |args: &Args| -> Vec<String> {
+        crate::load_entries(args)
+            .1
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect()
+    };
+    let mut args = make_args("test/internal.nix", "strings", "", None);
+    assert_eq!(names(&args), ["join", "escapeHelper"]);
+    let output = main_with_args(&args);
+    assert!(!output.contains("splitHelper"));
+
+    // Internal entries are only kept in JSON output.
+    args.keep_internal = true;
+    assert_eq!(names(&args), ["join", "escapeHelper"]);
+    args.json_output = true;
+    let output = main_with_args(&args);
+    let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1]["name"], "splitHelper");
+    assert_eq!(entries[1]["internal"], true);
+    assert_eq!(
+        entries[1]["description"],
+        serde_json::json!(["Split a string, used by `join`."])
+    );
+    assert!(entries[0].get("internal").is_none());
+    // Also when rendering several files.
+    args.file = None;
+    args.category = String::new();
+    args.files = vec![
+        PathBuf::from("test/internal.nix"),
+        PathBuf::from("test/strings.nix"),
+    ];
+    let inputs = crate::combined_inputs(&args).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&crate::render_inputs(&args, &inputs)).unwrap();
+    assert_eq!(json["entries"][1]["name"], "splitHelper");
+
+    let dir = std::env::temp_dir().join(format!("docgen-internal-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("docgen.toml");
+    fs::write(
+        &config,
+        "[visibility.inputs.\"test/internal.nix\"]\ninternal_marker = \"@private\"\n",
+    )
+    .unwrap();
+    let mut args = make_args("test/internal.nix", "strings", "", None);
+    args.config = Some(config);
+    // The configured marker is recognised in place of `@internal`.
+    assert_eq!(names(&args), ["join", "splitHelper"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_inherit_doc() {
+    let args = make_args("test/inherit.nix", "inherit", "", None);
+    let (_, entries) = crate::load_entries(&args);
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    // The internal function is left out, but still inherited from.
+    assert_eq!(names, ["join", "concat", "concatAll", "missing"]);
+    assert_eq!(entries[0].description, ["Join strings with a separator."]);
+    assert_eq!(
+        entries[0].fn_type.as_deref(),
+        Some("joinWith :: String -> [String] -> String")
+    );
+    assert!(
+        entries[0]
+            .example
+            .as_deref()
+            .unwrap()
+            .contains("=> \"a, b\"")
+    );
+    assert_eq!(entries[0].returns.as_deref(), Some("The joined string."));
+    let docs: Vec<Option<&str>> = entries[0]
+        .args
+        .iter()
+        .map(|arg| match arg {
+            docgen::Argument::Flat(arg) => arg.doc.as_deref(),
+            docgen::Argument::Pattern(_) => unreachable!(),
+        })
+        .collect();
+    assert_eq!(docs, [Some("The separator."), None]);
+    // Inheriting from an entry that inherits, after the entry's own text.
+    assert_eq!(
+        entries[1].description,
+        ["Kept for compatibility.", "Join strings with a separator."]
+    );
+    assert!(entries[1].inherit_doc.is_none());
+    // Functions of other files are resolved with the run.
+    assert_eq!(
+        entries[2].inherit_doc.as_deref(),
+        Some("lib.strings.concatStrings")
+    );
+
+    let mut args = make_args("", "", "", None);
+    args.no_metadata = true;
+    args.files = vec![
+        PathBuf::from("test/inherit.nix"),
+        PathBuf::from("test/strings.nix"),
+    ];
+    let inputs = crate::combined_inputs(&args).unwrap();
+    let (loaded, _) = crate::load_inputs(&args, &inputs);
+    let entries = &loaded[0].1;
+    assert_eq!(entries[2].description, ["Concatenate a list of strings."]);
+    assert!(entries[2].example.is_some());
+    assert!(entries[3].inherit_doc.is_some());
+    assert_eq!(entries[3].description, [""]);
+
+    // Streamed entries are resolved the same way.
+    args.json_output = true;
+    let mut streamed = Vec::new();
+    crate::stream_entries(&args, &inputs, &mut streamed).unwrap();
+    let line: serde_json::Value =
+        serde_json::from_str(String::from_utf8(streamed).unwrap().lines().nth(2).unwrap()).unwrap();
+    assert_eq!(
+        line["description"],
+        serde_json::json!(["Concatenate a list of strings."])
+    );
+}
+
+#[test]
+fn test_custom_anchor() {
+    let mut args = make_args("test/anchor.nix", "strings", "", None);
+    let output = main_with_args(&args);
+    assert!(output.contains("## `lib.strings.join` {#strings-join}\n"));
+    assert!(output.contains("::: {.example #example-strings-join}\n"));
+    assert!(output.contains("## `lib.strings.split` {#strings-split}\n"));
+    // An invalid anchor is ignored.
+    assert!(output.contains("## `lib.strings.repeat` {#function-library-lib.strings.repeat}\n"));
+    assert!(!output.contains("@anchor"));
+
+    // The pinned anchor does not depend on the prefix or the name.
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[1].description, ["Split a string at a separator."]);
+    let mut renamed = entries[0].clone();
+    renamed.name = "joinWith".to_string();
+    assert_eq!(renamed.anchor("{category}-{name}"), "strings-join");
+
+    args.json_output = true;
+    let json: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(json["entries"][0]["anchor"], "strings-join");
+    assert!(json["entries"][2].get("anchor").is_none());
+}
+
+#[test]
+fn test_group_by_tag() {
+    let mut args = make_args("test/tags.nix", "strings", "", None);
+    let (_, entries) = crate::load_entries(&args);
+    assert_eq!(entries[0].tags, ["string-manipulation", "lists"]);
+    assert_eq!(entries[2].tags, ["lists", "string-manipulation"]);
+    assert!(entries[0].description.iter().all(|p| !p.contains("@tag")));
+    let mut retagged = entries.clone();
+    retagged[1].tags = vec!["Lists".to_string()];
+    let headings: Vec<String> = docgen::groups::tag_groups(
+        &retagged,
+        "strings",
+        "function-library-",
+        &Default::default(),
+    )
+    .into_iter()
+    .map(|group| group.heading)
+    .collect();
+    assert_eq!(
+        headings,
+        [
+            "String manipulation {#sec-functions-library-strings-tag-string-manipulation}",
+            "Lists {#sec-functions-library-strings-tag-lists}",
+            "Lists {#sec-functions-library-strings-tag-lists-2}",
+        ]
+    );
+
+    args.group_by = Some(docgen::groups::GroupBy::Tag);
+    let output = main_with_args(&args);
+    let headings: Vec<&str> = output.lines().filter(|l| l.starts_with('#')).collect();
+    assert_eq!(
+        headings,
+        [
+            "#  {#sec-functions-library-strings}",
+            "## String manipulation {#sec-functions-library-strings-tag-string-manipulation}",
+            "### `lib.strings.join` {#function-library-lib.strings.join}",
+            "## Lists {#sec-functions-library-strings-tag-lists}",
+            "### `lib.strings.split` {#function-library-lib.strings.split}",
+            "## Predicates {#sec-functions-library-strings-tag-predicates}",
+            "### `lib.strings.hasPrefix` {#function-library-lib.strings.hasPrefix}",
+            "## Other functions {#sec-functions-library-strings-other}",
+            "### `lib.strings.empty` {#function-library-lib.strings.empty}",
+        ]
+    );
+    // Each group lists all entries with its tag, also those written under
+    // another tag.
+    assert!(output.contains(
+        "## Lists {#sec-functions-library-strings-tag-lists}\n\n\
+         - [`lib.strings.join`](#function-library-lib.strings.join)\n\
+         - [`lib.strings.split`](#function-library-lib.strings.split)\n\n"
+    ));
+
+    args.json_output = true;
+    let json: serde_json::Value = serde_json::from_str(&main_with_args(&args)).unwrap();
+    assert_eq!(
+        json["entries"][1]["tags"],
+        serde_json::json!(["predicates"])
+    );
+    assert!(json["entries"][3].get("tags").is_none());
+}
+
+#[test]
+fn test_markdown_dialect() {
+    let mut args = make_args("test/admonition.nix", "strings", "", None);
+    let nixpkgs = main_with_args(&args);
+    assert!(
+        nixpkgs.contains("::: {.note}\nThe separator is not added after the last string.\n:::\n")
+    );
+
+    args.markdown_dialect = docgen::admonition::Dialect::Github;
+    let output = main_with_args(&args);
+    assert!(!output.contains(":::"));
+    assert!(output.contains(
+        "Join strings with a separator.\n\n\
+         > [!NOTE]\n\
+         > The separator is not added after the last string.\n\n\
+         <a id=\"join-escaping\"></a>\n\
+         > [!WARNING]\n\
+         > The strings are not escaped:\n\
+         >\n\
+         > ```nix\n\
+         > join \",\" [ \"a,b\" ]\n\
+         > ```\n\n`sep`"
+    ));
+    // Other blocks keep their id as an anchor, and their headings are not
+    // headings of the document; heading ids become anchors too.
+    assert!(output.contains(
+        "<a id=\"function-library-example-lib.strings.join\"></a>\n**`lib.strings.join` usage example**\n"
+    ));
+    assert!(output.starts_with("<a id=\"sec-functions-library-strings\"></a>\n#\n"));
+    assert!(
+        output
+            .contains("<a id=\"function-library-lib.strings.join\"></a>\n## `lib.strings.join`\n")
+    );
+    assert!(!output.contains("{#"));
+
+    args.markdown_dialect = docgen::admonition::Dialect::Html;
+    let output = main_with_args(&args);
+    assert!(!output.contains(":::"));
+    assert!(output.contains(
+        "<div class=\"note\">\n\nThe separator is not added after the last string.\n\n</div>\n\n"
+    ));
+    assert!(output.contains("<div class=\"warning\" id=\"join-escaping\">\n"));
+
+    // Fences in code blocks are left alone, and text right after an alert
+    // does not continue it.
+    let markdown = "```\n::: {.note}\n```\n\n::: {.tip}\nUse it.\n:::\nAfter.\n";
+    assert_eq!(
+        docgen::admonition::to_dialect(markdown, docgen::admonition::Dialect::Github),
+        "```\n::: {.note}\n```\n\n> [!TIP]\n> Use it.\n\nAfter.\n"
+    );
+}
@@ -0,0 +1,108 @@
+//! Type signatures from a companion file.
+//!
+//! Signatures produced by a type checker, or written by hand while doc
+//! comments are being given `# Type` sections, can be kept next to a file as
+//! `<name>.types.json` or `<name>.types.nix`, or given with `--types`:
+//!
+//! ```nix
+//! {
+//!   hasPrefix = "hasPrefix :: string -> string -> bool";
+//!   "lib.strings.splitString" = "string -> string -> [string]";
+//! }
+//! ```
+//!
+//! The JSON form is an object of the same shape. Keys are binding names as
+//! declared in the file or full entry titles. A Nix file is read without
+//! evaluating it, so its values must be string literals.
+
+use crate::commonmark::ManualEntry;
+use crate::signature::{self, Signature};
+use rnix::ast::{Attr, Expr, HasEntry};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The companion types file of `file`, if there is one.
+pub fn companion(file: &Path) -> Option<PathBuf> {
+    companion_paths(file)
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// The paths a companion file of `file` is looked for at, in order.
+pub fn companion_paths(file: &Path) -> Vec<PathBuf> {
+    let Some(stem) = file.file_stem() else {
+        return vec![];
+    };
+    let stem = stem.to_string_lossy();
+    ["json", "nix"]
+        .iter()
+        .map(|ext| file.with_file_name(format!("{}.types.{}", stem, ext)))
+        .collect()
+}
+
+/// The signatures of a Nix attribute set of string literals.
+fn parse_nix(src: &str) -> Result<HashMap<String, String>, String> {
+    let root = rnix::Root::parse(src)
+        .ok()
+        .map_err(|e| format!("parse error: {}", e))?;
+    let Some(Expr::AttrSet(set)) = root.expr() else {
+        return Err("expected an attribute set".to_string());
+    };
+    let mut types = HashMap::new();
+    for apv in set.attrpath_values() {
+        let (Some(path), Some(value)) = (apv.attrpath(), apv.value()) else {
+            continue;
+        };
+        let name: Option<Vec<String>> = path
+            .attrs()
+            .map(|attr| match attr {
+                Attr::Ident(ident) => Some(ident.to_string()),
+                Attr::Str(s) => crate::string_literal(&Expr::Str(s)),
+                Attr::Dynamic(_) => None,
+            })
+            .collect();
+        let name = name.ok_or_else(|| format!("dynamic attribute name `{}`", path))?;
+        let value = crate::string_literal(&value)
+            .ok_or_else(|| format!("the type of `{}` is not a string literal", path))?;
+        types.insert(name.join("."), value.trim().to_string());
+    }
+    Ok(types)
+}
+
+/// Read the signatures of a types file, as JSON or, for `.nix` files, Nix.
+pub fn read_types(path: &Path) -> Result<HashMap<String, String>, String> {
+    let src =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let types = if path.extension().is_some_and(|ext| ext == "nix") {
+        parse_nix(&src)
+    } else {
+        serde_json::from_str(&src).map_err(|e| e.to_string())
+    };
+    types.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Give the entries without a `# Type` section their signature from
+/// `types`, if it has one.
+pub fn apply_types(entries: &mut [ManualEntry], types: &HashMap<String, String>) {
+    for entry in entries {
+        if entry.fn_type.is_some() {
+            continue;
+        }
+        let (_, title) = entry.get_ident_title();
+        let Some(fn_type) = types.get(&title).or_else(|| types.get(&entry.name)) else {
+            continue;
+        };
+        let parsed = signature::parse_type(fn_type);
+        match &mut entry.signature {
+            Some(signature) => signature.fn_type = Some(parsed),
+            None => {
+                entry.signature = Some(Signature {
+                    params: vec![],
+                    fn_type: Some(parsed),
+                })
+            }
+        }
+        entry.fn_type = Some(fn_type.clone());
+    }
+}
@@ -0,0 +1,571 @@
+//! Typst output.
+//!
+//! The generated CommonMark is converted as a whole, so the document can be
+//! `#include`d in a Typst manual and compiled to PDF:
+//!
+//! - headings become `=` headings by level, followed by a `<anchor>` label if
+//!   they have an `{#id}`,
+//! - links to `#anchor` link to the label, other links to their URL,
+//! - code blocks become raw blocks, tables `#table` calls and definition
+//!   lists term lists,
+//! - lists and the blocks of their items are indented as Typst expects,
+//! - `example` blocks become figures of the kind `"example"`, captioned by
+//!   their heading and labelled with their id.
+//!
+//! Links to anchors need their label in the compiled document, so pages
+//! linking to each other are best included in the same manual. HTML blocks
+//! and inline HTML are dropped, keeping the text between the tags, and the
+//! lines opening and closing other `:::` blocks are removed.
+
+use crate::format::{DivFence, div_fence, get_fence, trim_leading_whitespace};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::fmt::Write as _;
+
+/// Escape text so it is not read as Typst markup.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | '#' | '*' | '_' | '`' | '$' | '<' | '>' | '@' | '[' | ']' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // `--` is a dash, `-?` a soft hyphen and `//`, `/*` comments.
+            '-' if chars.peek().is_some_and(|&n| n == '-' || n == '?') => escaped.push_str("\\-"),
+            '/' if chars.peek().is_some_and(|&n| n == '/' || n == '*') => escaped.push_str("\\/"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `text` as a Typst string literal.
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Whether `id` can be written as a `<label>`.
+fn is_label_name(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+/// The label `id`, attached to the element before it.
+fn label(id: &str) -> String {
+    if is_label_name(id) {
+        format!("<{}>", id)
+    } else {
+        format!("#label({})", string(id))
+    }
+}
+
+/// The label `id` as a value, for `#link`.
+fn label_value(id: &str) -> String {
+    if is_label_name(id) {
+        format!("<{}>", id)
+    } else {
+        format!("label({})", string(id))
+    }
+}
+
+/// Inline code as raw text.
+fn raw(code: &str) -> (String, bool) {
+    if code.is_empty() || code.contains('`') {
+        // Backticks cannot hold these; fall back to a call.
+        return (format!("#raw({})", string(code)), true);
+    }
+    (format!("`{}`", code), false)
+}
+
+/// Escape the start of a line that would be read as block markup: a heading,
+/// list item, term or numbered item.
+fn guard(line: &str) -> String {
+    if line.starts_with(['=', '-', '+', '/']) {
+        return format!("\\{}", line);
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && line[digits..].starts_with('.') {
+        return format!("{}\\{}", &line[..digits], &line[digits..]);
+    }
+    line.to_string()
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    has_header: bool,
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Indentation of the enclosing blocks.
+    indent: Vec<String>,
+    /// Marker replacing the innermost indentation on the next line written.
+    marker: Option<String>,
+    /// Inline content of the current block.
+    buf: String,
+    /// Set after an embedded call, which text could otherwise continue.
+    after_call: bool,
+    /// Set while writing the term of a term list, which ends at a colon.
+    term: bool,
+    /// Targets of the links being written, with where their text starts in
+    /// `buf`.
+    links: Vec<(String, usize)>,
+    lists: Vec<Option<u64>>,
+    table: Option<Table>,
+    code: Option<(String, String)>,
+    /// Level and id of the heading being written.
+    heading: Option<(usize, Option<String>)>,
+    /// For each open `:::` block that is a figure, its id.
+    blocks: Vec<Option<Option<String>>>,
+    /// The id of an example block opened, until its caption is known.
+    example: Option<Option<String>>,
+}
+
+impl Writer {
+    fn prefix(&mut self) -> String {
+        match self.marker.take() {
+            Some(marker) => {
+                let outer = self.indent.len().saturating_sub(1);
+                format!("{}{}", self.indent[..outer].concat(), marker)
+            }
+            None => self.indent.concat(),
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        let prefix = self.prefix();
+        if line.is_empty() {
+            self.out.push_str(prefix.trim_end());
+        } else {
+            self.out.push_str(&prefix);
+            self.out.push_str(line);
+        }
+        self.out.push('\n');
+    }
+
+    /// Write the marker still waiting for a line, before a nested list
+    /// brings its own.
+    fn pending_marker(&mut self) {
+        if self.marker.is_some() {
+            self.line("");
+        }
+    }
+
+    fn blank(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Write the pending inline content as a paragraph.
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.buf);
+        let text = text.trim();
+        self.after_call = false;
+        if text.is_empty() {
+            return;
+        }
+        for line in text.lines() {
+            self.line(&guard(line.trim()));
+        }
+        self.blank();
+    }
+
+    fn text(&mut self, text: &str) {
+        if std::mem::take(&mut self.after_call) {
+            let mut chars = text.chars();
+            let continues = match chars.next() {
+                Some('(') => true,
+                Some('.') => chars.next().is_some_and(|c| c.is_alphabetic() || c == '_'),
+                _ => false,
+            };
+            if continues {
+                // A semicolon ends the call.
+                self.buf.push(';');
+            }
+        }
+        let escaped = escape(text);
+        if self.term {
+            self.buf.push_str(&escaped.replace(':', "\\:"));
+        } else {
+            self.buf.push_str(&escaped);
+        }
+    }
+
+    /// Write an embedded call, ending with `call`.
+    fn call(&mut self, call: &str) {
+        self.buf.push_str(call);
+        self.after_call = true;
+    }
+
+    /// Open the content block of an embedded call.
+    fn open(&mut self, open: &str) {
+        self.after_call = false;
+        self.buf.push_str(open);
+    }
+
+    fn heading(&mut self, level: usize, id: Option<&str>) {
+        let title = std::mem::take(&mut self.buf);
+        let title = title.trim().replace('\n', " ");
+        self.after_call = false;
+        if self.example.is_some() {
+            // The heading of an example is its caption.
+            self.open_example(Some(&title));
+            return;
+        }
+        let mut line = "=".repeat(level.clamp(1, 6));
+        for part in [Some(title), id.map(label)].into_iter().flatten() {
+            if !part.is_empty() {
+                let _ = write!(line, " {}", part);
+            }
+        }
+        self.line(&line);
+        self.blank();
+    }
+
+    /// Open the figure of the pending example block.
+    fn open_example(&mut self, caption: Option<&str>) {
+        let Some(id) = self.example.take() else {
+            return;
+        };
+        let caption = caption
+            .filter(|caption| !caption.is_empty())
+            .map(|caption| format!(", caption: figure.caption(position: top)[{}]", caption))
+            .unwrap_or_default();
+        self.line(&format!(
+            "#figure(kind: \"example\", supplement: [Example]{})[",
+            caption
+        ));
+        self.indent.push("  ".to_string());
+        if let Some(block) = self.blocks.last_mut() {
+            *block = Some(id);
+        }
+    }
+
+    fn open_block(&mut self, classes: &[&str], id: Option<&str>) {
+        self.flush();
+        if classes.first() == Some(&"example") {
+            self.example = Some(id.map(str::to_string));
+        }
+        self.blocks.push(None);
+    }
+
+    fn close_block(&mut self) {
+        self.flush();
+        self.open_example(None);
+        if let Some(Some(id)) = self.blocks.pop() {
+            self.indent.pop();
+            while self.out.ends_with("\n\n") {
+                self.out.pop();
+            }
+            match id {
+                Some(id) => self.line(&format!("] {}", label(&id))),
+                None => self.line("]"),
+            }
+            self.blank();
+        }
+    }
+
+    /// Convert a chunk of CommonMark between `:::` lines.
+    fn convert(&mut self, markdown: &str) {
+        let options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_DEFINITION_LIST;
+        for event in Parser::new_ext(markdown, options) {
+            if self.example.is_some()
+                && self.heading.is_none()
+                && !matches!(event, Event::Start(Tag::Heading { .. }))
+            {
+                self.open_example(None);
+            }
+            match event {
+                Event::Start(Tag::Heading { level, ref id, .. }) => {
+                    self.heading = Some((level as usize, id.as_ref().map(|id| id.to_string())));
+                    self.event(event);
+                }
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, id)) = self.heading.take() {
+                        self.heading(level, id.as_deref());
+                    }
+                }
+                event => self.event(event),
+            }
+        }
+        self.flush();
+    }
+
+    fn table(&mut self, table: Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(1);
+        self.line("#table(");
+        self.indent.push("  ".to_string());
+        self.line(&format!("columns: {},", columns));
+        for (i, row) in table.rows.iter().enumerate() {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| format!("[{}]", cell.trim()))
+                .collect();
+            if i == 0 && table.has_header {
+                self.line(&format!("table.header({}),", cells.join(", ")));
+            } else {
+                self.line(&format!("{},", cells.join(", ")));
+            }
+        }
+        self.indent.pop();
+        self.line(")");
+        self.blank();
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some((_, code)) = &mut self.code {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let (lang, code) = self.code.take().unwrap_or_default();
+                    let code = code.trim_end_matches('\n');
+                    // The fence is longer than any run of backticks in the code.
+                    let longest = code
+                        .split(|c| c != '`')
+                        .map(str::len)
+                        .max()
+                        .unwrap_or_default();
+                    let fence = "`".repeat(longest.max(2) + 1);
+                    self.line(&format!("{}{}", fence, lang));
+                    for line in code.lines() {
+                        self.line(line);
+                    }
+                    self.line(&fence);
+                    self.blank();
+                }
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Paragraph) => self.flush(),
+            Event::End(TagEnd::Paragraph) => self.flush(),
+            Event::Start(Tag::Heading { .. }) => self.flush(),
+            Event::End(TagEnd::Heading(_)) => {}
+            Event::Start(Tag::CodeBlock(kind)) => {
+                self.flush();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((lang, String::new()));
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+                self.blank();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                self.pending_marker();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        let marker = format!("{}. ", n);
+                        *n += 1;
+                        marker
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.indent.push(" ".repeat(marker.len()));
+                self.marker = Some(marker);
+            }
+            Event::End(TagEnd::Item) => {
+                self.flush();
+                self.pending_marker();
+                self.indent.pop();
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush();
+                self.line("#quote(block: true)[");
+                self.indent.push("  ".to_string());
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush();
+                self.indent.pop();
+                while self.out.ends_with("\n\n") {
+                    self.out.pop();
+                }
+                self.line("]");
+                self.blank();
+            }
+            Event::Start(Tag::DefinitionList) => self.flush(),
+            Event::End(TagEnd::DefinitionList) => {
+                self.flush();
+                self.blank();
+            }
+            Event::Start(Tag::DefinitionListTitle) => {
+                self.flush();
+                self.pending_marker();
+                self.term = true;
+            }
+            Event::End(TagEnd::DefinitionListTitle) => {
+                let term = std::mem::take(&mut self.buf);
+                self.term = false;
+                self.after_call = false;
+                self.marker = Some(format!("/ {}: ", term.trim().replace('\n', " ")));
+            }
+            Event::Start(Tag::DefinitionListDefinition) => self.indent.push("  ".to_string()),
+            Event::End(TagEnd::DefinitionListDefinition) => {
+                self.flush();
+                self.pending_marker();
+                self.indent.pop();
+            }
+            Event::Start(Tag::Table(_)) => {
+                self.flush();
+                self.table = Some(Table::default());
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(table) = self.table.take() {
+                    self.table(table);
+                }
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some(table) = &mut self.table {
+                    table.has_header = true;
+                    table.rows.push(vec![]);
+                }
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(vec![]);
+                }
+            }
+            Event::End(TagEnd::TableCell) => {
+                let cell = std::mem::take(&mut self.buf);
+                self.after_call = false;
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(cell.replace('\n', " "));
+                }
+            }
+            Event::Start(Tag::Emphasis) => self.open("#emph["),
+            Event::Start(Tag::Strong) => self.open("#strong["),
+            Event::End(TagEnd::Emphasis) | Event::End(TagEnd::Strong) => self.call("]"),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.after_call = false;
+                self.links.push((dest_url.to_string(), self.buf.len()));
+            }
+            Event::End(TagEnd::Link) => {
+                let Some((url, start)) = self.links.pop() else {
+                    return;
+                };
+                let text = self.buf.split_off(start);
+                let text = text.trim();
+                let target = match url.strip_prefix('#') {
+                    Some(anchor) => label_value(anchor),
+                    None => string(&url),
+                };
+                let text = match (text.is_empty(), url.strip_prefix('#')) {
+                    (false, _) => text.to_string(),
+                    (true, Some(anchor)) => escape(anchor),
+                    (true, None) => escape(&url),
+                };
+                self.call(&format!("#link({})[{}]", target, text));
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.after_call = false;
+                self.links.push((dest_url.to_string(), self.buf.len()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, start)) = self.links.pop() {
+                    // Alt text is a string, not markup.
+                    let alt = self.buf.split_off(start);
+                    let alt = alt.replace('\\', "");
+                    self.call(&format!(
+                        "#image({}, alt: {})",
+                        string(&url),
+                        string(alt.trim())
+                    ));
+                }
+            }
+            Event::Code(code) => {
+                let (code, is_call) = raw(&code);
+                if is_call {
+                    self.call(&code);
+                } else {
+                    self.after_call = false;
+                    self.buf.push_str(&code);
+                }
+            }
+            Event::Text(text) => self.text(&text),
+            Event::SoftBreak => self.buf.push('\n'),
+            Event::HardBreak => self.buf.push_str(" \\\n"),
+            Event::Rule => {
+                self.flush();
+                self.line("#line(length: 100%)");
+                self.blank();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `text` as a block comment.
+pub fn comment(text: &str) -> String {
+    format!("/*\n{}*/\n", text.replace("*/", "* /").replace("/*", "/ *"))
+}
+
+/// Convert CommonMark, as generated by docgen, to Typst.
+pub fn markdown_to_typst(markdown: &str) -> String {
+    let mut writer = Writer::default();
+    let mut chunk = String::new();
+    let mut fence: Option<(usize, char)> = None;
+    for line in markdown.lines() {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(div) = div_fence(&fence_line) {
+                    writer.convert(&std::mem::take(&mut chunk));
+                    match div {
+                        DivFence::Open { classes, id } => writer.open_block(&classes, id),
+                        DivFence::Close => writer.close_block(),
+                    }
+                    continue;
+                }
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+            }
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    writer.convert(&chunk);
+    while !writer.blocks.is_empty() {
+        writer.close_block();
+    }
+    let mut out = writer.out;
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
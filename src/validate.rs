@@ -0,0 +1,107 @@
+//! Structural validation of generated CommonMark.
+//!
+//! The renderers assemble markdown from strings, so a bug (or an odd doc
+//! comment) can produce a document that looks fine in review but breaks links
+//! or swallows half the manual into a code block. Re-parsing the output with a
+//! real CommonMark parser catches that before it is published.
+
+use crate::format::{DivFence, div_fence};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+
+/// Re-parse `doc` and report structural problems, one message per problem.
+///
+/// Checks that:
+///
+/// - every anchor, of a heading (`{#id}`) or a `:::` block, is unique,
+/// - no heading contains an anchor attribute that failed to parse,
+/// - every fenced code block is closed before the end of its container.
+pub fn validate_commonmark(doc: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut anchors: HashMap<String, usize> = HashMap::new();
+    let mut heading: Option<(HeadingLevel, usize, String)> = None;
+    // The fence of the open code block, and where its content ends so far.
+    let mut fence: Option<(char, usize, usize)> = None;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(doc.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+    let mut add_anchor = |problems: &mut Vec<String>, id: &str, line: usize| {
+        if let Some(first) = anchors.get(id) {
+            problems.push(format!(
+                "line {}: duplicate anchor '{}' (first defined on line {})",
+                line, id, first
+            ));
+        } else {
+            anchors.insert(id.to_string(), line);
+        }
+    };
+
+    let parser = Parser::new_ext(doc, Options::ENABLE_HEADING_ATTRIBUTES).into_offset_iter();
+    for (event, range) in parser {
+        match event {
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                let line = line_of(range.start);
+                if let Some(id) = id {
+                    add_anchor(&mut problems, &id, line);
+                }
+                heading = Some((level, line, String::new()));
+            }
+            // `:::` lines are not CommonMark, and parse as paragraphs.
+            Event::Start(Tag::Paragraph) => {
+                let first = line_of(range.start);
+                for (i, line) in doc[range].lines().enumerate() {
+                    let line = line.trim_start_matches(['>', ' ', '\t']);
+                    if let Some(DivFence::Open { id: Some(id), .. }) = div_fence(line) {
+                        add_anchor(&mut problems, id, first + i);
+                    }
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, ref mut content)) = heading {
+                    content.push_str(&text);
+                }
+                if let Some((_, _, ref mut end)) = fence {
+                    *end = range.end;
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, line, content)) = heading.take()
+                    && content.contains("{#")
+                {
+                    problems.push(format!(
+                        "line {}: {} heading has an unparsed anchor attribute: {}",
+                        line, level, content
+                    ));
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                let open = doc[range.clone()].trim_start();
+                let fence_char = open.chars().next().unwrap_or('`');
+                let fence_len = open.len() - open.trim_start_matches(fence_char).len();
+                let content_start = doc[range.clone()]
+                    .find('\n')
+                    .map_or(range.end, |i| range.start + i + 1);
+                fence = Some((fence_char, fence_len, content_start));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                // The closing fence is the rest of the block after its content,
+                // behind the markers of the block quotes or list items around it.
+                if let Some((fence_char, fence_len, end)) = fence.take()
+                    && !doc[end.min(range.end)..range.end].lines().any(|line| {
+                        let close = line.trim_start_matches(['>', ' ', '\t']).trim_end();
+                        close.len() >= fence_len && close.chars().all(|c| c == fence_char)
+                    })
+                {
+                    problems.push(format!(
+                        "line {}: code fence is never closed",
+                        line_of(range.start)
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    problems
+}
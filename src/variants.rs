@@ -0,0 +1,166 @@
+//! Comparison tables of related functions.
+//!
+//! A line of a doc comment naming a family of similar functions
+//!
+//! ```markdown
+//! @variants foldl foldl' foldr
+//! ```
+//!
+//! is replaced by a table with a row per function: a link to its entry, its
+//! type from the `# Type` section and a note on its strictness. The note is
+//! the entry's `strictness` metadata, or else the first sentence of its
+//! description that mentions being strict or lazy. Names are the bindings as
+//! declared in the file or the entries' full titles; names that are not
+//! documented are reported and left out of the table.
+
+use crate::commonmark::ManualEntry;
+use crate::format::{get_fence, trim_leading_whitespace};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+/// The directive starting a line.
+const DIRECTIVE: &str = "@variants";
+
+/// A table cell: on one line, with pipes escaped.
+fn cell(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// The type of an entry without the name before `::`.
+fn entry_type(entry: &ManualEntry) -> Option<String> {
+    if let Some(ty) = entry
+        .signature
+        .as_ref()
+        .and_then(|signature| signature.fn_type.as_ref())
+    {
+        let mut parts = ty.args.clone();
+        parts.push(ty.returns.clone());
+        return Some(parts.join(" -> "));
+    }
+    entry.fn_type.clone()
+}
+
+/// The note on the strictness of an entry, if it has one.
+fn strictness(entry: &ManualEntry) -> Option<String> {
+    if let Some((_, note)) = entry.meta.iter().find(|(key, _)| key == "strictness") {
+        return Some(note.clone());
+    }
+    entry
+        .description
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.starts_with('#') && !p.starts_with("```") && !p.starts_with(DIRECTIVE))
+        .flat_map(|p| p.split_inclusive(". "))
+        .find(|sentence| {
+            let sentence = sentence.to_lowercase();
+            sentence.contains("strict") || sentence.contains("lazy") || sentence.contains("lazily")
+        })
+        .map(|sentence| sentence.trim().to_string())
+}
+
+/// The comparison table of the entries named on a directive line.
+fn variants_table(
+    names: &str,
+    entries: &[ManualEntry],
+    anchor_prefix: &str,
+    warnings: &mut Vec<String>,
+) -> String {
+    let mut table = String::from("| Function | Type | Strictness |\n| --- | --- | --- |\n");
+    for name in names.split_whitespace() {
+        let Some(entry) = entries
+            .iter()
+            .find(|entry| entry.name == name || entry.get_ident_title().1 == name)
+        else {
+            warnings.push(format!(
+                "{} names no documented entry '{}'",
+                DIRECTIVE, name
+            ));
+            continue;
+        };
+        let (_, title) = entry.get_ident_title();
+        let _ = writeln!(
+            table,
+            "| [`{}`](#{}) | {} | {} |",
+            title,
+            entry.anchor(anchor_prefix),
+            entry_type(entry).map_or(String::new(), |ty| format!("`{}`", cell(&ty))),
+            strictness(entry).map_or(String::new(), |note| cell(&note)),
+        );
+    }
+    table
+}
+
+/// Replace the `@variants` lines of `text`, outside code blocks, with their
+/// tables. Names not found among `entries` are added to `warnings`.
+pub fn expand_variants<'a>(
+    text: &'a str,
+    entries: &[ManualEntry],
+    anchor_prefix: &str,
+    warnings: &mut Vec<String>,
+) -> Cow<'a, str> {
+    if !text.contains(DIRECTIVE) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut fence: Option<(usize, char)> = None;
+    for line in text.split_inclusive('\n') {
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+            }
+            None => {
+                fence = get_fence(&fence_line, true).filter(|(count, _)| *count >= 3);
+                if fence.is_none()
+                    && let Some(names) = line.trim().strip_prefix(DIRECTIVE)
+                    && (names.is_empty() || names.starts_with(char::is_whitespace))
+                {
+                    let table = variants_table(names, entries, anchor_prefix, warnings);
+                    out.push_str(if line.ends_with('\n') {
+                        &table
+                    } else {
+                        table.trim_end()
+                    });
+                    continue;
+                }
+            }
+        }
+        out.push_str(line);
+    }
+    Cow::Owned(out)
+}
+
+/// Expand the `@variants` lines of the descriptions of `entries` and of the
+/// file description, returning the warnings.
+pub fn expand_all(
+    description: &mut String,
+    entries: &mut [ManualEntry],
+    anchor_prefix: &str,
+) -> Vec<String> {
+    let mut warnings = vec![];
+    if let Cow::Owned(expanded) =
+        expand_variants(description, entries, anchor_prefix, &mut warnings)
+    {
+        *description = expanded;
+    }
+    // Expand against the entries as written, so no table is read for notes.
+    let mut expanded = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        for (j, paragraph) in entry.description.iter().enumerate() {
+            if let Cow::Owned(text) =
+                expand_variants(paragraph, entries, anchor_prefix, &mut warnings)
+            {
+                expanded.push((i, j, text));
+            }
+        }
+    }
+    for (i, j, text) in expanded {
+        entries[i].description[j] = text;
+    }
+    warnings
+}
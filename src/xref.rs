@@ -0,0 +1,286 @@
+//! Links between options that mention each other in their descriptions.
+//!
+//! Two kinds of mention are recognised outside code blocks and existing
+//! links:
+//!
+//! - the `` {option}`services.foo.enable` `` role, which is always linked,
+//! - a known option name, bare or as inline code, which is linked when the
+//!   names of all options are known (not when streaming).
+//!
+//! The mentions also give each option the list of options referring to it.
+//! [`link_references`] links mentions by rules of its caller, as the
+//! `` {ref}`lib.strings.foo` `` references between functions are (see
+//! [`crate::see`]).
+
+use crate::format::{get_fence, trim_leading_whitespace};
+use crate::options::make_anchor_id;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+/// Role marking an option name in nixpkgs markdown.
+const ROLE: &str = "{option}";
+
+/// Option names and the options mentioning each of them.
+#[derive(Debug, Default)]
+pub struct CrossRefs<'a> {
+    pub known: HashSet<&'a str>,
+    /// Mentioning options by mentioned option, in the order given to [`CrossRefs::new`].
+    pub referenced_by: HashMap<String, Vec<&'a str>>,
+}
+
+impl<'a> CrossRefs<'a> {
+    /// Index options given as names and descriptions.
+    pub fn new(options: &[(&'a str, Option<&'a str>)], anchor_prefix: &str) -> Self {
+        let known: HashSet<&str> = options.iter().map(|(name, _)| *name).collect();
+        let mut referenced_by: HashMap<String, Vec<&str>> = HashMap::new();
+        for (name, description) in options {
+            let Some(description) = description else {
+                continue;
+            };
+            for target in link_mentions(description, name, Some(&known), anchor_prefix).1 {
+                referenced_by.entry(target).or_default().push(name);
+            }
+        }
+        CrossRefs {
+            known,
+            referenced_by,
+        }
+    }
+}
+
+/// Link the options mentioned in the description of `current`. Bare names
+/// are only linked if they are in `known`. Returns the text and the
+/// mentioned options other than `current`, without duplicates.
+pub fn link_mentions<'t>(
+    text: &'t str,
+    current: &str,
+    known: Option<&HashSet<&str>>,
+    anchor_prefix: &str,
+) -> (Cow<'t, str>, Vec<String>) {
+    if known.is_none() && !text.contains(ROLE) {
+        return (Cow::Borrowed(text), vec![]);
+    }
+    let url = |name: &str, role: bool| {
+        (role || known.is_some_and(|known| known.contains(name)))
+            .then(|| format!("#{}", make_anchor_id(name, anchor_prefix)))
+    };
+    let linked = link_references(
+        text,
+        &Mentions {
+            role: ROLE,
+            bare: true,
+            current,
+            url: &url,
+        },
+    );
+    (linked.text, linked.mentions)
+}
+
+/// How the mentions of a text are linked.
+pub struct Mentions<'a> {
+    /// Role marking a name that is always linked, such as `{option}`.
+    pub role: &'a str,
+    /// Whether names outside inline code are linked too.
+    pub bare: bool,
+    /// The name the text documents, only linked when written with the role.
+    pub current: &'a str,
+    /// Link of a name, given whether it is written with the role, or `None`
+    /// to leave it as it is.
+    pub url: &'a dyn Fn(&str, bool) -> Option<String>,
+}
+
+/// A text with its mentions linked.
+pub struct Linked<'t> {
+    pub text: Cow<'t, str>,
+    /// The names linked, other than the current one, without duplicates.
+    pub mentions: Vec<String>,
+    /// Names written with the role that have no link, left as inline code.
+    pub unresolved: Vec<String>,
+}
+
+/// Link the names `text` mentions, outside code blocks and existing links,
+/// by `rules`: names written with the role, and names as inline code or
+/// bare words that `rules.url` knows.
+pub fn link_references<'t>(text: &'t str, rules: &Mentions) -> Linked<'t> {
+    let mut linker = Linker {
+        rules,
+        output: String::with_capacity(text.len()),
+        mentions: vec![],
+        unresolved: vec![],
+    };
+    let mut fence: Option<(usize, char)> = None;
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            linker.output.push('\n');
+        }
+        let fence_line = trim_leading_whitespace(line, 3);
+        match fence {
+            Some((count, ch)) => {
+                if get_fence(&fence_line, false).is_some_and(|(n, c)| c == ch && n >= count) {
+                    fence = None;
+                }
+                linker.output.push_str(line);
+            }
+            None => match get_fence(&fence_line, true).filter(|(count, _)| *count >= 3) {
+                Some(opening) => {
+                    fence = Some(opening);
+                    linker.output.push_str(line);
+                }
+                None => linker.line(line),
+            },
+        }
+    }
+    let Linker {
+        output,
+        mentions,
+        unresolved,
+        ..
+    } = linker;
+    Linked {
+        text: if output == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(output)
+        },
+        mentions,
+        unresolved,
+    }
+}
+
+struct Linker<'k> {
+    rules: &'k Mentions<'k>,
+    output: String,
+    mentions: Vec<String>,
+    unresolved: Vec<String>,
+}
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'-')
+}
+
+impl Linker<'_> {
+    /// Link of a name mentioned without the role.
+    fn known_url(&self, name: &str) -> Option<String> {
+        (name != self.rules.current)
+            .then(|| (self.rules.url)(name, false))
+            .flatten()
+    }
+
+    fn link(&mut self, name: &str, url: &str) {
+        let _ = write!(self.output, "[`{}`]({})", name, url);
+        if name != self.rules.current && !self.mentions.iter().any(|m| m == name) {
+            self.mentions.push(name.to_string());
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'`' => {
+                    let run = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+                    let start = i + run;
+                    let Some(end) = closing_backticks(line, start, run) else {
+                        self.output.push_str(&line[i..start]);
+                        i = start;
+                        continue;
+                    };
+                    let name = line[start..end].trim();
+                    let role = self.rules.role;
+                    if self.output.ends_with(role) {
+                        self.output.truncate(self.output.len() - role.len());
+                        match (self.rules.url)(name, true) {
+                            Some(url) => self.link(name, &url),
+                            None => {
+                                self.output.push_str(&line[i..end + run]);
+                                self.unresolved.push(name.to_string());
+                            }
+                        }
+                    } else if let Some(url) =
+                        self.known_url(name).filter(|_| !self.output.ends_with('['))
+                    {
+                        self.link(name, &url);
+                    } else {
+                        self.output.push_str(&line[i..end + run]);
+                    }
+                    i = end + run;
+                }
+                // Existing links, autolinks and HTML are kept as they are.
+                b'[' | b'<' => {
+                    let end = match bytes[i] {
+                        b'[' => closing(line, i, b'[', b']').map(|end| {
+                            if bytes.get(end + 1) == Some(&b'(') {
+                                closing(line, end + 1, b'(', b')').unwrap_or(end)
+                            } else {
+                                end
+                            }
+                        }),
+                        _ => line[i..].find('>').map(|n| i + n),
+                    };
+                    let end = end.map_or(i + 1, |end| end + 1);
+                    self.output.push_str(&line[i..end]);
+                    i = end;
+                }
+                c if is_name_char(c) && (i == 0 || !is_name_char(bytes[i - 1])) => {
+                    let end = i + bytes[i..].iter().take_while(|&&b| is_name_char(b)).count();
+                    let word = &line[i..end];
+                    let name = word.trim_end_matches(['.', '-']);
+                    // Skip parts of URLs, paths and addresses.
+                    let in_url = (i > 0 && matches!(bytes[i - 1], b'/' | b':' | b'@' | b'\\'))
+                        || matches!(bytes.get(end), Some(b'/' | b':' | b'@'));
+                    let url = (self.rules.bare && name.contains('.') && !in_url)
+                        .then(|| self.known_url(name))
+                        .flatten();
+                    if let Some(url) = url {
+                        self.link(name, &url);
+                        self.output.push_str(&word[name.len()..]);
+                    } else {
+                        self.output.push_str(word);
+                    }
+                    i = end;
+                }
+                _ => {
+                    let c = line[i..].chars().next().expect("index is a char boundary");
+                    self.output.push(c);
+                    i += c.len_utf8();
+                }
+            }
+        }
+    }
+}
+
+/// Start of the run of exactly `run` backticks closing a code span.
+fn closing_backticks(line: &str, from: usize, run: usize) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] == b'`' {
+            let len = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+            if len == run {
+                return Some(i);
+            }
+            i += len;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Index of the bracket closing the one at `open`, allowing nesting.
+fn closing(line: &str, open: usize, left: u8, right: u8) -> Option<usize> {
+    let mut depth = 0;
+    for (i, &b) in line.as_bytes().iter().enumerate().skip(open) {
+        if b == left {
+            depth += 1;
+        } else if b == right {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,29 @@
+/**
+  Functions with admonitions.
+*/
+{ lib }:
+{
+  /**
+    Join strings with a separator.
+
+    ::: {.note}
+    The separator is not added after the last string.
+    :::
+
+    ::: {.warning #join-escaping}
+    The strings are not escaped:
+
+    ```nix
+    join "," [ "a,b" ]
+    ```
+
+    :::
+
+    # Example
+
+    ```nix
+    join ", " [ "a" "b" ]
+    ```
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+}
@@ -0,0 +1,32 @@
+/**
+  Functions with pinned anchors.
+*/
+{ lib }:
+{
+  /**
+    Join strings with a separator.
+
+    @anchor strings-join
+
+    # Example
+
+    ```nix
+    join ", " [ "a" "b" ]
+    ```
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+
+  /**
+    {#strings-split}
+
+    Split a string at a separator.
+  */
+  split = sep: s: lib.splitString sep s;
+
+  /**
+    Repeat a string.
+
+    @anchor not an id
+  */
+  repeat = n: s: lib.concatStrings (lib.replicate n s);
+}
@@ -0,0 +1,26 @@
+/**
+  Arithmetic helpers, exported by name.
+*/
+{ lib }:
+let
+  /**
+    Add one to a number.
+  */
+  inc = x: x + 1;
+
+  /**
+    Subtract one from a number.
+  */
+  dec = x: x - 1;
+
+  twice = f: x: f (f x);
+
+  fns = {
+    inherit inc dec twice;
+  };
+in
+lib.genAttrs [
+  "inc"
+  "dec"
+  "twice"
+] (name: fns.${name})
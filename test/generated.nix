@@ -0,0 +1,27 @@
+{ lib }:
+{
+  toFormat = lib.genAttrs [ "json" "yaml" ] (
+    /**
+      Serialise a value as ${format}.
+
+      # Arguments
+
+      value
+      : The value to convert to ${format}
+    */
+    format: value: lib.generators.${format} { } value
+  );
+
+  /**
+    Check whether a port is the default port for ${service} (${port}).
+  */
+  isDefaultPort = builtins.mapAttrs (service: port: p: p == port) {
+    http = 80;
+    "https" = 443;
+  };
+
+  /**
+    Not generated: the names are computed.
+  */
+  computed = lib.genAttrs (lib.attrNames { a = 1; }) (name: x: x);
+}
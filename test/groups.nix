@@ -0,0 +1,39 @@
+/**
+  String manipulation functions.
+
+  ## String predicates {#sec-functions-library-groups-predicates}
+
+  Functions testing a string.
+
+  - `hasPrefix`
+  - `lib.groups.hasSuffix`: checks the
+    other end
+
+  ## Notes
+
+  - `notABinding`
+*/
+{ lib }:
+{
+  /**
+    Concatenate a list of strings.
+  */
+  concatStrings = builtins.concatStringsSep "";
+
+  /**
+    Determine whether a string has given suffix.
+
+    # Example
+
+    ```nix
+    hasSuffix "bar" "foobar"
+    => true
+    ```
+  */
+  hasSuffix = suffix: str: lib.hasSuffix suffix str;
+
+  /**
+    Determine whether a string has given prefix.
+  */
+  hasPrefix = pref: str: lib.hasPrefix pref str;
+}
@@ -0,0 +1,52 @@
+/**
+  Aliases sharing the docs of the functions they wrap.
+*/
+{ lib }:
+let
+  /**
+    Join strings with a separator.
+
+    # Type
+
+    ```
+    joinWith :: String -> [String] -> String
+    ```
+
+    # Example
+
+    ```nix
+    joinWith ", " [ "a" "b" ]
+    => "a, b"
+    ```
+
+    @param sep The separator.
+    @return The joined string.
+    @internal
+  */
+  joinWith = sep: list: lib.concatStringsSep sep list;
+in
+{
+  inherit joinWith;
+
+  /**
+    @inheritDoc joinWith
+  */
+  join = sep: strings: joinWith sep strings;
+
+  /**
+    Kept for compatibility.
+
+    @inheritDoc lib.inherit.join
+  */
+  concat = join;
+
+  /**
+    @inheritDoc lib.strings.concatStrings
+  */
+  concatAll = lib.concatStrings;
+
+  /**
+    @inheritDoc lib.missing
+  */
+  missing = x: x;
+}
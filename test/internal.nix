@@ -0,0 +1,20 @@
+{
+  /**
+    Join strings with a separator.
+  */
+  join = sep: list: 1;
+
+  /**
+    Split a string, used by `join`.
+
+    @internal
+  */
+  splitHelper = s: s;
+
+  /**
+    Escape a string.
+
+    @private
+  */
+  escapeHelper = s: s;
+}
@@ -0,0 +1,49 @@
+{ lib }:
+{
+  /**
+    Separator of path components.
+  */
+  separator = "/";
+
+  /**
+    Concatenate a list of strings.
+
+    # Type
+
+    ```
+    concatStrings :: [string] -> string
+    ```
+  */
+  concatStrings = lib.concatStringsSep "";
+
+  /**
+    Join strings with a separator.
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+
+  /**
+    Make a derivation writing a file.
+  */
+  writeFile = { name, text }: builtins.toFile name text;
+
+  /**
+    Make a package set.
+
+    # Type
+
+    ```
+    makeScope :: AttrSet -> (AttrSet -> AttrSet) -> AttrSet
+    ```
+  */
+  makeScope = lib.makeScope;
+
+  /**
+    Identity.
+  */
+  id = x: x;
+
+  /**
+    A function built elsewhere.
+  */
+  generated = lib.generate "x";
+}
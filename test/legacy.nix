@@ -0,0 +1,37 @@
+{ lib }:
+let
+  /* Return a string unchanged.
+
+     Type: identity :: string -> string
+  */
+  identity = s: s;
+in
+{
+  /* Concatenate a list of strings.
+
+     Type: concatStrings :: [string] -> string
+
+     Example:
+       concatStrings ["foo" "bar"]
+       => "foobar"
+  */
+  concatStrings = builtins.concatStringsSep "";
+
+  /* Map a function over a list and concatenate the resulting strings.
+
+     Type:
+       concatMapStrings :: (a -> string) -> [a] -> string
+  */
+  concatMapStrings = f: list: concatStrings (map f list);
+
+  /**
+    Documented with an RFC 145 comment, which takes precedence.
+  */
+  /* Ignored. */
+  hasPrefix = pref: str: lib.substring 0 (lib.stringLength pref) str == pref;
+
+  # A line comment documents nothing.
+  id = x: x;
+
+  inherit identity;
+}
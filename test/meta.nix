@@ -0,0 +1,53 @@
+{ lib }:
+let
+  /**
+    Defined in the let block.
+  */
+  inner = x: x;
+
+  innerMeta = {
+    since = "23.11";
+  };
+in
+{
+  inherit inner;
+
+  /**
+    Repeat a string.
+  */
+  repeat = n: s: lib.concatStrings (lib.replicate n s);
+
+  repeatMeta = {
+    maintainers = [
+      "alice"
+      lib.maintainers.bob
+    ];
+    since = "24.05";
+  };
+
+  /**
+    Join strings.
+  */
+  join = sep: lib.concatStringsSep sep;
+
+  __docsMeta = {
+    join = {
+      since = "22.05";
+      platforms = lib.platforms.all;
+    };
+  };
+
+  /**
+    Split a string.
+  */
+  split = sep: lib.splitString sep;
+
+  __docsMeta.split = {
+    since = "21.11";
+  };
+
+  /**
+    Split a string into its metadata and content, despite its name.
+  */
+  splitMeta = s: lib.splitString "\n\n" s;
+}
@@ -0,0 +1,57 @@
+{ config, lib, ... }:
+let
+  cfg = config.services.web;
+  # Not an option of the module.
+  unused = lib.mkOption { type = lib.types.str; };
+in
+{
+  options.services.web = {
+    enable = lib.mkEnableOption "the web server";
+
+    port = lib.mkOption {
+      type = lib.types.port;
+      default = 8080;
+      description = "Port to listen on.";
+    };
+
+    hosts = lib.mkOption {
+      type = with lib.types; listOf str;
+      default = [ "localhost" ];
+      example = lib.literalExpression ''[ "example.org" ]'';
+      description = lib.mdDoc ''
+        Host names to answer to.
+      '';
+    };
+
+    stateDir = lib.mkOption {
+      type = lib.types.path;
+      default = "/var/lib/${cfg.user}";
+      defaultText = lib.literalExpression ''"/var/lib/''${config.services.web.user}"'';
+      description = "Directory holding the state.";
+    };
+
+    "tls-certificate" = lib.mkOption {
+      type = lib.types.nullOr lib.types.path;
+      default = null;
+      readOnly = true;
+      description = "Certificate file.";
+    };
+
+    vhosts = lib.mkOption {
+      type = lib.types.attrsOf (lib.types.submodule {
+        options.root = lib.mkOption { type = lib.types.path; };
+      });
+      default = { };
+      description = "Virtual hosts.";
+    };
+
+    package = lib.mkOption {
+      internal = true;
+      type = lib.types.package;
+    };
+  };
+
+  config = lib.mkIf cfg.enable {
+    networking.firewall.allowedTCPPorts = [ cfg.port ];
+  };
+}
@@ -0,0 +1,10 @@
+{
+  /**
+    Add two numbers.
+  */
+  add = a: b: a + b;
+
+  inherit (import ./reexport/versions.nix { }) majorMinor splitVersion;
+
+  inherit (import ./reexport/missing.nix) undocumented;
+}
@@ -0,0 +1,24 @@
+{ }:
+{
+  /**
+    Split a version string into its components.
+  */
+  splitVersion = builtins.splitVersion;
+
+  /**
+    Major and minor version of a version string.
+
+    # Example
+
+    ```nix
+    majorMinor "24.05.1"
+    => "24.05"
+    ```
+  */
+  majorMinor = v: builtins.concatStringsSep "." (builtins.genList (i: builtins.elemAt (builtins.splitVersion v) i) 2);
+
+  /**
+    Not re-exported.
+  */
+  patch = v: builtins.elemAt (builtins.splitVersion v) 2;
+}
@@ -0,0 +1,24 @@
+/**
+  Functions referring to others.
+*/
+{ lib }:
+{
+  /**
+    Join strings with a separator.
+
+    @see lib.strings.concatStrings, `lib.strings.hasPrefix`
+    @see split
+    @see lib.missing
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+
+  /**
+    Split a string at a separator, the inverse of {ref}`join`, unlike
+    `lib.strings.concatStrings`, {ref}`lib.nope` or `split`.
+
+    ```nix
+    lib.strings.hasPrefix
+    ```
+  */
+  split = sep: s: lib.splitString sep s;
+}
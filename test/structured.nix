@@ -0,0 +1,59 @@
+{ lib }:
+{
+  /**
+    Pad a string on the left to the given width.
+
+    # Inputs
+
+    `width`
+
+    : Width of the result
+
+    `str`
+
+    : String to pad
+
+    # Type
+
+    ```
+    padLeft :: Int -> String -> String
+    ```
+
+    # Examples
+    :::{.example}
+    ## `lib.structured.padLeft` usage example
+
+    Shorter strings are padded with spaces:
+
+    ```nix
+    padLeft 5 "ab"
+    => "   ab"
+    ```
+
+    :::
+
+    # Notes
+
+    Longer strings are left as they are.
+  */
+  padLeft = width: str: lib.fixedWidthString width " " str;
+
+  /**
+    Make a derivation writing a file.
+
+    # Inputs
+
+    - `name`: Name of the derivation
+    - `environment`: Not a parameter
+  */
+  writeFile =
+    {
+      name,
+      /**
+        Text of the file
+      */
+      text ? "",
+      ...
+    }@args:
+    builtins.toFile name text;
+}
@@ -0,0 +1,32 @@
+/**
+  String functions, tagged by topic.
+*/
+{ lib }:
+{
+  /**
+    Join strings with a separator.
+
+    @tag string-manipulation, lists
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+
+  /**
+    Whether a string starts with a prefix.
+
+    @tag predicates
+  */
+  hasPrefix = pre: s: lib.hasPrefix pre s;
+
+  /**
+    Split a string at a separator.
+
+    @tag lists
+    @tag string-manipulation
+  */
+  split = sep: s: lib.splitString sep s;
+
+  /**
+    The empty string.
+  */
+  empty = "";
+}
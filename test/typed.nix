@@ -0,0 +1,26 @@
+/**
+  Functions with signatures kept in typed.types.nix.
+*/
+{ lib }:
+{
+  /**
+    Determine whether a string has given prefix.
+  */
+  hasPrefix = pref: str: lib.hasPrefix pref str;
+
+  /**
+    Split a string at a separator.
+
+    # Type
+
+    ```
+    splitString :: string -> string -> [string]
+    ```
+  */
+  splitString = sep: s: lib.splitString sep s;
+
+  /**
+    Concatenate a list of strings.
+  */
+  concatStrings = builtins.concatStringsSep "";
+}
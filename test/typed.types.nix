@@ -0,0 +1,7 @@
+{
+  hasPrefix = "hasPrefix :: string -> string -> bool";
+  splitString = "not used, the doc comment has a type";
+  "lib.typed.concatStrings" = ''
+    [string] -> string
+  '';
+}
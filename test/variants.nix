@@ -0,0 +1,41 @@
+/**
+  Folds over lists.
+
+  @variants foldl foldl' foldr
+*/
+{ lib }:
+{
+  /**
+    Fold a list from the left.
+
+    The accumulator is built lazily, which can exhaust the stack on long
+    lists.
+
+    # Type
+
+    ```
+    foldl :: (b -> a -> b) -> b -> [a] -> b
+    ```
+  */
+  foldl = op: nul: list: builtins.foldl' op nul list;
+
+  /**
+    Fold a list from the left. Strict in the accumulator, which is
+    forced at each step.
+
+    # Type
+
+    ```
+    foldl' :: (b -> a -> b) -> b -> [a] -> b
+    ```
+
+    @variants foldl lib.variants.foldr unknownFold
+  */
+  foldl' = builtins.foldl';
+
+  /**
+    Fold a list from the right.
+  */
+  foldr = op: nul: list: lib.foldr op nul list;
+  foldrMeta = { strictness = "Lazy in the rest of the list."; };
+}
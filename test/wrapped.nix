@@ -0,0 +1,34 @@
+{ lib }:
+let
+  /**
+    Flip the order of the arguments of a function.
+  */
+  flip = f: a: b: f b a;
+
+  /**
+    Split a string at a separator.
+
+    # Type
+
+    ```
+    splitAt :: String -> String -> [String]
+    ```
+  */
+  splitAt = sep: str: lib.splitString sep str;
+in
+{
+  /**
+    Join a list of strings with a separator.
+  */
+  join = sep: list: lib.concatStringsSep sep list;
+
+  joinLines = join "\n";
+
+  inherit splitAt;
+
+  splitOn = flip splitAt;
+
+  commas = list: join ", " list;
+
+  unrelated = x: x + 1;
+}